@@ -0,0 +1,130 @@
+//! Accesses of the keys for OBJECT IDLETIME and FREQ.
+//!
+//! The accesses are counted in memory by each member for the keys of the
+//! commands it serves, like the LFU counters of Redis: a logarithmic counter
+//! of 8 bits decayed by one per minute without access. Keeping them out of
+//! the meta values leaves the reads read-only in TiKV. At most
+//! `object_access_max_keys` keys are tracked, an arbitrary one is dropped to
+//! make room for a new one.
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+use bytes::Bytes;
+use tokio::time::Instant;
+
+use crate::Frame;
+use crate::{config_object_access_max_keys_or_default, config_object_access_tracking_or_default};
+
+/// Counter of a key accessed for the first time, so it is not dropped at its
+/// first decay
+const LFU_INIT_VAL: u8 = 5;
+/// The larger, the more accesses it takes to increment a high counter
+const LFU_LOG_FACTOR: f64 = 10.0;
+/// Seconds without access to decrement a counter
+const LFU_DECAY_SECS: u64 = 60;
+
+struct Access {
+    last_access: Instant,
+    counter: u8,
+}
+
+impl Access {
+    /// The counter decayed for the time since the last access
+    fn decayed_counter(&self) -> u8 {
+        let periods = self.last_access.elapsed().as_secs() / LFU_DECAY_SECS;
+        self.counter
+            .saturating_sub(periods.min(u8::MAX as u64) as u8)
+    }
+}
+
+lazy_static! {
+    static ref ACCESSES: Mutex<HashMap<Bytes, Access>> = Mutex::new(HashMap::new());
+    /// Untracked keys are idle since the tracking started
+    static ref TRACKING_START: Instant = Instant::now();
+}
+
+/// Increment the counter with a probability decreasing as it grows, so it
+/// reaches 255 after about a million accesses
+fn log_incr(counter: u8) -> u8 {
+    if counter == u8::MAX {
+        return counter;
+    }
+    let baseval = counter.saturating_sub(LFU_INIT_VAL) as f64;
+    let p = 1.0 / (baseval * LFU_LOG_FACTOR + 1.0);
+    if rand::random::<f64>() < p {
+        counter + 1
+    } else {
+        counter
+    }
+}
+
+/// The key of a command frame, its first argument
+pub fn accessed_key(frame: &Frame) -> Option<Bytes> {
+    if !config_object_access_tracking_or_default() {
+        return None;
+    }
+    match frame {
+        Frame::Array(parts) => match parts.get(1) {
+            Some(Frame::Bulk(key)) => Some(key.clone()),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+/// Count an access of the key
+pub fn track_access(key: Bytes) {
+    let now = Instant::now();
+    lazy_static::initialize(&TRACKING_START);
+    let mut accesses = ACCESSES.lock().unwrap();
+    if let Some(access) = accesses.get_mut(&key) {
+        access.counter = log_incr(access.decayed_counter());
+        access.last_access = now;
+        return;
+    }
+    if accesses.len() >= config_object_access_max_keys_or_default() {
+        let dropped = accesses.keys().next().cloned();
+        if let Some(dropped) = dropped {
+            accesses.remove(&dropped);
+        }
+    }
+    accesses.insert(
+        key,
+        Access {
+            last_access: now,
+            counter: LFU_INIT_VAL,
+        },
+    );
+}
+
+/// Seconds since the last access of the key, None if accesses are not
+/// tracked
+pub fn idle_time(key: &[u8]) -> Option<u64> {
+    if !config_object_access_tracking_or_default() {
+        return None;
+    }
+    let idle = match ACCESSES.lock().unwrap().get(key) {
+        Some(access) => access.last_access.elapsed(),
+        None => TRACKING_START.elapsed(),
+    };
+    Some(idle.as_secs())
+}
+
+/// Number of keys whose accesses are tracked
+pub fn tracked_keys() -> usize {
+    ACCESSES.lock().unwrap().len()
+}
+
+/// The access counter of the key, None if accesses are not tracked
+pub fn access_frequency(key: &[u8]) -> Option<u8> {
+    if !config_object_access_tracking_or_default() {
+        return None;
+    }
+    let counter = ACCESSES
+        .lock()
+        .unwrap()
+        .get(key)
+        .map_or(0, |access| access.decayed_counter());
+    Some(counter)
+}
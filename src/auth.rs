@@ -0,0 +1,208 @@
+use std::collections::HashMap;
+use std::sync::{Mutex, RwLock};
+use std::time::{Duration, Instant};
+
+use futures::FutureExt;
+use hyper::{Body, Client, Method, Request};
+use slog::{error, info, warn};
+
+use crate::config::{is_use_txn_api, LOGGER};
+use crate::metrics::AUTH_REQUEST_COUNTER;
+use crate::tikv::backend::Transaction;
+use crate::tikv::encoding::KeyDecoder;
+use crate::tikv::errors::AsyncResult;
+use crate::tikv::{get_txn_client, KEY_ENCODER};
+use crate::utils::{json_quote, now_timestamp_in_micros, sha1hex};
+use crate::{
+    config_auth_backend_or_default, config_auth_cache_ttl_or_default,
+    config_auth_webhook_timeout_or_default, config_auth_webhook_url_or_default, is_auth_matched,
+};
+
+/// Revocations are kept this long in the backend for all the members to load
+/// them, in microseconds
+const REVOCATION_RETENTION: u64 = 24 * 3600 * 1_000_000;
+
+lazy_static! {
+    /// Successful external authentications with their user, keyed by the sha1
+    /// of the credentials so the plain password is never kept in memory longer
+    /// than the request.
+    static ref AUTH_CACHE: Mutex<HashMap<String, (String, Instant)>> = Mutex::new(HashMap::new());
+
+    /// When each revoked user was revoked, the connections it authenticated
+    /// before are revoked.
+    static ref REVOCATIONS: RwLock<HashMap<String, u64>> = RwLock::new(HashMap::new());
+}
+
+/// Verify the credentials sent by `AUTH [username] password`.
+///
+/// The `password` backend compares with the static password in config, the
+/// `webhook` backend posts the credentials as json to `auth_webhook_url`, so
+/// LDAP binds or OAuth token introspection can be fronted by a small service.
+/// External backends fail closed, any error or timeout denies the client.
+pub async fn authenticate(username: &str, password: &str) -> bool {
+    let backend = config_auth_backend_or_default();
+    match backend.as_str() {
+        "password" => {
+            let passed = is_auth_matched(password);
+            AUTH_REQUEST_COUNTER
+                .with_label_values(&[&backend, if passed { "ok" } else { "denied" }])
+                .inc();
+            passed
+        }
+        "webhook" => webhook_authenticate(username, password).await,
+        _ => {
+            error!(LOGGER, "unknown auth backend {}, deny all clients", backend);
+            AUTH_REQUEST_COUNTER
+                .with_label_values(&[&backend, "error"])
+                .inc();
+            false
+        }
+    }
+}
+
+async fn webhook_authenticate(username: &str, password: &str) -> bool {
+    let cache_ttl = config_auth_cache_ttl_or_default();
+    let cache_key = sha1hex(&format!("{}\0{}", username, password));
+    if cache_ttl > 0 {
+        let cache = AUTH_CACHE.lock().unwrap();
+        if let Some((_, expire_at)) = cache.get(&cache_key) {
+            if *expire_at > Instant::now() {
+                AUTH_REQUEST_COUNTER
+                    .with_label_values(&["webhook", "cached"])
+                    .inc();
+                return true;
+            }
+        }
+    }
+
+    let body = format!(
+        "{{\"username\":{},\"password\":{}}}",
+        json_quote(username),
+        json_quote(password)
+    );
+    let req = match Request::builder()
+        .method(Method::POST)
+        .uri(config_auth_webhook_url_or_default())
+        .header("content-type", "application/json")
+        .body(Body::from(body))
+    {
+        Ok(req) => req,
+        Err(e) => {
+            error!(LOGGER, "invalid auth webhook request: {}", e);
+            AUTH_REQUEST_COUNTER
+                .with_label_values(&["webhook", "error"])
+                .inc();
+            return false;
+        }
+    };
+
+    let timeout = Duration::from_millis(config_auth_webhook_timeout_or_default());
+    let client = Client::new();
+    let (passed, result) = match tokio::time::timeout(timeout, client.request(req)).await {
+        Ok(Ok(resp)) if resp.status().is_success() => (true, "ok"),
+        Ok(Ok(resp)) => {
+            warn!(
+                LOGGER,
+                "auth webhook denied user {}: {}",
+                username,
+                resp.status()
+            );
+            (false, "denied")
+        }
+        Ok(Err(e)) => {
+            error!(LOGGER, "auth webhook request failed: {}", e);
+            (false, "error")
+        }
+        Err(_) => {
+            error!(LOGGER, "auth webhook request timeout after {:?}", timeout);
+            (false, "error")
+        }
+    };
+    AUTH_REQUEST_COUNTER
+        .with_label_values(&["webhook", result])
+        .inc();
+
+    if passed && cache_ttl > 0 {
+        let now = Instant::now();
+        let mut cache = AUTH_CACHE.lock().unwrap();
+        cache.retain(|_, (_, expire_at)| *expire_at > now);
+        cache.insert(
+            cache_key,
+            (username.to_owned(), now + Duration::from_millis(cache_ttl)),
+        );
+    }
+    passed
+}
+
+/// Number of external authentications cached, expired ones included until
+/// they are authenticated again
+pub fn auth_cache_len() -> usize {
+    AUTH_CACHE.lock().unwrap().len()
+}
+
+/// True if `user` was revoked after the connection authenticated at
+/// `auth_time`
+pub fn is_revoked(user: &str, auth_time: u64) -> bool {
+    match REVOCATIONS.read().unwrap().get(user) {
+        Some(revoked_at) => auth_time <= *revoked_at,
+        None => false,
+    }
+}
+
+/// Record a revocation of `user` in this member, its cached external
+/// authentications are dropped so the backend verifies it again
+fn revoke_local(user: &str, revoked_at: u64) {
+    let mut revocations = REVOCATIONS.write().unwrap();
+    let last = revocations.entry(user.to_owned()).or_insert(0);
+    if *last >= revoked_at {
+        return;
+    }
+    *last = revoked_at;
+    AUTH_CACHE
+        .lock()
+        .unwrap()
+        .retain(|_, (cached_user, _)| cached_user != user);
+    info!(LOGGER, "[AUTH] sessions of user {} revoked", user);
+}
+
+/// Revoke the sessions of `user` authenticated before now, in this member
+/// at once and in the other members when they load the revocation written to
+/// the backend with `load_revocations`
+pub async fn revoke_user(user: &str) -> AsyncResult<()> {
+    let revoked_at = now_timestamp_in_micros();
+    revoke_local(user, revoked_at);
+    if !is_use_txn_api() {
+        return Ok(());
+    }
+
+    let mut client = get_txn_client()?;
+    let key = KEY_ENCODER.encode_txnkv_auth_revoke_key(user);
+    client
+        .exec_in_txn(None, |txn_rc| {
+            async move {
+                let mut txn = txn_rc.lock().await;
+                txn.put(key, revoked_at.to_be_bytes().to_vec()).await?;
+                Ok(())
+            }
+            .boxed()
+        })
+        .await
+}
+
+/// Load the revocations written by the members, the ones past the retention
+/// are removed from the backend
+pub async fn load_revocations(txn: &mut Transaction) -> AsyncResult<()> {
+    let now = now_timestamp_in_micros();
+    let range = KEY_ENCODER.encode_txnkv_auth_revoke_key_range();
+    for kv in txn.scan(range, u32::MAX).await? {
+        let revoked_at = KeyDecoder::decode_auth_revoke_value(&kv.1);
+        if revoked_at + REVOCATION_RETENTION < now {
+            txn.delete(kv.0).await?;
+            continue;
+        }
+        let encoded_key: Vec<u8> = kv.0.into();
+        let user = KeyDecoder::decode_auth_revoke_key_user(&encoded_key);
+        revoke_local(&String::from_utf8_lossy(user), revoked_at);
+    }
+    Ok(())
+}
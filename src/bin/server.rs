@@ -1,10 +1,11 @@
 use tidis::{
-    config_instance_id_or_default, config_listen_or_default, config_pd_addrs_or_default,
-    config_port_or_default, config_prometheus_listen_or_default, config_prometheus_port_or_default,
-    config_tls_auth_client_or_default, config_tls_ca_cert_file_or_default,
-    config_tls_cert_file_or_default, config_tls_key_file_or_default, config_tls_listen_or_default,
-    config_tls_port_or_default, do_async_connect, server, set_global_config, set_instance_id,
-    utils, Config, PrometheusServer,
+    check_config, check_data_format, config_instance_id_or_default, config_listen_or_default,
+    config_pd_addrs_or_default, config_port_or_default, config_prometheus_listen_or_default,
+    config_prometheus_port_or_default, config_tls_auth_client_or_default,
+    config_tls_ca_cert_file_or_default, config_tls_cert_file_or_default,
+    config_tls_key_file_or_default, config_tls_listen_or_default, config_tls_port_or_default,
+    do_async_connect, init_data_keys, server, set_global_config, set_instance_id, utils, Config,
+    Playground, PrometheusServer,
 };
 
 use slog::info;
@@ -22,11 +23,39 @@ use async_tls::TlsAcceptor;
 pub async fn main() -> tidis::Result<()> {
     let cli = Cli::from_args();
 
+    if let Some(SubCommand::CheckConfig { file }) = &cli.cmd {
+        let config_file_name = match file.as_ref().or_else(|| cli.config.as_ref()) {
+            Some(name) => name,
+            None => {
+                println!("No config file to check");
+                exit(2);
+            }
+        };
+        let config_content =
+            fs::read_to_string(config_file_name).expect("Failed to read config file");
+        let errors = check_config(config_file_name, &config_content);
+        for e in &errors {
+            println!("{}", e);
+        }
+        if !errors.is_empty() {
+            exit(1);
+        }
+        println!("{}: ok", config_file_name);
+        exit(0);
+    }
+
     let mut config: Option<Config> = None;
 
     if let Some(config_file_name) = cli.config {
         let config_content =
-            fs::read_to_string(config_file_name).expect("Failed to read config file");
+            fs::read_to_string(&config_file_name).expect("Failed to read config file");
+        let errors = check_config(&config_file_name, &config_content);
+        if !errors.is_empty() {
+            for e in &errors {
+                println!("Unable to load config file {}", e);
+            }
+            exit(1);
+        }
 
         // deserialize toml config
         config = match toml::from_str(&config_content) {
@@ -38,9 +67,14 @@ pub async fn main() -> tidis::Result<()> {
         };
     };
 
-    match &config {
+    match &mut config {
         Some(c) => {
+            // print before secret references are resolved
             println!("{:?}", c);
+            if let Err(e) = c.resolve_secrets() {
+                println!("Unable to load config file {}", e);
+                exit(1);
+            }
             set_global_config(c.clone())
         }
         None => (),
@@ -65,7 +99,14 @@ pub async fn main() -> tidis::Result<()> {
         .tls_ca_cert_file
         .as_deref()
         .unwrap_or(&c_tls_ca_cert_file);
-    let c_pd_addrs = config_pd_addrs_or_default();
+    let playground = match cli.cmd {
+        Some(SubCommand::Dev) => Some(Playground::start().await?),
+        _ => None,
+    };
+    let c_pd_addrs = match &playground {
+        Some(p) => p.pd_addr().to_owned(),
+        None => config_pd_addrs_or_default(),
+    };
     let pd_addrs = cli.pd_addrs.as_deref().unwrap_or(&c_pd_addrs);
     let c_instance_id = config_instance_id_or_default();
     let instance_id_str = cli.instance_id.as_deref().unwrap_or(&c_instance_id);
@@ -89,6 +130,8 @@ pub async fn main() -> tidis::Result<()> {
     //do_async_raw_connect(addrs).await?;
     //do_async_txn_connect(addrs).await?;
     do_async_connect(addrs).await?;
+    check_data_format().await?;
+    init_data_keys().await?;
 
     let server = PrometheusServer::new(
         format!("{}:{}", &prom_listen, prom_port),
@@ -128,6 +171,10 @@ pub async fn main() -> tidis::Result<()> {
 
     server::run(listener, tls_listener, tls_acceptor, signal::ctrl_c()).await;
 
+    if let Some(p) = playground {
+        p.stop().await;
+    }
+
     Ok(())
 }
 
@@ -172,4 +219,18 @@ struct Cli {
 
     #[structopt(name = "config", long = "--config")]
     config: Option<String>,
+
+    #[structopt(subcommand)]
+    cmd: Option<SubCommand>,
+}
+
+#[derive(StructOpt, Debug)]
+enum SubCommand {
+    /// Run with a local single node TiKV, launched by tiup playground if not running
+    Dev,
+    /// Check the config file and report every invalid or unknown setting
+    CheckConfig {
+        /// config file, defaults to the --config one
+        file: Option<String>,
+    },
 }
@@ -0,0 +1,150 @@
+//! Clients blocked on keys, for BLMPOP and BZMPOP.
+//!
+//! A blocked client registers the keys it waits for and is woken up when a
+//! command of this member pushes to one of them, then tries to pop again.
+//! Pushes served by other members are not seen here, so a blocked client
+//! also tries again every `BLOCKED_POLL_INTERVAL` until its timeout.
+
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use tokio::select;
+use tokio::sync::Notify;
+use tokio::time::Instant;
+
+use crate::{Frame, Shutdown};
+
+/// Longest wait before a blocked client tries again without being woken
+pub const BLOCKED_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Longest timeout of a blocking command in seconds, that of Redis whose
+/// timeouts in ms fit in an i64
+pub const MAX_BLOCK_TIMEOUT_SECS: f64 = (i64::MAX / 1000) as f64;
+
+struct Waiters {
+    notify: Arc<Notify>,
+    /// Clients registered on the key, the entry is removed with the last one
+    count: usize,
+}
+
+lazy_static! {
+    static ref BLOCKED_KEYS: Mutex<HashMap<String, Waiters>> = Mutex::new(HashMap::new());
+}
+
+/// Wake up the clients blocked on the key, called once an element is added
+pub fn signal_key_ready(key: &str) {
+    if let Some(waiters) = BLOCKED_KEYS.lock().unwrap().get(key) {
+        waiters.notify.notify_waiters();
+    }
+}
+
+/// Number of keys clients are blocked on
+pub fn blocked_keys() -> usize {
+    BLOCKED_KEYS.lock().unwrap().len()
+}
+
+/// Registration of a blocked client on its keys, removed when dropped
+pub struct BlockedKeys {
+    keys: Vec<String>,
+    notifies: Vec<Arc<Notify>>,
+}
+
+impl BlockedKeys {
+    pub fn register(keys: &[String]) -> BlockedKeys {
+        let mut blocked = BLOCKED_KEYS.lock().unwrap();
+        let notifies = keys
+            .iter()
+            .map(|key| {
+                let waiters = blocked.entry(key.clone()).or_insert_with(|| Waiters {
+                    notify: Arc::new(Notify::new()),
+                    count: 0,
+                });
+                waiters.count += 1;
+                waiters.notify.clone()
+            })
+            .collect();
+        BlockedKeys {
+            keys: keys.to_vec(),
+            notifies,
+        }
+    }
+
+    /// Wait until one of the keys is signaled or `timeout` elapsed
+    pub async fn wait(&self, timeout: Duration) {
+        let notified = self
+            .notifies
+            .iter()
+            .map(|notify| Box::pin(notify.notified()));
+        let _ = tokio::time::timeout(timeout, futures::future::select_all(notified)).await;
+    }
+}
+
+impl Drop for BlockedKeys {
+    fn drop(&mut self) {
+        let mut blocked = BLOCKED_KEYS.lock().unwrap();
+        for key in &self.keys {
+            if let Some(waiters) = blocked.get_mut(key) {
+                waiters.count -= 1;
+                if waiters.count == 0 {
+                    blocked.remove(key);
+                }
+            }
+        }
+    }
+}
+
+/// Pop with `pop` until it replies something else than nil, `timeout`
+/// seconds elapsed, 0 for ever, or the server shuts down, None then. A write
+/// between an attempt and the wait does not wake the client up, it is seen
+/// by the next attempt after `BLOCKED_POLL_INTERVAL`.
+pub async fn block_on_keys<F, Fut>(
+    keys: &[String],
+    timeout: f64,
+    shutdown: &mut Shutdown,
+    mut pop: F,
+) -> Option<Frame>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Frame>,
+{
+    let blocked = BlockedKeys::register(keys);
+    let deadline = if timeout > 0.0 {
+        // the commands bound the timeout, a deadline out of the range of an
+        // Instant waits for ever instead of panicking
+        duration_from_secs_f64(timeout).and_then(|timeout| Instant::now().checked_add(timeout))
+    } else {
+        None
+    };
+    loop {
+        let response = pop().await;
+        if !matches!(response, Frame::Null) {
+            return Some(response);
+        }
+        let wait = match deadline {
+            Some(deadline) => {
+                let now = Instant::now();
+                if now >= deadline {
+                    return Some(response);
+                }
+                std::cmp::min(deadline - now, BLOCKED_POLL_INTERVAL)
+            }
+            None => BLOCKED_POLL_INTERVAL,
+        };
+        select! {
+            _ = blocked.wait(wait) => {}
+            _ = shutdown.recv() => return None,
+        }
+    }
+}
+
+/// The duration of `secs` seconds, None if it is not one, like the
+/// `Duration::try_from_secs_f64` not stable on the toolchain yet
+fn duration_from_secs_f64(secs: f64) -> Option<Duration> {
+    if secs.is_finite() && secs >= 0.0 && secs < u64::MAX as f64 {
+        Some(Duration::from_secs_f64(secs))
+    } else {
+        None
+    }
+}
@@ -6,9 +6,17 @@ use std::sync::atomic::{AtomicU64, Ordering};
 use std::time::SystemTime;
 use tokio::sync::mpsc::Sender;
 
+use crate::metrics::PUSH_MESSAGE_COUNTER;
+use crate::utils::now_timestamp_in_micros;
+use crate::Frame;
+
 // reserve id 0
 static COUNTER: AtomicU64 = AtomicU64::new(1);
 
+/// Push messages queued to a connection, more are dropped until the
+/// connection catches up.
+pub const PUSH_CHANNEL_CAPACITY: usize = 1024;
+
 #[derive(Debug, Clone)]
 pub struct Client {
     id: u64,
@@ -24,11 +32,16 @@ pub struct Client {
     create_time: SystemTime,
     last_interaction: SystemTime,
 
+    // user authenticated with AUTH, and when in microseconds
+    user: String,
+    auth_time: u64,
+
     kill_tx: Sender<()>,
+    push_tx: Sender<Frame>,
 }
 
 impl Client {
-    pub fn new(socket: TcpStream, kill_tx: Sender<()>) -> Client {
+    pub fn new(socket: TcpStream, kill_tx: Sender<()>, push_tx: Sender<Frame>) -> Client {
         let now = SystemTime::now();
         Client {
             id: COUNTER.fetch_add(1, Ordering::Relaxed),
@@ -39,7 +52,10 @@ impl Client {
             peer_addr: socket.peer_addr().unwrap().to_string(),
             create_time: now,
             last_interaction: now,
+            user: "default".to_owned(),
+            auth_time: 0,
             kill_tx,
+            push_tx,
         }
     }
 
@@ -52,6 +68,22 @@ impl Client {
         let _ = self.kill_tx.send(()).await;
     }
 
+    /// Queue an out-of-band message to the connection, e.g. an invalidation or
+    /// a monitor line. It is written between replies, so it never splits an
+    /// in-flight reply. Returns false if the queue of the connection is full.
+    pub fn push(&self, frame: Frame) -> bool {
+        match self.push_tx.try_send(frame) {
+            Ok(_) => {
+                PUSH_MESSAGE_COUNTER.with_label_values(&["queued"]).inc();
+                true
+            }
+            Err(_) => {
+                PUSH_MESSAGE_COUNTER.with_label_values(&["dropped"]).inc();
+                false
+            }
+        }
+    }
+
     pub fn id(&self) -> u64 {
         self.id
     }
@@ -67,6 +99,10 @@ impl Client {
         &self.peer_addr
     }
 
+    pub fn set_peer_addr(&mut self, peer_addr: &str) {
+        self.peer_addr = peer_addr.to_string();
+    }
+
     pub fn age(&self) -> u64 {
         self.create_time.elapsed().unwrap().as_secs()
     }
@@ -75,9 +111,27 @@ impl Client {
         self.last_interaction.elapsed().unwrap().as_secs()
     }
 
+    /// The last command played, in flight or not
+    pub fn cmd(&self) -> &str {
+        &self.cmd
+    }
+
     pub fn set_name(&mut self, name: &str) {
         self.name = name.to_string();
     }
+
+    pub fn user(&self) -> &str {
+        &self.user
+    }
+
+    pub fn auth_time(&self) -> u64 {
+        self.auth_time
+    }
+
+    pub fn set_user(&mut self, user: &str) {
+        self.user = user.to_string();
+        self.auth_time = now_timestamp_in_micros();
+    }
 }
 
 impl fmt::Display for Client {
@@ -86,7 +140,7 @@ impl fmt::Display for Client {
             f,
             "id={} addr={} laddr={} fd={} name={} age={} idle={} flags=N \
             db=0 sub=0 psub=0 multi=-1 qbuf=0 qbuf-free=0 argv-mem=10 obl=0 oll=0 omem=0 \
-            tot-mem=0 events=r cmd={} user=default redir=-1",
+            tot-mem=0 events=r cmd={} user={} redir=-1",
             self.id,
             self.peer_addr,
             self.local_addr,
@@ -94,7 +148,8 @@ impl fmt::Display for Client {
             self.name,
             self.age(),
             self.idle(),
-            self.cmd
+            self.cmd,
+            self.user
         )
     }
 }
@@ -1,5 +1,6 @@
 use std::sync::{Arc, RwLock};
 
+use crc::{Crc, CRC_16_XMODEM};
 use hex::ToHex;
 use sha1::{Digest, Sha1};
 
@@ -8,6 +9,31 @@ use crate::{
     Frame,
 };
 
+const CRC16: Crc<u16> = Crc::<u16>::new(&CRC_16_XMODEM);
+
+/// The hashtag of the key, the content of the `{}` closed by its first `}`
+/// if not empty
+pub fn key_hashtag(key: &[u8]) -> Option<&[u8]> {
+    let mut left_tag_idx = None;
+    for (idx, byte) in key.iter().enumerate() {
+        if byte == &b'{' {
+            left_tag_idx = Some(idx);
+        }
+        if let (Some(left), &b'}') = (left_tag_idx, byte) {
+            if idx - left > 1 {
+                return Some(&key[left + 1..idx]);
+            }
+            return None;
+        }
+    }
+    None
+}
+
+/// Slot of the key like Redis cluster, crc16 of the key or of its hashtag `{}`
+pub fn key_slot(key: &[u8]) -> usize {
+    (CRC16.checksum(key_hashtag(key).unwrap_or(key)) & 0x3FFF).into()
+}
+
 #[derive(Debug, Clone)]
 pub struct Cluster {
     nodes: Arc<RwLock<Vec<Node>>>,
@@ -187,4 +213,24 @@ impl Cluster {
             .unwrap();
         (myself.slot_start, myself.slot_end)
     }
+
+    /// The id and address of the member owning the slot
+    pub fn slot_owner(&self, slot: usize) -> Option<(String, String)> {
+        let nodes_guard = self.nodes.read().unwrap();
+        nodes_guard
+            .iter()
+            .find(|node| node.slot_start <= slot && slot <= node.slot_end)
+            .map(|node| (node.id.clone(), format!("{}:{}", node.ip, node.port)))
+    }
+
+    /// The id of this member, derived from its address
+    pub fn myself_id(&self) -> String {
+        let nodes_guard = self.nodes.read().unwrap();
+        nodes_guard
+            .iter()
+            .find(|node| node.flags.is_some())
+            .unwrap()
+            .id
+            .clone()
+    }
 }
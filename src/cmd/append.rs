@@ -0,0 +1,94 @@
+use std::sync::Arc;
+
+use crate::config::LOGGER;
+use crate::tikv::backend::Transaction;
+use crate::tikv::errors::AsyncResult;
+use crate::tikv::string::StringCommandCtx;
+use crate::utils::resp_invalid_arguments;
+use crate::{Connection, Frame, Parse};
+use bytes::Bytes;
+use slog::debug;
+use tokio::sync::Mutex;
+
+use crate::cmd::Invalid;
+use crate::config::is_use_txn_api;
+
+/// Append `value` at the end of the string held by `key`, the key is created
+/// if it does not exist. Replies the length of the string after the append.
+#[derive(Debug, Clone)]
+pub struct Append {
+    key: String,
+    value: Bytes,
+    valid: bool,
+}
+
+impl Append {
+    pub fn new(key: impl ToString, value: Bytes) -> Append {
+        Append {
+            key: key.to_string(),
+            value,
+            valid: true,
+        }
+    }
+
+    pub fn key(&self) -> &str {
+        &self.key
+    }
+
+    pub(crate) fn parse_frames(parse: &mut Parse) -> crate::Result<Append> {
+        let key = parse.next_string()?;
+        let value = parse.next_bytes()?;
+
+        Ok(Append::new(key, value))
+    }
+
+    pub(crate) fn parse_argv(argv: &Vec<Bytes>) -> crate::Result<Append> {
+        if argv.len() != 2 {
+            return Ok(Append::new_invalid());
+        }
+        let key = &String::from_utf8_lossy(&argv[0]);
+        Ok(Append::new(key, argv[1].clone()))
+    }
+
+    pub(crate) async fn apply(self, dst: &mut Connection) -> crate::Result<()> {
+        let response = self.append(None).await.unwrap_or_else(Into::into);
+
+        debug!(
+            LOGGER,
+            "res, {} -> {}, {:?}",
+            dst.local_addr(),
+            dst.peer_addr(),
+            response
+        );
+
+        dst.write_frame(&response).await?;
+
+        Ok(())
+    }
+
+    pub async fn append(&self, txn: Option<Arc<Mutex<Transaction>>>) -> AsyncResult<Frame> {
+        if !self.valid {
+            return Ok(resp_invalid_arguments());
+        }
+
+        if is_use_txn_api() {
+            StringCommandCtx::new(txn)
+                .do_async_txnkv_append(&self.key, &self.value)
+                .await
+        } else {
+            StringCommandCtx::new(None)
+                .do_async_rawkv_append(&self.key, &self.value)
+                .await
+        }
+    }
+}
+
+impl Invalid for Append {
+    fn new_invalid() -> Append {
+        Append {
+            key: "".to_owned(),
+            value: Bytes::new(),
+            valid: false,
+        }
+    }
+}
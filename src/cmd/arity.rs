@@ -0,0 +1,94 @@
+use crate::{Connection, Frame};
+
+use crate::config::LOGGER;
+use slog::debug;
+
+/// Arity of the command like Redis, counting the command name. A positive
+/// arity is the exact number of arguments, a negative one the minimum.
+fn arity_of(command_name: &str) -> Option<i32> {
+    let arity = match command_name {
+        "get" | "type" | "ttl" | "pttl" | "persist" | "incr" | "decr" | "strlen" | "hlen"
+        | "hgetall" | "hkeys" | "hvals" | "llen" | "scard" | "smembers" | "zcard" | "xlen"
+        | "idempotency" | "tidis.route" | "dump" | "getdel" => 2,
+        "publish" | "setnx" | "append" | "expire" | "expireat" | "pexpire" | "pexpireat"
+        | "incrby" | "decrby" | "hget" | "hexists" | "hstrlen" | "lindex" | "sismember"
+        | "zscore" | "zrank" | "sdrain" | "unlock" | "getbit" | "tidis.move" => 3,
+        "setex" | "getrange" | "substr" | "hsetnx" | "hincrby" | "lrange" | "lset" | "ltrim"
+        | "lrem" | "zremrangebyscore" | "zremrangebyrank" | "zcount" | "zincrby" | "lock"
+        | "extend" | "setbit" | "setrange" => 4,
+        "linsert" => 5,
+        "cl.throttle" | "xadd" | "geoadd" => -5,
+        "xclaim" => -6,
+        "xreadgroup" | "geosearch" => -7,
+        "migrate" => -6,
+        "readwrite" | "readonly" | "multi" | "exec" | "discard" | "unwatch" | "randomkey" => 1,
+        "unsubscribe" | "punsubscribe" | "ping" | "lolwut" | "ttlforecast" => -1,
+        "del" | "subscribe" | "psubscribe" | "mget" | "exists" | "lpop" | "rpop" | "script"
+        | "srandmember" | "spop" | "zpopmin" | "zpopmax" | "auth" | "debug" | "cluster"
+        | "client" | "info" | "scan" | "xscan" | "sinter" | "watch" | "json.get" | "json.del"
+        | "xgroup" | "hindex" | "geopos" | "bitcount" | "config" | "bitfield" | "sort"
+        | "object" | "getex" | "tidis.rpclog" | "tidis.format" | "tidis.snapshot" => -2,
+        "set" | "mset" | "hmget" | "hdel" | "lpush" | "rpush" | "eval" | "evalsha" | "sadd"
+        | "smismember" | "srem" | "zrem" | "zmscore" | "sintercard" | "zinter" | "zintercard"
+        | "zunion" | "zdiff" | "xpending" | "bitpos" | "copy" | "lpos" => -3,
+        "hset" | "hmset" | "zadd" | "zrange" | "zrevrange" | "zrangebyscore"
+        | "zrevrangebyscore" | "xrange" | "xrevrange" | "xread" | "json.set" | "xack"
+        | "geodist" | "bitop" | "restore" | "lmpop" | "zmpop" | "zinterstore" | "zunionstore"
+        | "zdiffstore" => -4,
+        "blmpop" | "bzmpop" | "zrangestore" => -5,
+        _ => return None,
+    };
+    Some(arity)
+}
+
+/// Returns true if `argc` arguments, counting the command name, match the
+/// arity of the command. Commands not in the table are not checked.
+pub(crate) fn arity_matches(command_name: &str, argc: usize) -> bool {
+    match arity_of(command_name) {
+        Some(arity) if arity >= 0 => argc == arity as usize,
+        Some(arity) => argc >= (-arity) as usize,
+        None => true,
+    }
+}
+
+/// Represents a command called with a wrong number of arguments, which is
+/// rejected before it is parsed.
+#[derive(Debug, Clone)]
+pub struct WrongArity {
+    command_name: String,
+}
+
+impl WrongArity {
+    pub(crate) fn new(command_name: impl ToString) -> WrongArity {
+        WrongArity {
+            command_name: command_name.to_string(),
+        }
+    }
+
+    /// Returns the command name
+    pub(crate) fn get_name(&self) -> &str {
+        &self.command_name
+    }
+
+    pub(crate) fn response(&self) -> Frame {
+        Frame::ErrorOwned(format!(
+            "ERR wrong number of arguments for '{}' command",
+            self.command_name
+        ))
+    }
+
+    pub(crate) async fn apply(self, dst: &mut Connection) -> crate::Result<()> {
+        let response = self.response();
+
+        debug!(
+            LOGGER,
+            "res, {} -> {}, {:?}",
+            dst.local_addr(),
+            dst.peer_addr(),
+            response
+        );
+
+        dst.write_frame(&response).await?;
+        Ok(())
+    }
+}
@@ -1,8 +1,9 @@
 use crate::cmd::Invalid;
-use crate::Parse;
+use crate::{Parse, ParseError};
 
 #[derive(Debug, Clone)]
 pub struct Auth {
+    username: String,
     passwd: String,
     valid: bool,
 }
@@ -10,11 +11,16 @@ pub struct Auth {
 impl Auth {
     pub fn new(passwd: String) -> Auth {
         Auth {
+            username: "default".to_owned(),
             passwd,
             valid: true,
         }
     }
 
+    pub fn username(&self) -> &str {
+        &self.username
+    }
+
     pub fn passwd(&self) -> &str {
         &self.passwd
     }
@@ -24,18 +30,25 @@ impl Auth {
     }
 
     pub(crate) fn parse_frames(parse: &mut Parse) -> crate::Result<Auth> {
-        let passwd = parse.next_string()?;
+        let first = parse.next_string()?;
 
-        Ok(Auth {
-            passwd,
-            valid: true,
-        })
+        // AUTH [username] password
+        match parse.next_string() {
+            Ok(passwd) => Ok(Auth {
+                username: first,
+                passwd,
+                valid: true,
+            }),
+            Err(ParseError::EndOfStream) => Ok(Auth::new(first)),
+            Err(err) => Err(err.into()),
+        }
     }
 }
 
 impl Invalid for Auth {
     fn new_invalid() -> Auth {
         Auth {
+            username: "".to_owned(),
             passwd: "".to_owned(),
             valid: false,
         }
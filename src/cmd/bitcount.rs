@@ -0,0 +1,122 @@
+use std::sync::Arc;
+
+use crate::cmd::{Invalid, Parse};
+use crate::config::is_use_txn_api;
+use crate::tikv::bitmap::{BitRange, BitmapCommandCtx};
+use crate::tikv::errors::{AsyncResult, REDIS_NOT_SUPPORTED_ERR};
+use crate::utils::{resp_err, resp_invalid_arguments};
+use crate::{Connection, Frame};
+
+use crate::config::LOGGER;
+use crate::tikv::backend::Transaction;
+use bytes::Bytes;
+use slog::debug;
+use tokio::sync::Mutex;
+
+/// `BITCOUNT key [start end [BYTE|BIT]]`
+#[derive(Debug, Clone)]
+pub struct Bitcount {
+    key: String,
+    range: Option<BitRange>,
+    valid: bool,
+}
+
+impl Bitcount {
+    pub fn key(&self) -> &str {
+        &self.key
+    }
+
+    pub(crate) fn parse_frames(parse: &mut Parse) -> crate::Result<Bitcount> {
+        let mut args = vec![];
+        while let Ok(arg) = parse.next_bytes() {
+            args.push(arg);
+        }
+        Ok(Bitcount::from_args(&args))
+    }
+
+    pub(crate) fn parse_argv(argv: &Vec<Bytes>) -> crate::Result<Bitcount> {
+        Ok(Bitcount::from_args(argv))
+    }
+
+    fn from_args(args: &[Bytes]) -> Bitcount {
+        // the end is required with a start
+        if args.is_empty() || args.len() == 2 {
+            return Bitcount::new_invalid();
+        }
+        match parse_bit_range(&args[1..]) {
+            Some(range) => Bitcount {
+                key: String::from_utf8_lossy(&args[0]).to_string(),
+                range,
+                valid: true,
+            },
+            None => Bitcount::new_invalid(),
+        }
+    }
+
+    pub(crate) async fn apply(self, dst: &mut Connection) -> crate::Result<()> {
+        let response = self.bitcount(None).await.unwrap_or_else(Into::into);
+        debug!(
+            LOGGER,
+            "res, {} -> {}, {:?}",
+            dst.local_addr(),
+            dst.peer_addr(),
+            response
+        );
+        dst.write_frame(&response).await?;
+
+        Ok(())
+    }
+
+    pub async fn bitcount(&self, txn: Option<Arc<Mutex<Transaction>>>) -> AsyncResult<Frame> {
+        if !self.valid {
+            return Ok(resp_invalid_arguments());
+        }
+        if is_use_txn_api() {
+            BitmapCommandCtx::new(txn)
+                .do_async_txnkv_bitcount(&self.key, self.range)
+                .await
+        } else {
+            Ok(resp_err(REDIS_NOT_SUPPORTED_ERR))
+        }
+    }
+}
+
+/// Parse `[start [end [BYTE|BIT]]]` of BITCOUNT and BITPOS, None if the
+/// arguments are invalid and Some(None) without a start.
+pub(crate) fn parse_bit_range(args: &[Bytes]) -> Option<Option<BitRange>> {
+    if args.is_empty() {
+        return Some(None);
+    }
+    if args.len() > 3 {
+        return None;
+    }
+    let int = |arg: &Bytes| String::from_utf8_lossy(arg).parse::<i64>().ok();
+    let start = int(&args[0])?;
+    let end = match args.get(1) {
+        Some(arg) => Some(int(arg)?),
+        None => None,
+    };
+    let in_bits = match args.get(2) {
+        Some(unit) => match String::from_utf8_lossy(unit).to_uppercase().as_str() {
+            "BYTE" => false,
+            "BIT" => true,
+            _ => return None,
+        },
+        None => false,
+    };
+    Some(Some(BitRange {
+        start,
+        end,
+        in_bits,
+    }))
+}
+
+impl Invalid for Bitcount {
+    fn new_invalid() -> Bitcount {
+        Bitcount {
+            key: "".to_string(),
+            range: None,
+            valid: false,
+        }
+    }
+}
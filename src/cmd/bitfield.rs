@@ -0,0 +1,179 @@
+use std::sync::Arc;
+
+use crate::cmd::{Invalid, Parse};
+use crate::config::is_use_txn_api;
+use crate::tikv::bitmap::{BitmapCommandCtx, FieldOp, FieldType, Overflow, BITMAP_MAX_OFFSET};
+use crate::tikv::errors::{
+    AsyncResult, REDIS_BITFIELD_OVERFLOW_ERR, REDIS_BITFIELD_TYPE_ERR, REDIS_BIT_OFFSET_ERR,
+    REDIS_NOT_SUPPORTED_ERR, REDIS_VALUE_IS_NOT_INTEGER_ERR,
+};
+use crate::utils::{resp_err, resp_invalid_arguments};
+use crate::{Connection, Frame};
+
+use crate::config::LOGGER;
+use crate::tikv::backend::Transaction;
+use bytes::Bytes;
+use slog::debug;
+use tokio::sync::Mutex;
+
+/// `BITFIELD key [GET type offset] [SET type offset value] [INCRBY type
+/// offset increment] [OVERFLOW WRAP|SAT|FAIL] ...`, the operations are
+/// parsed when the command runs so the first invalid one is replied.
+#[derive(Debug, Clone)]
+pub struct Bitfield {
+    key: String,
+    args: Vec<Bytes>,
+    valid: bool,
+}
+
+impl Bitfield {
+    pub fn key(&self) -> &str {
+        &self.key
+    }
+
+    pub(crate) fn parse_frames(parse: &mut Parse) -> crate::Result<Bitfield> {
+        let mut args = vec![];
+        while let Ok(arg) = parse.next_bytes() {
+            args.push(arg);
+        }
+        Ok(Bitfield::from_args(&args))
+    }
+
+    pub(crate) fn parse_argv(argv: &Vec<Bytes>) -> crate::Result<Bitfield> {
+        Ok(Bitfield::from_args(argv))
+    }
+
+    fn from_args(args: &[Bytes]) -> Bitfield {
+        if args.is_empty() {
+            return Bitfield::new_invalid();
+        }
+        Bitfield {
+            key: String::from_utf8_lossy(&args[0]).to_string(),
+            args: args[1..].to_vec(),
+            valid: true,
+        }
+    }
+
+    pub(crate) async fn apply(self, dst: &mut Connection) -> crate::Result<()> {
+        let response = self.bitfield(None).await.unwrap_or_else(Into::into);
+        debug!(
+            LOGGER,
+            "res, {} -> {}, {:?}",
+            dst.local_addr(),
+            dst.peer_addr(),
+            response
+        );
+        dst.write_frame(&response).await?;
+
+        Ok(())
+    }
+
+    pub async fn bitfield(&self, txn: Option<Arc<Mutex<Transaction>>>) -> AsyncResult<Frame> {
+        if !self.valid {
+            return Ok(resp_invalid_arguments());
+        }
+        let ops = match parse_ops(&self.args) {
+            Ok(ops) => ops,
+            Err(frame) => return Ok(frame),
+        };
+        if is_use_txn_api() {
+            BitmapCommandCtx::new(txn)
+                .do_async_txnkv_bitfield(&self.key, ops)
+                .await
+        } else {
+            Ok(resp_err(REDIS_NOT_SUPPORTED_ERR))
+        }
+    }
+}
+
+/// The operations of the subcommands, OVERFLOW applying to the SET and INCRBY
+/// after it
+fn parse_ops(args: &[Bytes]) -> Result<Vec<FieldOp>, Frame> {
+    let mut ops = vec![];
+    let mut overflow = Overflow::Wrap;
+    let mut idx = 0;
+    while idx < args.len() {
+        let subcommand = String::from_utf8_lossy(&args[idx]).to_uppercase();
+        let argc = match subcommand.as_str() {
+            "GET" => 2,
+            "SET" | "INCRBY" => 3,
+            "OVERFLOW" => 1,
+            _ => return Err(resp_invalid_arguments()),
+        };
+        if idx + argc >= args.len() {
+            return Err(resp_invalid_arguments());
+        }
+        let sub_args = &args[idx + 1..idx + 1 + argc];
+        idx += 1 + argc;
+
+        if subcommand == "OVERFLOW" {
+            overflow = match String::from_utf8_lossy(&sub_args[0])
+                .to_uppercase()
+                .as_str()
+            {
+                "WRAP" => Overflow::Wrap,
+                "SAT" => Overflow::Sat,
+                "FAIL" => Overflow::Fail,
+                _ => return Err(resp_err(REDIS_BITFIELD_OVERFLOW_ERR)),
+            };
+            continue;
+        }
+        let ty = parse_type(&sub_args[0]).ok_or_else(|| resp_err(REDIS_BITFIELD_TYPE_ERR))?;
+        let offset =
+            parse_offset(&sub_args[1], ty).ok_or_else(|| resp_err(REDIS_BIT_OFFSET_ERR))?;
+        if subcommand == "GET" {
+            ops.push(FieldOp::Get(ty, offset));
+            continue;
+        }
+        let value = String::from_utf8_lossy(&sub_args[2])
+            .parse::<i64>()
+            .map_err(|_| resp_err(REDIS_VALUE_IS_NOT_INTEGER_ERR))?;
+        if subcommand == "SET" {
+            ops.push(FieldOp::Set(ty, offset, value, overflow));
+        } else {
+            ops.push(FieldOp::Incrby(ty, offset, value, overflow));
+        }
+    }
+    Ok(ops)
+}
+
+/// `i1` to `i64` or `u1` to `u63`
+fn parse_type(arg: &[u8]) -> Option<FieldType> {
+    let signed = match arg.first() {
+        Some(b'i') | Some(b'I') => true,
+        Some(b'u') | Some(b'U') => false,
+        _ => return None,
+    };
+    let bits = String::from_utf8_lossy(&arg[1..]).parse::<u32>().ok()?;
+    let max_bits = if signed { 64 } else { 63 };
+    if bits == 0 || bits > max_bits {
+        return None;
+    }
+    Some(FieldType { signed, bits })
+}
+
+/// A bit offset, or with a `#` prefix an index in fields of the type
+fn parse_offset(arg: &[u8], ty: FieldType) -> Option<u64> {
+    let (by_field, number) = match arg.strip_prefix(b"#") {
+        Some(number) => (true, number),
+        None => (false, arg),
+    };
+    let mut offset = String::from_utf8_lossy(number).parse::<u64>().ok()?;
+    if by_field {
+        offset = offset.checked_mul(ty.bits as u64)?;
+    }
+    if offset.checked_add(ty.bits as u64 - 1)? > BITMAP_MAX_OFFSET {
+        return None;
+    }
+    Some(offset)
+}
+
+impl Invalid for Bitfield {
+    fn new_invalid() -> Bitfield {
+        Bitfield {
+            key: "".to_string(),
+            args: vec![],
+            valid: false,
+        }
+    }
+}
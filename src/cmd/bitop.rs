@@ -0,0 +1,101 @@
+use std::sync::Arc;
+
+use crate::cmd::{Invalid, Parse};
+use crate::config::is_use_txn_api;
+use crate::tikv::bitmap::{BitOp, BitmapCommandCtx};
+use crate::tikv::errors::{AsyncResult, REDIS_BITOP_NOT_ERR, REDIS_NOT_SUPPORTED_ERR};
+use crate::utils::{resp_err, resp_invalid_arguments};
+use crate::{Connection, Frame};
+
+use crate::config::LOGGER;
+use crate::tikv::backend::Transaction;
+use bytes::Bytes;
+use slog::debug;
+use tokio::sync::Mutex;
+
+/// `BITOP AND|OR|XOR|NOT destkey key [key ...]`, NOT takes a single key.
+#[derive(Debug, Clone)]
+pub struct Bitop {
+    op: BitOp,
+    dest: String,
+    keys: Vec<String>,
+    valid: bool,
+}
+
+impl Bitop {
+    pub fn dest(&self) -> &str {
+        &self.dest
+    }
+
+    pub(crate) fn parse_frames(parse: &mut Parse) -> crate::Result<Bitop> {
+        let mut args = vec![];
+        while let Ok(arg) = parse.next_bytes() {
+            args.push(arg);
+        }
+        Ok(Bitop::from_args(&args))
+    }
+
+    pub(crate) fn parse_argv(argv: &Vec<Bytes>) -> crate::Result<Bitop> {
+        Ok(Bitop::from_args(argv))
+    }
+
+    fn from_args(args: &[Bytes]) -> Bitop {
+        if args.len() < 3 {
+            return Bitop::new_invalid();
+        }
+        let op = match BitOp::from_name(&String::from_utf8_lossy(&args[0])) {
+            Some(op) => op,
+            None => return Bitop::new_invalid(),
+        };
+        Bitop {
+            op,
+            dest: String::from_utf8_lossy(&args[1]).to_string(),
+            keys: args[2..]
+                .iter()
+                .map(|k| String::from_utf8_lossy(k).to_string())
+                .collect(),
+            valid: true,
+        }
+    }
+
+    pub(crate) async fn apply(self, dst: &mut Connection) -> crate::Result<()> {
+        let response = self.bitop(None).await.unwrap_or_else(Into::into);
+        debug!(
+            LOGGER,
+            "res, {} -> {}, {:?}",
+            dst.local_addr(),
+            dst.peer_addr(),
+            response
+        );
+        dst.write_frame(&response).await?;
+
+        Ok(())
+    }
+
+    pub async fn bitop(&self, txn: Option<Arc<Mutex<Transaction>>>) -> AsyncResult<Frame> {
+        if !self.valid {
+            return Ok(resp_invalid_arguments());
+        }
+        if self.op == BitOp::Not && self.keys.len() != 1 {
+            return Ok(resp_err(REDIS_BITOP_NOT_ERR));
+        }
+        if is_use_txn_api() {
+            BitmapCommandCtx::new(txn)
+                .do_async_txnkv_bitop(self.op, &self.dest, &self.keys)
+                .await
+        } else {
+            Ok(resp_err(REDIS_NOT_SUPPORTED_ERR))
+        }
+    }
+}
+
+impl Invalid for Bitop {
+    fn new_invalid() -> Bitop {
+        Bitop {
+            op: BitOp::And,
+            dest: "".to_string(),
+            keys: vec![],
+            valid: false,
+        }
+    }
+}
@@ -0,0 +1,113 @@
+use std::sync::Arc;
+
+use crate::cmd::bitcount::parse_bit_range;
+use crate::cmd::{Invalid, Parse};
+use crate::config::is_use_txn_api;
+use crate::tikv::bitmap::{BitRange, BitmapCommandCtx};
+use crate::tikv::errors::{AsyncResult, REDIS_BITPOS_BIT_ERR, REDIS_NOT_SUPPORTED_ERR};
+use crate::utils::{resp_err, resp_invalid_arguments};
+use crate::{Connection, Frame};
+
+use crate::config::LOGGER;
+use crate::tikv::backend::Transaction;
+use bytes::Bytes;
+use slog::debug;
+use tokio::sync::Mutex;
+
+/// `BITPOS key bit [start [end [BYTE|BIT]]]`
+#[derive(Debug, Clone)]
+pub struct Bitpos {
+    key: String,
+    bit: Option<bool>,
+    range: BitRange,
+    valid: bool,
+}
+
+impl Bitpos {
+    pub fn key(&self) -> &str {
+        &self.key
+    }
+
+    pub(crate) fn parse_frames(parse: &mut Parse) -> crate::Result<Bitpos> {
+        let mut args = vec![];
+        while let Ok(arg) = parse.next_bytes() {
+            args.push(arg);
+        }
+        Ok(Bitpos::from_args(&args))
+    }
+
+    pub(crate) fn parse_argv(argv: &Vec<Bytes>) -> crate::Result<Bitpos> {
+        Ok(Bitpos::from_args(argv))
+    }
+
+    fn from_args(args: &[Bytes]) -> Bitpos {
+        if args.len() < 2 {
+            return Bitpos::new_invalid();
+        }
+        let range = match parse_bit_range(&args[2..]) {
+            Some(range) => range.unwrap_or(BitRange {
+                start: 0,
+                end: None,
+                in_bits: false,
+            }),
+            None => return Bitpos::new_invalid(),
+        };
+        let bit = match args[1].as_ref() {
+            b"0" => Some(false),
+            b"1" => Some(true),
+            _ => None,
+        };
+        Bitpos {
+            key: String::from_utf8_lossy(&args[0]).to_string(),
+            bit,
+            range,
+            valid: true,
+        }
+    }
+
+    pub(crate) async fn apply(self, dst: &mut Connection) -> crate::Result<()> {
+        let response = self.bitpos(None).await.unwrap_or_else(Into::into);
+        debug!(
+            LOGGER,
+            "res, {} -> {}, {:?}",
+            dst.local_addr(),
+            dst.peer_addr(),
+            response
+        );
+        dst.write_frame(&response).await?;
+
+        Ok(())
+    }
+
+    pub async fn bitpos(&self, txn: Option<Arc<Mutex<Transaction>>>) -> AsyncResult<Frame> {
+        if !self.valid {
+            return Ok(resp_invalid_arguments());
+        }
+        let bit = match self.bit {
+            Some(bit) => bit,
+            None => return Ok(resp_err(REDIS_BITPOS_BIT_ERR)),
+        };
+        if is_use_txn_api() {
+            BitmapCommandCtx::new(txn)
+                .do_async_txnkv_bitpos(&self.key, bit, self.range)
+                .await
+        } else {
+            Ok(resp_err(REDIS_NOT_SUPPORTED_ERR))
+        }
+    }
+}
+
+impl Invalid for Bitpos {
+    fn new_invalid() -> Bitpos {
+        Bitpos {
+            key: "".to_string(),
+            bit: None,
+            range: BitRange {
+                start: 0,
+                end: None,
+                in_bits: false,
+            },
+            valid: false,
+        }
+    }
+}
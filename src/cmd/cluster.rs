@@ -1,29 +1,38 @@
 use crate::cluster::Cluster as Topo;
-use crate::cmd::Invalid;
-use crate::config::LOGGER;
-use crate::tikv::errors::REDIS_UNKNOWN_SUBCOMMAND;
-use crate::utils::{resp_err, resp_invalid_arguments};
+use crate::cmd::{resp_help, Invalid};
+use crate::config::{is_use_txn_api, LOGGER};
+use crate::tikv::errors::{
+    AsyncResult, REDIS_INVALID_SLOT_ERR, REDIS_NOT_SUPPORTED_ERR, REDIS_UNKNOWN_SUBCOMMAND,
+};
+use crate::tikv::string::StringCommandCtx;
+use crate::utils::{resp_array, resp_bulk, resp_err, resp_int, resp_invalid_arguments};
 use crate::{Connection, Parse};
 use slog::debug;
 
 #[derive(Debug, Clone)]
 pub struct Cluster {
     subcommand: String,
+    args: Vec<String>,
     valid: bool,
 }
 
 impl Cluster {
-    pub fn new(subcommand: impl ToString) -> Cluster {
+    pub fn new(subcommand: impl ToString, args: Vec<String>) -> Cluster {
         Cluster {
             subcommand: subcommand.to_string(),
+            args,
             valid: true,
         }
     }
 
     pub(crate) fn parse_frames(parse: &mut Parse) -> crate::Result<Cluster> {
         let subcommand = parse.next_string()?;
+        let mut args = vec![];
+        while let Ok(arg) = parse.next_string() {
+            args.push(arg);
+        }
 
-        Ok(Cluster::new(subcommand))
+        Ok(Cluster::new(subcommand, args))
     }
 
     pub(crate) async fn apply(self, topo: &Topo, dst: &mut Connection) -> crate::Result<()> {
@@ -36,6 +45,21 @@ impl Cluster {
             "INFO" => topo.cluster_info(),
             "SLOTS" => topo.cluster_slots(),
             "NODES" => topo.cluster_nodes(),
+            "COUNTKEYSINSLOT" if self.args.len() == 1 => self
+                .keys_in_slot(None)
+                .await
+                .map(|(in_slot, _)| resp_int(in_slot as i64))
+                .unwrap_or_else(Into::into),
+            "GETKEYSINSLOT" if self.args.len() == 2 => match self.args[1].parse::<usize>() {
+                Ok(count) => self
+                    .keys_in_slot(Some(count))
+                    .await
+                    .map(|(_, keys)| resp_array(keys.into_iter().map(resp_bulk).collect()))
+                    .unwrap_or_else(Into::into),
+                Err(_) => resp_invalid_arguments(),
+            },
+            "COUNTKEYSINSLOT" | "GETKEYSINSLOT" => resp_invalid_arguments(),
+            "HELP" => resp_help("CLUSTER"),
             _ => resp_err(REDIS_UNKNOWN_SUBCOMMAND),
         };
 
@@ -51,12 +75,28 @@ impl Cluster {
 
         Ok(())
     }
+
+    /// Number of keys in the slot of the first argument, and the first
+    /// `count` of them if some are asked for
+    async fn keys_in_slot(&self, count: Option<usize>) -> AsyncResult<(u64, Vec<Vec<u8>>)> {
+        let slot = match self.args[0].parse::<usize>() {
+            Ok(slot) if slot < 16384 => slot,
+            _ => return Err(REDIS_INVALID_SLOT_ERR),
+        };
+        if !is_use_txn_api() {
+            return Err(REDIS_NOT_SUPPORTED_ERR);
+        }
+        StringCommandCtx::new(None)
+            .do_async_txnkv_keys_in_slot(slot, count)
+            .await
+    }
 }
 
 impl Invalid for Cluster {
     fn new_invalid() -> Cluster {
         Cluster {
             subcommand: "".to_owned(),
+            args: vec![],
             valid: false,
         }
     }
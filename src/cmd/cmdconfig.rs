@@ -0,0 +1,118 @@
+use crate::cmd::{resp_help, Invalid, Parse};
+use crate::config::LOGGER;
+use crate::gc::{gc_setting, set_gc_setting, GC_SETTINGS_NAMES};
+use crate::logsample::{log_sample_setting, set_log_sample_setting, LOG_SAMPLE_SETTINGS_NAMES};
+use crate::tikv::errors::{RTError, REDIS_UNKNOWN_SUBCOMMAND};
+use crate::utils::{glob_match, resp_array, resp_bulk, resp_err, resp_invalid_arguments, resp_ok};
+use crate::{Connection, Frame};
+use slog::debug;
+
+/// `CONFIG GET pattern` and `CONFIG SET parameter value [parameter value ...]`
+/// on the settings changed at runtime, the gc throttling and log sampling
+/// ones. The config file is not rewritten, a restart goes back to it.
+#[derive(Debug, Clone)]
+pub struct Config {
+    subcommand: String,
+    args: Vec<String>,
+    valid: bool,
+}
+
+impl Config {
+    pub(crate) fn parse_frames(parse: &mut Parse) -> crate::Result<Config> {
+        let subcommand = parse.next_string()?;
+        let mut args = vec![];
+        while let Ok(arg) = parse.next_string() {
+            args.push(arg);
+        }
+        Ok(Config {
+            subcommand,
+            args,
+            valid: true,
+        })
+    }
+
+    pub(crate) async fn apply(self, dst: &mut Connection) -> crate::Result<()> {
+        let response = self.config();
+        debug!(
+            LOGGER,
+            "res, {} -> {}, {:?}",
+            dst.local_addr(),
+            dst.peer_addr(),
+            response
+        );
+        dst.write_frame(&response).await?;
+
+        Ok(())
+    }
+
+    fn config(&self) -> Frame {
+        if !self.valid {
+            return resp_invalid_arguments();
+        }
+        match self.subcommand.to_uppercase().as_str() {
+            "GET" => {
+                if self.args.len() != 1 {
+                    return resp_invalid_arguments();
+                }
+                let pattern = self.args[0].to_lowercase();
+                let mut resp = vec![];
+                for name in GC_SETTINGS_NAMES.iter().chain(&LOG_SAMPLE_SETTINGS_NAMES) {
+                    if glob_match(pattern.as_bytes(), name.as_bytes(), false) {
+                        let value = setting(name).unwrap_or(0);
+                        resp.push(resp_bulk(name.as_bytes().to_vec()));
+                        resp.push(resp_bulk(value.to_string().into_bytes()));
+                    }
+                }
+                resp_array(resp)
+            }
+            "SET" => {
+                if self.args.is_empty() || self.args.len() % 2 != 0 {
+                    return resp_invalid_arguments();
+                }
+                // all values are checked before any is applied
+                let mut settings = vec![];
+                for pair in self.args.chunks(2) {
+                    let name = pair[0].to_lowercase();
+                    if setting(&name).is_none() {
+                        return resp_err(RTError::Owned(format!(
+                            "ERR Unknown option or number of arguments for CONFIG SET - '{}'",
+                            pair[0]
+                        )));
+                    }
+                    match pair[1].parse::<u64>() {
+                        Ok(value) => settings.push((name, value)),
+                        Err(_) => {
+                            return resp_err(RTError::Owned(format!(
+                                "ERR Invalid argument '{}' for CONFIG SET '{}'",
+                                pair[1], pair[0]
+                            )))
+                        }
+                    }
+                }
+                for (name, value) in settings {
+                    if !set_gc_setting(&name, value) {
+                        set_log_sample_setting(&name, value);
+                    }
+                }
+                resp_ok()
+            }
+            "HELP" => resp_help("CONFIG"),
+            _ => resp_err(REDIS_UNKNOWN_SUBCOMMAND),
+        }
+    }
+}
+
+/// The value of a runtime setting, None for an unknown name
+fn setting(name: &str) -> Option<u64> {
+    gc_setting(name).or_else(|| log_sample_setting(name))
+}
+
+impl Invalid for Config {
+    fn new_invalid() -> Config {
+        Config {
+            subcommand: "".to_owned(),
+            args: vec![],
+            valid: false,
+        }
+    }
+}
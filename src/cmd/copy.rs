@@ -0,0 +1,119 @@
+use std::sync::Arc;
+
+use crate::cmd::{Invalid, Parse};
+use crate::config::is_use_txn_api;
+use crate::tikv::copy::CopyCommandCtx;
+use crate::tikv::errors::{AsyncResult, REDIS_DB_INDEX_OUT_OF_RANGE_ERR, REDIS_NOT_SUPPORTED_ERR};
+use crate::utils::{resp_err, resp_invalid_arguments};
+use crate::{Connection, Frame};
+
+use crate::config::LOGGER;
+use crate::tikv::backend::Transaction;
+use bytes::Bytes;
+use slog::debug;
+use tokio::sync::Mutex;
+
+/// `COPY source destination [DB destination-db] [REPLACE]`, there is only
+/// the db 0 like for the keyspace notifications.
+#[derive(Debug, Clone)]
+pub struct CopyKey {
+    source: String,
+    destination: String,
+    db: i64,
+    replace: bool,
+    valid: bool,
+}
+
+impl CopyKey {
+    pub fn source(&self) -> &str {
+        &self.source
+    }
+
+    pub fn destination(&self) -> &str {
+        &self.destination
+    }
+
+    pub(crate) fn parse_frames(parse: &mut Parse) -> crate::Result<CopyKey> {
+        let mut args = vec![];
+        while let Ok(arg) = parse.next_bytes() {
+            args.push(arg);
+        }
+        Ok(CopyKey::from_args(&args))
+    }
+
+    pub(crate) fn parse_argv(argv: &Vec<Bytes>) -> crate::Result<CopyKey> {
+        Ok(CopyKey::from_args(argv))
+    }
+
+    fn from_args(args: &[Bytes]) -> CopyKey {
+        if args.len() < 2 {
+            return CopyKey::new_invalid();
+        }
+        let mut db = 0;
+        let mut replace = false;
+        let mut idx = 2;
+        while idx < args.len() {
+            match String::from_utf8_lossy(&args[idx]).to_uppercase().as_str() {
+                "REPLACE" => replace = true,
+                "DB" if idx + 1 < args.len() => {
+                    match String::from_utf8_lossy(&args[idx + 1]).parse::<i64>() {
+                        Ok(v) => db = v,
+                        Err(_) => return CopyKey::new_invalid(),
+                    }
+                    idx += 1;
+                }
+                _ => return CopyKey::new_invalid(),
+            }
+            idx += 1;
+        }
+        CopyKey {
+            source: String::from_utf8_lossy(&args[0]).to_string(),
+            destination: String::from_utf8_lossy(&args[1]).to_string(),
+            db,
+            replace,
+            valid: true,
+        }
+    }
+
+    pub(crate) async fn apply(self, dst: &mut Connection) -> crate::Result<()> {
+        let response = self.copy(None).await.unwrap_or_else(Into::into);
+        debug!(
+            LOGGER,
+            "res, {} -> {}, {:?}",
+            dst.local_addr(),
+            dst.peer_addr(),
+            response
+        );
+        dst.write_frame(&response).await?;
+
+        Ok(())
+    }
+
+    pub async fn copy(&self, txn: Option<Arc<Mutex<Transaction>>>) -> AsyncResult<Frame> {
+        if !self.valid {
+            return Ok(resp_invalid_arguments());
+        }
+        if self.db != 0 {
+            return Ok(resp_err(REDIS_DB_INDEX_OUT_OF_RANGE_ERR));
+        }
+        if is_use_txn_api() {
+            CopyCommandCtx::new(txn)
+                .do_async_txnkv_copy(&self.source, &self.destination, self.replace)
+                .await
+        } else {
+            Ok(resp_err(REDIS_NOT_SUPPORTED_ERR))
+        }
+    }
+}
+
+impl Invalid for CopyKey {
+    fn new_invalid() -> CopyKey {
+        CopyKey {
+            source: "".to_string(),
+            destination: "".to_string(),
+            db: 0,
+            replace: false,
+            valid: false,
+        }
+    }
+}
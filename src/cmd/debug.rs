@@ -1,10 +1,15 @@
-use crate::cmd::Invalid;
+use crate::cmd::{resp_help, Invalid};
 use crate::config::LOGGER;
-use crate::tikv::errors::REDIS_NOT_SUPPORTED_DEBUG_SUB_COMMAND_ERR;
+use crate::replication::change_repl_id;
+use crate::tikv::encryption::rotate_data_key;
+use crate::tikv::errors::{
+    REDIS_DEBUG_COMMAND_DISABLED_ERR, REDIS_NOT_SUPPORTED_DEBUG_SUB_COMMAND_ERR,
+};
 use crate::tikv::{start_profiler, stop_profiler};
-use crate::utils::{resp_err, resp_invalid_arguments, resp_ok};
-use crate::{Connection, Parse};
+use crate::utils::{resp_err, resp_int, resp_invalid_arguments, resp_ok};
+use crate::{config_enable_debug_command_or_default, Connection, Parse};
 use slog::debug;
+use std::net::SocketAddr;
 
 #[derive(Debug, Clone)]
 pub struct Debug {
@@ -31,6 +36,11 @@ impl Debug {
             dst.write_frame(&resp_invalid_arguments()).await?;
             return Ok(());
         }
+        if !debug_command_allowed(dst.peer_addr()) {
+            dst.write_frame(&resp_err(REDIS_DEBUG_COMMAND_DISABLED_ERR))
+                .await?;
+            return Ok(());
+        }
 
         let response = match self.subcommand.to_lowercase().as_str() {
             "profiler_start" => {
@@ -41,6 +51,15 @@ impl Debug {
                 stop_profiler();
                 resp_ok()
             }
+            "rotate_data_key" => match rotate_data_key().await {
+                Ok(id) => resp_int(id as i64),
+                Err(e) => resp_err(e),
+            },
+            "change-repl-id" => {
+                change_repl_id();
+                resp_ok()
+            }
+            "help" => resp_help("DEBUG"),
             _ => resp_err(REDIS_NOT_SUPPORTED_DEBUG_SUB_COMMAND_ERR),
         };
 
@@ -58,6 +77,18 @@ impl Debug {
     }
 }
 
+/// Check `enable_debug_command` for the connection from `peer_addr`
+pub(crate) fn debug_command_allowed(peer_addr: &str) -> bool {
+    match config_enable_debug_command_or_default().as_str() {
+        "yes" => true,
+        "local" => peer_addr
+            .parse::<SocketAddr>()
+            .map(|addr| addr.ip().is_loopback())
+            .unwrap_or(false),
+        _ => false,
+    }
+}
+
 impl Invalid for Debug {
     fn new_invalid() -> Debug {
         Debug {
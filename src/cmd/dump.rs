@@ -0,0 +1,82 @@
+use std::sync::Arc;
+
+use crate::cmd::{Invalid, Parse};
+use crate::config::is_use_txn_api;
+use crate::tikv::dump::DumpCommandCtx;
+use crate::tikv::errors::{AsyncResult, REDIS_NOT_SUPPORTED_ERR};
+use crate::utils::{resp_err, resp_invalid_arguments};
+use crate::{Connection, Frame};
+
+use crate::config::LOGGER;
+use crate::tikv::backend::Transaction;
+use bytes::Bytes;
+use slog::debug;
+use tokio::sync::Mutex;
+
+/// `DUMP key`, the value serialized like Redis does, see `crate::rdb`.
+#[derive(Debug, Clone)]
+pub struct Dump {
+    key: String,
+    valid: bool,
+}
+
+impl Dump {
+    pub fn new(key: impl ToString) -> Dump {
+        Dump {
+            key: key.to_string(),
+            valid: true,
+        }
+    }
+
+    pub fn key(&self) -> &str {
+        &self.key
+    }
+
+    pub(crate) fn parse_frames(parse: &mut Parse) -> crate::Result<Dump> {
+        let key = parse.next_string()?;
+        Ok(Dump::new(key))
+    }
+
+    pub(crate) fn parse_argv(argv: &Vec<Bytes>) -> crate::Result<Dump> {
+        if argv.len() != 1 {
+            return Ok(Dump::new_invalid());
+        }
+        Ok(Dump::new(String::from_utf8_lossy(&argv[0])))
+    }
+
+    pub(crate) async fn apply(self, dst: &mut Connection) -> crate::Result<()> {
+        let response = self.dump(None).await.unwrap_or_else(Into::into);
+        debug!(
+            LOGGER,
+            "res, {} -> {}, {:?}",
+            dst.local_addr(),
+            dst.peer_addr(),
+            response
+        );
+        dst.write_frame(&response).await?;
+
+        Ok(())
+    }
+
+    pub async fn dump(&self, txn: Option<Arc<Mutex<Transaction>>>) -> AsyncResult<Frame> {
+        if !self.valid {
+            return Ok(resp_invalid_arguments());
+        }
+        if is_use_txn_api() {
+            DumpCommandCtx::new(txn)
+                .do_async_txnkv_dump(&self.key)
+                .await
+        } else {
+            Ok(resp_err(REDIS_NOT_SUPPORTED_ERR))
+        }
+    }
+}
+
+impl Invalid for Dump {
+    fn new_invalid() -> Dump {
+        Dump {
+            key: "".to_string(),
+            valid: false,
+        }
+    }
+}
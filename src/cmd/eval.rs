@@ -1,10 +1,12 @@
 use std::sync::Arc;
 
+use crate::cmdlog::{cmdlog_enabled, ship};
 use crate::config::is_use_txn_api;
 use crate::db::Db;
 use crate::tikv::errors::{AsyncResult, REDIS_NOT_SUPPORTED_ERR};
 use crate::tikv::get_txn_client;
 use crate::tikv::lua::LuaCommandCtx;
+use crate::triggers::fire_write;
 use crate::utils::{resp_err, resp_invalid_arguments};
 use crate::{Connection, Frame, Parse};
 
@@ -109,6 +111,7 @@ impl Eval {
         let txn_rc = Arc::new(Mutex::new(txn));
 
         let ctx = LuaCommandCtx::new(Some(txn_rc.clone()), lua);
+        let effects = ctx.clone();
 
         let resp = if is_sha {
             ctx.do_async_evalsha(&self.script, db, &self.keys, &self.args)
@@ -120,6 +123,12 @@ impl Eval {
         match resp {
             Ok(r) => {
                 txn_rc.lock().await.commit().await?;
+                for event in effects.take_effects() {
+                    fire_write(&event).await;
+                    if cmdlog_enabled() {
+                        ship(event).await;
+                    }
+                }
                 Ok(r)
             }
             Err(e) => {
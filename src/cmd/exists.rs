@@ -1,12 +1,12 @@
 use std::sync::Arc;
 
 use crate::config::is_use_txn_api;
+use crate::tikv::backend::Transaction;
 use crate::tikv::errors::AsyncResult;
 use crate::tikv::string::StringCommandCtx;
 use crate::utils::resp_invalid_arguments;
 use crate::{Connection, Frame, Parse};
 use bytes::Bytes;
-use tikv_client::Transaction;
 use tokio::sync::Mutex;
 
 use crate::cmd::Invalid;
@@ -3,13 +3,13 @@ use std::sync::Arc;
 use crate::cmd::Invalid;
 use crate::config::is_use_txn_api;
 use crate::config::LOGGER;
+use crate::tikv::backend::Transaction;
 use crate::tikv::errors::{AsyncResult, REDIS_NOT_SUPPORTED_ERR};
 use crate::tikv::string::StringCommandCtx;
 use crate::utils::{resp_err, resp_invalid_arguments, timestamp_from_ttl};
 use crate::{Connection, Frame, Parse};
 use bytes::Bytes;
 use slog::debug;
-use tikv_client::Transaction;
 use tokio::sync::Mutex;
 
 #[derive(Debug, Clone)]
@@ -3,12 +3,20 @@ use std::collections::HashMap;
 use std::sync::Arc;
 use tokio::sync::Mutex;
 
+use crate::auth::{is_revoked, revoke_user};
 use crate::client::Client;
-use crate::cmd::Invalid;
+use crate::cmd::{resp_help, Invalid};
+use crate::config_auth_revoke_mode_or_default;
+use crate::gc::encode_gc_info;
+use crate::jobs::encode_jobs_info;
+use crate::replication::encode_replication_info;
+use crate::slo::encode_slo_info;
 use crate::tikv::errors::{
     REDIS_INVALID_CLIENT_ID_ERR, REDIS_NOT_SUPPORTED_ERR, REDIS_NO_SUCH_CLIENT_ERR,
     REDIS_VALUE_IS_NOT_INTEGER_ERR,
 };
+use crate::tikv::eviction::encode_stats_info;
+use crate::tikv::health::encode_backend_info;
 use crate::{
     config::LOGGER,
     tikv::errors::REDIS_UNKNOWN_SUBCOMMAND,
@@ -34,9 +42,20 @@ impl Fake {
         {
             return Ok(Fake::new_invalid());
         }
+        if command.to_uppercase().as_str() == "IDEMPOTENCY" && args.len() != 1 {
+            return Ok(Fake::new_invalid());
+        }
         Ok(Fake { args, valid: true })
     }
 
+    /// The token of a valid `IDEMPOTENCY <token>`
+    pub(crate) fn token(&self) -> Option<String> {
+        if !self.valid {
+            return None;
+        }
+        self.args.first().cloned()
+    }
+
     pub(crate) async fn apply(
         self,
         command: &str,
@@ -71,6 +90,7 @@ impl Fake {
         let response = match command.to_uppercase().as_str() {
             "READWRITE" => resp_ok(),
             "READONLY" => resp_ok(),
+            "IDEMPOTENCY" => resp_ok(),
             "CLIENT" => {
                 // TODO client more management will be added later
                 match self.args[0].clone().to_uppercase().as_str() {
@@ -143,6 +163,7 @@ impl Fake {
                         let mut filter_peer_addr = "".to_owned();
                         let mut filter_local_addr = "".to_owned();
                         let mut filter_id = 0;
+                        let mut filter_user = "".to_owned();
                         // skipme is set to yes by default in redis
                         let mut filter_skipme = true;
 
@@ -156,6 +177,7 @@ impl Fake {
                                 },
                                 "ADDR" => filter_peer_addr = value,
                                 "LADDR" => filter_local_addr = value,
+                                "USER" => filter_user = value,
                                 "SKIPME" => match value.to_uppercase().as_str() {
                                     "YES" => filter_skipme = true,
                                     "NO" => filter_skipme = false,
@@ -185,6 +207,9 @@ impl Fake {
                                 if filter_id != 0 && lk_client.id() != filter_id {
                                     continue;
                                 }
+                                if !filter_user.is_empty() && lk_client.user() != filter_user {
+                                    continue;
+                                }
                                 if cur_client_id == lk_client.id() && filter_skipme {
                                     continue;
                                 }
@@ -201,6 +226,36 @@ impl Fake {
 
                         resp_int(killed)
                     }
+                    "REVOKE" => {
+                        if self.args.len() != 2 {
+                            return resp_invalid_arguments();
+                        }
+                        let user = &self.args[1];
+                        if let Err(e) = revoke_user(user).await {
+                            return resp_err(e);
+                        }
+
+                        // the connections of the user to this listener are closed now, the
+                        // others on their next command
+                        let mut revoked_clients = vec![];
+                        {
+                            let lk_clients = clients.lock().await;
+                            for client in lk_clients.values() {
+                                let lk_client = client.lock().await;
+                                if lk_client.user() == user
+                                    && is_revoked(user, lk_client.auth_time())
+                                {
+                                    revoked_clients.push(client.clone());
+                                }
+                            }
+                        }
+                        if config_auth_revoke_mode_or_default() != "reauth" {
+                            for revoked_client in &revoked_clients {
+                                revoked_client.lock().await.kill().await;
+                            }
+                        }
+                        resp_int(revoked_clients.len() as i64)
+                    }
                     "SETNAME" => {
                         if self.args.len() != 2 {
                             return resp_invalid_arguments();
@@ -219,6 +274,7 @@ impl Fake {
 
                         resp_bulk(name.into_bytes())
                     }
+                    "HELP" => resp_help("CLIENT"),
                     _ => resp_err(REDIS_UNKNOWN_SUBCOMMAND),
                 }
             }
@@ -228,6 +284,12 @@ impl Fake {
                         let fake_info = "connected_clients:1\r\n".to_string();
                         resp_bulk(fake_info.into_bytes())
                     }
+                    "SLO" => resp_bulk(encode_slo_info().into_bytes()),
+                    "BACKEND" => resp_bulk(encode_backend_info().into_bytes()),
+                    "STATS" => resp_bulk(encode_stats_info().into_bytes()),
+                    "REPLICATION" => resp_bulk(encode_replication_info().into_bytes()),
+                    "JOBS" => resp_bulk(encode_jobs_info().into_bytes()),
+                    "GC" => resp_bulk(encode_gc_info().into_bytes()),
                     // TODO support more info command for admin
                     _ => resp_err(REDIS_UNKNOWN_SUBCOMMAND),
                 }
@@ -0,0 +1,76 @@
+use crate::cmd::{resp_help, Invalid, Parse};
+use crate::config::{is_use_txn_api, LOGGER};
+use crate::tikv::errors::REDIS_NOT_SUPPORTED_ERR;
+use crate::tikv::format::{data_format_info, upgrade_data_format};
+use crate::utils::{resp_err, resp_invalid_arguments};
+use crate::{Connection, Frame};
+
+use slog::debug;
+
+/// `TIDIS.FORMAT GET | UPGRADE | HELP`, the data format record of the
+/// namespace checked at startup, see `crate::tikv::format`.
+#[derive(Debug, Clone)]
+pub struct Format {
+    subcommand: String,
+    valid: bool,
+}
+
+impl Format {
+    /// Returns true for UPGRADE, which rewrites the data format record
+    pub fn upgrades(&self) -> bool {
+        self.subcommand == "upgrade"
+    }
+
+    pub(crate) fn parse_frames(parse: &mut Parse) -> crate::Result<Format> {
+        let subcommand = match parse.next_string() {
+            Ok(subcommand) => subcommand.to_lowercase(),
+            Err(_) => return Ok(Format::new_invalid()),
+        };
+        if parse.next_string().is_ok() {
+            return Ok(Format::new_invalid());
+        }
+        Ok(Format {
+            subcommand,
+            valid: true,
+        })
+    }
+
+    pub(crate) async fn apply(self, dst: &mut Connection) -> crate::Result<()> {
+        let response = self.format().await;
+        debug!(
+            LOGGER,
+            "res, {} -> {}, {:?}",
+            dst.local_addr(),
+            dst.peer_addr(),
+            response
+        );
+        dst.write_frame(&response).await?;
+
+        Ok(())
+    }
+
+    async fn format(&self) -> Frame {
+        if !self.valid {
+            return resp_invalid_arguments();
+        }
+        if !is_use_txn_api() {
+            return resp_err(REDIS_NOT_SUPPORTED_ERR);
+        }
+        let res = match self.subcommand.as_str() {
+            "get" => data_format_info().await,
+            "upgrade" => upgrade_data_format().await,
+            "help" => return resp_help("TIDIS.FORMAT"),
+            _ => return resp_invalid_arguments(),
+        };
+        res.unwrap_or_else(Into::into)
+    }
+}
+
+impl Invalid for Format {
+    fn new_invalid() -> Format {
+        Format {
+            subcommand: "".to_owned(),
+            valid: false,
+        }
+    }
+}
@@ -0,0 +1,142 @@
+use std::sync::Arc;
+
+use crate::cmd::{Invalid, Parse};
+use crate::config::is_use_txn_api;
+use crate::geohash::{encode, valid_position, GEO_STEP_MAX};
+use crate::tikv::errors::{AsyncResult, RTError, REDIS_NOT_SUPPORTED_ERR};
+use crate::tikv::zset::ZsetCommandCtx;
+use crate::utils::{resp_err, resp_invalid_arguments};
+use crate::{Connection, Frame};
+
+use crate::config::LOGGER;
+use crate::tikv::backend::Transaction;
+use bytes::Bytes;
+use slog::debug;
+use tokio::sync::Mutex;
+
+/// `GEOADD key [NX|XX] [CH] longitude latitude member [...]`, the members are
+/// added to the sorted set with the geohash of their position as score.
+#[derive(Debug, Clone)]
+pub struct Geoadd {
+    key: String,
+    members: Vec<String>,
+    scores: Vec<f64>,
+    exists: Option<bool>,
+    changed_only: bool,
+    invalid_position: Option<(f64, f64)>,
+    valid: bool,
+}
+
+impl Geoadd {
+    /// Get the key
+    pub fn key(&self) -> &str {
+        &self.key
+    }
+
+    pub(crate) fn parse_frames(parse: &mut Parse) -> crate::Result<Geoadd> {
+        let mut args = vec![];
+        while let Ok(arg) = parse.next_bytes() {
+            args.push(arg);
+        }
+        Ok(Geoadd::from_args(&args))
+    }
+
+    pub(crate) fn parse_argv(argv: &Vec<Bytes>) -> crate::Result<Geoadd> {
+        Ok(Geoadd::from_args(argv))
+    }
+
+    fn from_args(args: &[Bytes]) -> Geoadd {
+        if args.is_empty() {
+            return Geoadd::new_invalid();
+        }
+        let mut geoadd = Geoadd::new_invalid();
+        geoadd.key = String::from_utf8_lossy(&args[0]).to_string();
+
+        let mut idx = 1;
+        while idx < args.len() {
+            match String::from_utf8_lossy(&args[idx]).to_uppercase().as_str() {
+                "NX" => geoadd.exists = Some(false),
+                "XX" => geoadd.exists = Some(true),
+                "CH" => geoadd.changed_only = true,
+                _ => break,
+            }
+            idx += 1;
+        }
+
+        let triples = &args[idx..];
+        if triples.is_empty() || triples.len() % 3 != 0 {
+            return Geoadd::new_invalid();
+        }
+        for triple in triples.chunks(3) {
+            let lon = String::from_utf8_lossy(&triple[0]).parse::<f64>();
+            let lat = String::from_utf8_lossy(&triple[1]).parse::<f64>();
+            let (lon, lat) = match (lon, lat) {
+                (Ok(lon), Ok(lat)) => (lon, lat),
+                _ => return Geoadd::new_invalid(),
+            };
+            if !valid_position(lon, lat) && geoadd.invalid_position.is_none() {
+                geoadd.invalid_position = Some((lon, lat));
+            }
+            geoadd.scores.push(encode(lon, lat, GEO_STEP_MAX) as f64);
+            geoadd
+                .members
+                .push(String::from_utf8_lossy(&triple[2]).to_string());
+        }
+        geoadd.valid = true;
+        geoadd
+    }
+
+    pub(crate) async fn apply(self, dst: &mut Connection) -> crate::Result<()> {
+        let response = self.geoadd(None).await.unwrap_or_else(Into::into);
+        debug!(
+            LOGGER,
+            "res, {} -> {}, {:?}",
+            dst.local_addr(),
+            dst.peer_addr(),
+            response
+        );
+        dst.write_frame(&response).await?;
+
+        Ok(())
+    }
+
+    pub async fn geoadd(&self, txn: Option<Arc<Mutex<Transaction>>>) -> AsyncResult<Frame> {
+        if !self.valid {
+            return Ok(resp_invalid_arguments());
+        }
+        if let Some((lon, lat)) = self.invalid_position {
+            return Ok(resp_err(RTError::Owned(format!(
+                "ERR invalid longitude,latitude pair {:.6},{:.6}",
+                lon, lat
+            ))));
+        }
+        if is_use_txn_api() {
+            ZsetCommandCtx::new(txn)
+                .do_async_txnkv_zadd(
+                    &self.key,
+                    &self.members,
+                    &self.scores,
+                    self.exists,
+                    self.changed_only,
+                    false,
+                )
+                .await
+        } else {
+            Ok(resp_err(REDIS_NOT_SUPPORTED_ERR))
+        }
+    }
+}
+
+impl Invalid for Geoadd {
+    fn new_invalid() -> Geoadd {
+        Geoadd {
+            key: "".to_string(),
+            members: vec![],
+            scores: vec![],
+            exists: None,
+            changed_only: false,
+            invalid_position: None,
+            valid: false,
+        }
+    }
+}
@@ -0,0 +1,103 @@
+use std::sync::Arc;
+
+use crate::cmd::{Invalid, Parse};
+use crate::config::is_use_txn_api;
+use crate::geohash::unit_to_meters;
+use crate::tikv::errors::{AsyncResult, REDIS_NOT_SUPPORTED_ERR};
+use crate::tikv::geo::GeoCommandCtx;
+use crate::utils::{resp_err, resp_invalid_arguments};
+use crate::{Connection, Frame};
+
+use crate::config::LOGGER;
+use crate::tikv::backend::Transaction;
+use bytes::Bytes;
+use slog::debug;
+use tokio::sync::Mutex;
+
+/// `GEODIST key member1 member2 [M|KM|FT|MI]`
+#[derive(Debug, Clone)]
+pub struct Geodist {
+    key: String,
+    member1: String,
+    member2: String,
+    unit: f64,
+    valid: bool,
+}
+
+impl Geodist {
+    /// Get the key
+    pub fn key(&self) -> &str {
+        &self.key
+    }
+
+    pub(crate) fn parse_frames(parse: &mut Parse) -> crate::Result<Geodist> {
+        let mut args = vec![];
+        while let Ok(arg) = parse.next_bytes() {
+            args.push(arg);
+        }
+        Ok(Geodist::from_args(&args))
+    }
+
+    pub(crate) fn parse_argv(argv: &Vec<Bytes>) -> crate::Result<Geodist> {
+        Ok(Geodist::from_args(argv))
+    }
+
+    fn from_args(args: &[Bytes]) -> Geodist {
+        if args.len() != 3 && args.len() != 4 {
+            return Geodist::new_invalid();
+        }
+        let unit = match args.get(3) {
+            Some(unit) => match unit_to_meters(&String::from_utf8_lossy(unit)) {
+                Some(unit) => unit,
+                None => return Geodist::new_invalid(),
+            },
+            None => 1.0,
+        };
+        Geodist {
+            key: String::from_utf8_lossy(&args[0]).to_string(),
+            member1: String::from_utf8_lossy(&args[1]).to_string(),
+            member2: String::from_utf8_lossy(&args[2]).to_string(),
+            unit,
+            valid: true,
+        }
+    }
+
+    pub(crate) async fn apply(self, dst: &mut Connection) -> crate::Result<()> {
+        let response = self.geodist(None).await.unwrap_or_else(Into::into);
+        debug!(
+            LOGGER,
+            "res, {} -> {}, {:?}",
+            dst.local_addr(),
+            dst.peer_addr(),
+            response
+        );
+        dst.write_frame(&response).await?;
+
+        Ok(())
+    }
+
+    pub async fn geodist(&self, txn: Option<Arc<Mutex<Transaction>>>) -> AsyncResult<Frame> {
+        if !self.valid {
+            return Ok(resp_invalid_arguments());
+        }
+        if is_use_txn_api() {
+            GeoCommandCtx::new(txn)
+                .do_async_txnkv_geodist(&self.key, &self.member1, &self.member2, self.unit)
+                .await
+        } else {
+            Ok(resp_err(REDIS_NOT_SUPPORTED_ERR))
+        }
+    }
+}
+
+impl Invalid for Geodist {
+    fn new_invalid() -> Geodist {
+        Geodist {
+            key: "".to_string(),
+            member1: "".to_string(),
+            member2: "".to_string(),
+            unit: 1.0,
+            valid: false,
+        }
+    }
+}
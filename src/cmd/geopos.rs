@@ -0,0 +1,92 @@
+use std::sync::Arc;
+
+use crate::cmd::{Invalid, Parse};
+use crate::config::is_use_txn_api;
+use crate::tikv::errors::{AsyncResult, REDIS_NOT_SUPPORTED_ERR};
+use crate::tikv::geo::GeoCommandCtx;
+use crate::utils::{resp_err, resp_invalid_arguments};
+use crate::{Connection, Frame};
+
+use crate::config::LOGGER;
+use crate::tikv::backend::Transaction;
+use bytes::Bytes;
+use slog::debug;
+use tokio::sync::Mutex;
+
+/// `GEOPOS key member [member ...]`
+#[derive(Debug, Clone)]
+pub struct Geopos {
+    key: String,
+    members: Vec<String>,
+    valid: bool,
+}
+
+impl Geopos {
+    /// Get the key
+    pub fn key(&self) -> &str {
+        &self.key
+    }
+
+    pub(crate) fn parse_frames(parse: &mut Parse) -> crate::Result<Geopos> {
+        let mut args = vec![];
+        while let Ok(arg) = parse.next_bytes() {
+            args.push(arg);
+        }
+        Ok(Geopos::from_args(&args))
+    }
+
+    pub(crate) fn parse_argv(argv: &Vec<Bytes>) -> crate::Result<Geopos> {
+        Ok(Geopos::from_args(argv))
+    }
+
+    fn from_args(args: &[Bytes]) -> Geopos {
+        if args.is_empty() {
+            return Geopos::new_invalid();
+        }
+        Geopos {
+            key: String::from_utf8_lossy(&args[0]).to_string(),
+            members: args[1..]
+                .iter()
+                .map(|m| String::from_utf8_lossy(m).to_string())
+                .collect(),
+            valid: true,
+        }
+    }
+
+    pub(crate) async fn apply(self, dst: &mut Connection) -> crate::Result<()> {
+        let response = self.geopos(None).await.unwrap_or_else(Into::into);
+        debug!(
+            LOGGER,
+            "res, {} -> {}, {:?}",
+            dst.local_addr(),
+            dst.peer_addr(),
+            response
+        );
+        dst.write_frame(&response).await?;
+
+        Ok(())
+    }
+
+    pub async fn geopos(&self, txn: Option<Arc<Mutex<Transaction>>>) -> AsyncResult<Frame> {
+        if !self.valid {
+            return Ok(resp_invalid_arguments());
+        }
+        if is_use_txn_api() {
+            GeoCommandCtx::new(txn)
+                .do_async_txnkv_geopos(&self.key, &self.members)
+                .await
+        } else {
+            Ok(resp_err(REDIS_NOT_SUPPORTED_ERR))
+        }
+    }
+}
+
+impl Invalid for Geopos {
+    fn new_invalid() -> Geopos {
+        Geopos {
+            key: "".to_string(),
+            members: vec![],
+            valid: false,
+        }
+    }
+}
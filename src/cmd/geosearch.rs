@@ -0,0 +1,172 @@
+use std::sync::Arc;
+
+use crate::cmd::{Invalid, Parse};
+use crate::config::is_use_txn_api;
+use crate::geohash::{unit_to_meters, valid_position, Shape};
+use crate::tikv::errors::{AsyncResult, REDIS_NOT_SUPPORTED_ERR};
+use crate::tikv::geo::{GeoCommandCtx, Origin, SearchOptions, SortOrder};
+use crate::utils::{resp_err, resp_invalid_arguments};
+use crate::{Connection, Frame};
+
+use crate::config::LOGGER;
+use crate::tikv::backend::Transaction;
+use bytes::Bytes;
+use slog::debug;
+use tokio::sync::Mutex;
+
+/// `GEOSEARCH key FROMMEMBER member|FROMLONLAT longitude latitude
+/// BYRADIUS radius unit|BYBOX width height unit [ASC|DESC]
+/// [COUNT count [ANY]] [WITHCOORD] [WITHDIST] [WITHHASH]`
+#[derive(Debug, Clone)]
+pub struct Geosearch {
+    key: String,
+    origin: Origin,
+    shape: Shape,
+    options: SearchOptions,
+    valid: bool,
+}
+
+impl Geosearch {
+    /// Get the key
+    pub fn key(&self) -> &str {
+        &self.key
+    }
+
+    pub(crate) fn parse_frames(parse: &mut Parse) -> crate::Result<Geosearch> {
+        let mut args = vec![];
+        while let Ok(arg) = parse.next_bytes() {
+            args.push(arg);
+        }
+        Ok(Geosearch::from_args(&args))
+    }
+
+    pub(crate) fn parse_argv(argv: &Vec<Bytes>) -> crate::Result<Geosearch> {
+        Ok(Geosearch::from_args(argv))
+    }
+
+    fn from_args(args: &[Bytes]) -> Geosearch {
+        if args.is_empty() {
+            return Geosearch::new_invalid();
+        }
+        let float = |idx: usize| {
+            args.get(idx)
+                .and_then(|a| String::from_utf8_lossy(a).parse::<f64>().ok())
+        };
+        let unit = |idx: usize| {
+            args.get(idx)
+                .and_then(|a| unit_to_meters(&String::from_utf8_lossy(a)))
+        };
+
+        let mut origin = None;
+        let mut shape = None;
+        let mut options = SearchOptions::default();
+        let mut idx = 1;
+        while idx < args.len() {
+            match String::from_utf8_lossy(&args[idx]).to_uppercase().as_str() {
+                "FROMMEMBER" if origin.is_none() && args.len() > idx + 1 => {
+                    let member = String::from_utf8_lossy(&args[idx + 1]).to_string();
+                    origin = Some(Origin::Member(member));
+                    idx += 1;
+                }
+                "FROMLONLAT" if origin.is_none() => match (float(idx + 1), float(idx + 2)) {
+                    (Some(lon), Some(lat)) if valid_position(lon, lat) => {
+                        origin = Some(Origin::LonLat(lon, lat));
+                        idx += 2;
+                    }
+                    _ => return Geosearch::new_invalid(),
+                },
+                "BYRADIUS" if shape.is_none() => match (float(idx + 1), unit(idx + 2)) {
+                    (Some(radius), Some(unit)) if radius >= 0.0 => {
+                        shape = Some(Shape::Radius(radius * unit));
+                        options.unit = unit;
+                        idx += 2;
+                    }
+                    _ => return Geosearch::new_invalid(),
+                },
+                "BYBOX" if shape.is_none() => {
+                    match (float(idx + 1), float(idx + 2), unit(idx + 3)) {
+                        (Some(width), Some(height), Some(unit))
+                            if width >= 0.0 && height >= 0.0 =>
+                        {
+                            shape = Some(Shape::Box(width * unit, height * unit));
+                            options.unit = unit;
+                            idx += 3;
+                        }
+                        _ => return Geosearch::new_invalid(),
+                    }
+                }
+                "ASC" => options.sort = Some(SortOrder::Asc),
+                "DESC" => options.sort = Some(SortOrder::Desc),
+                "COUNT" => {
+                    match args
+                        .get(idx + 1)
+                        .and_then(|a| String::from_utf8_lossy(a).parse::<usize>().ok())
+                    {
+                        Some(count) if count > 0 => options.count = Some(count),
+                        _ => return Geosearch::new_invalid(),
+                    }
+                    idx += 1;
+                    if matches!(args.get(idx + 1), Some(a) if a.eq_ignore_ascii_case(b"ANY")) {
+                        options.any = true;
+                        idx += 1;
+                    }
+                }
+                "WITHCOORD" => options.withcoord = true,
+                "WITHDIST" => options.withdist = true,
+                "WITHHASH" => options.withhash = true,
+                _ => return Geosearch::new_invalid(),
+            }
+            idx += 1;
+        }
+
+        match (origin, shape) {
+            (Some(origin), Some(shape)) => Geosearch {
+                key: String::from_utf8_lossy(&args[0]).to_string(),
+                origin,
+                shape,
+                options,
+                valid: true,
+            },
+            _ => Geosearch::new_invalid(),
+        }
+    }
+
+    pub(crate) async fn apply(self, dst: &mut Connection) -> crate::Result<()> {
+        let response = self.geosearch(None).await.unwrap_or_else(Into::into);
+        debug!(
+            LOGGER,
+            "res, {} -> {}, {:?}",
+            dst.local_addr(),
+            dst.peer_addr(),
+            response
+        );
+        dst.write_frame(&response).await?;
+
+        Ok(())
+    }
+
+    pub async fn geosearch(&self, txn: Option<Arc<Mutex<Transaction>>>) -> AsyncResult<Frame> {
+        if !self.valid {
+            return Ok(resp_invalid_arguments());
+        }
+        if is_use_txn_api() {
+            GeoCommandCtx::new(txn)
+                .do_async_txnkv_geosearch(&self.key, self.origin.clone(), self.shape, self.options)
+                .await
+        } else {
+            Ok(resp_err(REDIS_NOT_SUPPORTED_ERR))
+        }
+    }
+}
+
+impl Invalid for Geosearch {
+    fn new_invalid() -> Geosearch {
+        Geosearch {
+            key: "".to_string(),
+            origin: Origin::LonLat(0.0, 0.0),
+            shape: Shape::Radius(0.0),
+            options: SearchOptions::default(),
+            valid: false,
+        }
+    }
+}
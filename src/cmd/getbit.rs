@@ -0,0 +1,95 @@
+use std::sync::Arc;
+
+use crate::cmd::{Invalid, Parse};
+use crate::config::is_use_txn_api;
+use crate::tikv::bitmap::{BitmapCommandCtx, BITMAP_MAX_OFFSET};
+use crate::tikv::errors::{AsyncResult, REDIS_BIT_OFFSET_ERR, REDIS_NOT_SUPPORTED_ERR};
+use crate::utils::{resp_err, resp_invalid_arguments};
+use crate::{Connection, Frame};
+
+use crate::config::LOGGER;
+use crate::tikv::backend::Transaction;
+use bytes::Bytes;
+use slog::debug;
+use tokio::sync::Mutex;
+
+/// `GETBIT key offset`, 0 past the end of the string.
+#[derive(Debug, Clone)]
+pub struct Getbit {
+    key: String,
+    offset: Option<u64>,
+    valid: bool,
+}
+
+impl Getbit {
+    pub fn key(&self) -> &str {
+        &self.key
+    }
+
+    pub(crate) fn parse_frames(parse: &mut Parse) -> crate::Result<Getbit> {
+        let mut args = vec![];
+        while let Ok(arg) = parse.next_bytes() {
+            args.push(arg);
+        }
+        Ok(Getbit::from_args(&args))
+    }
+
+    pub(crate) fn parse_argv(argv: &Vec<Bytes>) -> crate::Result<Getbit> {
+        Ok(Getbit::from_args(argv))
+    }
+
+    fn from_args(args: &[Bytes]) -> Getbit {
+        if args.len() != 2 {
+            return Getbit::new_invalid();
+        }
+        Getbit {
+            key: String::from_utf8_lossy(&args[0]).to_string(),
+            offset: String::from_utf8_lossy(&args[1])
+                .parse::<u64>()
+                .ok()
+                .filter(|offset| *offset <= BITMAP_MAX_OFFSET),
+            valid: true,
+        }
+    }
+
+    pub(crate) async fn apply(self, dst: &mut Connection) -> crate::Result<()> {
+        let response = self.getbit(None).await.unwrap_or_else(Into::into);
+        debug!(
+            LOGGER,
+            "res, {} -> {}, {:?}",
+            dst.local_addr(),
+            dst.peer_addr(),
+            response
+        );
+        dst.write_frame(&response).await?;
+
+        Ok(())
+    }
+
+    pub async fn getbit(&self, txn: Option<Arc<Mutex<Transaction>>>) -> AsyncResult<Frame> {
+        if !self.valid {
+            return Ok(resp_invalid_arguments());
+        }
+        let offset = match self.offset {
+            Some(offset) => offset,
+            None => return Ok(resp_err(REDIS_BIT_OFFSET_ERR)),
+        };
+        if is_use_txn_api() {
+            BitmapCommandCtx::new(txn)
+                .do_async_txnkv_getbit(&self.key, offset)
+                .await
+        } else {
+            Ok(resp_err(REDIS_NOT_SUPPORTED_ERR))
+        }
+    }
+}
+
+impl Invalid for Getbit {
+    fn new_invalid() -> Getbit {
+        Getbit {
+            key: "".to_string(),
+            offset: None,
+            valid: false,
+        }
+    }
+}
@@ -0,0 +1,83 @@
+use std::sync::Arc;
+
+use crate::cmd::{Invalid, Parse};
+use crate::config::is_use_txn_api;
+use crate::tikv::errors::{AsyncResult, REDIS_NOT_SUPPORTED_ERR};
+use crate::tikv::string::StringCommandCtx;
+use crate::utils::{resp_err, resp_invalid_arguments};
+use crate::{Connection, Frame};
+
+use crate::config::LOGGER;
+use crate::tikv::backend::Transaction;
+use bytes::Bytes;
+use slog::debug;
+use tokio::sync::Mutex;
+
+/// `GETDEL key`, the value of the string key which is deleted in the same
+/// transaction.
+#[derive(Debug, Clone)]
+pub struct Getdel {
+    key: String,
+    valid: bool,
+}
+
+impl Getdel {
+    pub fn new(key: impl ToString) -> Getdel {
+        Getdel {
+            key: key.to_string(),
+            valid: true,
+        }
+    }
+
+    pub fn key(&self) -> &str {
+        &self.key
+    }
+
+    pub(crate) fn parse_frames(parse: &mut Parse) -> crate::Result<Getdel> {
+        let key = parse.next_string()?;
+        Ok(Getdel::new(key))
+    }
+
+    pub(crate) fn parse_argv(argv: &Vec<Bytes>) -> crate::Result<Getdel> {
+        if argv.len() != 1 {
+            return Ok(Getdel::new_invalid());
+        }
+        Ok(Getdel::new(String::from_utf8_lossy(&argv[0])))
+    }
+
+    pub(crate) async fn apply(self, dst: &mut Connection) -> crate::Result<()> {
+        let response = self.getdel(None).await.unwrap_or_else(Into::into);
+        debug!(
+            LOGGER,
+            "res, {} -> {}, {:?}",
+            dst.local_addr(),
+            dst.peer_addr(),
+            response
+        );
+        dst.write_frame(&response).await?;
+
+        Ok(())
+    }
+
+    pub async fn getdel(&self, txn: Option<Arc<Mutex<Transaction>>>) -> AsyncResult<Frame> {
+        if !self.valid {
+            return Ok(resp_invalid_arguments());
+        }
+        if is_use_txn_api() {
+            StringCommandCtx::new(txn)
+                .do_async_txnkv_getdel(&self.key)
+                .await
+        } else {
+            Ok(resp_err(REDIS_NOT_SUPPORTED_ERR))
+        }
+    }
+}
+
+impl Invalid for Getdel {
+    fn new_invalid() -> Getdel {
+        Getdel {
+            key: "".to_string(),
+            valid: false,
+        }
+    }
+}
@@ -0,0 +1,129 @@
+use std::sync::Arc;
+
+use crate::cmd::{Invalid, Parse};
+use crate::config::is_use_txn_api;
+use crate::tikv::errors::{AsyncResult, REDIS_GETEX_INVALID_EXPIRE_ERR, REDIS_NOT_SUPPORTED_ERR};
+use crate::tikv::string::StringCommandCtx;
+use crate::utils::{resp_err, resp_invalid_arguments, timestamp_from_ttl};
+use crate::{Connection, Frame};
+
+use crate::config::LOGGER;
+use crate::tikv::backend::Transaction;
+use bytes::Bytes;
+use slog::debug;
+use tokio::sync::Mutex;
+
+/// `GETEX key [EX seconds|PX milliseconds|EXAT timestamp|PXAT
+/// milliseconds-timestamp|PERSIST]`, the value of the string key whose ttl
+/// is updated in the same transaction.
+#[derive(Debug, Clone)]
+pub struct Getex {
+    key: String,
+    /// The ttl or the timestamp with `expire_at`, in milliseconds
+    expire: Option<i64>,
+    expire_at: bool,
+    persist: bool,
+    valid: bool,
+}
+
+impl Getex {
+    pub fn key(&self) -> &str {
+        &self.key
+    }
+
+    pub(crate) fn parse_frames(parse: &mut Parse) -> crate::Result<Getex> {
+        let mut args = vec![];
+        while let Ok(arg) = parse.next_bytes() {
+            args.push(arg);
+        }
+        Ok(Getex::from_args(&args))
+    }
+
+    pub(crate) fn parse_argv(argv: &Vec<Bytes>) -> crate::Result<Getex> {
+        Ok(Getex::from_args(argv))
+    }
+
+    fn from_args(args: &[Bytes]) -> Getex {
+        if args.is_empty() || args.len() > 3 {
+            return Getex::new_invalid();
+        }
+        let mut getex = Getex {
+            key: String::from_utf8_lossy(&args[0]).to_string(),
+            expire: None,
+            expire_at: false,
+            persist: false,
+            valid: true,
+        };
+        match args.len() {
+            1 => {}
+            2 if args[1].eq_ignore_ascii_case(b"PERSIST") => getex.persist = true,
+            3 => {
+                let value = match String::from_utf8_lossy(&args[2]).parse::<i64>() {
+                    Ok(value) => value,
+                    Err(_) => return Getex::new_invalid(),
+                };
+                let (millis, expire_at) =
+                    match String::from_utf8_lossy(&args[1]).to_uppercase().as_str() {
+                        "EX" => (false, false),
+                        "PX" => (true, false),
+                        "EXAT" => (false, true),
+                        "PXAT" => (true, true),
+                        _ => return Getex::new_invalid(),
+                    };
+                getex.expire = Some(if millis {
+                    value
+                } else {
+                    value.saturating_mul(1000)
+                });
+                getex.expire_at = expire_at;
+            }
+            _ => return Getex::new_invalid(),
+        }
+        getex
+    }
+
+    pub(crate) async fn apply(self, dst: &mut Connection) -> crate::Result<()> {
+        let response = self.getex(None).await.unwrap_or_else(Into::into);
+        debug!(
+            LOGGER,
+            "res, {} -> {}, {:?}",
+            dst.local_addr(),
+            dst.peer_addr(),
+            response
+        );
+        dst.write_frame(&response).await?;
+
+        Ok(())
+    }
+
+    pub async fn getex(&self, txn: Option<Arc<Mutex<Transaction>>>) -> AsyncResult<Frame> {
+        if !self.valid {
+            return Ok(resp_invalid_arguments());
+        }
+        if !is_use_txn_api() {
+            return Ok(resp_err(REDIS_NOT_SUPPORTED_ERR));
+        }
+        let timestamp = match self.expire {
+            Some(expire) if expire <= 0 => return Ok(resp_err(REDIS_GETEX_INVALID_EXPIRE_ERR)),
+            Some(expire) if self.expire_at => Some(expire as u64),
+            Some(expire) => Some(timestamp_from_ttl(expire as u64)),
+            None if self.persist => Some(0),
+            None => None,
+        };
+        StringCommandCtx::new(txn)
+            .do_async_txnkv_getex(&self.key, timestamp)
+            .await
+    }
+}
+
+impl Invalid for Getex {
+    fn new_invalid() -> Getex {
+        Getex {
+            key: "".to_string(),
+            expire: None,
+            expire_at: false,
+            persist: false,
+            valid: false,
+        }
+    }
+}
@@ -0,0 +1,101 @@
+use std::sync::Arc;
+
+use crate::config::LOGGER;
+use crate::tikv::backend::Transaction;
+use crate::tikv::errors::AsyncResult;
+use crate::tikv::string::StringCommandCtx;
+use crate::utils::resp_invalid_arguments;
+use crate::{Connection, Frame, Parse};
+use bytes::Bytes;
+use slog::debug;
+use tokio::sync::Mutex;
+
+use crate::cmd::Invalid;
+use crate::config::is_use_txn_api;
+
+#[derive(Debug, Clone)]
+pub struct Getrange {
+    key: String,
+    start: i64,
+    end: i64,
+    valid: bool,
+}
+
+impl Getrange {
+    pub fn new(key: impl ToString, start: i64, end: i64) -> Getrange {
+        Getrange {
+            key: key.to_string(),
+            start,
+            end,
+            valid: true,
+        }
+    }
+
+    pub fn key(&self) -> &str {
+        &self.key
+    }
+
+    pub(crate) fn parse_frames(parse: &mut Parse) -> crate::Result<Getrange> {
+        let key = parse.next_string()?;
+        let start = parse.next_int()?;
+        let end = parse.next_int()?;
+
+        Ok(Getrange::new(key, start, end))
+    }
+
+    pub(crate) fn parse_argv(argv: &Vec<Bytes>) -> crate::Result<Getrange> {
+        if argv.len() != 3 {
+            return Ok(Getrange::new_invalid());
+        }
+        let key = &String::from_utf8_lossy(&argv[0]);
+        let start = String::from_utf8_lossy(&argv[1]).parse::<i64>();
+        let end = String::from_utf8_lossy(&argv[2]).parse::<i64>();
+        match (start, end) {
+            (Ok(start), Ok(end)) => Ok(Getrange::new(key, start, end)),
+            _ => Ok(Getrange::new_invalid()),
+        }
+    }
+
+    pub(crate) async fn apply(self, dst: &mut Connection) -> crate::Result<()> {
+        let response = self.getrange(None).await.unwrap_or_else(Into::into);
+
+        debug!(
+            LOGGER,
+            "res, {} -> {}, {:?}",
+            dst.local_addr(),
+            dst.peer_addr(),
+            response
+        );
+
+        dst.write_frame(&response).await?;
+
+        Ok(())
+    }
+
+    pub async fn getrange(&self, txn: Option<Arc<Mutex<Transaction>>>) -> AsyncResult<Frame> {
+        if !self.valid {
+            return Ok(resp_invalid_arguments());
+        }
+
+        if is_use_txn_api() {
+            StringCommandCtx::new(txn)
+                .do_async_txnkv_getrange(&self.key, self.start, self.end)
+                .await
+        } else {
+            StringCommandCtx::new(None)
+                .do_async_rawkv_getrange(&self.key, self.start, self.end)
+                .await
+        }
+    }
+}
+
+impl Invalid for Getrange {
+    fn new_invalid() -> Getrange {
+        Getrange {
+            key: "".to_owned(),
+            start: 0,
+            end: 0,
+            valid: false,
+        }
+    }
+}
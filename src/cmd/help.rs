@@ -0,0 +1,271 @@
+//! Subcommand metadata of the container commands.
+//!
+//! `<COMMAND> HELP` is rendered from these tables in the format of redis, so
+//! the help text stays in sync with what the subcommand parsers accept.
+
+use crate::utils::{resp_array, resp_sstr, resp_str};
+use crate::Frame;
+
+pub struct SubcommandDoc {
+    pub name: &'static str,
+    pub arguments: &'static str,
+    pub summary: &'static str,
+}
+
+pub struct CommandDoc {
+    pub name: &'static str,
+    pub subcommands: &'static [SubcommandDoc],
+}
+
+const HELP_DOC: SubcommandDoc = SubcommandDoc {
+    name: "HELP",
+    arguments: "",
+    summary: "Print this help.",
+};
+
+pub static COMMAND_DOCS: &[CommandDoc] = &[
+    CommandDoc {
+        name: "CLIENT",
+        subcommands: &[
+            SubcommandDoc {
+                name: "ID",
+                arguments: "",
+                summary: "Return the ID of the current connection.",
+            },
+            SubcommandDoc {
+                name: "LIST",
+                arguments: "[ID <id> [<id> ...]]",
+                summary: "Return information about client connections.",
+            },
+            SubcommandDoc {
+                name: "KILL",
+                arguments: "<option> <value> [<option> <value> [...]]",
+                summary: "Kill connections, options are ID, ADDR, LADDR, USER and SKIPME.",
+            },
+            SubcommandDoc {
+                name: "REVOKE",
+                arguments: "<username>",
+                summary: "Revoke the connections of the user on all members.",
+            },
+            SubcommandDoc {
+                name: "SETNAME",
+                arguments: "<name>",
+                summary: "Assign the name to the current connection.",
+            },
+            SubcommandDoc {
+                name: "GETNAME",
+                arguments: "",
+                summary: "Return the name of the current connection.",
+            },
+            HELP_DOC,
+        ],
+    },
+    CommandDoc {
+        name: "CLUSTER",
+        subcommands: &[
+            SubcommandDoc {
+                name: "INFO",
+                arguments: "",
+                summary: "Return information about the cluster.",
+            },
+            SubcommandDoc {
+                name: "SLOTS",
+                arguments: "",
+                summary: "Return the mapping of slot ranges to nodes.",
+            },
+            SubcommandDoc {
+                name: "NODES",
+                arguments: "",
+                summary: "Return the cluster configuration seen by this node.",
+            },
+            SubcommandDoc {
+                name: "COUNTKEYSINSLOT",
+                arguments: "<slot>",
+                summary: "Return the number of keys in the slot, scanned up to a bound.",
+            },
+            SubcommandDoc {
+                name: "GETKEYSINSLOT",
+                arguments: "<slot> <count>",
+                summary: "Return at most count keys in the slot.",
+            },
+            HELP_DOC,
+        ],
+    },
+    CommandDoc {
+        name: "SCRIPT",
+        subcommands: &[
+            SubcommandDoc {
+                name: "LOAD",
+                arguments: "<script>",
+                summary: "Load a script into the scripts cache without executing it.",
+            },
+            SubcommandDoc {
+                name: "EXISTS",
+                arguments: "<sha1> [<sha1> ...]",
+                summary: "Return whether each script exists in the scripts cache.",
+            },
+            SubcommandDoc {
+                name: "FLUSH",
+                arguments: "",
+                summary: "Flush the scripts cache.",
+            },
+            SubcommandDoc {
+                name: "KILL",
+                arguments: "",
+                summary: "Kill the scripts in execution which have not written yet.",
+            },
+            HELP_DOC,
+        ],
+    },
+    CommandDoc {
+        name: "CONFIG",
+        subcommands: &[
+            SubcommandDoc {
+                name: "GET",
+                arguments: "<pattern>",
+                summary: "Return the runtime settings matching the glob-style pattern.",
+            },
+            SubcommandDoc {
+                name: "SET",
+                arguments: "<parameter> <value> [<parameter> <value> ...]",
+                summary: "Change runtime settings until the next restart.",
+            },
+            HELP_DOC,
+        ],
+    },
+    CommandDoc {
+        name: "DEBUG",
+        subcommands: &[
+            SubcommandDoc {
+                name: "PROFILER_START",
+                arguments: "",
+                summary: "Start the CPU profiler.",
+            },
+            SubcommandDoc {
+                name: "PROFILER_STOP",
+                arguments: "",
+                summary: "Stop the CPU profiler and write the flamegraph and profile.",
+            },
+            SubcommandDoc {
+                name: "ROTATE_DATA_KEY",
+                arguments: "",
+                summary: "Create a new data key for string value encryption.",
+            },
+            SubcommandDoc {
+                name: "CHANGE-REPL-ID",
+                arguments: "",
+                summary: "Replace the replication id reported in INFO replication.",
+            },
+            HELP_DOC,
+        ],
+    },
+    CommandDoc {
+        name: "OBJECT",
+        subcommands: &[
+            SubcommandDoc {
+                name: "ENCODING",
+                arguments: "<key>",
+                summary: "Return the encoding of the key's meta value.",
+            },
+            SubcommandDoc {
+                name: "FREQ",
+                arguments: "<key>",
+                summary: "Return the access frequency counted by this member.",
+            },
+            SubcommandDoc {
+                name: "IDLETIME",
+                arguments: "<key>",
+                summary: "Return the seconds since the last access on this member.",
+            },
+            SubcommandDoc {
+                name: "REFCOUNT",
+                arguments: "<key>",
+                summary: "Return 1, the values are not shared.",
+            },
+            HELP_DOC,
+        ],
+    },
+    CommandDoc {
+        name: "TIDIS.RPCLOG",
+        subcommands: &[
+            SubcommandDoc {
+                name: "GET",
+                arguments: "[<count>]",
+                summary: "Return the latest slow storage calls, 10 by default, all if negative.",
+            },
+            SubcommandDoc {
+                name: "LEN",
+                arguments: "",
+                summary: "Return the number of slow storage calls in the log.",
+            },
+            SubcommandDoc {
+                name: "RESET",
+                arguments: "",
+                summary: "Empty the log of slow storage calls.",
+            },
+            HELP_DOC,
+        ],
+    },
+    CommandDoc {
+        name: "TIDIS.FORMAT",
+        subcommands: &[
+            SubcommandDoc {
+                name: "GET",
+                arguments: "",
+                summary:
+                    "Return the data format record of the namespace and the versions supported.",
+            },
+            SubcommandDoc {
+                name: "UPGRADE",
+                arguments: "",
+                summary: "Bump the data format record to the version of this binary once migrated.",
+            },
+            HELP_DOC,
+        ],
+    },
+    CommandDoc {
+        name: "TIDIS.SNAPSHOT",
+        subcommands: &[
+            SubcommandDoc {
+                name: "GET",
+                arguments: "",
+                summary: "Return the diagnostic snapshot of the instance as json.",
+            },
+            SubcommandDoc {
+                name: "SAVE",
+                arguments: "<path>",
+                summary:
+                    "Write the diagnostic snapshot to the file, if enable_debug_command allows.",
+            },
+            HELP_DOC,
+        ],
+    },
+];
+
+pub fn command_doc(command: &str) -> Option<&'static CommandDoc> {
+    COMMAND_DOCS
+        .iter()
+        .find(|doc| doc.name.eq_ignore_ascii_case(command))
+}
+
+/// Reply of `<COMMAND> HELP`, a header line followed by each subcommand with
+/// its arguments and the indented summary.
+pub fn resp_help(command: &str) -> Frame {
+    let doc = match command_doc(command) {
+        Some(doc) => doc,
+        None => return resp_array(vec![]),
+    };
+    let mut lines = vec![resp_str(&format!(
+        "{} <subcommand> [<arg> [value] [opt] ...]. Subcommands are:",
+        doc.name
+    ))];
+    for sub in doc.subcommands {
+        if sub.arguments.is_empty() {
+            lines.push(resp_sstr(sub.name));
+        } else {
+            lines.push(resp_str(&format!("{} {}", sub.name, sub.arguments)));
+        }
+        lines.push(resp_str(&format!("    {}", sub.summary)));
+    }
+    resp_array(lines)
+}
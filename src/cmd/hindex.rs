@@ -0,0 +1,137 @@
+use std::sync::Arc;
+
+use crate::cmd::{Invalid, Parse};
+use crate::config::is_use_txn_api;
+use crate::config_hash_indexes_or_default;
+use crate::tikv::errors::{AsyncResult, REDIS_NOT_SUPPORTED_ERR};
+use crate::tikv::index::IndexCommandCtx;
+use crate::utils::{resp_array, resp_bulk, resp_err, resp_invalid_arguments};
+use crate::{Connection, Frame};
+
+use crate::config::LOGGER;
+use crate::tikv::backend::Transaction;
+use bytes::Bytes;
+use slog::debug;
+use tokio::sync::Mutex;
+
+#[derive(Debug, Clone)]
+enum Subcommand {
+    Lookup {
+        prefix: String,
+        field: String,
+        value: Bytes,
+        count: usize,
+    },
+    List,
+}
+
+/// `HINDEX LOOKUP prefix field value [COUNT count]` replies with the hashes
+/// of an index declared in the config, `HINDEX LIST` with the declared
+/// indexes as prefix and field pairs.
+#[derive(Debug, Clone)]
+pub struct Hindex {
+    subcommand: Subcommand,
+    valid: bool,
+}
+
+impl Hindex {
+    pub(crate) fn parse_frames(parse: &mut Parse) -> crate::Result<Hindex> {
+        let mut args = vec![];
+        while let Ok(arg) = parse.next_bytes() {
+            args.push(arg);
+        }
+        Ok(Hindex::from_args(&args))
+    }
+
+    pub(crate) fn parse_argv(argv: &Vec<Bytes>) -> crate::Result<Hindex> {
+        Ok(Hindex::from_args(argv))
+    }
+
+    fn from_args(args: &[Bytes]) -> Hindex {
+        if args.is_empty() {
+            return Hindex::new_invalid();
+        }
+        let subcommand = match String::from_utf8_lossy(&args[0]).to_uppercase().as_str() {
+            "LOOKUP" if args.len() == 4 || args.len() == 6 => {
+                let mut count = 0;
+                if args.len() == 6 {
+                    if String::from_utf8_lossy(&args[4]).to_uppercase() != "COUNT" {
+                        return Hindex::new_invalid();
+                    }
+                    match String::from_utf8_lossy(&args[5]).parse::<usize>() {
+                        Ok(n) if n > 0 => count = n,
+                        _ => return Hindex::new_invalid(),
+                    }
+                }
+                Subcommand::Lookup {
+                    prefix: String::from_utf8_lossy(&args[1]).to_string(),
+                    field: String::from_utf8_lossy(&args[2]).to_string(),
+                    value: args[3].clone(),
+                    count,
+                }
+            }
+            "LIST" if args.len() == 1 => Subcommand::List,
+            _ => return Hindex::new_invalid(),
+        };
+        Hindex {
+            subcommand,
+            valid: true,
+        }
+    }
+
+    pub(crate) async fn apply(self, dst: &mut Connection) -> crate::Result<()> {
+        let response = self.hindex(None).await.unwrap_or_else(Into::into);
+        debug!(
+            LOGGER,
+            "res, {} -> {}, {:?}",
+            dst.local_addr(),
+            dst.peer_addr(),
+            response
+        );
+        dst.write_frame(&response).await?;
+
+        Ok(())
+    }
+
+    pub async fn hindex(&self, txn: Option<Arc<Mutex<Transaction>>>) -> AsyncResult<Frame> {
+        if !self.valid {
+            return Ok(resp_invalid_arguments());
+        }
+        match &self.subcommand {
+            Subcommand::List => {
+                let indexes = config_hash_indexes_or_default()
+                    .into_iter()
+                    .map(|rule| {
+                        resp_array(vec![
+                            resp_bulk(rule.prefix.into_bytes()),
+                            resp_bulk(rule.field.into_bytes()),
+                        ])
+                    })
+                    .collect();
+                Ok(resp_array(indexes))
+            }
+            Subcommand::Lookup {
+                prefix,
+                field,
+                value,
+                count,
+            } => {
+                if !is_use_txn_api() {
+                    return Ok(resp_err(REDIS_NOT_SUPPORTED_ERR));
+                }
+                IndexCommandCtx::new(txn)
+                    .do_async_txnkv_hindex_lookup(prefix, field, value, *count)
+                    .await
+            }
+        }
+    }
+}
+
+impl Invalid for Hindex {
+    fn new_invalid() -> Hindex {
+        Hindex {
+            subcommand: Subcommand::List,
+            valid: false,
+        }
+    }
+}
@@ -8,9 +8,10 @@ use crate::utils::{resp_err, resp_invalid_arguments};
 use crate::{Connection, Frame};
 
 use crate::config::LOGGER;
+use crate::tikv::backend::Transaction;
 use bytes::Bytes;
 use slog::debug;
-use tikv_client::{KvPair, Transaction};
+use tikv_client::KvPair;
 use tokio::sync::Mutex;
 
 #[derive(Debug, Clone)]
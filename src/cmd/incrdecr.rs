@@ -8,9 +8,9 @@ use crate::{Connection, Frame, Parse};
 
 use crate::cmd::Invalid;
 use crate::config::LOGGER;
+use crate::tikv::backend::Transaction;
 use bytes::Bytes;
 use slog::debug;
-use tikv_client::Transaction;
 use tokio::sync::Mutex;
 
 #[derive(Debug, Clone)]
@@ -0,0 +1,102 @@
+use std::sync::Arc;
+
+use crate::cmd::{Invalid, Parse};
+use crate::config::is_use_txn_api;
+use crate::jsonpath::JsonPath;
+use crate::tikv::errors::{AsyncResult, REDIS_JSON_INVALID_PATH_ERR, REDIS_NOT_SUPPORTED_ERR};
+use crate::tikv::json::JsonCommandCtx;
+use crate::utils::{resp_err, resp_invalid_arguments};
+use crate::{Connection, Frame};
+
+use crate::config::LOGGER;
+use crate::tikv::backend::Transaction;
+use bytes::Bytes;
+use slog::debug;
+use tokio::sync::Mutex;
+
+/// `JSON.DEL key [path]`, the whole key with no path
+#[derive(Debug, Clone)]
+pub struct Jsondel {
+    key: String,
+    path: String,
+    valid: bool,
+}
+
+impl Jsondel {
+    pub fn new(key: &str, path: &str) -> Jsondel {
+        Jsondel {
+            key: key.to_owned(),
+            path: path.to_owned(),
+            valid: true,
+        }
+    }
+
+    /// Get the key
+    pub fn key(&self) -> &str {
+        &self.key
+    }
+
+    pub(crate) fn parse_frames(parse: &mut Parse) -> crate::Result<Jsondel> {
+        let mut args = vec![];
+        while let Ok(arg) = parse.next_bytes() {
+            args.push(arg);
+        }
+        Ok(Jsondel::from_args(&args))
+    }
+
+    pub(crate) fn parse_argv(argv: &Vec<Bytes>) -> crate::Result<Jsondel> {
+        Ok(Jsondel::from_args(argv))
+    }
+
+    fn from_args(args: &[Bytes]) -> Jsondel {
+        match args.len() {
+            1 => Jsondel::new(&String::from_utf8_lossy(&args[0]), "."),
+            2 => Jsondel::new(
+                &String::from_utf8_lossy(&args[0]),
+                &String::from_utf8_lossy(&args[1]),
+            ),
+            _ => Jsondel::new_invalid(),
+        }
+    }
+
+    pub(crate) async fn apply(self, dst: &mut Connection) -> crate::Result<()> {
+        let response = self.json_del(None).await.unwrap_or_else(Into::into);
+        debug!(
+            LOGGER,
+            "res, {} -> {}, {:?}",
+            dst.local_addr(),
+            dst.peer_addr(),
+            response
+        );
+        dst.write_frame(&response).await?;
+
+        Ok(())
+    }
+
+    pub async fn json_del(&self, txn: Option<Arc<Mutex<Transaction>>>) -> AsyncResult<Frame> {
+        if !self.valid {
+            return Ok(resp_invalid_arguments());
+        }
+        let path = match JsonPath::parse(&self.path) {
+            Some(path) => path,
+            None => return Ok(resp_err(REDIS_JSON_INVALID_PATH_ERR)),
+        };
+        if is_use_txn_api() {
+            JsonCommandCtx::new(txn)
+                .do_async_txnkv_json_del(&self.key, path)
+                .await
+        } else {
+            Ok(resp_err(REDIS_NOT_SUPPORTED_ERR))
+        }
+    }
+}
+
+impl Invalid for Jsondel {
+    fn new_invalid() -> Jsondel {
+        Jsondel {
+            key: "".to_owned(),
+            path: "".to_owned(),
+            valid: false,
+        }
+    }
+}
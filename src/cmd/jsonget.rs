@@ -0,0 +1,111 @@
+use std::sync::Arc;
+
+use crate::cmd::{Invalid, Parse};
+use crate::config::is_use_txn_api;
+use crate::jsonpath::JsonPath;
+use crate::tikv::errors::{AsyncResult, REDIS_JSON_INVALID_PATH_ERR, REDIS_NOT_SUPPORTED_ERR};
+use crate::tikv::json::JsonCommandCtx;
+use crate::utils::{resp_err, resp_invalid_arguments};
+use crate::{Connection, Frame};
+
+use crate::config::LOGGER;
+use crate::tikv::backend::Transaction;
+use bytes::Bytes;
+use slog::debug;
+use tokio::sync::Mutex;
+
+/// `JSON.GET key [path ...]`, the root with no path. The result is always
+/// compact as the INDENT, NEWLINE and SPACE options are not supported.
+#[derive(Debug, Clone)]
+pub struct Jsonget {
+    key: String,
+    paths: Vec<String>,
+    valid: bool,
+}
+
+impl Jsonget {
+    pub fn new(key: &str, paths: Vec<String>) -> Jsonget {
+        Jsonget {
+            key: key.to_owned(),
+            paths,
+            valid: true,
+        }
+    }
+
+    /// Get the key
+    pub fn key(&self) -> &str {
+        &self.key
+    }
+
+    pub(crate) fn parse_frames(parse: &mut Parse) -> crate::Result<Jsonget> {
+        let mut args = vec![];
+        while let Ok(arg) = parse.next_bytes() {
+            args.push(arg);
+        }
+        Ok(Jsonget::from_args(&args))
+    }
+
+    pub(crate) fn parse_argv(argv: &Vec<Bytes>) -> crate::Result<Jsonget> {
+        Ok(Jsonget::from_args(argv))
+    }
+
+    fn from_args(args: &[Bytes]) -> Jsonget {
+        if args.is_empty() {
+            return Jsonget::new_invalid();
+        }
+        let paths = args[1..]
+            .iter()
+            .map(|p| String::from_utf8_lossy(p).to_string())
+            .collect();
+        Jsonget::new(&String::from_utf8_lossy(&args[0]), paths)
+    }
+
+    pub(crate) async fn apply(self, dst: &mut Connection) -> crate::Result<()> {
+        let response = self.json_get(None).await.unwrap_or_else(Into::into);
+        debug!(
+            LOGGER,
+            "res, {} -> {}, {:?}",
+            dst.local_addr(),
+            dst.peer_addr(),
+            response
+        );
+        dst.write_frame(&response).await?;
+
+        Ok(())
+    }
+
+    pub async fn json_get(&self, txn: Option<Arc<Mutex<Transaction>>>) -> AsyncResult<Frame> {
+        if !self.valid {
+            return Ok(resp_invalid_arguments());
+        }
+        let names = if self.paths.is_empty() {
+            vec![".".to_owned()]
+        } else {
+            self.paths.clone()
+        };
+        let mut paths = Vec::with_capacity(names.len());
+        for name in names {
+            match JsonPath::parse(&name) {
+                Some(path) => paths.push((name, path)),
+                None => return Ok(resp_err(REDIS_JSON_INVALID_PATH_ERR)),
+            }
+        }
+        if is_use_txn_api() {
+            JsonCommandCtx::new(txn)
+                .do_async_txnkv_json_get(&self.key, paths)
+                .await
+        } else {
+            Ok(resp_err(REDIS_NOT_SUPPORTED_ERR))
+        }
+    }
+}
+
+impl Invalid for Jsonget {
+    fn new_invalid() -> Jsonget {
+        Jsonget {
+            key: "".to_owned(),
+            paths: vec![],
+            valid: false,
+        }
+    }
+}
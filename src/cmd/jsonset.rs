@@ -0,0 +1,123 @@
+use std::sync::Arc;
+
+use crate::cmd::{Invalid, Parse};
+use crate::config::is_use_txn_api;
+use crate::jsonpath::{JsonPath, SetMode};
+use crate::tikv::errors::{
+    AsyncResult, RTError, REDIS_JSON_INVALID_PATH_ERR, REDIS_NOT_SUPPORTED_ERR,
+};
+use crate::tikv::json::JsonCommandCtx;
+use crate::utils::{resp_err, resp_invalid_arguments};
+use crate::{Connection, Frame};
+
+use crate::config::LOGGER;
+use crate::tikv::backend::Transaction;
+use bytes::Bytes;
+use slog::debug;
+use tokio::sync::Mutex;
+
+#[derive(Debug, Clone)]
+pub struct Jsonset {
+    key: String,
+    path: String,
+    value: Bytes,
+    mode: SetMode,
+    valid: bool,
+}
+
+impl Jsonset {
+    pub fn new(key: &str, path: &str, value: Bytes, mode: SetMode) -> Jsonset {
+        Jsonset {
+            key: key.to_owned(),
+            path: path.to_owned(),
+            value,
+            mode,
+            valid: true,
+        }
+    }
+
+    /// Get the key
+    pub fn key(&self) -> &str {
+        &self.key
+    }
+
+    pub(crate) fn parse_frames(parse: &mut Parse) -> crate::Result<Jsonset> {
+        let mut args = vec![];
+        while let Ok(arg) = parse.next_bytes() {
+            args.push(arg);
+        }
+        Ok(Jsonset::from_args(&args))
+    }
+
+    pub(crate) fn parse_argv(argv: &Vec<Bytes>) -> crate::Result<Jsonset> {
+        Ok(Jsonset::from_args(argv))
+    }
+
+    /// Parse `key path value [NX|XX]`
+    fn from_args(args: &[Bytes]) -> Jsonset {
+        if args.len() != 3 && args.len() != 4 {
+            return Jsonset::new_invalid();
+        }
+        let mode = match args.get(3) {
+            None => SetMode::Always,
+            Some(option) => match String::from_utf8_lossy(option).to_uppercase().as_str() {
+                "NX" => SetMode::Missing,
+                "XX" => SetMode::Existing,
+                _ => return Jsonset::new_invalid(),
+            },
+        };
+        Jsonset::new(
+            &String::from_utf8_lossy(&args[0]),
+            &String::from_utf8_lossy(&args[1]),
+            args[2].clone(),
+            mode,
+        )
+    }
+
+    pub(crate) async fn apply(self, dst: &mut Connection) -> crate::Result<()> {
+        let response = self.json_set(None).await.unwrap_or_else(Into::into);
+        debug!(
+            LOGGER,
+            "res, {} -> {}, {:?}",
+            dst.local_addr(),
+            dst.peer_addr(),
+            response
+        );
+        dst.write_frame(&response).await?;
+
+        Ok(())
+    }
+
+    pub async fn json_set(&self, txn: Option<Arc<Mutex<Transaction>>>) -> AsyncResult<Frame> {
+        if !self.valid {
+            return Ok(resp_invalid_arguments());
+        }
+        let path = match JsonPath::parse(&self.path) {
+            Some(path) => path,
+            None => return Ok(resp_err(REDIS_JSON_INVALID_PATH_ERR)),
+        };
+        let value = match serde_json::from_slice(&self.value) {
+            Ok(value) => value,
+            Err(e) => return Ok(resp_err(RTError::from(e))),
+        };
+        if is_use_txn_api() {
+            JsonCommandCtx::new(txn)
+                .do_async_txnkv_json_set(&self.key, path, value, self.mode)
+                .await
+        } else {
+            Ok(resp_err(REDIS_NOT_SUPPORTED_ERR))
+        }
+    }
+}
+
+impl Invalid for Jsonset {
+    fn new_invalid() -> Jsonset {
+        Jsonset {
+            key: "".to_owned(),
+            path: "".to_owned(),
+            value: Bytes::new(),
+            mode: SetMode::Always,
+            valid: false,
+        }
+    }
+}
@@ -0,0 +1,185 @@
+use std::sync::Arc;
+
+use crate::blocking::{block_on_keys, MAX_BLOCK_TIMEOUT_SECS};
+use crate::cmd::{Invalid, Parse};
+use crate::config::is_use_txn_api;
+use crate::tikv::errors::{
+    AsyncResult, REDIS_COUNT_NOT_POSITIVE_ERR, REDIS_NOT_SUPPORTED_ERR, REDIS_NUMKEYS_ERR,
+    REDIS_TIMEOUT_NEGATIVE_ERR, REDIS_TIMEOUT_OUT_OF_RANGE_ERR,
+};
+use crate::tikv::list::ListCommandCtx;
+use crate::utils::{resp_err, resp_invalid_arguments};
+use crate::{Connection, Frame, Shutdown};
+
+use crate::config::LOGGER;
+use crate::tikv::backend::Transaction;
+use bytes::Bytes;
+use slog::debug;
+use tokio::sync::Mutex;
+
+/// `LMPOP numkeys key [key ...] LEFT|RIGHT [COUNT count]` and
+/// `BLMPOP timeout numkeys key [key ...] LEFT|RIGHT [COUNT count]`, pop from
+/// the first non empty list of the keys. BLMPOP waits up to `timeout`
+/// seconds, 0 for ever, for a list to be pushed to, see `crate::blocking`.
+#[derive(Debug, Clone)]
+pub struct Lmpop {
+    keys: Vec<String>,
+    left: bool,
+    count: i64,
+    numkeys: i64,
+    /// The timeout of BLMPOP, in seconds
+    timeout: Option<f64>,
+    /// The timeout of BLMPOP is over `MAX_BLOCK_TIMEOUT_SECS`
+    timeout_out_of_range: bool,
+    valid: bool,
+}
+
+impl Lmpop {
+    pub fn keys(&self) -> &Vec<String> {
+        &self.keys
+    }
+
+    pub(crate) fn parse_frames(parse: &mut Parse, blocking: bool) -> crate::Result<Lmpop> {
+        let mut args = vec![];
+        while let Ok(arg) = parse.next_bytes() {
+            args.push(arg);
+        }
+        Ok(Lmpop::from_args(&args, blocking))
+    }
+
+    pub(crate) fn parse_argv(argv: &Vec<Bytes>, blocking: bool) -> crate::Result<Lmpop> {
+        Ok(Lmpop::from_args(argv, blocking))
+    }
+
+    fn from_args(args: &[Bytes], blocking: bool) -> Lmpop {
+        let mut args = args
+            .iter()
+            .map(|arg| String::from_utf8_lossy(arg).to_string());
+        let mut timeout_out_of_range = false;
+        let timeout = if blocking {
+            match args.next().map(|arg| arg.parse::<f64>()) {
+                Some(Ok(timeout)) if timeout.is_finite() && timeout <= MAX_BLOCK_TIMEOUT_SECS => {
+                    Some(timeout)
+                }
+                Some(Ok(timeout)) if timeout.is_finite() => {
+                    // replied as such by lmpop
+                    timeout_out_of_range = true;
+                    None
+                }
+                _ => return Lmpop::new_invalid(),
+            }
+        } else {
+            None
+        };
+        let numkeys = match args.next().map(|arg| arg.parse::<i64>()) {
+            Some(Ok(numkeys)) => numkeys,
+            _ => return Lmpop::new_invalid(),
+        };
+        let mut lmpop = Lmpop {
+            keys: vec![],
+            left: true,
+            count: 1,
+            numkeys,
+            timeout,
+            timeout_out_of_range,
+            valid: true,
+        };
+        if numkeys <= 0 {
+            // replied as such by lmpop
+            return lmpop;
+        }
+        for _ in 0..numkeys {
+            match args.next() {
+                Some(key) => lmpop.keys.push(key),
+                None => return Lmpop::new_invalid(),
+            }
+        }
+        lmpop.left = match args.next().map(|arg| arg.to_uppercase()).as_deref() {
+            Some("LEFT") => true,
+            Some("RIGHT") => false,
+            _ => return Lmpop::new_invalid(),
+        };
+        match args.next() {
+            Some(arg) if arg.eq_ignore_ascii_case("COUNT") => {
+                match args.next().map(|arg| arg.parse::<i64>()) {
+                    Some(Ok(count)) => lmpop.count = count,
+                    _ => return Lmpop::new_invalid(),
+                }
+            }
+            Some(_) => return Lmpop::new_invalid(),
+            None => {}
+        }
+        if args.next().is_some() {
+            return Lmpop::new_invalid();
+        }
+        lmpop
+    }
+
+    pub(crate) async fn apply(
+        self,
+        dst: &mut Connection,
+        shutdown: &mut Shutdown,
+    ) -> crate::Result<()> {
+        let response = match self.timeout {
+            Some(timeout) if self.valid && self.numkeys > 0 && self.count > 0 && timeout >= 0.0 => {
+                let pop = || async { self.lmpop(None).await.unwrap_or_else(Into::into) };
+                match block_on_keys(&self.keys, timeout, shutdown, pop).await {
+                    Some(response) => response,
+                    // the server is shutting down
+                    None => return Ok(()),
+                }
+            }
+            _ => self.lmpop(None).await.unwrap_or_else(Into::into),
+        };
+        debug!(
+            LOGGER,
+            "res, {} -> {}, {:?}",
+            dst.local_addr(),
+            dst.peer_addr(),
+            response
+        );
+        dst.write_frame(&response).await?;
+
+        Ok(())
+    }
+
+    /// Pop once without blocking, BLMPOP in a transaction or a script does
+    /// not wait like Redis
+    pub async fn lmpop(&self, txn: Option<Arc<Mutex<Transaction>>>) -> AsyncResult<Frame> {
+        if !self.valid {
+            return Ok(resp_invalid_arguments());
+        }
+        if self.timeout_out_of_range {
+            return Ok(resp_err(REDIS_TIMEOUT_OUT_OF_RANGE_ERR));
+        }
+        if self.numkeys <= 0 {
+            return Ok(resp_err(REDIS_NUMKEYS_ERR));
+        }
+        if self.count <= 0 {
+            return Ok(resp_err(REDIS_COUNT_NOT_POSITIVE_ERR));
+        }
+        if matches!(self.timeout, Some(timeout) if timeout < 0.0) {
+            return Ok(resp_err(REDIS_TIMEOUT_NEGATIVE_ERR));
+        }
+        if !is_use_txn_api() {
+            return Ok(resp_err(REDIS_NOT_SUPPORTED_ERR));
+        }
+        ListCommandCtx::new(txn)
+            .do_async_txnkv_mpop(&self.keys, self.left, self.count)
+            .await
+    }
+}
+
+impl Invalid for Lmpop {
+    fn new_invalid() -> Lmpop {
+        Lmpop {
+            keys: vec![],
+            left: true,
+            count: 0,
+            numkeys: 0,
+            timeout: None,
+            timeout_out_of_range: false,
+            valid: false,
+        }
+    }
+}
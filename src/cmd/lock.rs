@@ -0,0 +1,132 @@
+use std::sync::Arc;
+
+use crate::cmd::{Invalid, Parse};
+use crate::config::is_use_txn_api;
+use crate::tikv::errors::{AsyncResult, REDIS_NOT_SUPPORTED_ERR};
+use crate::tikv::lock::LockCommandCtx;
+use crate::utils::{resp_err, resp_invalid_arguments};
+use crate::{Connection, Frame};
+
+use crate::config::LOGGER;
+use crate::tikv::backend::Transaction;
+use bytes::Bytes;
+use slog::debug;
+use tokio::sync::Mutex;
+
+/// `LOCK name owner ttl`, `EXTEND name owner ttl` and `UNLOCK name owner`,
+/// the ttl is in milliseconds and the lock is released by its owner only.
+#[derive(Debug, Clone)]
+pub struct Lock {
+    name: String,
+    owner: String,
+    ttl: u64,
+    valid: bool,
+}
+
+impl Lock {
+    pub fn new(name: &str, owner: &str, ttl: u64) -> Lock {
+        Lock {
+            name: name.to_string(),
+            owner: owner.to_string(),
+            ttl,
+            valid: true,
+        }
+    }
+
+    pub(crate) fn parse_frames(parse: &mut Parse, with_ttl: bool) -> crate::Result<Lock> {
+        let name = parse.next_string()?;
+        let owner = parse.next_string()?;
+        if !with_ttl {
+            return Ok(Lock::new(&name, &owner, 0));
+        }
+        let ttl = parse.next_int()?;
+        if ttl <= 0 {
+            return Ok(Lock::new_invalid());
+        }
+        Ok(Lock::new(&name, &owner, ttl as u64))
+    }
+
+    pub(crate) fn parse_argv(argv: &Vec<Bytes>, with_ttl: bool) -> crate::Result<Lock> {
+        if (with_ttl && argv.len() != 3) || (!with_ttl && argv.len() != 2) {
+            return Ok(Lock::new_invalid());
+        }
+        let name = String::from_utf8_lossy(&argv[0]);
+        let owner = String::from_utf8_lossy(&argv[1]);
+        if !with_ttl {
+            return Ok(Lock::new(&name, &owner, 0));
+        }
+        match String::from_utf8_lossy(&argv[2]).parse::<i64>() {
+            Ok(ttl) if ttl > 0 => Ok(Lock::new(&name, &owner, ttl as u64)),
+            _ => Ok(Lock::new_invalid()),
+        }
+    }
+
+    pub(crate) async fn apply(self, dst: &mut Connection, cmd: &str) -> crate::Result<()> {
+        let response = match cmd {
+            "lock" => self.lock(None).await,
+            "extend" => self.extend(None).await,
+            _ => self.unlock(None).await,
+        }
+        .unwrap_or_else(Into::into);
+        debug!(
+            LOGGER,
+            "res, {} -> {}, {:?}",
+            dst.local_addr(),
+            dst.peer_addr(),
+            response
+        );
+        dst.write_frame(&response).await?;
+
+        Ok(())
+    }
+
+    pub async fn lock(&self, txn: Option<Arc<Mutex<Transaction>>>) -> AsyncResult<Frame> {
+        if !self.valid {
+            return Ok(resp_invalid_arguments());
+        }
+        if is_use_txn_api() {
+            LockCommandCtx::new(txn)
+                .do_async_txnkv_lock(&self.name, &self.owner, self.ttl)
+                .await
+        } else {
+            Ok(resp_err(REDIS_NOT_SUPPORTED_ERR))
+        }
+    }
+
+    pub async fn extend(&self, txn: Option<Arc<Mutex<Transaction>>>) -> AsyncResult<Frame> {
+        if !self.valid {
+            return Ok(resp_invalid_arguments());
+        }
+        if is_use_txn_api() {
+            LockCommandCtx::new(txn)
+                .do_async_txnkv_extend(&self.name, &self.owner, self.ttl)
+                .await
+        } else {
+            Ok(resp_err(REDIS_NOT_SUPPORTED_ERR))
+        }
+    }
+
+    pub async fn unlock(&self, txn: Option<Arc<Mutex<Transaction>>>) -> AsyncResult<Frame> {
+        if !self.valid {
+            return Ok(resp_invalid_arguments());
+        }
+        if is_use_txn_api() {
+            LockCommandCtx::new(txn)
+                .do_async_txnkv_unlock(&self.name, &self.owner)
+                .await
+        } else {
+            Ok(resp_err(REDIS_NOT_SUPPORTED_ERR))
+        }
+    }
+}
+
+impl Invalid for Lock {
+    fn new_invalid() -> Lock {
+        Lock {
+            name: "".to_string(),
+            owner: "".to_string(),
+            ttl: 0,
+            valid: false,
+        }
+    }
+}
@@ -0,0 +1,92 @@
+use crate::cmd::Invalid;
+use crate::config::LOGGER;
+use crate::utils::{resp_bulk, resp_invalid_arguments};
+use crate::{Connection, Frame, Parse, ParseError};
+use slog::debug;
+
+const DEFAULT_COLUMNS: i64 = 32;
+const MAX_COLUMNS: i64 = 256;
+
+/// Returns a small piece of generative art followed by the tidis version.
+///
+/// ```text
+/// LOLWUT [VERSION version] [columns]
+/// ```
+#[derive(Debug, Clone)]
+pub struct Lolwut {
+    columns: i64,
+    valid: bool,
+}
+
+impl Lolwut {
+    pub fn new(columns: i64) -> Lolwut {
+        Lolwut {
+            columns,
+            valid: true,
+        }
+    }
+
+    pub(crate) fn parse_frames(parse: &mut Parse) -> crate::Result<Lolwut> {
+        let mut columns = DEFAULT_COLUMNS;
+        loop {
+            match parse.next_string() {
+                Ok(arg) if arg.to_uppercase() == "VERSION" => {
+                    // only one version of the art, the number is accepted for compatibility
+                    if parse.next_int().is_err() {
+                        return Ok(Lolwut::new_invalid());
+                    }
+                }
+                Ok(arg) => match arg.parse::<i64>() {
+                    Ok(c) if c > 0 => columns = c.min(MAX_COLUMNS),
+                    _ => return Ok(Lolwut::new_invalid()),
+                },
+                Err(ParseError::EndOfStream) => break,
+                Err(e) => return Err(e.into()),
+            }
+        }
+        Ok(Lolwut::new(columns))
+    }
+
+    pub(crate) async fn apply(self, dst: &mut Connection) -> crate::Result<()> {
+        let response = self.lolwut();
+
+        debug!(
+            LOGGER,
+            "res, {} -> {}, {:?}",
+            dst.local_addr(),
+            dst.peer_addr(),
+            response
+        );
+
+        dst.write_frame(&response).await?;
+
+        Ok(())
+    }
+
+    fn lolwut(&self) -> Frame {
+        if !self.valid {
+            return resp_invalid_arguments();
+        }
+        // rows of blocks shading from dense to light, shifted by one each row
+        let shades = ['█', '▓', '▒', '░', ' '];
+        let mut art = String::new();
+        for row in 0..8 {
+            for col in 0..self.columns {
+                let idx = ((col / 4 + row) % shades.len() as i64) as usize;
+                art.push(shades[idx]);
+            }
+            art.push('\n');
+        }
+        art.push_str(&format!("\nTidis ver. {}\n", env!("CARGO_PKG_VERSION")));
+        resp_bulk(art.into_bytes())
+    }
+}
+
+impl Invalid for Lolwut {
+    fn new_invalid() -> Lolwut {
+        Lolwut {
+            columns: 0,
+            valid: false,
+        }
+    }
+}
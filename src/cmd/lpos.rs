@@ -0,0 +1,129 @@
+use std::sync::Arc;
+
+use crate::cmd::{Invalid, Parse};
+use crate::config::is_use_txn_api;
+use crate::tikv::errors::{
+    AsyncResult, REDIS_LPOS_COUNT_ERR, REDIS_LPOS_MAXLEN_ERR, REDIS_LPOS_RANK_ERR,
+    REDIS_NOT_SUPPORTED_ERR,
+};
+use crate::tikv::list::ListCommandCtx;
+use crate::utils::{resp_err, resp_invalid_arguments};
+use crate::{Connection, Frame};
+
+use crate::config::LOGGER;
+use crate::tikv::backend::Transaction;
+use bytes::Bytes;
+use slog::debug;
+use tokio::sync::Mutex;
+
+/// `LPOS key element [RANK rank] [COUNT num-matches] [MAXLEN len]`, the
+/// index of the matching elements of the list.
+#[derive(Debug, Clone)]
+pub struct Lpos {
+    key: String,
+    element: Bytes,
+    rank: i64,
+    count: Option<i64>,
+    maxlen: i64,
+    valid: bool,
+}
+
+impl Lpos {
+    pub fn key(&self) -> &str {
+        &self.key
+    }
+
+    pub(crate) fn parse_frames(parse: &mut Parse) -> crate::Result<Lpos> {
+        let mut args = vec![];
+        while let Ok(arg) = parse.next_bytes() {
+            args.push(arg);
+        }
+        Ok(Lpos::from_args(&args))
+    }
+
+    pub(crate) fn parse_argv(argv: &Vec<Bytes>) -> crate::Result<Lpos> {
+        Ok(Lpos::from_args(argv))
+    }
+
+    fn from_args(args: &[Bytes]) -> Lpos {
+        if args.len() < 2 || args.len() % 2 != 0 {
+            return Lpos::new_invalid();
+        }
+        let mut lpos = Lpos {
+            key: String::from_utf8_lossy(&args[0]).to_string(),
+            element: args[1].clone(),
+            rank: 1,
+            count: None,
+            maxlen: 0,
+            valid: true,
+        };
+        for option in args[2..].chunks(2) {
+            let value = match String::from_utf8_lossy(&option[1]).parse::<i64>() {
+                Ok(value) => value,
+                Err(_) => return Lpos::new_invalid(),
+            };
+            match String::from_utf8_lossy(&option[0]).to_uppercase().as_str() {
+                "RANK" => lpos.rank = value,
+                "COUNT" => lpos.count = Some(value),
+                "MAXLEN" => lpos.maxlen = value,
+                _ => return Lpos::new_invalid(),
+            }
+        }
+        lpos
+    }
+
+    pub(crate) async fn apply(self, dst: &mut Connection) -> crate::Result<()> {
+        let response = self.lpos(None).await.unwrap_or_else(Into::into);
+        debug!(
+            LOGGER,
+            "res, {} -> {}, {:?}",
+            dst.local_addr(),
+            dst.peer_addr(),
+            response
+        );
+        dst.write_frame(&response).await?;
+
+        Ok(())
+    }
+
+    pub async fn lpos(&self, txn: Option<Arc<Mutex<Transaction>>>) -> AsyncResult<Frame> {
+        if !self.valid {
+            return Ok(resp_invalid_arguments());
+        }
+        if self.rank == 0 {
+            return Ok(resp_err(REDIS_LPOS_RANK_ERR));
+        }
+        if self.count.map_or(false, |count| count < 0) {
+            return Ok(resp_err(REDIS_LPOS_COUNT_ERR));
+        }
+        if self.maxlen < 0 {
+            return Ok(resp_err(REDIS_LPOS_MAXLEN_ERR));
+        }
+        if is_use_txn_api() {
+            ListCommandCtx::new(txn)
+                .do_async_txnkv_lpos(
+                    &self.key,
+                    &self.element,
+                    self.rank,
+                    self.count.map(|count| count as usize),
+                    self.maxlen as usize,
+                )
+                .await
+        } else {
+            Ok(resp_err(REDIS_NOT_SUPPORTED_ERR))
+        }
+    }
+}
+
+impl Invalid for Lpos {
+    fn new_invalid() -> Lpos {
+        Lpos {
+            key: "".to_string(),
+            element: Bytes::new(),
+            rank: 1,
+            count: None,
+            maxlen: 0,
+            valid: false,
+        }
+    }
+}
@@ -0,0 +1,153 @@
+use std::sync::Arc;
+
+use crate::cmd::{Invalid, Parse};
+use crate::config::is_use_txn_api;
+use crate::tikv::errors::{AsyncResult, REDIS_NOT_SUPPORTED_ERR};
+use crate::tikv::migrate::{MigrateCommandCtx, MigrateTarget};
+use crate::utils::{resp_err, resp_invalid_arguments};
+use crate::{Connection, Frame};
+
+use crate::config::LOGGER;
+use crate::tikv::backend::Transaction;
+use bytes::Bytes;
+use slog::debug;
+use tokio::sync::Mutex;
+
+/// `MIGRATE host port key|"" destination-db timeout [COPY] [REPLACE] [AUTH
+/// password] [AUTH2 username password] [KEYS key ...]`, the keys are sent
+/// with DUMP and RESTORE, see `crate::tikv::migrate`.
+#[derive(Debug, Clone)]
+pub struct Migrate {
+    target: MigrateTarget,
+    keys: Vec<String>,
+    copy: bool,
+    replace: bool,
+    valid: bool,
+}
+
+impl Migrate {
+    pub fn keys(&self) -> &Vec<String> {
+        &self.keys
+    }
+
+    pub fn copy(&self) -> bool {
+        self.copy
+    }
+
+    pub(crate) fn parse_frames(parse: &mut Parse) -> crate::Result<Migrate> {
+        let mut args = vec![];
+        while let Ok(arg) = parse.next_bytes() {
+            args.push(arg);
+        }
+        Ok(Migrate::from_args(&args))
+    }
+
+    pub(crate) fn parse_argv(argv: &Vec<Bytes>) -> crate::Result<Migrate> {
+        Ok(Migrate::from_args(argv))
+    }
+
+    fn from_args(args: &[Bytes]) -> Migrate {
+        if args.len() < 5 {
+            return Migrate::new_invalid();
+        }
+        let arg = |idx: usize| String::from_utf8_lossy(&args[idx]).to_string();
+        let (port, db, timeout) = match (
+            arg(1).parse::<u16>(),
+            arg(3).parse::<i64>(),
+            arg(4).parse::<u64>(),
+        ) {
+            (Ok(port), Ok(db), Ok(timeout)) => (port, db, timeout),
+            _ => return Migrate::new_invalid(),
+        };
+        let mut target = MigrateTarget {
+            host: arg(0),
+            port,
+            db,
+            timeout,
+            auth: None,
+        };
+        let mut keys = vec![];
+        let mut copy = false;
+        let mut replace = false;
+        let mut idx = 5;
+        while idx < args.len() {
+            match arg(idx).to_uppercase().as_str() {
+                "COPY" => copy = true,
+                "REPLACE" => replace = true,
+                "AUTH" if idx + 1 < args.len() => {
+                    target.auth = Some((None, arg(idx + 1)));
+                    idx += 1;
+                }
+                "AUTH2" if idx + 2 < args.len() => {
+                    target.auth = Some((Some(arg(idx + 1)), arg(idx + 2)));
+                    idx += 2;
+                }
+                // the key argument must be empty with KEYS
+                "KEYS" if args[2].is_empty() && idx + 1 < args.len() => {
+                    keys = (idx + 1..args.len()).map(arg).collect();
+                    break;
+                }
+                _ => return Migrate::new_invalid(),
+            }
+            idx += 1;
+        }
+        if keys.is_empty() {
+            if args[2].is_empty() {
+                return Migrate::new_invalid();
+            }
+            keys.push(arg(2));
+        }
+        Migrate {
+            target,
+            keys,
+            copy,
+            replace,
+            valid: true,
+        }
+    }
+
+    pub(crate) async fn apply(self, dst: &mut Connection) -> crate::Result<()> {
+        let response = self.migrate(None).await.unwrap_or_else(Into::into);
+        debug!(
+            LOGGER,
+            "res, {} -> {}, {:?}",
+            dst.local_addr(),
+            dst.peer_addr(),
+            response
+        );
+        dst.write_frame(&response).await?;
+
+        Ok(())
+    }
+
+    pub async fn migrate(&self, txn: Option<Arc<Mutex<Transaction>>>) -> AsyncResult<Frame> {
+        if !self.valid {
+            return Ok(resp_invalid_arguments());
+        }
+        if is_use_txn_api() {
+            MigrateCommandCtx::new(txn)
+                .do_async_txnkv_migrate(&self.target, &self.keys, self.copy, self.replace)
+                .await
+        } else {
+            Ok(resp_err(REDIS_NOT_SUPPORTED_ERR))
+        }
+    }
+}
+
+impl Invalid for Migrate {
+    fn new_invalid() -> Migrate {
+        Migrate {
+            target: MigrateTarget {
+                host: "".to_owned(),
+                port: 0,
+                db: 0,
+                timeout: 0,
+                auth: None,
+            },
+            keys: vec![],
+            copy: false,
+            replace: false,
+            valid: false,
+        }
+    }
+}
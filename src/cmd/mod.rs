@@ -19,6 +19,58 @@ use tokio::sync::Mutex;
 mod strlen;
 pub use strlen::Strlen;
 
+mod getrange;
+pub use getrange::Getrange;
+
+mod append;
+pub use append::Append;
+
+mod setbit;
+pub use setbit::Setbit;
+
+mod getbit;
+pub use getbit::Getbit;
+
+mod bitcount;
+pub use bitcount::Bitcount;
+
+mod bitpos;
+pub use bitpos::Bitpos;
+
+mod bitop;
+pub use bitop::Bitop;
+
+mod bitfield;
+pub use bitfield::Bitfield;
+mod sort;
+pub use sort::Sort;
+mod object;
+pub use object::Object;
+mod randomkey;
+pub use randomkey::Randomkey;
+mod copy;
+pub use copy::CopyKey;
+mod movekey;
+pub use movekey::MoveKey;
+mod dump;
+pub use dump::Dump;
+mod restore;
+pub use restore::Restore;
+mod migrate;
+pub use migrate::Migrate;
+mod getdel;
+pub use getdel::Getdel;
+mod getex;
+pub use getex::Getex;
+mod setrange;
+pub use setrange::Setrange;
+
+mod throttle;
+pub use throttle::Throttle;
+
+mod lock;
+pub use lock::Lock;
+
 mod publish;
 pub use publish::Publish;
 
@@ -38,11 +90,14 @@ mod cmdtype;
 pub use cmdtype::Type;
 
 mod subscribe;
-pub use subscribe::{Subscribe, Unsubscribe};
+pub use subscribe::{Psubscribe, Punsubscribe, Subscribe, Unsubscribe};
 
 mod ping;
 pub use ping::Ping;
 
+mod lolwut;
+pub use lolwut::Lolwut;
+
 mod expire;
 pub use expire::Expire;
 
@@ -103,6 +158,12 @@ pub use llen::Llen;
 mod lindex;
 pub use lindex::Lindex;
 
+mod lpos;
+pub use lpos::Lpos;
+
+mod lmpop;
+pub use lmpop::Lmpop;
+
 mod lset;
 pub use lset::Lset;
 
@@ -139,9 +200,18 @@ pub use srandmember::Srandmember;
 mod spop;
 pub use spop::Spop;
 
+mod sdrain;
+pub use sdrain::Sdrain;
+
 mod srem;
 pub use srem::Srem;
 
+mod sinter;
+pub use sinter::Sinter;
+
+mod sintercard;
+pub use sintercard::Sintercard;
+
 mod zadd;
 pub use zadd::Zadd;
 
@@ -151,6 +221,9 @@ pub use zcard::Zcard;
 mod zscore;
 pub use zscore::Zscore;
 
+mod zmscore;
+pub use zmscore::Zmscore;
+
 mod zrem;
 pub use zrem::Zrem;
 
@@ -169,12 +242,74 @@ pub use zrevrange::Zrevrange;
 mod zrangebyscore;
 pub use zrangebyscore::Zrangebyscore;
 
+mod zinter;
+pub use zinter::Zinter;
+
+mod zintercard;
+pub use zintercard::Zintercard;
+
+mod geoadd;
+pub use geoadd::Geoadd;
+
+mod geopos;
+pub use geopos::Geopos;
+
+mod geodist;
+pub use geodist::Geodist;
+
+mod geosearch;
+pub use geosearch::Geosearch;
+
+mod xadd;
+pub use xadd::Xadd;
+
+mod xlen;
+pub use xlen::Xlen;
+
+mod xrange;
+pub use xrange::Xrange;
+
+mod xread;
+pub use xread::Xread;
+
+mod xgroup;
+pub use xgroup::Xgroup;
+
+mod xreadgroup;
+pub use xreadgroup::Xreadgroup;
+
+mod xack;
+pub use xack::Xack;
+
+mod xpending;
+pub use xpending::Xpending;
+
+mod xclaim;
+pub use xclaim::Xclaim;
+
+mod jsonset;
+pub use jsonset::Jsonset;
+
+mod jsonget;
+pub use jsonget::Jsonget;
+
+mod jsondel;
+pub use jsondel::Jsondel;
+
+mod hindex;
+pub use hindex::Hindex;
+
 mod zcount;
 pub use zcount::Zcount;
 
 mod zpop;
 pub use zpop::Zpop;
 
+mod zmpop;
+pub use zmpop::Zmpop;
+mod zrangestore;
+pub use zrangestore::Zrangestore;
+
 mod zrank;
 pub use zrank::Zrank;
 
@@ -182,33 +317,69 @@ mod zincrby;
 pub use zincrby::Zincrby;
 
 mod script;
-pub use script::script_clear_killed;
-pub use script::script_interuptted;
-pub use script::script_set_killed;
+pub use script::running_scripts;
+pub use script::script_interrupted;
+pub use script::script_set_written;
+pub use script::script_start;
 pub use script::Script;
+pub use script::SCRIPT_ID_REGISTRY_KEY;
 
 mod unknown;
 pub use unknown::Unknown;
 
+mod arity;
+use arity::arity_matches;
+pub use arity::WrongArity;
+
 mod auth;
 pub use auth::Auth;
 
 mod debug;
 pub use debug::Debug;
 
+mod ttlforecast;
+pub use ttlforecast::Ttlforecast;
+
+mod cmdconfig;
+pub use cmdconfig::Config;
+
 mod cluster;
 pub use cluster::Cluster;
 
+mod route;
+pub use route::Route;
+
+mod rpclog;
+pub use rpclog::Rpclog;
+mod snapshot;
+pub use snapshot::Snapshot;
+
+mod format;
+pub use format::Format;
+
 mod fake;
 pub use fake::Fake;
 
+mod help;
+pub use help::{command_doc, resp_help};
+
 mod multi;
 pub use multi::Multi;
 
+mod watch;
+pub use watch::Watch;
+pub use watch::WatchedKey;
+
 mod scan;
 pub use scan::Scan;
 
 use crate::client::Client;
+use crate::priority::Priority;
+use crate::tikv::backend::Transaction;
+use crate::tikv::encoding::DataType;
+use crate::tikv::errors::AsyncResult;
+use crate::tikv::intersect::SetOperation;
+use crate::utils::resp_invalid_arguments;
 use crate::{cluster::Cluster as Topo, Connection, Db, Frame, Parse, ParseError, Shutdown};
 
 /// All commands should be implement new_invalid() for invalid check
@@ -244,7 +415,10 @@ pub enum Command {
     Mset(Mset),
     Subscribe(Subscribe),
     Unsubscribe(Unsubscribe),
+    Psubscribe(Psubscribe),
+    Punsubscribe(Punsubscribe),
     Ping(Ping),
+    Lolwut(Lolwut),
     Type(Type),
     TTL(TTL),
     PTTL(TTL),
@@ -259,6 +433,30 @@ pub enum Command {
     IncrBy(IncrDecr),
     DecrBy(IncrDecr),
     Strlen(Strlen),
+    Getrange(Getrange),
+    Substr(Getrange),
+    Getdel(Getdel),
+    Getex(Getex),
+    Append(Append),
+    Setrange(Setrange),
+    Setbit(Setbit),
+    Getbit(Getbit),
+    Bitcount(Bitcount),
+    Bitpos(Bitpos),
+    Bitop(Bitop),
+    Bitfield(Bitfield),
+    Sort(Sort),
+    Object(Object),
+    Randomkey(Randomkey),
+    Copy(CopyKey),
+    Move(MoveKey),
+    Dump(Dump),
+    Restore(Restore),
+    Migrate(Migrate),
+    Throttle(Throttle),
+    Lock(Lock),
+    Extend(Lock),
+    Unlock(Lock),
 
     // hash
     Hset(Hset),
@@ -282,6 +480,9 @@ pub enum Command {
     Lrange(Lrange),
     Llen(Llen),
     Lindex(Lindex),
+    Lpos(Lpos),
+    Lmpop(Lmpop),
+    Blmpop(Lmpop),
     Lset(Lset),
     Ltrim(Ltrim),
     Lrem(Lrem),
@@ -294,11 +495,15 @@ pub enum Command {
     Smembers(Smembers),
     Srandmember(Srandmember),
     Spop(Spop),
+    Sdrain(Sdrain),
     Srem(Srem),
+    Sinter(Sinter),
+    Sintercard(Sintercard),
     // sorted set
     Zadd(Zadd),
     Zcard(Zcard),
     Zscore(Zscore),
+    Zmscore(Zmscore),
     Zrem(Zrem),
     Zremrangebyscore(Zremrangebyscore),
     Zremrangebyrank(Zremrangebyrank),
@@ -309,8 +514,44 @@ pub enum Command {
     Zcount(Zcount),
     Zpopmin(Zpop),
     Zpopmax(Zpop),
+    Zmpop(Zmpop),
+    Bzmpop(Zmpop),
+    Zrangestore(Zrangestore),
     Zrank(Zrank),
     Zincryby(Zincrby),
+    Zinter(Zinter),
+    Zunion(Zinter),
+    Zdiff(Zinter),
+    Zinterstore(Zinter),
+    Zunionstore(Zinter),
+    Zdiffstore(Zinter),
+    Zintercard(Zintercard),
+
+    // geo
+    Geoadd(Geoadd),
+    Geopos(Geopos),
+    Geodist(Geodist),
+    Geosearch(Geosearch),
+
+    // streams
+    Xadd(Xadd),
+    Xlen(Xlen),
+    Xrange(Xrange),
+    Xrevrange(Xrange),
+    Xread(Xread),
+    Xgroup(Xgroup),
+    Xreadgroup(Xreadgroup),
+    Xack(Xack),
+    Xpending(Xpending),
+    Xclaim(Xclaim),
+
+    // json
+    Jsonset(Jsonset),
+    Jsonget(Jsonget),
+    Jsondel(Jsondel),
+
+    // hash index
+    Hindex(Hindex),
 
     // scripts
     Eval(Eval),
@@ -319,10 +560,17 @@ pub enum Command {
 
     Auth(Auth),
     Debug(Debug),
+    Ttlforecast(Ttlforecast),
+    Config(Config),
 
     Cluster(Cluster),
+    Route(Route),
+    Rpclog(Rpclog),
+    Snapshot(Snapshot),
+    Format(Format),
     ReadWrite(Fake),
     ReadOnly(Fake),
+    Idempotency(Fake),
     Client(Fake),
     Info(Fake),
 
@@ -330,12 +578,15 @@ pub enum Command {
     Multi(Multi),
     Exec(Multi),
     Discard(Multi),
+    Watch(Watch),
+    Unwatch(Multi),
 
     Scan(Scan),
     // Xscan command is same as scan, for testing purpose, avoid some client decoding the response
     Xscan(Scan),
 
     Unknown(Unknown),
+    WrongArity(WrongArity),
 }
 
 impl Command {
@@ -359,6 +610,9 @@ impl Command {
         // is read and converted to lower cases in order to do case sensitive
         // matching.
         let command_name = parse.next_string()?.to_lowercase();
+        if !arity_matches(&command_name, parse.remaining() + 1) {
+            return Ok(Command::WrongArity(WrongArity::new(command_name)));
+        }
         // Match the command name, delegating the rest of the parsing to the
         // specific command.
         let command = match &command_name[..] {
@@ -379,7 +633,19 @@ impl Command {
                 Unsubscribe::parse_frames(&mut parse),
                 &mut parse,
             )),
+            "psubscribe" => Command::Psubscribe(transform_parse(
+                Psubscribe::parse_frames(&mut parse),
+                &mut parse,
+            )),
+            "punsubscribe" => Command::Punsubscribe(transform_parse(
+                Punsubscribe::parse_frames(&mut parse),
+                &mut parse,
+            )),
             "ping" => Command::Ping(transform_parse(Ping::parse_frames(&mut parse), &mut parse)),
+            "lolwut" => Command::Lolwut(transform_parse(
+                Lolwut::parse_frames(&mut parse),
+                &mut parse,
+            )),
             "type" => Command::Type(transform_parse(Type::parse_frames(&mut parse), &mut parse)),
             "mget" => Command::Mget(transform_parse(Mget::parse_frames(&mut parse), &mut parse)),
             "mset" => Command::Mset(transform_parse(Mset::parse_frames(&mut parse), &mut parse)),
@@ -429,6 +695,90 @@ impl Command {
                 Strlen::parse_frames(&mut parse),
                 &mut parse,
             )),
+            "getrange" => Command::Getrange(transform_parse(
+                Getrange::parse_frames(&mut parse),
+                &mut parse,
+            )),
+            "substr" => Command::Substr(transform_parse(
+                Getrange::parse_frames(&mut parse),
+                &mut parse,
+            )),
+            "getdel" => Command::Getdel(transform_parse(
+                Getdel::parse_frames(&mut parse),
+                &mut parse,
+            )),
+            "getex" => Command::Getex(transform_parse(Getex::parse_frames(&mut parse), &mut parse)),
+            "append" => Command::Append(transform_parse(
+                Append::parse_frames(&mut parse),
+                &mut parse,
+            )),
+            "setrange" => Command::Setrange(transform_parse(
+                Setrange::parse_frames(&mut parse),
+                &mut parse,
+            )),
+            "setbit" => Command::Setbit(transform_parse(
+                Setbit::parse_frames(&mut parse),
+                &mut parse,
+            )),
+            "getbit" => Command::Getbit(transform_parse(
+                Getbit::parse_frames(&mut parse),
+                &mut parse,
+            )),
+            "bitcount" => Command::Bitcount(transform_parse(
+                Bitcount::parse_frames(&mut parse),
+                &mut parse,
+            )),
+            "bitpos" => Command::Bitpos(transform_parse(
+                Bitpos::parse_frames(&mut parse),
+                &mut parse,
+            )),
+            "bitop" => Command::Bitop(transform_parse(Bitop::parse_frames(&mut parse), &mut parse)),
+            "bitfield" => Command::Bitfield(transform_parse(
+                Bitfield::parse_frames(&mut parse),
+                &mut parse,
+            )),
+            "sort" => Command::Sort(transform_parse(Sort::parse_frames(&mut parse), &mut parse)),
+            "object" => Command::Object(transform_parse(
+                Object::parse_frames(&mut parse),
+                &mut parse,
+            )),
+            "randomkey" => Command::Randomkey(transform_parse(
+                Randomkey::parse_frames(&mut parse),
+                &mut parse,
+            )),
+            "copy" => Command::Copy(transform_parse(
+                CopyKey::parse_frames(&mut parse),
+                &mut parse,
+            )),
+            "tidis.move" => Command::Move(transform_parse(
+                MoveKey::parse_frames(&mut parse),
+                &mut parse,
+            )),
+            "dump" => Command::Dump(transform_parse(Dump::parse_frames(&mut parse), &mut parse)),
+            "restore" => Command::Restore(transform_parse(
+                Restore::parse_frames(&mut parse),
+                &mut parse,
+            )),
+            "migrate" => Command::Migrate(transform_parse(
+                Migrate::parse_frames(&mut parse),
+                &mut parse,
+            )),
+            "cl.throttle" => Command::Throttle(transform_parse(
+                Throttle::parse_frames(&mut parse),
+                &mut parse,
+            )),
+            "lock" => Command::Lock(transform_parse(
+                Lock::parse_frames(&mut parse, true),
+                &mut parse,
+            )),
+            "extend" => Command::Extend(transform_parse(
+                Lock::parse_frames(&mut parse, true),
+                &mut parse,
+            )),
+            "unlock" => Command::Unlock(transform_parse(
+                Lock::parse_frames(&mut parse, false),
+                &mut parse,
+            )),
             "hset" => Command::Hset(transform_parse(Hset::parse_frames(&mut parse), &mut parse)),
             "hsetnx" => {
                 Command::Hsetnx(transform_parse(Hset::parse_frames(&mut parse), &mut parse))
@@ -465,6 +815,15 @@ impl Command {
                 &mut parse,
             )),
             "llen" => Command::Llen(transform_parse(Llen::parse_frames(&mut parse), &mut parse)),
+            "lpos" => Command::Lpos(transform_parse(Lpos::parse_frames(&mut parse), &mut parse)),
+            "lmpop" => Command::Lmpop(transform_parse(
+                Lmpop::parse_frames(&mut parse, false),
+                &mut parse,
+            )),
+            "blmpop" => Command::Blmpop(transform_parse(
+                Lmpop::parse_frames(&mut parse, true),
+                &mut parse,
+            )),
             "lindex" => Command::Lindex(transform_parse(
                 Lindex::parse_frames(&mut parse),
                 &mut parse,
@@ -503,13 +862,29 @@ impl Command {
                 &mut parse,
             )),
             "spop" => Command::Spop(transform_parse(Spop::parse_frames(&mut parse), &mut parse)),
+            "sdrain" => Command::Sdrain(transform_parse(
+                Sdrain::parse_frames(&mut parse),
+                &mut parse,
+            )),
             "srem" => Command::Srem(transform_parse(Srem::parse_frames(&mut parse), &mut parse)),
+            "sinter" => Command::Sinter(transform_parse(
+                Sinter::parse_frames(&mut parse),
+                &mut parse,
+            )),
+            "sintercard" => Command::Sintercard(transform_parse(
+                Sintercard::parse_frames(&mut parse),
+                &mut parse,
+            )),
             "zadd" => Command::Zadd(transform_parse(Zadd::parse_frames(&mut parse), &mut parse)),
             "zcard" => Command::Zcard(transform_parse(Zcard::parse_frames(&mut parse), &mut parse)),
             "zscore" => Command::Zscore(transform_parse(
                 Zscore::parse_frames(&mut parse),
                 &mut parse,
             )),
+            "zmscore" => Command::Zmscore(transform_parse(
+                Zmscore::parse_frames(&mut parse),
+                &mut parse,
+            )),
             "zrem" => Command::Zrem(transform_parse(Zrem::parse_frames(&mut parse), &mut parse)),
             "zremrangebyscore" => Command::Zremrangebyscore(transform_parse(
                 Zremrangebyscore::parse_frames(&mut parse),
@@ -545,17 +920,140 @@ impl Command {
             "zpopmax" => {
                 Command::Zpopmax(transform_parse(Zpop::parse_frames(&mut parse), &mut parse))
             }
+            "zmpop" => Command::Zmpop(transform_parse(
+                Zmpop::parse_frames(&mut parse, false),
+                &mut parse,
+            )),
+            "bzmpop" => Command::Bzmpop(transform_parse(
+                Zmpop::parse_frames(&mut parse, true),
+                &mut parse,
+            )),
+            "zrangestore" => Command::Zrangestore(transform_parse(
+                Zrangestore::parse_frames(&mut parse),
+                &mut parse,
+            )),
             "zrank" => Command::Zrank(transform_parse(Zrank::parse_frames(&mut parse), &mut parse)),
             "zincrby" => Command::Zincryby(transform_parse(
                 Zincrby::parse_frames(&mut parse),
                 &mut parse,
             )),
+            "zinter" => Command::Zinter(transform_parse(
+                Zinter::parse_frames(&mut parse, SetOperation::Inter, false),
+                &mut parse,
+            )),
+            "zunion" => Command::Zunion(transform_parse(
+                Zinter::parse_frames(&mut parse, SetOperation::Union, false),
+                &mut parse,
+            )),
+            "zdiff" => Command::Zdiff(transform_parse(
+                Zinter::parse_frames(&mut parse, SetOperation::Diff, false),
+                &mut parse,
+            )),
+            "zinterstore" => Command::Zinterstore(transform_parse(
+                Zinter::parse_frames(&mut parse, SetOperation::Inter, true),
+                &mut parse,
+            )),
+            "zunionstore" => Command::Zunionstore(transform_parse(
+                Zinter::parse_frames(&mut parse, SetOperation::Union, true),
+                &mut parse,
+            )),
+            "zdiffstore" => Command::Zdiffstore(transform_parse(
+                Zinter::parse_frames(&mut parse, SetOperation::Diff, true),
+                &mut parse,
+            )),
+            "zintercard" => Command::Zintercard(transform_parse(
+                Zintercard::parse_frames(&mut parse),
+                &mut parse,
+            )),
+            "geoadd" => Command::Geoadd(transform_parse(
+                Geoadd::parse_frames(&mut parse),
+                &mut parse,
+            )),
+            "geopos" => Command::Geopos(transform_parse(
+                Geopos::parse_frames(&mut parse),
+                &mut parse,
+            )),
+            "geodist" => Command::Geodist(transform_parse(
+                Geodist::parse_frames(&mut parse),
+                &mut parse,
+            )),
+            "geosearch" => Command::Geosearch(transform_parse(
+                Geosearch::parse_frames(&mut parse),
+                &mut parse,
+            )),
+            "xadd" => Command::Xadd(transform_parse(Xadd::parse_frames(&mut parse), &mut parse)),
+            "xlen" => Command::Xlen(transform_parse(Xlen::parse_frames(&mut parse), &mut parse)),
+            "xrange" => Command::Xrange(transform_parse(
+                Xrange::parse_frames(&mut parse),
+                &mut parse,
+            )),
+            "xrevrange" => Command::Xrevrange(transform_parse(
+                Xrange::parse_frames(&mut parse),
+                &mut parse,
+            )),
+            "xread" => Command::Xread(transform_parse(Xread::parse_frames(&mut parse), &mut parse)),
+            "xgroup" => Command::Xgroup(transform_parse(
+                Xgroup::parse_frames(&mut parse),
+                &mut parse,
+            )),
+            "xreadgroup" => Command::Xreadgroup(transform_parse(
+                Xreadgroup::parse_frames(&mut parse),
+                &mut parse,
+            )),
+            "xack" => Command::Xack(transform_parse(Xack::parse_frames(&mut parse), &mut parse)),
+            "xpending" => Command::Xpending(transform_parse(
+                Xpending::parse_frames(&mut parse),
+                &mut parse,
+            )),
+            "xclaim" => Command::Xclaim(transform_parse(
+                Xclaim::parse_frames(&mut parse),
+                &mut parse,
+            )),
+            "json.set" => Command::Jsonset(transform_parse(
+                Jsonset::parse_frames(&mut parse),
+                &mut parse,
+            )),
+            "json.get" => Command::Jsonget(transform_parse(
+                Jsonget::parse_frames(&mut parse),
+                &mut parse,
+            )),
+            "json.del" => Command::Jsondel(transform_parse(
+                Jsondel::parse_frames(&mut parse),
+                &mut parse,
+            )),
+            "hindex" => Command::Hindex(transform_parse(
+                Hindex::parse_frames(&mut parse),
+                &mut parse,
+            )),
             "auth" => Command::Auth(transform_parse(Auth::parse_frames(&mut parse), &mut parse)),
             "debug" => Command::Debug(transform_parse(Debug::parse_frames(&mut parse), &mut parse)),
+            "ttlforecast" => Command::Ttlforecast(transform_parse(
+                Ttlforecast::parse_frames(&mut parse),
+                &mut parse,
+            )),
+            "config" => Command::Config(transform_parse(
+                Config::parse_frames(&mut parse),
+                &mut parse,
+            )),
             "cluster" => Command::Cluster(transform_parse(
                 Cluster::parse_frames(&mut parse),
                 &mut parse,
             )),
+            "tidis.route" => {
+                Command::Route(transform_parse(Route::parse_frames(&mut parse), &mut parse))
+            }
+            "tidis.rpclog" => Command::Rpclog(transform_parse(
+                Rpclog::parse_frames(&mut parse),
+                &mut parse,
+            )),
+            "tidis.snapshot" => Command::Snapshot(transform_parse(
+                Snapshot::parse_frames(&mut parse),
+                &mut parse,
+            )),
+            "tidis.format" => Command::Format(transform_parse(
+                Format::parse_frames(&mut parse),
+                &mut parse,
+            )),
             "readwrite" => Command::ReadWrite(transform_parse(
                 Fake::parse_frames(&mut parse, "readwrite"),
                 &mut parse,
@@ -564,6 +1062,10 @@ impl Command {
                 Fake::parse_frames(&mut parse, "readonly"),
                 &mut parse,
             )),
+            "idempotency" => Command::Idempotency(transform_parse(
+                Fake::parse_frames(&mut parse, "idempotency"),
+                &mut parse,
+            )),
             "client" => Command::Client(transform_parse(
                 Fake::parse_frames(&mut parse, "client"),
                 &mut parse,
@@ -575,6 +1077,8 @@ impl Command {
             "multi" => Command::Multi(Multi::new()),
             "exec" => Command::Exec(Multi::new()),
             "discard" => Command::Discard(Multi::new()),
+            "watch" => Command::Watch(transform_parse(Watch::parse_frames(&mut parse), &mut parse)),
+            "unwatch" => Command::Unwatch(Multi::new()),
             "scan" => Command::Scan(transform_parse(Scan::parse_frames(&mut parse), &mut parse)),
             "xscan" => Command::Scan(transform_parse(Scan::parse_frames(&mut parse), &mut parse)),
             _ => {
@@ -596,6 +1100,9 @@ impl Command {
 
     pub fn from_argv(cmd_name: &str, argv: &Vec<Bytes>) -> crate::Result<Command> {
         let command_name = cmd_name.to_owned().to_lowercase();
+        if !arity_matches(&command_name, argv.len() + 1) {
+            return Ok(Command::WrongArity(WrongArity::new(command_name)));
+        }
         // Match the command name, delegating the rest of the parsing to the
         // specific command.
         let command = match &command_name[..] {
@@ -604,6 +1111,30 @@ impl Command {
             "incrby" => Command::IncrBy(IncrDecr::parse_argv(argv, false)?),
             "decrby" => Command::DecrBy(IncrDecr::parse_argv(argv, false)?),
             "strlen" => Command::Strlen(Strlen::parse_argv(argv)?),
+            "getrange" => Command::Getrange(Getrange::parse_argv(argv)?),
+            "substr" => Command::Substr(Getrange::parse_argv(argv)?),
+            "getdel" => Command::Getdel(Getdel::parse_argv(argv)?),
+            "getex" => Command::Getex(Getex::parse_argv(argv)?),
+            "append" => Command::Append(Append::parse_argv(argv)?),
+            "setrange" => Command::Setrange(Setrange::parse_argv(argv)?),
+            "setbit" => Command::Setbit(Setbit::parse_argv(argv)?),
+            "getbit" => Command::Getbit(Getbit::parse_argv(argv)?),
+            "bitcount" => Command::Bitcount(Bitcount::parse_argv(argv)?),
+            "bitpos" => Command::Bitpos(Bitpos::parse_argv(argv)?),
+            "bitop" => Command::Bitop(Bitop::parse_argv(argv)?),
+            "bitfield" => Command::Bitfield(Bitfield::parse_argv(argv)?),
+            "sort" => Command::Sort(Sort::parse_argv(argv)?),
+            "object" => Command::Object(Object::parse_argv(argv)?),
+            "randomkey" => Command::Randomkey(Randomkey::parse_argv(argv)?),
+            "copy" => Command::Copy(CopyKey::parse_argv(argv)?),
+            "tidis.move" => Command::Move(MoveKey::parse_argv(argv)?),
+            "dump" => Command::Dump(Dump::parse_argv(argv)?),
+            "restore" => Command::Restore(Restore::parse_argv(argv)?),
+            "migrate" => Command::Migrate(Migrate::parse_argv(argv)?),
+            "cl.throttle" => Command::Throttle(Throttle::parse_argv(argv)?),
+            "lock" => Command::Lock(Lock::parse_argv(argv, true)?),
+            "extend" => Command::Extend(Lock::parse_argv(argv, true)?),
+            "unlock" => Command::Unlock(Lock::parse_argv(argv, false)?),
             "del" => Command::Del(Del::parse_argv(argv)?),
             "type" => Command::Type(Type::parse_argv(argv)?),
             "exists" => Command::Exists(Exists::parse_argv(argv)?),
@@ -640,6 +1171,9 @@ impl Command {
             "lrange" => Command::Lrange(Lrange::parse_argv(argv)?),
             "llen" => Command::Llen(Llen::parse_argv(argv)?),
             "lindex" => Command::Lindex(Lindex::parse_argv(argv)?),
+            "lpos" => Command::Lpos(Lpos::parse_argv(argv)?),
+            "lmpop" => Command::Lmpop(Lmpop::parse_argv(argv, false)?),
+            "blmpop" => Command::Blmpop(Lmpop::parse_argv(argv, true)?),
             "lset" => Command::Lset(Lset::parse_argv(argv)?),
             "ltrim" => Command::Ltrim(Ltrim::parse_argv(argv)?),
             "lrem" => Command::Lrem(Lrem::parse_argv(argv)?),
@@ -651,10 +1185,14 @@ impl Command {
             "smembers" => Command::Smembers(Smembers::parse_argv(argv)?),
             "srandmember" => Command::Srandmember(Srandmember::parse_argv(argv)?),
             "spop" => Command::Spop(Spop::parse_argv(argv)?),
+            "sdrain" => Command::Sdrain(Sdrain::parse_argv(argv)?),
             "srem" => Command::Srem(Srem::parse_argv(argv)?),
+            "sinter" => Command::Sinter(Sinter::parse_argv(argv)?),
+            "sintercard" => Command::Sintercard(Sintercard::parse_argv(argv)?),
             "zadd" => Command::Zadd(Zadd::parse_argv(argv)?),
             "zcard" => Command::Zcard(Zcard::parse_argv(argv)?),
             "zscore" => Command::Zscore(Zscore::parse_argv(argv)?),
+            "zmscore" => Command::Zmscore(Zmscore::parse_argv(argv)?),
             "zrem" => Command::Zrem(Zrem::parse_argv(argv)?),
             "zremrangebyscore" => Command::Zremrangebyscore(Zremrangebyscore::parse_argv(argv)?),
             "zremrangebyrank" => Command::Zremrangebyrank(Zremrangebyrank::parse_argv(argv)?),
@@ -665,8 +1203,42 @@ impl Command {
             "zcount" => Command::Zcount(Zcount::parse_argv(argv)?),
             "zpopmin" => Command::Zpopmin(Zpop::parse_argv(argv)?),
             "zpopmax" => Command::Zpopmax(Zpop::parse_argv(argv)?),
+            "zmpop" => Command::Zmpop(Zmpop::parse_argv(argv, false)?),
+            "bzmpop" => Command::Bzmpop(Zmpop::parse_argv(argv, true)?),
+            "zrangestore" => Command::Zrangestore(Zrangestore::parse_argv(argv)?),
             "zrank" => Command::Zrank(Zrank::parse_argv(argv)?),
             "zincrby" => Command::Zincryby(Zincrby::parse_argv(argv)?),
+            "zinter" => Command::Zinter(Zinter::parse_argv(argv, SetOperation::Inter, false)?),
+            "zunion" => Command::Zunion(Zinter::parse_argv(argv, SetOperation::Union, false)?),
+            "zdiff" => Command::Zdiff(Zinter::parse_argv(argv, SetOperation::Diff, false)?),
+            "zinterstore" => {
+                Command::Zinterstore(Zinter::parse_argv(argv, SetOperation::Inter, true)?)
+            }
+            "zunionstore" => {
+                Command::Zunionstore(Zinter::parse_argv(argv, SetOperation::Union, true)?)
+            }
+            "zdiffstore" => {
+                Command::Zdiffstore(Zinter::parse_argv(argv, SetOperation::Diff, true)?)
+            }
+            "zintercard" => Command::Zintercard(Zintercard::parse_argv(argv)?),
+            "geoadd" => Command::Geoadd(Geoadd::parse_argv(argv)?),
+            "geopos" => Command::Geopos(Geopos::parse_argv(argv)?),
+            "geodist" => Command::Geodist(Geodist::parse_argv(argv)?),
+            "geosearch" => Command::Geosearch(Geosearch::parse_argv(argv)?),
+            "xadd" => Command::Xadd(Xadd::parse_argv(argv)?),
+            "xlen" => Command::Xlen(Xlen::parse_argv(argv)?),
+            "xrange" => Command::Xrange(Xrange::parse_argv(argv)?),
+            "xrevrange" => Command::Xrevrange(Xrange::parse_argv(argv)?),
+            "xread" => Command::Xread(Xread::parse_argv(argv)?),
+            "xgroup" => Command::Xgroup(Xgroup::parse_argv(argv)?),
+            "xreadgroup" => Command::Xreadgroup(Xreadgroup::parse_argv(argv)?),
+            "xack" => Command::Xack(Xack::parse_argv(argv)?),
+            "xpending" => Command::Xpending(Xpending::parse_argv(argv)?),
+            "xclaim" => Command::Xclaim(Xclaim::parse_argv(argv)?),
+            "json.set" => Command::Jsonset(Jsonset::parse_argv(argv)?),
+            "json.get" => Command::Jsonget(Jsonget::parse_argv(argv)?),
+            "json.del" => Command::Jsondel(Jsondel::parse_argv(argv)?),
+            "hindex" => Command::Hindex(Hindex::parse_argv(argv)?),
             "scan" => Command::Scan(Scan::parse_argv(argv)?),
             "xscan" => Command::Scan(Scan::parse_argv(argv)?),
             _ => {
@@ -704,7 +1276,11 @@ impl Command {
             SetNX(cmd) => cmd.apply(dst).await,
             SetEX(cmd) => cmd.apply(dst).await,
             Subscribe(cmd) => cmd.apply(db, dst, shutdown).await,
+            Psubscribe(cmd) => cmd.apply(db, dst, shutdown).await,
+            Unsubscribe(cmd) => cmd.apply(dst).await,
+            Punsubscribe(cmd) => cmd.apply(dst).await,
             Ping(cmd) => cmd.apply(dst).await,
+            Lolwut(cmd) => cmd.apply(dst).await,
             Type(cmd) => cmd.apply(dst).await,
             Mget(cmd) => cmd.apply(dst).await,
             Mset(cmd) => cmd.apply(dst).await,
@@ -721,6 +1297,30 @@ impl Command {
             IncrBy(cmd) => cmd.apply(dst, true).await,
             DecrBy(cmd) => cmd.apply(dst, false).await,
             Strlen(cmd) => cmd.apply(dst).await,
+            Getrange(cmd) => cmd.apply(dst).await,
+            Substr(cmd) => cmd.apply(dst).await,
+            Getdel(cmd) => cmd.apply(dst).await,
+            Getex(cmd) => cmd.apply(dst).await,
+            Append(cmd) => cmd.apply(dst).await,
+            Setrange(cmd) => cmd.apply(dst).await,
+            Setbit(cmd) => cmd.apply(dst).await,
+            Getbit(cmd) => cmd.apply(dst).await,
+            Bitcount(cmd) => cmd.apply(dst).await,
+            Bitpos(cmd) => cmd.apply(dst).await,
+            Bitop(cmd) => cmd.apply(dst).await,
+            Bitfield(cmd) => cmd.apply(dst).await,
+            Sort(cmd) => cmd.apply(dst).await,
+            Object(cmd) => cmd.apply(dst).await,
+            Randomkey(cmd) => cmd.apply(dst).await,
+            Copy(cmd) => cmd.apply(dst).await,
+            Move(cmd) => cmd.apply(dst).await,
+            Dump(cmd) => cmd.apply(dst).await,
+            Restore(cmd) => cmd.apply(dst).await,
+            Migrate(cmd) => cmd.apply(dst).await,
+            Throttle(cmd) => cmd.apply(dst).await,
+            Lock(cmd) => cmd.apply(dst, "lock").await,
+            Extend(cmd) => cmd.apply(dst, "extend").await,
+            Unlock(cmd) => cmd.apply(dst, "unlock").await,
             Hset(cmd) => cmd.apply(dst, false, false).await,
             Hmset(cmd) => cmd.apply(dst, true, false).await,
             Hsetnx(cmd) => cmd.apply(dst, false, true).await,
@@ -741,6 +1341,9 @@ impl Command {
             Lrange(cmd) => cmd.apply(dst).await,
             Llen(cmd) => cmd.apply(dst).await,
             Lindex(cmd) => cmd.apply(dst).await,
+            Lpos(cmd) => cmd.apply(dst).await,
+            Lmpop(cmd) => cmd.apply(dst, shutdown).await,
+            Blmpop(cmd) => cmd.apply(dst, shutdown).await,
             Lset(cmd) => cmd.apply(dst).await,
             Ltrim(cmd) => cmd.apply(dst).await,
             Lrem(cmd) => cmd.apply(dst).await,
@@ -755,10 +1358,14 @@ impl Command {
             Smembers(cmd) => cmd.apply(dst).await,
             Srandmember(cmd) => cmd.apply(dst).await,
             Spop(cmd) => cmd.apply(dst).await,
+            Sdrain(cmd) => cmd.apply(dst).await,
             Srem(cmd) => cmd.apply(dst).await,
+            Sinter(cmd) => cmd.apply(dst).await,
+            Sintercard(cmd) => cmd.apply(dst).await,
             Zadd(cmd) => cmd.apply(dst).await,
             Zcard(cmd) => cmd.apply(dst).await,
             Zscore(cmd) => cmd.apply(dst).await,
+            Zmscore(cmd) => cmd.apply(dst).await,
             Zrem(cmd) => cmd.apply(dst).await,
             Zremrangebyscore(cmd) => cmd.apply(dst).await,
             Zremrangebyrank(cmd) => cmd.apply(dst).await,
@@ -769,14 +1376,51 @@ impl Command {
             Zcount(cmd) => cmd.apply(dst).await,
             Zpopmin(cmd) => cmd.apply(dst, true).await,
             Zpopmax(cmd) => cmd.apply(dst, false).await,
+            Zmpop(cmd) => cmd.apply(dst, shutdown).await,
+            Bzmpop(cmd) => cmd.apply(dst, shutdown).await,
+            Zrangestore(cmd) => cmd.apply(dst).await,
             Zrank(cmd) => cmd.apply(dst).await,
             Zincryby(cmd) => cmd.apply(dst).await,
+            Zinter(cmd) => cmd.apply(dst).await,
+            Zunion(cmd) => cmd.apply(dst).await,
+            Zdiff(cmd) => cmd.apply(dst).await,
+            Zinterstore(cmd) => cmd.apply(dst).await,
+            Zunionstore(cmd) => cmd.apply(dst).await,
+            Zdiffstore(cmd) => cmd.apply(dst).await,
+            Zintercard(cmd) => cmd.apply(dst).await,
+            Geoadd(cmd) => cmd.apply(dst).await,
+            Geopos(cmd) => cmd.apply(dst).await,
+            Geodist(cmd) => cmd.apply(dst).await,
+            Geosearch(cmd) => cmd.apply(dst).await,
+
+            Xadd(cmd) => cmd.apply(dst).await,
+            Xlen(cmd) => cmd.apply(dst).await,
+            Xrange(cmd) => cmd.apply(dst, false).await,
+            Xrevrange(cmd) => cmd.apply(dst, true).await,
+            Xread(cmd) => cmd.apply(dst).await,
+            Xgroup(cmd) => cmd.apply(dst).await,
+            Xreadgroup(cmd) => cmd.apply(dst).await,
+            Xack(cmd) => cmd.apply(dst).await,
+            Xpending(cmd) => cmd.apply(dst).await,
+            Xclaim(cmd) => cmd.apply(dst).await,
+
+            Jsonset(cmd) => cmd.apply(dst).await,
+            Jsonget(cmd) => cmd.apply(dst).await,
+            Jsondel(cmd) => cmd.apply(dst).await,
+            Hindex(cmd) => cmd.apply(dst).await,
 
             Debug(cmd) => cmd.apply(dst).await,
+            Ttlforecast(cmd) => cmd.apply(dst).await,
+            Config(cmd) => cmd.apply(dst).await,
 
             Cluster(cmd) => cmd.apply(topo, dst).await,
+            Route(cmd) => cmd.apply(topo, dst).await,
+            Rpclog(cmd) => cmd.apply(dst).await,
+            Snapshot(cmd) => cmd.apply(dst, clients).await,
+            Format(cmd) => cmd.apply(dst).await,
             ReadWrite(cmd) => cmd.apply("readwrite", dst, cur_client, clients).await,
             ReadOnly(cmd) => cmd.apply("readonly", dst, cur_client, clients).await,
+            Idempotency(cmd) => cmd.apply("idempotency", dst, cur_client, clients).await,
             Client(cmd) => cmd.apply("client", dst, cur_client, clients).await,
             Info(cmd) => cmd.apply("info", dst, cur_client, clients).await,
 
@@ -784,14 +1428,149 @@ impl Command {
             Xscan(cmd) => cmd.apply(dst).await,
 
             Unknown(cmd) => cmd.apply(dst).await,
-            // `Unsubscribe` cannot be applied. It may only be received from the
-            // context of a `Subscribe` command.
-            Unsubscribe(_) => Err("`Unsubscribe` is unsupported in this context".into()),
-
+            WrongArity(cmd) => cmd.apply(dst).await,
             _ => Ok(()),
         }
     }
 
+    /// Execute a data command in `txn` or its own transaction if None, the
+    /// response is returned instead of written to the connection.
+    pub(crate) async fn execute(self, txn: Option<Arc<Mutex<Transaction>>>) -> AsyncResult<Frame> {
+        match self {
+            Command::Incr(mut cmd) => cmd.incr_by(txn.clone(), true).await,
+            Command::IncrBy(mut cmd) => cmd.incr_by(txn.clone(), true).await,
+            Command::Decr(mut cmd) => cmd.incr_by(txn.clone(), false).await,
+            Command::DecrBy(mut cmd) => cmd.incr_by(txn.clone(), false).await,
+            Command::Strlen(cmd) => cmd.strlen(txn.clone()).await,
+            Command::Getrange(cmd) => cmd.getrange(txn.clone()).await,
+            Command::Substr(cmd) => cmd.getrange(txn.clone()).await,
+            Command::Getdel(cmd) => cmd.getdel(txn.clone()).await,
+            Command::Getex(cmd) => cmd.getex(txn.clone()).await,
+            Command::Append(cmd) => cmd.append(txn.clone()).await,
+            Command::Setrange(cmd) => cmd.setrange(txn.clone()).await,
+            Command::Setbit(cmd) => cmd.setbit(txn.clone()).await,
+            Command::Getbit(cmd) => cmd.getbit(txn.clone()).await,
+            Command::Bitcount(cmd) => cmd.bitcount(txn.clone()).await,
+            Command::Bitpos(cmd) => cmd.bitpos(txn.clone()).await,
+            Command::Bitop(cmd) => cmd.bitop(txn.clone()).await,
+            Command::Bitfield(cmd) => cmd.bitfield(txn.clone()).await,
+            Command::Sort(cmd) => cmd.sort(txn.clone()).await,
+            Command::Object(cmd) => cmd.object(txn.clone()).await,
+            Command::Randomkey(cmd) => cmd.randomkey(txn.clone()).await,
+            Command::Copy(cmd) => cmd.copy(txn.clone()).await,
+            Command::Move(cmd) => cmd.move_key(txn.clone()).await,
+            Command::Dump(cmd) => cmd.dump(txn.clone()).await,
+            Command::Restore(cmd) => cmd.restore(txn.clone()).await,
+            Command::Migrate(cmd) => cmd.migrate(txn.clone()).await,
+            Command::Throttle(cmd) => cmd.throttle(txn.clone()).await,
+            Command::Lock(cmd) => cmd.lock(txn.clone()).await,
+            Command::Extend(cmd) => cmd.extend(txn.clone()).await,
+            Command::Unlock(cmd) => cmd.unlock(txn.clone()).await,
+            Command::Del(cmd) => cmd.del(txn.clone()).await,
+            Command::Exists(cmd) => cmd.exists(txn.clone()).await,
+            Command::Get(cmd) => cmd.get(txn.clone()).await,
+            Command::Set(cmd) => cmd.set(txn.clone()).await,
+            Command::SetNX(cmd) => cmd.put_not_exists(txn.clone()).await,
+            Command::SetEX(cmd) => cmd.setex(txn.clone()).await,
+            Command::Mget(cmd) => cmd.batch_get(txn.clone()).await,
+            Command::Mset(cmd) => cmd.batch_put(txn.clone()).await,
+            Command::Type(cmd) => cmd.cmd_type(txn.clone()).await,
+            Command::TTL(cmd) => cmd.ttl(false, txn.clone()).await,
+            Command::PTTL(cmd) => cmd.ttl(true, txn.clone()).await,
+            Command::Expire(cmd) => cmd.expire(false, false, txn.clone()).await,
+            Command::ExpireAt(cmd) => cmd.expire(false, true, txn.clone()).await,
+            Command::Pexpire(cmd) => cmd.expire(true, false, txn.clone()).await,
+            Command::PexpireAt(cmd) => cmd.expire(true, true, txn.clone()).await,
+            Command::Persist(cmd) => cmd.persist(txn.clone()).await,
+            Command::Hset(cmd) => cmd.hset(txn.clone(), false, false).await,
+            Command::Hmset(cmd) => cmd.hset(txn.clone(), true, false).await,
+            Command::Hsetnx(cmd) => cmd.hset(txn.clone(), false, true).await,
+            Command::Hget(cmd) => cmd.hget(txn.clone()).await,
+            Command::Hmget(cmd) => cmd.hmget(txn.clone()).await,
+            Command::Hlen(cmd) => cmd.hlen(txn.clone()).await,
+            Command::Hgetall(cmd) => cmd.hgetall(txn.clone()).await,
+            Command::Hdel(cmd) => cmd.hdel(txn.clone()).await,
+            Command::Hkeys(cmd) => cmd.hkeys(txn.clone()).await,
+            Command::Hvals(cmd) => cmd.hvals(txn.clone()).await,
+            Command::Hincrby(cmd) => cmd.hincrby(txn.clone()).await,
+            Command::Hexists(cmd) => cmd.hexists(txn.clone()).await,
+            Command::Hstrlen(cmd) => cmd.hstrlen(txn.clone()).await,
+            Command::Lpush(cmd) => cmd.push(txn.clone(), true).await,
+            Command::Rpush(cmd) => cmd.push(txn.clone(), false).await,
+            Command::Lpop(cmd) => cmd.pop(txn.clone(), true).await,
+            Command::Rpop(cmd) => cmd.pop(txn.clone(), false).await,
+            Command::Lrange(cmd) => cmd.lrange(txn.clone()).await,
+            Command::Llen(cmd) => cmd.llen(txn.clone()).await,
+            Command::Lindex(cmd) => cmd.lindex(txn.clone()).await,
+            Command::Lpos(cmd) => cmd.lpos(txn.clone()).await,
+            Command::Lmpop(cmd) => cmd.lmpop(txn.clone()).await,
+            Command::Blmpop(cmd) => cmd.lmpop(txn.clone()).await,
+            Command::Lset(cmd) => cmd.lset(txn.clone()).await,
+            Command::Ltrim(cmd) => cmd.ltrim(txn.clone()).await,
+            Command::Lrem(cmd) => cmd.lrem(txn.clone()).await,
+            Command::Linsert(cmd) => cmd.linsert(txn.clone()).await,
+            Command::Sadd(cmd) => cmd.sadd(txn.clone()).await,
+            Command::Scard(cmd) => cmd.scard(txn.clone()).await,
+            Command::Sismember(cmd) => cmd.sismember(txn.clone()).await,
+            Command::Smismember(cmd) => cmd.smismember(txn.clone()).await,
+            Command::Smembers(cmd) => cmd.smembers(txn.clone()).await,
+            Command::Srandmember(cmd) => cmd.srandmember(txn.clone()).await,
+            Command::Spop(cmd) => cmd.spop(txn.clone()).await,
+            Command::Sdrain(cmd) => cmd.sdrain(txn.clone()).await,
+            Command::Srem(cmd) => cmd.srem(txn.clone()).await,
+            Command::Sinter(cmd) => cmd.sinter(txn.clone()).await,
+            Command::Sintercard(cmd) => cmd.sintercard(txn.clone()).await,
+            Command::Zadd(cmd) => cmd.zadd(txn.clone()).await,
+            Command::Zcard(cmd) => cmd.zcard(txn.clone()).await,
+            Command::Zscore(cmd) => cmd.zscore(txn.clone()).await,
+            Command::Zmscore(cmd) => cmd.zmscore(txn.clone()).await,
+            Command::Zrem(cmd) => cmd.zrem(txn.clone()).await,
+            Command::Zremrangebyscore(cmd) => cmd.zremrangebyscore(txn.clone()).await,
+            Command::Zremrangebyrank(cmd) => cmd.zremrangebyrank(txn.clone()).await,
+            Command::Zrange(cmd) => cmd.zrange(txn.clone()).await,
+            Command::Zrevrange(cmd) => cmd.zrevrange(txn.clone()).await,
+            Command::Zrangebyscore(cmd) => cmd.zrangebyscore(txn.clone(), false).await,
+            Command::Zrevrangebyscore(cmd) => cmd.zrangebyscore(txn.clone(), true).await,
+            Command::Zcount(cmd) => cmd.zcount(txn.clone()).await,
+            Command::Zpopmin(cmd) => cmd.zpop(txn.clone(), true).await,
+            Command::Zpopmax(cmd) => cmd.zpop(txn.clone(), false).await,
+            Command::Zmpop(cmd) => cmd.zmpop(txn.clone()).await,
+            Command::Bzmpop(cmd) => cmd.zmpop(txn.clone()).await,
+            Command::Zrangestore(cmd) => cmd.zrangestore(txn.clone()).await,
+            Command::Zrank(cmd) => cmd.zrank(txn.clone()).await,
+            Command::Zincryby(cmd) => cmd.zincrby(txn.clone()).await,
+            Command::Zinter(cmd) => cmd.zcombine(txn.clone()).await,
+            Command::Zunion(cmd) => cmd.zcombine(txn.clone()).await,
+            Command::Zdiff(cmd) => cmd.zcombine(txn.clone()).await,
+            Command::Zinterstore(cmd) => cmd.zcombine(txn.clone()).await,
+            Command::Zunionstore(cmd) => cmd.zcombine(txn.clone()).await,
+            Command::Zdiffstore(cmd) => cmd.zcombine(txn.clone()).await,
+            Command::Zintercard(cmd) => cmd.zintercard(txn.clone()).await,
+            Command::Geoadd(cmd) => cmd.geoadd(txn.clone()).await,
+            Command::Geopos(cmd) => cmd.geopos(txn.clone()).await,
+            Command::Geodist(cmd) => cmd.geodist(txn.clone()).await,
+            Command::Geosearch(cmd) => cmd.geosearch(txn.clone()).await,
+            Command::Xadd(cmd) => cmd.xadd(txn.clone()).await,
+            Command::Xlen(cmd) => cmd.xlen(txn.clone()).await,
+            Command::Xrange(cmd) => cmd.xrange(txn.clone(), false).await,
+            Command::Xrevrange(cmd) => cmd.xrange(txn.clone(), true).await,
+            Command::Xread(cmd) => cmd.xread(txn.clone()).await,
+            Command::Xgroup(cmd) => cmd.xgroup(txn.clone()).await,
+            Command::Xreadgroup(cmd) => cmd.xreadgroup(txn.clone()).await,
+            Command::Xack(cmd) => cmd.xack(txn.clone()).await,
+            Command::Xpending(cmd) => cmd.xpending(txn.clone()).await,
+            Command::Xclaim(cmd) => cmd.xclaim(txn.clone()).await,
+            Command::Jsonset(cmd) => cmd.json_set(txn.clone()).await,
+            Command::Jsonget(cmd) => cmd.json_get(txn.clone()).await,
+            Command::Jsondel(cmd) => cmd.json_del(txn.clone()).await,
+            Command::Hindex(cmd) => cmd.hindex(txn.clone()).await,
+            Command::Scan(cmd) => cmd.scan(txn.clone()).await,
+            Command::Xscan(cmd) => cmd.scan(txn.clone()).await,
+            Command::WrongArity(cmd) => Ok(cmd.response()),
+            _ => Ok(resp_invalid_arguments()),
+        }
+    }
+
     /// Returns the command name
     pub(crate) fn get_name(&self) -> &str {
         match self {
@@ -803,7 +1582,10 @@ impl Command {
             Command::SetEX(_) => "setex",
             Command::Subscribe(_) => "subscribe",
             Command::Unsubscribe(_) => "unsubscribe",
+            Command::Psubscribe(_) => "psubscribe",
+            Command::Punsubscribe(_) => "punsubscribe",
             Command::Ping(_) => "ping",
+            Command::Lolwut(_) => "lolwut",
             Command::Type(_) => "type",
             Command::Mget(_) => "mget",
             Command::Mset(_) => "mset",
@@ -820,6 +1602,30 @@ impl Command {
             Command::IncrBy(_) => "incrby",
             Command::DecrBy(_) => "decrby",
             Command::Strlen(_) => "strlen",
+            Command::Getrange(_) => "getrange",
+            Command::Substr(_) => "substr",
+            Command::Getdel(_) => "getdel",
+            Command::Getex(_) => "getex",
+            Command::Append(_) => "append",
+            Command::Setrange(_) => "setrange",
+            Command::Setbit(_) => "setbit",
+            Command::Getbit(_) => "getbit",
+            Command::Bitcount(_) => "bitcount",
+            Command::Bitpos(_) => "bitpos",
+            Command::Bitop(_) => "bitop",
+            Command::Bitfield(_) => "bitfield",
+            Command::Sort(_) => "sort",
+            Command::Object(_) => "object",
+            Command::Randomkey(_) => "randomkey",
+            Command::Copy(_) => "copy",
+            Command::Move(_) => "tidis.move",
+            Command::Dump(_) => "dump",
+            Command::Restore(_) => "restore",
+            Command::Migrate(_) => "migrate",
+            Command::Throttle(_) => "cl.throttle",
+            Command::Lock(_) => "lock",
+            Command::Extend(_) => "extend",
+            Command::Unlock(_) => "unlock",
             Command::Hset(_) => "hset",
             Command::Hmset(_) => "hmset",
             Command::Hsetnx(_) => "hsetnx",
@@ -840,6 +1646,9 @@ impl Command {
             Command::Lrange(_) => "lrange",
             Command::Llen(_) => "llen",
             Command::Lindex(_) => "lindex",
+            Command::Lpos(_) => "lpos",
+            Command::Lmpop(_) => "lmpop",
+            Command::Blmpop(_) => "blmpop",
             Command::Lset(_) => "lset",
             Command::Ltrim(_) => "ltrim",
             Command::Lrem(_) => "lrem",
@@ -854,10 +1663,14 @@ impl Command {
             Command::Smembers(_) => "smembers",
             Command::Srandmember(_) => "srandmember",
             Command::Spop(_) => "spop",
+            Command::Sdrain(_) => "sdrain",
             Command::Srem(_) => "srem",
+            Command::Sinter(_) => "sinter",
+            Command::Sintercard(_) => "sintercard",
             Command::Zadd(_) => "zadd",
             Command::Zcard(_) => "zcard",
             Command::Zscore(_) => "zscore",
+            Command::Zmscore(_) => "zmscore",
             Command::Zrem(_) => "zrem",
             Command::Zremrangebyscore(_) => "zremrangebyscore",
             Command::Zremrangebyrank(_) => "zremrangebyrank",
@@ -868,21 +1681,353 @@ impl Command {
             Command::Zcount(_) => "zcount",
             Command::Zpopmin(_) => "zpopmin",
             Command::Zpopmax(_) => "zpopmax",
+            Command::Zmpop(_) => "zmpop",
+            Command::Bzmpop(_) => "bzmpop",
+            Command::Zrangestore(_) => "zrangestore",
             Command::Zrank(_) => "zrank",
             Command::Zincryby(_) => "zincrby",
+            Command::Zinter(_) => "zinter",
+            Command::Zunion(_) => "zunion",
+            Command::Zdiff(_) => "zdiff",
+            Command::Zinterstore(_) => "zinterstore",
+            Command::Zunionstore(_) => "zunionstore",
+            Command::Zdiffstore(_) => "zdiffstore",
+            Command::Zintercard(_) => "zintercard",
+            Command::Geoadd(_) => "geoadd",
+            Command::Geopos(_) => "geopos",
+            Command::Geodist(_) => "geodist",
+            Command::Geosearch(_) => "geosearch",
+            Command::Xadd(_) => "xadd",
+            Command::Xlen(_) => "xlen",
+            Command::Xrange(_) => "xrange",
+            Command::Xrevrange(_) => "xrevrange",
+            Command::Xread(_) => "xread",
+            Command::Xgroup(_) => "xgroup",
+            Command::Xreadgroup(_) => "xreadgroup",
+            Command::Xack(_) => "xack",
+            Command::Xpending(_) => "xpending",
+            Command::Xclaim(_) => "xclaim",
+            Command::Jsonset(_) => "json.set",
+            Command::Jsonget(_) => "json.get",
+            Command::Jsondel(_) => "json.del",
+            Command::Hindex(_) => "hindex",
             Command::Auth(_) => "auth",
             Command::Debug(_) => "debug",
+            Command::Ttlforecast(_) => "ttlforecast",
+            Command::Config(_) => "config",
             Command::Cluster(_) => "cluster",
+            Command::Route(_) => "tidis.route",
+            Command::Rpclog(_) => "tidis.rpclog",
+            Command::Snapshot(_) => "tidis.snapshot",
+            Command::Format(_) => "tidis.format",
             Command::ReadWrite(_) => "readwrite",
             Command::ReadOnly(_) => "readonly",
+            Command::Idempotency(_) => "idempotency",
             Command::Client(_) => "client",
             Command::Info(_) => "info",
             Command::Multi(_) => "multi",
             Command::Exec(_) => "exec",
             Command::Discard(_) => "discard",
+            Command::Watch(_) => "watch",
+            Command::Unwatch(_) => "unwatch",
             Command::Scan(_) => "scan",
             Command::Xscan(_) => "xscan",
             Command::Unknown(cmd) => cmd.get_name(),
+            Command::WrongArity(cmd) => cmd.get_name(),
+        }
+    }
+
+    /// Returns true if the command may modify the keyspace, such commands are
+    /// rejected on connections in READONLY mode. Scripts are treated as writes,
+    /// since `redis.call` can issue any command.
+    pub(crate) fn is_write(&self) -> bool {
+        matches!(
+            self,
+            Command::Del(_)
+                | Command::Set(_)
+                | Command::SetNX(_)
+                | Command::SetEX(_)
+                | Command::Mset(_)
+                | Command::Expire(_)
+                | Command::ExpireAt(_)
+                | Command::Pexpire(_)
+                | Command::PexpireAt(_)
+                | Command::Persist(_)
+                | Command::Incr(_)
+                | Command::Decr(_)
+                | Command::IncrBy(_)
+                | Command::DecrBy(_)
+                | Command::Append(_)
+                | Command::Setrange(_)
+                | Command::Setbit(_)
+                | Command::Bitop(_)
+                | Command::Bitfield(_)
+                | Command::Getdel(_)
+                | Command::Getex(_)
+                | Command::Throttle(_)
+                | Command::Lock(_)
+                | Command::Extend(_)
+                | Command::Unlock(_)
+                | Command::Hset(_)
+                | Command::Hmset(_)
+                | Command::Hsetnx(_)
+                | Command::Hdel(_)
+                | Command::Hincrby(_)
+                | Command::Lpush(_)
+                | Command::Rpush(_)
+                | Command::Lpop(_)
+                | Command::Rpop(_)
+                | Command::Lmpop(_)
+                | Command::Blmpop(_)
+                | Command::Lset(_)
+                | Command::Ltrim(_)
+                | Command::Lrem(_)
+                | Command::Linsert(_)
+                | Command::Sadd(_)
+                | Command::Spop(_)
+                | Command::Sdrain(_)
+                | Command::Srem(_)
+                | Command::Zadd(_)
+                | Command::Zrem(_)
+                | Command::Zremrangebyscore(_)
+                | Command::Zremrangebyrank(_)
+                | Command::Zpopmin(_)
+                | Command::Zpopmax(_)
+                | Command::Zmpop(_)
+                | Command::Bzmpop(_)
+                | Command::Zrangestore(_)
+                | Command::Zinterstore(_)
+                | Command::Zunionstore(_)
+                | Command::Zdiffstore(_)
+                | Command::Zincryby(_)
+                | Command::Geoadd(_)
+                | Command::Xadd(_)
+                | Command::Xgroup(_)
+                | Command::Xreadgroup(_)
+                | Command::Xack(_)
+                | Command::Xclaim(_)
+                | Command::Jsonset(_)
+                | Command::Jsondel(_)
+                | Command::Eval(_)
+                | Command::Evalsha(_)
+                | Command::Copy(_)
+                | Command::Move(_)
+                | Command::Restore(_)
+        ) || matches!(self, Command::Sort(cmd) if cmd.store().is_some())
+            || matches!(self, Command::Migrate(cmd) if !cmd.copy())
+            || matches!(self, Command::Format(cmd) if cmd.upgrades())
+    }
+
+    /// Returns the data type of the keys written by the command, None if it
+    /// does not write or works on keys of any type.
+    pub(crate) fn written_type(&self) -> Option<DataType> {
+        match self {
+            Command::Set(_)
+            | Command::SetNX(_)
+            | Command::SetEX(_)
+            | Command::Mset(_)
+            | Command::Incr(_)
+            | Command::Decr(_)
+            | Command::IncrBy(_)
+            | Command::DecrBy(_)
+            | Command::Append(_)
+            | Command::Getdel(_)
+            | Command::Getex(_)
+            | Command::Setrange(_)
+            | Command::Setbit(_)
+            | Command::Bitop(_)
+            | Command::Bitfield(_)
+            | Command::Throttle(_) => Some(DataType::String),
+            Command::Hset(_)
+            | Command::Hmset(_)
+            | Command::Hsetnx(_)
+            | Command::Hdel(_)
+            | Command::Hincrby(_) => Some(DataType::Hash),
+            Command::Lpush(_)
+            | Command::Rpush(_)
+            | Command::Lpop(_)
+            | Command::Rpop(_)
+            | Command::Lmpop(_)
+            | Command::Blmpop(_)
+            | Command::Lset(_)
+            | Command::Ltrim(_)
+            | Command::Lrem(_)
+            | Command::Linsert(_) => Some(DataType::List),
+            Command::Sadd(_) | Command::Spop(_) | Command::Sdrain(_) | Command::Srem(_) => {
+                Some(DataType::Set)
+            }
+            Command::Zadd(_)
+            | Command::Zrem(_)
+            | Command::Zremrangebyscore(_)
+            | Command::Zremrangebyrank(_)
+            | Command::Zpopmin(_)
+            | Command::Zpopmax(_)
+            | Command::Zmpop(_)
+            | Command::Bzmpop(_)
+            | Command::Zrangestore(_)
+            | Command::Zinterstore(_)
+            | Command::Zunionstore(_)
+            | Command::Zdiffstore(_)
+            | Command::Zincryby(_)
+            | Command::Geoadd(_) => Some(DataType::Zset),
+            Command::Xadd(_)
+            | Command::Xgroup(_)
+            | Command::Xreadgroup(_)
+            | Command::Xack(_)
+            | Command::Xclaim(_) => Some(DataType::Stream),
+            Command::Jsonset(_) | Command::Jsondel(_) => Some(DataType::Json),
+            Command::Sort(cmd) if cmd.store().is_some() => Some(DataType::List),
+            _ => None,
+        }
+    }
+
+    /// Returns the priority class of the command, see `crate::priority`.
+    pub(crate) fn priority(&self) -> Priority {
+        match self {
+            Command::Ping(_)
+            | Command::Auth(_)
+            | Command::Debug(_)
+            | Command::Config(_)
+            | Command::Cluster(_)
+            | Command::Route(_)
+            | Command::Rpclog(_)
+            | Command::Snapshot(_)
+            | Command::Format(_)
+            | Command::ReadWrite(_)
+            | Command::ReadOnly(_)
+            | Command::Idempotency(_)
+            | Command::Client(_)
+            | Command::Info(_)
+            | Command::Script(_)
+            | Command::Multi(_)
+            | Command::Discard(_)
+            | Command::Unwatch(_) => Priority::High,
+            Command::Scan(_)
+            | Command::Xscan(_)
+            | Command::Hgetall(_)
+            | Command::Hkeys(_)
+            | Command::Hvals(_)
+            | Command::Smembers(_)
+            | Command::Sinter(_)
+            | Command::Zinter(_)
+            | Command::Zunion(_)
+            | Command::Zdiff(_)
+            | Command::Zinterstore(_)
+            | Command::Zunionstore(_)
+            | Command::Zdiffstore(_)
+            | Command::Lrange(_)
+            | Command::Zrange(_)
+            | Command::Zrevrange(_)
+            | Command::Zrangebyscore(_)
+            | Command::Zrevrangebyscore(_)
+            | Command::Zrangestore(_)
+            | Command::Geosearch(_)
+            | Command::Xrange(_)
+            | Command::Xrevrange(_)
+            | Command::Xread(_)
+            | Command::Xreadgroup(_)
+            | Command::Xpending(_)
+            | Command::Hindex(_)
+            | Command::Sort(_)
+            | Command::Copy(_)
+            | Command::Move(_)
+            | Command::Dump(_)
+            | Command::Restore(_)
+            | Command::Migrate(_)
+            | Command::Ttlforecast(_) => Priority::Low,
+            _ => Priority::Normal,
+        }
+    }
+
+    /// Returns true if the command only reads the keyspace, so it can share
+    /// a snapshot with other reads of the same pipeline burst.
+    pub(crate) fn is_read(&self) -> bool {
+        matches!(
+            self,
+            Command::Get(_)
+                | Command::Mget(_)
+                | Command::Strlen(_)
+                | Command::Getrange(_)
+                | Command::Substr(_)
+                | Command::Getbit(_)
+                | Command::Bitcount(_)
+                | Command::Bitpos(_)
+                | Command::Type(_)
+                | Command::TTL(_)
+                | Command::PTTL(_)
+                | Command::Exists(_)
+                | Command::Hget(_)
+                | Command::Hmget(_)
+                | Command::Hlen(_)
+                | Command::Hgetall(_)
+                | Command::Hkeys(_)
+                | Command::Hvals(_)
+                | Command::Hexists(_)
+                | Command::Hstrlen(_)
+                | Command::Lrange(_)
+                | Command::Llen(_)
+                | Command::Lindex(_)
+                | Command::Lpos(_)
+                | Command::Scard(_)
+                | Command::Sismember(_)
+                | Command::Smismember(_)
+                | Command::Smembers(_)
+                | Command::Srandmember(_)
+                | Command::Sinter(_)
+                | Command::Sintercard(_)
+                | Command::Zcard(_)
+                | Command::Zscore(_)
+                | Command::Zmscore(_)
+                | Command::Zrange(_)
+                | Command::Zrevrange(_)
+                | Command::Zrangebyscore(_)
+                | Command::Zrevrangebyscore(_)
+                | Command::Zcount(_)
+                | Command::Zrank(_)
+                | Command::Zinter(_)
+                | Command::Zunion(_)
+                | Command::Zdiff(_)
+                | Command::Zintercard(_)
+                | Command::Geopos(_)
+                | Command::Geodist(_)
+                | Command::Geosearch(_)
+                | Command::Xlen(_)
+                | Command::Xrange(_)
+                | Command::Xrevrange(_)
+                | Command::Xread(_)
+                | Command::Xpending(_)
+                | Command::Jsonget(_)
+                | Command::Hindex(_)
+                | Command::Scan(_)
+                | Command::Xscan(_)
+                | Command::Randomkey(_)
+                | Command::Dump(_)
+        )
+    }
+
+    /// Returns true if the first argument of the command is a key whose
+    /// access is counted for OBJECT IDLETIME and FREQ.
+    pub(crate) fn tracks_access(&self) -> bool {
+        if matches!(
+            self,
+            Command::Scan(_)
+                | Command::Xscan(_)
+                | Command::Eval(_)
+                | Command::Evalsha(_)
+                | Command::Xread(_)
+                | Command::Xreadgroup(_)
+                | Command::Zinter(_)
+                | Command::Zunion(_)
+                | Command::Zdiff(_)
+                | Command::Zinterstore(_)
+                | Command::Zunionstore(_)
+                | Command::Zdiffstore(_)
+                | Command::Zintercard(_)
+                | Command::Sintercard(_)
+                | Command::Bitop(_)
+        ) {
+            return false;
         }
+        self.is_read() || self.is_write()
     }
 }
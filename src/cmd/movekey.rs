@@ -0,0 +1,92 @@
+use std::sync::Arc;
+
+use crate::cmd::{Invalid, Parse};
+use crate::config::is_use_txn_api;
+use crate::tikv::copy::CopyCommandCtx;
+use crate::tikv::errors::{AsyncResult, REDIS_NOT_SUPPORTED_ERR};
+use crate::utils::{resp_err, resp_invalid_arguments};
+use crate::{Connection, Frame};
+
+use crate::config::LOGGER;
+use crate::tikv::backend::Transaction;
+use bytes::Bytes;
+use slog::debug;
+use tokio::sync::Mutex;
+
+/// `TIDIS.MOVE key namespace`, move the key of any type to the namespace
+/// with the `instance_id` given, like MOVE does to another db of Redis.
+#[derive(Debug, Clone)]
+pub struct MoveKey {
+    key: String,
+    namespace: u16,
+    valid: bool,
+}
+
+impl MoveKey {
+    pub fn key(&self) -> &str {
+        &self.key
+    }
+
+    pub(crate) fn parse_frames(parse: &mut Parse) -> crate::Result<MoveKey> {
+        let mut args = vec![];
+        while let Ok(arg) = parse.next_bytes() {
+            args.push(arg);
+        }
+        Ok(MoveKey::from_args(&args))
+    }
+
+    pub(crate) fn parse_argv(argv: &Vec<Bytes>) -> crate::Result<MoveKey> {
+        Ok(MoveKey::from_args(argv))
+    }
+
+    fn from_args(args: &[Bytes]) -> MoveKey {
+        if args.len() != 2 {
+            return MoveKey::new_invalid();
+        }
+        match String::from_utf8_lossy(&args[1]).parse::<u16>() {
+            Ok(namespace) => MoveKey {
+                key: String::from_utf8_lossy(&args[0]).to_string(),
+                namespace,
+                valid: true,
+            },
+            Err(_) => MoveKey::new_invalid(),
+        }
+    }
+
+    pub(crate) async fn apply(self, dst: &mut Connection) -> crate::Result<()> {
+        let response = self.move_key(None).await.unwrap_or_else(Into::into);
+        debug!(
+            LOGGER,
+            "res, {} -> {}, {:?}",
+            dst.local_addr(),
+            dst.peer_addr(),
+            response
+        );
+        dst.write_frame(&response).await?;
+
+        Ok(())
+    }
+
+    pub async fn move_key(&self, txn: Option<Arc<Mutex<Transaction>>>) -> AsyncResult<Frame> {
+        if !self.valid {
+            return Ok(resp_invalid_arguments());
+        }
+        if is_use_txn_api() {
+            CopyCommandCtx::new(txn)
+                .do_async_txnkv_move(&self.key, self.namespace)
+                .await
+        } else {
+            Ok(resp_err(REDIS_NOT_SUPPORTED_ERR))
+        }
+    }
+}
+
+impl Invalid for MoveKey {
+    fn new_invalid() -> MoveKey {
+        MoveKey {
+            key: "".to_string(),
+            namespace: 0,
+            valid: false,
+        }
+    }
+}
@@ -1,12 +1,13 @@
 use std::sync::Arc;
 
 use crate::config::is_use_txn_api;
+use crate::tikv::backend::Transaction;
 use crate::tikv::errors::AsyncResult;
 use crate::tikv::string::StringCommandCtx;
 use crate::tikv::KEY_ENCODER;
-use crate::utils::resp_invalid_arguments;
+use crate::utils::{expire_timestamp_of_new_key, resp_invalid_arguments};
 use crate::{Connection, Frame, Parse};
-use tikv_client::{KvPair, Transaction};
+use tikv_client::KvPair;
 use tokio::sync::Mutex;
 
 use crate::cmd::Invalid;
@@ -98,7 +99,10 @@ impl Mset {
         let mut kvs = Vec::new();
         if is_use_txn_api() {
             for (idx, key) in self.keys.iter().enumerate() {
-                let val = KEY_ENCODER.encode_txnkv_string_value(&mut self.vals[idx].to_vec(), 0);
+                let val = KEY_ENCODER.encode_txnkv_string_value(
+                    &mut self.vals[idx].to_vec(),
+                    expire_timestamp_of_new_key(0),
+                )?;
                 let ekey = KEY_ENCODER.encode_txnkv_string(key);
                 let kvpair = KvPair::from((ekey, val.to_vec()));
                 kvs.push(kvpair);
@@ -1,12 +1,21 @@
 use std::sync::Arc;
 
 use slog::{debug, error};
+use tikv_client::Error;
 use tokio::sync::Mutex;
 
 use crate::{
+    cmd::watch::WatchedKey,
+    cmdlog::Reply,
     config::LOGGER,
-    tikv::{errors::REDIS_EXEC_ERR, get_txn_client},
-    utils::{resp_array, resp_err, resp_invalid_arguments, resp_nil},
+    tikv::{
+        backend::Transaction,
+        errors::{AsyncResult, REDIS_EXEC_ERR},
+        get_txn_client,
+        stamp::stamp_key,
+        KEY_ENCODER,
+    },
+    utils::{resp_array, resp_err, resp_nil},
     Command, Connection, Frame,
 };
 
@@ -18,7 +27,14 @@ impl Multi {
         Multi {}
     }
 
-    pub async fn exec(self, dst: &mut Connection, cmds: Vec<Command>) -> crate::Result<()> {
+    /// Execute the queued commands in one transaction, and reply. Returns what
+    /// the replies of the commands tell once committed, none if aborted.
+    pub async fn exec(
+        self,
+        dst: &mut Connection,
+        cmds: Vec<Command>,
+        watched: Vec<WatchedKey>,
+    ) -> crate::Result<Vec<Reply>> {
         let mut resp_arr = Vec::with_capacity(cmds.len());
 
         // create new txn
@@ -26,83 +42,18 @@ impl Multi {
         let txn = client.begin().await?;
         let txn_rc = Some(Arc::new(Mutex::new(txn)));
 
+        if !Multi::check_watched(txn_rc.clone().unwrap(), &watched).await? {
+            txn_rc.unwrap().lock().await.rollback().await?;
+            Multi::write_aborted(dst).await?;
+            return Ok(vec![]);
+        }
+
         let mut response = resp_nil();
+        let mut replies = vec![];
         let mut abort_on_error = false;
 
         for cmd in cmds {
-            let result = match cmd {
-                Command::Incr(mut cmd) => cmd.incr_by(txn_rc.clone(), true).await,
-                Command::IncrBy(mut cmd) => cmd.incr_by(txn_rc.clone(), true).await,
-                Command::Decr(mut cmd) => cmd.incr_by(txn_rc.clone(), false).await,
-                Command::DecrBy(mut cmd) => cmd.incr_by(txn_rc.clone(), false).await,
-                Command::Strlen(cmd) => cmd.strlen(txn_rc.clone()).await,
-                Command::Del(cmd) => cmd.del(txn_rc.clone()).await,
-                Command::Exists(cmd) => cmd.exists(txn_rc.clone()).await,
-                Command::Get(cmd) => cmd.get(txn_rc.clone()).await,
-                Command::Set(cmd) => cmd.set(txn_rc.clone()).await,
-                Command::SetNX(cmd) => cmd.put_not_exists(txn_rc.clone()).await,
-                Command::SetEX(cmd) => cmd.setex(txn_rc.clone()).await,
-                Command::Mget(cmd) => cmd.batch_get(txn_rc.clone()).await,
-                Command::Mset(cmd) => cmd.batch_put(txn_rc.clone()).await,
-                Command::Type(cmd) => cmd.cmd_type(txn_rc.clone()).await,
-                Command::TTL(cmd) => cmd.ttl(false, txn_rc.clone()).await,
-                Command::PTTL(cmd) => cmd.ttl(true, txn_rc.clone()).await,
-                Command::Expire(cmd) => cmd.expire(false, false, txn_rc.clone()).await,
-                Command::ExpireAt(cmd) => cmd.expire(false, true, txn_rc.clone()).await,
-                Command::Pexpire(cmd) => cmd.expire(true, false, txn_rc.clone()).await,
-                Command::PexpireAt(cmd) => cmd.expire(true, true, txn_rc.clone()).await,
-                Command::Persist(cmd) => cmd.persist(txn_rc.clone()).await,
-                Command::Hset(cmd) => cmd.hset(txn_rc.clone(), false, false).await,
-                Command::Hmset(cmd) => cmd.hset(txn_rc.clone(), true, false).await,
-                Command::Hsetnx(cmd) => cmd.hset(txn_rc.clone(), false, true).await,
-                Command::Hget(cmd) => cmd.hget(txn_rc.clone()).await,
-                Command::Hmget(cmd) => cmd.hmget(txn_rc.clone()).await,
-                Command::Hlen(cmd) => cmd.hlen(txn_rc.clone()).await,
-                Command::Hgetall(cmd) => cmd.hgetall(txn_rc.clone()).await,
-                Command::Hdel(cmd) => cmd.hdel(txn_rc.clone()).await,
-                Command::Hkeys(cmd) => cmd.hkeys(txn_rc.clone()).await,
-                Command::Hvals(cmd) => cmd.hvals(txn_rc.clone()).await,
-                Command::Hincrby(cmd) => cmd.hincrby(txn_rc.clone()).await,
-                Command::Hexists(cmd) => cmd.hexists(txn_rc.clone()).await,
-                Command::Hstrlen(cmd) => cmd.hstrlen(txn_rc.clone()).await,
-                Command::Lpush(cmd) => cmd.push(txn_rc.clone(), true).await,
-                Command::Rpush(cmd) => cmd.push(txn_rc.clone(), false).await,
-                Command::Lpop(cmd) => cmd.pop(txn_rc.clone(), true).await,
-                Command::Rpop(cmd) => cmd.pop(txn_rc.clone(), false).await,
-                Command::Lrange(cmd) => cmd.lrange(txn_rc.clone()).await,
-                Command::Llen(cmd) => cmd.llen(txn_rc.clone()).await,
-                Command::Lindex(cmd) => cmd.lindex(txn_rc.clone()).await,
-                Command::Lset(cmd) => cmd.lset(txn_rc.clone()).await,
-                Command::Ltrim(cmd) => cmd.ltrim(txn_rc.clone()).await,
-                Command::Lrem(cmd) => cmd.lrem(txn_rc.clone()).await,
-                Command::Linsert(cmd) => cmd.linsert(txn_rc.clone()).await,
-                Command::Sadd(cmd) => cmd.sadd(txn_rc.clone()).await,
-                Command::Scard(cmd) => cmd.scard(txn_rc.clone()).await,
-                Command::Sismember(cmd) => cmd.sismember(txn_rc.clone()).await,
-                Command::Smismember(cmd) => cmd.smismember(txn_rc.clone()).await,
-                Command::Smembers(cmd) => cmd.smembers(txn_rc.clone()).await,
-                Command::Srandmember(cmd) => cmd.srandmember(txn_rc.clone()).await,
-                Command::Spop(cmd) => cmd.spop(txn_rc.clone()).await,
-                Command::Srem(cmd) => cmd.srem(txn_rc.clone()).await,
-                Command::Zadd(cmd) => cmd.zadd(txn_rc.clone()).await,
-                Command::Zcard(cmd) => cmd.zcard(txn_rc.clone()).await,
-                Command::Zscore(cmd) => cmd.zscore(txn_rc.clone()).await,
-                Command::Zrem(cmd) => cmd.zrem(txn_rc.clone()).await,
-                Command::Zremrangebyscore(cmd) => cmd.zremrangebyscore(txn_rc.clone()).await,
-                Command::Zremrangebyrank(cmd) => cmd.zremrangebyrank(txn_rc.clone()).await,
-                Command::Zrange(cmd) => cmd.zrange(txn_rc.clone()).await,
-                Command::Zrevrange(cmd) => cmd.zrevrange(txn_rc.clone()).await,
-                Command::Zrangebyscore(cmd) => cmd.zrangebyscore(txn_rc.clone(), false).await,
-                Command::Zrevrangebyscore(cmd) => cmd.zrangebyscore(txn_rc.clone(), true).await,
-                Command::Zcount(cmd) => cmd.zcount(txn_rc.clone()).await,
-                Command::Zpopmin(cmd) => cmd.zpop(txn_rc.clone(), true).await,
-                Command::Zpopmax(cmd) => cmd.zpop(txn_rc.clone(), false).await,
-                Command::Zrank(cmd) => cmd.zrank(txn_rc.clone()).await,
-                Command::Zincryby(cmd) => cmd.zincrby(txn_rc.clone()).await,
-                Command::Scan(cmd) => cmd.scan(txn_rc.clone()).await,
-                Command::Xscan(cmd) => cmd.scan(txn_rc.clone()).await,
-                _ => Ok(resp_invalid_arguments()),
-            };
+            let result = cmd.execute(txn_rc.clone()).await;
             match result {
                 Ok(resp) => {
                     // check response error
@@ -125,12 +76,81 @@ impl Multi {
         }
 
         if !abort_on_error {
+            replies = resp_arr.iter().map(Reply::of).collect();
             response = resp_array(resp_arr);
-            txn_rc.unwrap().lock().await.commit().await?;
+            let committed = txn_rc.unwrap().lock().await.commit().await;
+            match committed {
+                Ok(_) => {}
+                // a watched key was written after it was checked
+                Err(e) if Multi::is_watched_conflict(&e, &watched) => {
+                    error!(LOGGER, "EXEC of watched keys failed to commit {}", e);
+                    Multi::write_aborted(dst).await?;
+                    return Ok(vec![]);
+                }
+                Err(e) => return Err(e.into()),
+            }
         } else {
             txn_rc.unwrap().lock().await.rollback().await?;
         }
 
+        debug!(
+            LOGGER,
+            "res, {} -> {}, {:?}",
+            dst.local_addr(),
+            dst.peer_addr(),
+            response
+        );
+        dst.write_frame(&response).await?;
+        Ok(replies)
+    }
+
+    /// Whether the commit failed on a write conflict on the meta key or the
+    /// stamp of one of the watched keys, other errors are not an abort of EXEC.
+    fn is_watched_conflict(err: &Error, watched: &[WatchedKey]) -> bool {
+        match err {
+            Error::KeyError(key_err) => key_err.conflict.as_ref().map_or(false, |conflict| {
+                watched.iter().any(|(key, _, _)| {
+                    Vec::<u8>::from(KEY_ENCODER.encode_txnkv_meta_key(key)) == conflict.key
+                        || Vec::<u8>::from(stamp_key(key)) == conflict.key
+                })
+            }),
+            Error::MultipleKeyErrors(errs) => {
+                errs.iter().any(|e| Multi::is_watched_conflict(e, watched))
+            }
+            Error::PessimisticLockError { inner, .. } => Multi::is_watched_conflict(inner, watched),
+            _ => false,
+        }
+    }
+
+    /// Returns false if a watched key has changed. The stamps of the watched
+    /// keys are written back, or deleted if they do not exist, so a write of
+    /// them committed after the snapshot of `txn` makes the commit of EXEC
+    /// fail, see `crate::tikv::stamp`.
+    async fn check_watched(
+        txn_rc: Arc<Mutex<Transaction>>,
+        watched: &[WatchedKey],
+    ) -> AsyncResult<bool> {
+        let mut txn = txn_rc.lock().await;
+        for (key, meta_value, stamp) in watched {
+            let meta_key = KEY_ENCODER.encode_txnkv_meta_key(key);
+            if txn.get(meta_key).await? != *meta_value {
+                return Ok(false);
+            }
+            let stamp_key = stamp_key(key);
+            let current = txn.get(stamp_key.clone()).await?;
+            if current != *stamp {
+                return Ok(false);
+            }
+            match current {
+                Some(current) => txn.put(stamp_key, current).await?,
+                None => txn.delete(stamp_key).await?,
+            }
+        }
+        Ok(true)
+    }
+
+    async fn write_aborted(dst: &mut Connection) -> crate::Result<()> {
+        let response = resp_nil();
         debug!(
             LOGGER,
             "res, {} -> {}, {:?}",
@@ -0,0 +1,123 @@
+use std::sync::Arc;
+
+use crate::access::{access_frequency, idle_time};
+use crate::cmd::{resp_help, Invalid, Parse};
+use crate::config::is_use_txn_api;
+use crate::tikv::errors::{
+    AsyncResult, REDIS_NOT_SUPPORTED_ERR, REDIS_OBJECT_ACCESS_NOT_TRACKED_ERR,
+    REDIS_UNKNOWN_SUBCOMMAND,
+};
+use crate::tikv::string::StringCommandCtx;
+use crate::utils::{resp_err, resp_int, resp_invalid_arguments};
+use crate::{Connection, Frame};
+
+use crate::config::LOGGER;
+use crate::tikv::backend::Transaction;
+use bytes::Bytes;
+use slog::debug;
+use tokio::sync::Mutex;
+
+/// `OBJECT ENCODING|REFCOUNT|IDLETIME|FREQ key`, the encoding is the layout
+/// of the meta of the key, IDLETIME and FREQ the accesses counted by this
+/// member, see `crate::access`.
+#[derive(Debug, Clone)]
+pub struct Object {
+    subcommand: String,
+    key: String,
+    valid: bool,
+}
+
+impl Object {
+    pub fn key(&self) -> &str {
+        &self.key
+    }
+
+    pub(crate) fn parse_frames(parse: &mut Parse) -> crate::Result<Object> {
+        let mut args = vec![];
+        while let Ok(arg) = parse.next_bytes() {
+            args.push(arg);
+        }
+        Ok(Object::from_args(&args))
+    }
+
+    pub(crate) fn parse_argv(argv: &Vec<Bytes>) -> crate::Result<Object> {
+        Ok(Object::from_args(argv))
+    }
+
+    fn from_args(args: &[Bytes]) -> Object {
+        let subcommand = match args.first() {
+            Some(subcommand) => String::from_utf8_lossy(subcommand).to_uppercase(),
+            None => return Object::new_invalid(),
+        };
+        let key = match (subcommand.as_str(), args.len()) {
+            ("HELP", 1) => "".to_owned(),
+            (_, 2) => String::from_utf8_lossy(&args[1]).to_string(),
+            _ => return Object::new_invalid(),
+        };
+        Object {
+            subcommand,
+            key,
+            valid: true,
+        }
+    }
+
+    pub(crate) async fn apply(self, dst: &mut Connection) -> crate::Result<()> {
+        let response = self.object(None).await.unwrap_or_else(Into::into);
+        debug!(
+            LOGGER,
+            "res, {} -> {}, {:?}",
+            dst.local_addr(),
+            dst.peer_addr(),
+            response
+        );
+        dst.write_frame(&response).await?;
+
+        Ok(())
+    }
+
+    pub async fn object(&self, txn: Option<Arc<Mutex<Transaction>>>) -> AsyncResult<Frame> {
+        if !self.valid {
+            return Ok(resp_invalid_arguments());
+        }
+        match self.subcommand.as_str() {
+            "HELP" => return Ok(resp_help("OBJECT")),
+            "ENCODING" | "REFCOUNT" | "IDLETIME" | "FREQ" => {}
+            _ => return Ok(resp_err(REDIS_UNKNOWN_SUBCOMMAND)),
+        }
+        if !is_use_txn_api() {
+            return Ok(resp_err(REDIS_NOT_SUPPORTED_ERR));
+        }
+
+        // nil for a missing key whatever the subcommand, like redis
+        let encoding = StringCommandCtx::new(txn)
+            .do_async_txnkv_object_encoding(&self.key)
+            .await?;
+        if !matches!(encoding, Frame::Bulk(_)) {
+            return Ok(encoding);
+        }
+        let key = self.key.as_bytes();
+        let response = match self.subcommand.as_str() {
+            "ENCODING" => encoding,
+            "REFCOUNT" => resp_int(1),
+            "IDLETIME" => match idle_time(key) {
+                Some(idle) => resp_int(idle as i64),
+                None => resp_err(REDIS_OBJECT_ACCESS_NOT_TRACKED_ERR),
+            },
+            _ => match access_frequency(key) {
+                Some(freq) => resp_int(freq as i64),
+                None => resp_err(REDIS_OBJECT_ACCESS_NOT_TRACKED_ERR),
+            },
+        };
+        Ok(response)
+    }
+}
+
+impl Invalid for Object {
+    fn new_invalid() -> Object {
+        Object {
+            subcommand: "".to_owned(),
+            key: "".to_owned(),
+            valid: false,
+        }
+    }
+}
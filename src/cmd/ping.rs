@@ -79,6 +79,15 @@ impl Ping {
 
         Ok(())
     }
+
+    /// Reply of a `PING` received in the subscribed state, an array of `pong`
+    /// and the message, empty if none.
+    pub(crate) fn into_subscribed_frame(self) -> Frame {
+        let mut response = Frame::array();
+        response.push_bulk(Bytes::from_static(b"pong"));
+        response.push_bulk(Bytes::from(self.msg.unwrap_or_default()));
+        response
+    }
 }
 
 impl Default for Ping {
@@ -1,5 +1,6 @@
 use std::sync::Arc;
 
+use crate::blocking::signal_key_ready;
 use crate::cmd::{Invalid, Parse};
 use crate::config::is_use_txn_api;
 use crate::tikv::errors::{AsyncResult, REDIS_NOT_SUPPORTED_ERR};
@@ -8,9 +9,9 @@ use crate::utils::{resp_err, resp_invalid_arguments};
 use crate::{Connection, Frame};
 
 use crate::config::LOGGER;
+use crate::tikv::backend::Transaction;
 use bytes::Bytes;
 use slog::debug;
-use tikv_client::Transaction;
 use tokio::sync::Mutex;
 
 #[derive(Debug, Clone)]
@@ -88,9 +89,13 @@ impl Push {
             return Ok(resp_invalid_arguments());
         }
         if is_use_txn_api() {
-            ListCommandCtx::new(txn)
+            let response = ListCommandCtx::new(txn)
                 .do_async_txnkv_push(&self.key, &self.items, op_left)
-                .await
+                .await?;
+            if matches!(response, Frame::Integer(_)) {
+                signal_key_ready(&self.key);
+            }
+            Ok(response)
         } else {
             Ok(resp_err(REDIS_NOT_SUPPORTED_ERR))
         }
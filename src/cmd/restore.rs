@@ -0,0 +1,146 @@
+use std::sync::Arc;
+
+use crate::cmd::{Invalid, Parse};
+use crate::config::is_use_txn_api;
+use crate::rdb::load;
+use crate::tikv::dump::DumpCommandCtx;
+use crate::tikv::errors::{AsyncResult, REDIS_INVALID_TTL_ERR, REDIS_NOT_SUPPORTED_ERR};
+use crate::utils::{resp_err, resp_invalid_arguments, timestamp_from_ttl};
+use crate::{Connection, Frame};
+
+use crate::config::LOGGER;
+use crate::tikv::backend::Transaction;
+use bytes::Bytes;
+use slog::debug;
+use tokio::sync::Mutex;
+
+/// `RESTORE key ttl serialized-value [REPLACE] [ABSTTL] [IDLETIME seconds]
+/// [FREQ frequency]`, the value of a DUMP of tidis or Redis. IDLETIME and
+/// FREQ are accepted but not restored, the accesses are counted by each
+/// member, see `crate::access`.
+#[derive(Debug, Clone)]
+pub struct Restore {
+    key: String,
+    ttl: i64,
+    payload: Bytes,
+    replace: bool,
+    absttl: bool,
+    valid: bool,
+}
+
+impl Restore {
+    pub fn key(&self) -> &str {
+        &self.key
+    }
+
+    pub(crate) fn parse_frames(parse: &mut Parse) -> crate::Result<Restore> {
+        let mut args = vec![];
+        while let Ok(arg) = parse.next_bytes() {
+            args.push(arg);
+        }
+        Ok(Restore::from_args(&args))
+    }
+
+    pub(crate) fn parse_argv(argv: &Vec<Bytes>) -> crate::Result<Restore> {
+        Ok(Restore::from_args(argv))
+    }
+
+    fn from_args(args: &[Bytes]) -> Restore {
+        if args.len() < 3 {
+            return Restore::new_invalid();
+        }
+        let ttl = match String::from_utf8_lossy(&args[1]).parse::<i64>() {
+            Ok(ttl) => ttl,
+            Err(_) => return Restore::new_invalid(),
+        };
+        let mut replace = false;
+        let mut absttl = false;
+        let (mut idletime, mut freq) = (false, false);
+        let mut idx = 3;
+        while idx < args.len() {
+            match String::from_utf8_lossy(&args[idx]).to_uppercase().as_str() {
+                "REPLACE" => replace = true,
+                "ABSTTL" => absttl = true,
+                "IDLETIME" | "FREQ" if idx + 1 < args.len() => {
+                    if String::from_utf8_lossy(&args[idx + 1])
+                        .parse::<u64>()
+                        .is_err()
+                    {
+                        return Restore::new_invalid();
+                    }
+                    if args[idx].eq_ignore_ascii_case(b"IDLETIME") {
+                        idletime = true;
+                    } else {
+                        freq = true;
+                    }
+                    idx += 1;
+                }
+                _ => return Restore::new_invalid(),
+            }
+            idx += 1;
+        }
+        // like Redis, the access of a key is either an idle time or a frequency
+        if idletime && freq {
+            return Restore::new_invalid();
+        }
+        Restore {
+            key: String::from_utf8_lossy(&args[0]).to_string(),
+            ttl,
+            payload: args[2].clone(),
+            replace,
+            absttl,
+            valid: true,
+        }
+    }
+
+    pub(crate) async fn apply(self, dst: &mut Connection) -> crate::Result<()> {
+        let response = self.restore(None).await.unwrap_or_else(Into::into);
+        debug!(
+            LOGGER,
+            "res, {} -> {}, {:?}",
+            dst.local_addr(),
+            dst.peer_addr(),
+            response
+        );
+        dst.write_frame(&response).await?;
+
+        Ok(())
+    }
+
+    pub async fn restore(&self, txn: Option<Arc<Mutex<Transaction>>>) -> AsyncResult<Frame> {
+        if !self.valid {
+            return Ok(resp_invalid_arguments());
+        }
+        if self.ttl < 0 {
+            return Ok(resp_err(REDIS_INVALID_TTL_ERR));
+        }
+        if !is_use_txn_api() {
+            return Ok(resp_err(REDIS_NOT_SUPPORTED_ERR));
+        }
+        let value = match load(&self.payload) {
+            Ok(value) => value,
+            Err(e) => return Ok(resp_err(e)),
+        };
+        let timestamp = match (self.ttl as u64, self.absttl) {
+            (0, _) => 0,
+            (ttl, true) => ttl,
+            (ttl, false) => timestamp_from_ttl(ttl),
+        };
+        DumpCommandCtx::new(txn)
+            .do_async_txnkv_restore(&self.key, value, timestamp, self.replace)
+            .await
+    }
+}
+
+impl Invalid for Restore {
+    fn new_invalid() -> Restore {
+        Restore {
+            key: "".to_string(),
+            ttl: 0,
+            payload: Bytes::new(),
+            replace: false,
+            absttl: false,
+            valid: false,
+        }
+    }
+}
@@ -0,0 +1,117 @@
+use crate::cluster::{key_hashtag, key_slot, Cluster as Topo};
+use crate::cmd::{Invalid, Parse};
+use crate::config::LOGGER;
+use crate::config::{backend_storage_or_default, config_instance_id_or_default, is_use_txn_api};
+use crate::tikv::KEY_ENCODER;
+use crate::utils::{resp_array, resp_bulk, resp_int, resp_invalid_arguments, resp_nil};
+use crate::{Connection, Frame};
+
+use bytes::Bytes;
+use slog::debug;
+
+/// `TIDIS.ROUTE key`, where the key is stored and which member serves it:
+/// the namespace and the TiKV key of its meta, of which the prefix is shared
+/// by all the keys of the namespace, and the hashtag, slot and owner of the
+/// key in the cluster. Nothing is read from the backend.
+#[derive(Debug, Clone)]
+pub struct Route {
+    key: String,
+    valid: bool,
+}
+
+impl Route {
+    pub fn key(&self) -> &str {
+        &self.key
+    }
+
+    pub(crate) fn parse_frames(parse: &mut Parse) -> crate::Result<Route> {
+        let mut args = vec![];
+        while let Ok(arg) = parse.next_bytes() {
+            args.push(arg);
+        }
+        Ok(Route::from_args(&args))
+    }
+
+    fn from_args(args: &[Bytes]) -> Route {
+        if args.len() != 1 {
+            return Route::new_invalid();
+        }
+        Route {
+            key: String::from_utf8_lossy(&args[0]).to_string(),
+            valid: true,
+        }
+    }
+
+    pub(crate) async fn apply(self, topo: &Topo, dst: &mut Connection) -> crate::Result<()> {
+        let response = self.route(topo);
+        debug!(
+            LOGGER,
+            "res, {} -> {}, {:?}",
+            dst.local_addr(),
+            dst.peer_addr(),
+            response
+        );
+        dst.write_frame(&response).await?;
+
+        Ok(())
+    }
+
+    fn route(&self, topo: &Topo) -> Frame {
+        if !self.valid {
+            return resp_invalid_arguments();
+        }
+        let ekey: Vec<u8> = if is_use_txn_api() {
+            KEY_ENCODER.encode_txnkv_meta_key(&self.key).into()
+        } else {
+            KEY_ENCODER.encode_rawkv_string(&self.key).into()
+        };
+        // the key type prefix and the 2 bytes of the instance id
+        let prefix = &ekey[..3];
+        let slot = key_slot(self.key.as_bytes());
+        let (slot_start, slot_end) = topo.myself_owned_slots();
+        let (node, addr) = match topo.slot_owner(slot) {
+            Some((id, addr)) => (resp_bulk(id.into_bytes()), resp_bulk(addr.into_bytes())),
+            None => (resp_nil(), resp_nil()),
+        };
+
+        let hashtag = key_hashtag(self.key.as_bytes());
+        let fields = vec![
+            (
+                "namespace",
+                resp_bulk(config_instance_id_or_default().into_bytes()),
+            ),
+            (
+                "backend",
+                resp_bulk(backend_storage_or_default().into_bytes()),
+            ),
+            ("prefix", resp_bulk(hex::encode(prefix).into_bytes())),
+            ("encoded_key", resp_bulk(hex::encode(&ekey).into_bytes())),
+            (
+                "hashtag",
+                hashtag.map_or_else(resp_nil, |tag| resp_bulk(tag.to_vec())),
+            ),
+            ("slot", resp_int(slot as i64)),
+            ("node", node),
+            ("addr", addr),
+            (
+                "local",
+                resp_int((slot_start <= slot && slot <= slot_end) as i64),
+            ),
+        ];
+        resp_array(
+            fields
+                .into_iter()
+                .flat_map(|(name, value)| vec![resp_bulk(name.as_bytes().to_vec()), value])
+                .collect(),
+        )
+    }
+}
+
+impl Invalid for Route {
+    fn new_invalid() -> Route {
+        Route {
+            key: "".to_owned(),
+            valid: false,
+        }
+    }
+}
@@ -0,0 +1,81 @@
+use crate::cmd::{resp_help, Invalid, Parse};
+use crate::config::LOGGER;
+use crate::tikv::rpclog::{rpc_log_entries, rpc_log_len, rpc_log_reset};
+use crate::utils::{resp_int, resp_invalid_arguments, resp_ok};
+use crate::{Connection, Frame};
+
+use slog::debug;
+
+/// Entries replied by GET without a count, like SLOWLOG
+const DEFAULT_GET_COUNT: usize = 10;
+
+/// `TIDIS.RPCLOG GET [count] | LEN | RESET | HELP`, the slowest storage
+/// calls of the transactions, see `crate::tikv::rpclog`.
+#[derive(Debug, Clone)]
+pub struct Rpclog {
+    subcommand: String,
+    count: usize,
+    valid: bool,
+}
+
+impl Rpclog {
+    pub(crate) fn parse_frames(parse: &mut Parse) -> crate::Result<Rpclog> {
+        let subcommand = match parse.next_string() {
+            Ok(subcommand) => subcommand.to_lowercase(),
+            Err(_) => return Ok(Rpclog::new_invalid()),
+        };
+        let mut count = DEFAULT_GET_COUNT;
+        if let Ok(arg) = parse.next_string() {
+            // a negative count is all the entries
+            match arg.parse::<i64>() {
+                Ok(n) if subcommand == "get" => count = if n < 0 { usize::MAX } else { n as usize },
+                _ => return Ok(Rpclog::new_invalid()),
+            }
+        }
+        Ok(Rpclog {
+            subcommand,
+            count,
+            valid: true,
+        })
+    }
+
+    pub(crate) async fn apply(self, dst: &mut Connection) -> crate::Result<()> {
+        let response = self.rpclog();
+        debug!(
+            LOGGER,
+            "res, {} -> {}, {:?}",
+            dst.local_addr(),
+            dst.peer_addr(),
+            response
+        );
+        dst.write_frame(&response).await?;
+
+        Ok(())
+    }
+
+    fn rpclog(&self) -> Frame {
+        if !self.valid {
+            return resp_invalid_arguments();
+        }
+        match self.subcommand.as_str() {
+            "get" => rpc_log_entries(self.count),
+            "len" => resp_int(rpc_log_len() as i64),
+            "reset" => {
+                rpc_log_reset();
+                resp_ok()
+            }
+            "help" => resp_help("TIDIS.RPCLOG"),
+            _ => resp_invalid_arguments(),
+        }
+    }
+}
+
+impl Invalid for Rpclog {
+    fn new_invalid() -> Rpclog {
+        Rpclog {
+            subcommand: "".to_owned(),
+            count: 0,
+            valid: false,
+        }
+    }
+}
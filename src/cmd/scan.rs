@@ -1,5 +1,6 @@
 use crate::cmd::{Invalid, Parse};
 use crate::config::is_use_txn_api;
+use crate::tikv::backend::Transaction;
 use crate::tikv::errors::{AsyncResult, REDIS_NOT_SUPPORTED_ERR};
 use crate::tikv::string::StringCommandCtx;
 use crate::utils::{resp_err, resp_invalid_arguments};
@@ -8,7 +9,6 @@ use bytes::Bytes;
 use slog::debug;
 use std::convert::TryInto;
 use std::sync::Arc;
-use tikv_client::Transaction;
 use tokio::sync::Mutex;
 
 use crate::config::LOGGER;
@@ -17,16 +17,16 @@ use crate::config::LOGGER;
 pub struct Scan {
     start: String,
     count: i64,
-    regex: String,
+    pattern: String,
     valid: bool,
 }
 
 impl Scan {
-    pub fn new(start: String, count: i64, regex: String) -> Scan {
+    pub fn new(start: String, count: i64, pattern: String) -> Scan {
         Scan {
             start,
             count,
-            regex,
+            pattern,
             valid: true,
         }
     }
@@ -38,21 +38,21 @@ impl Scan {
     pub(crate) fn parse_frames(parse: &mut Parse) -> crate::Result<Scan> {
         let start = parse.next_string()?;
         let mut count = 10;
-        let mut regex = ".*?".to_owned();
+        let mut pattern = "*".to_owned();
         while let Ok(flag) = parse.next_string() {
             if flag.to_uppercase().as_str() == "COUNT" {
                 if let Ok(c) = parse.next_int() {
                     count = c;
                 };
             } else if flag.to_uppercase().as_str() == "MATCH" {
-                regex = parse.next_string()?;
+                pattern = parse.next_string()?;
             }
         }
 
         Ok(Scan {
             start,
             count,
-            regex,
+            pattern,
             valid: true,
         })
     }
@@ -63,7 +63,7 @@ impl Scan {
         }
 
         let mut count = 10;
-        let mut regex = ".*?".to_owned();
+        let mut pattern = "*".to_owned();
         let start = String::from_utf8_lossy(&argv[0]);
         if argv.len() >= 3 {
             if argv[1].to_ascii_uppercase() == b"COUNT" {
@@ -73,7 +73,7 @@ impl Scan {
                     return Ok(Scan::new_invalid());
                 }
             } else if argv[1].to_ascii_uppercase() == b"MATCH" {
-                regex = String::from_utf8_lossy(&argv[2]).to_string();
+                pattern = String::from_utf8_lossy(&argv[2]).to_string();
             } else {
                 return Ok(Scan::new_invalid());
             }
@@ -85,7 +85,7 @@ impl Scan {
                         return Ok(Scan::new_invalid());
                     }
                 } else if argv[3].to_ascii_uppercase() == b"MATCH" {
-                    regex = String::from_utf8_lossy(&argv[4]).to_string();
+                    pattern = String::from_utf8_lossy(&argv[4]).to_string();
                 } else {
                     return Ok(Scan::new_invalid());
                 }
@@ -95,7 +95,7 @@ impl Scan {
         Ok(Scan {
             start: start.to_string(),
             count,
-            regex,
+            pattern,
             valid: true,
         })
     }
@@ -120,7 +120,7 @@ impl Scan {
         }
         if is_use_txn_api() {
             StringCommandCtx::new(txn)
-                .do_async_txnkv_scan(&self.start, self.count.try_into().unwrap(), &self.regex)
+                .do_async_txnkv_scan(&self.start, self.count.try_into().unwrap(), &self.pattern)
                 .await
         } else {
             Ok(resp_err(REDIS_NOT_SUPPORTED_ERR))
@@ -133,7 +133,7 @@ impl Invalid for Scan {
         Scan {
             start: "".to_owned(),
             count: 0,
-            regex: "".to_owned(),
+            pattern: "".to_owned(),
             valid: false,
         }
     }
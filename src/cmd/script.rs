@@ -1,26 +1,110 @@
-use std::sync::atomic::{AtomicBool, Ordering};
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::Instant;
 
-use crate::cmd::Invalid;
+use crate::cmd::{resp_help, Invalid};
 use crate::config::LOGGER;
+use crate::config_lua_time_limit_or_default;
 use crate::db::Db;
-use crate::tikv::errors::AsyncResult;
+use crate::tikv::errors::{AsyncResult, REDIS_NOTBUSY_ERR, REDIS_UNKILLABLE_ERR};
 use crate::utils::{resp_array, resp_bulk, resp_int, resp_invalid_arguments, resp_ok, sha1hex};
 use crate::{Connection, Frame, Parse};
 use bytes::Bytes;
 use slog::debug;
 
-static SCRIPT_KILLED: AtomicBool = AtomicBool::new(false);
+/// Name of the lua registry value holding the id of the script in execution
+pub const SCRIPT_ID_REGISTRY_KEY: &str = "tidis_script_id";
 
-pub fn script_interuptted() -> bool {
-    SCRIPT_KILLED.load(Ordering::Relaxed)
+/// A script in execution, checked by the lua hook of its connection
+struct RunningScript {
+    started_at: Instant,
+    written: bool,
+    killed: bool,
 }
 
-pub fn script_set_killed() {
-    SCRIPT_KILLED.store(true, Ordering::Relaxed)
+lazy_static! {
+    static ref RUNNING_SCRIPTS: Mutex<HashMap<u64, RunningScript>> = Mutex::new(HashMap::new());
 }
 
-pub fn script_clear_killed() {
-    SCRIPT_KILLED.store(false, Ordering::Relaxed)
+static NEXT_SCRIPT_ID: AtomicU64 = AtomicU64::new(1);
+
+/// Registration of a script in execution, removed when dropped
+pub struct ScriptGuard {
+    id: u64,
+}
+
+impl ScriptGuard {
+    pub fn id(&self) -> u64 {
+        self.id
+    }
+}
+
+impl Drop for ScriptGuard {
+    fn drop(&mut self) {
+        RUNNING_SCRIPTS.lock().unwrap().remove(&self.id);
+    }
+}
+
+pub fn script_start() -> ScriptGuard {
+    let id = NEXT_SCRIPT_ID.fetch_add(1, Ordering::Relaxed);
+    RUNNING_SCRIPTS.lock().unwrap().insert(
+        id,
+        RunningScript {
+            started_at: Instant::now(),
+            written: false,
+            killed: false,
+        },
+    );
+    ScriptGuard { id }
+}
+
+/// Number of scripts in execution
+pub fn running_scripts() -> usize {
+    RUNNING_SCRIPTS.lock().unwrap().len()
+}
+
+/// Mark the script as written, it can not be killed anymore
+pub fn script_set_written(id: u64) {
+    if let Some(script) = RUNNING_SCRIPTS.lock().unwrap().get_mut(&id) {
+        script.written = true;
+    }
+}
+
+/// Returns the error aborting the script, if it was killed or ran over
+/// `lua_time_limit`. The writes of an aborted script are rolled back with
+/// its transaction.
+pub fn script_interrupted(id: u64) -> Option<String> {
+    let scripts = RUNNING_SCRIPTS.lock().unwrap();
+    let script = scripts.get(&id)?;
+    if script.killed {
+        return Some("ERR Error running script, killed by user with SCRIPT KILL".to_owned());
+    }
+    let limit = config_lua_time_limit_or_default();
+    if limit > 0 && script.started_at.elapsed().as_millis() as u64 > limit {
+        return Some(format!(
+            "BUSY script ran over lua_time_limit of {}ms and was aborted",
+            limit
+        ));
+    }
+    None
+}
+
+/// Kill the scripts in execution which have not written yet
+fn script_kill() -> AsyncResult<Frame> {
+    let mut scripts = RUNNING_SCRIPTS.lock().unwrap();
+    if scripts.is_empty() {
+        return Err(REDIS_NOTBUSY_ERR);
+    }
+    let mut killed = 0;
+    for script in scripts.values_mut().filter(|script| !script.written) {
+        script.killed = true;
+        killed += 1;
+    }
+    if killed == 0 {
+        return Err(REDIS_UNKILLABLE_ERR);
+    }
+    Ok(resp_ok())
 }
 
 #[derive(Debug, Clone)]
@@ -31,6 +115,7 @@ pub struct Script {
     is_exists: bool,
     is_flush: bool,
     is_kill: bool,
+    is_help: bool,
     valid: bool,
 }
 
@@ -40,6 +125,7 @@ impl Script {
         let mut is_exists = false;
         let mut is_flush = false;
         let mut is_kill = false;
+        let mut is_help = false;
         match subcommand.to_uppercase().as_str() {
             "LOAD" => {
                 is_load = true;
@@ -53,6 +139,9 @@ impl Script {
             "KILL" => {
                 is_kill = true;
             }
+            "HELP" => {
+                is_help = true;
+            }
             _ => {}
         }
         Script {
@@ -62,6 +151,7 @@ impl Script {
             is_exists,
             is_flush,
             is_kill,
+            is_help,
             valid: true,
         }
     }
@@ -130,7 +220,9 @@ impl Script {
             }
             return Ok(resp_array(resp));
         } else if self.is_kill {
-            script_set_killed();
+            return script_kill();
+        } else if self.is_help {
+            return Ok(resp_help("SCRIPT"));
         }
         Ok(resp_ok())
     }
@@ -145,6 +237,7 @@ impl Invalid for Script {
             is_exists: false,
             is_flush: false,
             is_kill: false,
+            is_help: false,
             valid: true,
         }
     }
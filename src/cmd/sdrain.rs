@@ -0,0 +1,89 @@
+use std::sync::Arc;
+
+use crate::cmd::{Invalid, Parse};
+use crate::config::is_use_txn_api;
+use crate::tikv::errors::{AsyncResult, REDIS_NOT_SUPPORTED_ERR};
+use crate::tikv::set::SetCommandCtx;
+use crate::utils::{resp_err, resp_invalid_arguments};
+use crate::{Connection, Frame};
+
+use crate::config::LOGGER;
+use crate::tikv::backend::Transaction;
+use bytes::Bytes;
+use slog::debug;
+use tokio::sync::Mutex;
+
+#[derive(Debug, Clone)]
+pub struct Sdrain {
+    key: String,
+    count: i64,
+    valid: bool,
+}
+
+impl Sdrain {
+    pub fn new(key: &str, count: i64) -> Sdrain {
+        Sdrain {
+            key: key.to_string(),
+            count,
+            valid: true,
+        }
+    }
+
+    pub(crate) fn parse_frames(parse: &mut Parse) -> crate::Result<Sdrain> {
+        let key = parse.next_string()?;
+        let count = parse.next_int()?;
+        if count <= 0 || count > u32::MAX as i64 {
+            return Ok(Sdrain::new_invalid());
+        }
+        Ok(Sdrain::new(&key, count))
+    }
+
+    pub(crate) fn parse_argv(argv: &Vec<Bytes>) -> crate::Result<Sdrain> {
+        if argv.len() != 2 {
+            return Ok(Sdrain::new_invalid());
+        }
+        match String::from_utf8_lossy(&argv[1]).parse::<i64>() {
+            Ok(count) if count > 0 && count <= u32::MAX as i64 => {
+                Ok(Sdrain::new(&String::from_utf8_lossy(&argv[0]), count))
+            }
+            _ => Ok(Sdrain::new_invalid()),
+        }
+    }
+
+    pub(crate) async fn apply(self, dst: &mut Connection) -> crate::Result<()> {
+        let response = self.sdrain(None).await?;
+        debug!(
+            LOGGER,
+            "res, {} -> {}, {:?}",
+            dst.local_addr(),
+            dst.peer_addr(),
+            response
+        );
+        dst.write_frame(&response).await?;
+
+        Ok(())
+    }
+
+    pub async fn sdrain(&self, txn: Option<Arc<Mutex<Transaction>>>) -> AsyncResult<Frame> {
+        if !self.valid {
+            return Ok(resp_invalid_arguments());
+        }
+        if is_use_txn_api() {
+            SetCommandCtx::new(txn)
+                .do_async_txnkv_sdrain(&self.key, self.count as u64)
+                .await
+        } else {
+            Ok(resp_err(REDIS_NOT_SUPPORTED_ERR))
+        }
+    }
+}
+
+impl Invalid for Sdrain {
+    fn new_invalid() -> Sdrain {
+        Sdrain {
+            key: "".to_string(),
+            count: 0,
+            valid: false,
+        }
+    }
+}
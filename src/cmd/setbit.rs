@@ -0,0 +1,110 @@
+use std::sync::Arc;
+
+use crate::cmd::{Invalid, Parse};
+use crate::config::is_use_txn_api;
+use crate::tikv::bitmap::{BitmapCommandCtx, BITMAP_MAX_OFFSET};
+use crate::tikv::errors::{
+    AsyncResult, REDIS_BIT_OFFSET_ERR, REDIS_BIT_VALUE_ERR, REDIS_NOT_SUPPORTED_ERR,
+};
+use crate::utils::{resp_err, resp_invalid_arguments};
+use crate::{Connection, Frame};
+
+use crate::config::LOGGER;
+use crate::tikv::backend::Transaction;
+use bytes::Bytes;
+use slog::debug;
+use tokio::sync::Mutex;
+
+/// `SETBIT key offset value`, replies with the previous bit at the offset.
+#[derive(Debug, Clone)]
+pub struct Setbit {
+    key: String,
+    offset: Option<u64>,
+    value: Option<bool>,
+    valid: bool,
+}
+
+impl Setbit {
+    pub fn key(&self) -> &str {
+        &self.key
+    }
+
+    pub(crate) fn parse_frames(parse: &mut Parse) -> crate::Result<Setbit> {
+        let mut args = vec![];
+        while let Ok(arg) = parse.next_bytes() {
+            args.push(arg);
+        }
+        Ok(Setbit::from_args(&args))
+    }
+
+    pub(crate) fn parse_argv(argv: &Vec<Bytes>) -> crate::Result<Setbit> {
+        Ok(Setbit::from_args(argv))
+    }
+
+    fn from_args(args: &[Bytes]) -> Setbit {
+        if args.len() != 3 {
+            return Setbit::new_invalid();
+        }
+        let offset = String::from_utf8_lossy(&args[1])
+            .parse::<u64>()
+            .ok()
+            .filter(|offset| *offset <= BITMAP_MAX_OFFSET);
+        let value = match args[2].as_ref() {
+            b"0" => Some(false),
+            b"1" => Some(true),
+            _ => None,
+        };
+        Setbit {
+            key: String::from_utf8_lossy(&args[0]).to_string(),
+            offset,
+            value,
+            valid: true,
+        }
+    }
+
+    pub(crate) async fn apply(self, dst: &mut Connection) -> crate::Result<()> {
+        let response = self.setbit(None).await.unwrap_or_else(Into::into);
+        debug!(
+            LOGGER,
+            "res, {} -> {}, {:?}",
+            dst.local_addr(),
+            dst.peer_addr(),
+            response
+        );
+        dst.write_frame(&response).await?;
+
+        Ok(())
+    }
+
+    pub async fn setbit(&self, txn: Option<Arc<Mutex<Transaction>>>) -> AsyncResult<Frame> {
+        if !self.valid {
+            return Ok(resp_invalid_arguments());
+        }
+        let offset = match self.offset {
+            Some(offset) => offset,
+            None => return Ok(resp_err(REDIS_BIT_OFFSET_ERR)),
+        };
+        let value = match self.value {
+            Some(value) => value,
+            None => return Ok(resp_err(REDIS_BIT_VALUE_ERR)),
+        };
+        if is_use_txn_api() {
+            BitmapCommandCtx::new(txn)
+                .do_async_txnkv_setbit(&self.key, offset, value)
+                .await
+        } else {
+            Ok(resp_err(REDIS_NOT_SUPPORTED_ERR))
+        }
+    }
+}
+
+impl Invalid for Setbit {
+    fn new_invalid() -> Setbit {
+        Setbit {
+            key: "".to_string(),
+            offset: None,
+            value: None,
+            valid: false,
+        }
+    }
+}
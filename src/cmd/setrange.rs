@@ -0,0 +1,103 @@
+use std::sync::Arc;
+
+use crate::cmd::{Invalid, Parse};
+use crate::config::is_use_txn_api;
+use crate::tikv::errors::{
+    AsyncResult, REDIS_NOT_SUPPORTED_ERR, REDIS_OFFSET_OUT_OF_RANGE_ERR, REDIS_STRING_TOO_LONG_ERR,
+};
+use crate::tikv::string::{StringCommandCtx, STRING_MAX_LEN};
+use crate::utils::{resp_err, resp_invalid_arguments};
+use crate::{Connection, Frame};
+
+use crate::config::LOGGER;
+use crate::tikv::backend::Transaction;
+use bytes::Bytes;
+use slog::debug;
+use tokio::sync::Mutex;
+
+/// `SETRANGE key offset value`, overwrites the string from `offset` on,
+/// zero-padded if it is shorter. Replies the length of the string after.
+#[derive(Debug, Clone)]
+pub struct Setrange {
+    key: String,
+    offset: Option<u64>,
+    value: Bytes,
+    valid: bool,
+}
+
+impl Setrange {
+    pub fn key(&self) -> &str {
+        &self.key
+    }
+
+    pub(crate) fn parse_frames(parse: &mut Parse) -> crate::Result<Setrange> {
+        let mut args = vec![];
+        while let Ok(arg) = parse.next_bytes() {
+            args.push(arg);
+        }
+        Ok(Setrange::from_args(&args))
+    }
+
+    pub(crate) fn parse_argv(argv: &Vec<Bytes>) -> crate::Result<Setrange> {
+        Ok(Setrange::from_args(argv))
+    }
+
+    fn from_args(args: &[Bytes]) -> Setrange {
+        if args.len() != 3 {
+            return Setrange::new_invalid();
+        }
+        Setrange {
+            key: String::from_utf8_lossy(&args[0]).to_string(),
+            offset: String::from_utf8_lossy(&args[1]).parse::<u64>().ok(),
+            value: args[2].clone(),
+            valid: true,
+        }
+    }
+
+    pub(crate) async fn apply(self, dst: &mut Connection) -> crate::Result<()> {
+        let response = self.setrange(None).await.unwrap_or_else(Into::into);
+        debug!(
+            LOGGER,
+            "res, {} -> {}, {:?}",
+            dst.local_addr(),
+            dst.peer_addr(),
+            response
+        );
+        dst.write_frame(&response).await?;
+
+        Ok(())
+    }
+
+    pub async fn setrange(&self, txn: Option<Arc<Mutex<Transaction>>>) -> AsyncResult<Frame> {
+        if !self.valid {
+            return Ok(resp_invalid_arguments());
+        }
+        let offset = match self.offset {
+            Some(offset) => offset,
+            None => return Ok(resp_err(REDIS_OFFSET_OUT_OF_RANGE_ERR)),
+        };
+        // the end may not fit in an u64 with an offset near u64::MAX
+        let end = offset.checked_add(self.value.len() as u64);
+        if !self.value.is_empty() && end.map_or(true, |end| end > STRING_MAX_LEN) {
+            return Ok(resp_err(REDIS_STRING_TOO_LONG_ERR));
+        }
+        if is_use_txn_api() {
+            StringCommandCtx::new(txn)
+                .do_async_txnkv_setrange(&self.key, offset as usize, &self.value)
+                .await
+        } else {
+            Ok(resp_err(REDIS_NOT_SUPPORTED_ERR))
+        }
+    }
+}
+
+impl Invalid for Setrange {
+    fn new_invalid() -> Setrange {
+        Setrange {
+            key: "".to_string(),
+            offset: None,
+            value: Bytes::new(),
+            valid: false,
+        }
+    }
+}
@@ -0,0 +1,89 @@
+use std::sync::Arc;
+
+use crate::cmd::{Invalid, Parse};
+use crate::config::is_use_txn_api;
+use crate::tikv::errors::{AsyncResult, REDIS_NOT_SUPPORTED_ERR};
+use crate::tikv::set::SetCommandCtx;
+use crate::utils::{resp_err, resp_invalid_arguments};
+use crate::{Connection, Frame};
+
+use crate::config::LOGGER;
+use crate::tikv::backend::Transaction;
+use bytes::Bytes;
+use slog::debug;
+use tokio::sync::Mutex;
+
+#[derive(Debug, Clone)]
+pub struct Sinter {
+    keys: Vec<String>,
+    valid: bool,
+}
+
+impl Sinter {
+    pub fn new(keys: Vec<String>) -> Sinter {
+        Sinter { keys, valid: true }
+    }
+
+    /// Get the keys
+    pub fn keys(&self) -> &Vec<String> {
+        &self.keys
+    }
+
+    pub(crate) fn parse_frames(parse: &mut Parse) -> crate::Result<Sinter> {
+        let mut keys = vec![];
+        while let Ok(key) = parse.next_string() {
+            keys.push(key);
+        }
+        if keys.is_empty() {
+            return Ok(Sinter::new_invalid());
+        }
+        Ok(Sinter::new(keys))
+    }
+
+    pub(crate) fn parse_argv(argv: &Vec<Bytes>) -> crate::Result<Sinter> {
+        if argv.is_empty() {
+            return Ok(Sinter::new_invalid());
+        }
+        Ok(Sinter::new(
+            argv.iter()
+                .map(|x| String::from_utf8_lossy(x).to_string())
+                .collect(),
+        ))
+    }
+
+    pub(crate) async fn apply(self, dst: &mut Connection) -> crate::Result<()> {
+        let response = self.sinter(None).await.unwrap_or_else(Into::into);
+        debug!(
+            LOGGER,
+            "res, {} -> {}, {:?}",
+            dst.local_addr(),
+            dst.peer_addr(),
+            response
+        );
+        dst.write_frame(&response).await?;
+
+        Ok(())
+    }
+
+    pub async fn sinter(&self, txn: Option<Arc<Mutex<Transaction>>>) -> AsyncResult<Frame> {
+        if !self.valid {
+            return Ok(resp_invalid_arguments());
+        }
+        if is_use_txn_api() {
+            SetCommandCtx::new(txn)
+                .do_async_txnkv_sinter(&self.keys)
+                .await
+        } else {
+            Ok(resp_err(REDIS_NOT_SUPPORTED_ERR))
+        }
+    }
+}
+
+impl Invalid for Sinter {
+    fn new_invalid() -> Sinter {
+        Sinter {
+            keys: vec![],
+            valid: false,
+        }
+    }
+}
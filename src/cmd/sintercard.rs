@@ -0,0 +1,123 @@
+use std::sync::Arc;
+
+use crate::cmd::{Invalid, Parse};
+use crate::config::is_use_txn_api;
+use crate::tikv::errors::{AsyncResult, REDIS_NOT_SUPPORTED_ERR};
+use crate::tikv::set::SetCommandCtx;
+use crate::utils::{resp_err, resp_invalid_arguments};
+use crate::{Connection, Frame};
+
+use crate::config::LOGGER;
+use crate::tikv::backend::Transaction;
+use bytes::Bytes;
+use slog::debug;
+use tokio::sync::Mutex;
+
+#[derive(Debug, Clone)]
+pub struct Sintercard {
+    keys: Vec<String>,
+    limit: usize,
+    valid: bool,
+}
+
+impl Sintercard {
+    pub fn new(keys: Vec<String>, limit: usize) -> Sintercard {
+        Sintercard {
+            keys,
+            limit,
+            valid: true,
+        }
+    }
+
+    /// Get the keys
+    pub fn keys(&self) -> &Vec<String> {
+        &self.keys
+    }
+
+    pub(crate) fn parse_frames(parse: &mut Parse) -> crate::Result<Sintercard> {
+        let mut args = vec![];
+        while let Ok(arg) = parse.next_bytes() {
+            args.push(arg);
+        }
+        Ok(Sintercard::from_args(&args))
+    }
+
+    pub(crate) fn parse_argv(argv: &Vec<Bytes>) -> crate::Result<Sintercard> {
+        Ok(Sintercard::from_args(argv))
+    }
+
+    /// Parse `numkeys key [key ...] [LIMIT limit]`
+    fn from_args(args: &[Bytes]) -> Sintercard {
+        let (keys, options) = match split_numkeys(args) {
+            Some(split) => split,
+            None => return Sintercard::new_invalid(),
+        };
+        match parse_limit(options) {
+            Some(limit) => Sintercard::new(keys, limit),
+            None => Sintercard::new_invalid(),
+        }
+    }
+
+    pub(crate) async fn apply(self, dst: &mut Connection) -> crate::Result<()> {
+        let response = self.sintercard(None).await.unwrap_or_else(Into::into);
+        debug!(
+            LOGGER,
+            "res, {} -> {}, {:?}",
+            dst.local_addr(),
+            dst.peer_addr(),
+            response
+        );
+        dst.write_frame(&response).await?;
+
+        Ok(())
+    }
+
+    pub async fn sintercard(&self, txn: Option<Arc<Mutex<Transaction>>>) -> AsyncResult<Frame> {
+        if !self.valid {
+            return Ok(resp_invalid_arguments());
+        }
+        if is_use_txn_api() {
+            SetCommandCtx::new(txn)
+                .do_async_txnkv_sintercard(&self.keys, self.limit)
+                .await
+        } else {
+            Ok(resp_err(REDIS_NOT_SUPPORTED_ERR))
+        }
+    }
+}
+
+/// Split `numkeys key [key ...] [options ...]` into the keys and the options
+pub(crate) fn split_numkeys(args: &[Bytes]) -> Option<(Vec<String>, &[Bytes])> {
+    let numkeys = String::from_utf8_lossy(args.first()?)
+        .parse::<usize>()
+        .ok()?;
+    if numkeys == 0 || args.len() < numkeys + 1 {
+        return None;
+    }
+    let keys = args[1..=numkeys]
+        .iter()
+        .map(|x| String::from_utf8_lossy(x).to_string())
+        .collect();
+    Some((keys, &args[numkeys + 1..]))
+}
+
+/// Parse the optional `LIMIT limit` of the cardinality commands, 0 for none
+pub(crate) fn parse_limit(options: &[Bytes]) -> Option<usize> {
+    match options {
+        [] => Some(0),
+        [name, limit] if String::from_utf8_lossy(name).to_uppercase() == "LIMIT" => {
+            String::from_utf8_lossy(limit).parse::<usize>().ok()
+        }
+        _ => None,
+    }
+}
+
+impl Invalid for Sintercard {
+    fn new_invalid() -> Sintercard {
+        Sintercard {
+            keys: vec![],
+            limit: 0,
+            valid: false,
+        }
+    }
+}
@@ -0,0 +1,101 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use crate::client::Client;
+use crate::cmd::debug::debug_command_allowed;
+use crate::cmd::{resp_help, Invalid, Parse};
+use crate::config::LOGGER;
+use crate::diag::snapshot;
+use crate::tikv::errors::{RTError, REDIS_DEBUG_COMMAND_DISABLED_ERR};
+use crate::utils::{resp_bulk, resp_err, resp_invalid_arguments, resp_ok};
+use crate::{Connection, Frame};
+
+use slog::{debug, info};
+use tokio::sync::Mutex;
+
+/// `TIDIS.SNAPSHOT GET | SAVE path | HELP`, the diagnostic snapshot of the
+/// instance, see `crate::diag`.
+#[derive(Debug, Clone)]
+pub struct Snapshot {
+    subcommand: String,
+    path: Option<String>,
+    valid: bool,
+}
+
+impl Snapshot {
+    pub(crate) fn parse_frames(parse: &mut Parse) -> crate::Result<Snapshot> {
+        let subcommand = match parse.next_string() {
+            Ok(subcommand) => subcommand.to_lowercase(),
+            Err(_) => return Ok(Snapshot::new_invalid()),
+        };
+        let path = parse.next_string().ok();
+        if (subcommand == "save") != path.is_some() || parse.next_string().is_ok() {
+            return Ok(Snapshot::new_invalid());
+        }
+        Ok(Snapshot {
+            subcommand,
+            path,
+            valid: true,
+        })
+    }
+
+    pub(crate) async fn apply(
+        self,
+        dst: &mut Connection,
+        clients: Arc<Mutex<HashMap<u64, Arc<Mutex<Client>>>>>,
+    ) -> crate::Result<()> {
+        let response = self.snapshot(dst.peer_addr(), &clients).await;
+        debug!(
+            LOGGER,
+            "res, {} -> {}, {:?}",
+            dst.local_addr(),
+            dst.peer_addr(),
+            response
+        );
+        dst.write_frame(&response).await?;
+
+        Ok(())
+    }
+
+    async fn snapshot(
+        &self,
+        peer_addr: &str,
+        clients: &Arc<Mutex<HashMap<u64, Arc<Mutex<Client>>>>>,
+    ) -> Frame {
+        if !self.valid {
+            return resp_invalid_arguments();
+        }
+        match self.subcommand.as_str() {
+            "get" => resp_bulk(snapshot(clients).await.into_bytes()),
+            "save" => {
+                // writes a file of the host on behalf of the client
+                if !debug_command_allowed(peer_addr) {
+                    return resp_err(REDIS_DEBUG_COMMAND_DISABLED_ERR);
+                }
+                let path = self.path.as_deref().unwrap_or_default();
+                match tokio::fs::write(path, snapshot(clients).await).await {
+                    Ok(_) => {
+                        info!(LOGGER, "diagnostic snapshot saved to {}", path);
+                        resp_ok()
+                    }
+                    Err(e) => resp_err(RTError::Owned(format!(
+                        "ERR saving the snapshot failed: {}",
+                        e
+                    ))),
+                }
+            }
+            "help" => resp_help("TIDIS.SNAPSHOT"),
+            _ => resp_invalid_arguments(),
+        }
+    }
+}
+
+impl Invalid for Snapshot {
+    fn new_invalid() -> Snapshot {
+        Snapshot {
+            subcommand: "".to_owned(),
+            path: None,
+            valid: false,
+        }
+    }
+}
@@ -0,0 +1,129 @@
+use std::sync::Arc;
+
+use crate::cmd::{Invalid, Parse};
+use crate::config::is_use_txn_api;
+use crate::tikv::errors::{AsyncResult, REDIS_NOT_SUPPORTED_ERR};
+use crate::tikv::sort::{SortCommandCtx, SortOptions};
+use crate::utils::{resp_err, resp_invalid_arguments};
+use crate::{Connection, Frame};
+
+use crate::config::LOGGER;
+use crate::tikv::backend::Transaction;
+use bytes::Bytes;
+use slog::debug;
+use tokio::sync::Mutex;
+
+/// `SORT key [BY pattern] [LIMIT offset count] [GET pattern [GET pattern
+/// ...]] [ASC|DESC] [ALPHA] [STORE destination]`
+#[derive(Debug, Clone)]
+pub struct Sort {
+    key: String,
+    options: SortOptions,
+    valid: bool,
+}
+
+impl Sort {
+    pub fn key(&self) -> &str {
+        &self.key
+    }
+
+    /// The destination of STORE, the command only reads without it
+    pub fn store(&self) -> Option<&str> {
+        self.options.store.as_deref()
+    }
+
+    pub(crate) fn parse_frames(parse: &mut Parse) -> crate::Result<Sort> {
+        let mut args = vec![];
+        while let Ok(arg) = parse.next_bytes() {
+            args.push(arg);
+        }
+        Ok(Sort::from_args(&args))
+    }
+
+    pub(crate) fn parse_argv(argv: &Vec<Bytes>) -> crate::Result<Sort> {
+        Ok(Sort::from_args(argv))
+    }
+
+    fn from_args(args: &[Bytes]) -> Sort {
+        if args.is_empty() {
+            return Sort::new_invalid();
+        }
+        let args: Vec<String> = args
+            .iter()
+            .map(|arg| String::from_utf8_lossy(arg).to_string())
+            .collect();
+        let mut options = SortOptions::default();
+        let mut idx = 1;
+        while idx < args.len() {
+            let has_next = idx + 1 < args.len();
+            match args[idx].to_uppercase().as_str() {
+                "ASC" => options.desc = false,
+                "DESC" => options.desc = true,
+                "ALPHA" => options.alpha = true,
+                "LIMIT" if idx + 2 < args.len() => {
+                    match (args[idx + 1].parse::<i64>(), args[idx + 2].parse::<i64>()) {
+                        (Ok(offset), Ok(count)) => options.limit = Some((offset, count)),
+                        _ => return Sort::new_invalid(),
+                    }
+                    idx += 2;
+                }
+                "BY" if has_next => {
+                    options.by = Some(args[idx + 1].clone());
+                    idx += 1;
+                }
+                "GET" if has_next => {
+                    options.gets.push(args[idx + 1].clone());
+                    idx += 1;
+                }
+                "STORE" if has_next => {
+                    options.store = Some(args[idx + 1].clone());
+                    idx += 1;
+                }
+                _ => return Sort::new_invalid(),
+            }
+            idx += 1;
+        }
+        Sort {
+            key: args[0].clone(),
+            options,
+            valid: true,
+        }
+    }
+
+    pub(crate) async fn apply(self, dst: &mut Connection) -> crate::Result<()> {
+        let response = self.sort(None).await.unwrap_or_else(Into::into);
+        debug!(
+            LOGGER,
+            "res, {} -> {}, {:?}",
+            dst.local_addr(),
+            dst.peer_addr(),
+            response
+        );
+        dst.write_frame(&response).await?;
+
+        Ok(())
+    }
+
+    pub async fn sort(&self, txn: Option<Arc<Mutex<Transaction>>>) -> AsyncResult<Frame> {
+        if !self.valid {
+            return Ok(resp_invalid_arguments());
+        }
+        if is_use_txn_api() {
+            SortCommandCtx::new(txn)
+                .do_async_txnkv_sort(&self.key, self.options.clone())
+                .await
+        } else {
+            Ok(resp_err(REDIS_NOT_SUPPORTED_ERR))
+        }
+    }
+}
+
+impl Invalid for Sort {
+    fn new_invalid() -> Sort {
+        Sort {
+            key: "".to_string(),
+            options: SortOptions::default(),
+            valid: false,
+        }
+    }
+}
@@ -29,12 +29,46 @@ pub struct Unsubscribe {
     valid: bool,
 }
 
+/// Subscribes the client to one or more glob patterns, the client receives
+/// the messages published on every channel matching one of them.
+#[derive(Debug, Clone)]
+pub struct Psubscribe {
+    patterns: Vec<String>,
+    valid: bool,
+}
+
+/// Unsubscribes the client from one or more patterns, or from all the
+/// previously subscribed patterns when none are specified.
+#[derive(Clone, Debug)]
+pub struct Punsubscribe {
+    patterns: Vec<String>,
+    valid: bool,
+}
+
 /// Stream of messages. The stream receives messages from the
 /// `broadcast::Receiver`. We use `stream!` to create a `Stream` that consumes
 /// messages. Because `stream!` values cannot be named, we box the stream using
 /// a trait object.
 type Messages = Pin<Box<dyn Stream<Item = Bytes> + Send>>;
 
+/// Stream of the messages of a pattern subscription, with the channel each
+/// message was published on.
+type PatternMessages = Pin<Box<dyn Stream<Item = (String, Bytes)> + Send>>;
+
+/// Channel and pattern subscriptions of a client in the subscribed state.
+struct Subscriptions {
+    channels: StreamMap<String, Messages>,
+    patterns: StreamMap<String, PatternMessages>,
+}
+
+impl Subscriptions {
+    /// Number of subscriptions reported to the client, channels and patterns
+    /// are counted together like Redis.
+    fn len(&self) -> usize {
+        self.channels.len() + self.patterns.len()
+    }
+}
+
 impl Subscribe {
     #[allow(dead_code)]
     /// Creates a new `Subscribe` command to listen on the specified channels.
@@ -108,7 +142,7 @@ impl Subscribe {
     ///
     /// [here]: https://redis.io/topics/pubsub
     pub(crate) async fn apply(
-        mut self,
+        self,
         db: &Db,
         dst: &mut Connection,
         shutdown: &mut Shutdown,
@@ -118,54 +152,7 @@ impl Subscribe {
             return Ok(());
         }
 
-        // Each individual channel subscription is handled using a
-        // `sync::broadcast` channel. Messages are then fanned out to all
-        // clients currently subscribed to the channels.
-        //
-        // An individual client may subscribe to multiple channels and may
-        // dynamically add and remove channels from its subscription set. To
-        // handle this, a `StreamMap` is used to track active subscriptions. The
-        // `StreamMap` merges messages from individual broadcast channels as
-        // they are received.
-        let mut subscriptions = StreamMap::new();
-
-        loop {
-            // `self.channels` is used to track additional channels to subscribe
-            // to. When new `SUBSCRIBE` commands are received during the
-            // execution of `apply`, the new channels are pushed onto this vec.
-            for channel_name in self.channels.drain(..) {
-                subscribe_to_channel(channel_name, &mut subscriptions, db, dst).await?;
-            }
-
-            // Wait for one of the following to happen:
-            //
-            // - Receive a message from one of the subscribed channels.
-            // - Receive a subscribe or unsubscribe command from the client.
-            // - A server shutdown signal.
-            select! {
-                // Receive messages from subscribed channels
-                Some((channel_name, msg)) = subscriptions.next() => {
-                    dst.write_frame(&make_message_frame(channel_name, msg)).await?;
-                }
-                res = dst.read_frame() => {
-                    let frame = match res? {
-                        Some(frame) => frame,
-                        // This happens if the remote client has disconnected.
-                        None => return Ok(())
-                    };
-
-                    handle_command(
-                        frame,
-                        &mut self.channels,
-                        &mut subscriptions,
-                        dst,
-                    ).await?;
-                }
-                _ = shutdown.recv() => {
-                    return Ok(());
-                }
-            };
-        }
+        run_subscribed(self.channels, vec![], db, dst, shutdown).await
     }
 
     #[allow(dead_code)]
@@ -183,9 +170,80 @@ impl Subscribe {
     }
 }
 
+/// Serve the client in the subscribed state, starting with the `channels` and
+/// `patterns` subscriptions, until it disconnects or the server shuts down.
+async fn run_subscribed(
+    mut channels: Vec<String>,
+    mut patterns: Vec<String>,
+    db: &Db,
+    dst: &mut Connection,
+    shutdown: &mut Shutdown,
+) -> crate::Result<()> {
+    // Each individual channel subscription is handled using a
+    // `sync::broadcast` channel. Messages are then fanned out to all
+    // clients currently subscribed to the channels.
+    //
+    // An individual client may subscribe to multiple channels and may
+    // dynamically add and remove channels from its subscription set. To
+    // handle this, a `StreamMap` is used to track active subscriptions. The
+    // `StreamMap` merges messages from individual broadcast channels as
+    // they are received. Pattern subscriptions are tracked the same way in
+    // their own `StreamMap`.
+    let mut subscriptions = Subscriptions {
+        channels: StreamMap::new(),
+        patterns: StreamMap::new(),
+    };
+
+    loop {
+        // `channels` and `patterns` are used to track additional
+        // subscriptions. When new `SUBSCRIBE` or `PSUBSCRIBE` commands are
+        // received, the new channels and patterns are pushed onto these vecs.
+        for channel_name in channels.drain(..) {
+            subscribe_to_channel(channel_name, &mut subscriptions, db, dst).await?;
+        }
+        for pattern in patterns.drain(..) {
+            subscribe_to_pattern(pattern, &mut subscriptions, db, dst).await?;
+        }
+
+        // Wait for one of the following to happen:
+        //
+        // - Receive a message from one of the subscribed channels or patterns.
+        // - Receive a command from the client.
+        // - A server shutdown signal.
+        select! {
+            // Receive messages from subscribed channels
+            Some((channel_name, msg)) = subscriptions.channels.next() => {
+                dst.write_push(&make_message_frame(channel_name, msg)).await?;
+            }
+            // Receive messages from subscribed patterns
+            Some((pattern, (channel_name, msg))) = subscriptions.patterns.next() => {
+                dst.write_push(&make_pmessage_frame(pattern, channel_name, msg)).await?;
+            }
+            res = dst.read_frame() => {
+                let frame = match res? {
+                    Some(frame) => frame,
+                    // This happens if the remote client has disconnected.
+                    None => return Ok(())
+                };
+
+                handle_command(
+                    frame,
+                    &mut channels,
+                    &mut patterns,
+                    &mut subscriptions,
+                    dst,
+                ).await?;
+            }
+            _ = shutdown.recv() => {
+                return Ok(());
+            }
+        };
+    }
+}
+
 async fn subscribe_to_channel(
     channel_name: String,
-    subscriptions: &mut StreamMap<String, Messages>,
+    subscriptions: &mut Subscriptions,
     db: &Db,
     dst: &mut Connection,
 ) -> crate::Result<()> {
@@ -204,36 +262,67 @@ async fn subscribe_to_channel(
     });
 
     // Track subscription in this client's subscription set.
-    subscriptions.insert(channel_name.clone(), rx);
+    subscriptions.channels.insert(channel_name.clone(), rx);
 
     // Respond with the successful subscription
-    let response = make_subscribe_frame(channel_name, subscriptions.len());
-    dst.write_frame(&response).await?;
+    let response = make_subscribe_frame("subscribe", channel_name, subscriptions.len());
+    dst.write_push(&response).await?;
 
     Ok(())
 }
 
-/// Handle a command received while inside `Subscribe::apply`. Only subscribe
-/// and unsubscribe commands are permitted in this context.
+async fn subscribe_to_pattern(
+    pattern: String,
+    subscriptions: &mut Subscriptions,
+    db: &Db,
+    dst: &mut Connection,
+) -> crate::Result<()> {
+    let mut rx = db.psubscribe(pattern.clone());
+
+    let rx = Box::pin(async_stream::stream! {
+        loop {
+            match rx.recv().await {
+                Ok(msg) => yield msg,
+                // If we lagged in consuming messages, just resume.
+                Err(broadcast::error::RecvError::Lagged(_)) => {}
+                Err(_) => break,
+            }
+        }
+    });
+
+    subscriptions.patterns.insert(pattern.clone(), rx);
+
+    let response = make_subscribe_frame("psubscribe", pattern, subscriptions.len());
+    dst.write_push(&response).await?;
+
+    Ok(())
+}
+
+/// Handle a command received in the subscribed state. Only subscribe,
+/// unsubscribe and ping commands are permitted in this context.
 ///
-/// Any new subscriptions are appended to `subscribe_to` instead of modifying
-/// `subscriptions`.
+/// Any new subscriptions are appended to `subscribe_to` and `psubscribe_to`
+/// instead of modifying `subscriptions`.
 async fn handle_command(
     frame: Frame,
     subscribe_to: &mut Vec<String>,
-    subscriptions: &mut StreamMap<String, Messages>,
+    psubscribe_to: &mut Vec<String>,
+    subscriptions: &mut Subscriptions,
     dst: &mut Connection,
 ) -> crate::Result<()> {
     // A command has been received from the client.
     //
-    // Only `SUBSCRIBE` and `UNSUBSCRIBE` commands are permitted
-    // in this context.
+    // Only `SUBSCRIBE`, `PSUBSCRIBE`, `UNSUBSCRIBE`, `PUNSUBSCRIBE` and `PING`
+    // commands are permitted in this context.
     match Command::from_frame(frame)? {
         Command::Subscribe(subscribe) => {
-            // The `apply` method will subscribe to the channels we add to this
+            // `run_subscribed` will subscribe to the channels we add to this
             // vector.
             subscribe_to.extend(subscribe.channels.into_iter());
         }
+        Command::Psubscribe(psubscribe) => {
+            psubscribe_to.extend(psubscribe.patterns.into_iter());
+        }
         Command::Unsubscribe(mut unsubscribe) => {
             // If no channels are specified, this requests unsubscribing from
             // **all** channels. To implement this, the `unsubscribe.channels`
@@ -241,17 +330,51 @@ async fn handle_command(
             // to.
             if unsubscribe.channels.is_empty() {
                 unsubscribe.channels = subscriptions
+                    .channels
                     .keys()
                     .map(|channel_name| channel_name.to_string())
                     .collect();
             }
 
+            // Nothing to unsubscribe from is still acknowledged, with a nil
+            // channel.
+            if unsubscribe.channels.is_empty() {
+                let response = make_unsubscribe_frame("unsubscribe", None, subscriptions.len());
+                dst.write_push(&response).await?;
+            }
+
             for channel_name in unsubscribe.channels {
-                subscriptions.remove(&channel_name);
+                subscriptions.channels.remove(&channel_name);
+
+                let response =
+                    make_unsubscribe_frame("unsubscribe", Some(channel_name), subscriptions.len());
+                dst.write_push(&response).await?;
+            }
+        }
+        Command::Punsubscribe(mut punsubscribe) => {
+            if punsubscribe.patterns.is_empty() {
+                punsubscribe.patterns = subscriptions
+                    .patterns
+                    .keys()
+                    .map(|pattern| pattern.to_string())
+                    .collect();
+            }
 
-                let response = make_unsubscribe_frame(channel_name, subscriptions.len());
-                dst.write_frame(&response).await?;
+            if punsubscribe.patterns.is_empty() {
+                let response = make_unsubscribe_frame("punsubscribe", None, subscriptions.len());
+                dst.write_push(&response).await?;
             }
+
+            for pattern in punsubscribe.patterns {
+                subscriptions.patterns.remove(&pattern);
+
+                let response =
+                    make_unsubscribe_frame("punsubscribe", Some(pattern), subscriptions.len());
+                dst.write_push(&response).await?;
+            }
+        }
+        Command::Ping(ping) => {
+            dst.write_frame(&ping.into_subscribed_frame()).await?;
         }
         command => {
             let cmd = Unknown::new(command.get_name());
@@ -267,21 +390,30 @@ async fn handle_command(
 /// a `&str` since `Bytes::from` can reuse the allocation in the `String`, and
 /// taking a `&str` would require copying the data. This allows the caller to
 /// decide whether to clone the channel name or not.
-fn make_subscribe_frame(channel_name: String, num_subs: usize) -> Frame {
+fn make_subscribe_frame(kind: &'static str, channel_name: String, num_subs: usize) -> Frame {
     let mut response = Frame::array();
-    response.push_bulk(Bytes::from_static(b"subscribe"));
+    response.push_bulk(Bytes::from_static(kind.as_bytes()));
     response.push_bulk(Bytes::from(channel_name));
     response.push_int(num_subs as i64);
     response
 }
 
-/// Creates the response to an unsubcribe request.
-fn make_unsubscribe_frame(channel_name: String, num_subs: usize) -> Frame {
-    let mut response = Frame::array();
-    response.push_bulk(Bytes::from_static(b"unsubscribe"));
-    response.push_bulk(Bytes::from(channel_name));
-    response.push_int(num_subs as i64);
-    response
+/// Creates the response to an unsubcribe request, the channel is nil when the
+/// client had nothing to unsubscribe from.
+fn make_unsubscribe_frame(
+    kind: &'static str,
+    channel_name: Option<String>,
+    num_subs: usize,
+) -> Frame {
+    let channel = match channel_name {
+        Some(channel_name) => Frame::Bulk(Bytes::from(channel_name)),
+        None => Frame::Null,
+    };
+    Frame::Array(vec![
+        Frame::Bulk(Bytes::from_static(kind.as_bytes())),
+        channel,
+        Frame::Integer(num_subs as i64),
+    ])
 }
 
 /// Creates a message informing the client about a new message on a channel that
@@ -294,6 +426,17 @@ fn make_message_frame(channel_name: String, msg: Bytes) -> Frame {
     response
 }
 
+/// Creates a message informing the client about a new message on a channel
+/// matching a pattern that the client subscribes to.
+fn make_pmessage_frame(pattern: String, channel_name: String, msg: Bytes) -> Frame {
+    let mut response = Frame::array();
+    response.push_bulk(Bytes::from_static(b"pmessage"));
+    response.push_bulk(Bytes::from(pattern));
+    response.push_bulk(Bytes::from(channel_name));
+    response.push_bulk(msg);
+    response
+}
+
 impl Unsubscribe {
     #[allow(dead_code)]
     /// Create a new `Unsubscribe` command with the given `channels`.
@@ -353,6 +496,18 @@ impl Unsubscribe {
         })
     }
 
+    /// Apply the `Unsubscribe` command outside of the subscribed state, there
+    /// is nothing to unsubscribe from so each channel is acknowledged with no
+    /// subscriptions left.
+    pub(crate) async fn apply(self, dst: &mut Connection) -> crate::Result<()> {
+        if !self.valid {
+            dst.write_frame(&resp_invalid_arguments()).await?;
+            return Ok(());
+        }
+
+        write_unsubscribed("unsubscribe", self.channels, dst).await
+    }
+
     #[allow(dead_code)]
     /// Converts the command into an equivalent `Frame`.
     ///
@@ -374,6 +529,95 @@ impl Unsubscribe {
     }
 }
 
+impl Psubscribe {
+    /// Parse a `Psubscribe` instance from a received frame.
+    ///
+    /// # Format
+    ///
+    /// ```text
+    /// PSUBSCRIBE pattern [pattern ...]
+    /// ```
+    pub(crate) fn parse_frames(parse: &mut Parse) -> crate::Result<Psubscribe> {
+        Ok(Psubscribe {
+            patterns: parse_names(parse)?,
+            valid: true,
+        })
+    }
+
+    /// Apply the `Psubscribe` command, the client enters the subscribed state
+    /// like with `SUBSCRIBE`.
+    pub(crate) async fn apply(
+        self,
+        db: &Db,
+        dst: &mut Connection,
+        shutdown: &mut Shutdown,
+    ) -> crate::Result<()> {
+        if !self.valid || self.patterns.is_empty() {
+            dst.write_frame(&resp_invalid_arguments()).await?;
+            return Ok(());
+        }
+
+        run_subscribed(vec![], self.patterns, db, dst, shutdown).await
+    }
+}
+
+impl Punsubscribe {
+    /// Parse a `Punsubscribe` instance from a received frame.
+    ///
+    /// # Format
+    ///
+    /// ```text
+    /// PUNSUBSCRIBE [pattern [pattern ...]]
+    /// ```
+    pub(crate) fn parse_frames(parse: &mut Parse) -> crate::Result<Punsubscribe> {
+        Ok(Punsubscribe {
+            patterns: parse_names(parse)?,
+            valid: true,
+        })
+    }
+
+    /// Apply the `Punsubscribe` command outside of the subscribed state, like
+    /// `Unsubscribe::apply`.
+    pub(crate) async fn apply(self, dst: &mut Connection) -> crate::Result<()> {
+        if !self.valid {
+            dst.write_frame(&resp_invalid_arguments()).await?;
+            return Ok(());
+        }
+
+        write_unsubscribed("punsubscribe", self.patterns, dst).await
+    }
+}
+
+/// Parse the channel or pattern names left in `parse`, there may be none.
+fn parse_names(parse: &mut Parse) -> crate::Result<Vec<String>> {
+    let mut names = vec![];
+    loop {
+        match parse.next_string() {
+            Ok(s) => names.push(s),
+            Err(ParseError::EndOfStream) => break,
+            Err(err) => return Err(err.into()),
+        }
+    }
+    Ok(names)
+}
+
+/// Acknowledge an unsubscribe request of a client without subscriptions.
+async fn write_unsubscribed(
+    kind: &'static str,
+    names: Vec<String>,
+    dst: &mut Connection,
+) -> crate::Result<()> {
+    if names.is_empty() {
+        dst.write_push(&make_unsubscribe_frame(kind, None, 0))
+            .await?;
+    }
+    for name in names {
+        dst.write_push(&make_unsubscribe_frame(kind, Some(name), 0))
+            .await?;
+    }
+    Ok(())
+}
+
 impl Invalid for Subscribe {
     fn new_invalid() -> Subscribe {
         Subscribe {
@@ -391,3 +635,21 @@ impl Invalid for Unsubscribe {
         }
     }
 }
+
+impl Invalid for Psubscribe {
+    fn new_invalid() -> Psubscribe {
+        Psubscribe {
+            patterns: vec![],
+            valid: false,
+        }
+    }
+}
+
+impl Invalid for Punsubscribe {
+    fn new_invalid() -> Punsubscribe {
+        Punsubscribe {
+            patterns: vec![],
+            valid: false,
+        }
+    }
+}
@@ -0,0 +1,145 @@
+use std::sync::Arc;
+
+use crate::cmd::{Invalid, Parse};
+use crate::config::is_use_txn_api;
+use crate::tikv::errors::{AsyncResult, REDIS_NOT_SUPPORTED_ERR};
+use crate::tikv::string::StringCommandCtx;
+use crate::utils::{resp_err, resp_invalid_arguments};
+use crate::{Connection, Frame};
+
+use crate::config::LOGGER;
+use crate::tikv::backend::Transaction;
+use bytes::Bytes;
+use slog::debug;
+use tokio::sync::Mutex;
+
+/// `CL.THROTTLE key max_burst count_per_period period [quantity]`, allows
+/// `count_per_period` requests every `period` seconds with bursts of up to
+/// `max_burst` more. Replies with whether the request is limited, the limit,
+/// the remaining requests, and the seconds before a retry and before the
+/// limit is fully reset.
+#[derive(Debug, Clone)]
+pub struct Throttle {
+    key: String,
+    max_burst: i64,
+    count_per_period: i64,
+    period: i64,
+    quantity: i64,
+    valid: bool,
+}
+
+impl Throttle {
+    pub fn new(
+        key: &str,
+        max_burst: i64,
+        count_per_period: i64,
+        period: i64,
+        quantity: i64,
+    ) -> Throttle {
+        let mut throttle = Throttle {
+            key: key.to_string(),
+            max_burst,
+            count_per_period,
+            period,
+            quantity,
+            valid: true,
+        };
+        if throttle.emission_interval() == 0 || max_burst < 0 || quantity < 0 {
+            throttle.valid = false;
+        }
+        throttle
+    }
+
+    pub(crate) fn parse_frames(parse: &mut Parse) -> crate::Result<Throttle> {
+        let key = parse.next_string()?;
+        let max_burst = parse.next_int()?;
+        let count_per_period = parse.next_int()?;
+        let period = parse.next_int()?;
+        let quantity = if parse.remaining() > 0 {
+            parse.next_int()?
+        } else {
+            1
+        };
+        Ok(Throttle::new(
+            &key,
+            max_burst,
+            count_per_period,
+            period,
+            quantity,
+        ))
+    }
+
+    pub(crate) fn parse_argv(argv: &Vec<Bytes>) -> crate::Result<Throttle> {
+        if argv.len() != 4 && argv.len() != 5 {
+            return Ok(Throttle::new_invalid());
+        }
+        let mut ints = vec![];
+        for arg in &argv[1..] {
+            match String::from_utf8_lossy(arg).parse::<i64>() {
+                Ok(n) => ints.push(n),
+                Err(_) => return Ok(Throttle::new_invalid()),
+            }
+        }
+        Ok(Throttle::new(
+            &String::from_utf8_lossy(&argv[0]),
+            ints[0],
+            ints[1],
+            ints[2],
+            ints.get(3).copied().unwrap_or(1),
+        ))
+    }
+
+    /// Microseconds between two requests at the sustained rate, 0 if the rate
+    /// is not valid
+    fn emission_interval(&self) -> i64 {
+        if self.count_per_period <= 0 || self.period <= 0 {
+            return 0;
+        }
+        self.period.saturating_mul(1_000_000) / self.count_per_period
+    }
+
+    pub(crate) async fn apply(self, dst: &mut Connection) -> crate::Result<()> {
+        let response = self.throttle(None).await.unwrap_or_else(Into::into);
+        debug!(
+            LOGGER,
+            "res, {} -> {}, {:?}",
+            dst.local_addr(),
+            dst.peer_addr(),
+            response
+        );
+        dst.write_frame(&response).await?;
+
+        Ok(())
+    }
+
+    pub async fn throttle(&self, txn: Option<Arc<Mutex<Transaction>>>) -> AsyncResult<Frame> {
+        if !self.valid {
+            return Ok(resp_invalid_arguments());
+        }
+        if is_use_txn_api() {
+            StringCommandCtx::new(txn)
+                .do_async_txnkv_throttle(
+                    &self.key,
+                    self.max_burst,
+                    self.emission_interval(),
+                    self.quantity,
+                )
+                .await
+        } else {
+            Ok(resp_err(REDIS_NOT_SUPPORTED_ERR))
+        }
+    }
+}
+
+impl Invalid for Throttle {
+    fn new_invalid() -> Throttle {
+        Throttle {
+            key: "".to_string(),
+            max_burst: 0,
+            count_per_period: 0,
+            period: 0,
+            quantity: 0,
+            valid: false,
+        }
+    }
+}
@@ -0,0 +1,114 @@
+use crate::cmd::{Invalid, Parse};
+use crate::config::LOGGER;
+use crate::config_instance_id_or_default;
+use crate::tikv::expiration::expiration_forecast;
+use crate::utils::{resp_array, resp_bulk, resp_err, resp_int, resp_invalid_arguments};
+use crate::{Connection, Frame};
+use slog::debug;
+
+/// Most buckets of a forecast, a day of 1m buckets
+const MAX_BUCKETS: usize = 1440;
+
+/// `TTLFORECAST [BUCKET 1m|5m|1h] [COUNT count]` replies with the number of
+/// keys of the namespace expiring in each of the next `count` buckets, 60
+/// buckets of 1m by default.
+#[derive(Debug, Clone)]
+pub struct Ttlforecast {
+    bucket: String,
+    bucket_ms: u64,
+    count: usize,
+    valid: bool,
+}
+
+impl Ttlforecast {
+    pub(crate) fn parse_frames(parse: &mut Parse) -> crate::Result<Ttlforecast> {
+        let mut forecast = Ttlforecast {
+            bucket: "1m".to_owned(),
+            bucket_ms: 60_000,
+            count: 60,
+            valid: true,
+        };
+        while let Ok(option) = parse.next_string() {
+            let value = match parse.next_string() {
+                Ok(value) => value.to_lowercase(),
+                Err(_) => return Ok(Ttlforecast::new_invalid()),
+            };
+            match option.to_uppercase().as_str() {
+                "BUCKET" => {
+                    forecast.bucket_ms = match value.as_str() {
+                        "1m" => 60_000,
+                        "5m" => 300_000,
+                        "1h" => 3_600_000,
+                        _ => return Ok(Ttlforecast::new_invalid()),
+                    };
+                    forecast.bucket = value;
+                }
+                "COUNT" => match value.parse::<usize>() {
+                    Ok(count) if count > 0 && count <= MAX_BUCKETS => forecast.count = count,
+                    _ => return Ok(Ttlforecast::new_invalid()),
+                },
+                _ => return Ok(Ttlforecast::new_invalid()),
+            }
+        }
+        Ok(forecast)
+    }
+
+    pub(crate) async fn apply(self, dst: &mut Connection) -> crate::Result<()> {
+        let response = self.ttlforecast().await;
+        debug!(
+            LOGGER,
+            "res, {} -> {}, {:?}",
+            dst.local_addr(),
+            dst.peer_addr(),
+            response
+        );
+        dst.write_frame(&response).await?;
+
+        Ok(())
+    }
+
+    /// The forecast as field and value pairs like XINFO
+    async fn ttlforecast(&self) -> Frame {
+        if !self.valid {
+            return resp_invalid_arguments();
+        }
+        let forecast = match expiration_forecast(self.bucket_ms, self.count).await {
+            Ok(forecast) => forecast,
+            Err(e) => return resp_err(e),
+        };
+        let field = |name: &str| resp_bulk(name.as_bytes().to_vec());
+        resp_array(vec![
+            field("namespace"),
+            resp_bulk(config_instance_id_or_default().into_bytes()),
+            field("bucket"),
+            resp_bulk(self.bucket.clone().into_bytes()),
+            field("start"),
+            resp_int(forecast.now as i64),
+            field("buckets"),
+            resp_array(
+                forecast
+                    .buckets
+                    .into_iter()
+                    .map(|count| resp_int(count as i64))
+                    .collect(),
+            ),
+            field("later"),
+            resp_int(forecast.later as i64),
+            field("overdue"),
+            resp_int(forecast.overdue as i64),
+            field("persistent"),
+            resp_int(forecast.persistent as i64),
+        ])
+    }
+}
+
+impl Invalid for Ttlforecast {
+    fn new_invalid() -> Ttlforecast {
+        Ttlforecast {
+            bucket: "".to_owned(),
+            bucket_ms: 0,
+            count: 0,
+            valid: false,
+        }
+    }
+}
@@ -0,0 +1,71 @@
+use crate::cmd::{Invalid, Parse};
+use crate::config::is_use_txn_api;
+use crate::tikv::errors::{AsyncResult, REDIS_NOT_SUPPORTED_ERR};
+use crate::tikv::stamp::stamp_key;
+use crate::tikv::{get_txn_client, KEY_ENCODER};
+
+use tikv_client::Value;
+
+/// A watched key with its meta value and its write stamp when it was watched,
+/// see `crate::tikv::stamp`, None if the key did not exist or had no stamp.
+pub type WatchedKey = (String, Option<Value>, Option<Value>);
+
+#[derive(Debug, Clone)]
+pub struct Watch {
+    keys: Vec<String>,
+    valid: bool,
+}
+
+impl Watch {
+    pub fn new(keys: Vec<String>) -> Watch {
+        Watch { keys, valid: true }
+    }
+
+    /// Get the keys
+    pub fn keys(&self) -> &Vec<String> {
+        &self.keys
+    }
+
+    pub fn valid(&self) -> bool {
+        self.valid
+    }
+
+    pub(crate) fn parse_frames(parse: &mut Parse) -> crate::Result<Watch> {
+        let mut keys = vec![];
+        while let Ok(key) = parse.next_string() {
+            keys.push(key);
+        }
+        if keys.is_empty() {
+            return Ok(Watch::new_invalid());
+        }
+        Ok(Watch::new(keys))
+    }
+
+    /// Read the meta values and the stamps of the keys, EXEC is aborted if
+    /// one of them has changed when it runs.
+    pub async fn watch(&self) -> AsyncResult<Vec<WatchedKey>> {
+        if !is_use_txn_api() {
+            return Err(REDIS_NOT_SUPPORTED_ERR);
+        }
+        let client = get_txn_client()?;
+        let mut txn = client.begin().await?;
+        let mut watched = Vec::with_capacity(self.keys.len());
+        for key in &self.keys {
+            let meta_key = KEY_ENCODER.encode_txnkv_meta_key(key);
+            let meta_value = txn.get(meta_key).await?;
+            let stamp = txn.get(stamp_key(key)).await?;
+            watched.push((key.clone(), meta_value, stamp));
+        }
+        txn.rollback().await?;
+        Ok(watched)
+    }
+}
+
+impl Invalid for Watch {
+    fn new_invalid() -> Watch {
+        Watch {
+            keys: vec![],
+            valid: false,
+        }
+    }
+}
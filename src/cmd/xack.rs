@@ -0,0 +1,107 @@
+use std::sync::Arc;
+
+use crate::cmd::{Invalid, Parse};
+use crate::config::is_use_txn_api;
+use crate::tikv::errors::{AsyncResult, REDIS_NOT_SUPPORTED_ERR};
+use crate::tikv::stream::{parse_stream_id, StreamCommandCtx, StreamId};
+use crate::utils::{resp_err, resp_invalid_arguments};
+use crate::{Connection, Frame};
+
+use crate::config::LOGGER;
+use crate::tikv::backend::Transaction;
+use bytes::Bytes;
+use slog::debug;
+use tokio::sync::Mutex;
+
+#[derive(Debug, Clone)]
+pub struct Xack {
+    key: String,
+    group: String,
+    ids: Vec<StreamId>,
+    valid: bool,
+}
+
+impl Xack {
+    pub fn new(key: &str, group: &str, ids: Vec<StreamId>) -> Xack {
+        Xack {
+            key: key.to_owned(),
+            group: group.to_owned(),
+            ids,
+            valid: true,
+        }
+    }
+
+    /// Get the key
+    pub fn key(&self) -> &str {
+        &self.key
+    }
+
+    pub(crate) fn parse_frames(parse: &mut Parse) -> crate::Result<Xack> {
+        let mut args = vec![];
+        while let Ok(arg) = parse.next_bytes() {
+            args.push(arg);
+        }
+        Ok(Xack::from_args(&args))
+    }
+
+    pub(crate) fn parse_argv(argv: &Vec<Bytes>) -> crate::Result<Xack> {
+        Ok(Xack::from_args(argv))
+    }
+
+    /// Parse `key group id [id ...]`
+    fn from_args(args: &[Bytes]) -> Xack {
+        if args.len() < 3 {
+            return Xack::new_invalid();
+        }
+        let mut ids = Vec::with_capacity(args.len() - 2);
+        for id in &args[2..] {
+            match parse_stream_id(&String::from_utf8_lossy(id), 0) {
+                Some(id) => ids.push(id),
+                None => return Xack::new_invalid(),
+            }
+        }
+        Xack::new(
+            &String::from_utf8_lossy(&args[0]),
+            &String::from_utf8_lossy(&args[1]),
+            ids,
+        )
+    }
+
+    pub(crate) async fn apply(self, dst: &mut Connection) -> crate::Result<()> {
+        let response = self.xack(None).await.unwrap_or_else(Into::into);
+        debug!(
+            LOGGER,
+            "res, {} -> {}, {:?}",
+            dst.local_addr(),
+            dst.peer_addr(),
+            response
+        );
+        dst.write_frame(&response).await?;
+
+        Ok(())
+    }
+
+    pub async fn xack(&self, txn: Option<Arc<Mutex<Transaction>>>) -> AsyncResult<Frame> {
+        if !self.valid {
+            return Ok(resp_invalid_arguments());
+        }
+        if is_use_txn_api() {
+            StreamCommandCtx::new(txn)
+                .do_async_txnkv_xack(&self.key, &self.group, &self.ids)
+                .await
+        } else {
+            Ok(resp_err(REDIS_NOT_SUPPORTED_ERR))
+        }
+    }
+}
+
+impl Invalid for Xack {
+    fn new_invalid() -> Xack {
+        Xack {
+            key: "".to_owned(),
+            group: "".to_owned(),
+            ids: vec![],
+            valid: false,
+        }
+    }
+}
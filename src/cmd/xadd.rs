@@ -0,0 +1,160 @@
+use std::sync::Arc;
+
+use crate::cmd::{Invalid, Parse};
+use crate::config::is_use_txn_api;
+use crate::tikv::errors::{AsyncResult, REDIS_NOT_SUPPORTED_ERR};
+use crate::tikv::stream::{parse_stream_id, NewEntryId, StreamCommandCtx};
+use crate::utils::{resp_err, resp_invalid_arguments};
+use crate::{Connection, Frame};
+
+use crate::config::LOGGER;
+use crate::tikv::backend::Transaction;
+use bytes::Bytes;
+use slog::debug;
+use tokio::sync::Mutex;
+
+#[derive(Debug, Clone)]
+pub struct Xadd {
+    key: String,
+    id: NewEntryId,
+    pairs: Vec<Bytes>,
+    maxlen: Option<u64>,
+    nomkstream: bool,
+    valid: bool,
+}
+
+impl Xadd {
+    pub fn new(
+        key: &str,
+        id: NewEntryId,
+        pairs: Vec<Bytes>,
+        maxlen: Option<u64>,
+        nomkstream: bool,
+    ) -> Xadd {
+        Xadd {
+            key: key.to_string(),
+            id,
+            pairs,
+            maxlen,
+            nomkstream,
+            valid: true,
+        }
+    }
+
+    /// Get the key
+    pub fn key(&self) -> &str {
+        &self.key
+    }
+
+    pub(crate) fn parse_frames(parse: &mut Parse) -> crate::Result<Xadd> {
+        let mut args = vec![];
+        while let Ok(arg) = parse.next_bytes() {
+            args.push(arg);
+        }
+        Ok(Xadd::from_args(&args))
+    }
+
+    pub(crate) fn parse_argv(argv: &Vec<Bytes>) -> crate::Result<Xadd> {
+        Ok(Xadd::from_args(argv))
+    }
+
+    /// Parse `key [NOMKSTREAM] [MAXLEN [=|~] len] *|id field value
+    /// [field value ...]`, an approximate `~` trim is exact.
+    fn from_args(args: &[Bytes]) -> Xadd {
+        if args.is_empty() {
+            return Xadd::new_invalid();
+        }
+        let key = String::from_utf8_lossy(&args[0]);
+        let mut maxlen = None;
+        let mut nomkstream = false;
+
+        let mut idx = 1;
+        while idx < args.len() {
+            match String::from_utf8_lossy(&args[idx]).to_uppercase().as_str() {
+                "NOMKSTREAM" => nomkstream = true,
+                "MAXLEN" => {
+                    idx += 1;
+                    if matches!(args.get(idx).map(|a| &a[..]), Some(b"=") | Some(b"~")) {
+                        idx += 1;
+                    }
+                    match args
+                        .get(idx)
+                        .and_then(|a| String::from_utf8_lossy(a).parse::<u64>().ok())
+                    {
+                        Some(len) => maxlen = Some(len),
+                        None => return Xadd::new_invalid(),
+                    }
+                }
+                _ => break,
+            }
+            idx += 1;
+        }
+
+        let pairs = match args.get(idx + 1..) {
+            Some(pairs) if !pairs.is_empty() && pairs.len() % 2 == 0 => pairs.to_vec(),
+            _ => return Xadd::new_invalid(),
+        };
+        let id = String::from_utf8_lossy(&args[idx]);
+        let id = if id == "*" {
+            NewEntryId::Auto
+        } else if let Some(ms) = id.strip_suffix("-*") {
+            match ms.parse::<u64>() {
+                Ok(ms) => NewEntryId::AutoSeq(ms),
+                Err(_) => return Xadd::new_invalid(),
+            }
+        } else {
+            match parse_stream_id(&id, 0) {
+                Some(id) if id != (u64::MAX, u64::MAX) => NewEntryId::Explicit(id),
+                _ => return Xadd::new_invalid(),
+            }
+        };
+
+        Xadd::new(&key, id, pairs, maxlen, nomkstream)
+    }
+
+    pub(crate) async fn apply(self, dst: &mut Connection) -> crate::Result<()> {
+        let response = self.xadd(None).await.unwrap_or_else(Into::into);
+        debug!(
+            LOGGER,
+            "res, {} -> {}, {:?}",
+            dst.local_addr(),
+            dst.peer_addr(),
+            response
+        );
+        dst.write_frame(&response).await?;
+
+        Ok(())
+    }
+
+    pub async fn xadd(&self, txn: Option<Arc<Mutex<Transaction>>>) -> AsyncResult<Frame> {
+        if !self.valid {
+            return Ok(resp_invalid_arguments());
+        }
+        if is_use_txn_api() {
+            StreamCommandCtx::new(txn)
+                .do_async_txnkv_xadd(
+                    &self.key,
+                    self.id,
+                    &self.pairs,
+                    self.maxlen,
+                    self.nomkstream,
+                )
+                .await
+        } else {
+            Ok(resp_err(REDIS_NOT_SUPPORTED_ERR))
+        }
+    }
+}
+
+impl Invalid for Xadd {
+    fn new_invalid() -> Xadd {
+        Xadd {
+            key: "".to_string(),
+            id: NewEntryId::Auto,
+            pairs: vec![],
+            maxlen: None,
+            nomkstream: false,
+            valid: false,
+        }
+    }
+}
@@ -0,0 +1,177 @@
+use std::sync::Arc;
+
+use crate::cmd::{Invalid, Parse};
+use crate::config::is_use_txn_api;
+use crate::tikv::errors::{AsyncResult, REDIS_NOT_SUPPORTED_ERR};
+use crate::tikv::stream::{parse_stream_id, ClaimOptions, StreamCommandCtx, StreamId};
+use crate::utils::{resp_err, resp_invalid_arguments};
+use crate::{Connection, Frame};
+
+use crate::config::LOGGER;
+use crate::tikv::backend::Transaction;
+use bytes::Bytes;
+use slog::debug;
+use tokio::sync::Mutex;
+
+#[derive(Debug, Clone)]
+pub struct Xclaim {
+    key: String,
+    group: String,
+    consumer: String,
+    min_idle: u64,
+    ids: Vec<StreamId>,
+    options: ClaimOptions,
+    valid: bool,
+}
+
+impl Xclaim {
+    pub fn new(
+        key: &str,
+        group: &str,
+        consumer: &str,
+        min_idle: u64,
+        ids: Vec<StreamId>,
+        options: ClaimOptions,
+    ) -> Xclaim {
+        Xclaim {
+            key: key.to_owned(),
+            group: group.to_owned(),
+            consumer: consumer.to_owned(),
+            min_idle,
+            ids,
+            options,
+            valid: true,
+        }
+    }
+
+    /// Get the key
+    pub fn key(&self) -> &str {
+        &self.key
+    }
+
+    pub(crate) fn parse_frames(parse: &mut Parse) -> crate::Result<Xclaim> {
+        let mut args = vec![];
+        while let Ok(arg) = parse.next_bytes() {
+            args.push(arg);
+        }
+        Ok(Xclaim::from_args(&args))
+    }
+
+    pub(crate) fn parse_argv(argv: &Vec<Bytes>) -> crate::Result<Xclaim> {
+        Ok(Xclaim::from_args(argv))
+    }
+
+    /// Parse `key group consumer min-idle-time id [id ...] [IDLE ms]
+    /// [TIME ms] [RETRYCOUNT count] [FORCE] [JUSTID] [LASTID id]`, the last id
+    /// of LASTID is ignored as claims never move the group.
+    fn from_args(args: &[Bytes]) -> Xclaim {
+        if args.len() < 5 {
+            return Xclaim::new_invalid();
+        }
+        let min_idle = match String::from_utf8_lossy(&args[3]).parse::<u64>() {
+            Ok(n) => n,
+            Err(_) => return Xclaim::new_invalid(),
+        };
+
+        let mut ids = vec![];
+        let mut idx = 4;
+        while let Some(id) = args
+            .get(idx)
+            .and_then(|a| parse_stream_id(&String::from_utf8_lossy(a), 0))
+        {
+            ids.push(id);
+            idx += 1;
+        }
+        if ids.is_empty() {
+            return Xclaim::new_invalid();
+        }
+
+        let mut options = ClaimOptions::default();
+        while idx < args.len() {
+            let option = String::from_utf8_lossy(&args[idx]).to_uppercase();
+            match option.as_str() {
+                "FORCE" => options.force = true,
+                "JUSTID" => options.justid = true,
+                "IDLE" | "TIME" | "RETRYCOUNT" | "LASTID" if args.len() > idx + 1 => {
+                    idx += 1;
+                    let arg = String::from_utf8_lossy(&args[idx]);
+                    if option == "LASTID" {
+                        if parse_stream_id(&arg, 0).is_none() {
+                            return Xclaim::new_invalid();
+                        }
+                        idx += 1;
+                        continue;
+                    }
+                    let n = match arg.parse::<u64>() {
+                        Ok(n) => n,
+                        Err(_) => return Xclaim::new_invalid(),
+                    };
+                    match option.as_str() {
+                        "IDLE" => options.idle = Some(n),
+                        "TIME" => options.time = Some(n),
+                        _ => options.retry_count = Some(n),
+                    }
+                }
+                _ => return Xclaim::new_invalid(),
+            }
+            idx += 1;
+        }
+
+        Xclaim::new(
+            &String::from_utf8_lossy(&args[0]),
+            &String::from_utf8_lossy(&args[1]),
+            &String::from_utf8_lossy(&args[2]),
+            min_idle,
+            ids,
+            options,
+        )
+    }
+
+    pub(crate) async fn apply(self, dst: &mut Connection) -> crate::Result<()> {
+        let response = self.xclaim(None).await.unwrap_or_else(Into::into);
+        debug!(
+            LOGGER,
+            "res, {} -> {}, {:?}",
+            dst.local_addr(),
+            dst.peer_addr(),
+            response
+        );
+        dst.write_frame(&response).await?;
+
+        Ok(())
+    }
+
+    pub async fn xclaim(&self, txn: Option<Arc<Mutex<Transaction>>>) -> AsyncResult<Frame> {
+        if !self.valid {
+            return Ok(resp_invalid_arguments());
+        }
+        if is_use_txn_api() {
+            StreamCommandCtx::new(txn)
+                .do_async_txnkv_xclaim(
+                    &self.key,
+                    &self.group,
+                    &self.consumer,
+                    self.min_idle,
+                    &self.ids,
+                    self.options,
+                )
+                .await
+        } else {
+            Ok(resp_err(REDIS_NOT_SUPPORTED_ERR))
+        }
+    }
+}
+
+impl Invalid for Xclaim {
+    fn new_invalid() -> Xclaim {
+        Xclaim {
+            key: "".to_owned(),
+            group: "".to_owned(),
+            consumer: "".to_owned(),
+            min_idle: 0,
+            ids: vec![],
+            options: ClaimOptions::default(),
+            valid: false,
+        }
+    }
+}
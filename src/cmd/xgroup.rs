@@ -0,0 +1,186 @@
+use std::sync::Arc;
+
+use crate::cmd::{Invalid, Parse};
+use crate::config::is_use_txn_api;
+use crate::tikv::errors::{AsyncResult, REDIS_NOT_SUPPORTED_ERR};
+use crate::tikv::stream::{parse_stream_id, StreamCommandCtx, StreamId};
+use crate::utils::{resp_err, resp_invalid_arguments};
+use crate::{Connection, Frame};
+
+use crate::config::LOGGER;
+use crate::tikv::backend::Transaction;
+use bytes::Bytes;
+use slog::debug;
+use tokio::sync::Mutex;
+
+/// Subcommands of XGROUP, an id of None is `$`, the last entry of the stream
+#[derive(Debug, Clone)]
+enum Subcommand {
+    Create {
+        group: String,
+        id: Option<StreamId>,
+        mkstream: bool,
+    },
+    Destroy {
+        group: String,
+    },
+    Setid {
+        group: String,
+        id: Option<StreamId>,
+    },
+    Delconsumer {
+        group: String,
+        consumer: String,
+    },
+}
+
+/// `XGROUP CREATE|DESTROY|SETID|DELCONSUMER key group ...`, consumers exist
+/// through their pending entries only so CREATECONSUMER is not supported.
+#[derive(Debug, Clone)]
+pub struct Xgroup {
+    key: String,
+    subcommand: Option<Subcommand>,
+    valid: bool,
+}
+
+impl Xgroup {
+    /// Get the key
+    pub fn key(&self) -> &str {
+        &self.key
+    }
+
+    pub(crate) fn parse_frames(parse: &mut Parse) -> crate::Result<Xgroup> {
+        let mut args = vec![];
+        while let Ok(arg) = parse.next_bytes() {
+            args.push(arg);
+        }
+        Ok(Xgroup::from_args(&args))
+    }
+
+    pub(crate) fn parse_argv(argv: &Vec<Bytes>) -> crate::Result<Xgroup> {
+        Ok(Xgroup::from_args(argv))
+    }
+
+    /// Parse `CREATE key group id|$ [MKSTREAM] [ENTRIESREAD n]`, `DESTROY key
+    /// group`, `SETID key group id|$ [ENTRIESREAD n]` or `DELCONSUMER key group
+    /// consumer`, the read counter of ENTRIESREAD is not kept.
+    fn from_args(args: &[Bytes]) -> Xgroup {
+        if args.len() < 3 {
+            return Xgroup::new_invalid();
+        }
+        let key = String::from_utf8_lossy(&args[1]).to_string();
+        let group = String::from_utf8_lossy(&args[2]).to_string();
+        let subcommand = match String::from_utf8_lossy(&args[0]).to_uppercase().as_str() {
+            "CREATE" | "SETID" if args.len() >= 4 => {
+                let id = match group_id(&args[3]) {
+                    Some(id) => id,
+                    None => return Xgroup::new_invalid(),
+                };
+                let is_create = args[0].eq_ignore_ascii_case(b"CREATE");
+                let mut mkstream = false;
+                let mut idx = 4;
+                while idx < args.len() {
+                    match String::from_utf8_lossy(&args[idx]).to_uppercase().as_str() {
+                        "MKSTREAM" if is_create => mkstream = true,
+                        "ENTRIESREAD" if args.len() > idx + 1 => {
+                            if String::from_utf8_lossy(&args[idx + 1])
+                                .parse::<i64>()
+                                .is_err()
+                            {
+                                return Xgroup::new_invalid();
+                            }
+                            idx += 1;
+                        }
+                        _ => return Xgroup::new_invalid(),
+                    }
+                    idx += 1;
+                }
+                if is_create {
+                    Some(Subcommand::Create {
+                        group,
+                        id,
+                        mkstream,
+                    })
+                } else {
+                    Some(Subcommand::Setid { group, id })
+                }
+            }
+            "DESTROY" if args.len() == 3 => Some(Subcommand::Destroy { group }),
+            "DELCONSUMER" if args.len() == 4 => Some(Subcommand::Delconsumer {
+                group,
+                consumer: String::from_utf8_lossy(&args[3]).to_string(),
+            }),
+            "CREATECONSUMER" => None,
+            _ => return Xgroup::new_invalid(),
+        };
+        Xgroup {
+            key,
+            subcommand,
+            valid: true,
+        }
+    }
+
+    pub(crate) async fn apply(self, dst: &mut Connection) -> crate::Result<()> {
+        let response = self.xgroup(None).await.unwrap_or_else(Into::into);
+        debug!(
+            LOGGER,
+            "res, {} -> {}, {:?}",
+            dst.local_addr(),
+            dst.peer_addr(),
+            response
+        );
+        dst.write_frame(&response).await?;
+
+        Ok(())
+    }
+
+    pub async fn xgroup(&self, txn: Option<Arc<Mutex<Transaction>>>) -> AsyncResult<Frame> {
+        if !self.valid {
+            return Ok(resp_invalid_arguments());
+        }
+        let subcommand = match &self.subcommand {
+            Some(subcommand) if is_use_txn_api() => subcommand,
+            _ => return Ok(resp_err(REDIS_NOT_SUPPORTED_ERR)),
+        };
+        let ctx = StreamCommandCtx::new(txn);
+        match subcommand {
+            Subcommand::Create {
+                group,
+                id,
+                mkstream,
+            } => {
+                ctx.do_async_txnkv_xgroup_create(&self.key, group, *id, *mkstream)
+                    .await
+            }
+            Subcommand::Destroy { group } => {
+                ctx.do_async_txnkv_xgroup_destroy(&self.key, group).await
+            }
+            Subcommand::Setid { group, id } => {
+                ctx.do_async_txnkv_xgroup_setid(&self.key, group, *id).await
+            }
+            Subcommand::Delconsumer { group, consumer } => {
+                ctx.do_async_txnkv_xgroup_delconsumer(&self.key, group, consumer)
+                    .await
+            }
+        }
+    }
+}
+
+/// Parse the id of a group, None for `$`
+fn group_id(arg: &[u8]) -> Option<Option<StreamId>> {
+    let id = String::from_utf8_lossy(arg);
+    if id == "$" {
+        return Some(None);
+    }
+    parse_stream_id(&id, 0).map(Some)
+}
+
+impl Invalid for Xgroup {
+    fn new_invalid() -> Xgroup {
+        Xgroup {
+            key: "".to_owned(),
+            subcommand: None,
+            valid: false,
+        }
+    }
+}
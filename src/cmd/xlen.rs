@@ -0,0 +1,83 @@
+use std::sync::Arc;
+
+use crate::cmd::{Invalid, Parse};
+use crate::config::is_use_txn_api;
+use crate::tikv::errors::{AsyncResult, REDIS_NOT_SUPPORTED_ERR};
+use crate::tikv::stream::StreamCommandCtx;
+use crate::utils::{resp_err, resp_invalid_arguments};
+use crate::{Connection, Frame};
+
+use crate::config::LOGGER;
+use crate::tikv::backend::Transaction;
+use bytes::Bytes;
+use slog::debug;
+use tokio::sync::Mutex;
+
+#[derive(Debug, Clone)]
+pub struct Xlen {
+    key: String,
+    valid: bool,
+}
+
+impl Xlen {
+    pub fn new(key: &str) -> Xlen {
+        Xlen {
+            key: key.to_owned(),
+            valid: true,
+        }
+    }
+
+    pub fn key(&self) -> &str {
+        &self.key
+    }
+
+    pub(crate) fn parse_frames(parse: &mut Parse) -> crate::Result<Xlen> {
+        let key = parse.next_string()?;
+
+        Ok(Xlen { key, valid: true })
+    }
+
+    pub(crate) fn parse_argv(argv: &Vec<Bytes>) -> crate::Result<Xlen> {
+        if argv.len() != 1 {
+            return Ok(Xlen::new_invalid());
+        }
+        let key = &String::from_utf8_lossy(&argv[0]);
+        Ok(Xlen::new(key))
+    }
+
+    pub(crate) async fn apply(self, dst: &mut Connection) -> crate::Result<()> {
+        let response = self.xlen(None).await?;
+        debug!(
+            LOGGER,
+            "res, {} -> {}, {:?}",
+            dst.local_addr(),
+            dst.peer_addr(),
+            response
+        );
+        dst.write_frame(&response).await?;
+
+        Ok(())
+    }
+
+    pub async fn xlen(self, txn: Option<Arc<Mutex<Transaction>>>) -> AsyncResult<Frame> {
+        if !self.valid {
+            return Ok(resp_invalid_arguments());
+        }
+        if is_use_txn_api() {
+            StreamCommandCtx::new(txn)
+                .do_async_txnkv_xlen(&self.key)
+                .await
+        } else {
+            Ok(resp_err(REDIS_NOT_SUPPORTED_ERR))
+        }
+    }
+}
+
+impl Invalid for Xlen {
+    fn new_invalid() -> Xlen {
+        Xlen {
+            key: "".to_owned(),
+            valid: false,
+        }
+    }
+}
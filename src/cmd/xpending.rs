@@ -0,0 +1,155 @@
+use std::sync::Arc;
+
+use crate::cmd::xrange::range_bound;
+use crate::cmd::{Invalid, Parse};
+use crate::config::is_use_txn_api;
+use crate::tikv::errors::{AsyncResult, REDIS_NOT_SUPPORTED_ERR};
+use crate::tikv::stream::{PendingRange, StreamCommandCtx};
+use crate::utils::{resp_array, resp_err, resp_invalid_arguments};
+use crate::{Connection, Frame};
+
+use crate::config::LOGGER;
+use crate::tikv::backend::Transaction;
+use bytes::Bytes;
+use slog::debug;
+use tokio::sync::Mutex;
+
+/// The extended form of XPENDING, its bounds are resolved like the ones of
+/// XRANGE.
+#[derive(Debug, Clone)]
+struct Extended {
+    idle: u64,
+    start: String,
+    end: String,
+    count: u32,
+    consumer: Option<String>,
+}
+
+/// `XPENDING key group [[IDLE min-idle-time] start end count [consumer]]`
+#[derive(Debug, Clone)]
+pub struct Xpending {
+    key: String,
+    group: String,
+    extended: Option<Extended>,
+    valid: bool,
+}
+
+impl Xpending {
+    /// Get the key
+    pub fn key(&self) -> &str {
+        &self.key
+    }
+
+    pub(crate) fn parse_frames(parse: &mut Parse) -> crate::Result<Xpending> {
+        let mut args = vec![];
+        while let Ok(arg) = parse.next_bytes() {
+            args.push(arg);
+        }
+        Ok(Xpending::from_args(&args))
+    }
+
+    pub(crate) fn parse_argv(argv: &Vec<Bytes>) -> crate::Result<Xpending> {
+        Ok(Xpending::from_args(argv))
+    }
+
+    fn from_args(args: &[Bytes]) -> Xpending {
+        if args.len() < 2 {
+            return Xpending::new_invalid();
+        }
+        let mut xpending = Xpending {
+            key: String::from_utf8_lossy(&args[0]).to_string(),
+            group: String::from_utf8_lossy(&args[1]).to_string(),
+            extended: None,
+            valid: true,
+        };
+        let mut rest = &args[2..];
+        if rest.is_empty() {
+            return xpending;
+        }
+
+        let mut idle = 0;
+        if rest[0].eq_ignore_ascii_case(b"IDLE") {
+            match rest
+                .get(1)
+                .and_then(|a| String::from_utf8_lossy(a).parse::<u64>().ok())
+            {
+                Some(n) => idle = n,
+                None => return Xpending::new_invalid(),
+            }
+            rest = &rest[2..];
+        }
+        if rest.len() != 3 && rest.len() != 4 {
+            return Xpending::new_invalid();
+        }
+        let count = match String::from_utf8_lossy(&rest[2]).parse::<i64>() {
+            Ok(n) => n.clamp(0, u32::MAX as i64) as u32,
+            Err(_) => return Xpending::new_invalid(),
+        };
+        xpending.extended = Some(Extended {
+            idle,
+            start: String::from_utf8_lossy(&rest[0]).to_string(),
+            end: String::from_utf8_lossy(&rest[1]).to_string(),
+            count,
+            consumer: rest.get(3).map(|c| String::from_utf8_lossy(c).to_string()),
+        });
+        xpending
+    }
+
+    pub(crate) async fn apply(self, dst: &mut Connection) -> crate::Result<()> {
+        let response = self.xpending(None).await.unwrap_or_else(Into::into);
+        debug!(
+            LOGGER,
+            "res, {} -> {}, {:?}",
+            dst.local_addr(),
+            dst.peer_addr(),
+            response
+        );
+        dst.write_frame(&response).await?;
+
+        Ok(())
+    }
+
+    pub async fn xpending(&self, txn: Option<Arc<Mutex<Transaction>>>) -> AsyncResult<Frame> {
+        if !self.valid {
+            return Ok(resp_invalid_arguments());
+        }
+        let range = match &self.extended {
+            Some(extended) => {
+                let (start, end) = match (
+                    range_bound(&extended.start, true),
+                    range_bound(&extended.end, false),
+                ) {
+                    (Ok(Some(start)), Ok(Some(end))) => (start, end),
+                    (Err(e), _) | (_, Err(e)) => return Ok(resp_err(e)),
+                    _ => return Ok(resp_array(vec![])),
+                };
+                Some(PendingRange {
+                    idle: extended.idle,
+                    start,
+                    end,
+                    count: extended.count,
+                    consumer: extended.consumer.clone(),
+                })
+            }
+            None => None,
+        };
+        if is_use_txn_api() {
+            StreamCommandCtx::new(txn)
+                .do_async_txnkv_xpending(&self.key, &self.group, range)
+                .await
+        } else {
+            Ok(resp_err(REDIS_NOT_SUPPORTED_ERR))
+        }
+    }
+}
+
+impl Invalid for Xpending {
+    fn new_invalid() -> Xpending {
+        Xpending {
+            key: "".to_owned(),
+            group: "".to_owned(),
+            extended: None,
+            valid: false,
+        }
+    }
+}
@@ -0,0 +1,155 @@
+use std::sync::Arc;
+
+use crate::cmd::{Invalid, Parse};
+use crate::config::is_use_txn_api;
+use crate::tikv::errors::{
+    AsyncResult, RTError, REDIS_INVALID_STREAM_ID_ERR, REDIS_NOT_SUPPORTED_ERR,
+};
+use crate::tikv::stream::{
+    next_stream_id, parse_stream_id, prev_stream_id, StreamCommandCtx, StreamId,
+};
+use crate::utils::{resp_array, resp_err, resp_invalid_arguments};
+use crate::{Connection, Frame};
+
+use crate::config::LOGGER;
+use crate::tikv::backend::Transaction;
+use bytes::Bytes;
+use slog::debug;
+use tokio::sync::Mutex;
+
+/// `XRANGE key start end [COUNT count]` and `XREVRANGE key end start
+/// [COUNT count]`, the bounds are resolved once the order is known as an id
+/// without sequence is the first or the last one of its milliseconds.
+#[derive(Debug, Clone)]
+pub struct Xrange {
+    key: String,
+    first: String,
+    second: String,
+    count: Option<u32>,
+    valid: bool,
+}
+
+impl Xrange {
+    pub fn new(key: &str, first: &str, second: &str, count: Option<u32>) -> Xrange {
+        Xrange {
+            key: key.to_owned(),
+            first: first.to_owned(),
+            second: second.to_owned(),
+            count,
+            valid: true,
+        }
+    }
+
+    pub fn key(&self) -> &str {
+        &self.key
+    }
+
+    pub(crate) fn parse_frames(parse: &mut Parse) -> crate::Result<Xrange> {
+        let mut args = vec![];
+        while let Ok(arg) = parse.next_bytes() {
+            args.push(arg);
+        }
+        Ok(Xrange::from_args(&args))
+    }
+
+    pub(crate) fn parse_argv(argv: &Vec<Bytes>) -> crate::Result<Xrange> {
+        Ok(Xrange::from_args(argv))
+    }
+
+    fn from_args(args: &[Bytes]) -> Xrange {
+        if args.len() != 3 && args.len() != 5 {
+            return Xrange::new_invalid();
+        }
+        let mut count = None;
+        if args.len() == 5 {
+            if String::from_utf8_lossy(&args[3]).to_uppercase() != "COUNT" {
+                return Xrange::new_invalid();
+            }
+            match String::from_utf8_lossy(&args[4]).parse::<i64>() {
+                Ok(n) => count = Some(n.clamp(0, u32::MAX as i64) as u32),
+                Err(_) => return Xrange::new_invalid(),
+            }
+        }
+        Xrange::new(
+            &String::from_utf8_lossy(&args[0]),
+            &String::from_utf8_lossy(&args[1]),
+            &String::from_utf8_lossy(&args[2]),
+            count,
+        )
+    }
+
+    pub(crate) async fn apply(self, dst: &mut Connection, reverse: bool) -> crate::Result<()> {
+        let response = self.xrange(None, reverse).await.unwrap_or_else(Into::into);
+        debug!(
+            LOGGER,
+            "res, {} -> {}, {:?}",
+            dst.local_addr(),
+            dst.peer_addr(),
+            response
+        );
+        dst.write_frame(&response).await?;
+
+        Ok(())
+    }
+
+    pub async fn xrange(
+        &self,
+        txn: Option<Arc<Mutex<Transaction>>>,
+        reverse: bool,
+    ) -> AsyncResult<Frame> {
+        if !self.valid {
+            return Ok(resp_invalid_arguments());
+        }
+        let (start, end) = if reverse {
+            (&self.second, &self.first)
+        } else {
+            (&self.first, &self.second)
+        };
+        let (start, end) = match (range_bound(start, true), range_bound(end, false)) {
+            (Ok(Some(start)), Ok(Some(end))) => (start, end),
+            (Err(e), _) | (_, Err(e)) => return Ok(resp_err(e)),
+            _ => return Ok(resp_array(vec![])),
+        };
+        if self.count == Some(0) {
+            return Ok(resp_array(vec![]));
+        }
+        if is_use_txn_api() {
+            StreamCommandCtx::new(txn)
+                .do_async_txnkv_xrange(&self.key, start, end, self.count.unwrap_or(0), reverse)
+                .await
+        } else {
+            Ok(resp_err(REDIS_NOT_SUPPORTED_ERR))
+        }
+    }
+}
+
+/// Resolve a range bound, a `(` prefix excludes the id. None if nothing is
+/// after an excluded start or before an excluded end.
+pub(crate) fn range_bound(arg: &str, is_start: bool) -> Result<Option<StreamId>, RTError> {
+    let (exclusive, id) = match arg.strip_prefix('(') {
+        Some(id) => (true, id),
+        None => (false, arg),
+    };
+    let default_seq = if is_start { 0 } else { u64::MAX };
+    let id = parse_stream_id(id, default_seq).ok_or(REDIS_INVALID_STREAM_ID_ERR)?;
+    if !exclusive {
+        return Ok(Some(id));
+    }
+    if is_start {
+        Ok(next_stream_id(id))
+    } else {
+        Ok(prev_stream_id(id))
+    }
+}
+
+impl Invalid for Xrange {
+    fn new_invalid() -> Xrange {
+        Xrange {
+            key: "".to_owned(),
+            first: "".to_owned(),
+            second: "".to_owned(),
+            count: None,
+            valid: false,
+        }
+    }
+}
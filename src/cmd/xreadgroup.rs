@@ -0,0 +1,180 @@
+use std::sync::Arc;
+
+use crate::cmd::{Invalid, Parse};
+use crate::config::is_use_txn_api;
+use crate::tikv::errors::{AsyncResult, REDIS_NOT_SUPPORTED_ERR};
+use crate::tikv::stream::{parse_stream_id, StreamCommandCtx, StreamId};
+use crate::utils::{resp_err, resp_invalid_arguments};
+use crate::{Connection, Frame};
+
+use crate::config::LOGGER;
+use crate::tikv::backend::Transaction;
+use bytes::Bytes;
+use slog::debug;
+use tokio::sync::Mutex;
+
+/// `XREADGROUP GROUP group consumer [COUNT count] [NOACK] STREAMS key
+/// [key ...] id [id ...]`, reads never block so `BLOCK` is refused. The id
+/// `>` is None and reads the entries never delivered to the group.
+#[derive(Debug, Clone)]
+pub struct Xreadgroup {
+    group: String,
+    consumer: String,
+    keys: Vec<String>,
+    ids: Vec<Option<StreamId>>,
+    count: u32,
+    noack: bool,
+    block: bool,
+    valid: bool,
+}
+
+impl Xreadgroup {
+    pub fn new(
+        group: &str,
+        consumer: &str,
+        keys: Vec<String>,
+        ids: Vec<Option<StreamId>>,
+        count: u32,
+        noack: bool,
+    ) -> Xreadgroup {
+        Xreadgroup {
+            group: group.to_owned(),
+            consumer: consumer.to_owned(),
+            keys,
+            ids,
+            count,
+            noack,
+            block: false,
+            valid: true,
+        }
+    }
+
+    /// Get the keys
+    pub fn keys(&self) -> &Vec<String> {
+        &self.keys
+    }
+
+    pub(crate) fn parse_frames(parse: &mut Parse) -> crate::Result<Xreadgroup> {
+        let mut args = vec![];
+        while let Ok(arg) = parse.next_bytes() {
+            args.push(arg);
+        }
+        Ok(Xreadgroup::from_args(&args))
+    }
+
+    pub(crate) fn parse_argv(argv: &Vec<Bytes>) -> crate::Result<Xreadgroup> {
+        Ok(Xreadgroup::from_args(argv))
+    }
+
+    fn from_args(args: &[Bytes]) -> Xreadgroup {
+        if args.len() < 3 || !args[0].eq_ignore_ascii_case(b"GROUP") {
+            return Xreadgroup::new_invalid();
+        }
+        let group = String::from_utf8_lossy(&args[1]);
+        let consumer = String::from_utf8_lossy(&args[2]);
+        let mut count = 0;
+        let mut noack = false;
+        let mut block = false;
+
+        let mut idx = 3;
+        loop {
+            let option = match args.get(idx) {
+                Some(option) => String::from_utf8_lossy(option).to_uppercase(),
+                None => return Xreadgroup::new_invalid(),
+            };
+            match option.as_str() {
+                "STREAMS" => break,
+                "NOACK" => noack = true,
+                "COUNT" | "BLOCK" if args.len() > idx + 1 => {
+                    let n = match String::from_utf8_lossy(&args[idx + 1]).parse::<i64>() {
+                        Ok(n) if n >= 0 => n,
+                        _ => return Xreadgroup::new_invalid(),
+                    };
+                    if option == "COUNT" {
+                        count = n.min(u32::MAX as i64) as u32;
+                    } else {
+                        block = true;
+                    }
+                    idx += 1;
+                }
+                _ => return Xreadgroup::new_invalid(),
+            }
+            idx += 1;
+        }
+
+        let streams = &args[idx + 1..];
+        if streams.is_empty() || streams.len() % 2 != 0 {
+            return Xreadgroup::new_invalid();
+        }
+        let (keys, ids) = streams.split_at(streams.len() / 2);
+        let keys = keys
+            .iter()
+            .map(|k| String::from_utf8_lossy(k).to_string())
+            .collect();
+        let mut parsed_ids = Vec::with_capacity(ids.len());
+        for id in ids {
+            let id = String::from_utf8_lossy(id);
+            if id == ">" {
+                parsed_ids.push(None);
+                continue;
+            }
+            match parse_stream_id(&id, 0) {
+                Some(id) => parsed_ids.push(Some(id)),
+                None => return Xreadgroup::new_invalid(),
+            }
+        }
+
+        let mut xreadgroup = Xreadgroup::new(&group, &consumer, keys, parsed_ids, count, noack);
+        xreadgroup.block = block;
+        xreadgroup
+    }
+
+    pub(crate) async fn apply(self, dst: &mut Connection) -> crate::Result<()> {
+        let response = self.xreadgroup(None).await.unwrap_or_else(Into::into);
+        debug!(
+            LOGGER,
+            "res, {} -> {}, {:?}",
+            dst.local_addr(),
+            dst.peer_addr(),
+            response
+        );
+        dst.write_frame(&response).await?;
+
+        Ok(())
+    }
+
+    pub async fn xreadgroup(&self, txn: Option<Arc<Mutex<Transaction>>>) -> AsyncResult<Frame> {
+        if !self.valid {
+            return Ok(resp_invalid_arguments());
+        }
+        if is_use_txn_api() && !self.block {
+            StreamCommandCtx::new(txn)
+                .do_async_txnkv_xreadgroup(
+                    &self.group,
+                    &self.consumer,
+                    &self.keys,
+                    &self.ids,
+                    self.count,
+                    self.noack,
+                )
+                .await
+        } else {
+            Ok(resp_err(REDIS_NOT_SUPPORTED_ERR))
+        }
+    }
+}
+
+impl Invalid for Xreadgroup {
+    fn new_invalid() -> Xreadgroup {
+        Xreadgroup {
+            group: "".to_owned(),
+            consumer: "".to_owned(),
+            keys: vec![],
+            ids: vec![],
+            count: 0,
+            noack: false,
+            block: false,
+            valid: false,
+        }
+    }
+}
@@ -0,0 +1,198 @@
+use std::sync::Arc;
+
+use crate::blocking::signal_key_ready;
+use crate::cmd::sintercard::split_numkeys;
+use crate::cmd::{Invalid, Parse};
+use crate::config::is_use_txn_api;
+use crate::tikv::errors::{AsyncResult, REDIS_NOT_SUPPORTED_ERR};
+use crate::tikv::intersect::{Aggregate, SetOperation};
+use crate::tikv::zset::ZsetCommandCtx;
+use crate::utils::{resp_err, resp_invalid_arguments};
+use crate::{Connection, Frame};
+
+use crate::config::LOGGER;
+use crate::tikv::backend::Transaction;
+use bytes::Bytes;
+use slog::debug;
+use tokio::sync::Mutex;
+
+/// `ZINTER`, `ZUNION` and `ZDIFF` and their STORE variants, writing the
+/// result to `dst`, see `crate::tikv::intersect`.
+#[derive(Debug, Clone)]
+pub struct Zinter {
+    operation: SetOperation,
+    dst: Option<String>,
+    keys: Vec<String>,
+    weights: Vec<f64>,
+    aggregate: Aggregate,
+    withscores: bool,
+    valid: bool,
+}
+
+impl Zinter {
+    pub fn new(
+        operation: SetOperation,
+        dst: Option<String>,
+        keys: Vec<String>,
+        weights: Vec<f64>,
+        aggregate: Aggregate,
+        withscores: bool,
+    ) -> Zinter {
+        Zinter {
+            operation,
+            dst,
+            keys,
+            weights,
+            aggregate,
+            withscores,
+            valid: true,
+        }
+    }
+
+    /// Get the keys
+    pub fn keys(&self) -> &Vec<String> {
+        &self.keys
+    }
+
+    /// Get the destination of a STORE variant
+    pub fn dst(&self) -> Option<&str> {
+        self.dst.as_deref()
+    }
+
+    pub(crate) fn parse_frames(
+        parse: &mut Parse,
+        operation: SetOperation,
+        store: bool,
+    ) -> crate::Result<Zinter> {
+        let mut args = vec![];
+        while let Ok(arg) = parse.next_bytes() {
+            args.push(arg);
+        }
+        Ok(Zinter::from_args(&args, operation, store))
+    }
+
+    pub(crate) fn parse_argv(
+        argv: &Vec<Bytes>,
+        operation: SetOperation,
+        store: bool,
+    ) -> crate::Result<Zinter> {
+        Ok(Zinter::from_args(argv, operation, store))
+    }
+
+    /// Parse `[dst] numkeys key [key ...] [WEIGHTS weight [weight ...]]
+    /// [AGGREGATE SUM|MIN|MAX] [WITHSCORES]`, the destination of a STORE
+    /// variant first, which has no WITHSCORES. ZDIFF has neither WEIGHTS nor
+    /// AGGREGATE.
+    fn from_args(args: &[Bytes], operation: SetOperation, store: bool) -> Zinter {
+        let (dst, args) = match args.split_first() {
+            Some((dst, args)) if store => (Some(String::from_utf8_lossy(dst).to_string()), args),
+            _ => (None, args),
+        };
+        let (keys, options) = match split_numkeys(args) {
+            Some(split) => split,
+            None => return Zinter::new_invalid(),
+        };
+        let mut weights = vec![];
+        let mut aggregate = Aggregate::Sum;
+        let mut withscores = false;
+
+        let mut idx = 0;
+        while idx < options.len() {
+            match String::from_utf8_lossy(&options[idx])
+                .to_uppercase()
+                .as_str()
+            {
+                "WEIGHTS"
+                    if operation != SetOperation::Diff && options.len() > idx + keys.len() =>
+                {
+                    for weight in &options[idx + 1..=idx + keys.len()] {
+                        match String::from_utf8_lossy(weight).parse::<f64>() {
+                            Ok(weight) => weights.push(weight),
+                            Err(_) => return Zinter::new_invalid(),
+                        }
+                    }
+                    idx += keys.len();
+                }
+                "AGGREGATE" if operation != SetOperation::Diff && options.len() > idx + 1 => {
+                    match Aggregate::from_name(&String::from_utf8_lossy(&options[idx + 1])) {
+                        Some(agg) => aggregate = agg,
+                        None => return Zinter::new_invalid(),
+                    }
+                    idx += 1;
+                }
+                "WITHSCORES" if !store => {
+                    withscores = true;
+                }
+                _ => return Zinter::new_invalid(),
+            }
+            idx += 1;
+        }
+
+        Zinter::new(operation, dst, keys, weights, aggregate, withscores)
+    }
+
+    pub(crate) async fn apply(self, dst: &mut Connection) -> crate::Result<()> {
+        let response = self.zcombine(None).await.unwrap_or_else(Into::into);
+        debug!(
+            LOGGER,
+            "res, {} -> {}, {:?}",
+            dst.local_addr(),
+            dst.peer_addr(),
+            response
+        );
+        dst.write_frame(&response).await?;
+
+        Ok(())
+    }
+
+    pub async fn zcombine(&self, txn: Option<Arc<Mutex<Transaction>>>) -> AsyncResult<Frame> {
+        if !self.valid {
+            return Ok(resp_invalid_arguments());
+        }
+        if !is_use_txn_api() {
+            return Ok(resp_err(REDIS_NOT_SUPPORTED_ERR));
+        }
+        let ctx = ZsetCommandCtx::new(txn);
+        match &self.dst {
+            Some(dst) => {
+                let response = ctx
+                    .do_async_txnkv_zcombinestore(
+                        dst,
+                        self.operation,
+                        &self.keys,
+                        &self.weights,
+                        self.aggregate,
+                    )
+                    .await?;
+                if matches!(response, Frame::Integer(stored) if stored > 0) {
+                    signal_key_ready(dst);
+                }
+                Ok(response)
+            }
+            None => {
+                ctx.do_async_txnkv_zcombine(
+                    self.operation,
+                    &self.keys,
+                    &self.weights,
+                    self.aggregate,
+                    self.withscores,
+                )
+                .await
+            }
+        }
+    }
+}
+
+impl Invalid for Zinter {
+    fn new_invalid() -> Zinter {
+        Zinter {
+            operation: SetOperation::Inter,
+            dst: None,
+            keys: vec![],
+            weights: vec![],
+            aggregate: Aggregate::Sum,
+            withscores: false,
+            valid: false,
+        }
+    }
+}
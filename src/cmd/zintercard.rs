@@ -0,0 +1,98 @@
+use std::sync::Arc;
+
+use crate::cmd::sintercard::{parse_limit, split_numkeys};
+use crate::cmd::{Invalid, Parse};
+use crate::config::is_use_txn_api;
+use crate::tikv::errors::{AsyncResult, REDIS_NOT_SUPPORTED_ERR};
+use crate::tikv::zset::ZsetCommandCtx;
+use crate::utils::{resp_err, resp_invalid_arguments};
+use crate::{Connection, Frame};
+
+use crate::config::LOGGER;
+use crate::tikv::backend::Transaction;
+use bytes::Bytes;
+use slog::debug;
+use tokio::sync::Mutex;
+
+#[derive(Debug, Clone)]
+pub struct Zintercard {
+    keys: Vec<String>,
+    limit: usize,
+    valid: bool,
+}
+
+impl Zintercard {
+    pub fn new(keys: Vec<String>, limit: usize) -> Zintercard {
+        Zintercard {
+            keys,
+            limit,
+            valid: true,
+        }
+    }
+
+    /// Get the keys
+    pub fn keys(&self) -> &Vec<String> {
+        &self.keys
+    }
+
+    pub(crate) fn parse_frames(parse: &mut Parse) -> crate::Result<Zintercard> {
+        let mut args = vec![];
+        while let Ok(arg) = parse.next_bytes() {
+            args.push(arg);
+        }
+        Ok(Zintercard::from_args(&args))
+    }
+
+    pub(crate) fn parse_argv(argv: &Vec<Bytes>) -> crate::Result<Zintercard> {
+        Ok(Zintercard::from_args(argv))
+    }
+
+    /// Parse `numkeys key [key ...] [LIMIT limit]`
+    fn from_args(args: &[Bytes]) -> Zintercard {
+        let (keys, options) = match split_numkeys(args) {
+            Some(split) => split,
+            None => return Zintercard::new_invalid(),
+        };
+        match parse_limit(options) {
+            Some(limit) => Zintercard::new(keys, limit),
+            None => Zintercard::new_invalid(),
+        }
+    }
+
+    pub(crate) async fn apply(self, dst: &mut Connection) -> crate::Result<()> {
+        let response = self.zintercard(None).await.unwrap_or_else(Into::into);
+        debug!(
+            LOGGER,
+            "res, {} -> {}, {:?}",
+            dst.local_addr(),
+            dst.peer_addr(),
+            response
+        );
+        dst.write_frame(&response).await?;
+
+        Ok(())
+    }
+
+    pub async fn zintercard(&self, txn: Option<Arc<Mutex<Transaction>>>) -> AsyncResult<Frame> {
+        if !self.valid {
+            return Ok(resp_invalid_arguments());
+        }
+        if is_use_txn_api() {
+            ZsetCommandCtx::new(txn)
+                .do_async_txnkv_zintercard(&self.keys, self.limit)
+                .await
+        } else {
+            Ok(resp_err(REDIS_NOT_SUPPORTED_ERR))
+        }
+    }
+}
+
+impl Invalid for Zintercard {
+    fn new_invalid() -> Zintercard {
+        Zintercard {
+            keys: vec![],
+            limit: 0,
+            valid: false,
+        }
+    }
+}
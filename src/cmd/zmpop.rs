@@ -0,0 +1,186 @@
+use std::sync::Arc;
+
+use crate::blocking::{block_on_keys, MAX_BLOCK_TIMEOUT_SECS};
+use crate::cmd::{Invalid, Parse};
+use crate::config::is_use_txn_api;
+use crate::tikv::errors::{
+    AsyncResult, REDIS_COUNT_NOT_POSITIVE_ERR, REDIS_NOT_SUPPORTED_ERR, REDIS_NUMKEYS_ERR,
+    REDIS_TIMEOUT_NEGATIVE_ERR, REDIS_TIMEOUT_OUT_OF_RANGE_ERR,
+};
+use crate::tikv::zset::ZsetCommandCtx;
+use crate::utils::{resp_err, resp_invalid_arguments};
+use crate::{Connection, Frame, Shutdown};
+
+use crate::config::LOGGER;
+use crate::tikv::backend::Transaction;
+use bytes::Bytes;
+use slog::debug;
+use tokio::sync::Mutex;
+
+/// `ZMPOP numkeys key [key ...] MIN|MAX [COUNT count]` and
+/// `BZMPOP timeout numkeys key [key ...] MIN|MAX [COUNT count]`, pop the
+/// members of the lowest or highest scores from the first non empty sorted
+/// set of the keys. BZMPOP waits up to `timeout` seconds, 0 for ever, for a
+/// sorted set to be added to, see `crate::blocking`.
+#[derive(Debug, Clone)]
+pub struct Zmpop {
+    keys: Vec<String>,
+    from_min: bool,
+    count: i64,
+    numkeys: i64,
+    /// The timeout of BZMPOP, in seconds
+    timeout: Option<f64>,
+    /// The timeout of BZMPOP is over `MAX_BLOCK_TIMEOUT_SECS`
+    timeout_out_of_range: bool,
+    valid: bool,
+}
+
+impl Zmpop {
+    pub fn keys(&self) -> &Vec<String> {
+        &self.keys
+    }
+
+    pub(crate) fn parse_frames(parse: &mut Parse, blocking: bool) -> crate::Result<Zmpop> {
+        let mut args = vec![];
+        while let Ok(arg) = parse.next_bytes() {
+            args.push(arg);
+        }
+        Ok(Zmpop::from_args(&args, blocking))
+    }
+
+    pub(crate) fn parse_argv(argv: &Vec<Bytes>, blocking: bool) -> crate::Result<Zmpop> {
+        Ok(Zmpop::from_args(argv, blocking))
+    }
+
+    fn from_args(args: &[Bytes], blocking: bool) -> Zmpop {
+        let mut args = args
+            .iter()
+            .map(|arg| String::from_utf8_lossy(arg).to_string());
+        let mut timeout_out_of_range = false;
+        let timeout = if blocking {
+            match args.next().map(|arg| arg.parse::<f64>()) {
+                Some(Ok(timeout)) if timeout.is_finite() && timeout <= MAX_BLOCK_TIMEOUT_SECS => {
+                    Some(timeout)
+                }
+                Some(Ok(timeout)) if timeout.is_finite() => {
+                    // replied as such by zmpop
+                    timeout_out_of_range = true;
+                    None
+                }
+                _ => return Zmpop::new_invalid(),
+            }
+        } else {
+            None
+        };
+        let numkeys = match args.next().map(|arg| arg.parse::<i64>()) {
+            Some(Ok(numkeys)) => numkeys,
+            _ => return Zmpop::new_invalid(),
+        };
+        let mut zmpop = Zmpop {
+            keys: vec![],
+            from_min: true,
+            count: 1,
+            numkeys,
+            timeout,
+            timeout_out_of_range,
+            valid: true,
+        };
+        if numkeys <= 0 {
+            // replied as such by zmpop
+            return zmpop;
+        }
+        for _ in 0..numkeys {
+            match args.next() {
+                Some(key) => zmpop.keys.push(key),
+                None => return Zmpop::new_invalid(),
+            }
+        }
+        zmpop.from_min = match args.next().map(|arg| arg.to_uppercase()).as_deref() {
+            Some("MIN") => true,
+            Some("MAX") => false,
+            _ => return Zmpop::new_invalid(),
+        };
+        match args.next() {
+            Some(arg) if arg.eq_ignore_ascii_case("COUNT") => {
+                match args.next().map(|arg| arg.parse::<i64>()) {
+                    Some(Ok(count)) => zmpop.count = count,
+                    _ => return Zmpop::new_invalid(),
+                }
+            }
+            Some(_) => return Zmpop::new_invalid(),
+            None => {}
+        }
+        if args.next().is_some() {
+            return Zmpop::new_invalid();
+        }
+        zmpop
+    }
+
+    pub(crate) async fn apply(
+        self,
+        dst: &mut Connection,
+        shutdown: &mut Shutdown,
+    ) -> crate::Result<()> {
+        let response = match self.timeout {
+            Some(timeout) if self.valid && self.numkeys > 0 && self.count > 0 && timeout >= 0.0 => {
+                let pop = || async { self.zmpop(None).await.unwrap_or_else(Into::into) };
+                match block_on_keys(&self.keys, timeout, shutdown, pop).await {
+                    Some(response) => response,
+                    // the server is shutting down
+                    None => return Ok(()),
+                }
+            }
+            _ => self.zmpop(None).await.unwrap_or_else(Into::into),
+        };
+        debug!(
+            LOGGER,
+            "res, {} -> {}, {:?}",
+            dst.local_addr(),
+            dst.peer_addr(),
+            response
+        );
+        dst.write_frame(&response).await?;
+
+        Ok(())
+    }
+
+    /// Pop once without blocking, BZMPOP in a transaction or a script does
+    /// not wait like Redis
+    pub async fn zmpop(&self, txn: Option<Arc<Mutex<Transaction>>>) -> AsyncResult<Frame> {
+        if !self.valid {
+            return Ok(resp_invalid_arguments());
+        }
+        if self.timeout_out_of_range {
+            return Ok(resp_err(REDIS_TIMEOUT_OUT_OF_RANGE_ERR));
+        }
+        if self.numkeys <= 0 {
+            return Ok(resp_err(REDIS_NUMKEYS_ERR));
+        }
+        if self.count <= 0 {
+            return Ok(resp_err(REDIS_COUNT_NOT_POSITIVE_ERR));
+        }
+        if matches!(self.timeout, Some(timeout) if timeout < 0.0) {
+            return Ok(resp_err(REDIS_TIMEOUT_NEGATIVE_ERR));
+        }
+        if !is_use_txn_api() {
+            return Ok(resp_err(REDIS_NOT_SUPPORTED_ERR));
+        }
+        ZsetCommandCtx::new(txn)
+            .do_async_txnkv_zmpop(&self.keys, self.from_min, self.count as u64)
+            .await
+    }
+}
+
+impl Invalid for Zmpop {
+    fn new_invalid() -> Zmpop {
+        Zmpop {
+            keys: vec![],
+            from_min: true,
+            count: 0,
+            numkeys: 0,
+            timeout: None,
+            timeout_out_of_range: false,
+            valid: false,
+        }
+    }
+}
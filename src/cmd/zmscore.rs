@@ -0,0 +1,101 @@
+use std::sync::Arc;
+
+use crate::cmd::{Invalid, Parse};
+use crate::config::is_use_txn_api;
+use crate::tikv::errors::{AsyncResult, REDIS_NOT_SUPPORTED_ERR};
+use crate::tikv::zset::ZsetCommandCtx;
+use crate::utils::{resp_err, resp_invalid_arguments};
+use crate::{Connection, Frame};
+
+use crate::config::LOGGER;
+use crate::tikv::backend::Transaction;
+use bytes::Bytes;
+use slog::debug;
+use tokio::sync::Mutex;
+
+#[derive(Debug, Clone)]
+pub struct Zmscore {
+    key: String,
+    members: Vec<String>,
+    valid: bool,
+}
+
+impl Zmscore {
+    pub fn new(key: &str) -> Zmscore {
+        Zmscore {
+            key: key.to_string(),
+            members: vec![],
+            valid: true,
+        }
+    }
+
+    /// Get the key
+    pub fn key(&self) -> &str {
+        &self.key
+    }
+
+    pub fn set_key(&mut self, key: &str) {
+        self.key = key.to_owned();
+    }
+
+    pub fn add_member(&mut self, member: &str) {
+        self.members.push(member.to_string());
+    }
+
+    pub(crate) fn parse_frames(parse: &mut Parse) -> crate::Result<Zmscore> {
+        let key = parse.next_string()?;
+        let mut zmscore = Zmscore::new(&key);
+        while let Ok(member) = parse.next_string() {
+            zmscore.add_member(&member);
+        }
+        Ok(zmscore)
+    }
+
+    pub(crate) fn parse_argv(argv: &Vec<Bytes>) -> crate::Result<Zmscore> {
+        if argv.len() < 2 {
+            return Ok(Zmscore::new_invalid());
+        }
+        let mut s = Zmscore::new(&String::from_utf8_lossy(&argv[0]));
+        for arg in &argv[1..] {
+            s.add_member(&String::from_utf8_lossy(arg));
+        }
+        Ok(s)
+    }
+
+    pub(crate) async fn apply(self, dst: &mut Connection) -> crate::Result<()> {
+        let response = self.zmscore(None).await?;
+        debug!(
+            LOGGER,
+            "res, {} -> {}, {:?}",
+            dst.local_addr(),
+            dst.peer_addr(),
+            response
+        );
+        dst.write_frame(&response).await?;
+
+        Ok(())
+    }
+
+    pub async fn zmscore(&self, txn: Option<Arc<Mutex<Transaction>>>) -> AsyncResult<Frame> {
+        if !self.valid {
+            return Ok(resp_invalid_arguments());
+        }
+        if is_use_txn_api() {
+            ZsetCommandCtx::new(txn)
+                .do_async_txnkv_zmscore(&self.key, &self.members)
+                .await
+        } else {
+            Ok(resp_err(REDIS_NOT_SUPPORTED_ERR))
+        }
+    }
+}
+
+impl Invalid for Zmscore {
+    fn new_invalid() -> Zmscore {
+        Zmscore {
+            key: "".to_string(),
+            members: vec![],
+            valid: false,
+        }
+    }
+}
@@ -0,0 +1,199 @@
+use std::sync::Arc;
+
+use crate::blocking::signal_key_ready;
+use crate::cmd::{Invalid, Parse};
+use crate::config::is_use_txn_api;
+use crate::tikv::errors::{
+    AsyncResult, REDIS_MIN_MAX_NOT_FLOAT_ERR, REDIS_MIN_MAX_NOT_STRING_RANGE_ERR,
+    REDIS_NOT_SUPPORTED_ERR,
+};
+use crate::tikv::zset::{LexBound, ZrangeBy, ZsetCommandCtx};
+use crate::utils::{resp_err, resp_invalid_arguments};
+use crate::{Connection, Frame};
+
+use crate::config::LOGGER;
+use crate::tikv::backend::Transaction;
+use bytes::Bytes;
+use slog::debug;
+use tokio::sync::Mutex;
+
+/// `ZRANGESTORE dst src min max [BYSCORE|BYLEX] [REV] [LIMIT offset count]`,
+/// store the range of `src` in `dst`, see `ZsetCommandCtx::do_async_txnkv_zrangestore`.
+#[derive(Debug, Clone)]
+pub struct Zrangestore {
+    dst: String,
+    src: String,
+    /// None if a bound is not valid for the kind of range
+    by: Option<ZrangeBy>,
+    bylex: bool,
+    rev: bool,
+    limit: Option<(i64, i64)>,
+    valid: bool,
+}
+
+/// `(score` is exclusive, `-inf` and `+inf` are the infinities
+fn parse_score_bound(arg: &str) -> Option<(f64, bool)> {
+    let (arg, inclusive) = match arg.strip_prefix('(') {
+        Some(arg) => (arg, false),
+        None => (arg, true),
+    };
+    match arg.parse::<f64>() {
+        Ok(score) if !score.is_nan() => Some((score, inclusive)),
+        _ => None,
+    }
+}
+
+/// `-` and `+` are the lowest and highest members, `[member` is inclusive
+/// and `(member` exclusive
+fn parse_lex_bound(arg: &str) -> Option<LexBound> {
+    match arg {
+        "-" => Some(LexBound::Min),
+        "+" => Some(LexBound::Max),
+        _ => match arg.strip_prefix('[') {
+            Some(member) => Some(LexBound::Inclusive(member.to_owned())),
+            None => arg
+                .strip_prefix('(')
+                .map(|member| LexBound::Exclusive(member.to_owned())),
+        },
+    }
+}
+
+impl Zrangestore {
+    pub fn dst(&self) -> &str {
+        &self.dst
+    }
+
+    pub(crate) fn parse_frames(parse: &mut Parse) -> crate::Result<Zrangestore> {
+        let mut args = vec![];
+        while let Ok(arg) = parse.next_bytes() {
+            args.push(arg);
+        }
+        Ok(Zrangestore::from_args(&args))
+    }
+
+    pub(crate) fn parse_argv(argv: &Vec<Bytes>) -> crate::Result<Zrangestore> {
+        Ok(Zrangestore::from_args(argv))
+    }
+
+    fn from_args(args: &[Bytes]) -> Zrangestore {
+        if args.len() < 4 {
+            return Zrangestore::new_invalid();
+        }
+        let args: Vec<String> = args
+            .iter()
+            .map(|arg| String::from_utf8_lossy(arg).to_string())
+            .collect();
+        let mut byscore = false;
+        let mut bylex = false;
+        let mut rev = false;
+        let mut limit = None;
+        let mut idx = 4;
+        while idx < args.len() {
+            match args[idx].to_uppercase().as_str() {
+                "BYSCORE" => byscore = true,
+                "BYLEX" => bylex = true,
+                "REV" => rev = true,
+                "LIMIT" if idx + 2 < args.len() => {
+                    match (args[idx + 1].parse::<i64>(), args[idx + 2].parse::<i64>()) {
+                        (Ok(offset), Ok(count)) => limit = Some((offset, count)),
+                        _ => return Zrangestore::new_invalid(),
+                    }
+                    idx += 2;
+                }
+                _ => return Zrangestore::new_invalid(),
+            }
+            idx += 1;
+        }
+        // LIMIT is only for a score or lex range
+        if (byscore && bylex) || (limit.is_some() && !byscore && !bylex) {
+            return Zrangestore::new_invalid();
+        }
+
+        // the bounds of a reversed score or lex range are given highest first
+        let (min, max) = if rev && (byscore || bylex) {
+            (&args[3], &args[2])
+        } else {
+            (&args[2], &args[3])
+        };
+        let by = if byscore {
+            match (parse_score_bound(min), parse_score_bound(max)) {
+                (Some((min, min_inclusive)), Some((max, max_inclusive))) => Some(ZrangeBy::Score {
+                    min,
+                    min_inclusive,
+                    max,
+                    max_inclusive,
+                }),
+                _ => None,
+            }
+        } else if bylex {
+            match (parse_lex_bound(min), parse_lex_bound(max)) {
+                (Some(min), Some(max)) => Some(ZrangeBy::Lex(min, max)),
+                _ => None,
+            }
+        } else {
+            match (min.parse::<i64>(), max.parse::<i64>()) {
+                (Ok(start), Ok(stop)) => Some(ZrangeBy::Rank(start, stop)),
+                _ => return Zrangestore::new_invalid(),
+            }
+        };
+        Zrangestore {
+            dst: args[0].clone(),
+            src: args[1].clone(),
+            by,
+            bylex,
+            rev,
+            limit,
+            valid: true,
+        }
+    }
+
+    pub(crate) async fn apply(self, dst: &mut Connection) -> crate::Result<()> {
+        let response = self.zrangestore(None).await.unwrap_or_else(Into::into);
+        debug!(
+            LOGGER,
+            "res, {} -> {}, {:?}",
+            dst.local_addr(),
+            dst.peer_addr(),
+            response
+        );
+        dst.write_frame(&response).await?;
+
+        Ok(())
+    }
+
+    pub async fn zrangestore(&self, txn: Option<Arc<Mutex<Transaction>>>) -> AsyncResult<Frame> {
+        if !self.valid {
+            return Ok(resp_invalid_arguments());
+        }
+        let by = match &self.by {
+            Some(by) => by.clone(),
+            None if self.bylex => return Ok(resp_err(REDIS_MIN_MAX_NOT_STRING_RANGE_ERR)),
+            None => return Ok(resp_err(REDIS_MIN_MAX_NOT_FLOAT_ERR)),
+        };
+        if is_use_txn_api() {
+            let response = ZsetCommandCtx::new(txn)
+                .do_async_txnkv_zrangestore(&self.dst, &self.src, by, self.rev, self.limit)
+                .await?;
+            if matches!(response, Frame::Integer(stored) if stored > 0) {
+                signal_key_ready(&self.dst);
+            }
+            Ok(response)
+        } else {
+            Ok(resp_err(REDIS_NOT_SUPPORTED_ERR))
+        }
+    }
+}
+
+impl Invalid for Zrangestore {
+    fn new_invalid() -> Zrangestore {
+        Zrangestore {
+            dst: "".to_string(),
+            src: "".to_string(),
+            by: None,
+            bylex: false,
+            rev: false,
+            limit: None,
+            valid: false,
+        }
+    }
+}
@@ -0,0 +1,487 @@
+//! Command log shipping.
+//!
+//! Write commands which changed the keyspace are queued as events with the
+//! command, keys, data type and namespace, and shipped in batches by a
+//! background task to NATS (`nats://host:port/subject`) or over HTTP to a
+//! Kafka REST proxy (`http://host:port/topics/<topic>`), so downstream search
+//! indexes and caches can follow the changes without TiCDC. A lua script is logged as
+//! the write commands it called, once its transaction is committed, not as
+//! the script itself, so the consumers follow the same writes even if the
+//! script is not deterministic. The writes queued by MULTI are logged once
+//! EXEC committed them. A write replying an error, or which changed nothing
+//! like a DEL of missing keys, is not logged.
+//!
+//! The queue is bounded, writers wait for it when it is full unless
+//! `cmdlog_drop_on_full` is set.
+
+use std::sync::RwLock;
+use std::time::Duration;
+
+use bytes::Bytes;
+use hyper::{Body, Client, Method, Request};
+use slog::{error, info, warn};
+use tokio::io::AsyncWriteExt;
+use tokio::net::TcpStream;
+use tokio::sync::mpsc::{self, Receiver, Sender};
+use tokio::time::{self, Instant, MissedTickBehavior};
+
+use crate::config::LOGGER;
+use crate::metrics::CMDLOG_EVENT_COUNTER;
+use crate::tikv::encoding::DataType;
+use crate::triggers::EVENT_EXPIRED;
+use crate::utils::{json_quote, now_timestamp_in_millis};
+use crate::{
+    config_cmdlog_batch_size_or_default, config_cmdlog_batch_wait_ms_or_default,
+    config_cmdlog_drop_on_full_or_default, config_cmdlog_queue_size_or_default,
+    config_cmdlog_sink_or_default, config_instance_id_or_default, Frame,
+};
+
+const PUBLISH_RETRIES: u64 = 3;
+// answer PINGs of the NATS server while idle
+const KEEPALIVE_INTERVAL: u64 = 30000;
+
+lazy_static! {
+    static ref CMDLOG_TX: RwLock<Option<Sender<WriteEvent>>> = RwLock::new(None);
+}
+
+/// A write command applied to the keyspace.
+#[derive(Debug, Clone)]
+pub struct WriteEvent {
+    pub ts: u64,
+    pub cmd: String,
+    pub data_type: Option<DataType>,
+    pub keys: Vec<Bytes>,
+}
+
+impl WriteEvent {
+    /// Event of command `cmd` with the arguments of its request frame
+    pub fn new(cmd: &str, data_type: Option<DataType>, args: &[Bytes]) -> WriteEvent {
+        WriteEvent {
+            ts: now_timestamp_in_millis(),
+            cmd: cmd.to_owned(),
+            data_type,
+            keys: write_keys(cmd, args),
+        }
+    }
+
+    /// Event of the removal of `key` expired at `expire_at`
+    pub fn expired(data_type: DataType, key: &str, expire_at: u64) -> WriteEvent {
+        WriteEvent {
+            ts: expire_at,
+            cmd: EVENT_EXPIRED.to_owned(),
+            data_type: Some(data_type),
+            keys: vec![Bytes::from(key.to_owned())],
+        }
+    }
+
+    fn to_json(&self) -> String {
+        let keys: Vec<String> = self
+            .keys
+            .iter()
+            .map(|k| json_quote(&String::from_utf8_lossy(k)))
+            .collect();
+        let data_type = match &self.data_type {
+            Some(dt) => json_quote(&dt.to_string()),
+            None => "null".to_owned(),
+        };
+        format!(
+            "{{\"ts\":{},\"namespace\":{},\"cmd\":{},\"type\":{},\"keys\":[{}]}}",
+            self.ts,
+            json_quote(&config_instance_id_or_default()),
+            json_quote(&self.cmd),
+            data_type,
+            keys.join(",")
+        )
+    }
+}
+
+/// The arguments of a request frame, without the command name
+pub fn frame_args(frame: &Frame) -> Vec<Bytes> {
+    match frame {
+        Frame::Array(parts) => parts
+            .iter()
+            .skip(1)
+            .filter_map(|part| match part {
+                Frame::Bulk(b) => Some(b.clone()),
+                Frame::Simple(s) => Some(Bytes::from(s.clone())),
+                _ => None,
+            })
+            .collect(),
+        _ => vec![],
+    }
+}
+
+/// Keys written by the command, taken from its arguments
+fn write_keys(cmd: &str, args: &[Bytes]) -> Vec<Bytes> {
+    match cmd {
+        "del" => args.to_vec(),
+        "mset" => args.iter().step_by(2).cloned().collect(),
+        "xgroup" | "bitop" | "copy" => args.get(1).cloned().into_iter().collect(),
+        "sort" => match args.iter().position(|a| a.eq_ignore_ascii_case(b"STORE")) {
+            Some(idx) => args.get(idx + 1).cloned().into_iter().collect(),
+            None => vec![],
+        },
+        // the keys removed by a migration without COPY
+        "migrate" => match args.iter().position(|a| a.eq_ignore_ascii_case(b"KEYS")) {
+            Some(idx) => args[idx + 1..].to_vec(),
+            None => args.get(2).cloned().into_iter().collect(),
+        },
+        // the keys probed, the one popped from is not known here
+        "lmpop" | "blmpop" | "zmpop" | "bzmpop" => {
+            let at = if cmd.starts_with('b') { 1 } else { 0 };
+            let numkeys = args
+                .get(at)
+                .and_then(|n| String::from_utf8_lossy(n).parse::<usize>().ok())
+                .unwrap_or(0);
+            args.iter().skip(at + 1).take(numkeys).cloned().collect()
+        }
+        "xreadgroup" => match args.iter().position(|a| a.eq_ignore_ascii_case(b"STREAMS")) {
+            Some(idx) => {
+                let streams = &args[idx + 1..];
+                streams[..streams.len() / 2].to_vec()
+            }
+            None => vec![],
+        },
+        _ => args.first().cloned().into_iter().collect(),
+    }
+}
+
+/// The writes replying the number of changes they made, or 0 and 1, so a
+/// zero reply is a write which changed nothing.
+const COUNTING_WRITES: &[&str] = &[
+    "del",
+    "setnx",
+    "expire",
+    "expireat",
+    "pexpire",
+    "pexpireat",
+    "persist",
+    "copy",
+    "tidis.move",
+    "extend",
+    "unlock",
+    "hsetnx",
+    "hdel",
+    "lrem",
+    "linsert",
+    "sadd",
+    "srem",
+    "zrem",
+    "zremrangebyscore",
+    "zremrangebyrank",
+    "xack",
+    "json.del",
+];
+
+/// What a reply tells of the command which replied it
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Reply {
+    Error,
+    /// A nil or an empty array
+    Nil,
+    Integer(i64),
+    Value,
+}
+
+impl Reply {
+    pub fn of(frame: &Frame) -> Reply {
+        match frame {
+            Frame::ErrorString(_) | Frame::ErrorOwned(_) => Reply::Error,
+            Frame::Null => Reply::Nil,
+            Frame::Array(val) if val.is_empty() => Reply::Nil,
+            Frame::Integer(val) => Reply::Integer(*val),
+            _ => Reply::Value,
+        }
+    }
+}
+
+/// Whether the write command `cmd` changed the keyspace, told by its reply.
+/// The writes which failed or changed nothing, like a DEL of missing keys
+/// or a SET NX of an existing key, are not replicated, shipped or notified.
+pub fn write_applied(cmd: &str, reply: Reply) -> bool {
+    match reply {
+        Reply::Error => false,
+        // a script replies whatever it computed
+        _ if cmd == "eval" || cmd == "evalsha" => true,
+        Reply::Nil => false,
+        Reply::Integer(0) => !COUNTING_WRITES.contains(&cmd),
+        // the pivot was not found
+        Reply::Integer(-1) => cmd != "linsert",
+        _ => true,
+    }
+}
+
+pub fn cmdlog_enabled() -> bool {
+    !config_cmdlog_sink_or_default().is_empty()
+}
+
+/// Ship the removal of the expired key if the command log is enabled
+pub async fn ship_expired(data_type: DataType, key: &str, expire_at: u64) {
+    if cmdlog_enabled() {
+        ship(WriteEvent::expired(data_type, key, expire_at)).await;
+    }
+}
+
+/// Number of events queued to be shipped
+pub fn cmdlog_queue_depth() -> usize {
+    match CMDLOG_TX.read().unwrap().as_ref() {
+        Some(tx) => config_cmdlog_queue_size_or_default().saturating_sub(tx.capacity()),
+        None => 0,
+    }
+}
+
+/// Queue the event to be shipped, waits if the queue is full unless
+/// `cmdlog_drop_on_full` is set.
+pub async fn ship(event: WriteEvent) {
+    let tx = match CMDLOG_TX.read().unwrap().clone() {
+        Some(tx) => tx,
+        None => return,
+    };
+    let queued = if config_cmdlog_drop_on_full_or_default() {
+        tx.try_send(event).is_ok()
+    } else {
+        tx.send(event).await.is_ok()
+    };
+    if !queued {
+        CMDLOG_EVENT_COUNTER.with_label_values(&["dropped"]).inc();
+    }
+}
+
+enum Sink {
+    Nats(NatsSink),
+    Http(String),
+}
+
+impl Sink {
+    fn from_url(url: &str) -> Option<Sink> {
+        if url.starts_with("nats://") {
+            NatsSink::from_url(url).map(Sink::Nats)
+        } else if url.starts_with("http://") {
+            Some(Sink::Http(url.to_owned()))
+        } else {
+            None
+        }
+    }
+
+    async fn publish(&mut self, batch: &[WriteEvent]) -> crate::Result<()> {
+        match self {
+            Sink::Nats(sink) => {
+                let payloads: Vec<String> = batch.iter().map(|e| e.to_json()).collect();
+                sink.publish(&payloads).await
+            }
+            Sink::Http(url) => publish_http(url, batch).await,
+        }
+    }
+
+    async fn keepalive(&mut self) {
+        if let Sink::Nats(sink) = self {
+            sink.keepalive().await;
+        }
+    }
+}
+
+/// Publisher to a NATS subject, speaking the text protocol of NATS directly.
+pub(crate) struct NatsSink {
+    addr: String,
+    subject: String,
+    stream: Option<TcpStream>,
+}
+
+impl NatsSink {
+    /// Parse `nats://host:port/subject`
+    pub(crate) fn from_url(url: &str) -> Option<NatsSink> {
+        let (addr, subject) = url.strip_prefix("nats://")?.split_once('/')?;
+        if subject.is_empty() {
+            return None;
+        }
+        Some(NatsSink {
+            addr: addr.to_owned(),
+            subject: subject.to_owned(),
+            stream: None,
+        })
+    }
+
+    /// Answer the PINGs of the server while idle, so the connection is kept
+    pub(crate) async fn keepalive(&mut self) {
+        if let Err(e) = self.answer_pings().await {
+            warn!(LOGGER, "nats connection to {} lost: {}", self.addr, e);
+            self.stream = None;
+        }
+    }
+
+    async fn connect(&mut self) -> crate::Result<&mut TcpStream> {
+        if self.stream.is_none() {
+            let mut stream = TcpStream::connect(&self.addr).await?;
+            stream
+                .write_all(b"CONNECT {\"verbose\":false,\"pedantic\":false}\r\n")
+                .await?;
+            info!(LOGGER, "connected to nats {}", self.addr);
+            self.stream = Some(stream);
+        }
+        Ok(self.stream.as_mut().unwrap())
+    }
+
+    /// Reply PONG to the PINGs received, the server info and other
+    /// messages are ignored.
+    async fn answer_pings(&mut self) -> crate::Result<()> {
+        let stream = match self.stream.as_mut() {
+            Some(stream) => stream,
+            None => return Ok(()),
+        };
+        let mut buf = [0u8; 4096];
+        let mut pings = 0;
+        loop {
+            match stream.try_read(&mut buf) {
+                Ok(0) => return Err("closed by nats server".into()),
+                Ok(n) => {
+                    let received = &buf[..n];
+                    if received.windows(5).any(|w| w == b"-ERR ") {
+                        return Err(String::from_utf8_lossy(received).to_string().into());
+                    }
+                    pings += received.windows(4).filter(|w| *w == b"PING").count();
+                }
+                Err(e) if e.kind() == std::io::ErrorKind::WouldBlock => break,
+                Err(e) => return Err(e.into()),
+            }
+        }
+        for _ in 0..pings {
+            stream.write_all(b"PONG\r\n").await?;
+        }
+        Ok(())
+    }
+
+    pub(crate) async fn publish(&mut self, payloads: &[String]) -> crate::Result<()> {
+        let res = self.try_publish(payloads).await;
+        if res.is_err() {
+            // reconnect on the next publish
+            self.stream = None;
+        }
+        res
+    }
+
+    async fn try_publish(&mut self, payloads: &[String]) -> crate::Result<()> {
+        let subject = self.subject.clone();
+        self.connect().await?;
+        self.answer_pings().await?;
+        let mut buf = Vec::new();
+        for payload in payloads {
+            buf.extend_from_slice(format!("PUB {} {}\r\n", subject, payload.len()).as_bytes());
+            buf.extend_from_slice(payload.as_bytes());
+            buf.extend_from_slice(b"\r\n");
+        }
+        self.stream.as_mut().unwrap().write_all(&buf).await?;
+        Ok(())
+    }
+}
+
+/// POST the batch in the format of the Kafka REST proxy, keyed by the first key
+/// so events of a key stay in one partition.
+async fn publish_http(url: &str, batch: &[WriteEvent]) -> crate::Result<()> {
+    let records: Vec<String> = batch
+        .iter()
+        .map(|event| {
+            let key = match event.keys.first() {
+                Some(k) => json_quote(&String::from_utf8_lossy(k)),
+                None => "null".to_owned(),
+            };
+            format!("{{\"key\":{},\"value\":{}}}", key, event.to_json())
+        })
+        .collect();
+    let body = format!("{{\"records\":[{}]}}", records.join(","));
+    let req = Request::builder()
+        .method(Method::POST)
+        .uri(url)
+        .header("content-type", "application/vnd.kafka.json.v2+json")
+        .body(Body::from(body))?;
+    let resp = Client::new().request(req).await?;
+    if !resp.status().is_success() {
+        return Err(format!("cmdlog sink replied {}", resp.status()).into());
+    }
+    Ok(())
+}
+
+/// Collect up to `cmdlog_batch_size` events, waiting at most
+/// `cmdlog_batch_wait_ms` after the first one.
+async fn next_batch(rx: &mut Receiver<WriteEvent>, first: WriteEvent) -> Vec<WriteEvent> {
+    let batch_size = config_cmdlog_batch_size_or_default();
+    let deadline = Instant::now() + Duration::from_millis(config_cmdlog_batch_wait_ms_or_default());
+    let mut batch = vec![first];
+    while batch.len() < batch_size {
+        match time::timeout_at(deadline, rx.recv()).await {
+            Ok(Some(event)) => batch.push(event),
+            _ => break,
+        }
+    }
+    batch
+}
+
+/// Ship the queued events until the server exits, does nothing if no sink
+/// is configured.
+pub async fn run_cmdlog_shipper() {
+    let url = config_cmdlog_sink_or_default();
+    if url.is_empty() {
+        return;
+    }
+    let mut sink = match Sink::from_url(&url) {
+        Some(sink) => sink,
+        None => {
+            error!(
+                LOGGER,
+                "[CMDLOG] unsupported sink {}, shipping disabled", url
+            );
+            return;
+        }
+    };
+
+    let (tx, mut rx) = mpsc::channel(config_cmdlog_queue_size_or_default());
+    *CMDLOG_TX.write().unwrap() = Some(tx);
+    info!(LOGGER, "[CMDLOG] shipping write commands to {}", url);
+
+    let mut keepalive = time::interval(Duration::from_millis(KEEPALIVE_INTERVAL));
+    keepalive.set_missed_tick_behavior(MissedTickBehavior::Delay);
+    loop {
+        let first = tokio::select! {
+            event = rx.recv() => match event {
+                Some(event) => event,
+                None => return,
+            },
+            _ = keepalive.tick() => {
+                sink.keepalive().await;
+                continue;
+            }
+        };
+        let batch = next_batch(&mut rx, first).await;
+
+        let mut attempt = 0;
+        loop {
+            match sink.publish(&batch).await {
+                Ok(_) => {
+                    CMDLOG_EVENT_COUNTER
+                        .with_label_values(&["shipped"])
+                        .inc_by(batch.len() as u64);
+                    break;
+                }
+                Err(e) if attempt < PUBLISH_RETRIES => {
+                    attempt += 1;
+                    warn!(
+                        LOGGER,
+                        "[CMDLOG] publish failed: {}, retry {}/{}", e, attempt, PUBLISH_RETRIES
+                    );
+                    time::sleep(Duration::from_millis(100 * attempt)).await;
+                }
+                Err(e) => {
+                    error!(
+                        LOGGER,
+                        "[CMDLOG] publish failed: {}, drop {} events",
+                        e,
+                        batch.len()
+                    );
+                    CMDLOG_EVENT_COUNTER
+                        .with_label_values(&["failed"])
+                        .inc_by(batch.len() as u64);
+                    break;
+                }
+            }
+        }
+    }
+}
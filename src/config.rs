@@ -1,4 +1,5 @@
 use serde::Deserialize;
+use std::collections::HashMap;
 
 use crate::{DEFAULT_PORT, DEFAULT_TLS_PORT};
 
@@ -19,17 +20,21 @@ lazy_static! {
         .use_custom_timestamp(crate::utils::timestamp_local)
         .build()
         .filter_level(slog::Level::from_usize(log_level()).unwrap())
+        .filter(crate::logsample::keep_record)
         .fuse(),
         slog::o!()
     );
 }
 
 #[derive(Debug, Deserialize, Clone)]
+#[serde(deny_unknown_fields)]
 pub struct Config {
     server: Server,
     backend: Backend,
+    namespaces: Option<HashMap<String, Namespace>>,
 }
 #[derive(Debug, Deserialize, Clone)]
+#[serde(deny_unknown_fields)]
 struct Server {
     listen: Option<String>,
     port: Option<u16>,
@@ -43,18 +48,125 @@ struct Server {
     instance_id: Option<String>,
     prometheus_listen: Option<String>,
     prometheus_port: Option<u16>,
+    memcache_listen: Option<String>,
+    memcache_port: Option<u16>,
+    http_api_listen: Option<String>,
+    http_api_port: Option<u16>,
+    grpc_listen: Option<String>,
+    grpc_port: Option<u16>,
+    ws_listen: Option<String>,
+    ws_port: Option<u16>,
     // username: Option<String>,
     password: Option<String>,
+    auth_backend: Option<String>,
+    auth_webhook_url: Option<String>,
+    auth_webhook_timeout: Option<u64>,
+    auth_cache_ttl: Option<u64>,
+    auth_revoke_mode: Option<String>,
+    object_access_tracking: Option<bool>,
+    object_access_max_keys: Option<usize>,
+    ip_allow_list: Option<Vec<String>>,
+    ip_deny_list: Option<Vec<String>>,
+    ip_filter_file: Option<String>,
+    proxy_protocol: Option<bool>,
+    tls_proxy_protocol: Option<bool>,
+    latency_slo: Option<HashMap<String, u64>>,
+    latency_slo_alert_log: Option<bool>,
+    enable_debug_command: Option<String>,
+    restore_skip_zero_checksum: Option<bool>,
+    cmdlog_sink: Option<String>,
+    cmdlog_batch_size: Option<usize>,
+    cmdlog_batch_wait_ms: Option<u64>,
+    cmdlog_queue_size: Option<usize>,
+    cmdlog_drop_on_full: Option<bool>,
+    triggers: Option<Vec<TriggerRule>>,
+    jobs: Option<Vec<JobRule>>,
+    hash_indexes: Option<Vec<HashIndexRule>>,
+    low_priority_concurrency: Option<usize>,
+    pipeline_concurrency: Option<usize>,
+    memory_soft_limit: Option<u64>,
+    memory_hard_limit: Option<u64>,
+    lua_time_limit: Option<u64>,
+    notify_keyspace_events: Option<String>,
+    resource_group: Option<String>,
+    user_resource_groups: Option<HashMap<String, String>>,
     log_level: Option<String>,
     log_file: Option<String>,
+    log_redact: Option<String>,
+    log_redact_namespaces: Option<HashMap<String, String>>,
+    log_max_args: Option<usize>,
+    log_max_arg_len: Option<usize>,
+    log_sample_rate: Option<u64>,
+    log_sample_slower_than_us: Option<u64>,
     cluster_broadcast_addr: Option<String>,
     cluster_topology_interval: Option<u64>,
     cluster_topology_expire: Option<u64>,
+    cluster_slot_scan_max_keys: Option<u64>,
     meta_key_number: Option<u16>,
 }
 
+/// A rule notifying `target` of the `events` on keys matching `pattern`.
 #[derive(Debug, Deserialize, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct TriggerRule {
+    pub pattern: String,
+    pub events: Vec<String>,
+    pub target: String,
+}
+
+/// Snapshot of the reads of the commands that write nothing, see
+/// `TxnClientWrapper::begin_consistent_read`. Other values are rejected when
+/// the config is loaded.
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum ReadConsistency {
+    /// the latest committed data
+    Latest,
+    /// at a timestamp from PD
+    Linearizable,
+    /// at `stale_read_ms` in the past
+    Stale,
+}
+
+/// Schedule of the maintenance job `name`, unset fields keep the defaults.
+#[derive(Debug, Deserialize, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct JobRule {
+    pub name: String,
+    pub interval_ms: Option<u64>,
+    pub max_deletes: Option<usize>,
+    pub enabled: Option<bool>,
+}
+
+/// A secondary index of `field` for the hashes with keys starting with
+/// `prefix`.
+#[derive(Debug, Deserialize, Clone, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct HashIndexRule {
+    pub prefix: String,
+    pub field: String,
+}
+
+/// Backend settings overridden for a namespace, keyed by instance_id in
+/// `[namespaces.<instance_id>]`, so tenants can share a config file.
+#[derive(Debug, Deserialize, Clone)]
+#[serde(deny_unknown_fields)]
+struct Namespace {
+    default_ttl_ms: Option<u64>,
+    max_ttl_ms: Option<u64>,
+    eviction_max_keys: Option<u64>,
+    eviction_max_bytes: Option<u64>,
+    eviction_budget: Option<usize>,
+    stale_read_ms: Option<u64>,
+    read_consistency: Option<ReadConsistency>,
+    cmd_lrem_length_limit: Option<u32>,
+    cmd_linsert_length_limit: Option<u32>,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+#[serde(deny_unknown_fields)]
 struct Backend {
+    storage: Option<String>,
     timeout: Option<u64>,
     ca_file: Option<String>,
     cert_file: Option<String>,
@@ -84,27 +196,220 @@ struct Backend {
 
     cmd_lrem_length_limit: Option<u32>,
     cmd_linsert_length_limit: Option<u32>,
+    cmd_inter_length_limit: Option<u32>,
+    cmd_bitop_max_source_bytes: Option<u64>,
+    cmd_sort_length_limit: Option<u32>,
 
     async_deletion_enabled: Option<bool>,
 
     async_gc_worker_number: Option<usize>,
     async_gc_worker_queue_size: Option<usize>,
     async_gc_interval: Option<u64>,
+    async_gc_batch_size: Option<usize>,
+    async_gc_concurrency: Option<usize>,
+    async_gc_deletes_per_sec: Option<u64>,
+    async_gc_target_latency_ms: Option<u64>,
 
     async_del_list_threshold: Option<u32>,
     async_del_hash_threshold: Option<u32>,
     async_del_set_threshold: Option<u32>,
     async_del_zset_threshold: Option<u32>,
+    async_del_stream_threshold: Option<u32>,
 
     async_expire_list_threshold: Option<u32>,
     async_expire_hash_threshold: Option<u32>,
     async_expire_set_threshold: Option<u32>,
     async_expire_zset_threshold: Option<u32>,
+
+    health_check_interval: Option<u64>,
+    stale_read_ms: Option<u64>,
+    read_consistency: Option<ReadConsistency>,
+    rpc_log_slower_than_ms: Option<u64>,
+    rpc_log_max_len: Option<usize>,
+    leader_read_timeout_ms: Option<u64>,
+    pipeline_snapshot_max_cmds: Option<u32>,
+    pipeline_snapshot_max_age_ms: Option<u64>,
+    idempotency_token_ttl_ms: Option<u64>,
+    idempotency_derive_tokens: Option<bool>,
+    default_ttl_ms: Option<u64>,
+    max_ttl_ms: Option<u64>,
+    eviction_max_keys: Option<u64>,
+    eviction_max_bytes: Option<u64>,
+    eviction_budget: Option<usize>,
+
+    encryption_enabled: Option<bool>,
+    encryption_master_key: Option<String>,
+    encryption_kms_url: Option<String>,
+    encryption_kms_key_id: Option<String>,
+    encryption_kms_token: Option<String>,
+}
+
+/// Resolve a config value given as a secret reference.
+///
+/// `env:NAME` is replaced with the value of environment variable `NAME`,
+/// and if `read_file`, `file:/path` with the content of the file with
+/// trailing newlines trimmed, so secrets mounted by kubernetes can be used
+/// directly. Other values are returned as is.
+fn resolve_secret_ref(value: &str, read_file: bool) -> Result<String, String> {
+    if let Some(name) = value.strip_prefix("env:") {
+        std::env::var(name).map_err(|e| format!("failed to resolve {}: {}", value, e))
+    } else if let Some(path) = value.strip_prefix("file:").filter(|_| read_file) {
+        std::fs::read_to_string(path)
+            .map(|s| s.trim_end_matches(&['\r', '\n'][..]).to_owned())
+            .map_err(|e| format!("failed to resolve {}: {}", value, e))
+    } else {
+        Ok(value.to_owned())
+    }
+}
+
+/// Resolve a credential field, given by value
+fn resolve_secret_field(field: &mut Option<String>) -> Result<(), String> {
+    if let Some(v) = field {
+        *v = resolve_secret_ref(v, true)?;
+    }
+    Ok(())
+}
+
+/// Resolve a field which is the path of a file, only `env:` references are
+/// resolved, the content of the file is read by its user
+fn resolve_path_field(field: &mut Option<String>) -> Result<(), String> {
+    if let Some(v) = field {
+        *v = resolve_secret_ref(v, false)?;
+    }
+    Ok(())
+}
+
+impl Config {
+    /// Resolve all `env:` and `file:` references of secret fields in place,
+    /// must be called every time the config is (re)loaded.
+    pub fn resolve_secrets(&mut self) -> Result<(), String> {
+        resolve_secret_field(&mut self.server.password)?;
+        resolve_secret_field(&mut self.server.auth_webhook_url)?;
+        resolve_secret_field(&mut self.backend.encryption_master_key)?;
+        resolve_secret_field(&mut self.backend.encryption_kms_token)?;
+        resolve_path_field(&mut self.server.tls_key_file)?;
+        resolve_path_field(&mut self.server.tls_cert_file)?;
+        resolve_path_field(&mut self.server.tls_ca_cert_file)?;
+        resolve_path_field(&mut self.backend.ca_file)?;
+        resolve_path_field(&mut self.backend.cert_file)?;
+        resolve_path_field(&mut self.backend.key_file)?;
+        Ok(())
+    }
+}
+
+/// Line of `key` in `section` of the config file, or of the section header.
+/// Array of tables like `[[server.triggers]]` are located by their header.
+fn locate_setting(content: &str, section: &str, key: Option<&str>) -> usize {
+    let header = match key {
+        Some(key) => format!("{}.{}", section, key),
+        None => section.to_owned(),
+    };
+    let mut current = String::new();
+    for (idx, line) in content.lines().enumerate() {
+        let line = line.trim();
+        if line.starts_with('[') {
+            current = line
+                .trim_matches(|c| c == '[' || c == ']')
+                .trim()
+                .replace('"', "");
+            if current == header {
+                return idx + 1;
+            }
+        } else if current == section && line.split('=').next().map(str::trim) == key {
+            return idx + 1;
+        }
+    }
+    0
+}
+
+/// Check the settings of `section` one by one, fields are optional so a
+/// single setting can be deserialized alone.
+fn check_settings<T: serde::de::DeserializeOwned>(
+    content: &str,
+    section: &str,
+    settings: &toml::Value,
+    errors: &mut Vec<(usize, String)>,
+) {
+    let settings = match settings.as_table() {
+        Some(settings) => settings,
+        None => {
+            let line = locate_setting(content, section, None);
+            errors.push((line, format!("[{}] is not a table", section)));
+            return;
+        }
+    };
+    for (key, setting) in settings {
+        let mut single = toml::value::Table::new();
+        single.insert(key.clone(), setting.clone());
+        if let Err(e) = toml::Value::Table(single).try_into::<T>() {
+            let full = e.to_string();
+            let msg = full.split(", expected one of").next().unwrap_or("");
+            let line = locate_setting(content, section, Some(key));
+            errors.push((line, format!("[{}] {}: {}", section, key, msg)));
+        }
+    }
+}
+
+/// Check every setting of the config file, returns the error of each invalid
+/// or unknown one located by `file:line`, section and key.
+pub fn check_config(file: &str, content: &str) -> Vec<String> {
+    let value: toml::Value = match toml::from_str(content) {
+        Ok(v) => v,
+        Err(e) => {
+            let line = e.line_col().map(|(line, _)| line + 1).unwrap_or(0);
+            return vec![format!("{}:{}: {}", file, line, e)];
+        }
+    };
+    let sections = match value.as_table() {
+        Some(sections) => sections,
+        None => return vec![],
+    };
+
+    let mut errors: Vec<(usize, String)> = vec![];
+    for (section, settings) in sections {
+        match (section.as_str(), settings.as_table()) {
+            ("server", _) => check_settings::<Server>(content, section, settings, &mut errors),
+            ("backend", _) => check_settings::<Backend>(content, section, settings, &mut errors),
+            ("namespaces", Some(namespaces)) => {
+                for (name, settings) in namespaces {
+                    let section = format!("namespaces.{}", name);
+                    check_settings::<Namespace>(content, &section, settings, &mut errors);
+                }
+            }
+            _ => {
+                let line = locate_setting(content, section, None);
+                errors.push((line, format!("unknown section [{}]", section)));
+            }
+        }
+    }
+    if errors.is_empty() {
+        if let Err(e) = toml::from_str::<Config>(content) {
+            errors.push((0, e.to_string()));
+        }
+    }
+
+    errors.sort_by_key(|(line, _)| *line);
+    errors
+        .into_iter()
+        .map(|(line, msg)| format!("{}:{}: {}", file, line, msg))
+        .collect()
 }
 
 // Config
 pub static mut SERVER_CONFIG: Option<Config> = None;
 
+/// Overrides of the namespace served by this instance
+fn namespace_config() -> Option<&'static Namespace> {
+    unsafe {
+        if let Some(c) = &SERVER_CONFIG {
+            if let Some(namespaces) = &c.namespaces {
+                return namespaces.get(&config_instance_id_or_default());
+            }
+        }
+    }
+    None
+}
+
 pub fn is_auth_enabled() -> bool {
     unsafe {
         if let Some(c) = &SERVER_CONFIG {
@@ -113,7 +418,8 @@ pub fn is_auth_enabled() -> bool {
             }
         }
     }
-    false
+    // external backends always require AUTH
+    config_auth_backend_or_default() != "password"
 }
 
 // return false only if auth is enabled and password mismatch
@@ -128,6 +434,397 @@ pub fn is_auth_matched(password: &str) -> bool {
     true
 }
 
+pub fn config_auth_backend_or_default() -> String {
+    unsafe {
+        if let Some(c) = &SERVER_CONFIG {
+            if let Some(s) = c.server.auth_backend.clone() {
+                return s;
+            }
+        }
+    }
+
+    // default to the static requirepass style password
+    "password".to_owned()
+}
+
+pub fn config_auth_webhook_url_or_default() -> String {
+    unsafe {
+        if let Some(c) = &SERVER_CONFIG {
+            if let Some(s) = c.server.auth_webhook_url.clone() {
+                return s;
+            }
+        }
+    }
+
+    String::new()
+}
+
+pub fn config_auth_webhook_timeout_or_default() -> u64 {
+    unsafe {
+        if let Some(c) = &SERVER_CONFIG {
+            if let Some(s) = c.server.auth_webhook_timeout {
+                return s;
+            }
+        }
+    }
+
+    // default webhook timeout in ms
+    1000
+}
+
+pub fn config_auth_cache_ttl_or_default() -> u64 {
+    unsafe {
+        if let Some(c) = &SERVER_CONFIG {
+            if let Some(s) = c.server.auth_cache_ttl {
+                return s;
+            }
+        }
+    }
+
+    // default cache successful external auth result for 60s, 0 to disable
+    60000
+}
+
+pub fn config_auth_revoke_mode_or_default() -> String {
+    unsafe {
+        if let Some(c) = &SERVER_CONFIG {
+            if let Some(s) = c.server.auth_revoke_mode.clone() {
+                return s;
+            }
+        }
+    }
+
+    // default close the connections of a revoked user, "reauth" to keep them
+    // open until the user authenticates again
+    "kill".to_owned()
+}
+
+pub fn config_object_access_tracking_or_default() -> bool {
+    unsafe {
+        if let Some(c) = &SERVER_CONFIG {
+            if let Some(b) = c.server.object_access_tracking {
+                return b;
+            }
+        }
+    }
+
+    // default not track the accesses of OBJECT IDLETIME and FREQ
+    false
+}
+
+pub fn config_object_access_max_keys_or_default() -> usize {
+    unsafe {
+        if let Some(c) = &SERVER_CONFIG {
+            if let Some(n) = c.server.object_access_max_keys {
+                return n;
+            }
+        }
+    }
+
+    // default track the accesses of up to 100000 keys
+    100000
+}
+
+pub fn config_ip_allow_list_or_default() -> Vec<String> {
+    unsafe {
+        if let Some(c) = &SERVER_CONFIG {
+            if let Some(s) = c.server.ip_allow_list.clone() {
+                return s;
+            }
+        }
+    }
+
+    // default allow all
+    vec![]
+}
+
+pub fn config_ip_deny_list_or_default() -> Vec<String> {
+    unsafe {
+        if let Some(c) = &SERVER_CONFIG {
+            if let Some(s) = c.server.ip_deny_list.clone() {
+                return s;
+            }
+        }
+    }
+
+    vec![]
+}
+
+pub fn config_ip_filter_file_or_default() -> String {
+    unsafe {
+        if let Some(c) = &SERVER_CONFIG {
+            if let Some(s) = c.server.ip_filter_file.clone() {
+                return s;
+            }
+        }
+    }
+
+    String::new()
+}
+
+pub fn config_proxy_protocol_or_default() -> bool {
+    unsafe {
+        if let Some(c) = &SERVER_CONFIG {
+            if let Some(s) = c.server.proxy_protocol {
+                return s;
+            }
+        }
+    }
+
+    // default no PROXY protocol header expected
+    false
+}
+
+pub fn config_tls_proxy_protocol_or_default() -> bool {
+    unsafe {
+        if let Some(c) = &SERVER_CONFIG {
+            if let Some(s) = c.server.tls_proxy_protocol {
+                return s;
+            }
+        }
+    }
+
+    false
+}
+
+pub fn config_latency_slo_or_default() -> HashMap<String, u64> {
+    unsafe {
+        if let Some(c) = &SERVER_CONFIG {
+            if let Some(s) = c.server.latency_slo.clone() {
+                return s;
+            }
+        }
+    }
+
+    // default no latency slo declared
+    HashMap::new()
+}
+
+pub fn config_latency_slo_alert_log_or_default() -> bool {
+    unsafe {
+        if let Some(c) = &SERVER_CONFIG {
+            if let Some(s) = c.server.latency_slo_alert_log {
+                return s;
+            }
+        }
+    }
+
+    false
+}
+
+pub fn config_low_priority_concurrency_or_default() -> usize {
+    unsafe {
+        if let Some(c) = &SERVER_CONFIG {
+            if let Some(s) = c.server.low_priority_concurrency {
+                return s;
+            }
+        }
+    }
+    // default no limit on concurrent scans
+    0
+}
+
+pub fn config_cmdlog_sink_or_default() -> String {
+    unsafe {
+        if let Some(c) = &SERVER_CONFIG {
+            if let Some(s) = c.server.cmdlog_sink.clone() {
+                return s;
+            }
+        }
+    }
+    // default write commands are not shipped
+    "".to_owned()
+}
+
+pub fn config_cmdlog_batch_size_or_default() -> usize {
+    unsafe {
+        if let Some(c) = &SERVER_CONFIG {
+            if let Some(s) = c.server.cmdlog_batch_size {
+                return s;
+            }
+        }
+    }
+    100
+}
+
+pub fn config_cmdlog_batch_wait_ms_or_default() -> u64 {
+    unsafe {
+        if let Some(c) = &SERVER_CONFIG {
+            if let Some(s) = c.server.cmdlog_batch_wait_ms {
+                return s;
+            }
+        }
+    }
+    10
+}
+
+pub fn config_cmdlog_queue_size_or_default() -> usize {
+    unsafe {
+        if let Some(c) = &SERVER_CONFIG {
+            if let Some(s) = c.server.cmdlog_queue_size {
+                return s;
+            }
+        }
+    }
+    10000
+}
+
+pub fn config_cmdlog_drop_on_full_or_default() -> bool {
+    unsafe {
+        if let Some(c) = &SERVER_CONFIG {
+            if let Some(s) = c.server.cmdlog_drop_on_full {
+                return s;
+            }
+        }
+    }
+    // default writers wait for the sink to catch up
+    false
+}
+
+pub fn config_triggers_or_default() -> Vec<TriggerRule> {
+    unsafe {
+        if let Some(c) = &SERVER_CONFIG {
+            if let Some(s) = c.server.triggers.clone() {
+                return s;
+            }
+        }
+    }
+    vec![]
+}
+
+pub fn config_hash_indexes_or_default() -> Vec<HashIndexRule> {
+    unsafe {
+        if let Some(c) = &SERVER_CONFIG {
+            if let Some(s) = c.server.hash_indexes.clone() {
+                return s;
+            }
+        }
+    }
+    vec![]
+}
+
+pub fn config_jobs_or_default() -> Vec<JobRule> {
+    unsafe {
+        if let Some(c) = &SERVER_CONFIG {
+            if let Some(s) = c.server.jobs.clone() {
+                return s;
+            }
+        }
+    }
+    vec![]
+}
+
+pub fn config_enable_debug_command_or_default() -> String {
+    unsafe {
+        if let Some(c) = &SERVER_CONFIG {
+            if let Some(s) = c.server.enable_debug_command.clone() {
+                return s;
+            }
+        }
+    }
+    // default DEBUG is disabled, `yes` for all connections or `local` for loopback ones
+    "no".to_owned()
+}
+
+pub fn config_restore_skip_zero_checksum_or_default() -> bool {
+    unsafe {
+        if let Some(c) = &SERVER_CONFIG {
+            if let Some(s) = c.server.restore_skip_zero_checksum {
+                return s;
+            }
+        }
+    }
+
+    // default every RESTORE payload is checked
+    false
+}
+
+pub fn config_pipeline_concurrency_or_default() -> usize {
+    unsafe {
+        if let Some(c) = &SERVER_CONFIG {
+            if let Some(s) = c.server.pipeline_concurrency {
+                return s;
+            }
+        }
+    }
+    // default execute pipelined commands one by one
+    1
+}
+
+pub fn config_memory_soft_limit_or_default() -> u64 {
+    unsafe {
+        if let Some(c) = &SERVER_CONFIG {
+            if let Some(s) = c.server.memory_soft_limit {
+                return s;
+            }
+        }
+    }
+    // default no limit
+    0
+}
+
+pub fn config_lua_time_limit_or_default() -> u64 {
+    unsafe {
+        if let Some(c) = &SERVER_CONFIG {
+            if let Some(s) = c.server.lua_time_limit {
+                return s;
+            }
+        }
+    }
+    // default abort scripts running longer than 5s
+    5000
+}
+
+pub fn config_notify_keyspace_events_or_default() -> String {
+    unsafe {
+        if let Some(c) = &SERVER_CONFIG {
+            if let Some(s) = c.server.notify_keyspace_events.clone() {
+                return s;
+            }
+        }
+    }
+    // default no keyspace notifications
+    String::new()
+}
+
+pub fn config_memory_hard_limit_or_default() -> u64 {
+    unsafe {
+        if let Some(c) = &SERVER_CONFIG {
+            if let Some(s) = c.server.memory_hard_limit {
+                return s;
+            }
+        }
+    }
+    // default no limit
+    0
+}
+
+pub fn config_resource_group_or_default() -> String {
+    unsafe {
+        if let Some(c) = &SERVER_CONFIG {
+            if let Some(s) = c.server.resource_group.clone() {
+                return s;
+            }
+        }
+    }
+    // default resource group of the namespace
+    "default".to_owned()
+}
+
+/// Resource group of the user, falls back to the namespace resource group.
+pub fn config_user_resource_group(username: &str) -> String {
+    unsafe {
+        if let Some(c) = &SERVER_CONFIG {
+            if let Some(s) = &c.server.user_resource_groups {
+                if let Some(group) = s.get(username) {
+                    return group.clone();
+                }
+            }
+        }
+    }
+    config_resource_group_or_default()
+}
+
 pub fn txn_retry_count() -> u32 {
     unsafe {
         if let Some(c) = &SERVER_CONFIG {
@@ -280,6 +977,98 @@ pub fn config_prometheus_port_or_default() -> String {
     "8080".to_owned()
 }
 
+pub fn config_memcache_listen_or_default() -> String {
+    unsafe {
+        if let Some(c) = &SERVER_CONFIG {
+            if let Some(s) = c.server.memcache_listen.clone() {
+                return s;
+            }
+        }
+    }
+    "0.0.0.0".to_owned()
+}
+
+pub fn config_memcache_port_or_default() -> u16 {
+    unsafe {
+        if let Some(c) = &SERVER_CONFIG {
+            if let Some(s) = c.server.memcache_port {
+                return s;
+            }
+        }
+    }
+    // default to 0, the memcached listener is disabled
+    0
+}
+
+pub fn config_http_api_listen_or_default() -> String {
+    unsafe {
+        if let Some(c) = &SERVER_CONFIG {
+            if let Some(s) = c.server.http_api_listen.clone() {
+                return s;
+            }
+        }
+    }
+    "0.0.0.0".to_owned()
+}
+
+pub fn config_http_api_port_or_default() -> u16 {
+    unsafe {
+        if let Some(c) = &SERVER_CONFIG {
+            if let Some(s) = c.server.http_api_port {
+                return s;
+            }
+        }
+    }
+    // default to 0, the http data api is disabled
+    0
+}
+
+pub fn config_grpc_listen_or_default() -> String {
+    unsafe {
+        if let Some(c) = &SERVER_CONFIG {
+            if let Some(s) = c.server.grpc_listen.clone() {
+                return s;
+            }
+        }
+    }
+    "0.0.0.0".to_owned()
+}
+
+pub fn config_grpc_port_or_default() -> u16 {
+    unsafe {
+        if let Some(c) = &SERVER_CONFIG {
+            if let Some(s) = c.server.grpc_port {
+                return s;
+            }
+        }
+    }
+    // default to 0, the grpc api is disabled
+    0
+}
+
+pub fn config_ws_listen_or_default() -> String {
+    unsafe {
+        if let Some(c) = &SERVER_CONFIG {
+            if let Some(s) = c.server.ws_listen.clone() {
+                return s;
+            }
+        }
+    }
+    "0.0.0.0".to_owned()
+}
+
+pub fn config_ws_port_or_default() -> u16 {
+    unsafe {
+        if let Some(c) = &SERVER_CONFIG {
+            if let Some(s) = c.server.ws_port {
+                return s;
+            }
+        }
+    }
+    // default to 0, the websocket listener is disabled
+    0
+}
+
 pub fn config_local_pool_number() -> usize {
     unsafe {
         if let Some(c) = &SERVER_CONFIG {
@@ -321,6 +1110,18 @@ pub fn config_cluster_topology_interval_or_default() -> u64 {
     10000
 }
 
+pub fn config_cluster_slot_scan_max_keys_or_default() -> u64 {
+    unsafe {
+        if let Some(c) = &SERVER_CONFIG {
+            if let Some(s) = c.server.cluster_slot_scan_max_keys {
+                return s;
+            }
+        }
+    }
+    // default scan at most 100000 keys for CLUSTER COUNTKEYSINSLOT and GETKEYSINSLOT
+    100000
+}
+
 pub fn config_cluster_topology_expire_or_default() -> u64 {
     unsafe {
         if let Some(c) = &SERVER_CONFIG {
@@ -383,6 +1184,82 @@ pub fn log_file() -> String {
     "tikv-service.log".to_owned()
 }
 
+fn log_redact_str() -> String {
+    unsafe {
+        if let Some(c) = &SERVER_CONFIG {
+            // the override of the namespace served by this instance first
+            if let Some(m) = &c.server.log_redact_namespaces {
+                if let Some(l) = m.get(&config_instance_id_or_default()) {
+                    return l.clone();
+                }
+            }
+            if let Some(l) = c.server.log_redact.clone() {
+                return l;
+            }
+        }
+    }
+    "off".to_owned()
+}
+
+/// Redact argument and reply values in logs, `values` keeps the command and
+/// key names, `hash` replaces key names with a short hash as well
+pub fn log_redact_values() -> bool {
+    let redact = log_redact_str();
+    redact == "values" || redact == "hash"
+}
+
+pub fn log_redact_key_hash() -> bool {
+    log_redact_str() == "hash"
+}
+
+pub fn log_max_args() -> usize {
+    unsafe {
+        if let Some(c) = &SERVER_CONFIG {
+            if let Some(s) = c.server.log_max_args {
+                return s;
+            }
+        }
+    }
+    // default log all arguments of a request
+    0
+}
+
+pub fn log_max_arg_len() -> usize {
+    unsafe {
+        if let Some(c) = &SERVER_CONFIG {
+            if let Some(s) = c.server.log_max_arg_len {
+                return s;
+            }
+        }
+    }
+    // default log whole arguments of a request
+    0
+}
+
+pub fn log_sample_rate() -> u64 {
+    unsafe {
+        if let Some(c) = &SERVER_CONFIG {
+            if let Some(s) = c.server.log_sample_rate {
+                return s;
+            }
+        }
+    }
+    // default log the debug lines of every command
+    1
+}
+
+pub fn log_sample_slower_than_us() -> u64 {
+    unsafe {
+        if let Some(c) = &SERVER_CONFIG {
+            if let Some(s) = c.server.log_sample_slower_than_us {
+                return s;
+            }
+        }
+    }
+    // default no slow command is logged outside the samples
+    0
+}
+
 pub fn set_global_config(config: Config) {
     unsafe {
         SERVER_CONFIG.replace(config);
@@ -460,49 +1337,91 @@ pub fn txn_region_backoff_delay_attemps() -> u32 {
     2
 }
 
-pub fn txn_lock_backoff_delay_ms() -> u64 {
+pub fn txn_lock_backoff_delay_ms() -> u64 {
+    unsafe {
+        if let Some(c) = &SERVER_CONFIG {
+            if let Some(b) = c.backend.txn_lock_backoff_delay_ms {
+                return b;
+            }
+        }
+    }
+    2
+}
+
+pub fn txn_lock_backoff_delay_attemps() -> u32 {
+    unsafe {
+        if let Some(c) = &SERVER_CONFIG {
+            if let Some(b) = c.backend.txn_lock_backoff_delay_attemps {
+                return b;
+            }
+        }
+    }
+    2
+}
+
+pub fn cmd_lrem_length_limit_or_default() -> u32 {
+    if let Some(s) = namespace_config().and_then(|n| n.cmd_lrem_length_limit) {
+        return s;
+    }
+    unsafe {
+        if let Some(c) = &SERVER_CONFIG {
+            if let Some(b) = c.backend.cmd_lrem_length_limit {
+                return b;
+            }
+        }
+    }
+    // default lrem length no limit
+    0
+}
+
+pub fn cmd_linsert_length_limit_or_default() -> u32 {
+    if let Some(s) = namespace_config().and_then(|n| n.cmd_linsert_length_limit) {
+        return s;
+    }
     unsafe {
         if let Some(c) = &SERVER_CONFIG {
-            if let Some(b) = c.backend.txn_lock_backoff_delay_ms {
+            if let Some(b) = c.backend.cmd_linsert_length_limit {
                 return b;
             }
         }
     }
-    2
+    // default linsert length no limit
+    0
 }
 
-pub fn txn_lock_backoff_delay_attemps() -> u32 {
+pub fn cmd_inter_length_limit_or_default() -> u32 {
     unsafe {
         if let Some(c) = &SERVER_CONFIG {
-            if let Some(b) = c.backend.txn_lock_backoff_delay_attemps {
+            if let Some(b) = c.backend.cmd_inter_length_limit {
                 return b;
             }
         }
     }
-    2
+    // default sinter and zinter length no limit
+    0
 }
 
-pub fn cmd_lrem_length_limit_or_default() -> u32 {
+pub fn cmd_bitop_max_source_bytes_or_default() -> u64 {
     unsafe {
         if let Some(c) = &SERVER_CONFIG {
-            if let Some(b) = c.backend.cmd_lrem_length_limit {
+            if let Some(b) = c.backend.cmd_bitop_max_source_bytes {
                 return b;
             }
         }
     }
-    // default lrem length no limit
-    0
+    // default 64MB per bitop source string
+    64 * 1024 * 1024
 }
 
-pub fn cmd_linsert_length_limit_or_default() -> u32 {
+pub fn cmd_sort_length_limit_or_default() -> u32 {
     unsafe {
         if let Some(c) = &SERVER_CONFIG {
-            if let Some(b) = c.backend.cmd_linsert_length_limit {
+            if let Some(b) = c.backend.cmd_sort_length_limit {
                 return b;
             }
         }
     }
-    // default linsert length no limit
+    // default sort length no limit
     0
 }
 
@@ -566,6 +1485,21 @@ pub fn async_del_zset_threshold_or_default() -> u32 {
     }
 }
 
+pub fn async_del_stream_threshold_or_default() -> u32 {
+    unsafe {
+        if let Some(c) = &SERVER_CONFIG {
+            if let Some(b) = c.backend.async_del_stream_threshold {
+                return b;
+            }
+        }
+    }
+    if async_deletion_enabled_or_default() {
+        1000
+    } else {
+        u32::MAX
+    }
+}
+
 pub fn async_expire_list_threshold_or_default() -> u32 {
     unsafe {
         if let Some(c) = &SERVER_CONFIG {
@@ -674,6 +1608,66 @@ pub fn async_gc_worker_queue_size_or_default() -> usize {
     100000
 }
 
+pub fn async_gc_batch_size_or_default() -> usize {
+    unsafe {
+        if let Some(c) = &SERVER_CONFIG {
+            if let Some(b) = c.backend.async_gc_batch_size {
+                return b.max(1);
+            }
+        }
+    }
+    // default keys deleted by a gc worker in one transaction
+    1000
+}
+
+pub fn async_gc_concurrency_or_default() -> usize {
+    unsafe {
+        if let Some(c) = &SERVER_CONFIG {
+            if let Some(b) = c.backend.async_gc_concurrency {
+                return b;
+            }
+        }
+    }
+    // default gc workers deleting at the same time, 0 for all of them
+    0
+}
+
+pub fn async_gc_deletes_per_sec_or_default() -> u64 {
+    unsafe {
+        if let Some(c) = &SERVER_CONFIG {
+            if let Some(b) = c.backend.async_gc_deletes_per_sec {
+                return b;
+            }
+        }
+    }
+    // default keys deleted per second by all gc workers, 0 for no limit
+    0
+}
+
+pub fn async_gc_target_latency_ms_or_default() -> u64 {
+    unsafe {
+        if let Some(c) = &SERVER_CONFIG {
+            if let Some(b) = c.backend.async_gc_target_latency_ms {
+                return b;
+            }
+        }
+    }
+    // default latency of a gc batch over which the gc slows down, 0 to disable
+    200
+}
+
+pub fn backend_storage_or_default() -> String {
+    unsafe {
+        if let Some(c) = &SERVER_CONFIG {
+            if let Some(b) = c.backend.storage.clone() {
+                return b;
+            }
+        }
+    }
+    // default store data in tikv, `memory` for the in-process backend
+    "tikv".to_owned()
+}
+
 pub fn backend_timeout_or_default() -> u64 {
     unsafe {
         if let Some(c) = &SERVER_CONFIG {
@@ -686,6 +1680,207 @@ pub fn backend_timeout_or_default() -> u64 {
     10000
 }
 
+pub fn backend_health_check_interval_or_default() -> u64 {
+    unsafe {
+        if let Some(c) = &SERVER_CONFIG {
+            if let Some(b) = c.backend.health_check_interval {
+                return b;
+            }
+        }
+    }
+    // default backend health check interval in ms
+    2000
+}
+
+pub fn backend_stale_read_ms_or_default() -> u64 {
+    if let Some(s) = namespace_config().and_then(|n| n.stale_read_ms) {
+        return s;
+    }
+    unsafe {
+        if let Some(c) = &SERVER_CONFIG {
+            if let Some(b) = c.backend.stale_read_ms {
+                return b;
+            }
+        }
+    }
+    // default READONLY connections read the latest data
+    0
+}
+
+pub fn backend_read_consistency_or_default() -> ReadConsistency {
+    if let Some(s) = namespace_config().and_then(|n| n.read_consistency) {
+        return s;
+    }
+    unsafe {
+        if let Some(c) = &SERVER_CONFIG {
+            if let Some(b) = c.backend.read_consistency {
+                return b;
+            }
+        }
+    }
+    // default snapshot reads read the latest committed data
+    ReadConsistency::Latest
+}
+
+pub fn backend_rpc_log_slower_than_ms_or_default() -> u64 {
+    unsafe {
+        if let Some(c) = &SERVER_CONFIG {
+            if let Some(b) = c.backend.rpc_log_slower_than_ms {
+                return b;
+            }
+        }
+    }
+    // default record the storage calls of at least 100ms, 0 for none
+    100
+}
+
+pub fn backend_rpc_log_max_len_or_default() -> usize {
+    unsafe {
+        if let Some(c) = &SERVER_CONFIG {
+            if let Some(b) = c.backend.rpc_log_max_len {
+                return b;
+            }
+        }
+    }
+    // default keep the 128 latest slow calls
+    128
+}
+
+pub fn backend_leader_read_timeout_ms_or_default() -> u64 {
+    unsafe {
+        if let Some(c) = &SERVER_CONFIG {
+            if let Some(b) = c.backend.leader_read_timeout_ms {
+                return b;
+            }
+        }
+    }
+    // default reads wait for the region leader, no stale fallback
+    0
+}
+
+pub fn backend_idempotency_token_ttl_ms_or_default() -> u64 {
+    unsafe {
+        if let Some(c) = &SERVER_CONFIG {
+            if let Some(b) = c.backend.idempotency_token_ttl_ms {
+                return b;
+            }
+        }
+    }
+    // default remember applied tokens for one hour
+    3600000
+}
+
+pub fn backend_idempotency_derive_tokens_or_default() -> bool {
+    unsafe {
+        if let Some(c) = &SERVER_CONFIG {
+            if let Some(b) = c.backend.idempotency_derive_tokens {
+                return b;
+            }
+        }
+    }
+    // default only writes with a client supplied token are deduplicated
+    false
+}
+
+pub fn backend_default_ttl_ms_or_default() -> u64 {
+    if let Some(s) = namespace_config().and_then(|n| n.default_ttl_ms) {
+        return s;
+    }
+    unsafe {
+        if let Some(c) = &SERVER_CONFIG {
+            if let Some(b) = c.backend.default_ttl_ms {
+                return b;
+            }
+        }
+    }
+    // default keys created without expiration never expire
+    0
+}
+
+pub fn backend_max_ttl_ms_or_default() -> u64 {
+    if let Some(s) = namespace_config().and_then(|n| n.max_ttl_ms) {
+        return s;
+    }
+    unsafe {
+        if let Some(c) = &SERVER_CONFIG {
+            if let Some(b) = c.backend.max_ttl_ms {
+                return b;
+            }
+        }
+    }
+    // default no limit on the ttl
+    0
+}
+
+pub fn backend_eviction_max_keys_or_default() -> u64 {
+    if let Some(s) = namespace_config().and_then(|n| n.eviction_max_keys) {
+        return s;
+    }
+    unsafe {
+        if let Some(c) = &SERVER_CONFIG {
+            if let Some(b) = c.backend.eviction_max_keys {
+                return b;
+            }
+        }
+    }
+    // default no quota on the number of keys
+    0
+}
+
+pub fn backend_eviction_max_bytes_or_default() -> u64 {
+    if let Some(s) = namespace_config().and_then(|n| n.eviction_max_bytes) {
+        return s;
+    }
+    unsafe {
+        if let Some(c) = &SERVER_CONFIG {
+            if let Some(b) = c.backend.eviction_max_bytes {
+                return b;
+            }
+        }
+    }
+    // default no quota on the size of the keyspace
+    0
+}
+
+pub fn backend_eviction_budget_or_default() -> usize {
+    if let Some(s) = namespace_config().and_then(|n| n.eviction_budget) {
+        return s;
+    }
+    unsafe {
+        if let Some(c) = &SERVER_CONFIG {
+            if let Some(b) = c.backend.eviction_budget {
+                return b;
+            }
+        }
+    }
+    // default evict at most 100 keys per gc round
+    100
+}
+
+pub fn backend_pipeline_snapshot_max_cmds_or_default() -> u32 {
+    unsafe {
+        if let Some(c) = &SERVER_CONFIG {
+            if let Some(b) = c.backend.pipeline_snapshot_max_cmds {
+                return b;
+            }
+        }
+    }
+    // default max read commands sharing one snapshot, 0 to disable
+    16
+}
+
+pub fn backend_pipeline_snapshot_max_age_ms_or_default() -> u64 {
+    unsafe {
+        if let Some(c) = &SERVER_CONFIG {
+            if let Some(b) = c.backend.pipeline_snapshot_max_age_ms {
+                return b;
+            }
+        }
+    }
+    // default max age of a shared snapshot in ms
+    10
+}
+
 pub fn backend_ca_file_or_default() -> String {
     unsafe {
         if let Some(c) = &SERVER_CONFIG {
@@ -828,3 +2023,61 @@ pub fn backend_max_inflight_requests_or_default() -> usize {
     // default backend max inflight requests
     100
 }
+
+pub fn encryption_enabled_or_default() -> bool {
+    unsafe {
+        if let Some(c) = &SERVER_CONFIG {
+            if let Some(b) = c.backend.encryption_enabled {
+                return b;
+            }
+        }
+    }
+    // default value encryption disabled
+    false
+}
+
+pub fn encryption_master_key_or_default() -> String {
+    unsafe {
+        if let Some(c) = &SERVER_CONFIG {
+            if let Some(b) = c.backend.encryption_master_key.clone() {
+                return b;
+            }
+        }
+    }
+    // default no master key, hex encoded 32 bytes key is required when enabled
+    String::new()
+}
+
+pub fn encryption_kms_url_or_default() -> String {
+    unsafe {
+        if let Some(c) = &SERVER_CONFIG {
+            if let Some(s) = c.backend.encryption_kms_url.clone() {
+                return s;
+            }
+        }
+    }
+    // default no kms, the master key is given in the config
+    String::new()
+}
+
+pub fn encryption_kms_key_id_or_default() -> String {
+    unsafe {
+        if let Some(c) = &SERVER_CONFIG {
+            if let Some(s) = c.backend.encryption_kms_key_id.clone() {
+                return s;
+            }
+        }
+    }
+    String::new()
+}
+
+pub fn encryption_kms_token_or_default() -> String {
+    unsafe {
+        if let Some(c) = &SERVER_CONFIG {
+            if let Some(s) = c.backend.encryption_kms_token.clone() {
+                return s;
+            }
+        }
+    }
+    String::new()
+}
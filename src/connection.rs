@@ -1,12 +1,95 @@
+use crate::cmdlog::Reply;
+use crate::diag::record_error;
 use crate::frame::{self, Frame};
 use crate::metrics::{DATA_TRAFFIC_IN, DATA_TRAFFIC_OUT};
+use crate::websocket::WsStream;
 
 use async_std::io::{BufReader, BufWriter, WriteExt};
 use async_std::net::TcpStream;
 use async_tls::server::TlsStream;
 use bytes::{Buf, BytesMut};
 use futures::AsyncReadExt;
-use std::io::{self, Cursor};
+use std::io::{self, Cursor, IoSlice};
+
+/// Replies with at least this many bytes of bulk strings are written with
+/// vectored writes, the bulk strings straight from their buffers instead of
+/// copied into the write buffer.
+const VECTORED_WRITE_THRESHOLD: usize = 64 * 1024;
+
+/// Bulk strings shorter than this are copied next to the headers of a
+/// vectored write, a slice of their own would cost more than the copy.
+const VECTORED_WRITE_MIN_SLICE: usize = 1024;
+
+/// Slices of one vectored write call, like the IOV_MAX of Linux.
+const VECTORED_WRITE_MAX_SLICES: usize = 1024;
+
+/// A part of a vectored write, a range of the encoded headers or the bytes
+/// of a bulk string.
+enum Chunk<'a> {
+    Head(usize, usize),
+    Data(&'a [u8]),
+}
+
+/// The bytes of the bulk strings of the frame and its nested arrays.
+fn bulk_len(frame: &Frame) -> usize {
+    match frame {
+        Frame::Bulk(val) => val.len(),
+        Frame::Array(val) => val.iter().map(bulk_len).sum(),
+        _ => 0,
+    }
+}
+
+/// Encode the frame into `head`, except the large bulk strings which are
+/// borrowed as chunks of their own. `start` is where the range of `head`
+/// not yet in a chunk begins.
+fn encode_chunks<'a>(
+    frame: &'a Frame,
+    head: &mut Vec<u8>,
+    start: &mut usize,
+    chunks: &mut Vec<Chunk<'a>>,
+) {
+    use std::io::Write;
+
+    match frame {
+        Frame::Simple(val) => {
+            head.push(b'+');
+            head.extend_from_slice(val.as_bytes());
+            head.extend_from_slice(b"\r\n");
+        }
+        Frame::ErrorString(val) => {
+            head.push(b'-');
+            head.extend_from_slice(val.as_bytes());
+            head.extend_from_slice(b"\r\n");
+        }
+        Frame::ErrorOwned(val) => {
+            head.push(b'-');
+            head.extend_from_slice(val.as_bytes());
+            head.extend_from_slice(b"\r\n");
+        }
+        Frame::Integer(val) => {
+            // writing to a Vec can't fail
+            let _ = write!(head, ":{}\r\n", val);
+        }
+        Frame::Null => head.extend_from_slice(b"$-1\r\n"),
+        Frame::Bulk(val) => {
+            let _ = write!(head, "${}\r\n", val.len());
+            if val.len() < VECTORED_WRITE_MIN_SLICE {
+                head.extend_from_slice(val);
+            } else {
+                chunks.push(Chunk::Head(*start, head.len()));
+                chunks.push(Chunk::Data(val));
+                *start = head.len();
+            }
+            head.extend_from_slice(b"\r\n");
+        }
+        Frame::Array(val) => {
+            let _ = write!(head, "*{}\r\n", val.len());
+            for entry in val {
+                encode_chunks(entry, head, start, chunks);
+            }
+        }
+    }
+}
 
 /// Send and receive `Frame` values from a remote peer.
 ///
@@ -33,9 +116,18 @@ pub struct Connection {
     tls_w: Option<BufWriter<futures::io::WriteHalf<TlsStream<TcpStream>>>>,
     tls_r: Option<BufReader<futures::io::ReadHalf<TlsStream<TcpStream>>>>,
 
+    // RESP inside the messages of an upgraded WebSocket connection
+    ws: Option<WsStream>,
+
     local_addr: String,
     peer_addr: String,
 
+    // Push messages are sent as RESP3 push type instead of arrays.
+    resp3: bool,
+
+    // What the last frame written tells of the command which replied it.
+    last_reply: Option<Reply>,
+
     // The buffer for reading frames.
     buffer: BytesMut,
 }
@@ -48,12 +140,15 @@ impl Connection {
             tls: false,
             local_addr: socket.local_addr().unwrap().to_string(),
             peer_addr: socket.peer_addr().unwrap().to_string(),
+            resp3: false,
+            last_reply: None,
 
             w: Some(BufWriter::new(socket.clone())),
             r: Some(BufReader::new(socket)),
 
             tls_w: None,
             tls_r: None,
+            ws: None,
             // Default to a 4KB read buffer. For the use case of mini redis,
             // this is fine. However, real applications will want to tune this
             // value to their specific use case. There is a high likelihood that
@@ -72,12 +167,35 @@ impl Connection {
             tls: true,
             local_addr: local_addr.to_string(),
             peer_addr: peer_addr.to_string(),
+            resp3: false,
+            last_reply: None,
 
             w: None,
             r: None,
 
             tls_w: Some(BufWriter::new(tls_w)),
             tls_r: Some(BufReader::new(tls_r)),
+            ws: None,
+            buffer: BytesMut::with_capacity(32 * 1024),
+        }
+    }
+
+    /// Create a `Connection` on the WebSocket upgraded from `socket`, `read`
+    /// are the bytes read after the upgrade request.
+    pub fn new_ws(socket: TcpStream, read: Vec<u8>) -> Connection {
+        Connection {
+            tls: false,
+            local_addr: socket.local_addr().unwrap().to_string(),
+            peer_addr: socket.peer_addr().unwrap().to_string(),
+            resp3: false,
+            last_reply: None,
+
+            w: None,
+            r: None,
+
+            tls_w: None,
+            tls_r: None,
+            ws: Some(WsStream::new(socket, read)),
             buffer: BytesMut::with_capacity(32 * 1024),
         }
     }
@@ -90,8 +208,34 @@ impl Connection {
         &self.peer_addr
     }
 
+    /// Returns true if more data of the client is already read into the
+    /// buffer, i.e. the requests are pipelined.
+    pub fn has_buffered_data(&self) -> bool {
+        !self.buffer.is_empty()
+    }
+
+    pub fn set_peer_addr(&mut self, peer_addr: &str) {
+        self.peer_addr = peer_addr.to_string();
+    }
+
+    pub fn is_resp3(&self) -> bool {
+        self.resp3
+    }
+
+    pub fn set_resp3(&mut self, resp3: bool) {
+        self.resp3 = resp3;
+    }
+
+    /// What the frame last written tells of the command which replied it,
+    /// `None` if nothing was written since the previous call
+    pub fn take_last_reply(&mut self) -> Option<Reply> {
+        self.last_reply.take()
+    }
+
     async fn write_all(&mut self, buf: &[u8]) -> io::Result<()> {
-        if self.tls {
+        if let Some(ws) = self.ws.as_mut() {
+            ws.write(buf);
+        } else if self.tls {
             self.tls_w.as_mut().unwrap().write_all(buf).await?;
         } else {
             self.w.as_mut().unwrap().write_all(buf).await?;
@@ -100,8 +244,52 @@ impl Connection {
         Ok(())
     }
 
+    /// Write all of `bufs` with vectored writes on the stream under the write
+    /// buffer, which must be flushed.
+    async fn write_all_vectored(&mut self, bufs: &[&[u8]]) -> io::Result<()> {
+        // the message is built in memory anyway
+        if let Some(ws) = self.ws.as_mut() {
+            for buf in bufs {
+                ws.write(buf);
+                DATA_TRAFFIC_OUT.inc_by(buf.len() as u64);
+            }
+            return Ok(());
+        }
+        let mut idx = 0;
+        let mut offset = 0;
+        while idx < bufs.len() {
+            let end = bufs.len().min(idx + VECTORED_WRITE_MAX_SLICES);
+            let mut slices = Vec::with_capacity(end - idx);
+            slices.push(IoSlice::new(&bufs[idx][offset..]));
+            slices.extend(bufs[idx + 1..end].iter().map(|buf| IoSlice::new(buf)));
+
+            let mut written = if self.tls {
+                let w = self.tls_w.as_mut().unwrap().get_mut();
+                w.write_vectored(&slices).await?
+            } else {
+                let w = self.w.as_mut().unwrap().get_mut();
+                w.write_vectored(&slices).await?
+            };
+            if written == 0 {
+                return Err(io::ErrorKind::WriteZero.into());
+            }
+            DATA_TRAFFIC_OUT.inc_by(written as u64);
+
+            // skip the slices written, the last one may be written partially
+            while idx < bufs.len() && written >= bufs[idx].len() - offset {
+                written -= bufs[idx].len() - offset;
+                offset = 0;
+                idx += 1;
+            }
+            offset += written;
+        }
+        Ok(())
+    }
+
     async fn flush(&mut self) -> io::Result<()> {
-        if self.tls {
+        if let Some(ws) = self.ws.as_mut() {
+            ws.flush().await?;
+        } else if self.tls {
             self.tls_w.as_mut().unwrap().flush().await?;
         } else {
             self.w.as_mut().unwrap().flush().await?;
@@ -110,7 +298,9 @@ impl Connection {
     }
 
     async fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
-        Ok(if self.tls {
+        Ok(if let Some(ws) = self.ws.as_mut() {
+            ws.read(buf).await?
+        } else if self.tls {
             self.tls_r.as_mut().unwrap().read(buf).await?
         } else {
             self.r.as_mut().unwrap().read(buf).await?
@@ -161,6 +351,18 @@ impl Connection {
         }
     }
 
+    /// Parse the next pipelined frame if it is already in the read buffer,
+    /// never waits for the socket.
+    pub fn read_buffered_frame(&mut self) -> crate::Result<Option<Frame>> {
+        match self.parse_frame()? {
+            (Some(frame), len) => {
+                DATA_TRAFFIC_IN.inc_by(len as u64);
+                Ok(Some(frame))
+            }
+            (None, _) => Ok(None),
+        }
+    }
+
     /// Tries to parse a frame from the buffer. If the buffer contains enough
     /// data, the frame is returned and the data removed from the buffer. If not
     /// enough data has been buffered yet, `Ok(None)` is returned. If the
@@ -234,44 +436,25 @@ impl Connection {
     /// syscalls. However, it is fine to call these functions on a *buffered*
     /// write stream. The data will be written to the buffer. Once the buffer is
     /// full, it is flushed to the underlying socket.
+    ///
+    /// Replies with large bulk strings are written with vectored writes
+    /// instead, see `write_frame_vectored`.
     pub async fn write_frame(&mut self, frame: &Frame) -> io::Result<()> {
-        // Arrays are encoded by encoding each entry. All other frame types are
-        // considered literals. For now, this is not able to encode
-        // recursive frame structures. See below for more details.
-        match frame {
-            Frame::Array(val) => {
-                // Encode the frame type prefix. For an array, it is `*`.
-                self.write_all(b"*").await?;
-
-                // Encode the length of the array.
-                self.write_decimal(val.len() as i64).await?;
+        self.last_reply = Some(Reply::of(frame));
+        if bulk_len(frame) >= VECTORED_WRITE_THRESHOLD {
+            return self.write_frame_vectored(frame).await;
+        }
 
-                // Iterate and encode each entry in the array.
-                for entry in &**val {
-                    // TODO make this to be recursive
-                    // we need nested array response only for `cluster slots` command for now
-                    match entry {
-                        Frame::Array(val) => {
-                            self.write_all(b"*").await?;
-                            self.write_decimal(val.len() as i64).await?;
-                            for entry in &**val {
-                                match entry {
-                                    Frame::Array(val) => {
-                                        self.write_all(b"*").await?;
-                                        self.write_decimal(val.len() as i64).await?;
-                                        for entry in &**val {
-                                            self.write_value(entry).await?;
-                                        }
-                                    }
-                                    _ => self.write_value(entry).await?,
-                                }
-                            }
-                        }
-                        _ => self.write_value(entry).await?,
-                    }
-                }
+        match frame {
+            // kept for the diagnostic snapshot
+            Frame::ErrorString(val) => {
+                record_error(self.peer_addr(), val);
+                self.write_value(frame).await?
+            }
+            Frame::ErrorOwned(val) => {
+                record_error(self.peer_addr(), val);
+                self.write_value(frame).await?
             }
-            // The frame type is a literal. Encode the value directly.
             _ => self.write_value(frame).await?,
         }
 
@@ -281,6 +464,50 @@ impl Connection {
         self.flush().await
     }
 
+    /// Write the frame with vectored writes, the headers and small values
+    /// encoded in one buffer and the large bulk strings written from their
+    /// own buffers, so they are not copied before reaching the socket. This
+    /// supports nested arrays of any depth, as the encoding is not async.
+    async fn write_frame_vectored(&mut self, frame: &Frame) -> io::Result<()> {
+        let mut head = Vec::with_capacity(4 * 1024);
+        let mut start = 0;
+        let mut chunks = vec![];
+        encode_chunks(frame, &mut head, &mut start, &mut chunks);
+        chunks.push(Chunk::Head(start, head.len()));
+
+        let bufs: Vec<&[u8]> = chunks
+            .iter()
+            .map(|chunk| match chunk {
+                Chunk::Head(start, end) => &head[*start..*end],
+                Chunk::Data(data) => *data,
+            })
+            .filter(|buf| !buf.is_empty())
+            .collect();
+
+        // what is buffered goes first, the flush after is for the TLS stream
+        self.flush().await?;
+        self.write_all_vectored(&bufs).await?;
+        self.flush().await
+    }
+
+    /// Write an out-of-band push message, `frame` must be an array.
+    ///
+    /// RESP3 connections get the push type `>`, so clients can tell it from a
+    /// reply. RESP2 connections get a plain array, like pub/sub messages.
+    pub async fn write_push(&mut self, frame: &Frame) -> io::Result<()> {
+        match frame {
+            Frame::Array(val) if self.resp3 => {
+                self.write_all(b">").await?;
+                self.write_decimal(val.len() as i64).await?;
+                for entry in &**val {
+                    self.write_value(entry).await?;
+                }
+                self.flush().await
+            }
+            _ => self.write_frame(frame).await,
+        }
+    }
+
     /// Write a frame literal to the stream
     async fn write_value(&mut self, frame: &Frame) -> io::Result<()> {
         match frame {
@@ -314,16 +541,31 @@ impl Connection {
                 self.write_all(val).await?;
                 self.write_all(b"\r\n").await?;
             }
-            // Encoding an `Array` from within a value cannot be done using a
-            // recursive strategy. In general, async fns do not support
-            // recursion.
-            // We do support at most 3 nested array response in up-level for now.
-            Frame::Array(_val) => unreachable!(),
+            Frame::Array(_) => self.write_array(frame).await?,
         }
 
         Ok(())
     }
 
+    /// Write an array to the stream. Async fns can't recurse, so the array
+    /// and the arrays nested in it, to any depth, are encoded first and the
+    /// encoding written after.
+    async fn write_array(&mut self, frame: &Frame) -> io::Result<()> {
+        let mut head = Vec::with_capacity(1024);
+        let mut start = 0;
+        let mut chunks = vec![];
+        encode_chunks(frame, &mut head, &mut start, &mut chunks);
+        chunks.push(Chunk::Head(start, head.len()));
+
+        for chunk in &chunks {
+            match chunk {
+                Chunk::Head(start, end) => self.write_all(&head[*start..*end]).await?,
+                Chunk::Data(data) => self.write_all(data).await?,
+            }
+        }
+        Ok(())
+    }
+
     /// Write a decimal frame to the stream
     async fn write_decimal(&mut self, val: i64) -> io::Result<()> {
         use std::io::Write;
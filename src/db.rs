@@ -2,6 +2,7 @@ use tokio::sync::{broadcast, Notify};
 use tokio::time::{self, Duration, Instant};
 
 use crate::config::LOGGER;
+use crate::utils::glob_match;
 use bytes::Bytes;
 use slog::debug;
 use std::collections::{BTreeMap, HashMap};
@@ -68,6 +69,10 @@ struct State {
 
     pub_sub: HashMap<String, broadcast::Sender<Bytes>>,
 
+    /// Pattern subscriptions, each message is sent with the channel it was
+    /// published on.
+    pattern_sub: HashMap<String, broadcast::Sender<(String, Bytes)>>,
+
     /// Tracks key TTLs.
     ///
     /// A `BTreeMap` is used to maintain expirations sorted by when they expire.
@@ -138,6 +143,7 @@ impl Db {
                 entries: HashMap::new(),
                 scripts: HashMap::new(),
                 pub_sub: HashMap::new(),
+                pattern_sub: HashMap::new(),
                 expirations: BTreeMap::new(),
                 next_id: 0,
                 shutdown: false,
@@ -284,21 +290,55 @@ impl Db {
         }
     }
 
+    /// Returns a `Receiver` for the requested glob pattern, it receives the
+    /// messages published on every channel matching the pattern.
+    #[allow(clippy::significant_drop_in_scrutinee)]
+    pub(crate) fn psubscribe(&self, pattern: String) -> broadcast::Receiver<(String, Bytes)> {
+        use std::collections::hash_map::Entry;
+
+        let mut state = self.shared.state.lock().unwrap();
+
+        // Same capacity and lagging behavior as the channel subscriptions.
+        match state.pattern_sub.entry(pattern) {
+            Entry::Occupied(e) => e.get().subscribe(),
+            Entry::Vacant(e) => {
+                let (tx, rx) = broadcast::channel(1024);
+                e.insert(tx);
+                rx
+            }
+        }
+    }
+
     /// Publish a message to the channel. Returns the number of subscribers
-    /// listening on the channel.
+    /// listening on the channel, counting the pattern subscribers.
     pub(crate) fn publish(&self, key: &str, value: Bytes) -> usize {
-        let state = self.shared.state.lock().unwrap();
+        let mut state = self.shared.state.lock().unwrap();
 
-        state
+        let num_subscribers = state
             .pub_sub
             .get(key)
             // On a successful message send on the broadcast channel, the number
             // of subscribers is returned. An error indicates there are no
             // receivers, in which case, `0` should be returned.
-            .map(|tx| tx.send(value).unwrap_or(0))
+            .map(|tx| tx.send(value.clone()).unwrap_or(0))
             // If there is no entry for the channel key, then there are no
             // subscribers. In this case, return `0`.
-            .unwrap_or(0)
+            .unwrap_or(0);
+
+        // Every pattern is matched against the channel, the patterns left
+        // without subscribers are dropped on the way.
+        let mut num_pattern_subscribers = 0;
+        state.pattern_sub.retain(|pattern, tx| {
+            if tx.receiver_count() == 0 {
+                return false;
+            }
+            if glob_match(pattern.as_bytes(), key.as_bytes(), false) {
+                num_pattern_subscribers += tx.send((key.to_owned(), value.clone())).unwrap_or(0);
+            }
+            true
+        });
+
+        num_subscribers + num_pattern_subscribers
     }
 
     /// Signals the purge background task to shut down. This is called by the
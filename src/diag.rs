@@ -0,0 +1,236 @@
+//! Diagnostic snapshot of the instance, for TIDIS.SNAPSHOT.
+//!
+//! The snapshot is a json document of the state of the member in memory:
+//! its connections and the commands in flight on them, the resident memory,
+//! the sizes of the caches and registries, the depths of the queues of the
+//! background workers and the latest error replies. It does not touch the
+//! storage and does not wait for a lock held by a connection, so it can be
+//! captured from a wedged instance before restarting it.
+
+use std::collections::{HashMap, VecDeque};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex as StdMutex};
+
+use tokio::sync::Mutex;
+use tokio::time::Instant;
+
+use crate::access::tracked_keys;
+use crate::async_gc_worker_number_or_default;
+use crate::auth::auth_cache_len;
+use crate::blocking::blocked_keys;
+use crate::client::Client;
+use crate::cmd::running_scripts;
+use crate::cmdlog::cmdlog_queue_depth;
+use crate::memlimit::resident_memory;
+use crate::metrics::GC_TASK_QUEUE_COUNTER;
+use crate::priority::low_priority_permits_available;
+use crate::tikv::{TIKV_TNX_CONN_POOL, TIKV_TRANSACTIONS};
+use crate::triggers::trigger_queue_depth;
+use crate::utils::{json_quote, now_timestamp_in_millis};
+
+/// Error replies kept for the snapshot, the oldest are dropped
+const RECENT_ERRORS_LEN: usize = 128;
+
+struct InFlightCommand {
+    client_id: u64,
+    cmd: String,
+    started_at: Instant,
+}
+
+/// An error replied to a client
+struct ErrorEntry {
+    /// Unix time of the reply, in milliseconds
+    timestamp: u64,
+    peer_addr: String,
+    error: String,
+}
+
+lazy_static! {
+    static ref IN_FLIGHT: StdMutex<HashMap<u64, InFlightCommand>> = StdMutex::new(HashMap::new());
+    static ref RECENT_ERRORS: StdMutex<VecDeque<ErrorEntry>> = StdMutex::new(VecDeque::new());
+}
+
+static NEXT_IN_FLIGHT_ID: AtomicU64 = AtomicU64::new(0);
+
+/// Registration of a command in flight, removed when dropped
+pub struct InFlight {
+    id: u64,
+}
+
+impl InFlight {
+    pub fn start(client_id: u64, cmd: &str) -> InFlight {
+        let id = NEXT_IN_FLIGHT_ID.fetch_add(1, Ordering::Relaxed);
+        IN_FLIGHT.lock().unwrap().insert(
+            id,
+            InFlightCommand {
+                client_id,
+                cmd: cmd.to_owned(),
+                started_at: Instant::now(),
+            },
+        );
+        InFlight { id }
+    }
+}
+
+impl Drop for InFlight {
+    fn drop(&mut self) {
+        IN_FLIGHT.lock().unwrap().remove(&self.id);
+    }
+}
+
+/// Keep the error replied to the client at `peer_addr`
+pub fn record_error(peer_addr: &str, error: &str) {
+    let mut errors = RECENT_ERRORS.lock().unwrap();
+    if errors.len() >= RECENT_ERRORS_LEN {
+        errors.pop_back();
+    }
+    errors.push_front(ErrorEntry {
+        timestamp: now_timestamp_in_millis(),
+        peer_addr: peer_addr.to_owned(),
+        error: error.to_owned(),
+    });
+}
+
+fn json_object(fields: &[(&str, String)]) -> String {
+    let fields: Vec<String> = fields
+        .iter()
+        .map(|(name, value)| format!("{}:{}", json_quote(name), value))
+        .collect();
+    format!("{{{}}}", fields.join(","))
+}
+
+fn json_array(values: Vec<String>) -> String {
+    format!("[{}]", values.join(","))
+}
+
+fn json_option(value: Option<usize>) -> String {
+    value.map_or("null".to_owned(), |value| value.to_string())
+}
+
+/// The connections of `clients`, those locked by their connection at the
+/// time are only counted
+async fn connections_json(clients: &Arc<Mutex<HashMap<u64, Arc<Mutex<Client>>>>>) -> String {
+    let clients: Vec<Arc<Mutex<Client>>> = clients.lock().await.values().cloned().collect();
+    let mut connections = vec![];
+    let mut locked = 0;
+    for client in clients {
+        let client = match client.try_lock() {
+            Ok(client) => client,
+            Err(_) => {
+                locked += 1;
+                continue;
+            }
+        };
+        connections.push(json_object(&[
+            ("id", client.id().to_string()),
+            ("addr", json_quote(client.peer_addr())),
+            ("laddr", json_quote(client.local_addr())),
+            ("name", json_quote(client.name())),
+            ("user", json_quote(client.user())),
+            ("age", client.age().to_string()),
+            ("idle", client.idle().to_string()),
+            ("cmd", json_quote(client.cmd())),
+        ]));
+    }
+    json_object(&[
+        ("count", (connections.len() + locked).to_string()),
+        ("locked", locked.to_string()),
+        ("list", json_array(connections)),
+    ])
+}
+
+/// The commands in flight, the longest running first
+fn in_flight_json() -> String {
+    let mut commands: Vec<(u64, String, u128)> = IN_FLIGHT
+        .lock()
+        .unwrap()
+        .values()
+        .map(|c| {
+            (
+                c.client_id,
+                c.cmd.clone(),
+                c.started_at.elapsed().as_millis(),
+            )
+        })
+        .collect();
+    commands.sort_by(|a, b| b.2.cmp(&a.2));
+    json_array(
+        commands
+            .into_iter()
+            .map(|(client_id, cmd, duration_ms)| {
+                json_object(&[
+                    ("client_id", client_id.to_string()),
+                    ("cmd", json_quote(&cmd)),
+                    ("duration_ms", duration_ms.to_string()),
+                ])
+            })
+            .collect(),
+    )
+}
+
+fn caches_json() -> String {
+    // the pool is only held while a client is taken or put back
+    let txn_client_pool = TIKV_TNX_CONN_POOL.try_lock().ok().map(|pool| pool.len());
+    json_object(&[
+        ("auth_cache", auth_cache_len().to_string()),
+        ("access_tracking_keys", tracked_keys().to_string()),
+        ("blocked_keys", blocked_keys().to_string()),
+        (
+            "open_transactions",
+            TIKV_TRANSACTIONS.read().unwrap().len().to_string(),
+        ),
+        ("txn_client_pool", json_option(txn_client_pool)),
+        ("running_scripts", running_scripts().to_string()),
+    ])
+}
+
+fn queues_json() -> String {
+    let gc_workers = (0..async_gc_worker_number_or_default())
+        .map(|id| {
+            GC_TASK_QUEUE_COUNTER
+                .with_label_values(&[&id.to_string()])
+                .get()
+                .to_string()
+        })
+        .collect();
+    json_object(&[
+        ("cmdlog", cmdlog_queue_depth().to_string()),
+        ("triggers", trigger_queue_depth().to_string()),
+        ("gc_workers", json_array(gc_workers)),
+        (
+            "low_priority_permits_available",
+            json_option(low_priority_permits_available()),
+        ),
+    ])
+}
+
+fn recent_errors_json() -> String {
+    json_array(
+        RECENT_ERRORS
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|entry| {
+                json_object(&[
+                    ("timestamp", entry.timestamp.to_string()),
+                    ("addr", json_quote(&entry.peer_addr)),
+                    ("error", json_quote(&entry.error)),
+                ])
+            })
+            .collect(),
+    )
+}
+
+/// The snapshot of the member serving `clients`, as a json document
+pub async fn snapshot(clients: &Arc<Mutex<HashMap<u64, Arc<Mutex<Client>>>>>) -> String {
+    json_object(&[
+        ("timestamp", now_timestamp_in_millis().to_string()),
+        ("pid", std::process::id().to_string()),
+        ("resident_memory", resident_memory().to_string()),
+        ("connections", connections_json(clients).await),
+        ("in_flight", in_flight_json()),
+        ("caches", caches_json()),
+        ("queues", queues_json()),
+        ("recent_errors", recent_errors_json()),
+    ])
+}
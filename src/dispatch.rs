@@ -0,0 +1,140 @@
+//! The command path of the data APIs which are not RESP connections, the
+//! memcached listener, the HTTP data API and gRPC.
+//!
+//! Their commands run with the checks and hooks of the commands of RESP
+//! clients: they are counted and timed in the same metrics and latency SLO,
+//! the low priority ones are shed over the soft memory limit and wait for a
+//! permit, and a write which changed the keyspace increments the replication
+//! offset, fires the triggers and keyspace notifications and is shipped to
+//! the command log, once committed. The listeners authenticate their clients
+//! themselves, and have no READONLY state, their requests may write.
+
+use std::sync::{Arc, Mutex as StdMutex};
+
+use bytes::Bytes;
+use tokio::sync::Mutex;
+use tokio::time::Instant;
+
+use crate::cmd::Command;
+use crate::cmdlog::{cmdlog_enabled, ship, write_applied, Reply, WriteEvent};
+use crate::memlimit::over_soft_limit;
+use crate::metrics::{
+    MEMORY_SHED_COMMAND_COUNTER, REQUEST_CMD_COUNTER, REQUEST_CMD_ERROR_COUNTER,
+    REQUEST_CMD_FINISH_COUNTER, REQUEST_CMD_HANDLE_TIME, REQUEST_COUNTER,
+};
+use crate::notify::notify_enabled;
+use crate::priority::{acquire_permit, Priority};
+use crate::replication::incr_repl_offset;
+use crate::server::duration_to_sec;
+use crate::slo::{check_latency_slo, slo_class};
+use crate::tikv::backend::Transaction;
+use crate::tikv::client::PRIORITY;
+use crate::tikv::errors::{AsyncResult, RTError, REDIS_MEMORY_SOFT_LIMIT_ERR};
+use crate::triggers::{fire_write, triggers_enabled};
+use crate::utils::resp_err;
+use crate::Frame;
+
+/// Replicate a write which changed the keyspace, fire its triggers and
+/// notifications and ship it to the command log. `event` is none if there
+/// is no consumer of the writes.
+pub async fn write_committed(event: Option<WriteEvent>) {
+    incr_repl_offset();
+    if let Some(event) = event {
+        fire_write(&event).await;
+        if cmdlog_enabled() {
+            ship(event).await;
+        }
+    }
+}
+
+/// The event of a write for the consumers of the writes, none if there are
+/// none
+pub fn write_event(cmd: &Command, args: &[Bytes]) -> Option<WriteEvent> {
+    if cmdlog_enabled() || triggers_enabled() || notify_enabled() {
+        Some(WriteEvent::new(cmd.get_name(), cmd.written_type(), args))
+    } else {
+        None
+    }
+}
+
+/// The writes applied in a transaction, passed to `write_committed` once it
+/// is committed
+#[derive(Clone, Default)]
+pub struct Writes {
+    applied: Arc<StdMutex<Vec<Option<WriteEvent>>>>,
+}
+
+impl Writes {
+    /// Forget the writes, those of a transaction which is retried
+    pub fn clear(&self) {
+        self.applied.lock().unwrap().clear();
+    }
+
+    pub async fn committed(self) {
+        let applied = std::mem::take(&mut *self.applied.lock().unwrap());
+        for event in applied {
+            write_committed(event).await;
+        }
+    }
+}
+
+/// Run the command `name` and its hooks
+pub async fn execute(name: &str, args: Vec<Bytes>) -> AsyncResult<Frame> {
+    let writes = Writes::default();
+    let frame = execute_in_txn(name, args, None, &writes).await?;
+    writes.committed().await;
+    Ok(frame)
+}
+
+/// Run the command `name` in the transaction if there is one, its writes
+/// are added to `writes` to be committed
+pub async fn execute_in_txn(
+    name: &str,
+    args: Vec<Bytes>,
+    txn: Option<Arc<Mutex<Transaction>>>,
+    writes: &Writes,
+) -> AsyncResult<Frame> {
+    REQUEST_COUNTER.inc();
+    REQUEST_CMD_COUNTER.with_label_values(&[name]).inc();
+    let cmd = Command::from_argv(name, &args).map_err(|e| RTError::Owned(e.to_string()))?;
+    let cmd_name = cmd.get_name().to_owned();
+    let class = slo_class(&cmd);
+    if cmd.priority() == Priority::Low && over_soft_limit() {
+        MEMORY_SHED_COMMAND_COUNTER
+            .with_label_values(&[&cmd_name])
+            .inc();
+        return Ok(resp_err(REDIS_MEMORY_SOFT_LIMIT_ERR));
+    }
+
+    let start_at = Instant::now();
+    let _permit = acquire_permit(cmd.priority()).await;
+    let event = if cmd.is_write() {
+        Some(write_event(&cmd, &args))
+    } else {
+        None
+    };
+    let frame = match PRIORITY.scope(cmd.priority(), cmd.execute(txn)).await {
+        Ok(frame) => frame,
+        Err(e) => {
+            REQUEST_CMD_ERROR_COUNTER
+                .with_label_values(&[&cmd_name])
+                .inc();
+            return Err(e);
+        }
+    };
+    if let Some(event) = event {
+        if write_applied(&cmd_name, Reply::of(&frame)) {
+            writes.applied.lock().unwrap().push(event);
+        }
+    }
+
+    let duration = Instant::now() - start_at;
+    REQUEST_CMD_HANDLE_TIME
+        .with_label_values(&[&cmd_name])
+        .observe(duration_to_sec(duration));
+    check_latency_slo(&cmd_name, class, duration);
+    REQUEST_CMD_FINISH_COUNTER
+        .with_label_values(&[&cmd_name])
+        .inc();
+    Ok(frame)
+}
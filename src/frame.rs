@@ -1,6 +1,7 @@
 //! Provides a type representing a Redis protocol frame as well as utilities for
 //! parsing frames from a byte array.
 
+use crate::log_redact_values;
 use crate::tikv::errors::RTError;
 use bytes::{Buf, Bytes};
 use std::convert::TryInto;
@@ -10,7 +11,7 @@ use std::num::TryFromIntError;
 use std::string::FromUtf8Error;
 
 /// A frame in the Redis protocol.
-#[derive(Clone, Debug)]
+#[derive(Clone)]
 pub enum Frame {
     Simple(String),
     ErrorOwned(String),
@@ -208,6 +209,25 @@ impl fmt::Display for Frame {
     }
 }
 
+/// Bulk values are redacted when `log_redact` is configured, so replies
+/// logged by commands never leak the user data.
+impl fmt::Debug for Frame {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Frame::Simple(s) => fmt.debug_tuple("Simple").field(s).finish(),
+            Frame::ErrorOwned(s) => fmt.debug_tuple("ErrorOwned").field(s).finish(),
+            Frame::ErrorString(s) => fmt.debug_tuple("ErrorString").field(s).finish(),
+            Frame::Integer(n) => fmt.debug_tuple("Integer").field(n).finish(),
+            Frame::Bulk(b) if log_redact_values() => {
+                write!(fmt, "Bulk(<redacted {} bytes>)", b.len())
+            }
+            Frame::Bulk(b) => fmt.debug_tuple("Bulk").field(b).finish(),
+            Frame::Null => write!(fmt, "Null"),
+            Frame::Array(parts) => fmt.debug_tuple("Array").field(parts).finish(),
+        }
+    }
+}
+
 impl From<RTError> for Frame {
     fn from(e: RTError) -> Self {
         match e {
@@ -0,0 +1,363 @@
+//! HTTP data API.
+//!
+//! With `http_api_port` set, clients that can't hold a RESP connection, like
+//! serverless functions or edge workers, read and write keys with plain HTTP
+//! requests run as the commands of Redis:
+//!
+//! - `GET|PUT|DELETE /v1/kv/{key}` a string, the value is the body, PUT
+//!   takes `?ttl=seconds`
+//! - `GET /v1/hash/{key}` the fields as a JSON object, `GET|PUT|DELETE
+//!   /v1/hash/{key}/{field}` a field
+//! - `GET /v1/list/{key}?start=0&stop=-1` the elements as a JSON array,
+//!   `POST /v1/list/{key}` pushes the body at the tail, or the head with
+//!   `?side=left`, and `DELETE /v1/list/{key}` pops from the same sides
+//!
+//! Keys and fields are percent-decoded. With a password every request
+//! authenticates like AUTH, with `Authorization: Basic` or `Bearer` and the
+//! password of the default user, and the ip filter applies too. The commands
+//! run through `crate::dispatch`, with the metrics, shedding and write hooks
+//! of the commands of RESP clients.
+
+use std::convert::Infallible;
+use std::net::SocketAddr;
+
+use bytes::Bytes;
+use hyper::body::HttpBody;
+use hyper::header::{AUTHORIZATION, CONTENT_LENGTH, CONTENT_TYPE, WWW_AUTHENTICATE};
+use hyper::server::conn::AddrStream;
+use hyper::service::{make_service_fn, service_fn};
+use hyper::{Body, Method, Request, Response, Server, StatusCode};
+use slog::{error, info};
+
+use crate::auth::authenticate;
+use crate::config::LOGGER;
+use crate::dispatch::execute;
+use crate::ipfilter::is_ip_allowed;
+use crate::tikv::errors::{AsyncResult, RTError};
+use crate::utils::json_quote;
+use crate::{
+    config_http_api_listen_or_default, config_http_api_port_or_default, is_auth_enabled, Frame,
+};
+
+/// Largest body, the largest bulk string of Redis
+const MAX_BODY_LEN: usize = 512 * 1024 * 1024;
+
+/// Serve the data API on `http_api_listen:http_api_port`, returns at once if
+/// the port is not set.
+pub async fn run_http_gateway() {
+    let port = config_http_api_port_or_default();
+    if port == 0 {
+        return;
+    }
+    let addr = format!("{}:{}", config_http_api_listen_or_default(), port);
+    let addr: SocketAddr = match addr.parse() {
+        Ok(addr) => addr,
+        Err(e) => {
+            error!(LOGGER, "[HTTP API] invalid listen address {}, {}", addr, e);
+            return;
+        }
+    };
+    let builder = match Server::try_bind(&addr) {
+        Ok(builder) => builder,
+        Err(e) => {
+            error!(LOGGER, "[HTTP API] failed to listen on {}, {}", addr, e);
+            return;
+        }
+    };
+    info!(LOGGER, "[HTTP API] Server Listen on: {}", addr);
+
+    let make_svc = make_service_fn(|conn: &AddrStream| {
+        let peer = conn.remote_addr();
+        async move { Ok::<_, Infallible>(service_fn(move |req| serve_req(req, peer))) }
+    });
+    if let Err(e) = builder.serve(make_svc).await {
+        error!(LOGGER, "[HTTP API] Got Error: {}", e);
+    }
+}
+
+fn text(status: StatusCode, body: impl Into<Body>) -> Response<Body> {
+    Response::builder()
+        .status(status)
+        .header(CONTENT_TYPE, "text/plain")
+        .body(body.into())
+        .unwrap()
+}
+
+fn json(body: String) -> Response<Body> {
+    Response::builder()
+        .status(StatusCode::OK)
+        .header(CONTENT_TYPE, "application/json")
+        .body(Body::from(body))
+        .unwrap()
+}
+
+fn no_content() -> Response<Body> {
+    Response::builder()
+        .status(StatusCode::NO_CONTENT)
+        .body(Body::empty())
+        .unwrap()
+}
+
+fn not_found() -> Response<Body> {
+    text(StatusCode::NOT_FOUND, "not found")
+}
+
+fn bad_request(reason: &'static str) -> Response<Body> {
+    text(StatusCode::BAD_REQUEST, reason)
+}
+
+async fn serve_req(req: Request<Body>, peer: SocketAddr) -> Result<Response<Body>, Infallible> {
+    if !is_ip_allowed(&peer.ip()) {
+        return Ok(text(StatusCode::FORBIDDEN, "forbidden"));
+    }
+    if is_auth_enabled() && !authorized(&req).await {
+        let mut response = text(StatusCode::UNAUTHORIZED, "unauthorized");
+        response
+            .headers_mut()
+            .insert(WWW_AUTHENTICATE, "Basic realm=\"tidis\"".parse().unwrap());
+        return Ok(response);
+    }
+    Ok(route(req)
+        .await
+        .unwrap_or_else(|e| text(StatusCode::INTERNAL_SERVER_ERROR, e.to_string())))
+}
+
+/// The credentials of the request, checked like the ones of AUTH
+async fn authorized(req: &Request<Body>) -> bool {
+    match req.headers().get(AUTHORIZATION).map(|h| h.to_str()) {
+        Some(Ok(header)) => authorized_header(header).await,
+        _ => false,
+    }
+}
+
+/// The credentials of an `Authorization` header, `Basic` or `Bearer`
+pub(crate) async fn authorized_header(header: &str) -> bool {
+    let (user, password) = if let Some(token) = header.strip_prefix("Bearer ") {
+        ("default".to_owned(), token.trim().to_owned())
+    } else if let Some(basic) = header.strip_prefix("Basic ") {
+        let credentials = match base64_decode(basic.trim()).map(String::from_utf8) {
+            Some(Ok(credentials)) => credentials,
+            _ => return false,
+        };
+        match credentials.split_once(':') {
+            Some((user, password)) => (user.to_owned(), password.to_owned()),
+            None => return false,
+        }
+    } else {
+        return false;
+    };
+    authenticate(&user, &password).await
+}
+
+/// Standard base64, for the credentials of `Basic`
+fn base64_decode(s: &str) -> Option<Vec<u8>> {
+    let mut out = Vec::with_capacity(s.len() / 4 * 3);
+    let mut acc = 0u32;
+    let mut bits = 0;
+    for c in s.bytes().take_while(|&c| c != b'=') {
+        let v = match c {
+            b'A'..=b'Z' => c - b'A',
+            b'a'..=b'z' => c - b'a' + 26,
+            b'0'..=b'9' => c - b'0' + 52,
+            b'+' => 62,
+            b'/' => 63,
+            _ => return None,
+        };
+        acc = (acc << 6) | v as u32;
+        bits += 6;
+        if bits >= 8 {
+            bits -= 8;
+            out.push((acc >> bits) as u8);
+            acc &= (1 << bits) - 1;
+        }
+    }
+    Some(out)
+}
+
+fn hex_value(c: u8) -> Option<u8> {
+    (c as char).to_digit(16).map(|v| v as u8)
+}
+
+fn percent_decode(s: &str) -> Option<Vec<u8>> {
+    let bytes = s.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%' {
+            let high = hex_value(*bytes.get(i + 1)?)?;
+            let low = hex_value(*bytes.get(i + 2)?)?;
+            out.push(high << 4 | low);
+            i += 3;
+        } else {
+            out.push(bytes[i]);
+            i += 1;
+        }
+    }
+    Some(out)
+}
+
+/// The value of the query parameter `name`
+fn query_param(query: Option<&str>, name: &str) -> Option<String> {
+    query?.split('&').find_map(|pair| {
+        let (k, v) = pair.split_once('=').unwrap_or((pair, ""));
+        if k != name {
+            return None;
+        }
+        percent_decode(v).map(|v| String::from_utf8_lossy(&v).to_string())
+    })
+}
+
+async fn read_body(body: &mut Body) -> AsyncResult<Option<Bytes>> {
+    let mut buf = vec![];
+    while let Some(chunk) = body.data().await {
+        let chunk = chunk.map_err(|e| RTError::Owned(e.to_string()))?;
+        if buf.len() + chunk.len() > MAX_BODY_LEN {
+            return Ok(None);
+        }
+        buf.extend_from_slice(&chunk);
+    }
+    Ok(Some(buf.into()))
+}
+
+/// The response of the reply of a command, strings as they are, integers
+/// and arrays as JSON
+fn response_of(frame: Frame) -> Response<Body> {
+    match frame {
+        Frame::Bulk(value) => Response::builder()
+            .status(StatusCode::OK)
+            .header(CONTENT_TYPE, "application/octet-stream")
+            .body(Body::from(value))
+            .unwrap(),
+        Frame::Null => not_found(),
+        Frame::Simple(_) => no_content(),
+        Frame::Integer(v) => json(v.to_string()),
+        Frame::Array(items) => {
+            let items: Vec<String> = items.iter().map(json_value).collect();
+            json(format!("[{}]", items.join(",")))
+        }
+        Frame::ErrorOwned(e) => error_response(&e),
+        Frame::ErrorString(e) => error_response(e),
+    }
+}
+
+fn json_value(frame: &Frame) -> String {
+    match frame {
+        Frame::Bulk(value) => json_quote(&String::from_utf8_lossy(value)),
+        Frame::Integer(v) => v.to_string(),
+        _ => "null".to_owned(),
+    }
+}
+
+fn error_response(e: &str) -> Response<Body> {
+    if e.starts_with("WRONGTYPE") {
+        text(StatusCode::CONFLICT, e.to_owned())
+    } else {
+        text(StatusCode::INTERNAL_SERVER_ERROR, e.to_owned())
+    }
+}
+
+async fn route(mut req: Request<Body>) -> AsyncResult<Response<Body>> {
+    let path = req.uri().path().to_owned();
+    let query = req.uri().query().map(str::to_owned);
+    let query = query.as_deref();
+    let parts: Vec<&str> = path.trim_start_matches('/').split('/').collect();
+    if parts.len() < 3 || parts.len() > 4 || parts[0] != "v1" || parts[2].is_empty() {
+        return Ok(not_found());
+    }
+    let (key, field) = match (
+        percent_decode(parts[2]),
+        parts.get(3).map(|f| percent_decode(f)),
+    ) {
+        (Some(key), None) => (Bytes::from(key), None),
+        (Some(key), Some(Some(field))) => (Bytes::from(key), Some(Bytes::from(field))),
+        _ => return Ok(bad_request("invalid percent-encoding")),
+    };
+
+    let too_large = req
+        .headers()
+        .get(CONTENT_LENGTH)
+        .and_then(|len| len.to_str().ok()?.parse::<usize>().ok())
+        .map_or(false, |len| len > MAX_BODY_LEN);
+    if too_large {
+        return Ok(text(StatusCode::PAYLOAD_TOO_LARGE, "body too large"));
+    }
+    let method = req.method().clone();
+    let body = if method == Method::PUT || method == Method::POST {
+        match read_body(req.body_mut()).await? {
+            Some(body) => body,
+            None => return Ok(text(StatusCode::PAYLOAD_TOO_LARGE, "body too large")),
+        }
+    } else {
+        Bytes::new()
+    };
+
+    let frame = match (parts[1], field, method) {
+        ("kv", None, Method::GET) => execute("get", vec![key]).await?,
+        ("kv", None, Method::PUT) => {
+            let mut args = vec![key, body];
+            if let Some(ttl) = query_param(query, "ttl") {
+                match ttl.parse::<u64>() {
+                    Ok(ttl) if ttl > 0 => {
+                        args.push(Bytes::from("EX"));
+                        args.push(Bytes::from(ttl.to_string()));
+                    }
+                    _ => return Ok(bad_request("ttl must be a positive integer")),
+                }
+            }
+            execute("set", args).await?
+        }
+        ("kv", None, Method::DELETE) => match execute("del", vec![key]).await? {
+            Frame::Integer(0) => Frame::Null,
+            Frame::Integer(_) => Frame::Simple("OK".to_owned()),
+            frame => frame,
+        },
+        ("hash", None, Method::GET) => match execute("hgetall", vec![key]).await? {
+            Frame::Array(fvs) => {
+                let fields: Vec<String> = fvs
+                    .chunks(2)
+                    .map(|fv| format!("{}:{}", json_value(&fv[0]), json_value(&fv[1])))
+                    .collect();
+                return Ok(json(format!("{{{}}}", fields.join(","))));
+            }
+            frame => frame,
+        },
+        ("hash", Some(field), Method::GET) => execute("hget", vec![key, field]).await?,
+        ("hash", Some(field), Method::PUT) => {
+            match execute("hset", vec![key, field, body]).await? {
+                Frame::Integer(_) => Frame::Simple("OK".to_owned()),
+                frame => frame,
+            }
+        }
+        ("hash", Some(field), Method::DELETE) => match execute("hdel", vec![key, field]).await? {
+            Frame::Integer(0) => Frame::Null,
+            Frame::Integer(_) => Frame::Simple("OK".to_owned()),
+            frame => frame,
+        },
+        ("list", None, Method::GET) => {
+            let start = query_param(query, "start").unwrap_or_else(|| "0".to_owned());
+            let stop = query_param(query, "stop").unwrap_or_else(|| "-1".to_owned());
+            if start.parse::<i64>().is_err() || stop.parse::<i64>().is_err() {
+                return Ok(bad_request("start and stop must be integers"));
+            }
+            execute("lrange", vec![key, Bytes::from(start), Bytes::from(stop)]).await?
+        }
+        ("list", None, method) if method == Method::POST || method == Method::DELETE => {
+            let left = match query_param(query, "side").as_deref() {
+                Some("left") => true,
+                Some("right") | None => false,
+                _ => return Ok(bad_request("side must be left or right")),
+            };
+            match (method == Method::POST, left) {
+                (true, true) => execute("lpush", vec![key, body]).await?,
+                (true, false) => execute("rpush", vec![key, body]).await?,
+                (false, true) => execute("lpop", vec![key]).await?,
+                (false, false) => execute("rpop", vec![key]).await?,
+            }
+        }
+        ("kv", None, _) | ("hash", _, _) | ("list", None, _) => {
+            return Ok(text(StatusCode::METHOD_NOT_ALLOWED, "method not allowed"))
+        }
+        _ => return Ok(not_found()),
+    };
+    Ok(response_of(frame))
+}
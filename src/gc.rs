@@ -2,21 +2,25 @@ use futures::{FutureExt, StreamExt};
 use slog::{debug, error, info};
 use std::collections::HashSet;
 use std::convert::TryInto;
-use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex as StdMutex};
+use tikv_client::BoundRange;
 use tokio::sync::mpsc::{self, Receiver, Sender};
 use tokio::sync::Mutex;
-use tokio::time::{self, Duration, MissedTickBehavior};
+use tokio::time::{self, Duration, Instant, MissedTickBehavior};
 
 use crc::{Crc, CRC_16_XMODEM};
 
-use crate::cluster::Cluster;
+use crate::cluster::{key_slot, Cluster};
 use crate::config::LOGGER;
 use crate::metrics::GC_TASK_QUEUE_COUNTER;
 use crate::tikv::encoding::{DataType, KeyDecoder};
 use crate::tikv::errors::{AsyncResult, RTError};
 use crate::tikv::{get_txn_client, KEY_ENCODER};
 use crate::{
-    async_deletion_enabled_or_default, async_gc_interval_or_default,
+    async_deletion_enabled_or_default, async_gc_batch_size_or_default,
+    async_gc_concurrency_or_default, async_gc_deletes_per_sec_or_default,
+    async_gc_interval_or_default, async_gc_target_latency_ms_or_default,
     async_gc_worker_queue_size_or_default,
 };
 
@@ -47,6 +51,200 @@ impl GcTask {
     }
 }
 
+/// Fewest keys per second the pacing slows the gc down to
+const GC_MIN_DELETES_PER_SEC: f64 = 100.0;
+
+/// The gc settings changed at runtime with CONFIG SET
+pub const GC_SETTINGS_NAMES: [&str; 4] = [
+    "async_gc_batch_size",
+    "async_gc_concurrency",
+    "async_gc_deletes_per_sec",
+    "async_gc_target_latency_ms",
+];
+
+/// Settings of the gc workers, initialized from the config
+struct GcSettings {
+    batch_size: AtomicUsize,
+    concurrency: AtomicUsize,
+    deletes_per_sec: AtomicU64,
+    target_latency_ms: AtomicU64,
+}
+
+/// Pace of the deletions of all gc workers. The rate is halved while the
+/// moving average of the batch latency is over the target, as TiKV is then
+/// busy serving the foreground too, and raised by a quarter per batch below
+/// it up to `async_gc_deletes_per_sec`.
+#[derive(Debug)]
+struct GcPacer {
+    /// moving average of the batch latency in ms
+    latency_ms: f64,
+    /// keys deleted per second, None for no limit
+    rate: Option<f64>,
+    /// when the next batch may start at the current rate
+    next_batch_at: Instant,
+}
+
+lazy_static! {
+    static ref GC_SETTINGS: GcSettings = GcSettings {
+        batch_size: AtomicUsize::new(async_gc_batch_size_or_default()),
+        concurrency: AtomicUsize::new(async_gc_concurrency_or_default()),
+        deletes_per_sec: AtomicU64::new(async_gc_deletes_per_sec_or_default()),
+        target_latency_ms: AtomicU64::new(async_gc_target_latency_ms_or_default()),
+    };
+    static ref GC_PACER: StdMutex<GcPacer> = StdMutex::new(GcPacer {
+        latency_ms: 0.0,
+        rate: budget(),
+        next_batch_at: Instant::now(),
+    });
+}
+
+/// Batches being deleted by the gc workers
+static GC_ACTIVE_BATCHES: AtomicUsize = AtomicUsize::new(0);
+
+/// The configured keys per second, None for no limit
+fn budget() -> Option<f64> {
+    match GC_SETTINGS.deletes_per_sec.load(Ordering::Relaxed) {
+        0 => None,
+        budget => Some(budget as f64),
+    }
+}
+
+impl GcPacer {
+    /// Adjust the rate to the latency of a batch deleting `deleted` keys
+    fn observe(&mut self, elapsed: Duration, deleted: usize) {
+        let ms = elapsed.as_secs_f64() * 1000.0;
+        self.latency_ms = if self.latency_ms == 0.0 {
+            ms
+        } else {
+            self.latency_ms * 0.8 + ms * 0.2
+        };
+        let target = GC_SETTINGS.target_latency_ms.load(Ordering::Relaxed) as f64;
+        if target == 0.0 {
+            self.rate = budget();
+            return;
+        }
+        let throughput = deleted as f64 / elapsed.as_secs_f64().max(0.001);
+        if self.latency_ms > target {
+            // slow down from the throughput of the batch when not limited yet
+            let rate = self.rate.unwrap_or(throughput);
+            self.rate = Some((rate / 2.0).max(GC_MIN_DELETES_PER_SEC));
+        } else if let Some(rate) = self.rate {
+            let raised = rate * 1.25;
+            self.rate = match budget() {
+                Some(budget) => Some(raised.min(budget)),
+                // far above the throughput of a batch the rate is not a limit
+                None if raised > throughput * 4.0 => None,
+                None => Some(raised),
+            };
+        }
+    }
+
+    /// Reserve the time to delete `deleted` keys at the current rate, returns
+    /// how long to wait before the next batch
+    fn reserve(&mut self, deleted: usize) -> Duration {
+        let rate = match self.rate {
+            Some(rate) => rate,
+            None => return Duration::ZERO,
+        };
+        let now = Instant::now();
+        self.next_batch_at =
+            self.next_batch_at.max(now) + Duration::from_secs_f64(deleted as f64 / rate);
+        self.next_batch_at - now
+    }
+}
+
+/// A batch allowed by `async_gc_concurrency`, released when dropped
+struct BatchSlot;
+
+impl BatchSlot {
+    async fn acquire() -> BatchSlot {
+        loop {
+            let limit = GC_SETTINGS.concurrency.load(Ordering::Relaxed);
+            let active = GC_ACTIVE_BATCHES.fetch_add(1, Ordering::SeqCst);
+            if limit == 0 || active < limit {
+                return BatchSlot;
+            }
+            GC_ACTIVE_BATCHES.fetch_sub(1, Ordering::SeqCst);
+            time::sleep(Duration::from_millis(10)).await;
+        }
+    }
+}
+
+impl Drop for BatchSlot {
+    fn drop(&mut self) {
+        GC_ACTIVE_BATCHES.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+/// The value of a runtime gc setting, None for an unknown name
+pub fn gc_setting(name: &str) -> Option<u64> {
+    let value = match name {
+        "async_gc_batch_size" => GC_SETTINGS.batch_size.load(Ordering::Relaxed) as u64,
+        "async_gc_concurrency" => GC_SETTINGS.concurrency.load(Ordering::Relaxed) as u64,
+        "async_gc_deletes_per_sec" => GC_SETTINGS.deletes_per_sec.load(Ordering::Relaxed),
+        "async_gc_target_latency_ms" => GC_SETTINGS.target_latency_ms.load(Ordering::Relaxed),
+        _ => return None,
+    };
+    Some(value)
+}
+
+/// Change a runtime gc setting, false for an unknown name. The pacing starts
+/// again from the budget.
+pub fn set_gc_setting(name: &str, value: u64) -> bool {
+    match name {
+        "async_gc_batch_size" => GC_SETTINGS
+            .batch_size
+            .store(value.max(1) as usize, Ordering::Relaxed),
+        "async_gc_concurrency" => GC_SETTINGS
+            .concurrency
+            .store(value as usize, Ordering::Relaxed),
+        "async_gc_deletes_per_sec" => GC_SETTINGS.deletes_per_sec.store(value, Ordering::Relaxed),
+        "async_gc_target_latency_ms" => GC_SETTINGS
+            .target_latency_ms
+            .store(value, Ordering::Relaxed),
+        _ => return false,
+    }
+    GC_PACER.lock().unwrap().rate = budget();
+    true
+}
+
+/// Settings and pace of the gc reported in `INFO gc`
+pub fn encode_gc_info() -> String {
+    let mut info = "# GC\r\n".to_owned();
+    for name in GC_SETTINGS_NAMES {
+        info.push_str(&format!("{}:{}\r\n", name, gc_setting(name).unwrap_or(0)));
+    }
+    let pacer = GC_PACER.lock().unwrap();
+    info.push_str(&format!(
+        "gc_active_batches:{}\r\ngc_batch_latency_ms:{:.2}\r\ngc_paced_deletes_per_sec:{}\r\n",
+        GC_ACTIVE_BATCHES.load(Ordering::Relaxed),
+        pacer.latency_ms,
+        pacer.rate.map_or(0, |rate| rate as u64)
+    ));
+    info
+}
+
+/// Delete at most `limit` keys of the range in one transaction, returns the
+/// number of keys deleted
+async fn delete_batch(range: BoundRange, limit: usize) -> AsyncResult<usize> {
+    let mut txn_client = get_txn_client()?;
+    txn_client
+        .exec_in_txn(None, |txn_rc| {
+            async move {
+                let mut txn = txn_rc.lock().await;
+                // the old version is not readable, it is not a write of the key
+                txn.without_stamps();
+                let keys: Vec<_> = txn.scan_keys(range, limit as u32).await?.collect();
+                for k in keys.iter().cloned() {
+                    txn.delete(k).await?;
+                }
+                Ok(keys.len())
+            }
+            .boxed()
+        })
+        .await
+}
+
 #[derive(Debug, Clone)]
 pub struct GcMaster {
     workers: Vec<GcWorker>,
@@ -124,39 +322,13 @@ impl GcMaster {
                 let (user_key, version) = KeyDecoder::decode_key_gc_userkey_version(kv.0);
 
                 let (slot_range_left, slot_range_right) = self.topo.myself_owned_slots();
-                // crc16 to user key with hashtag `{}` support
-                // check if user key contains valid hashtag
-                let mut left_tag_idx = usize::MAX;
-                let mut right_tag_idx = usize::MAX;
-                for (idx, byte) in user_key.iter().enumerate() {
-                    if byte == &b'{' {
-                        left_tag_idx = idx;
-                    }
-                    if left_tag_idx != usize::MAX && byte == &b'}' {
-                        right_tag_idx = idx;
-                        break;
-                    }
-                }
-                let user_key_hash: usize =
-                    if right_tag_idx != usize::MAX && right_tag_idx - left_tag_idx > 1 {
-                        // we have a valid hashtag, do crc16 to string to the content in hashtag
-                        (CRC16.checksum(&user_key[left_tag_idx + 1..right_tag_idx]) & 0x3FFF).into()
-                    } else {
-                        (CRC16.checksum(&user_key) & 0x3FFF).into()
-                    };
+                let user_key_hash = key_slot(&user_key);
 
                 // skip if user key is not owned by myself
                 if user_key_hash < slot_range_left || user_key_hash > slot_range_right {
                     continue;
                 }
-                let key_type = match kv.1[0] {
-                    0 => DataType::String,
-                    1 => DataType::Hash,
-                    2 => DataType::List,
-                    3 => DataType::Set,
-                    4 => DataType::Zset,
-                    _ => DataType::Null,
-                };
+                let key_type = DataType::from_type_byte(kv.1[0]).unwrap_or(DataType::Null);
                 let task = GcTask::new(key_type, user_key, version);
                 if let Err(e) = self.dispatch_task(task).await {
                     error!(LOGGER, "[GC] dispatch task failed: {:?}", e);
@@ -218,115 +390,88 @@ impl GcWorker {
         Ok(())
     }
 
+    /// The ranges of keys of the task, deleted in batches before its gc
+    /// version key
+    fn task_ranges(task: &GcTask) -> Vec<BoundRange> {
+        let user_key = String::from_utf8_lossy(&task.user_key);
+        let version = task.version;
+        match task.key_type {
+            DataType::String | DataType::Json => {
+                panic!("string not support async deletion");
+            }
+            DataType::Hash => vec![
+                KEY_ENCODER.encode_txnkv_sub_meta_key_range(&user_key, version),
+                KEY_ENCODER.encode_txnkv_hash_data_key_range(&user_key, version),
+            ],
+            DataType::List => {
+                vec![KEY_ENCODER.encode_txnkv_list_data_key_range(&user_key, version)]
+            }
+            DataType::Set => vec![
+                KEY_ENCODER.encode_txnkv_sub_meta_key_range(&user_key, version),
+                KEY_ENCODER.encode_txnkv_set_data_key_range(&user_key, version),
+            ],
+            DataType::Zset => vec![
+                KEY_ENCODER.encode_txnkv_sub_meta_key_range(&user_key, version),
+                KEY_ENCODER.encode_txnkv_zset_score_key_range(&user_key, version),
+                KEY_ENCODER.encode_txnkv_zset_data_key_range(&user_key, version),
+            ],
+            // entries, groups and pending entries of this version
+            DataType::Stream => vec![KEY_ENCODER.encode_txnkv_stream_key_range(&user_key, version)],
+            DataType::Null => {
+                panic!("unknown data type to do async deletion");
+            }
+        }
+    }
+
+    /// Delete the keys of the task, `async_gc_batch_size` keys per transaction
+    /// paced by `GcPacer`. The keys of an old version are not read anymore, so
+    /// a task interrupted between two batches is resumed from its gc version
+    /// key by the next scan.
     pub async fn handle_task(&self, task: GcTask) -> AsyncResult<()> {
         let mut txn_client = get_txn_client()?;
+        debug!(
+            LOGGER,
+            "[GC] async delete {} key {} with version {}",
+            task.key_type,
+            String::from_utf8_lossy(&task.user_key),
+            task.version
+        );
+
+        for range in Self::task_ranges(&task) {
+            loop {
+                let batch_size = GC_SETTINGS.batch_size.load(Ordering::Relaxed);
+                let slot = BatchSlot::acquire().await;
+                let start_at = Instant::now();
+                let deleted = delete_batch(range.clone(), batch_size).await?;
+                let elapsed = start_at.elapsed();
+                drop(slot);
+                if deleted == 0 {
+                    break;
+                }
+                let wait = {
+                    let mut pacer = GC_PACER.lock().unwrap();
+                    pacer.observe(elapsed, deleted);
+                    pacer.reserve(deleted)
+                };
+                if !wait.is_zero() {
+                    time::sleep(wait).await;
+                }
+                if deleted < batch_size {
+                    break;
+                }
+            }
+        }
 
+        // delete gc version key
         txn_client
             .exec_in_txn(None, |txn_rc| {
                 let task = task.clone();
                 async move {
                     let mut txn = txn_rc.lock().await;
                     let user_key = String::from_utf8_lossy(&task.user_key);
-                    let version = task.version;
-                    match task.key_type {
-                        DataType::String => {
-                            panic!("string not support async deletion");
-                        }
-                        DataType::Hash => {
-                            debug!(
-                                LOGGER,
-                                "[GC] async delete hash key {} with version {}", user_key, version
-                            );
-                            // delete all sub meta key of this key and version
-                            let bound_range =
-                                KEY_ENCODER.encode_txnkv_sub_meta_key_range(&user_key, version);
-                            let iter = txn.scan_keys(bound_range, u32::MAX).await?;
-                            for k in iter {
-                                txn.delete(k).await?;
-                            }
-
-                            // delete all data key of this key and version
-                            let bound_range =
-                                KEY_ENCODER.encode_txnkv_hash_data_key_range(&user_key, version);
-                            let mut iter = txn.scan_keys_stream(bound_range, u32::MAX).await?;
-                            while let Some(k) = iter.next().await {
-                                txn.delete(k).await?;
-                            }
-                        }
-                        DataType::List => {
-                            debug!(
-                                LOGGER,
-                                "[GC] async delete list key {} with version {}", user_key, version
-                            );
-                            // delete all data key of this key and version
-                            let bound_range =
-                                KEY_ENCODER.encode_txnkv_list_data_key_range(&user_key, version);
-                            let mut iter = txn.scan_keys_stream(bound_range, u32::MAX).await?;
-                            while let Some(k) = iter.next().await {
-                                txn.delete(k).await?;
-                            }
-                        }
-                        DataType::Set => {
-                            debug!(
-                                LOGGER,
-                                "[GC] async delete set key {} with version {}", user_key, version
-                            );
-                            // delete all sub meta key of this key and version
-                            let bound_range =
-                                KEY_ENCODER.encode_txnkv_sub_meta_key_range(&user_key, version);
-                            let iter = txn.scan_keys(bound_range, u32::MAX).await?;
-                            for k in iter {
-                                txn.delete(k).await?;
-                            }
-                            // delete all data key of this key and version
-                            let bound_range =
-                                KEY_ENCODER.encode_txnkv_set_data_key_range(&user_key, version);
-                            let mut iter = txn.scan_keys_stream(bound_range, u32::MAX).await?;
-                            while let Some(k) = iter.next().await {
-                                txn.delete(k).await?;
-                            }
-                        }
-                        DataType::Zset => {
-                            debug!(
-                                LOGGER,
-                                "[GC] async delete zset key {} with version {}", user_key, version
-                            );
-                            // delete all sub meta key of this key and version
-                            let bound_range =
-                                KEY_ENCODER.encode_txnkv_sub_meta_key_range(&user_key, version);
-                            let iter = txn.scan_keys(bound_range, u32::MAX).await?;
-                            for k in iter {
-                                txn.delete(k).await?;
-                            }
-
-                            // delete all score key of this key and version
-                            let bound_range = KEY_ENCODER.encode_txnkv_zset_score_key_range(
-                                &String::from_utf8_lossy(&task.user_key),
-                                task.version,
-                            );
-                            let mut iter = txn.scan_keys_stream(bound_range, u32::MAX).await?;
-                            while let Some(k) = iter.next().await {
-                                txn.delete(k).await?;
-                            }
-
-                            // delete all data key of this key and version
-                            let bound_range =
-                                KEY_ENCODER.encode_txnkv_zset_data_key_range(&user_key, version);
-                            let mut iter = txn.scan_keys_stream(bound_range, u32::MAX).await?;
-                            while let Some(k) = iter.next().await {
-                                txn.delete(k).await?;
-                            }
-                        }
-                        DataType::Null => {
-                            panic!("unknown data type to do async deletion");
-                        }
-                    }
-
-                    // delete gc version key
                     let gc_version_key =
-                        KEY_ENCODER.encode_txnkv_gc_version_key(&user_key, version);
+                        KEY_ENCODER.encode_txnkv_gc_version_key(&user_key, task.version);
                     txn.delete(gc_version_key).await?;
-
                     Ok(())
                 }
                 .boxed()
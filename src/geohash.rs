@@ -0,0 +1,239 @@
+//! Geohash scores of the GEO commands.
+//!
+//! Like Redis, a position is stored as the score of a sorted set member, the
+//! 52 bits geohash interleaving 26 bits of latitude and 26 bits of longitude
+//! with the latitude in the even bits. A cell of `step` bits per axis covers
+//! a contiguous range of scores, so a search scans the cell of the center
+//! and its neighbors at a step large enough to cover the searched area, and
+//! filters the members by distance.
+
+pub const GEO_STEP_MAX: u8 = 26;
+
+pub const GEO_LAT_MIN: f64 = -85.05112878;
+pub const GEO_LAT_MAX: f64 = 85.05112878;
+pub const GEO_LONG_MIN: f64 = -180.0;
+pub const GEO_LONG_MAX: f64 = 180.0;
+
+const EARTH_RADIUS_IN_METERS: f64 = 6372797.560856;
+const MERCATOR_MAX: f64 = 20037726.37;
+
+/// The area searched by GEOSEARCH, in meters
+#[derive(Debug, Clone, Copy)]
+pub enum Shape {
+    Radius(f64),
+    Box(f64, f64),
+}
+
+/// The meters in a distance unit of the GEO commands
+pub fn unit_to_meters(unit: &str) -> Option<f64> {
+    match unit.to_lowercase().as_str() {
+        "m" => Some(1.0),
+        "km" => Some(1000.0),
+        "ft" => Some(0.3048),
+        "mi" => Some(1609.34),
+        _ => None,
+    }
+}
+
+pub fn valid_position(lon: f64, lat: f64) -> bool {
+    (GEO_LONG_MIN..=GEO_LONG_MAX).contains(&lon) && (GEO_LAT_MIN..=GEO_LAT_MAX).contains(&lat)
+}
+
+fn spread(v: u32) -> u64 {
+    let mut x = v as u64;
+    x = (x | (x << 16)) & 0x0000_FFFF_0000_FFFF;
+    x = (x | (x << 8)) & 0x00FF_00FF_00FF_00FF;
+    x = (x | (x << 4)) & 0x0F0F_0F0F_0F0F_0F0F;
+    x = (x | (x << 2)) & 0x3333_3333_3333_3333;
+    (x | (x << 1)) & 0x5555_5555_5555_5555
+}
+
+fn squash(x: u64) -> u32 {
+    let mut x = x & 0x5555_5555_5555_5555;
+    x = (x | (x >> 1)) & 0x3333_3333_3333_3333;
+    x = (x | (x >> 2)) & 0x0F0F_0F0F_0F0F_0F0F;
+    x = (x | (x >> 4)) & 0x00FF_00FF_00FF_00FF;
+    x = (x | (x >> 8)) & 0x0000_FFFF_0000_FFFF;
+    ((x | (x >> 16)) & 0x0000_0000_FFFF_FFFF) as u32
+}
+
+/// The cell of `step` bits per axis holding the position
+pub fn encode(lon: f64, lat: f64, step: u8) -> u64 {
+    let cells = (1u64 << step) as f64;
+    let lat_offset = (lat - GEO_LAT_MIN) / (GEO_LAT_MAX - GEO_LAT_MIN);
+    let lon_offset = (lon - GEO_LONG_MIN) / (GEO_LONG_MAX - GEO_LONG_MIN);
+    // the maximum of each axis belongs to the last cell
+    let max = (1u64 << step) - 1;
+    let lat_idx = ((lat_offset * cells) as u64).min(max) as u32;
+    let lon_idx = ((lon_offset * cells) as u64).min(max) as u32;
+    spread(lat_idx) | (spread(lon_idx) << 1)
+}
+
+/// The (min longitude, min latitude, max longitude, max latitude) of a cell
+fn cell_area(bits: u64, step: u8) -> (f64, f64, f64, f64) {
+    let cells = (1u64 << step) as f64;
+    let lat_idx = squash(bits) as f64;
+    let lon_idx = squash(bits >> 1) as f64;
+    let lat_scale = GEO_LAT_MAX - GEO_LAT_MIN;
+    let lon_scale = GEO_LONG_MAX - GEO_LONG_MIN;
+    (
+        GEO_LONG_MIN + lon_idx / cells * lon_scale,
+        GEO_LAT_MIN + lat_idx / cells * lat_scale,
+        GEO_LONG_MIN + (lon_idx + 1.0) / cells * lon_scale,
+        GEO_LAT_MIN + (lat_idx + 1.0) / cells * lat_scale,
+    )
+}
+
+/// The (longitude, latitude) of the center of the cell of a 52 bits score
+pub fn decode(score: u64) -> (f64, f64) {
+    let (min_lon, min_lat, max_lon, max_lat) = cell_area(score, GEO_STEP_MAX);
+    (
+        ((min_lon + max_lon) / 2.0).clamp(GEO_LONG_MIN, GEO_LONG_MAX),
+        ((min_lat + max_lat) / 2.0).clamp(GEO_LAT_MIN, GEO_LAT_MAX),
+    )
+}
+
+/// Great circle distance in meters
+pub fn distance(lon1: f64, lat1: f64, lon2: f64, lat2: f64) -> f64 {
+    let (lat1r, lat2r) = (lat1.to_radians(), lat2.to_radians());
+    let u = ((lat2r - lat1r) / 2.0).sin();
+    let v = ((lon2.to_radians() - lon1.to_radians()) / 2.0).sin();
+    2.0 * EARTH_RADIUS_IN_METERS * (u * u + lat1r.cos() * lat2r.cos() * v * v).sqrt().asin()
+}
+
+/// The distance from the center if the position is in the shape
+pub fn distance_in_shape(center: (f64, f64), shape: Shape, lon: f64, lat: f64) -> Option<f64> {
+    match shape {
+        Shape::Radius(radius) => {
+            let d = distance(center.0, center.1, lon, lat);
+            if d <= radius {
+                Some(d)
+            } else {
+                None
+            }
+        }
+        Shape::Box(width, height) => {
+            let lat_distance =
+                EARTH_RADIUS_IN_METERS * (lat.to_radians() - center.1.to_radians()).abs();
+            if lat_distance > height / 2.0 {
+                return None;
+            }
+            if distance(center.0, lat, lon, lat) > width / 2.0 {
+                return None;
+            }
+            Some(distance(center.0, center.1, lon, lat))
+        }
+    }
+}
+
+/// The largest step whose cells are at least as large as `range` meters at
+/// latitude `lat`
+fn estimate_step(mut range: f64, lat: f64) -> u8 {
+    if range == 0.0 {
+        return GEO_STEP_MAX;
+    }
+    let mut step: i32 = 1;
+    while range < MERCATOR_MAX {
+        range *= 2.0;
+        step += 1;
+    }
+    // the cells are narrower near the poles
+    step -= 2;
+    if !(-66.0..=66.0).contains(&lat) {
+        step -= 1;
+        if !(-80.0..=80.0).contains(&lat) {
+            step -= 1;
+        }
+    }
+    step.clamp(1, GEO_STEP_MAX as i32) as u8
+}
+
+/// The (min longitude, min latitude, max longitude, max latitude) of the
+/// shape around the center
+fn bounding_box(center: (f64, f64), shape: Shape) -> (f64, f64, f64, f64) {
+    let (half_width, half_height) = match shape {
+        Shape::Radius(radius) => (radius, radius),
+        Shape::Box(width, height) => (width / 2.0, height / 2.0),
+    };
+    let (lon, lat) = center;
+    let lat_delta = (half_height / EARTH_RADIUS_IN_METERS).to_degrees();
+    let lon_delta_top =
+        (half_width / EARTH_RADIUS_IN_METERS / (lat + lat_delta).to_radians().cos()).to_degrees();
+    let lon_delta_bottom =
+        (half_width / EARTH_RADIUS_IN_METERS / (lat - lat_delta).to_radians().cos()).to_degrees();
+    let lon_delta = lon_delta_top.max(lon_delta_bottom);
+    (
+        lon - lon_delta,
+        lat - lat_delta,
+        lon + lon_delta,
+        lat + lat_delta,
+    )
+}
+
+/// The cell `(dlon, dlat)` cells away from `bits`, the longitude wraps around
+/// and None past the latitude limits
+fn neighbor(bits: u64, step: u8, dlon: i64, dlat: i64) -> Option<u64> {
+    let cells = 1i64 << step;
+    let lat_idx = squash(bits) as i64 + dlat;
+    if lat_idx < 0 || lat_idx >= cells {
+        return None;
+    }
+    let lon_idx = (squash(bits >> 1) as i64 + dlon).rem_euclid(cells);
+    Some(spread(lat_idx as u32) | (spread(lon_idx as u32) << 1))
+}
+
+/// The ranges of scores `[start, end)` to scan for the members in the shape,
+/// the cell of the center and its 8 neighbors
+pub fn search_ranges(center: (f64, f64), shape: Shape) -> Vec<(u64, u64)> {
+    let range = match shape {
+        Shape::Radius(radius) => radius,
+        Shape::Box(width, height) => ((width / 2.0).powi(2) + (height / 2.0).powi(2)).sqrt(),
+    };
+    let (min_lon, min_lat, max_lon, max_lat) = bounding_box(center, shape);
+    let mut step = estimate_step(range, center.1);
+    let mut bits = encode(center.0, center.1, step);
+
+    // the estimated cells may be too small for the box at the border of the
+    // center cell, a larger step covers it
+    if step > 1 {
+        let north = neighbor(bits, step, 0, 1).map(|n| cell_area(n, step).3);
+        let south = neighbor(bits, step, 0, -1).map(|s| cell_area(s, step).1);
+        let (_, _, east, _) = cell_area(neighbor(bits, step, 1, 0).unwrap(), step);
+        let (west, _, _, _) = cell_area(neighbor(bits, step, -1, 0).unwrap(), step);
+        let (center_min_lon, _, center_max_lon, _) = cell_area(bits, step);
+        // a wrapped neighbor is on the other side of the antimeridian
+        let east = if east < center_max_lon {
+            east + 360.0
+        } else {
+            east
+        };
+        let west = if west > center_min_lon {
+            west - 360.0
+        } else {
+            west
+        };
+        if north.map_or(false, |n| n < max_lat)
+            || south.map_or(false, |s| s > min_lat)
+            || east < max_lon
+            || west > min_lon
+        {
+            step -= 1;
+            bits = encode(center.0, center.1, step);
+        }
+    }
+
+    let shift = 2 * (GEO_STEP_MAX - step) as u32;
+    let mut ranges = vec![];
+    for dlat in -1..=1 {
+        for dlon in -1..=1 {
+            if let Some(cell) = neighbor(bits, step, dlon, dlat) {
+                let range = (cell << shift, (cell + 1) << shift);
+                if !ranges.contains(&range) {
+                    ranges.push(range);
+                }
+            }
+        }
+    }
+    ranges.sort_unstable();
+    ranges
+}
@@ -0,0 +1,426 @@
+//! gRPC data API, built with the `grpc` feature.
+//!
+//! With `grpc_port` set, internal services that prefer protobuf contracts to
+//! RESP call the service of `proto/tidis.proto`. Each call runs as the
+//! commands of Redis through `crate::dispatch`, like the HTTP data API, and
+//! `Execute` runs the other commands on the data of the keys, the admin ones
+//! are refused. With a password
+//! every call authenticates like AUTH, with the `authorization` metadata set
+//! to `Basic` or `Bearer` credentials, and the ip filter applies too.
+
+use std::net::SocketAddr;
+
+use bytes::Bytes;
+use slog::{error, info};
+use tokio::sync::mpsc;
+use tokio_stream::wrappers::ReceiverStream;
+use tonic::transport::Server;
+use tonic::{Request, Response, Status};
+
+use crate::config::LOGGER;
+use crate::dispatch::execute;
+use crate::gateway::authorized_header;
+use crate::ipfilter::is_ip_allowed;
+use crate::{config_grpc_listen_or_default, config_grpc_port_or_default, is_auth_enabled, Frame};
+
+pub mod pb {
+    tonic::include_proto!("tidis.v1");
+}
+
+use pb::tidis_server::{Tidis, TidisServer};
+use pb::{
+    reply, CountResponse, Empty, ExecuteRequest, FieldRequest, FieldValue, FieldsResponse,
+    HSetRequest, KeyRequest, KeyResponse, KeysRequest, PushRequest, RangeRequest, Reply,
+    ReplyArray, ScanRequest, SetRequest, ValueResponse, ValuesResponse,
+};
+
+/// Keys buffered by a scan ahead of the client
+const SCAN_BUFFER: usize = 128;
+
+/// The commands `Execute` runs, those on the data of the keys. The admin
+/// commands, like CONFIG, DEBUG, MIGRATE or RESTORE, the scripts and the
+/// commands of a connection, like MULTI or SUBSCRIBE, are refused.
+const EXECUTE_COMMANDS: &[&str] = &[
+    // keys
+    "del",
+    "exists",
+    "type",
+    "ttl",
+    "pttl",
+    "expire",
+    "expireat",
+    "pexpire",
+    "pexpireat",
+    "persist",
+    "copy",
+    "sort",
+    "scan",
+    "object",
+    "randomkey",
+    // strings
+    "get",
+    "set",
+    "setnx",
+    "setex",
+    "mget",
+    "mset",
+    "incr",
+    "decr",
+    "incrby",
+    "decrby",
+    "strlen",
+    "getrange",
+    "substr",
+    "getdel",
+    "getex",
+    "append",
+    "setrange",
+    "setbit",
+    "getbit",
+    "bitcount",
+    "bitpos",
+    "bitop",
+    "bitfield",
+    "cl.throttle",
+    "lock",
+    "extend",
+    "unlock",
+    // hashes
+    "hset",
+    "hmset",
+    "hsetnx",
+    "hget",
+    "hmget",
+    "hlen",
+    "hgetall",
+    "hdel",
+    "hkeys",
+    "hvals",
+    "hincrby",
+    "hexists",
+    "hstrlen",
+    "hindex",
+    // lists
+    "lpush",
+    "rpush",
+    "lpop",
+    "rpop",
+    "lrange",
+    "llen",
+    "lindex",
+    "lpos",
+    "lmpop",
+    "lset",
+    "ltrim",
+    "lrem",
+    "linsert",
+    // sets
+    "sadd",
+    "scard",
+    "sismember",
+    "smismember",
+    "smembers",
+    "srandmember",
+    "spop",
+    "sdrain",
+    "srem",
+    "sinter",
+    "sintercard",
+    // sorted sets
+    "zadd",
+    "zcard",
+    "zscore",
+    "zmscore",
+    "zrem",
+    "zremrangebyscore",
+    "zremrangebyrank",
+    "zrange",
+    "zrevrange",
+    "zrangebyscore",
+    "zrevrangebyscore",
+    "zcount",
+    "zpopmin",
+    "zpopmax",
+    "zmpop",
+    "zrangestore",
+    "zrank",
+    "zincrby",
+    "zinter",
+    "zunion",
+    "zdiff",
+    "zinterstore",
+    "zunionstore",
+    "zdiffstore",
+    "zintercard",
+    // geo, streams and json
+    "geoadd",
+    "geopos",
+    "geodist",
+    "geosearch",
+    "xadd",
+    "xlen",
+    "xrange",
+    "xrevrange",
+    "xread",
+    "xgroup",
+    "xreadgroup",
+    "xack",
+    "xpending",
+    "xclaim",
+    "xscan",
+    "json.set",
+    "json.get",
+    "json.del",
+];
+
+type GrpcResult<T> = Result<Response<T>, Status>;
+
+/// Serve the gRPC API on `grpc_listen:grpc_port`, returns at once if the
+/// port is not set.
+pub async fn run_grpc_server() {
+    let port = config_grpc_port_or_default();
+    if port == 0 {
+        return;
+    }
+    let addr = format!("{}:{}", config_grpc_listen_or_default(), port);
+    let addr: SocketAddr = match addr.parse() {
+        Ok(addr) => addr,
+        Err(e) => {
+            error!(LOGGER, "[gRPC] invalid listen address {}, {}", addr, e);
+            return;
+        }
+    };
+    info!(LOGGER, "[gRPC] Server Listen on: {}", addr);
+
+    if let Err(e) = Server::builder()
+        .add_service(TidisServer::new(TidisService))
+        .serve(addr)
+        .await
+    {
+        error!(LOGGER, "[gRPC] Got Error: {}", e);
+    }
+}
+
+/// The ip filter and the credentials of the call
+async fn check<T>(req: &Request<T>) -> Result<(), Status> {
+    if let Some(peer) = req.remote_addr() {
+        if !is_ip_allowed(&peer.ip()) {
+            return Err(Status::permission_denied("forbidden"));
+        }
+    }
+    if is_auth_enabled() {
+        let header = req
+            .metadata()
+            .get("authorization")
+            .and_then(|h| h.to_str().ok())
+            .map(|h| h.to_owned());
+        match header {
+            Some(header) if authorized_header(&header).await => {}
+            _ => return Err(Status::unauthenticated("unauthorized")),
+        }
+    }
+    Ok(())
+}
+
+fn status_of(e: &str) -> Status {
+    if e.starts_with("WRONGTYPE") {
+        Status::failed_precondition(e)
+    } else {
+        Status::internal(e)
+    }
+}
+
+/// Run the command, an error reply is the status of the call
+async fn call(name: &str, args: Vec<Bytes>) -> Result<Frame, Status> {
+    match execute(name, args).await {
+        Ok(Frame::ErrorOwned(e)) => Err(status_of(&e)),
+        Ok(Frame::ErrorString(e)) => Err(status_of(e)),
+        Ok(frame) => Ok(frame),
+        Err(e) => Err(Status::internal(e.to_string())),
+    }
+}
+
+fn value_of(frame: Frame) -> Option<Vec<u8>> {
+    match frame {
+        Frame::Bulk(value) => Some(value.to_vec()),
+        _ => None,
+    }
+}
+
+fn count_of(frame: Frame) -> CountResponse {
+    let count = match frame {
+        Frame::Integer(count) => count,
+        _ => 0,
+    };
+    CountResponse { count }
+}
+
+fn values_of(frame: Frame) -> Vec<Vec<u8>> {
+    match frame {
+        Frame::Array(items) => items.into_iter().filter_map(value_of).collect(),
+        _ => vec![],
+    }
+}
+
+fn reply_of(frame: Frame) -> Reply {
+    let value = match frame {
+        Frame::Simple(s) => reply::Value::Simple(s),
+        Frame::ErrorOwned(e) => reply::Value::Error(e),
+        Frame::ErrorString(e) => reply::Value::Error(e.to_owned()),
+        Frame::Integer(v) => reply::Value::Integer(v),
+        Frame::Bulk(value) => reply::Value::Bulk(value.to_vec()),
+        Frame::Null => reply::Value::Null(true),
+        Frame::Array(items) => reply::Value::Array(ReplyArray {
+            items: items.into_iter().map(reply_of).collect(),
+        }),
+    };
+    Reply { value: Some(value) }
+}
+
+pub struct TidisService;
+
+#[tonic::async_trait]
+impl Tidis for TidisService {
+    async fn get(&self, req: Request<KeyRequest>) -> GrpcResult<ValueResponse> {
+        check(&req).await?;
+        let req = req.into_inner();
+        let value = value_of(call("get", vec![req.key.into()]).await?);
+        Ok(Response::new(ValueResponse { value }))
+    }
+
+    async fn set(&self, req: Request<SetRequest>) -> GrpcResult<Empty> {
+        check(&req).await?;
+        let req = req.into_inner();
+        let mut args = vec![req.key.into(), req.value.into()];
+        if req.ttl > 0 {
+            args.push(Bytes::from("EX"));
+            args.push(Bytes::from(req.ttl.to_string()));
+        }
+        call("set", args).await?;
+        Ok(Response::new(Empty {}))
+    }
+
+    async fn del(&self, req: Request<KeysRequest>) -> GrpcResult<CountResponse> {
+        check(&req).await?;
+        let keys = req.into_inner().keys.into_iter().map(Bytes::from).collect();
+        Ok(Response::new(count_of(call("del", keys).await?)))
+    }
+
+    async fn h_get(&self, req: Request<FieldRequest>) -> GrpcResult<ValueResponse> {
+        check(&req).await?;
+        let req = req.into_inner();
+        let value = value_of(call("hget", vec![req.key.into(), req.field.into()]).await?);
+        Ok(Response::new(ValueResponse { value }))
+    }
+
+    async fn h_set(&self, req: Request<HSetRequest>) -> GrpcResult<CountResponse> {
+        check(&req).await?;
+        let req = req.into_inner();
+        let mut args = vec![req.key.into()];
+        for fv in req.fields {
+            args.push(fv.field.into());
+            args.push(fv.value.into());
+        }
+        Ok(Response::new(count_of(call("hset", args).await?)))
+    }
+
+    async fn h_get_all(&self, req: Request<KeyRequest>) -> GrpcResult<FieldsResponse> {
+        check(&req).await?;
+        let req = req.into_inner();
+        let fvs = values_of(call("hgetall", vec![req.key.into()]).await?);
+        let fields = fvs
+            .chunks(2)
+            .map(|fv| FieldValue {
+                field: fv[0].clone(),
+                value: fv[1].clone(),
+            })
+            .collect();
+        Ok(Response::new(FieldsResponse { fields }))
+    }
+
+    async fn l_range(&self, req: Request<RangeRequest>) -> GrpcResult<ValuesResponse> {
+        check(&req).await?;
+        let req = req.into_inner();
+        let args = vec![
+            req.key.into(),
+            Bytes::from(req.start.to_string()),
+            Bytes::from(req.stop.to_string()),
+        ];
+        let values = values_of(call("lrange", args).await?);
+        Ok(Response::new(ValuesResponse { values }))
+    }
+
+    async fn push(&self, req: Request<PushRequest>) -> GrpcResult<CountResponse> {
+        check(&req).await?;
+        let req = req.into_inner();
+        let name = if req.left { "lpush" } else { "rpush" };
+        let mut args = vec![req.key.into()];
+        args.extend(req.values.into_iter().map(Bytes::from));
+        Ok(Response::new(count_of(call(name, args).await?)))
+    }
+
+    type ScanStream = ReceiverStream<Result<KeyResponse, Status>>;
+
+    async fn scan(&self, req: Request<ScanRequest>) -> GrpcResult<Self::ScanStream> {
+        check(&req).await?;
+        let req = req.into_inner();
+        let pattern = if req.pattern.is_empty() {
+            Bytes::from("*")
+        } else {
+            Bytes::from(req.pattern)
+        };
+        let count = if req.count > 0 { req.count } else { 10 };
+
+        let (tx, rx) = mpsc::channel(SCAN_BUFFER);
+        tokio::spawn(async move {
+            let mut cursor = Bytes::from(req.cursor);
+            loop {
+                let args = vec![
+                    cursor.clone(),
+                    Bytes::from("COUNT"),
+                    Bytes::from(count.to_string()),
+                    Bytes::from("MATCH"),
+                    pattern.clone(),
+                ];
+                let mut reply = match call("scan", args).await {
+                    Ok(Frame::Array(reply)) if reply.len() == 2 => reply,
+                    Ok(_) => return,
+                    Err(status) => {
+                        let _ = tx.send(Err(status)).await;
+                        return;
+                    }
+                };
+                let keys = values_of(reply.pop().unwrap());
+                for key in keys {
+                    // the client went away
+                    if tx.send(Ok(KeyResponse { key })).await.is_err() {
+                        return;
+                    }
+                }
+                // scanned to the end with an empty cursor
+                match value_of(reply.pop().unwrap()) {
+                    Some(next) if !next.is_empty() => cursor = next.into(),
+                    _ => return,
+                }
+            }
+        });
+        Ok(Response::new(ReceiverStream::new(rx)))
+    }
+
+    async fn execute(&self, req: Request<ExecuteRequest>) -> GrpcResult<Reply> {
+        check(&req).await?;
+        let req = req.into_inner();
+        let name = req.name.to_lowercase();
+        if !EXECUTE_COMMANDS.contains(&name.as_str()) {
+            return Err(Status::permission_denied(format!(
+                "{} is not allowed by Execute",
+                name
+            )));
+        }
+        let args = req.args.into_iter().map(Bytes::from).collect();
+        let frame = execute(&name, args)
+            .await
+            .unwrap_or_else(|e| Frame::ErrorOwned(e.to_string()));
+        Ok(Response::new(reply_of(frame)))
+    }
+}
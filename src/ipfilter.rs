@@ -0,0 +1,181 @@
+//! Listener level ip allow and deny lists.
+//!
+//! Connections are checked right after accept, before any RESP frame is read,
+//! so it works as a first line of defense independent of AUTH. The lists from
+//! config can be extended by `ip_filter_file`, which is reloaded when modified.
+//! Each line of the file is `allow <cidr>` or `deny <cidr>`, `#` starts a comment.
+
+use std::fs;
+use std::net::IpAddr;
+use std::str::FromStr;
+use std::sync::RwLock;
+use std::time::{Duration, SystemTime};
+
+use slog::{info, warn};
+
+use crate::config::LOGGER;
+use crate::metrics::REJECTED_CONNECTION_COUNTER;
+use crate::{
+    config_ip_allow_list_or_default, config_ip_deny_list_or_default,
+    config_ip_filter_file_or_default,
+};
+
+const RELOAD_INTERVAL: u64 = 1000;
+
+#[derive(Debug, Clone)]
+pub struct IpNet {
+    addr: IpAddr,
+    prefix: u8,
+}
+
+impl IpNet {
+    pub fn contains(&self, ip: &IpAddr) -> bool {
+        match (self.addr, normalize(ip)) {
+            (IpAddr::V4(net), IpAddr::V4(ip)) => {
+                let mask = u32::MAX.checked_shl(32 - self.prefix as u32).unwrap_or(0);
+                u32::from(net) & mask == u32::from(ip) & mask
+            }
+            (IpAddr::V6(net), IpAddr::V6(ip)) => {
+                let mask = u128::MAX.checked_shl(128 - self.prefix as u32).unwrap_or(0);
+                u128::from(net) & mask == u128::from(ip) & mask
+            }
+            _ => false,
+        }
+    }
+}
+
+impl FromStr for IpNet {
+    type Err = String;
+
+    /// parse `10.0.0.0/8`, `fe80::/10` or a single address
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (addr, prefix) = match s.split_once('/') {
+            Some((addr, prefix)) => (addr, Some(prefix)),
+            None => (s, None),
+        };
+        let addr = normalize(
+            &addr
+                .parse::<IpAddr>()
+                .map_err(|e| format!("invalid cidr {}: {}", s, e))?,
+        );
+        let max_prefix = if addr.is_ipv4() { 32 } else { 128 };
+        let prefix = match prefix {
+            Some(p) => p
+                .parse::<u8>()
+                .ok()
+                .filter(|p| *p <= max_prefix)
+                .ok_or_else(|| format!("invalid cidr prefix {}", s))?,
+            None => max_prefix,
+        };
+        Ok(IpNet { addr, prefix })
+    }
+}
+
+/// treat ipv4 mapped ipv6 address as ipv4
+fn normalize(ip: &IpAddr) -> IpAddr {
+    match ip {
+        IpAddr::V6(v6) => match v6.to_ipv4_mapped() {
+            Some(v4) => IpAddr::V4(v4),
+            None => *ip,
+        },
+        _ => *ip,
+    }
+}
+
+#[derive(Debug, Default)]
+struct IpFilter {
+    allow: Vec<IpNet>,
+    deny: Vec<IpNet>,
+    file_modified: Option<SystemTime>,
+}
+
+lazy_static! {
+    static ref IP_FILTER: RwLock<IpFilter> = RwLock::new(IpFilter::default());
+}
+
+fn parse_list(list: &[String], nets: &mut Vec<IpNet>) {
+    for cidr in list {
+        match cidr.trim().parse::<IpNet>() {
+            Ok(net) => nets.push(net),
+            Err(e) => warn!(LOGGER, "ignore ip filter rule, {}", e),
+        }
+    }
+}
+
+fn load() -> IpFilter {
+    let mut filter = IpFilter::default();
+    parse_list(&config_ip_allow_list_or_default(), &mut filter.allow);
+    parse_list(&config_ip_deny_list_or_default(), &mut filter.deny);
+
+    let file = config_ip_filter_file_or_default();
+    if file.is_empty() {
+        return filter;
+    }
+    filter.file_modified = fs::metadata(&file).and_then(|m| m.modified()).ok();
+    match fs::read_to_string(&file) {
+        Ok(content) => {
+            for line in content.lines() {
+                let line = line.split('#').next().unwrap().trim();
+                if line.is_empty() {
+                    continue;
+                }
+                let (action, cidr) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+                let cidr = [cidr.to_owned()];
+                match action {
+                    "allow" => parse_list(&cidr, &mut filter.allow),
+                    "deny" => parse_list(&cidr, &mut filter.deny),
+                    _ => warn!(LOGGER, "ignore ip filter rule, {}", line),
+                }
+            }
+        }
+        Err(e) => warn!(LOGGER, "failed to read ip filter file {}: {}", file, e),
+    }
+    filter
+}
+
+/// Load the lists, must be called before listeners accept connections.
+pub fn init_ip_filter() {
+    let filter = load();
+    info!(
+        LOGGER,
+        "ip filter loaded, {} allow rules, {} deny rules",
+        filter.allow.len(),
+        filter.deny.len()
+    );
+    *IP_FILTER.write().unwrap() = filter;
+}
+
+/// Deny rules take precedence, if there is any allow rule the ip must match one of them.
+pub fn is_ip_allowed(ip: &IpAddr) -> bool {
+    let filter = IP_FILTER.read().unwrap();
+    if filter.deny.iter().any(|net| net.contains(ip)) {
+        REJECTED_CONNECTION_COUNTER
+            .with_label_values(&["ip_denied"])
+            .inc();
+        return false;
+    }
+    if !filter.allow.is_empty() && !filter.allow.iter().any(|net| net.contains(ip)) {
+        REJECTED_CONNECTION_COUNTER
+            .with_label_values(&["ip_not_allowed"])
+            .inc();
+        return false;
+    }
+    true
+}
+
+/// Reload the lists once the filter file changed.
+pub async fn run_ip_filter_reloader() {
+    let file = config_ip_filter_file_or_default();
+    if file.is_empty() {
+        return;
+    }
+    let mut interval = tokio::time::interval(Duration::from_millis(RELOAD_INTERVAL));
+    loop {
+        interval.tick().await;
+        let modified = fs::metadata(&file).and_then(|m| m.modified()).ok();
+        let loaded = IP_FILTER.read().unwrap().file_modified;
+        if modified != loaded {
+            init_ip_filter();
+        }
+    }
+}
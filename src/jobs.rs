@@ -0,0 +1,227 @@
+//! Scheduler of the recurring maintenance jobs of the namespace.
+//!
+//! Each job runs on its own interval on the leader of the cluster only, so
+//! the work is not done once per instance. The leader is the member holding
+//! the `tidis.jobs` lock of LOCK as a lease, renewed every
+//! `JOBS_LEASE_RENEW_MS`. Another member takes it over once it expired, so
+//! there is a single leader whatever the slots owned by the members, as long
+//! as their clocks are within the lease ttl of each other. A run is never
+//! overlapped by the next one, a slow run delays the next tick, and deletes
+//! at most `max_deletes` keys. Jobs are scheduled with `[[server.jobs]]`, the
+//! status of the last runs is reported in `INFO jobs`.
+
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::RwLock;
+use std::time::Instant;
+
+use slog::{error, info};
+use tokio::time::{self, Duration, MissedTickBehavior};
+
+use crate::cluster::Cluster;
+use crate::config::LOGGER;
+use crate::metrics::JOB_RUN_COUNTER;
+use crate::tikv::errors::AsyncResult;
+use crate::tikv::eviction::evict_over_quota;
+use crate::tikv::expiration::remove_expired_keys;
+use crate::tikv::idempotency::sweep_expired_tokens;
+use crate::tikv::lock::LockCommandCtx;
+use crate::tikv::stamp::sweep_expired_stamps;
+use crate::utils::now_timestamp_in_millis;
+use crate::{async_gc_interval_or_default, config_jobs_or_default, Frame};
+
+/// remove the expired idempotency tokens
+const JOB_SWEEP_IDEMPOTENCY: &str = "sweep_idempotency";
+/// evict the keys over quota and refresh the keyspace stats
+const JOB_EVICT: &str = "evict";
+/// remove the expired keys not accessed since they expired
+const JOB_EXPIRE: &str = "expire";
+/// remove the expired write stamps of WATCH
+const JOB_SWEEP_WATCH_STAMPS: &str = "sweep_watch_stamps";
+
+const JOBS: [&str; 4] = [
+    JOB_SWEEP_IDEMPOTENCY,
+    JOB_EVICT,
+    JOB_EXPIRE,
+    JOB_SWEEP_WATCH_STAMPS,
+];
+
+/// Keys deleted by a run at most, unless set by `max_deletes`
+const DEFAULT_MAX_DELETES: usize = 1000;
+
+/// The lock held by the leader
+const JOBS_LEASE: &str = "tidis.jobs";
+const JOBS_LEASE_TTL_MS: u64 = 10000;
+const JOBS_LEASE_RENEW_MS: u64 = 3000;
+
+/// Until when the lease is held by this member, in ms
+static LEASE_EXPIRE_AT: AtomicU64 = AtomicU64::new(0);
+
+#[derive(Debug, Clone)]
+struct JobStatus {
+    name: &'static str,
+    interval_ms: u64,
+    max_deletes: usize,
+    enabled: bool,
+    runs: u64,
+    failures: u64,
+    last_run_ms: u64,
+    last_duration_ms: u64,
+    last_error: String,
+}
+
+lazy_static! {
+    static ref JOB_STATUS: RwLock<Vec<JobStatus>> = RwLock::new(vec![]);
+}
+
+async fn run_job(name: &str, max_deletes: usize) -> AsyncResult<()> {
+    match name {
+        JOB_SWEEP_IDEMPOTENCY => sweep_expired_tokens(max_deletes).await.map(|_| ()),
+        JOB_EVICT => evict_over_quota(max_deletes).await.map(|_| ()),
+        JOB_EXPIRE => remove_expired_keys(max_deletes).await.map(|_| ()),
+        JOB_SWEEP_WATCH_STAMPS => sweep_expired_stamps(max_deletes).await.map(|_| ()),
+        _ => Ok(()),
+    }
+}
+
+fn update_status(idx: usize, f: impl FnOnce(&mut JobStatus)) {
+    if let Some(status) = JOB_STATUS.write().unwrap().get_mut(idx) {
+        f(status);
+    }
+}
+
+fn is_leader() -> bool {
+    LEASE_EXPIRE_AT.load(Ordering::Relaxed) > now_timestamp_in_millis()
+}
+
+/// Take or renew the lease of the leader as `owner`, a member which can not
+/// reach the backend keeps it until it expires, as the others can not take
+/// it over before.
+async fn hold_lease(owner: String) {
+    let mut interval = time::interval(Duration::from_millis(JOBS_LEASE_RENEW_MS));
+    interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+    loop {
+        interval.tick().await;
+        let now = now_timestamp_in_millis();
+        let was_leader = is_leader();
+        match LockCommandCtx::new(None)
+            .do_async_txnkv_lock(JOBS_LEASE, &owner, JOBS_LEASE_TTL_MS)
+            .await
+        {
+            Ok(Frame::Integer(_)) => {
+                LEASE_EXPIRE_AT.store(now + JOBS_LEASE_TTL_MS, Ordering::Relaxed);
+                if !was_leader {
+                    info!(LOGGER, "[JOBS] {} is the leader of the jobs", owner);
+                }
+            }
+            Ok(_) => LEASE_EXPIRE_AT.store(0, Ordering::Relaxed),
+            Err(e) => error!(
+                LOGGER,
+                "[JOBS] renew the lease of the leader failed: {:?}", e
+            ),
+        }
+    }
+}
+
+async fn run_schedule(idx: usize, name: &'static str, interval_ms: u64, max_deletes: usize) {
+    let mut interval = time::interval(Duration::from_millis(interval_ms));
+    interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+    loop {
+        interval.tick().await;
+        if !is_leader() {
+            continue;
+        }
+
+        let start_at = Instant::now();
+        let res = run_job(name, max_deletes).await;
+        let duration = start_at.elapsed().as_millis() as u64;
+        let result = if res.is_ok() { "ok" } else { "error" };
+        JOB_RUN_COUNTER.with_label_values(&[name, result]).inc();
+        if let Err(e) = &res {
+            error!(LOGGER, "[JOBS] job {} failed: {:?}", name, e);
+        }
+        update_status(idx, |status| {
+            status.runs += 1;
+            status.last_run_ms = now_timestamp_in_millis();
+            status.last_duration_ms = duration;
+            if let Err(e) = res {
+                status.failures += 1;
+                status.last_error = format!("{:?}", e);
+            }
+        });
+    }
+}
+
+/// Start the schedule of each enabled job, jobs not configured run at the
+/// GC interval.
+pub fn start_jobs(topo: Cluster) {
+    let rules = config_jobs_or_default();
+    for rule in &rules {
+        if !JOBS.contains(&rule.name.as_str()) {
+            error!(LOGGER, "[JOBS] unknown job {} is ignored", rule.name);
+        }
+    }
+
+    let mut statuses = Vec::with_capacity(JOBS.len());
+    for name in JOBS {
+        let rule = rules.iter().find(|rule| rule.name == name);
+        let interval_ms = rule
+            .and_then(|rule| rule.interval_ms)
+            .unwrap_or_else(async_gc_interval_or_default);
+        let max_deletes = rule
+            .and_then(|rule| rule.max_deletes)
+            .unwrap_or(DEFAULT_MAX_DELETES);
+        let enabled = rule.and_then(|rule| rule.enabled).unwrap_or(true);
+        statuses.push(JobStatus {
+            name,
+            interval_ms,
+            max_deletes,
+            enabled,
+            runs: 0,
+            failures: 0,
+            last_run_ms: 0,
+            last_duration_ms: 0,
+            last_error: String::new(),
+        });
+    }
+    *JOB_STATUS.write().unwrap() = statuses.clone();
+
+    if statuses.iter().any(|status| status.enabled) {
+        tokio::spawn(hold_lease(topo.myself_id()));
+    }
+    for (idx, status) in statuses.into_iter().enumerate() {
+        if !status.enabled {
+            continue;
+        }
+        info!(
+            LOGGER,
+            "[JOBS] schedule job {} every {}ms", status.name, status.interval_ms
+        );
+        tokio::spawn(run_schedule(
+            idx,
+            status.name,
+            status.interval_ms,
+            status.max_deletes,
+        ));
+    }
+}
+
+/// Status of the jobs reported in `INFO jobs`
+pub fn encode_jobs_info() -> String {
+    let mut info = format!("# Jobs\r\nleader:{}\r\n", is_leader() as u8);
+    for status in JOB_STATUS.read().unwrap().iter() {
+        info.push_str(&format!(
+            "job_{}:enabled={},interval_ms={},max_deletes={},runs={},failures={},\
+            last_run_ms={},last_duration_ms={},last_error={}\r\n",
+            status.name,
+            status.enabled as u8,
+            status.interval_ms,
+            status.max_deletes,
+            status.runs,
+            status.failures,
+            status.last_run_ms,
+            status.last_duration_ms,
+            status.last_error
+        ));
+    }
+    info
+}
@@ -0,0 +1,273 @@
+//! The JSONPath subset of the JSON commands.
+//!
+//! A path starting with `$` is a JSONPath and selects any number of values,
+//! others are legacy RedisJSON paths like `.a.b` or `a[0]` which select a
+//! single value. Both are made of `.name`, `['name']`, `[index]` with
+//! negative indexes from the end of arrays, and the `*` wildcard. Recursive
+//! descent, filters and slices are not supported.
+
+use serde_json::Value;
+
+#[derive(Debug, Clone, PartialEq)]
+enum Segment {
+    Key(String),
+    Index(i64),
+    Wildcard,
+}
+
+/// A concrete step from a value to one of its children
+#[derive(Debug, Clone)]
+enum Step {
+    Key(String),
+    Index(usize),
+}
+
+/// How JSON.SET treats the values already at the path
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SetMode {
+    Always,
+    /// NX, only add values
+    Missing,
+    /// XX, only replace values
+    Existing,
+}
+
+#[derive(Debug, Clone)]
+pub struct JsonPath {
+    segments: Vec<Segment>,
+    legacy: bool,
+}
+
+impl JsonPath {
+    pub fn parse(path: &str) -> Option<JsonPath> {
+        match path.strip_prefix('$') {
+            Some(rest) => JsonPath::parse_segments(rest, false),
+            // a legacy path may omit the leading dot
+            None if !path.starts_with('.') && !path.starts_with('[') => {
+                JsonPath::parse_segments(&format!(".{}", path), true)
+            }
+            None => JsonPath::parse_segments(path, true),
+        }
+    }
+
+    fn parse_segments(mut rest: &str, legacy: bool) -> Option<JsonPath> {
+        let mut segments = vec![];
+        // `.` alone is the root of legacy paths
+        if legacy && rest == "." {
+            rest = "";
+        }
+        while !rest.is_empty() {
+            if let Some(r) = rest.strip_prefix('.') {
+                let end = r.find(|c| c == '.' || c == '[').unwrap_or(r.len());
+                let name = &r[..end];
+                if name.is_empty() {
+                    return None;
+                }
+                segments.push(if name == "*" {
+                    Segment::Wildcard
+                } else {
+                    Segment::Key(name.to_owned())
+                });
+                rest = &r[end..];
+            } else if let Some(r) = rest.strip_prefix('[') {
+                let end = r.find(']')?;
+                let inner = r[..end].trim();
+                let segment = if inner == "*" {
+                    Segment::Wildcard
+                } else if let Some(name) = strip_quotes(inner) {
+                    Segment::Key(name.to_owned())
+                } else {
+                    Segment::Index(inner.parse().ok()?)
+                };
+                segments.push(segment);
+                rest = &r[end + 1..];
+            } else {
+                return None;
+            }
+        }
+        Some(JsonPath { segments, legacy })
+    }
+
+    /// Legacy paths select one value and reply with it instead of an array
+    pub fn is_legacy(&self) -> bool {
+        self.legacy
+    }
+
+    pub fn is_root(&self) -> bool {
+        self.segments.is_empty()
+    }
+
+    /// The values selected in `doc`
+    pub fn get<'a>(&self, doc: &'a Value) -> Vec<&'a Value> {
+        resolve(doc, &self.segments)
+            .iter()
+            .filter_map(|steps| lookup(doc, steps))
+            .collect()
+    }
+
+    /// Set `value` at the path in `doc`, the parents must exist, a missing
+    /// object member is added but arrays are not extended. Returns the number
+    /// of values set.
+    pub fn set(&self, doc: &mut Value, value: &Value, mode: SetMode) -> usize {
+        let (last, parents) = match self.segments.split_last() {
+            Some(split) => split,
+            None => {
+                if mode == SetMode::Missing {
+                    return 0;
+                }
+                *doc = value.clone();
+                return 1;
+            }
+        };
+        let mut count = 0;
+        for steps in resolve(doc, parents) {
+            let parent = match lookup_mut(doc, &steps) {
+                Some(parent) => parent,
+                None => continue,
+            };
+            match (parent, last) {
+                (Value::Object(map), Segment::Key(k)) => {
+                    let exists = map.contains_key(k);
+                    if (exists && mode == SetMode::Missing)
+                        || (!exists && mode == SetMode::Existing)
+                    {
+                        continue;
+                    }
+                    map.insert(k.clone(), value.clone());
+                    count += 1;
+                }
+                (Value::Array(arr), Segment::Index(i)) => {
+                    if mode == SetMode::Missing {
+                        continue;
+                    }
+                    if let Some(idx) = array_index(arr.len(), *i) {
+                        arr[idx] = value.clone();
+                        count += 1;
+                    }
+                }
+                (Value::Object(map), Segment::Wildcard) if mode != SetMode::Missing => {
+                    for v in map.values_mut() {
+                        *v = value.clone();
+                        count += 1;
+                    }
+                }
+                (Value::Array(arr), Segment::Wildcard) if mode != SetMode::Missing => {
+                    for v in arr.iter_mut() {
+                        *v = value.clone();
+                        count += 1;
+                    }
+                }
+                _ => {}
+            }
+        }
+        count
+    }
+
+    /// Delete the values selected in `doc`, the root can not be deleted this
+    /// way. Returns the number of values deleted.
+    pub fn delete(&self, doc: &mut Value) -> usize {
+        let (last, parents) = match self.segments.split_last() {
+            Some(split) => split,
+            None => return 0,
+        };
+        let mut count = 0;
+        for steps in resolve(doc, parents) {
+            let parent = match lookup_mut(doc, &steps) {
+                Some(parent) => parent,
+                None => continue,
+            };
+            match (parent, last) {
+                (Value::Object(map), Segment::Key(k)) => {
+                    if map.remove(k).is_some() {
+                        count += 1;
+                    }
+                }
+                (Value::Array(arr), Segment::Index(i)) => {
+                    if let Some(idx) = array_index(arr.len(), *i) {
+                        arr.remove(idx);
+                        count += 1;
+                    }
+                }
+                (Value::Object(map), Segment::Wildcard) => {
+                    count += map.len();
+                    map.clear();
+                }
+                (Value::Array(arr), Segment::Wildcard) => {
+                    count += arr.len();
+                    arr.clear();
+                }
+                _ => {}
+            }
+        }
+        count
+    }
+}
+
+fn strip_quotes(s: &str) -> Option<&str> {
+    s.strip_prefix('\'')
+        .and_then(|s| s.strip_suffix('\''))
+        .or_else(|| s.strip_prefix('"').and_then(|s| s.strip_suffix('"')))
+}
+
+/// Position of index `i` in an array of `len` values, from the end if negative
+fn array_index(len: usize, i: i64) -> Option<usize> {
+    let idx = if i < 0 { len as i64 + i } else { i };
+    if idx < 0 || idx >= len as i64 {
+        return None;
+    }
+    Some(idx as usize)
+}
+
+/// The steps to each value selected by `segments` in `doc`
+fn resolve(doc: &Value, segments: &[Segment]) -> Vec<Vec<Step>> {
+    let mut matches = vec![(vec![], doc)];
+    for segment in segments {
+        let mut next = vec![];
+        for (steps, value) in matches {
+            let mut child = |step: Step, v| {
+                let mut steps = steps.clone();
+                steps.push(step);
+                next.push((steps, v));
+            };
+            match (value, segment) {
+                (Value::Object(map), Segment::Key(k)) => {
+                    if let Some(v) = map.get(k) {
+                        child(Step::Key(k.clone()), v);
+                    }
+                }
+                (Value::Array(arr), Segment::Index(i)) => {
+                    if let Some(idx) = array_index(arr.len(), *i) {
+                        child(Step::Index(idx), &arr[idx]);
+                    }
+                }
+                (Value::Object(map), Segment::Wildcard) => {
+                    for (k, v) in map {
+                        child(Step::Key(k.clone()), v);
+                    }
+                }
+                (Value::Array(arr), Segment::Wildcard) => {
+                    for (idx, v) in arr.iter().enumerate() {
+                        child(Step::Index(idx), v);
+                    }
+                }
+                _ => {}
+            }
+        }
+        matches = next;
+    }
+    matches.into_iter().map(|(steps, _)| steps).collect()
+}
+
+fn lookup<'a>(doc: &'a Value, steps: &[Step]) -> Option<&'a Value> {
+    steps.iter().try_fold(doc, |v, step| match step {
+        Step::Key(k) => v.get(k),
+        Step::Index(idx) => v.get(idx),
+    })
+}
+
+fn lookup_mut<'a>(doc: &'a mut Value, steps: &[Step]) -> Option<&'a mut Value> {
+    steps.iter().try_fold(doc, |v, step| match step {
+        Step::Key(k) => v.get_mut(k),
+        Step::Index(idx) => v.get_mut(idx),
+    })
+}
@@ -6,7 +6,18 @@ extern crate prometheus;
 extern crate hyper;
 extern crate thiserror;
 
+pub mod access;
+
+pub mod auth;
+
+pub mod blocking;
+
 pub mod cmd;
+pub mod cmdlog;
+
+pub mod diag;
+pub mod dispatch;
+
 use std::sync::atomic::AtomicU16;
 use std::sync::atomic::Ordering;
 
@@ -37,59 +48,171 @@ mod tikv;
 pub use tikv::do_async_connect;
 pub use tikv::do_async_raw_connect;
 pub use tikv::do_async_txn_connect;
+pub use tikv::encryption::init_data_keys;
+pub use tikv::format::check_data_format;
 pub use tikv::set_instance_id;
 
 pub mod cluster;
 
 pub mod client;
 
+pub mod ipfilter;
+
+pub mod jobs;
+
+pub mod jsonpath;
+
+pub mod logsample;
+
+pub mod geohash;
+
+pub mod gateway;
+
+#[cfg(feature = "grpc")]
+pub mod grpc;
+
+pub mod memcache;
+pub mod memlimit;
+
+pub mod notify;
+
+pub mod outbound;
+
+pub mod playground;
+pub use playground::Playground;
+
+pub mod priority;
+
+pub mod proxy;
+
+pub mod rdb;
+
+pub mod replication;
+
+pub mod slo;
+
+pub mod triggers;
+
 pub mod utils;
 
+pub mod websocket;
+
 pub mod config;
 pub use config::async_del_hash_threshold_or_default;
 pub use config::async_del_list_threshold_or_default;
 pub use config::async_del_set_threshold_or_default;
+pub use config::async_del_stream_threshold_or_default;
 pub use config::async_del_zset_threshold_or_default;
 pub use config::async_deletion_enabled_or_default;
 pub use config::async_expire_hash_threshold_or_default;
 pub use config::async_expire_list_threshold_or_default;
 pub use config::async_expire_set_threshold_or_default;
 pub use config::async_expire_zset_threshold_or_default;
+pub use config::async_gc_batch_size_or_default;
+pub use config::async_gc_concurrency_or_default;
+pub use config::async_gc_deletes_per_sec_or_default;
 pub use config::async_gc_interval_or_default;
+pub use config::async_gc_target_latency_ms_or_default;
 pub use config::async_gc_worker_number_or_default;
 pub use config::async_gc_worker_queue_size_or_default;
 pub use config::backend_allow_batch_or_default;
 pub use config::backend_ca_file_or_default;
 pub use config::backend_cert_file_or_default;
 pub use config::backend_completion_queue_size_or_default;
+pub use config::backend_default_ttl_ms_or_default;
+pub use config::backend_eviction_budget_or_default;
+pub use config::backend_eviction_max_bytes_or_default;
+pub use config::backend_eviction_max_keys_or_default;
 pub use config::backend_grpc_keepalive_time_or_default;
 pub use config::backend_grpc_keepalive_timeout_or_default;
+pub use config::backend_health_check_interval_or_default;
+pub use config::backend_idempotency_derive_tokens_or_default;
+pub use config::backend_idempotency_token_ttl_ms_or_default;
 pub use config::backend_key_file_or_default;
+pub use config::backend_leader_read_timeout_ms_or_default;
 pub use config::backend_max_batch_size_or_default;
 pub use config::backend_max_batch_wait_time_or_default;
 pub use config::backend_max_inflight_requests_or_default;
+pub use config::backend_max_ttl_ms_or_default;
 pub use config::backend_overload_threshold_or_default;
+pub use config::backend_pipeline_snapshot_max_age_ms_or_default;
+pub use config::backend_pipeline_snapshot_max_cmds_or_default;
+pub use config::backend_read_consistency_or_default;
+pub use config::backend_rpc_log_max_len_or_default;
+pub use config::backend_rpc_log_slower_than_ms_or_default;
+pub use config::backend_stale_read_ms_or_default;
+pub use config::backend_storage_or_default;
 pub use config::backend_timeout_or_default;
+pub use config::check_config;
+pub use config::cmd_bitop_max_source_bytes_or_default;
+pub use config::cmd_inter_length_limit_or_default;
 pub use config::cmd_linsert_length_limit_or_default;
 pub use config::cmd_lrem_length_limit_or_default;
+pub use config::cmd_sort_length_limit_or_default;
+pub use config::config_auth_backend_or_default;
+pub use config::config_auth_cache_ttl_or_default;
+pub use config::config_auth_revoke_mode_or_default;
+pub use config::config_auth_webhook_timeout_or_default;
+pub use config::config_auth_webhook_url_or_default;
 pub use config::config_cluster_broadcast_addr_or_default;
+pub use config::config_cluster_slot_scan_max_keys_or_default;
 pub use config::config_cluster_topology_expire_or_default;
 pub use config::config_cluster_topology_interval_or_default;
+pub use config::config_cmdlog_batch_size_or_default;
+pub use config::config_cmdlog_batch_wait_ms_or_default;
+pub use config::config_cmdlog_drop_on_full_or_default;
+pub use config::config_cmdlog_queue_size_or_default;
+pub use config::config_cmdlog_sink_or_default;
+pub use config::config_enable_debug_command_or_default;
+pub use config::config_grpc_listen_or_default;
+pub use config::config_grpc_port_or_default;
+pub use config::config_hash_indexes_or_default;
+pub use config::config_http_api_listen_or_default;
+pub use config::config_http_api_port_or_default;
 pub use config::config_instance_id_or_default;
+pub use config::config_ip_allow_list_or_default;
+pub use config::config_ip_deny_list_or_default;
+pub use config::config_ip_filter_file_or_default;
+pub use config::config_jobs_or_default;
+pub use config::config_latency_slo_alert_log_or_default;
+pub use config::config_latency_slo_or_default;
 pub use config::config_listen_or_default;
 pub use config::config_local_pool_number;
+pub use config::config_low_priority_concurrency_or_default;
+pub use config::config_lua_time_limit_or_default;
+pub use config::config_memcache_listen_or_default;
+pub use config::config_memcache_port_or_default;
+pub use config::config_memory_hard_limit_or_default;
+pub use config::config_memory_soft_limit_or_default;
 pub use config::config_meta_key_number_or_default;
+pub use config::config_notify_keyspace_events_or_default;
+pub use config::config_object_access_max_keys_or_default;
+pub use config::config_object_access_tracking_or_default;
 pub use config::config_pd_addrs_or_default;
+pub use config::config_pipeline_concurrency_or_default;
 pub use config::config_port_or_default;
 pub use config::config_prometheus_listen_or_default;
 pub use config::config_prometheus_port_or_default;
+pub use config::config_proxy_protocol_or_default;
+pub use config::config_resource_group_or_default;
+pub use config::config_restore_skip_zero_checksum_or_default;
 pub use config::config_tls_auth_client_or_default;
 pub use config::config_tls_ca_cert_file_or_default;
 pub use config::config_tls_cert_file_or_default;
 pub use config::config_tls_key_file_or_default;
 pub use config::config_tls_listen_or_default;
 pub use config::config_tls_port_or_default;
+pub use config::config_tls_proxy_protocol_or_default;
+pub use config::config_triggers_or_default;
+pub use config::config_user_resource_group;
+pub use config::config_ws_listen_or_default;
+pub use config::config_ws_port_or_default;
 pub use config::conn_concurrency_or_default;
+pub use config::encryption_enabled_or_default;
+pub use config::encryption_kms_key_id_or_default;
+pub use config::encryption_kms_token_or_default;
+pub use config::encryption_kms_url_or_default;
+pub use config::encryption_master_key_or_default;
 pub use config::get_global_config;
 pub use config::is_auth_enabled;
 pub use config::is_auth_matched;
@@ -97,6 +220,12 @@ pub use config::is_try_one_pc_commit;
 pub use config::is_use_async_commit;
 pub use config::is_use_pessimistic_txn;
 pub use config::is_use_txn_api;
+pub use config::log_max_arg_len;
+pub use config::log_max_args;
+pub use config::log_redact_key_hash;
+pub use config::log_redact_values;
+pub use config::log_sample_rate;
+pub use config::log_sample_slower_than_us;
 pub use config::set_global_config;
 pub use config::txn_lock_backoff_delay_attemps;
 pub use config::txn_lock_backoff_delay_ms;
@@ -0,0 +1,95 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+use tokio::time::Instant;
+
+use slog::{Level, Record};
+
+use crate::{log_sample_rate, log_sample_slower_than_us};
+
+/// The log sampling settings changed at runtime with CONFIG SET
+pub const LOG_SAMPLE_SETTINGS_NAMES: [&str; 2] = ["log_sample_rate", "log_sample_slower_than_us"];
+
+/// Settings of the sampling, initialized from the config
+struct LogSampleSettings {
+    /// debug lines of 1 in `rate` commands are logged, 0 for none
+    rate: AtomicU64,
+    /// debug lines of commands running at least this long are logged
+    /// whether sampled or not, 0 for none
+    slower_than_us: AtomicU64,
+}
+
+lazy_static! {
+    static ref LOG_SAMPLE_SETTINGS: LogSampleSettings = LogSampleSettings {
+        rate: AtomicU64::new(log_sample_rate()),
+        slower_than_us: AtomicU64::new(log_sample_slower_than_us()),
+    };
+}
+
+/// Commands started, the sampled ones are those at a multiple of the rate
+static COMMAND_SEQ: AtomicU64 = AtomicU64::new(0);
+
+tokio::task_local! {
+    /// Set while applying a command, its debug lines are kept if it's
+    /// sampled or slow.
+    pub static COMMAND_LOG: CommandLog;
+}
+
+/// Whether the debug lines of a command are logged
+#[derive(Debug, Clone, Copy)]
+pub struct CommandLog {
+    sampled: bool,
+    start_at: Instant,
+}
+
+impl CommandLog {
+    /// Sample the command started at `start_at`
+    pub fn start(start_at: Instant) -> CommandLog {
+        let sampled = match LOG_SAMPLE_SETTINGS.rate.load(Ordering::Relaxed) {
+            0 => false,
+            1 => true,
+            rate => COMMAND_SEQ.fetch_add(1, Ordering::Relaxed) % rate == 0,
+        };
+        CommandLog { sampled, start_at }
+    }
+
+    /// True if the debug lines logged now are kept
+    pub fn keep(&self) -> bool {
+        if self.sampled {
+            return true;
+        }
+        let slower_than_us = LOG_SAMPLE_SETTINGS.slower_than_us.load(Ordering::Relaxed);
+        slower_than_us > 0 && self.start_at.elapsed() >= Duration::from_micros(slower_than_us)
+    }
+}
+
+/// Filter of the logger, the debug and trace lines logged while applying a
+/// command are dropped unless the command is sampled or slow. Lines outside
+/// of commands and the ones of higher levels are always kept.
+pub fn keep_record(record: &Record) -> bool {
+    if record.level().is_at_least(Level::Info) {
+        return true;
+    }
+    COMMAND_LOG.try_with(|log| log.keep()).unwrap_or(true)
+}
+
+/// The value of a runtime log sampling setting, None for an unknown name
+pub fn log_sample_setting(name: &str) -> Option<u64> {
+    let value = match name {
+        "log_sample_rate" => LOG_SAMPLE_SETTINGS.rate.load(Ordering::Relaxed),
+        "log_sample_slower_than_us" => LOG_SAMPLE_SETTINGS.slower_than_us.load(Ordering::Relaxed),
+        _ => return None,
+    };
+    Some(value)
+}
+
+/// Change a runtime log sampling setting, false for an unknown name
+pub fn set_log_sample_setting(name: &str, value: u64) -> bool {
+    match name {
+        "log_sample_rate" => LOG_SAMPLE_SETTINGS.rate.store(value, Ordering::Relaxed),
+        "log_sample_slower_than_us" => LOG_SAMPLE_SETTINGS
+            .slower_than_us
+            .store(value, Ordering::Relaxed),
+        _ => return false,
+    }
+    true
+}
@@ -0,0 +1,453 @@
+//! Memcached text protocol listener.
+//!
+//! With `memcache_port` set, legacy memcached clients use the strings of the
+//! instance without a proxy. `get`, `gets`, `set`, `add`, `replace`,
+//! `append`, `delete`, `incr`, `decr` and `touch` run as the string commands
+//! of Redis, the conditional ones in one transaction. The flags of a value
+//! are not stored and read as 0, and there is no CAS, `gets` replies 0 as
+//! the unique of each value. The binary protocol is refused. As memcached
+//! has no authentication, the listener does not start with a password. The
+//! commands run through `crate::dispatch`, so their writes are shipped to
+//! the command log and fire the triggers and notifications like the writes
+//! of RESP clients.
+
+use std::sync::Arc;
+
+use bytes::Bytes;
+use futures::future::FutureExt;
+use slog::{debug, error, info, warn};
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader, BufWriter};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::Mutex;
+
+use crate::config::LOGGER;
+use crate::dispatch::{execute, execute_in_txn, Writes};
+use crate::ipfilter::is_ip_allowed;
+use crate::tikv::backend::Transaction;
+use crate::tikv::errors::AsyncResult;
+use crate::tikv::get_txn_client;
+use crate::utils::now_timestamp_in_millis;
+use crate::{
+    config_memcache_listen_or_default, config_memcache_port_or_default, is_auth_enabled, Frame,
+};
+
+/// Longest command line, like memcached
+const MAX_LINE_LEN: usize = 2048;
+/// Longest key, like memcached
+const MAX_KEY_LEN: usize = 250;
+/// Largest value, the default item size limit of memcached
+const MAX_VALUE_LEN: usize = 1024 * 1024;
+/// Exptimes up to 30 days are relative, larger ones are unix timestamps
+const RELATIVE_EXPTIME_MAX: i64 = 60 * 60 * 24 * 30;
+/// First byte of the requests of the binary protocol
+const BINARY_REQUEST_MAGIC: u8 = 0x80;
+
+const CLIENT_ERROR_FORMAT: &str = "CLIENT_ERROR bad command line format";
+const CLIENT_ERROR_NON_NUMERIC: &str =
+    "CLIENT_ERROR cannot increment or decrement non-numeric value";
+
+/// Accept memcached clients on `memcache_listen:memcache_port`, returns at
+/// once if the port is not set.
+pub async fn run_memcache_listener() {
+    let port = config_memcache_port_or_default();
+    if port == 0 {
+        return;
+    }
+    if is_auth_enabled() {
+        error!(
+            LOGGER,
+            "[MEMCACHE] listener not started, memcached clients can't authenticate"
+        );
+        return;
+    }
+    let addr = format!("{}:{}", config_memcache_listen_or_default(), port);
+    let listener = match TcpListener::bind(&addr).await {
+        Ok(listener) => listener,
+        Err(e) => {
+            error!(LOGGER, "[MEMCACHE] failed to listen on {}, {}", addr, e);
+            return;
+        }
+    };
+    info!(LOGGER, "[MEMCACHE] Server Listen on: {}", addr);
+
+    loop {
+        let (socket, peer) = match listener.accept().await {
+            Ok(accepted) => accepted,
+            Err(e) => {
+                error!(LOGGER, "[MEMCACHE] failed to accept, {}", e);
+                continue;
+            }
+        };
+        if !is_ip_allowed(&peer.ip()) {
+            continue;
+        }
+        tokio::spawn(async move {
+            if let Err(e) = handle_connection(socket).await {
+                debug!(LOGGER, "[MEMCACHE] connection of {} closed, {}", peer, e);
+            }
+        });
+    }
+}
+
+/// Seconds to live of an exptime, None for no expiry and Some(0) for a value
+/// expired already
+fn ttl_of(exptime: i64) -> Option<i64> {
+    match exptime {
+        0 => None,
+        e if e < 0 => Some(0),
+        e if e <= RELATIVE_EXPTIME_MAX => Some(e),
+        e => Some((e - (now_timestamp_in_millis() / 1000) as i64).max(0)),
+    }
+}
+
+fn valid_key(key: &[u8]) -> bool {
+    key.len() <= MAX_KEY_LEN && !key.iter().any(|b| b.is_ascii_control())
+}
+
+/// A transaction, with the writes applied in it
+type Txn = (Arc<Mutex<Transaction>>, Writes);
+
+/// Run the Redis command `name`, in the transaction if there is one
+async fn run(name: &str, args: Vec<Bytes>, txn: Option<Txn>) -> AsyncResult<Frame> {
+    match txn {
+        Some((txn, writes)) => execute_in_txn(name, args, Some(txn), &writes).await,
+        None => execute(name, args).await,
+    }
+}
+
+/// The reply of a memcached command, the error of a Redis command as a
+/// server error
+fn reply_of(frame: AsyncResult<Frame>, reply: impl FnOnce(Frame) -> String) -> String {
+    match frame {
+        Ok(Frame::ErrorOwned(e)) => format!("SERVER_ERROR {}\r\n", e),
+        Ok(Frame::ErrorString(e)) => format!("SERVER_ERROR {}\r\n", e),
+        Ok(frame) => reply(frame),
+        Err(e) => format!("SERVER_ERROR {}\r\n", e),
+    }
+}
+
+/// Set `key` to `value` for `ttl`, deleted if it's expired already
+async fn store(key: Bytes, value: Bytes, ttl: Option<i64>, txn: Option<Txn>) -> AsyncResult<Frame> {
+    match ttl {
+        Some(0) => run("del", vec![key], txn).await,
+        Some(ttl) => {
+            let args = vec![key, value, Bytes::from("EX"), Bytes::from(ttl.to_string())];
+            run("set", args, txn).await
+        }
+        None => run("set", vec![key, value], txn).await,
+    }
+}
+
+/// Run `f` in a transaction once the key is found to exist, None if it does
+/// not
+async fn if_exists<F>(key: Bytes, f: F) -> AsyncResult<Option<Frame>>
+where
+    F: FnOnce(Txn) -> futures::future::BoxFuture<'static, AsyncResult<Frame>>
+        + Clone
+        + Send
+        + 'static,
+{
+    let writes = Writes::default();
+    let txn_writes = writes.clone();
+    let mut client = get_txn_client()?;
+    let frame = client
+        .exec_in_txn(None, |txn_rc| {
+            async move {
+                // a retried transaction starts over
+                txn_writes.clear();
+                let txn = (txn_rc, txn_writes);
+                match run("exists", vec![key], Some(txn.clone())).await? {
+                    Frame::Integer(0) => Ok(None),
+                    Frame::Integer(_) => Ok(Some(f(txn).await?)),
+                    frame => Ok(Some(frame)),
+                }
+            }
+            .boxed()
+        })
+        .await?;
+    writes.committed().await;
+    Ok(frame)
+}
+
+/// The result of `incr` or `decr`
+enum Counter {
+    NotFound,
+    NotNumeric,
+    Changed(Frame),
+}
+
+/// Change the integer value of the key, floored at 0 like memcached when it
+/// is decremented
+async fn incr_decr(key: Bytes, delta: u64, incr: bool) -> AsyncResult<Counter> {
+    let writes = Writes::default();
+    let txn_writes = writes.clone();
+    let mut client = get_txn_client()?;
+    let counter = client
+        .exec_in_txn(None, |txn_rc| {
+            async move {
+                // a retried transaction starts over
+                txn_writes.clear();
+                let txn = (txn_rc, txn_writes);
+                let value = match run("get", vec![key.clone()], Some(txn.clone())).await? {
+                    Frame::Bulk(value) => value,
+                    Frame::Null => return Ok(Counter::NotFound),
+                    frame => return Ok(Counter::Changed(frame)),
+                };
+                let current = match std::str::from_utf8(&value).ok().map(str::parse::<u64>) {
+                    Some(Ok(current)) => current,
+                    _ => return Ok(Counter::NotNumeric),
+                };
+                let (name, delta) = if incr {
+                    ("incrby", delta)
+                } else {
+                    ("decrby", delta.min(current))
+                };
+                let args = vec![key, Bytes::from(delta.to_string())];
+                Ok(Counter::Changed(run(name, args, Some(txn)).await?))
+            }
+            .boxed()
+        })
+        .await?;
+    writes.committed().await;
+    Ok(counter)
+}
+
+/// Serve the requests of a memcached client until it quits
+async fn handle_connection(socket: TcpStream) -> crate::Result<()> {
+    let (r, w) = socket.into_split();
+    let mut r = BufReader::new(r);
+    let mut w = BufWriter::new(w);
+
+    let mut line = Vec::with_capacity(256);
+    loop {
+        line.clear();
+        let n = (&mut r)
+            .take(MAX_LINE_LEN as u64)
+            .read_until(b'\n', &mut line)
+            .await?;
+        if n == 0 {
+            return Ok(());
+        }
+        if line[0] == BINARY_REQUEST_MAGIC {
+            warn!(LOGGER, "[MEMCACHE] binary protocol refused");
+            return Ok(());
+        }
+        if line.last() != Some(&b'\n') {
+            w.write_all(b"CLIENT_ERROR line too long\r\n").await?;
+            w.flush().await?;
+            return Ok(());
+        }
+        let parts: Vec<Bytes> = line
+            .split(|b| b.is_ascii_whitespace())
+            .filter(|part| !part.is_empty())
+            .map(Bytes::copy_from_slice)
+            .collect();
+        let command = match parts.first() {
+            Some(command) => String::from_utf8_lossy(command).to_lowercase(),
+            None => {
+                w.write_all(b"ERROR\r\n").await?;
+                w.flush().await?;
+                continue;
+            }
+        };
+        let noreply = parts.len() > 1 && parts[parts.len() - 1].as_ref() == b"noreply";
+        let args = &parts[1..parts.len() - noreply as usize];
+
+        let reply = match command.as_str() {
+            "get" | "gets" if args.is_empty() || !args.iter().all(|k| valid_key(k)) => {
+                format!("{}\r\n", CLIENT_ERROR_FORMAT)
+            }
+            "get" | "gets" => {
+                let frame = run("mget", args.to_vec(), None).await;
+                let gets = command == "gets";
+                let mut out = vec![];
+                match frame {
+                    Ok(Frame::Array(values)) => {
+                        for (key, value) in args.iter().zip(values) {
+                            if let Frame::Bulk(value) = value {
+                                out.extend_from_slice(b"VALUE ");
+                                out.extend_from_slice(key);
+                                let header = if gets {
+                                    format!(" 0 {} 0\r\n", value.len())
+                                } else {
+                                    format!(" 0 {}\r\n", value.len())
+                                };
+                                out.extend_from_slice(header.as_bytes());
+                                out.extend_from_slice(&value);
+                                out.extend_from_slice(b"\r\n");
+                            }
+                        }
+                        out.extend_from_slice(b"END\r\n");
+                    }
+                    frame => {
+                        out.extend_from_slice(reply_of(frame, |_| "END\r\n".into()).as_bytes())
+                    }
+                }
+                w.write_all(&out).await?;
+                w.flush().await?;
+                continue;
+            }
+            "set" | "add" | "replace" | "append" | "cas" => {
+                let parsed = match args {
+                    [key, _flags, exptime, len] | [key, _flags, exptime, len, _]
+                        if valid_key(key) && (args.len() == 5) == (command == "cas") =>
+                    {
+                        let exptime = String::from_utf8_lossy(exptime).parse::<i64>().ok();
+                        let len = String::from_utf8_lossy(len).parse::<usize>().ok();
+                        exptime
+                            .zip(len)
+                            .map(|(exptime, len)| (key.clone(), exptime, len))
+                    }
+                    _ => None,
+                };
+                let (key, exptime, len) = match parsed {
+                    Some(parsed) => parsed,
+                    None => {
+                        w.write_all(format!("{}\r\n", CLIENT_ERROR_FORMAT).as_bytes())
+                            .await?;
+                        w.flush().await?;
+                        continue;
+                    }
+                };
+                if len > MAX_VALUE_LEN {
+                    // skip the data the client sends anyway
+                    tokio::io::copy(&mut (&mut r).take(len as u64 + 2), &mut tokio::io::sink())
+                        .await?;
+                    w.write_all(b"SERVER_ERROR object too large for cache\r\n")
+                        .await?;
+                    w.flush().await?;
+                    continue;
+                }
+                let mut data = vec![0; len + 2];
+                r.read_exact(&mut data).await?;
+                if !data.ends_with(b"\r\n") {
+                    w.write_all(b"CLIENT_ERROR bad data chunk\r\n").await?;
+                    w.flush().await?;
+                    continue;
+                }
+                data.truncate(len);
+                let value = Bytes::from(data);
+                let ttl = ttl_of(exptime);
+
+                match command.as_str() {
+                    "cas" => "SERVER_ERROR cas is not supported\r\n".into(),
+                    "set" => reply_of(store(key, value, ttl, None).await, |_| "STORED\r\n".into()),
+                    "add" => {
+                        let frame = match ttl {
+                            // nothing to store, only tell whether the key exists
+                            Some(0) => run("exists", vec![key], None).await.map(|f| match f {
+                                Frame::Integer(0) => Frame::Simple("OK".into()),
+                                _ => Frame::Null,
+                            }),
+                            Some(ttl) => {
+                                let args = vec![
+                                    key,
+                                    value,
+                                    Bytes::from("NX"),
+                                    Bytes::from("EX"),
+                                    Bytes::from(ttl.to_string()),
+                                ];
+                                run("set", args, None).await
+                            }
+                            None => run("set", vec![key, value, Bytes::from("NX")], None).await,
+                        };
+                        reply_of(frame, |f| match f {
+                            Frame::Null => "NOT_STORED\r\n".into(),
+                            _ => "STORED\r\n".into(),
+                        })
+                    }
+                    "replace" => {
+                        let k = key.clone();
+                        let frame =
+                            if_exists(key, move |txn| store(k, value, ttl, Some(txn)).boxed())
+                                .await;
+                        match frame {
+                            Ok(None) => "NOT_STORED\r\n".into(),
+                            frame => reply_of(frame.map(Option::unwrap), |_| "STORED\r\n".into()),
+                        }
+                    }
+                    _ => {
+                        let k = key.clone();
+                        let frame = if_exists(key, move |txn| {
+                            run("append", vec![k, value], Some(txn)).boxed()
+                        })
+                        .await;
+                        match frame {
+                            Ok(None) => "NOT_STORED\r\n".into(),
+                            frame => reply_of(frame.map(Option::unwrap), |_| "STORED\r\n".into()),
+                        }
+                    }
+                }
+            }
+            "delete" => match args {
+                // the time of old clients must be 0
+                [key] | [key, _]
+                    if valid_key(key) && args.get(1).map_or(true, |t| t.as_ref() == b"0") =>
+                {
+                    reply_of(run("del", vec![key.clone()], None).await, |f| match f {
+                        Frame::Integer(0) => "NOT_FOUND\r\n".into(),
+                        _ => "DELETED\r\n".into(),
+                    })
+                }
+                _ => format!("{}\r\n", CLIENT_ERROR_FORMAT),
+            },
+            "incr" | "decr" => match args {
+                [key, delta] if valid_key(key) => {
+                    match String::from_utf8_lossy(delta).parse::<u64>() {
+                        Ok(delta) => {
+                            let frame = incr_decr(key.clone(), delta, command == "incr").await;
+                            match frame {
+                                Ok(Counter::NotFound) => "NOT_FOUND\r\n".into(),
+                                Ok(Counter::NotNumeric) => {
+                                    format!("{}\r\n", CLIENT_ERROR_NON_NUMERIC)
+                                }
+                                Ok(Counter::Changed(frame)) => reply_of(Ok(frame), |f| match f {
+                                    Frame::Integer(v) => format!("{}\r\n", v),
+                                    _ => "NOT_FOUND\r\n".into(),
+                                }),
+                                Err(e) => format!("SERVER_ERROR {}\r\n", e),
+                            }
+                        }
+                        Err(_) => "CLIENT_ERROR invalid numeric delta argument\r\n".into(),
+                    }
+                }
+                _ => format!("{}\r\n", CLIENT_ERROR_FORMAT),
+            },
+            "touch" => match args {
+                [key, exptime] if valid_key(key) => {
+                    match String::from_utf8_lossy(exptime).parse::<i64>() {
+                        Ok(exptime) => {
+                            let k = key.clone();
+                            let ttl = ttl_of(exptime);
+                            let frame = if_exists(key.clone(), move |txn| match ttl {
+                                Some(0) => run("del", vec![k], Some(txn)).boxed(),
+                                Some(ttl) => {
+                                    let args = vec![k, Bytes::from(ttl.to_string())];
+                                    run("expire", args, Some(txn)).boxed()
+                                }
+                                None => run("persist", vec![k], Some(txn)).boxed(),
+                            })
+                            .await;
+                            match frame {
+                                Ok(None) => "NOT_FOUND\r\n".into(),
+                                frame => {
+                                    reply_of(frame.map(Option::unwrap), |_| "TOUCHED\r\n".into())
+                                }
+                            }
+                        }
+                        Err(_) => format!("{}\r\n", CLIENT_ERROR_FORMAT),
+                    }
+                }
+                _ => format!("{}\r\n", CLIENT_ERROR_FORMAT),
+            },
+            "version" => format!("VERSION {}\r\n", env!("CARGO_PKG_VERSION")),
+            "quit" => return Ok(()),
+            _ => "ERROR\r\n".into(),
+        };
+
+        if !noreply {
+            w.write_all(reply.as_bytes()).await?;
+            w.flush().await?;
+        }
+    }
+}
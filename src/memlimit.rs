@@ -0,0 +1,89 @@
+//! Soft and hard memory limits of the process.
+//!
+//! The resident memory is sampled every second. Above `memory_soft_limit`
+//! the scans and whole collection reads, the low priority commands which
+//! buffer the most, are refused while point reads and writes are still
+//! served. Above `memory_hard_limit` new connections are not accepted until
+//! the memory goes down. The resident memory is read from procfs, the limits
+//! are not enforced on other platforms.
+
+use std::fs;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use slog::warn;
+use tokio::time::{self, Duration, MissedTickBehavior};
+
+use crate::config::LOGGER;
+use crate::{config_memory_hard_limit_or_default, config_memory_soft_limit_or_default};
+
+const SAMPLE_INTERVAL_MS: u64 = 1000;
+
+static RESIDENT_BYTES: AtomicU64 = AtomicU64::new(0);
+
+fn resident_bytes() -> u64 {
+    let status = match fs::read_to_string("/proc/self/status") {
+        Ok(status) => status,
+        Err(_) => return 0,
+    };
+    status
+        .lines()
+        .find(|line| line.starts_with("VmRSS:"))
+        .and_then(|line| line.split_whitespace().nth(1))
+        .and_then(|kb| kb.parse::<u64>().ok())
+        .map(|kb| kb * 1024)
+        .unwrap_or(0)
+}
+
+fn over_limit(limit: u64) -> bool {
+    limit > 0 && RESIDENT_BYTES.load(Ordering::Relaxed) > limit
+}
+
+/// The resident memory read now, not the last sample, 0 if not on linux
+pub fn resident_memory() -> u64 {
+    resident_bytes()
+}
+
+/// Returns true if memory hungry commands should be refused
+pub fn over_soft_limit() -> bool {
+    over_limit(config_memory_soft_limit_or_default())
+}
+
+/// Returns true if new connections should not be accepted
+pub fn over_hard_limit() -> bool {
+    over_limit(config_memory_hard_limit_or_default())
+}
+
+/// Wait until the memory is under the hard limit
+pub async fn wait_under_hard_limit() {
+    while over_hard_limit() {
+        time::sleep(Duration::from_millis(SAMPLE_INTERVAL_MS)).await;
+    }
+}
+
+pub async fn run_memory_sampler() {
+    if config_memory_soft_limit_or_default() == 0 && config_memory_hard_limit_or_default() == 0 {
+        return;
+    }
+    let mut interval = time::interval(Duration::from_millis(SAMPLE_INTERVAL_MS));
+    interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+    loop {
+        interval.tick().await;
+
+        let was_over_soft = over_soft_limit();
+        let was_over_hard = over_hard_limit();
+        let bytes = resident_bytes();
+        RESIDENT_BYTES.store(bytes, Ordering::Relaxed);
+        if !was_over_soft && over_soft_limit() {
+            warn!(
+                LOGGER,
+                "[MEMORY] resident memory {} bytes over the soft limit, refuse scans", bytes
+            );
+        }
+        if !was_over_hard && over_hard_limit() {
+            warn!(
+                LOGGER,
+                "[MEMORY] resident memory {} bytes over the hard limit, pause accepts", bytes
+            );
+        }
+    }
+}
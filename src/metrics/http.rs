@@ -1,4 +1,5 @@
 use crate::config::LOGGER;
+use crate::tikv::health::backend_health;
 use crate::Result;
 use hyper::{
     header::CONTENT_TYPE,
@@ -45,7 +46,11 @@ impl PrometheusServer {
         Ok(())
     }
 
-    async fn serve_req(_r: Request<Body>) -> Result<Response<Body>> {
+    async fn serve_req(r: Request<Body>) -> Result<Response<Body>> {
+        if r.uri().path() == "/ready" {
+            return Ok(Self::serve_ready());
+        }
+
         let encoder = TextEncoder::new();
         let metric_families = prometheus::gather();
         let mut buffer = vec![];
@@ -59,4 +64,19 @@ impl PrometheusServer {
 
         Ok(response)
     }
+
+    /// Ready only if both PD and TiKV are reachable, so orchestration stops
+    /// routing to an instance whose storage path is degraded.
+    fn serve_ready() -> Response<Body> {
+        let health = backend_health();
+        let (status, body) = if health.is_ready() {
+            (200, "ok".to_owned())
+        } else {
+            (503, format!("not ready, {}", health.last_error))
+        };
+        Response::builder()
+            .status(status)
+            .body(Body::from(body))
+            .unwrap()
+    }
 }
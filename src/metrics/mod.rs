@@ -1,5 +1,6 @@
 use prometheus::{
-    exponential_buckets, Histogram, HistogramVec, IntCounter, IntCounterVec, IntGauge, IntGaugeVec,
+    exponential_buckets, CounterVec, Histogram, HistogramVec, IntCounter, IntCounterVec, IntGauge,
+    IntGaugeVec,
 };
 
 mod http;
@@ -32,6 +33,42 @@ lazy_static! {
         "Current tls connection counter"
     )
     .unwrap();
+    pub static ref CMDLOG_EVENT_COUNTER: IntCounterVec = register_int_counter_vec!(
+        "tikv_redis_cmdlog_events_total",
+        "Write command events of the command log sink",
+        &["result"]
+    )
+    .unwrap();
+    pub static ref TRIGGER_EVENT_COUNTER: IntCounterVec = register_int_counter_vec!(
+        "tikv_redis_trigger_events_total",
+        "Key events matched by the trigger rules",
+        &["result"]
+    )
+    .unwrap();
+    pub static ref PUSH_MESSAGE_COUNTER: IntCounterVec = register_int_counter_vec!(
+        "tikv_redis_push_messages_total",
+        "Out-of-band push messages queued to connections",
+        &["result"]
+    )
+    .unwrap();
+    pub static ref CONNECTION_EVENT_COUNTER: IntCounterVec = register_int_counter_vec!(
+        "tikv_redis_connection_events_total",
+        "Connection lifecycle event counter",
+        &["event"]
+    )
+    .unwrap();
+    pub static ref DISCONNECTION_COUNTER: IntCounterVec = register_int_counter_vec!(
+        "tikv_redis_disconnections_total",
+        "Closed connection counter",
+        &["reason"]
+    )
+    .unwrap();
+    pub static ref REJECTED_CONNECTION_COUNTER: IntCounterVec = register_int_counter_vec!(
+        "tikv_redis_rejected_connections_total",
+        "Rejected connection counter",
+        &["reason"]
+    )
+    .unwrap();
     pub static ref REQUEST_CMD_COUNTER: IntCounterVec = register_int_counter_vec!(
         "tikv_redis_command_requests_total",
         "Request command counter",
@@ -50,6 +87,37 @@ lazy_static! {
         &["cmd"]
     )
     .unwrap();
+    pub static ref RESOURCE_GROUP_REQUEST_COUNTER: IntCounterVec = register_int_counter_vec!(
+        "tikv_redis_resource_group_requests_total",
+        "Request counter of resource group",
+        &["group"]
+    )
+    .unwrap();
+    pub static ref RESOURCE_GROUP_HANDLE_SECONDS: CounterVec = register_counter_vec!(
+        "tikv_redis_resource_group_handle_seconds_total",
+        "Total command handle time of resource group",
+        &["group"]
+    )
+    .unwrap();
+    pub static ref MEMORY_SHED_COMMAND_COUNTER: IntCounterVec = register_int_counter_vec!(
+        "tikv_redis_memory_shed_commands_total",
+        "Commands refused over the soft memory limit",
+        &["cmd"]
+    )
+    .unwrap();
+    pub static ref PRIORITY_WAIT_DURATION: HistogramVec = register_histogram_vec!(
+        "tikv_redis_priority_wait_duration_seconds",
+        "Bucketed histogram of waiting for a permit of the priority class",
+        &["priority"],
+        exponential_buckets(0.0005, 2.0, 20).unwrap()
+    )
+    .unwrap();
+    pub static ref SLO_BREACH_COUNTER: IntCounterVec = register_int_counter_vec!(
+        "tikv_redis_command_slo_breach_total",
+        "Command latency slo breach counter",
+        &["cmd"]
+    )
+    .unwrap();
     pub static ref REQUEST_CMD_HANDLE_TIME: HistogramVec = register_histogram_vec!(
         "tikv_redis_command_handle_time_duration_seconds",
         "Bucketed histogram of command handle duration",
@@ -57,15 +125,40 @@ lazy_static! {
         exponential_buckets(0.0005, 2.0, 20).unwrap()
     )
     .unwrap();
+    pub static ref AUTH_REQUEST_COUNTER: IntCounterVec = register_int_counter_vec!(
+        "tikv_redis_auth_requests_total",
+        "Auth request counter",
+        &["backend", "result"]
+    )
+    .unwrap();
+    pub static ref JOB_RUN_COUNTER: IntCounterVec = register_int_counter_vec!(
+        "tikv_redis_job_runs_total",
+        "Runs of the scheduled maintenance jobs",
+        &["job", "result"]
+    )
+    .unwrap();
+    pub static ref EVICTED_KEY_COUNTER: IntCounter = register_int_counter!(
+        "tikv_redis_evicted_keys_total",
+        "The number of keys evicted when the namespace is over quota"
+    )
+    .unwrap();
     pub static ref REMOVED_EXPIRED_KEY_COUNTER: IntCounterVec = register_int_counter_vec!(
         "tikv_redis_removed_expired_keys_count_total",
         "The number of expired keys that have been removed",
         &["kind"]
     )
     .unwrap();
+    pub static ref EXPIRED_KEY_REMOVAL_COUNTER: IntCounterVec = register_int_counter_vec!(
+        "tikv_redis_expired_keys_removed_total",
+        "The number of expired keys removed by namespace, data type and reason",
+        &["namespace", "kind", "reason"]
+    )
+    .unwrap();
 
     // Trasactions
     pub static ref SNAPSHOT_COUNTER: IntCounter = register_int_counter!("tikv_redis_snapshot_count_total", "Snapshot count").unwrap();
+    pub static ref SNAPSHOT_REUSED_COUNTER: IntCounter = register_int_counter!("tikv_redis_snapshot_reused_total", "Commands served by a shared pipeline snapshot").unwrap();
+    pub static ref IDEMPOTENT_REPLAY_COUNTER: IntCounter = register_int_counter!("tikv_redis_idempotent_replay_total", "Writes not applied again because of a recorded idempotency token").unwrap();
     pub static ref TXN_COUNTER: IntCounter = register_int_counter!("tikv_redis_txn_count_total", "Transactions count").unwrap();
     pub static ref TXN_RETRY_COUNTER: IntCounter = register_int_counter!("tikv_redis_txn_retry_count_total", "Transactions retry count").unwrap();
     pub static ref TXN_MECHANISM_COUNTER: IntCounterVec = register_int_counter_vec!(
@@ -104,6 +197,18 @@ lazy_static! {
         exponential_buckets(0.0005, 2.0, 20).unwrap()
     )
     .unwrap();
+    pub static ref TSO_BATCH_SIZE: Histogram = register_histogram!(
+        "tikv_redis_tso_batch_size",
+        "Bucketed histogram of TSO requests batched together",
+        exponential_buckets(1.0, 2.0, 12).unwrap()
+    )
+    .unwrap();
+    pub static ref TSO_READ_BATCH_SIZE: Histogram = register_histogram!(
+        "tikv_redis_tso_read_batch_size",
+        "Bucketed histogram of read only transactions sharing a TSO",
+        exponential_buckets(1.0, 2.0, 12).unwrap()
+    )
+    .unwrap();
    pub static ref HANDLE_SNAPSHOT_DURATION: Histogram = register_histogram!(
         "tikv_redis_handle_snapshot_duration_seconds",
         "Bucketed histogram of handling snapshot duration",
@@ -111,6 +216,20 @@ lazy_static! {
     )
     .unwrap();
 
+    pub static ref BACKEND_UP_GAUGER: IntGaugeVec = register_int_gauge_vec!(
+        "tikv_redis_backend_up",
+        "Backend reachable observed by health check",
+        &["target"]
+    )
+    .unwrap();
+    pub static ref BACKEND_PROBE_DURATION: HistogramVec = register_histogram_vec!(
+        "tikv_redis_backend_probe_duration_seconds",
+        "Bucketed histogram of backend health probe duration",
+        &["target"],
+        exponential_buckets(0.0005, 2.0, 20).unwrap()
+    )
+    .unwrap();
+
     pub static ref PD_ERR_COUNTER: IntCounterVec = register_int_counter_vec!(
         "tikv_redis_pd_reported_errors_count_total",
         "PD reported err",
@@ -123,6 +242,25 @@ lazy_static! {
         &["err"]
     )
     .unwrap();
+    pub static ref TIKV_RPC_DURATION: HistogramVec = register_histogram_vec!(
+        "tikv_redis_tikv_rpc_duration_seconds",
+        "Bucketed histogram of the storage calls of transactions duration",
+        &["op"],
+        exponential_buckets(0.0001, 2.0, 20).unwrap()
+    )
+    .unwrap();
+    pub static ref TIKV_SLOW_RPC_COUNTER: IntCounterVec = register_int_counter_vec!(
+        "tikv_redis_tikv_slow_rpc_total",
+        "Storage calls of transactions recorded by the rpc log",
+        &["op"]
+    )
+    .unwrap();
+    pub static ref STALE_READ_FALLBACK_COUNTER: IntCounterVec = register_int_counter_vec!(
+        "tikv_redis_stale_read_fallback_total",
+        "Reads sent again on a stale snapshot after the leader read timeout",
+        &["op"]
+    )
+    .unwrap();
 
     // GC
     pub static ref GC_TASK_QUEUE_COUNTER: IntGaugeVec = register_int_gauge_vec!(
@@ -0,0 +1,173 @@
+//! Keyspace notifications like Redis `notify-keyspace-events`.
+//!
+//! `notify_keyspace_events` takes the class letters of Redis, `K` and `E`
+//! select the `__keyspace@0__:<key>` and `__keyevent@0__:<event>` channels,
+//! the others the classes of events published on them. Events are published
+//! once the write is committed, from the same hook as the triggers, on the
+//! pub/sub broker of the instance which served the write. Only the writes
+//! which changed the keyspace are published, not those replying an error or
+//! which changed nothing, like a DEL of a missing key. The `n` and `m`
+//! classes are accepted but never published.
+
+use std::sync::RwLock;
+
+use bytes::Bytes;
+use slog::error;
+
+use crate::config::LOGGER;
+use crate::config_notify_keyspace_events_or_default;
+use crate::Db;
+
+const NOTIFY_KEYSPACE: u32 = 1 << 0;
+const NOTIFY_KEYEVENT: u32 = 1 << 1;
+const NOTIFY_GENERIC: u32 = 1 << 2;
+const NOTIFY_STRING: u32 = 1 << 3;
+const NOTIFY_LIST: u32 = 1 << 4;
+const NOTIFY_SET: u32 = 1 << 5;
+const NOTIFY_HASH: u32 = 1 << 6;
+const NOTIFY_ZSET: u32 = 1 << 7;
+const NOTIFY_EXPIRED: u32 = 1 << 8;
+const NOTIFY_EVICTED: u32 = 1 << 9;
+const NOTIFY_STREAM: u32 = 1 << 10;
+const NOTIFY_KEY_MISS: u32 = 1 << 11;
+const NOTIFY_NEW: u32 = 1 << 12;
+const NOTIFY_ALL: u32 = NOTIFY_GENERIC
+    | NOTIFY_STRING
+    | NOTIFY_LIST
+    | NOTIFY_SET
+    | NOTIFY_HASH
+    | NOTIFY_ZSET
+    | NOTIFY_EXPIRED
+    | NOTIFY_EVICTED
+    | NOTIFY_STREAM;
+
+lazy_static! {
+    static ref NOTIFY_FLAGS: u32 = parse_flags(&config_notify_keyspace_events_or_default());
+    static ref NOTIFY_DB: RwLock<Option<Db>> = RwLock::new(None);
+}
+
+/// Flags of the class letters, no notification if a letter is unknown
+fn parse_flags(classes: &str) -> u32 {
+    let mut flags = 0;
+    for c in classes.chars() {
+        flags |= match c {
+            'A' => NOTIFY_ALL,
+            'K' => NOTIFY_KEYSPACE,
+            'E' => NOTIFY_KEYEVENT,
+            'g' => NOTIFY_GENERIC,
+            '$' => NOTIFY_STRING,
+            'l' => NOTIFY_LIST,
+            's' => NOTIFY_SET,
+            'h' => NOTIFY_HASH,
+            'z' => NOTIFY_ZSET,
+            'x' => NOTIFY_EXPIRED,
+            'e' => NOTIFY_EVICTED,
+            't' => NOTIFY_STREAM,
+            'm' => NOTIFY_KEY_MISS,
+            'n' => NOTIFY_NEW,
+            _ => {
+                error!(
+                    LOGGER,
+                    "[NOTIFY] invalid notify_keyspace_events {}, notifications disabled", classes
+                );
+                return 0;
+            }
+        };
+    }
+    // without a channel or a class nothing is ever published
+    let channels = NOTIFY_KEYSPACE | NOTIFY_KEYEVENT;
+    if flags & channels == 0 || flags & !channels == 0 {
+        return 0;
+    }
+    flags
+}
+
+/// Class and Redis event name of the events of a write command or key removal
+fn events_of(event: &str) -> &'static [(u32, &'static str)] {
+    match event {
+        "set" | "setnx" | "mset" | "bitop" => &[(NOTIFY_STRING, "set")],
+        "setex" => &[(NOTIFY_STRING, "set"), (NOTIFY_GENERIC, "expire")],
+        "incr" | "decr" | "incrby" | "decrby" => &[(NOTIFY_STRING, "incrby")],
+        "append" => &[(NOTIFY_STRING, "append")],
+        "setrange" => &[(NOTIFY_STRING, "setrange")],
+        "setbit" | "bitfield" => &[(NOTIFY_STRING, "setbit")],
+        "del" | "getdel" => &[(NOTIFY_GENERIC, "del")],
+        "expire" | "expireat" | "pexpire" | "pexpireat" | "getex" => &[(NOTIFY_GENERIC, "expire")],
+        "persist" => &[(NOTIFY_GENERIC, "persist")],
+        "hset" | "hmset" | "hsetnx" => &[(NOTIFY_HASH, "hset")],
+        "hdel" => &[(NOTIFY_HASH, "hdel")],
+        "hincrby" => &[(NOTIFY_HASH, "hincrby")],
+        "lpush" => &[(NOTIFY_LIST, "lpush")],
+        "rpush" => &[(NOTIFY_LIST, "rpush")],
+        "lpop" => &[(NOTIFY_LIST, "lpop")],
+        "rpop" => &[(NOTIFY_LIST, "rpop")],
+        "lset" => &[(NOTIFY_LIST, "lset")],
+        "ltrim" => &[(NOTIFY_LIST, "ltrim")],
+        "lrem" => &[(NOTIFY_LIST, "lrem")],
+        "linsert" => &[(NOTIFY_LIST, "linsert")],
+        "sadd" => &[(NOTIFY_SET, "sadd")],
+        "srem" => &[(NOTIFY_SET, "srem")],
+        "spop" | "sdrain" => &[(NOTIFY_SET, "spop")],
+        "zadd" => &[(NOTIFY_ZSET, "zadd")],
+        "zincrby" => &[(NOTIFY_ZSET, "zincr")],
+        "zrem" => &[(NOTIFY_ZSET, "zrem")],
+        "zremrangebyscore" => &[(NOTIFY_ZSET, "zremrangebyscore")],
+        "zremrangebyrank" => &[(NOTIFY_ZSET, "zremrangebyrank")],
+        "zpopmin" => &[(NOTIFY_ZSET, "zpopmin")],
+        "zpopmax" => &[(NOTIFY_ZSET, "zpopmax")],
+        "zrangestore" => &[(NOTIFY_ZSET, "zrangestore")],
+        "zinterstore" => &[(NOTIFY_ZSET, "zinterstore")],
+        "zunionstore" => &[(NOTIFY_ZSET, "zunionstore")],
+        "zdiffstore" => &[(NOTIFY_ZSET, "zdiffstore")],
+        "geoadd" => &[(NOTIFY_ZSET, "zadd")],
+        "xadd" => &[(NOTIFY_STREAM, "xadd")],
+        "sort" => &[(NOTIFY_LIST, "sortstore")],
+        "copy" => &[(NOTIFY_GENERIC, "copy_to")],
+        "tidis.move" => &[(NOTIFY_GENERIC, "move_from")],
+        "restore" => &[(NOTIFY_GENERIC, "restore")],
+        "migrate" => &[(NOTIFY_GENERIC, "del")],
+        "expired" => &[(NOTIFY_EXPIRED, "expired")],
+        "evicted" => &[(NOTIFY_EVICTED, "evicted")],
+        _ => &[],
+    }
+}
+
+pub fn notify_enabled() -> bool {
+    *NOTIFY_FLAGS != 0
+}
+
+/// Publish the notifications on the broker of `db`
+pub fn init_notifications(db: Db) {
+    if notify_enabled() {
+        NOTIFY_DB.write().unwrap().replace(db);
+    }
+}
+
+/// Publish the keyspace notifications of `event` on `key` selected by the
+/// configured classes
+pub fn notify_keyspace_event(event: &str, key: &str) {
+    if !notify_enabled() {
+        return;
+    }
+    let db = match &*NOTIFY_DB.read().unwrap() {
+        Some(db) => db.clone(),
+        None => return,
+    };
+    for (class, name) in events_of(event) {
+        if *NOTIFY_FLAGS & class == 0 {
+            continue;
+        }
+        if *NOTIFY_FLAGS & NOTIFY_KEYSPACE != 0 {
+            db.publish(
+                &format!("__keyspace@0__:{}", key),
+                Bytes::from_static(name.as_bytes()),
+            );
+        }
+        if *NOTIFY_FLAGS & NOTIFY_KEYEVENT != 0 {
+            db.publish(
+                &format!("__keyevent@0__:{}", name),
+                Bytes::from(key.to_owned()),
+            );
+        }
+    }
+}
@@ -0,0 +1,48 @@
+//! Connections of the server to other Redis or tidis instances, the target
+//! of MIGRATE. Commands are sent as RESP arrays of bulk strings and wait for
+//! their reply, each within the timeout of the connection.
+
+use std::time::Duration;
+
+use async_std::net::TcpStream;
+use bytes::Bytes;
+
+use crate::{Connection, Frame};
+
+pub struct OutboundClient {
+    conn: Connection,
+    timeout: Duration,
+}
+
+impl OutboundClient {
+    /// Connect to `host:port` within `timeout`, which bounds each command
+    /// later on too
+    pub async fn connect(host: &str, port: u16, timeout: Duration) -> crate::Result<Self> {
+        let socket = match tokio::time::timeout(timeout, TcpStream::connect((host, port))).await {
+            Ok(socket) => socket?,
+            Err(_) => return Err("connect timeout".into()),
+        };
+        socket.set_nodelay(true)?;
+        Ok(OutboundClient {
+            conn: Connection::new(socket),
+            timeout,
+        })
+    }
+
+    /// Send the command and read its reply
+    pub async fn call(&mut self, args: Vec<Bytes>) -> crate::Result<Frame> {
+        let request = Frame::Array(args.into_iter().map(Frame::Bulk).collect());
+        let conn = &mut self.conn;
+        let reply = tokio::time::timeout(self.timeout, async move {
+            conn.write_frame(&request).await?;
+            conn.read_frame().await
+        })
+        .await;
+        match reply {
+            Ok(Ok(Some(frame))) => Ok(frame),
+            Ok(Ok(None)) => Err("connection closed by the target".into()),
+            Ok(Err(e)) => Err(e),
+            Err(_) => Err("read timeout".into()),
+        }
+    }
+}
@@ -122,6 +122,11 @@ impl Parse {
         }
     }
 
+    /// Number of entries not consumed yet
+    pub(crate) fn remaining(&self) -> usize {
+        self.parts.len()
+    }
+
     pub(crate) fn check_finish(&mut self) -> bool {
         self.parts.next().is_none()
     }
@@ -0,0 +1,108 @@
+//! Local TiKV for `tidis-server dev`.
+//!
+//! Connects to the PD listening on the playground port if there is one,
+//! otherwise launches a single node PD and TiKV with `tiup playground`, which
+//! is torn down when the server exits.
+
+use std::process::Stdio;
+use std::time::Duration;
+
+use slog::{info, warn};
+use tokio::net::TcpStream;
+use tokio::process::{Child, Command};
+use tokio::time::Instant;
+
+use crate::config::LOGGER;
+
+const PLAYGROUND_PD_ADDR: &str = "127.0.0.1:2379";
+const PLAYGROUND_TAG: &str = "tidis-dev";
+const STARTUP_TIMEOUT: u64 = 120000;
+const SHUTDOWN_TIMEOUT: u64 = 30000;
+
+pub struct Playground {
+    pd_addr: String,
+    child: Option<Child>,
+}
+
+impl Playground {
+    /// Connect to the running playground or launch a new one, returns when
+    /// the PD is reachable.
+    pub async fn start() -> crate::Result<Playground> {
+        if TcpStream::connect(PLAYGROUND_PD_ADDR).await.is_ok() {
+            info!(
+                LOGGER,
+                "dev mode, use the PD running on {}", PLAYGROUND_PD_ADDR
+            );
+            return Ok(Playground {
+                pd_addr: PLAYGROUND_PD_ADDR.to_owned(),
+                child: None,
+            });
+        }
+
+        info!(LOGGER, "dev mode, launching tiup playground");
+        let mut child = Command::new("tiup")
+            .args(&[
+                "playground",
+                "--mode",
+                "tikv-slim",
+                "--pd",
+                "1",
+                "--kv",
+                "1",
+                "--without-monitor",
+                "--tag",
+                PLAYGROUND_TAG,
+            ])
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .kill_on_drop(true)
+            .spawn()
+            .map_err(|e| format!("failed to run tiup, is it installed? {}", e))?;
+
+        let deadline = Instant::now() + Duration::from_millis(STARTUP_TIMEOUT);
+        while TcpStream::connect(PLAYGROUND_PD_ADDR).await.is_err() {
+            if let Some(status) = child.try_wait()? {
+                return Err(format!("tiup playground exited with {}", status).into());
+            }
+            if Instant::now() > deadline {
+                return Err("timeout waiting for tiup playground to start".into());
+            }
+            tokio::time::sleep(Duration::from_millis(500)).await;
+        }
+        info!(
+            LOGGER,
+            "dev mode, playground PD is ready on {}", PLAYGROUND_PD_ADDR
+        );
+
+        Ok(Playground {
+            pd_addr: PLAYGROUND_PD_ADDR.to_owned(),
+            child: Some(child),
+        })
+    }
+
+    pub fn pd_addr(&self) -> &str {
+        &self.pd_addr
+    }
+
+    /// Stop the playground if it was launched by us, tiup cleans up the
+    /// cluster on SIGINT, it is killed if not exited in time.
+    pub async fn stop(mut self) {
+        let mut child = match self.child.take() {
+            Some(child) => child,
+            None => return,
+        };
+        if let Some(pid) = child.id() {
+            let _ = Command::new("kill")
+                .args(&["-INT", &pid.to_string()])
+                .status()
+                .await;
+        }
+        match tokio::time::timeout(Duration::from_millis(SHUTDOWN_TIMEOUT), child.wait()).await {
+            Ok(_) => info!(LOGGER, "dev mode, playground stopped"),
+            Err(_) => {
+                warn!(LOGGER, "dev mode, playground not stopped in time, kill it");
+                let _ = child.kill().await;
+            }
+        }
+    }
+}
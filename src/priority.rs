@@ -0,0 +1,71 @@
+//! Request priority classes.
+//!
+//! Commands are classified as admin, point reads/writes and scans. Scans,
+//! i.e. keyspace scans and whole collection reads, share the permits of
+//! `low_priority_concurrency` across all connections, so background jobs
+//! can't take the backend capacity away from foreground traffic. Admin
+//! commands never touch the storage and are never throttled. The classes are
+//! enforced here, and the storage calls of a command are tagged with its
+//! class, see `crate::tikv::rpclog`, TiKV requests are still sent with the
+//! default priority.
+
+use std::time::Duration;
+
+use tokio::sync::{Semaphore, SemaphorePermit};
+use tokio::time::Instant;
+
+use crate::config_low_priority_concurrency_or_default;
+use crate::metrics::PRIORITY_WAIT_DURATION;
+use crate::server::duration_to_sec;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Priority {
+    /// admin and connection management commands
+    High,
+    /// point reads and writes
+    Normal,
+    /// scans and analytics
+    Low,
+}
+
+impl Priority {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Priority::High => "high",
+            Priority::Normal => "normal",
+            Priority::Low => "low",
+        }
+    }
+}
+
+lazy_static! {
+    static ref LOW_PRIORITY_PERMITS: Option<Semaphore> =
+        match config_low_priority_concurrency_or_default() {
+            0 => None,
+            n => Some(Semaphore::new(n)),
+        };
+}
+
+/// Permits of the low priority commands available, None if not throttled
+pub fn low_priority_permits_available() -> Option<usize> {
+    LOW_PRIORITY_PERMITS
+        .as_ref()
+        .map(|permits| permits.available_permits())
+}
+
+/// Wait for a permit if the priority class is throttled, the command must be
+/// applied while holding the returned permit.
+pub async fn acquire_permit(priority: Priority) -> Option<SemaphorePermit<'static>> {
+    let permits = match (priority, LOW_PRIORITY_PERMITS.as_ref()) {
+        (Priority::Low, Some(permits)) => permits,
+        _ => return None,
+    };
+
+    let start_at = Instant::now();
+    let permit = permits.acquire().await.ok();
+    let duration: Duration = Instant::now() - start_at;
+    PRIORITY_WAIT_DURATION
+        .with_label_values(&[priority.as_str()])
+        .observe(duration_to_sec(duration));
+    permit
+}
@@ -0,0 +1,111 @@
+//! HAProxy PROXY protocol v1 and v2 header parsing.
+//!
+//! When tidis sits behind a L4 load balancer, the header sent ahead of the
+//! client data carries the real client address, which is then used for
+//! CLIENT LIST, ip filter and logs instead of the load balancer address.
+//! See https://www.haproxy.org/download/2.6/doc/proxy-protocol.txt
+
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::time::Duration;
+
+use async_std::net::TcpStream;
+use futures::AsyncReadExt;
+
+const V1_PREFIX: &[u8] = b"PROXY";
+const V1_MAX_LEN: usize = 107;
+const V2_SIGNATURE: &[u8] = b"\r\n\r\n\0\r\nQUIT\n";
+const HEADER_TIMEOUT: u64 = 3000;
+
+/// Read the PROXY protocol header from the stream, must be called before any
+/// other data is read. Returns the source address of the proxied connection,
+/// or None for `UNKNOWN`/`LOCAL` header, e.g. health checks of the balancer.
+pub async fn read_proxy_header(stream: &TcpStream) -> crate::Result<Option<SocketAddr>> {
+    match tokio::time::timeout(
+        Duration::from_millis(HEADER_TIMEOUT),
+        read_proxy_header_inner(stream),
+    )
+    .await
+    {
+        Ok(res) => res,
+        Err(_) => Err("proxy protocol header timeout".into()),
+    }
+}
+
+async fn read_proxy_header_inner(mut stream: &TcpStream) -> crate::Result<Option<SocketAddr>> {
+    let mut prefix = [0u8; 5];
+    stream.read_exact(&mut prefix).await?;
+    if prefix == V1_PREFIX {
+        read_v1(stream, &prefix).await
+    } else if prefix == V2_SIGNATURE[..5] {
+        read_v2(stream).await
+    } else {
+        Err("invalid proxy protocol header".into())
+    }
+}
+
+async fn read_v1(mut stream: &TcpStream, prefix: &[u8]) -> crate::Result<Option<SocketAddr>> {
+    let mut line = prefix.to_vec();
+    let mut byte = [0u8; 1];
+    // the header is terminated by CRLF, read byte by byte to not consume client data
+    while !line.ends_with(b"\r\n") {
+        if line.len() >= V1_MAX_LEN {
+            return Err("proxy protocol v1 header too long".into());
+        }
+        stream.read_exact(&mut byte).await?;
+        line.push(byte[0]);
+    }
+
+    let line = std::str::from_utf8(&line[..line.len() - 2])?;
+    let parts: Vec<&str> = line.split(' ').collect();
+    match parts.get(1) {
+        Some(&"UNKNOWN") => Ok(None),
+        Some(&"TCP4") | Some(&"TCP6") if parts.len() == 6 => {
+            let ip = parts[2].parse::<IpAddr>()?;
+            let port = parts[4].parse::<u16>()?;
+            Ok(Some(SocketAddr::new(ip, port)))
+        }
+        _ => Err("invalid proxy protocol v1 header".into()),
+    }
+}
+
+async fn read_v2(mut stream: &TcpStream) -> crate::Result<Option<SocketAddr>> {
+    let mut header = [0u8; 11];
+    stream.read_exact(&mut header).await?;
+    if header[..7] != V2_SIGNATURE[5..] {
+        return Err("invalid proxy protocol v2 signature".into());
+    }
+    let ver_cmd = header[7];
+    let family = header[8];
+    let len = u16::from_be_bytes([header[9], header[10]]) as usize;
+    let mut body = vec![0u8; len];
+    stream.read_exact(&mut body).await?;
+
+    if ver_cmd >> 4 != 2 {
+        return Err("unsupported proxy protocol version".into());
+    }
+    // LOCAL command, connection established by the proxy itself
+    if ver_cmd & 0x0f == 0 {
+        return Ok(None);
+    }
+    match family >> 4 {
+        // AF_INET: src addr, dst addr, src port, dst port
+        1 if len >= 12 => {
+            let ip = Ipv4Addr::new(body[0], body[1], body[2], body[3]);
+            let port = u16::from_be_bytes([body[8], body[9]]);
+            Ok(Some(SocketAddr::new(IpAddr::V4(ip), port)))
+        }
+        // AF_INET6
+        2 if len >= 36 => {
+            let mut octets = [0u8; 16];
+            octets.copy_from_slice(&body[..16]);
+            let port = u16::from_be_bytes([body[32], body[33]]);
+            Ok(Some(SocketAddr::new(
+                IpAddr::V6(Ipv6Addr::from(octets)),
+                port,
+            )))
+        }
+        // AF_UNSPEC or AF_UNIX, keep the socket address
+        0 | 3 => Ok(None),
+        _ => Err("invalid proxy protocol v2 address".into()),
+    }
+}
@@ -0,0 +1,492 @@
+//! Serialized values of DUMP and RESTORE.
+//!
+//! The payload is the one of Redis, so tools moving data between Redis
+//! servers move it in and out of tidis too: the RDB encoding of the value,
+//! the RDB version it was written with on 2 bytes and the CRC64 (Jones) of
+//! both, little endian. Values are dumped with the plain encodings every
+//! Redis since 6.0 restores. They are restored from any encoding of strings,
+//! lists, sets, sorted sets and hashes up to RDB 12, the compact ones
+//! (ziplist, listpack, intset and quicklist) and LZF compressed strings
+//! included, but not from the encodings of streams and modules.
+
+use std::convert::TryInto;
+
+use crc::{Algorithm, Crc};
+
+use crate::config_restore_skip_zero_checksum_or_default;
+use crate::tikv::errors::{RTError, REDIS_BAD_DATA_FORMAT_ERR, REDIS_DUMP_PAYLOAD_ERR};
+
+/// RDB version of the dumped payloads, the one of Redis 6.0 so older servers
+/// restore them too
+pub const RDB_VERSION: u16 = 9;
+/// Latest RDB version restored, the one of Redis 7.4
+const RDB_VERSION_MAX: u16 = 12;
+
+const RDB_TYPE_STRING: u8 = 0;
+const RDB_TYPE_LIST: u8 = 1;
+const RDB_TYPE_SET: u8 = 2;
+const RDB_TYPE_ZSET: u8 = 3;
+const RDB_TYPE_HASH: u8 = 4;
+const RDB_TYPE_ZSET_2: u8 = 5;
+const RDB_TYPE_LIST_ZIPLIST: u8 = 10;
+const RDB_TYPE_SET_INTSET: u8 = 11;
+const RDB_TYPE_ZSET_ZIPLIST: u8 = 12;
+const RDB_TYPE_HASH_ZIPLIST: u8 = 13;
+const RDB_TYPE_LIST_QUICKLIST: u8 = 14;
+const RDB_TYPE_HASH_LISTPACK: u8 = 16;
+const RDB_TYPE_ZSET_LISTPACK: u8 = 17;
+const RDB_TYPE_LIST_QUICKLIST_2: u8 = 18;
+const RDB_TYPE_SET_LISTPACK: u8 = 20;
+
+/// The nodes of a quicklist 2
+const QUICKLIST_NODE_PLAIN: usize = 1;
+const QUICKLIST_NODE_PACKED: usize = 2;
+
+/// End of ziplists and listpacks
+const PACK_END: u8 = 0xff;
+
+/// The most bytes an LZF compressed byte expands to, a back reference of 3
+/// bytes expands to 264
+const LZF_MAX_EXPANSION: usize = 88;
+
+const CRC_64_JONES: Algorithm<u64> = Algorithm {
+    poly: 0xad93d23594c935a9,
+    init: 0,
+    refin: true,
+    refout: true,
+    xorout: 0,
+    check: 0xe9c6d914c4b8d9ca,
+    residue: 0,
+};
+const CRC64: Crc<u64> = Crc::<u64>::new(&CRC_64_JONES);
+
+/// A value of any type DUMP and RESTORE support
+#[derive(Debug, Clone, PartialEq)]
+pub enum RdbValue {
+    String(Vec<u8>),
+    List(Vec<Vec<u8>>),
+    Set(Vec<Vec<u8>>),
+    Zset(Vec<(Vec<u8>, f64)>),
+    Hash(Vec<(Vec<u8>, Vec<u8>)>),
+}
+
+fn put_len(out: &mut Vec<u8>, len: usize) {
+    let len = len as u64;
+    if len < 1 << 6 {
+        out.push(len as u8);
+    } else if len < 1 << 14 {
+        out.push(0x40 | (len >> 8) as u8);
+        out.push(len as u8);
+    } else if len <= u32::MAX as u64 {
+        out.push(0x80);
+        out.extend_from_slice(&(len as u32).to_be_bytes());
+    } else {
+        out.push(0x81);
+        out.extend_from_slice(&len.to_be_bytes());
+    }
+}
+
+fn put_string(out: &mut Vec<u8>, s: &[u8]) {
+    put_len(out, s.len());
+    out.extend_from_slice(s);
+}
+
+/// The DUMP payload of the value
+pub fn dump(value: &RdbValue) -> Vec<u8> {
+    let mut out = vec![];
+    match value {
+        RdbValue::String(v) => {
+            out.push(RDB_TYPE_STRING);
+            put_string(&mut out, v);
+        }
+        RdbValue::List(items) | RdbValue::Set(items) => {
+            let rdb_type = match value {
+                RdbValue::List(_) => RDB_TYPE_LIST,
+                _ => RDB_TYPE_SET,
+            };
+            out.push(rdb_type);
+            put_len(&mut out, items.len());
+            for item in items {
+                put_string(&mut out, item);
+            }
+        }
+        RdbValue::Zset(members) => {
+            out.push(RDB_TYPE_ZSET_2);
+            put_len(&mut out, members.len());
+            for (member, score) in members {
+                put_string(&mut out, member);
+                out.extend_from_slice(&score.to_le_bytes());
+            }
+        }
+        RdbValue::Hash(fvs) => {
+            out.push(RDB_TYPE_HASH);
+            put_len(&mut out, fvs.len());
+            for (field, value) in fvs {
+                put_string(&mut out, field);
+                put_string(&mut out, value);
+            }
+        }
+    }
+    out.extend_from_slice(&RDB_VERSION.to_le_bytes());
+    let crc = CRC64.checksum(&out);
+    out.extend_from_slice(&crc.to_le_bytes());
+    out
+}
+
+/// The value of a RESTORE payload. A payload with a checksum of 0 is not
+/// checked, like in Redis, only if `restore_skip_zero_checksum` is set.
+pub fn load(payload: &[u8]) -> Result<RdbValue, RTError> {
+    if payload.len() < 10 {
+        return Err(REDIS_DUMP_PAYLOAD_ERR);
+    }
+    let (body, footer) = payload.split_at(payload.len() - 10);
+    let version = u16::from_le_bytes([footer[0], footer[1]]);
+    let crc = u64::from_le_bytes(footer[2..].try_into().unwrap());
+    let unchecked = crc == 0 && config_restore_skip_zero_checksum_or_default();
+    if version > RDB_VERSION_MAX
+        || (!unchecked && crc != CRC64.checksum(&payload[..payload.len() - 8]))
+    {
+        return Err(REDIS_DUMP_PAYLOAD_ERR);
+    }
+
+    let value = Reader { buf: body }
+        .value()
+        .ok_or(REDIS_BAD_DATA_FORMAT_ERR)?;
+    // there are no empty keys
+    let empty = match &value {
+        RdbValue::String(_) => false,
+        RdbValue::List(items) | RdbValue::Set(items) => items.is_empty(),
+        RdbValue::Zset(members) => members.is_empty(),
+        RdbValue::Hash(fvs) => fvs.is_empty(),
+    };
+    if empty {
+        return Err(REDIS_BAD_DATA_FORMAT_ERR);
+    }
+    Ok(value)
+}
+
+struct Reader<'a> {
+    buf: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, n: usize) -> Option<&'a [u8]> {
+        if self.buf.len() < n {
+            return None;
+        }
+        let (head, rest) = self.buf.split_at(n);
+        self.buf = rest;
+        Some(head)
+    }
+
+    fn byte(&mut self) -> Option<u8> {
+        self.take(1).map(|b| b[0])
+    }
+
+    fn int(&mut self, width: usize) -> Option<i64> {
+        let b = self.take(width)?;
+        match width {
+            1 => Some(b[0] as i8 as i64),
+            2 => Some(i16::from_le_bytes(b.try_into().ok()?) as i64),
+            // sign extended from the top of an i32
+            3 => Some((i32::from_le_bytes([0, b[0], b[1], b[2]]) >> 8) as i64),
+            4 => Some(i32::from_le_bytes(b.try_into().ok()?) as i64),
+            8 => Some(i64::from_le_bytes(b.try_into().ok()?)),
+            _ => None,
+        }
+    }
+
+    /// A length, or the special encoding of a string when the flag is set
+    fn len_or_encoding(&mut self) -> Option<(u64, bool)> {
+        let first = self.byte()?;
+        match first >> 6 {
+            0 => Some(((first & 0x3f) as u64, false)),
+            1 => Some(((((first & 0x3f) as u64) << 8) | self.byte()? as u64, false)),
+            2 => match first {
+                0x80 => Some((
+                    u32::from_be_bytes(self.take(4)?.try_into().ok()?) as u64,
+                    false,
+                )),
+                0x81 => Some((u64::from_be_bytes(self.take(8)?.try_into().ok()?), false)),
+                _ => None,
+            },
+            _ => Some(((first & 0x3f) as u64, true)),
+        }
+    }
+
+    fn len(&mut self) -> Option<usize> {
+        match self.len_or_encoding()? {
+            (len, false) => len.try_into().ok(),
+            _ => None,
+        }
+    }
+
+    fn string(&mut self) -> Option<Vec<u8>> {
+        match self.len_or_encoding()? {
+            (0, true) => Some(self.int(1)?.to_string().into_bytes()),
+            (1, true) => Some(self.int(2)?.to_string().into_bytes()),
+            (2, true) => Some(self.int(4)?.to_string().into_bytes()),
+            (3, true) => {
+                let compressed_len = self.len()?;
+                let len = self.len()?;
+                lzf_decompress(self.take(compressed_len)?, len)
+            }
+            (_, true) => None,
+            (len, false) => Some(self.take(len.try_into().ok()?)?.to_vec()),
+        }
+    }
+
+    fn strings(&mut self) -> Option<Vec<Vec<u8>>> {
+        let len = self.len()?;
+        let mut items = Vec::with_capacity(len.min(self.buf.len()));
+        for _ in 0..len {
+            items.push(self.string()?);
+        }
+        Some(items)
+    }
+
+    /// A score of the first sorted set encoding, as a string
+    fn string_double(&mut self) -> Option<f64> {
+        match self.byte()? {
+            253 => Some(f64::NAN),
+            254 => Some(f64::INFINITY),
+            255 => Some(f64::NEG_INFINITY),
+            len => std::str::from_utf8(self.take(len as usize)?)
+                .ok()?
+                .parse()
+                .ok(),
+        }
+    }
+
+    fn binary_double(&mut self) -> Option<f64> {
+        Some(f64::from_le_bytes(self.take(8)?.try_into().ok()?))
+    }
+
+    fn value(&mut self) -> Option<RdbValue> {
+        let rdb_type = self.byte()?;
+        let value = match rdb_type {
+            RDB_TYPE_STRING => RdbValue::String(self.string()?),
+            RDB_TYPE_LIST => RdbValue::List(self.strings()?),
+            RDB_TYPE_SET => RdbValue::Set(self.strings()?),
+            RDB_TYPE_ZSET | RDB_TYPE_ZSET_2 => {
+                let len = self.len()?;
+                let mut members = Vec::with_capacity(len.min(self.buf.len()));
+                for _ in 0..len {
+                    let member = self.string()?;
+                    let score = if rdb_type == RDB_TYPE_ZSET {
+                        self.string_double()?
+                    } else {
+                        self.binary_double()?
+                    };
+                    members.push((member, score));
+                }
+                RdbValue::Zset(members)
+            }
+            RDB_TYPE_HASH => RdbValue::Hash(pairs(self.strings()?)?),
+            RDB_TYPE_LIST_ZIPLIST => RdbValue::List(ziplist_entries(&self.string()?)?),
+            RDB_TYPE_SET_INTSET => RdbValue::Set(intset_entries(&self.string()?)?),
+            RDB_TYPE_SET_LISTPACK => RdbValue::Set(listpack_entries(&self.string()?)?),
+            RDB_TYPE_ZSET_ZIPLIST => RdbValue::Zset(scored(ziplist_entries(&self.string()?)?)?),
+            RDB_TYPE_ZSET_LISTPACK => RdbValue::Zset(scored(listpack_entries(&self.string()?)?)?),
+            RDB_TYPE_HASH_ZIPLIST => RdbValue::Hash(pairs(ziplist_entries(&self.string()?)?)?),
+            RDB_TYPE_HASH_LISTPACK => RdbValue::Hash(pairs(listpack_entries(&self.string()?)?)?),
+            RDB_TYPE_LIST_QUICKLIST => {
+                let mut items = vec![];
+                for _ in 0..self.len()? {
+                    items.extend(ziplist_entries(&self.string()?)?);
+                }
+                RdbValue::List(items)
+            }
+            RDB_TYPE_LIST_QUICKLIST_2 => {
+                let mut items = vec![];
+                for _ in 0..self.len()? {
+                    let container = self.len()?;
+                    let node = self.string()?;
+                    match container {
+                        QUICKLIST_NODE_PLAIN => items.push(node),
+                        QUICKLIST_NODE_PACKED => items.extend(listpack_entries(&node)?),
+                        _ => return None,
+                    }
+                }
+                RdbValue::List(items)
+            }
+            _ => return None,
+        };
+        Some(value)
+    }
+}
+
+/// The entries of a flat list of fields and values
+fn pairs(entries: Vec<Vec<u8>>) -> Option<Vec<(Vec<u8>, Vec<u8>)>> {
+    if entries.len() % 2 != 0 {
+        return None;
+    }
+    let mut iter = entries.into_iter();
+    let mut pairs = vec![];
+    while let (Some(first), Some(second)) = (iter.next(), iter.next()) {
+        pairs.push((first, second));
+    }
+    Some(pairs)
+}
+
+/// The entries of a flat list of members and scores
+fn scored(entries: Vec<Vec<u8>>) -> Option<Vec<(Vec<u8>, f64)>> {
+    pairs(entries)?
+        .into_iter()
+        .map(|(member, score)| {
+            let score = std::str::from_utf8(&score).ok()?.parse().ok()?;
+            Some((member, score))
+        })
+        .collect()
+}
+
+fn ziplist_entries(zl: &[u8]) -> Option<Vec<Vec<u8>>> {
+    let mut r = Reader { buf: zl };
+    // zlbytes, zltail and zllen
+    r.take(10)?;
+    let mut entries = vec![];
+    loop {
+        match r.byte()? {
+            PACK_END => return Some(entries),
+            0xfe => {
+                r.take(4)?;
+            }
+            _ => {}
+        }
+        let encoding = r.byte()?;
+        let entry = match encoding >> 6 {
+            0 => r.take((encoding & 0x3f) as usize)?.to_vec(),
+            1 => {
+                let len = (((encoding & 0x3f) as usize) << 8) | r.byte()? as usize;
+                r.take(len)?.to_vec()
+            }
+            2 => {
+                let len = u32::from_be_bytes(r.take(4)?.try_into().ok()?);
+                r.take(len as usize)?.to_vec()
+            }
+            _ => {
+                let v = match encoding {
+                    0xc0 => r.int(2)?,
+                    0xd0 => r.int(4)?,
+                    0xe0 => r.int(8)?,
+                    0xf0 => r.int(3)?,
+                    0xfe => r.int(1)?,
+                    // 4 bits immediate between 1 and 13 for 0 to 12
+                    0xf1..=0xfd => (encoding & 0x0f) as i64 - 1,
+                    _ => return None,
+                };
+                v.to_string().into_bytes()
+            }
+        };
+        entries.push(entry);
+    }
+}
+
+fn listpack_entries(lp: &[u8]) -> Option<Vec<Vec<u8>>> {
+    let mut r = Reader { buf: lp };
+    // total bytes and number of elements
+    r.take(6)?;
+    let mut entries = vec![];
+    loop {
+        let encoding = r.byte()?;
+        if encoding == PACK_END {
+            return Some(entries);
+        }
+        let (entry, size) = if encoding & 0x80 == 0 {
+            ((encoding as i64).to_string().into_bytes(), 1)
+        } else if encoding & 0xc0 == 0x80 {
+            let len = (encoding & 0x3f) as usize;
+            (r.take(len)?.to_vec(), 1 + len)
+        } else if encoding & 0xe0 == 0xc0 {
+            // 13 bits signed
+            let mut v = (((encoding & 0x1f) as i64) << 8) | r.byte()? as i64;
+            if v >= 1 << 12 {
+                v -= 1 << 13;
+            }
+            (v.to_string().into_bytes(), 2)
+        } else if encoding & 0xf0 == 0xe0 {
+            let len = (((encoding & 0x0f) as usize) << 8) | r.byte()? as usize;
+            (r.take(len)?.to_vec(), 2 + len)
+        } else {
+            match encoding {
+                0xf0 => {
+                    let len = u32::from_le_bytes(r.take(4)?.try_into().ok()?) as usize;
+                    (r.take(len)?.to_vec(), 5 + len)
+                }
+                0xf1 => (r.int(2)?.to_string().into_bytes(), 3),
+                0xf2 => (r.int(3)?.to_string().into_bytes(), 4),
+                0xf3 => (r.int(4)?.to_string().into_bytes(), 5),
+                0xf4 => (r.int(8)?.to_string().into_bytes(), 9),
+                _ => return None,
+            }
+        };
+        // the length of the entry backwards
+        let backlen = match size {
+            s if s <= 127 => 1,
+            s if s < 16383 => 2,
+            s if s < 2097151 => 3,
+            s if s < 268435455 => 4,
+            _ => 5,
+        };
+        r.take(backlen)?;
+        entries.push(entry);
+    }
+}
+
+fn intset_entries(is: &[u8]) -> Option<Vec<Vec<u8>>> {
+    let mut r = Reader { buf: is };
+    let width = u32::from_le_bytes(r.take(4)?.try_into().ok()?) as usize;
+    let len = u32::from_le_bytes(r.take(4)?.try_into().ok()?) as usize;
+    if !matches!(width, 2 | 4 | 8) {
+        return None;
+    }
+    let mut entries = Vec::with_capacity(len.min(r.buf.len()));
+    for _ in 0..len {
+        entries.push(r.int(width)?.to_string().into_bytes());
+    }
+    Some(entries)
+}
+
+/// LZF decompression of the compressed strings of `len` bytes. A length
+/// `input` can't expand to is refused before anything is allocated.
+fn lzf_decompress(input: &[u8], len: usize) -> Option<Vec<u8>> {
+    if len > input.len().saturating_mul(LZF_MAX_EXPANSION) {
+        return None;
+    }
+    let mut out = Vec::with_capacity(len);
+    let mut i = 0;
+    while i < input.len() {
+        let ctrl = input[i] as usize;
+        i += 1;
+        if ctrl < 1 << 5 {
+            // a run of ctrl + 1 literal bytes
+            out.extend_from_slice(input.get(i..i + ctrl + 1)?);
+            i += ctrl + 1;
+        } else {
+            // a back reference of run + 2 bytes
+            let mut run = ctrl >> 5;
+            if run == 7 {
+                run += *input.get(i)? as usize;
+                i += 1;
+            }
+            let back = ((ctrl & 0x1f) << 8) + *input.get(i)? as usize + 1;
+            i += 1;
+            if back > out.len() {
+                return None;
+            }
+            let start = out.len() - back;
+            for k in 0..run + 2 {
+                let b = out[start + k];
+                out.push(b);
+            }
+        }
+        if out.len() > len {
+            return None;
+        }
+    }
+    if out.len() == len {
+        Some(out)
+    } else {
+        None
+    }
+}
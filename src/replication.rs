@@ -0,0 +1,45 @@
+//! Replication metadata reported in `INFO replication`.
+//!
+//! Tidis has no replicas of its own, the data is replicated by TiKV, but
+//! tools like redis-shake inspect the replication id and offset before they
+//! attach. The id is random per process and rotated with
+//! `DEBUG CHANGE-REPL-ID`, the offset counts the write commands applied by
+//! this instance, the same commands shipped to the command log.
+
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::RwLock;
+
+use rand::Rng;
+
+lazy_static! {
+    static ref REPL_ID: RwLock<String> = RwLock::new(new_repl_id());
+}
+
+static REPL_OFFSET: AtomicU64 = AtomicU64::new(0);
+
+const NO_REPL_ID: &str = "0000000000000000000000000000000000000000";
+
+fn new_repl_id() -> String {
+    hex::encode(rand::thread_rng().gen::<[u8; 20]>())
+}
+
+/// Advance the offset by a write command applied
+pub fn incr_repl_offset() {
+    REPL_OFFSET.fetch_add(1, Ordering::Relaxed);
+}
+
+/// Replace the replication id with a new random one
+pub fn change_repl_id() {
+    *REPL_ID.write().unwrap() = new_repl_id();
+}
+
+pub fn encode_replication_info() -> String {
+    format!(
+        "# Replication\r\nrole:master\r\nconnected_slaves:0\r\nmaster_failover_state:no-failover\r\n\
+        master_replid:{}\r\nmaster_replid2:{}\r\nmaster_repl_offset:{}\r\nsecond_repl_offset:-1\r\n\
+        repl_backlog_active:0\r\n",
+        REPL_ID.read().unwrap(),
+        NO_REPL_ID,
+        REPL_OFFSET.load(Ordering::Relaxed)
+    )
+}
@@ -1,23 +1,58 @@
+use crate::access::{accessed_key, track_access};
+use crate::auth::{authenticate, is_revoked, load_revocations};
 use crate::cluster::Cluster;
+use crate::cmdlog::{cmdlog_enabled, frame_args, run_cmdlog_shipper, write_applied};
+use crate::diag::InFlight;
+use crate::dispatch::{write_committed, write_event};
+use crate::frame;
+use crate::gateway::run_http_gateway;
 use crate::gc::GcMaster;
+#[cfg(feature = "grpc")]
+use crate::grpc::run_grpc_server;
+use crate::ipfilter::{init_ip_filter, is_ip_allowed, run_ip_filter_reloader};
+use crate::jobs::start_jobs;
+use crate::logsample::{CommandLog, COMMAND_LOG};
+use crate::memcache::run_memcache_listener;
+use crate::memlimit::{over_soft_limit, run_memory_sampler, wait_under_hard_limit};
 use crate::metrics::{
-    CURRENT_CONNECTION_COUNTER, CURRENT_TLS_CONNECTION_COUNTER, REQUEST_CMD_COUNTER,
-    REQUEST_CMD_ERROR_COUNTER, REQUEST_CMD_FINISH_COUNTER, REQUEST_CMD_HANDLE_TIME,
-    REQUEST_COUNTER, TOTAL_CONNECTION_PROCESSED,
+    CONNECTION_EVENT_COUNTER, CURRENT_CONNECTION_COUNTER, CURRENT_TLS_CONNECTION_COUNTER,
+    DISCONNECTION_COUNTER, MEMORY_SHED_COMMAND_COUNTER, REJECTED_CONNECTION_COUNTER,
+    REQUEST_CMD_COUNTER, REQUEST_CMD_ERROR_COUNTER, REQUEST_CMD_FINISH_COUNTER,
+    REQUEST_CMD_HANDLE_TIME, REQUEST_COUNTER, RESOURCE_GROUP_HANDLE_SECONDS,
+    RESOURCE_GROUP_REQUEST_COUNTER, SNAPSHOT_REUSED_COUNTER, TOTAL_CONNECTION_PROCESSED,
+};
+use crate::notify::{init_notifications, notify_enabled};
+use crate::priority::{acquire_permit, Priority};
+use crate::proxy::read_proxy_header;
+use crate::slo::{check_latency_slo, slo_class};
+use crate::tikv::client::{
+    IDEMPOTENCY_TOKEN, PIPELINE_SNAPSHOT, PRIORITY, RESOURCE_GROUP, STALE_READ,
 };
 use crate::tikv::encoding::KeyDecoder;
+use crate::tikv::format::negotiate_features;
+use crate::tikv::health::run_backend_health_checker;
 use crate::tikv::{get_txn_client, KEY_ENCODER};
+use crate::triggers::{run_trigger_dispatcher, triggers_enabled};
 use crate::utils::{self, resp_err, resp_invalid_arguments, resp_ok, resp_queued, sleep};
+use crate::websocket::accept_handshake;
 use crate::{
-    async_gc_worker_number_or_default, config_cluster_broadcast_addr_or_default,
+    async_gc_worker_number_or_default, backend_idempotency_derive_tokens_or_default,
+    backend_pipeline_snapshot_max_age_ms_or_default, backend_pipeline_snapshot_max_cmds_or_default,
+    config_auth_revoke_mode_or_default, config_cluster_broadcast_addr_or_default,
     config_cluster_topology_expire_or_default, config_cluster_topology_interval_or_default,
-    config_local_pool_number, is_auth_enabled, is_auth_matched, Command, Connection, Db,
-    DbDropGuard, Shutdown,
+    config_local_pool_number, config_pipeline_concurrency_or_default,
+    config_proxy_protocol_or_default, config_resource_group_or_default,
+    config_tls_proxy_protocol_or_default, config_user_resource_group, config_ws_listen_or_default,
+    config_ws_port_or_default, is_auth_enabled, log_max_arg_len, log_max_args, log_redact_key_hash,
+    log_redact_values, Command, Connection, Db, DbDropGuard, Frame, ParseError, Shutdown,
 };
+use std::cell::Cell;
 use std::collections::HashMap;
 
+use crate::tikv::backend::Transaction;
 use async_std::net::{TcpListener, TcpStream};
-use futures::FutureExt;
+use bytes::Bytes;
+use futures::{future, FutureExt};
 use std::future::Future;
 use std::ops::Range;
 use std::sync::Arc;
@@ -34,15 +69,20 @@ use mlua::{HookTriggers, Lua};
 
 use crate::config::LOGGER;
 
-use crate::client::Client;
+use crate::client::{Client, PUSH_CHANNEL_CAPACITY};
 use tokio_util::task::LocalPoolHandle;
 
 use crate::tikv::errors::{
     REDIS_AUTH_INVALID_PASSWORD_ERR, REDIS_AUTH_REQUIRED_ERR, REDIS_AUTH_WHEN_DISABLED_ERR,
-    REDIS_DISCARD_WITHOUT_MULTI_ERR, REDIS_EXEC_WITHOUT_MULTI_ERR, REDIS_MULTI_NESTED_ERR,
+    REDIS_DISCARD_WITHOUT_MULTI_ERR, REDIS_EXEC_ERR, REDIS_EXEC_WITHOUT_MULTI_ERR,
+    REDIS_MEMORY_SOFT_LIMIT_ERR, REDIS_MULTI_NESTED_ERR, REDIS_READONLY_ERR,
+    REDIS_WATCH_INSIDE_MULTI_ERR,
 };
 
-use crate::cmd::{script_clear_killed, script_interuptted};
+use crate::cmd::{script_interrupted, WatchedKey, SCRIPT_ID_REGISTRY_KEY};
+
+/// Time for a websocket client to send its upgrade request
+const WS_HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
 
 /// Server listener state. Created in the `run` call. It includes a `run` method
 /// which performs the TCP listening and initialization of per-connection state.
@@ -109,6 +149,16 @@ struct TlsListener {
     tls_shutdown_complete_rx: mpsc::Receiver<()>,
     tls_shutdown_complete_tx: mpsc::Sender<()>,
 }
+/// RESP over WebSocket, see `crate::websocket`.
+struct WsListener {
+    db_holder: DbDropGuard,
+    topo_holder: Cluster,
+    clients: Arc<Mutex<HashMap<u64, Arc<Mutex<Client>>>>>,
+    ws_listener: TcpListener,
+    ws_notify_shutdown: broadcast::Sender<()>,
+    ws_shutdown_complete_tx: mpsc::Sender<()>,
+}
+
 #[derive(Debug, Clone)]
 struct TopologyManager {
     /// address of this instance
@@ -148,7 +198,32 @@ struct Handler {
 
     /// The txn state of this connection.
     inner_txn: bool,
-    queued_commands: Vec<Command>,
+    /// The commands queued by MULTI, with the arguments of their request.
+    queued_commands: Vec<(Command, Vec<Bytes>)>,
+    /// Keys watched for the next EXEC, cleared by EXEC, DISCARD and UNWATCH.
+    watched_keys: Vec<WatchedKey>,
+    /// Set when a command is rejected while queued by MULTI, EXEC then
+    /// discards the transaction.
+    txn_aborted: bool,
+
+    /// Set by READONLY, reset by READWRITE. Writes are rejected and snapshot
+    /// reads may use a stale timestamp, see `backend.stale_read_ms`.
+    readonly: bool,
+
+    /// Snapshot shared by consecutive reads of a pipeline burst, with the
+    /// time it was taken and the number of commands it served.
+    read_snapshot: Option<(Arc<Mutex<Transaction>>, Instant, u32)>,
+
+    /// Frame read ahead while collecting a batch of pipelined reads, it is
+    /// handled before reading from the connection again.
+    pending_frame: Option<Frame>,
+
+    /// Set by `IDEMPOTENCY <token>`, used by the next command if it writes.
+    idempotency_token: Option<String>,
+
+    /// Out-of-band messages queued by `Client::push`. They are only written
+    /// while waiting for the next request, never in the middle of a reply.
+    push_rx: mpsc::Receiver<Frame>,
 
     /// Max connection semaphore.
     ///
@@ -172,6 +247,10 @@ struct Handler {
     /// set authorized to true after `AUTH password` command executed.
     authorized: bool,
 
+    /// Resource group the requests of this connection are accounted to,
+    /// resolved from the AUTH user.
+    resource_group: String,
+
     /// Lua vm context, lazy initialized when eval/evalsha called
     lua: Option<Lua>,
 
@@ -221,6 +300,48 @@ pub async fn run(
     // a receiver is needed, the subscribe() method on the sender is used to create
     // one.
     let db_holder = DbDropGuard::new();
+    init_notifications(db_holder.db());
+
+    init_ip_filter();
+    tokio::spawn(run_ip_filter_reloader());
+    tokio::spawn(run_backend_health_checker());
+    tokio::spawn(run_cmdlog_shipper());
+    tokio::spawn(run_trigger_dispatcher());
+    tokio::spawn(run_memory_sampler());
+    tokio::spawn(run_memcache_listener());
+    tokio::spawn(run_http_gateway());
+    #[cfg(feature = "grpc")]
+    tokio::spawn(run_grpc_server());
+    // like the other side listeners the websocket clients are not drained on
+    // shutdown, they are closed with the process
+    if config_ws_port_or_default() != 0 {
+        let addr = format!(
+            "{}:{}",
+            config_ws_listen_or_default(),
+            config_ws_port_or_default()
+        );
+        match TcpListener::bind(&addr).await {
+            Ok(ws_listener) => {
+                info!(LOGGER, "[WebSocket] Server Listen on: {}", addr);
+                let (ws_notify_shutdown, _) = broadcast::channel(1);
+                let (ws_shutdown_complete_tx, _) = mpsc::channel(1);
+                let mut ws_server = WsListener {
+                    db_holder: db_holder.clone(),
+                    topo_holder: topo_holder.clone(),
+                    clients: Arc::new(Mutex::new(HashMap::new())),
+                    ws_listener,
+                    ws_notify_shutdown,
+                    ws_shutdown_complete_tx,
+                };
+                tokio::spawn(async move {
+                    if let Err(err) = ws_server.run().await {
+                        error!(LOGGER, "[WebSocket] failed to accept, cause {}", err);
+                    }
+                });
+            }
+            Err(e) => error!(LOGGER, "[WebSocket] failed to listen on {}, {}", addr, e),
+        }
+    }
 
     let topo_manager = TopologyManager {
         address: topo_addr,
@@ -231,6 +352,7 @@ pub async fn run(
 
     let mut gc_master = GcMaster::new(async_gc_worker_number_or_default(), topo_holder.clone());
     gc_master.start_workers().await;
+    start_jobs(topo_holder.clone());
 
     if tcp_enabled && !tls_enabled {
         let (notify_shutdown, _) = broadcast::channel(1);
@@ -435,9 +557,20 @@ impl Listener {
             // Accept a new socket. This will attempt to perform error handling.
             // The `accept` method internally attempts to recover errors, so an
             // error here is non-recoverable.
+            wait_under_hard_limit().await;
             let socket = self.accept().await?;
+            // with proxy protocol the real client address is checked in handler task
+            let proxy_protocol = config_proxy_protocol_or_default();
+            if !proxy_protocol {
+                match socket.peer_addr() {
+                    Ok(addr) if is_ip_allowed(&addr.ip()) => {}
+                    _ => continue,
+                }
+            }
+            let proxy_socket = socket.clone();
             let (kill_tx, kill_rx) = mpsc::channel(1);
-            let client = Client::new(socket.clone(), kill_tx);
+            let (push_tx, push_rx) = mpsc::channel(PUSH_CHANNEL_CAPACITY);
+            let client = Client::new(socket.clone(), kill_tx, push_tx);
             let client_id = client.id();
             let arc_client = Arc::new(Mutex::new(client));
             self.clients
@@ -460,6 +593,13 @@ impl Listener {
 
                 inner_txn: false,
                 queued_commands: vec![],
+                watched_keys: vec![],
+                txn_aborted: false,
+                readonly: false,
+                read_snapshot: None,
+                pending_frame: None,
+                idempotency_token: None,
+                push_rx,
 
                 // The connection state needs a handle to the max connections
                 // semaphore. When the handler is done processing the
@@ -470,6 +610,7 @@ impl Listener {
                 shutdown: Shutdown::new(self.notify_shutdown.subscribe(), kill_rx),
 
                 authorized: !is_auth_enabled(),
+                resource_group: config_resource_group_or_default(),
 
                 lua: None,
 
@@ -481,8 +622,11 @@ impl Listener {
                 // Process the connection. If an error is encountered, log it.
                 CURRENT_CONNECTION_COUNTER.inc();
                 TOTAL_CONNECTION_PROCESSED.inc();
-                if let Err(err) = handler.run().await {
-                    error!(LOGGER, "connection error {:?}", err);
+                if !proxy_protocol || handler.handle_proxy_header(&proxy_socket).await {
+                    handler.report_connect();
+                    let connected_at = Instant::now();
+                    let res = handler.run().await;
+                    handler.report_disconnect(res, connected_at);
                 }
                 handler
                     .clients
@@ -536,64 +680,107 @@ impl TlsListener {
         let local_pool = LocalPoolHandle::new(local_pool_number);
 
         let mut incoming = self.tls_listener.incoming();
-        while let Some(stream) = incoming.next().await {
+        loop {
+            wait_under_hard_limit().await;
+            let stream = match incoming.next().await {
+                Some(stream) => stream,
+                None => break,
+            };
             let acceptor = self.tls_acceptor.clone();
             let stream = stream?;
-            let (kill_tx, kill_rx) = mpsc::channel(1);
-            let client = Client::new(stream.clone(), kill_tx);
-            let client_id = client.id();
-            let arc_client = Arc::new(Mutex::new(client));
-            self.clients
-                .lock()
-                .await
-                .insert(client_id, arc_client.clone());
-
-            let local_addr = stream.local_addr().unwrap().to_string();
-            let peer_addr = stream.peer_addr().unwrap().to_string();
-
-            // start tls handshake
-            let handshake = acceptor.accept(stream);
-            // handshake is a future, await to get an encrypted stream back
-            let tls_stream = match handshake.await {
-                Ok(stream) => stream,
-                Err(e) => {
-                    error!(
-                        LOGGER,
-                        "{} -> {} handshake failed, {}",
-                        peer_addr,
-                        local_addr,
-                        e.to_string()
-                    );
-                    continue;
+            let db = self.db_holder.db();
+            let topo = self.topo_holder.clone();
+            let clients = self.clients.clone();
+            let notify_shutdown = self.tls_notify_shutdown.subscribe();
+            let shutdown_complete = self.tls_shutdown_complete_tx.clone();
+
+            // the PROXY header and the handshake are read in the connection
+            // task, a slow client does not hold the accept loop
+            local_pool.spawn_pinned(|| async move {
+                let mut peer_sock_addr = match stream.peer_addr() {
+                    Ok(addr) => addr,
+                    Err(_) => return,
+                };
+                // PROXY protocol header is sent in plain text before tls handshake
+                if config_tls_proxy_protocol_or_default() {
+                    match read_proxy_header(&stream).await {
+                        Ok(Some(addr)) => peer_sock_addr = addr,
+                        Ok(None) => {}
+                        Err(e) => {
+                            warn!(LOGGER, "{} proxy protocol error, {}", peer_sock_addr, e);
+                            REJECTED_CONNECTION_COUNTER
+                                .with_label_values(&["proxy_header"])
+                                .inc();
+                            return;
+                        }
+                    }
+                }
+                if !is_ip_allowed(&peer_sock_addr.ip()) {
+                    return;
                 }
-            };
 
-            let mut handler = Handler {
-                db: self.db_holder.db(),
-                topo: self.topo_holder.clone(),
-                cur_client: arc_client.clone(),
-                clients: self.clients.clone(),
-                connection: Connection::new_tls(&local_addr, &peer_addr, tls_stream),
-                inner_txn: false,
-                queued_commands: vec![],
-                shutdown: Shutdown::new(self.tls_notify_shutdown.subscribe(), kill_rx),
-                authorized: !is_auth_enabled(),
-                lua: None,
-                _shutdown_complete: self.tls_shutdown_complete_tx.clone(),
-            };
+                let local_addr = match stream.local_addr() {
+                    Ok(addr) => addr.to_string(),
+                    Err(_) => return,
+                };
+                let peer_addr = peer_sock_addr.to_string();
+
+                let (kill_tx, kill_rx) = mpsc::channel(1);
+                let (push_tx, push_rx) = mpsc::channel(PUSH_CHANNEL_CAPACITY);
+                let mut client = Client::new(stream.clone(), kill_tx, push_tx);
+                client.set_peer_addr(&peer_addr);
+                let client_id = client.id();
+                let arc_client = Arc::new(Mutex::new(client));
+                clients.lock().await.insert(client_id, arc_client.clone());
+
+                // start tls handshake
+                let handshake = acceptor.accept(stream);
+                // handshake is a future, await to get an encrypted stream back
+                let tls_stream = match handshake.await {
+                    Ok(stream) => stream,
+                    Err(e) => {
+                        error!(
+                            LOGGER,
+                            "{} -> {} handshake failed, {}",
+                            peer_addr,
+                            local_addr,
+                            e.to_string()
+                        );
+                        clients.lock().await.remove(&client_id);
+                        return;
+                    }
+                };
+
+                let mut handler = Handler {
+                    db,
+                    topo,
+                    cur_client: arc_client,
+                    clients,
+                    connection: Connection::new_tls(&local_addr, &peer_addr, tls_stream),
+                    inner_txn: false,
+                    queued_commands: vec![],
+                    watched_keys: vec![],
+                    txn_aborted: false,
+                    readonly: false,
+                    read_snapshot: None,
+                    pending_frame: None,
+                    idempotency_token: None,
+                    push_rx,
+                    shutdown: Shutdown::new(notify_shutdown, kill_rx),
+                    authorized: !is_auth_enabled(),
+                    resource_group: config_resource_group_or_default(),
+                    lua: None,
+                    _shutdown_complete: shutdown_complete,
+                };
 
-            local_pool.spawn_pinned(|| async move {
                 // Process the connection. If an error is encountered, log it.
                 CURRENT_TLS_CONNECTION_COUNTER.inc();
                 TOTAL_CONNECTION_PROCESSED.inc();
-                if let Err(err) = handler.run().await {
-                    error!(LOGGER, "tls connection error {:?}", err);
-                }
-                handler
-                    .clients
-                    .lock()
-                    .await
-                    .remove(&handler.cur_client.lock().await.id());
+                handler.report_connect();
+                let connected_at = Instant::now();
+                let res = handler.run().await;
+                handler.report_disconnect(res, connected_at);
+                handler.clients.lock().await.remove(&client_id);
                 CURRENT_TLS_CONNECTION_COUNTER.dec();
             });
         }
@@ -602,6 +789,92 @@ impl TlsListener {
     }
 }
 
+impl WsListener {
+    async fn run(&mut self) -> crate::Result<()> {
+        info!(LOGGER, "accepting inbound websocket connections");
+
+        let local_pool_number = config_local_pool_number();
+        let local_pool = LocalPoolHandle::new(local_pool_number);
+
+        let mut incoming = self.ws_listener.incoming();
+        loop {
+            wait_under_hard_limit().await;
+            let socket = match incoming.next().await {
+                Some(socket) => socket?,
+                None => break,
+            };
+            match socket.peer_addr() {
+                Ok(addr) if is_ip_allowed(&addr.ip()) => {}
+                _ => continue,
+            }
+
+            let (kill_tx, kill_rx) = mpsc::channel(1);
+            let (push_tx, push_rx) = mpsc::channel(PUSH_CHANNEL_CAPACITY);
+            let client = Client::new(socket.clone(), kill_tx, push_tx);
+            let client_id = client.id();
+            let arc_client = Arc::new(Mutex::new(client));
+
+            let db = self.db_holder.db();
+            let topo = self.topo_holder.clone();
+            let clients = self.clients.clone();
+            let shutdown = Shutdown::new(self.ws_notify_shutdown.subscribe(), kill_rx);
+            let shutdown_complete = self.ws_shutdown_complete_tx.clone();
+
+            local_pool.spawn_pinned(move || async move {
+                // a client slow to upgrade does not hold the listener
+                let read =
+                    match time::timeout(WS_HANDSHAKE_TIMEOUT, accept_handshake(&socket)).await {
+                        Ok(Ok(read)) => read,
+                        Ok(Err(e)) => {
+                            debug!(
+                                LOGGER,
+                                "[WebSocket] {:?} handshake failed, {}",
+                                socket.peer_addr(),
+                                e
+                            );
+                            return;
+                        }
+                        Err(_) => return,
+                    };
+                clients.lock().await.insert(client_id, arc_client.clone());
+
+                let mut handler = Handler {
+                    db,
+                    topo,
+                    cur_client: arc_client,
+                    clients,
+                    connection: Connection::new_ws(socket, read),
+                    inner_txn: false,
+                    queued_commands: vec![],
+                    watched_keys: vec![],
+                    txn_aborted: false,
+                    readonly: false,
+                    read_snapshot: None,
+                    pending_frame: None,
+                    idempotency_token: None,
+                    push_rx,
+                    shutdown,
+                    authorized: !is_auth_enabled(),
+                    resource_group: config_resource_group_or_default(),
+                    lua: None,
+                    _shutdown_complete: shutdown_complete,
+                };
+
+                CURRENT_CONNECTION_COUNTER.inc();
+                TOTAL_CONNECTION_PROCESSED.inc();
+                handler.report_connect();
+                let connected_at = Instant::now();
+                let res = handler.run().await;
+                handler.report_disconnect(res, connected_at);
+                handler.clients.lock().await.remove(&client_id);
+                CURRENT_CONNECTION_COUNTER.dec();
+            });
+        }
+
+        Ok(())
+    }
+}
+
 impl TopologyManager {
     async fn run(self) -> crate::Result<()> {
         let mut interval = time::interval(Duration::from_millis(self.interval));
@@ -641,21 +914,30 @@ impl TopologyManager {
                         for kv in iter {
                             let ts = KeyDecoder::decode_topo_value(&kv.1);
                             let ttl = utils::ttl_from_timestamp(ts);
+                            let encoded_key: Vec<u8> = kv.0.clone().into();
+                            let addr = KeyDecoder::decode_topo_key_addr(&encoded_key);
+                            let addr = String::from_utf8_lossy(addr).to_string();
                             if ttl == 0 {
                                 txn.delete(kv.0).await?;
+                                txn.delete(KEY_ENCODER.encode_txnkv_member_features_key(&addr))
+                                    .await?;
                             } else {
-                                let encoded_key: Vec<u8> = kv.0.into();
-                                let addr = KeyDecoder::decode_topo_key_addr(&encoded_key);
-                                remaining_node.push(String::from_utf8_lossy(addr).to_string());
+                                remaining_node.push(addr);
                             }
                         }
 
+                        // activate the features supported by all the members
+                        negotiate_features(&mut txn, &address, &remaining_node).await?;
+
                         // update topology snapshot and build topology in local if needed
                         // check if member changed
                         if topo_holder.cluster_member_changed(&remaining_node) {
                             topo_holder.update_topo(&remaining_node, &address);
                         }
 
+                        // users revoked by the other members
+                        load_revocations(&mut txn).await?;
+
                         Ok(())
                     }
                     .boxed()
@@ -677,6 +959,74 @@ impl TopologyManager {
 }
 
 impl Handler {
+    fn report_connect(&self) {
+        CONNECTION_EVENT_COUNTER
+            .with_label_values(&["connect"])
+            .inc();
+        debug!(LOGGER, "connection accepted";
+            "peer" => self.connection.peer_addr(),
+            "local" => self.connection.local_addr());
+    }
+
+    /// Account the end of the connection, `res` is the result of `run`.
+    fn report_disconnect(&self, res: crate::Result<()>, connected_at: Instant) {
+        let reason = match &res {
+            Ok(_) if self.shutdown.is_killed() => "killed",
+            Ok(_) if self.shutdown.is_shutdown() => "shutdown",
+            Ok(_) => "closed",
+            Err(e) if e.is::<frame::Error>() || e.is::<ParseError>() => "protocol_error",
+            Err(_) => "error",
+        };
+        DISCONNECTION_COUNTER.with_label_values(&[reason]).inc();
+        let duration_ms = connected_at.elapsed().as_millis() as u64;
+        match res {
+            Err(e) => error!(LOGGER, "connection closed";
+                "peer" => self.connection.peer_addr(),
+                "reason" => reason,
+                "duration_ms" => duration_ms,
+                "error" => %e),
+            Ok(_) if reason == "killed" => info!(LOGGER, "connection closed";
+                "peer" => self.connection.peer_addr(),
+                "reason" => reason,
+                "duration_ms" => duration_ms),
+            Ok(_) => debug!(LOGGER, "connection closed";
+                "peer" => self.connection.peer_addr(),
+                "reason" => reason,
+                "duration_ms" => duration_ms),
+        }
+    }
+
+    /// Replace the peer address with the client address in PROXY protocol
+    /// header, returns false if the connection should be closed.
+    async fn handle_proxy_header(&mut self, socket: &TcpStream) -> bool {
+        let addr = match read_proxy_header(socket).await {
+            Ok(Some(addr)) => addr,
+            Ok(None) => match socket.peer_addr() {
+                Ok(addr) => addr,
+                Err(_) => return false,
+            },
+            Err(e) => {
+                warn!(
+                    LOGGER,
+                    "{} proxy protocol error, {}",
+                    self.connection.peer_addr(),
+                    e
+                );
+                REJECTED_CONNECTION_COUNTER
+                    .with_label_values(&["proxy_header"])
+                    .inc();
+                return false;
+            }
+        };
+        if !is_ip_allowed(&addr.ip()) {
+            return false;
+        }
+        let addr = addr.to_string();
+        self.connection.set_peer_addr(&addr);
+        self.cur_client.lock().await.set_peer_addr(&addr);
+        true
+    }
+
     /// Process a single connection.
     ///
     /// Request frames are read from the socket and processed. Responses are
@@ -696,7 +1046,14 @@ impl Handler {
             // While reading a request frame, also listen for the shutdown
             // signal.
             let maybe_frame = tokio::select! {
-                res = self.connection.read_frame() => res?,
+                res = self.connection.read_frame(), if self.pending_frame.is_none() => res?,
+                frame = future::ready(self.pending_frame.take()), if self.pending_frame.is_some() => frame,
+                // read_frame keeps partially read data in the buffer, so it
+                // is safe to be interrupted by a push
+                Some(push) = self.push_rx.recv() => {
+                    self.connection.write_push(&push).await?;
+                    continue;
+                }
                 _ = self.shutdown.recv() => {
                     // If a shutdown signal is received, return from `run`.
                     // This will result in the task terminating.
@@ -715,25 +1072,61 @@ impl Handler {
             // Convert the redis frame into a command struct. This returns an
             // error if the frame is not a valid redis command or it is an
             // unsupported command.
+            let redacted_req = if log_redact_values() {
+                Some(utils::redact_request(&frame, log_redact_key_hash()))
+            } else if log_max_args() > 0 || log_max_arg_len() > 0 || utils::has_secret_args(&frame)
+            {
+                Some(utils::describe_request(
+                    &frame,
+                    log_max_args(),
+                    log_max_arg_len(),
+                ))
+            } else {
+                None
+            };
+            let args = if cmdlog_enabled() || triggers_enabled() || notify_enabled() {
+                frame_args(&frame)
+            } else {
+                vec![]
+            };
+            let accessed_key = accessed_key(&frame);
             let cmd = Command::from_frame(frame)?;
             let cmd_name = cmd.get_name().to_owned();
+            let class = slo_class(&cmd);
+            if let Some(key) = accessed_key {
+                if cmd.tracks_access() {
+                    track_access(key);
+                }
+            }
 
-            {
+            let _in_flight = {
                 let mut w_client = self.cur_client.lock().await;
                 w_client.interact(&cmd_name);
-            }
+                InFlight::start(w_client.id(), &cmd_name)
+            };
 
             let start_at = Instant::now();
+            let command_log = CommandLog::start(start_at);
             REQUEST_COUNTER.inc();
             REQUEST_CMD_COUNTER.with_label_values(&[&cmd_name]).inc();
 
-            debug!(
-                LOGGER,
-                "req {} -> {}, {:?}",
-                self.connection.peer_addr(),
-                self.connection.local_addr(),
-                cmd
-            );
+            match &redacted_req {
+                Some(req) if command_log.keep() => debug!(
+                    LOGGER,
+                    "req {} -> {}, {}",
+                    self.connection.peer_addr(),
+                    self.connection.local_addr(),
+                    req
+                ),
+                None if command_log.keep() => debug!(
+                    LOGGER,
+                    "req {} -> {}, {:?}",
+                    self.connection.peer_addr(),
+                    self.connection.local_addr(),
+                    cmd
+                ),
+                _ => {}
+            }
 
             match cmd {
                 Command::Auth(c) => {
@@ -746,42 +1139,78 @@ impl Handler {
                         self.connection
                             .write_frame(&resp_err(REDIS_AUTH_WHEN_DISABLED_ERR))
                             .await?;
-                    } else if is_auth_matched(c.passwd()) {
+                    } else if authenticate(c.username(), c.passwd()).await {
+                        CONNECTION_EVENT_COUNTER
+                            .with_label_values(&["auth_ok"])
+                            .inc();
                         self.connection.write_frame(&resp_ok()).await?;
                         self.authorized = true;
+                        self.resource_group = config_user_resource_group(c.username());
+                        self.cur_client.lock().await.set_user(c.username());
                     } else {
+                        CONNECTION_EVENT_COUNTER
+                            .with_label_values(&["auth_failed"])
+                            .inc();
+                        info!(LOGGER, "authentication failed";
+                            "peer" => self.connection.peer_addr(),
+                            "user" => c.username());
                         self.connection
                             .write_frame(&resp_err(REDIS_AUTH_INVALID_PASSWORD_ERR))
                             .await?;
                     }
                 }
                 _ => {
+                    if self.user_revoked().await {
+                        CONNECTION_EVENT_COUNTER
+                            .with_label_values(&["revoked"])
+                            .inc();
+                        if config_auth_revoke_mode_or_default() != "reauth" {
+                            return Ok(());
+                        }
+                        self.authorized = false;
+                    }
                     if !self.authorized {
                         self.connection
                             .write_frame(&resp_err(REDIS_AUTH_REQUIRED_ERR))
                             .await?;
                     } else {
                         match cmd {
+                            _ if cmd.priority() == Priority::Low && over_soft_limit() => {
+                                MEMORY_SHED_COMMAND_COUNTER
+                                    .with_label_values(&[&cmd_name])
+                                    .inc();
+                                self.connection
+                                    .write_frame(&resp_err(REDIS_MEMORY_SOFT_LIMIT_ERR))
+                                    .await?;
+                                continue;
+                            }
+                            _ if self.is_batchable_read(&cmd) => {
+                                self.apply_read_batch(cmd, command_log, start_at).await?;
+                                continue;
+                            }
+                            _ if self.readonly && cmd.is_write() => {
+                                self.connection
+                                    .write_frame(&resp_err(REDIS_READONLY_ERR))
+                                    .await?;
+                                continue;
+                            }
+                            Command::ReadOnly(_) => self.readonly = true,
+                            Command::ReadWrite(_) => self.readonly = false,
+                            Command::Idempotency(c) => self.idempotency_token = c.token(),
                             Command::Eval(_) | Command::Evalsha(_) => {
                                 if self.lua.is_none() {
                                     // initialize the mlua once in same connection
                                     let lua = Lua::new();
                                     // set script interupt handler
                                     lua.set_hook(HookTriggers::every_line(), |_lua, _debug| {
-                                        if script_interuptted() {
-                                            warn!(
-                                                LOGGER,
-                                                "Script kiiled by user with SCRIPT KILL..."
-                                            );
-
-                                            script_clear_killed();
-
-                                            Err(mlua::Error::RuntimeError(
-                                                "Script kiiled by user with SCRIPT KILL..."
-                                                    .to_string(),
-                                            ))
-                                        } else {
-                                            Ok(())
+                                        let id: Option<u64> =
+                                            _lua.named_registry_value(SCRIPT_ID_REGISTRY_KEY)?;
+                                        match id.and_then(script_interrupted) {
+                                            Some(e) => {
+                                                warn!(LOGGER, "Script aborted, {}", e);
+                                                Err(mlua::Error::RuntimeError(e))
+                                            }
+                                            None => Ok(()),
                                         }
                                     })
                                     .unwrap();
@@ -796,6 +1225,7 @@ impl Handler {
                                         .await?;
                                 } else {
                                     self.inner_txn = true;
+                                    self.txn_aborted = false;
                                     self.queued_commands.clear();
                                     self.connection.write_frame(&resp_ok()).await?;
                                 }
@@ -805,17 +1235,38 @@ impl Handler {
                                     self.connection
                                         .write_frame(&resp_err(REDIS_EXEC_WITHOUT_MULTI_ERR))
                                         .await?;
+                                } else if std::mem::take(&mut self.txn_aborted) {
+                                    self.inner_txn = false;
+                                    self.queued_commands.clear();
+                                    self.watched_keys.clear();
+                                    self.connection
+                                        .write_frame(&resp_err(REDIS_EXEC_ERR))
+                                        .await?;
                                 } else {
                                     self.inner_txn = false;
-                                    c.clone()
-                                        .exec(&mut self.connection, self.queued_commands.clone())
+                                    let watched = std::mem::take(&mut self.watched_keys);
+                                    let queued = std::mem::take(&mut self.queued_commands);
+                                    let replies = c
+                                        .clone()
+                                        .exec(
+                                            &mut self.connection,
+                                            queued.iter().map(|(cmd, _)| cmd.clone()).collect(),
+                                            watched,
+                                        )
                                         .await?;
+                                    // the writes are shipped and fired once committed
+                                    for ((cmd, args), reply) in queued.iter().zip(replies) {
+                                        if cmd.is_write() && write_applied(cmd.get_name(), reply) {
+                                            write_committed(write_event(cmd, args)).await;
+                                        }
+                                    }
                                 }
 
                                 let duration = Instant::now() - start_at;
                                 REQUEST_CMD_HANDLE_TIME
                                     .with_label_values(&[&cmd_name])
                                     .observe(duration_to_sec(duration));
+                                check_latency_slo(&cmd_name, class, duration);
                                 REQUEST_CMD_FINISH_COUNTER
                                     .with_label_values(&[&cmd_name])
                                     .inc();
@@ -824,7 +1275,9 @@ impl Handler {
                             Command::Discard(_) => {
                                 if self.inner_txn {
                                     self.inner_txn = false;
+                                    self.txn_aborted = false;
                                     self.queued_commands.clear();
+                                    self.watched_keys.clear();
                                     self.connection.write_frame(&resp_ok()).await?;
                                 } else {
                                     self.connection
@@ -832,9 +1285,35 @@ impl Handler {
                                         .await?;
                                 }
                             }
+                            Command::Watch(c) => {
+                                let response = if self.inner_txn {
+                                    resp_err(REDIS_WATCH_INSIDE_MULTI_ERR)
+                                } else if !c.valid() {
+                                    resp_invalid_arguments()
+                                } else {
+                                    match c.watch().await {
+                                        Ok(watched) => {
+                                            self.watched_keys.extend(watched);
+                                            resp_ok()
+                                        }
+                                        Err(e) => resp_err(e),
+                                    }
+                                };
+                                self.connection.write_frame(&response).await?;
+                            }
+                            Command::Unwatch(_) => {
+                                self.watched_keys.clear();
+                                self.connection.write_frame(&resp_ok()).await?;
+                            }
+                            // rejected right away, even in MULTI, which EXEC then discards
+                            Command::WrongArity(_) | Command::Unknown(_) => {
+                                if self.inner_txn {
+                                    self.txn_aborted = true;
+                                }
+                            }
                             _ => {
                                 if self.inner_txn {
-                                    self.queued_commands.push(cmd);
+                                    self.queued_commands.push((cmd, args));
                                     self.connection.write_frame(&resp_queued()).await?;
                                     continue;
                                 }
@@ -847,19 +1326,45 @@ impl Handler {
                         // command to write response frames directly to the connection. In
                         // the case of pub/sub, multiple frames may be send back to the
                         // peer.
-                        match cmd
-                            .apply(
-                                &self.db,
-                                &self.topo,
-                                &mut self.connection,
-                                self.cur_client.clone(),
-                                self.clients.clone(),
-                                &mut self.lua,
-                                &mut self.shutdown,
-                            )
-                            .await
-                        {
-                            Ok(_) => (),
+                        let priority = cmd.priority();
+                        let _permit = acquire_permit(priority).await;
+                        let token = self.take_idempotency_token(&cmd);
+                        let is_write = cmd.is_write();
+                        // scripts ship the write commands they called once committed
+                        let is_script = matches!(cmd, Command::Eval(_) | Command::Evalsha(_));
+                        let event = if is_write && !is_script {
+                            write_event(&cmd, &args)
+                        } else {
+                            None
+                        };
+                        let snapshot = self.pipeline_snapshot(&cmd).await;
+                        self.connection.take_last_reply();
+                        let applied = cmd.apply(
+                            &self.db,
+                            &self.topo,
+                            &mut self.connection,
+                            self.cur_client.clone(),
+                            self.clients.clone(),
+                            &mut self.lua,
+                            &mut self.shutdown,
+                        );
+                        let applied = PIPELINE_SNAPSHOT.scope(snapshot, applied);
+                        let applied = IDEMPOTENCY_TOKEN.scope(Cell::new(token), applied);
+                        let applied = COMMAND_LOG.scope(command_log, applied);
+                        let applied = RESOURCE_GROUP.scope(self.resource_group.clone(), applied);
+                        let applied = PRIORITY.scope(priority, applied);
+                        match STALE_READ.scope(self.readonly, applied).await {
+                            Ok(_) => {
+                                // the writes which failed or changed nothing are skipped
+                                let written = is_write
+                                    && self
+                                        .connection
+                                        .take_last_reply()
+                                        .map_or(false, |reply| write_applied(&cmd_name, reply));
+                                if written {
+                                    write_committed(event).await;
+                                }
+                            }
                             Err(e) => {
                                 REQUEST_CMD_ERROR_COUNTER
                                     .with_label_values(&[&cmd_name])
@@ -871,17 +1376,225 @@ impl Handler {
                 }
             }
 
+            // the pipeline burst is over, do not keep the snapshot to the next one
+            if !self.connection.has_buffered_data() {
+                self.finish_pipeline_snapshot().await;
+            }
+
             let duration = Instant::now() - start_at;
             REQUEST_CMD_HANDLE_TIME
                 .with_label_values(&[&cmd_name])
                 .observe(duration_to_sec(duration));
+            check_latency_slo(&cmd_name, class, duration);
             REQUEST_CMD_FINISH_COUNTER
                 .with_label_values(&[&cmd_name])
                 .inc();
+            RESOURCE_GROUP_REQUEST_COUNTER
+                .with_label_values(&[&self.resource_group])
+                .inc();
+            RESOURCE_GROUP_HANDLE_SECONDS
+                .with_label_values(&[&self.resource_group])
+                .inc_by(duration_to_sec(duration));
         }
 
         Ok(())
     }
+
+    /// Returns the idempotency token of the write command, the one set by the
+    /// preceding IDEMPOTENCY or a derived one if enabled. The set token only
+    /// applies to the command following IDEMPOTENCY.
+    fn take_idempotency_token(&mut self, cmd: &Command) -> Option<String> {
+        if let Command::Idempotency(_) = cmd {
+            return None;
+        }
+        let token = self.idempotency_token.take();
+        if !cmd.is_write() {
+            return None;
+        }
+        token.or_else(|| {
+            if backend_idempotency_derive_tokens_or_default() {
+                Some(format!("{:032x}", rand::random::<u128>()))
+            } else {
+                None
+            }
+        })
+    }
+
+    /// True if the user of the authorized connection was revoked with
+    /// CLIENT REVOKE since it authenticated
+    async fn user_revoked(&self) -> bool {
+        if !self.authorized || !is_auth_enabled() {
+            return false;
+        }
+        let client = self.cur_client.lock().await;
+        is_revoked(client.user(), client.auth_time())
+    }
+
+    fn is_batchable_read(&self, cmd: &Command) -> bool {
+        config_pipeline_concurrency_or_default() > 1
+            && !self.inner_txn
+            && cmd.is_read()
+            && cmd.priority() == Priority::Normal
+            && self.connection.has_buffered_data()
+    }
+
+    /// Execute the consecutive pipelined reads following `first` concurrently,
+    /// at most `pipeline_concurrency` of them, and reply in request order.
+    ///
+    /// Reads don't change the keyspace, so running them concurrently gives the
+    /// same results as running them one by one. The batch stops at the first
+    /// command that is not a batchable read, which is handled next as usual.
+    async fn apply_read_batch(
+        &mut self,
+        first: Command,
+        command_log: CommandLog,
+        start_at: Instant,
+    ) -> crate::Result<()> {
+        self.finish_pipeline_snapshot().await;
+        self.idempotency_token = None;
+
+        let concurrency = config_pipeline_concurrency_or_default();
+        let mut cmds = vec![first];
+        // the first one is registered by the caller
+        let mut in_flight = vec![];
+        while cmds.len() < concurrency {
+            let frame = match self.connection.read_buffered_frame()? {
+                Some(frame) => frame,
+                None => break,
+            };
+            let cmd = Command::from_frame(frame.clone())?;
+            if !self.authorized || !self.is_batchable_read(&cmd) || self.user_revoked().await {
+                self.pending_frame = Some(frame);
+                break;
+            }
+            if let Some(key) = accessed_key(&frame) {
+                if cmd.tracks_access() {
+                    track_access(key);
+                }
+            }
+            {
+                let mut w_client = self.cur_client.lock().await;
+                w_client.interact(cmd.get_name());
+                in_flight.push(InFlight::start(w_client.id(), cmd.get_name()));
+            }
+            REQUEST_COUNTER.inc();
+            REQUEST_CMD_COUNTER
+                .with_label_values(&[cmd.get_name()])
+                .inc();
+            cmds.push(cmd);
+        }
+
+        let cmd_names: Vec<(String, Option<&str>)> = cmds
+            .iter()
+            .map(|c| (c.get_name().to_owned(), slo_class(c)))
+            .collect();
+        let responses: Vec<Frame> = STALE_READ
+            .scope(
+                self.readonly,
+                COMMAND_LOG.scope(
+                    command_log,
+                    RESOURCE_GROUP.scope(
+                        self.resource_group.clone(),
+                        future::join_all(cmds.into_iter().map(|cmd| async move {
+                            let priority = cmd.priority();
+                            PRIORITY
+                                .scope(priority, cmd.execute(None))
+                                .await
+                                .unwrap_or_else(Into::into)
+                        })),
+                    ),
+                ),
+            )
+            .await;
+
+        let duration = Instant::now() - start_at;
+        for (response, (cmd_name, class)) in responses.iter().zip(cmd_names.iter()) {
+            if command_log.keep() {
+                debug!(
+                    LOGGER,
+                    "res, {} -> {}, {:?}",
+                    self.connection.local_addr(),
+                    self.connection.peer_addr(),
+                    response
+                );
+            }
+            self.connection.write_frame(response).await?;
+
+            REQUEST_CMD_HANDLE_TIME
+                .with_label_values(&[cmd_name])
+                .observe(duration_to_sec(duration));
+            check_latency_slo(cmd_name, *class, duration);
+            REQUEST_CMD_FINISH_COUNTER
+                .with_label_values(&[cmd_name])
+                .inc();
+            RESOURCE_GROUP_REQUEST_COUNTER
+                .with_label_values(&[&self.resource_group])
+                .inc();
+        }
+        RESOURCE_GROUP_HANDLE_SECONDS
+            .with_label_values(&[&self.resource_group])
+            .inc_by(duration_to_sec(duration));
+        Ok(())
+    }
+
+    /// Returns the snapshot shared with previous reads of the pipeline burst,
+    /// None if the command should begin its own transaction.
+    ///
+    /// A snapshot is only taken if more requests are already buffered, and it
+    /// is reused for at most `pipeline_snapshot_max_cmds` commands within
+    /// `pipeline_snapshot_max_age_ms`.
+    async fn pipeline_snapshot(&mut self, cmd: &Command) -> Option<Arc<Mutex<Transaction>>> {
+        let max_cmds = backend_pipeline_snapshot_max_cmds_or_default();
+        if max_cmds == 0 || !cmd.is_read() {
+            self.finish_pipeline_snapshot().await;
+            return None;
+        }
+
+        if let Some((_, taken_at, cmds)) = &self.read_snapshot {
+            let max_age = Duration::from_millis(backend_pipeline_snapshot_max_age_ms_or_default());
+            if *cmds >= max_cmds || taken_at.elapsed() >= max_age {
+                self.finish_pipeline_snapshot().await;
+            }
+        }
+
+        match &mut self.read_snapshot {
+            Some((txn, _, cmds)) => {
+                *cmds += 1;
+                SNAPSHOT_REUSED_COUNTER.inc();
+                Some(txn.clone())
+            }
+            None => {
+                if !self.connection.has_buffered_data() {
+                    return None;
+                }
+                let txn = match get_txn_client() {
+                    Ok(client) => client.begin_shared_snapshot().await,
+                    Err(_) => return None,
+                };
+                match txn {
+                    Ok(txn) => {
+                        let txn = Arc::new(Mutex::new(txn));
+                        self.read_snapshot = Some((txn.clone(), Instant::now(), 1));
+                        Some(txn)
+                    }
+                    Err(e) => {
+                        warn!(LOGGER, "failed to begin pipeline snapshot: {}", e);
+                        None
+                    }
+                }
+            }
+        }
+    }
+
+    /// Commit the shared snapshot, it only holds lazy expiration deletes of
+    /// the reads, so a failure is not reported to the client.
+    async fn finish_pipeline_snapshot(&mut self) {
+        if let Some((txn, _, _)) = self.read_snapshot.take() {
+            if let Err(e) = txn.lock().await.commit().await {
+                warn!(LOGGER, "failed to commit pipeline snapshot: {}", e);
+            }
+        }
+    }
 }
 
 #[inline]
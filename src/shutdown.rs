@@ -20,6 +20,9 @@ pub(crate) struct Shutdown {
 
     /// used to listen for P2P kill signal
     kill_rx: mpsc::Receiver<()>,
+
+    /// `true` if the shutdown is caused by the kill signal
+    killed: bool,
 }
 
 impl Shutdown {
@@ -29,6 +32,7 @@ impl Shutdown {
             shutdown: false,
             notify,
             kill_rx,
+            killed: false,
         }
     }
 
@@ -37,6 +41,12 @@ impl Shutdown {
         self.shutdown
     }
 
+    /// Returns `true` if the connection was killed rather than the server
+    /// shutdown.
+    pub(crate) fn is_killed(&self) -> bool {
+        self.killed
+    }
+
     /// Receive the shutdown notice, waiting if necessary.
     pub(crate) async fn recv(&mut self) {
         // If the shutdown signal has already been received, then return
@@ -54,6 +64,7 @@ impl Shutdown {
             _ = self.kill_rx.recv() => {
                 // kill signal caused shutdown
                 self.shutdown = true;
+                self.killed = true;
             }
         };
     }
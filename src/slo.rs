@@ -0,0 +1,104 @@
+//! Per command latency SLO tracking.
+//!
+//! Thresholds are declared in ms with `latency_slo` table in server config,
+//! keyed by command name or by class of commands: `read`, `write` and `scan`
+//! for the whole collection reads and scans. The threshold of a command
+//! applies over the one of its class, `default` applies to all other
+//! commands. Breaches
+//! are counted in `tikv_redis_command_slo_breach_total` and reported in
+//! `INFO SLO`, optionally logged as alert events.
+
+use std::collections::HashMap;
+use std::time::Duration;
+
+use prometheus::core::Collector;
+use slog::warn;
+
+use crate::cmd::Command;
+use crate::config::LOGGER;
+use crate::metrics::SLO_BREACH_COUNTER;
+use crate::priority::Priority;
+use crate::{config_latency_slo_alert_log_or_default, config_latency_slo_or_default};
+
+const DEFAULT_SLO_KEY: &str = "default";
+const READ_SLO_KEY: &str = "read";
+const WRITE_SLO_KEY: &str = "write";
+const SCAN_SLO_KEY: &str = "scan";
+
+lazy_static! {
+    static ref SLO_THRESHOLDS: HashMap<String, Duration> = config_latency_slo_or_default()
+        .into_iter()
+        .map(|(cmd, ms)| (cmd.to_lowercase(), Duration::from_millis(ms)))
+        .collect();
+    static ref SLO_ALERT_LOG: bool = config_latency_slo_alert_log_or_default();
+}
+
+/// The class of `cmd` for its SLO, None for the connection and admin
+/// commands which only have the default threshold
+pub fn slo_class(cmd: &Command) -> Option<&'static str> {
+    if cmd.is_write() {
+        return Some(WRITE_SLO_KEY);
+    }
+    match cmd.priority() {
+        Priority::High => None,
+        Priority::Normal => Some(READ_SLO_KEY),
+        Priority::Low => Some(SCAN_SLO_KEY),
+    }
+}
+
+fn threshold(cmd: &str, class: Option<&str>) -> Option<&'static Duration> {
+    SLO_THRESHOLDS
+        .get(cmd)
+        .or_else(|| class.and_then(|class| SLO_THRESHOLDS.get(class)))
+        .or_else(|| SLO_THRESHOLDS.get(DEFAULT_SLO_KEY))
+}
+
+/// Check the command handle duration against its SLO, the one of the
+/// command or else of its `class`, see `slo_class`.
+pub fn check_latency_slo(cmd: &str, class: Option<&str>, duration: Duration) {
+    if let Some(threshold) = threshold(cmd, class) {
+        if duration > *threshold {
+            SLO_BREACH_COUNTER.with_label_values(&[cmd]).inc();
+            if *SLO_ALERT_LOG {
+                warn!(
+                    LOGGER,
+                    "latency slo breach, cmd={} duration_ms={} threshold_ms={}",
+                    cmd,
+                    duration.as_millis(),
+                    threshold.as_millis()
+                );
+            }
+        }
+    }
+}
+
+/// Encode the `INFO SLO` section
+pub fn encode_slo_info() -> String {
+    let mut info = String::from("# SLO\r\n");
+    let mut thresholds: Vec<(&String, &Duration)> = SLO_THRESHOLDS.iter().collect();
+    thresholds.sort();
+    for (cmd, threshold) in thresholds {
+        info.push_str(&format!(
+            "slo_threshold_ms_{}:{}\r\n",
+            cmd,
+            threshold.as_millis()
+        ));
+    }
+
+    let mut breaches = vec![];
+    for mf in SLO_BREACH_COUNTER.collect() {
+        for m in mf.get_metric() {
+            if let Some(label) = m.get_label().iter().find(|l| l.get_name() == "cmd") {
+                breaches.push((
+                    label.get_value().to_owned(),
+                    m.get_counter().get_value() as u64,
+                ));
+            }
+        }
+    }
+    breaches.sort();
+    for (cmd, count) in breaches {
+        info.push_str(&format!("slo_breaches_{}:{}\r\n", cmd, count));
+    }
+    info
+}
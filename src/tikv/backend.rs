@@ -0,0 +1,393 @@
+//! Storage backend abstraction.
+//!
+//! The command contexts in `crate::tikv` work on `Transaction`, which
+//! dispatches to the `StorageTxn` created by the `StorageBackend` selected at
+//! startup, so the commands don't depend on a particular storage. The calls
+//! are timed for the rpc log, see `super::rpclog`, and the reads are retried
+//! on region errors, see `super::retry`.
+//!
+//! A read only snapshot at the latest data may have a stale snapshot to fall
+//! back to: a read taking longer than `backend.leader_read_timeout_ms`, while
+//! the leader of its region is moved for instance, is sent again on the stale
+//! snapshot, which the rest of the command reads too. The fallback is counted
+//! in `tikv_redis_stale_read_fallback_total`. The TiKV client sends the stale
+//! reads to the leader too, they don't wait on the locks of the writes in
+//! flight though, so the command gets older data instead of a timeout.
+//!
+//! The user keys written by a transaction are stamped when it commits, see
+//! `super::stamp`.
+
+use std::collections::HashSet;
+use std::ops::Bound;
+use std::time::Duration;
+
+use futures::future::BoxFuture;
+use futures::stream::BoxStream;
+use futures::{FutureExt, StreamExt};
+use tikv_client::{
+    BoundRange, Key, KvPair, Result as BackendResult, Timestamp, TransactionClient,
+    TransactionOptions, Value,
+};
+
+use super::retry::{is_region_error, region_backoff, TXN_READ_ATTEMPTS};
+use super::rpclog::{logged_key, traced};
+use super::stamp::new_stamp;
+use super::KEY_ENCODER;
+use crate::backend_leader_read_timeout_ms_or_default;
+use crate::metrics::{STALE_READ_FALLBACK_COUNTER, TIKV_CLIENT_RETRIES};
+
+/// A storage backend the transactions are started on.
+pub trait StorageBackend: Send + Sync {
+    /// Name of the backend, reported in logs
+    fn name(&self) -> &'static str;
+
+    /// Fetch a timestamp to start a transaction at
+    fn current_timestamp(&self) -> BoxFuture<'_, BackendResult<Timestamp>>;
+
+    /// Create a transaction reading the snapshot at `ts`
+    fn new_transaction(&self, ts: Timestamp, options: TransactionOptions) -> Transaction;
+}
+
+/// Operations of a transaction on a storage backend.
+pub trait StorageTxn: Send {
+    fn get(&mut self, key: Key) -> BoxFuture<'_, BackendResult<Option<Value>>>;
+
+    fn key_exists(&mut self, key: Key) -> BoxFuture<'_, BackendResult<bool>>;
+
+    fn batch_get(&mut self, keys: Vec<Key>) -> BoxFuture<'_, BackendResult<Vec<KvPair>>>;
+
+    fn scan(&mut self, range: BoundRange, limit: u32) -> BoxFuture<'_, BackendResult<Vec<KvPair>>>;
+
+    fn scan_keys(
+        &mut self,
+        range: BoundRange,
+        limit: u32,
+    ) -> BoxFuture<'_, BackendResult<Vec<Key>>>;
+
+    fn scan_stream(
+        &mut self,
+        range: BoundRange,
+        limit: u32,
+    ) -> BoxFuture<'_, BackendResult<BoxStream<'static, KvPair>>>;
+
+    fn scan_reverse_stream(
+        &mut self,
+        range: BoundRange,
+        limit: u32,
+    ) -> BoxFuture<'_, BackendResult<BoxStream<'static, KvPair>>>;
+
+    fn scan_keys_stream(
+        &mut self,
+        range: BoundRange,
+        limit: u32,
+    ) -> BoxFuture<'_, BackendResult<BoxStream<'static, Key>>>;
+
+    fn put(&mut self, key: Key, value: Value) -> BoxFuture<'_, BackendResult<()>>;
+
+    fn delete(&mut self, key: Key) -> BoxFuture<'_, BackendResult<()>>;
+
+    fn commit(&mut self) -> BoxFuture<'_, BackendResult<Option<Timestamp>>>;
+
+    fn rollback(&mut self) -> BoxFuture<'_, BackendResult<()>>;
+}
+
+/// Transaction of the selected storage backend.
+pub struct Transaction {
+    inner: Box<dyn StorageTxn>,
+    /// Snapshot read after a read of `inner` timed out, see the module doc
+    stale_fallback: Option<Box<dyn StorageTxn>>,
+    /// Stamp keys of the user keys written, None if they are not stamped
+    stamps: Option<HashSet<Key>>,
+}
+
+/// Read the snapshot with `$method` until it does not fail with a region
+/// error, each attempt recorded by the rpc log as `$method` on `$key`. An
+/// attempt timing out falls back to the stale snapshot if there is one.
+macro_rules! read_with_retry {
+    ($self:ident, $key:expr, $method:ident($($arg:expr),*)) => {{
+        let op = stringify!($method);
+        let mut attempt = 1;
+        loop {
+            let timeout_ms = backend_leader_read_timeout_ms_or_default();
+            let res = if $self.stale_fallback.is_some() && timeout_ms > 0 {
+                let leader_read = traced(op, $key.clone(), $self.inner.$method($($arg),*));
+                match tokio::time::timeout(Duration::from_millis(timeout_ms), leader_read).await {
+                    Ok(res) => res,
+                    Err(_) => {
+                        STALE_READ_FALLBACK_COUNTER.with_label_values(&[op]).inc();
+                        $self.inner = $self.stale_fallback.take().unwrap();
+                        traced(op, $key.clone(), $self.inner.$method($($arg),*)).await
+                    }
+                }
+            } else {
+                traced(op, $key.clone(), $self.inner.$method($($arg),*)).await
+            };
+            match res {
+                Err(err) if attempt < TXN_READ_ATTEMPTS && is_region_error(&err) => {
+                    TIKV_CLIENT_RETRIES.inc();
+                    region_backoff(attempt).await;
+                    attempt += 1;
+                }
+                res => break res,
+            }
+        }
+    }};
+}
+
+/// The first key of the range, recorded by the rpc log
+fn range_start(range: &BoundRange) -> Option<&Key> {
+    match &range.from {
+        Bound::Included(key) | Bound::Excluded(key) => Some(key),
+        Bound::Unbounded => None,
+    }
+}
+
+impl Transaction {
+    pub fn new(inner: Box<dyn StorageTxn>) -> Transaction {
+        Transaction {
+            inner,
+            stale_fallback: None,
+            stamps: Some(HashSet::new()),
+        }
+    }
+
+    /// Do not stamp the user keys written, for the cleanup of the data which
+    /// is not readable anymore, so it does not abort the EXEC watching them
+    pub fn without_stamps(&mut self) {
+        self.stamps = None;
+    }
+
+    fn stamp_written(&mut self, key: &Key) {
+        if let Some(stamps) = &mut self.stamps {
+            let key = Vec::<u8>::from(key.clone());
+            if let Some(stamp_key) = KEY_ENCODER.encode_txnkv_watch_stamp_key_of(&key) {
+                stamps.insert(stamp_key);
+            }
+        }
+    }
+
+    /// Fall back to the read only snapshot `fallback` when a read times out
+    pub fn with_stale_fallback(mut self, fallback: Transaction) -> Transaction {
+        self.stale_fallback = Some(fallback.inner);
+        self
+    }
+
+    pub async fn get(&mut self, key: impl Into<Key>) -> BackendResult<Option<Value>> {
+        let key = key.into();
+        let logged = logged_key(Some(&key));
+        read_with_retry!(self, logged, get(key.clone()))
+    }
+
+    pub async fn key_exists(&mut self, key: impl Into<Key>) -> BackendResult<bool> {
+        let key = key.into();
+        let logged = logged_key(Some(&key));
+        read_with_retry!(self, logged, key_exists(key.clone()))
+    }
+
+    pub async fn batch_get(
+        &mut self,
+        keys: impl IntoIterator<Item = impl Into<Key>>,
+    ) -> BackendResult<std::vec::IntoIter<KvPair>> {
+        let keys: Vec<Key> = keys.into_iter().map(Into::into).collect();
+        let first = logged_key(keys.first());
+        let pairs = read_with_retry!(self, first, batch_get(keys.clone()))?;
+        Ok(pairs.into_iter())
+    }
+
+    pub async fn scan(
+        &mut self,
+        range: impl Into<BoundRange>,
+        limit: u32,
+    ) -> BackendResult<std::vec::IntoIter<KvPair>> {
+        let range = range.into();
+        let start = logged_key(range_start(&range));
+        let pairs = read_with_retry!(self, start, scan(range.clone(), limit))?;
+        Ok(pairs.into_iter())
+    }
+
+    pub async fn scan_keys(
+        &mut self,
+        range: impl Into<BoundRange>,
+        limit: u32,
+    ) -> BackendResult<std::vec::IntoIter<Key>> {
+        let range = range.into();
+        let start = logged_key(range_start(&range));
+        let keys = read_with_retry!(self, start, scan_keys(range.clone(), limit))?;
+        Ok(keys.into_iter())
+    }
+
+    pub async fn scan_stream(
+        &mut self,
+        range: impl Into<BoundRange>,
+        limit: u32,
+    ) -> BackendResult<BoxStream<'static, KvPair>> {
+        let range = range.into();
+        let start = logged_key(range_start(&range));
+        read_with_retry!(self, start, scan_stream(range.clone(), limit))
+    }
+
+    pub async fn scan_reverse_stream(
+        &mut self,
+        range: impl Into<BoundRange>,
+        limit: u32,
+    ) -> BackendResult<BoxStream<'static, KvPair>> {
+        let range = range.into();
+        let start = logged_key(range_start(&range));
+        read_with_retry!(self, start, scan_reverse_stream(range.clone(), limit))
+    }
+
+    pub async fn scan_keys_stream(
+        &mut self,
+        range: impl Into<BoundRange>,
+        limit: u32,
+    ) -> BackendResult<BoxStream<'static, Key>> {
+        let range = range.into();
+        let start = logged_key(range_start(&range));
+        read_with_retry!(self, start, scan_keys_stream(range.clone(), limit))
+    }
+
+    pub async fn put(&mut self, key: impl Into<Key>, value: impl Into<Value>) -> BackendResult<()> {
+        let key = key.into();
+        self.stamp_written(&key);
+        traced(
+            "put",
+            logged_key(Some(&key)),
+            self.inner.put(key, value.into()),
+        )
+        .await
+    }
+
+    pub async fn delete(&mut self, key: impl Into<Key>) -> BackendResult<()> {
+        let key = key.into();
+        self.stamp_written(&key);
+        traced("delete", logged_key(Some(&key)), self.inner.delete(key)).await
+    }
+
+    pub async fn commit(&mut self) -> BackendResult<Option<Timestamp>> {
+        for stamp_key in self.stamps.take().unwrap_or_default() {
+            let logged = logged_key(Some(&stamp_key));
+            traced("put", logged, self.inner.put(stamp_key, new_stamp())).await?;
+        }
+        traced("commit", vec![], self.inner.commit()).await
+    }
+
+    pub async fn rollback(&mut self) -> BackendResult<()> {
+        traced("rollback", vec![], self.inner.rollback()).await
+    }
+}
+
+impl StorageBackend for TransactionClient {
+    fn name(&self) -> &'static str {
+        "tikv"
+    }
+
+    fn current_timestamp(&self) -> BoxFuture<'_, BackendResult<Timestamp>> {
+        TransactionClient::current_timestamp(self).boxed()
+    }
+
+    fn new_transaction(&self, ts: Timestamp, options: TransactionOptions) -> Transaction {
+        Transaction::new(Box::new(TransactionClient::new_transaction(
+            self, ts, options,
+        )))
+    }
+}
+
+impl StorageTxn for tikv_client::Transaction {
+    fn get(&mut self, key: Key) -> BoxFuture<'_, BackendResult<Option<Value>>> {
+        tikv_client::Transaction::get(self, key).boxed()
+    }
+
+    fn key_exists(&mut self, key: Key) -> BoxFuture<'_, BackendResult<bool>> {
+        tikv_client::Transaction::key_exists(self, key).boxed()
+    }
+
+    fn batch_get(&mut self, keys: Vec<Key>) -> BoxFuture<'_, BackendResult<Vec<KvPair>>> {
+        async move {
+            Ok(tikv_client::Transaction::batch_get(self, keys)
+                .await?
+                .collect())
+        }
+        .boxed()
+    }
+
+    fn scan(&mut self, range: BoundRange, limit: u32) -> BoxFuture<'_, BackendResult<Vec<KvPair>>> {
+        async move {
+            Ok(tikv_client::Transaction::scan(self, range, limit)
+                .await?
+                .collect())
+        }
+        .boxed()
+    }
+
+    fn scan_keys(
+        &mut self,
+        range: BoundRange,
+        limit: u32,
+    ) -> BoxFuture<'_, BackendResult<Vec<Key>>> {
+        async move {
+            Ok(tikv_client::Transaction::scan_keys(self, range, limit)
+                .await?
+                .collect())
+        }
+        .boxed()
+    }
+
+    fn scan_stream(
+        &mut self,
+        range: BoundRange,
+        limit: u32,
+    ) -> BoxFuture<'_, BackendResult<BoxStream<'static, KvPair>>> {
+        async move {
+            Ok(tikv_client::Transaction::scan_stream(self, range, limit)
+                .await?
+                .boxed())
+        }
+        .boxed()
+    }
+
+    fn scan_reverse_stream(
+        &mut self,
+        range: BoundRange,
+        limit: u32,
+    ) -> BoxFuture<'_, BackendResult<BoxStream<'static, KvPair>>> {
+        async move {
+            Ok(
+                tikv_client::Transaction::scan_reverse_stream(self, range, limit)
+                    .await?
+                    .boxed(),
+            )
+        }
+        .boxed()
+    }
+
+    fn scan_keys_stream(
+        &mut self,
+        range: BoundRange,
+        limit: u32,
+    ) -> BoxFuture<'_, BackendResult<BoxStream<'static, Key>>> {
+        async move {
+            Ok(
+                tikv_client::Transaction::scan_keys_stream(self, range, limit)
+                    .await?
+                    .boxed(),
+            )
+        }
+        .boxed()
+    }
+
+    fn put(&mut self, key: Key, value: Value) -> BoxFuture<'_, BackendResult<()>> {
+        tikv_client::Transaction::put(self, key, value).boxed()
+    }
+
+    fn delete(&mut self, key: Key) -> BoxFuture<'_, BackendResult<()>> {
+        tikv_client::Transaction::delete(self, key).boxed()
+    }
+
+    fn commit(&mut self) -> BoxFuture<'_, BackendResult<Option<Timestamp>>> {
+        tikv_client::Transaction::commit(self).boxed()
+    }
+
+    fn rollback(&mut self) -> BoxFuture<'_, BackendResult<()>> {
+        tikv_client::Transaction::rollback(self).boxed()
+    }
+}
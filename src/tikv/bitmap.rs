@@ -0,0 +1,639 @@
+//! Bitmap commands on the string encoding.
+//!
+//! A bitmap is a string whose bits are addressed from the most significant
+//! bit of the first byte, so GET and GETRANGE read the same value.
+//! A string is kept whole in the value of its key, SETBIT rewrites it and
+//! the reads get it once. BITCOUNT and BITPOS go over the requested range in
+//! chunks of `BITMAP_CHUNK_SIZE` bytes instead of the bits one by one, BITPOS
+//! stopping at the first chunk holding the bit. BITOP reads its sources and
+//! writes the destination in one transaction, the sources being limited to
+//! `cmd_bitop_max_source_bytes` as they are all held in memory. BITFIELD
+//! runs its operations in order on the string read once, and writes it back
+//! if one of them is a SET or an INCRBY.
+
+use std::collections::HashMap;
+use std::convert::TryInto;
+use std::sync::Arc;
+
+use futures::future::FutureExt;
+use tikv_client::{Key, Value};
+use tokio::sync::Mutex;
+
+use super::backend::Transaction;
+use super::encoding::{DataType, KeyDecoder};
+use super::errors::{AsyncResult, RTError, REDIS_BITOP_SOURCE_TOO_LARGE_ERR, REDIS_WRONG_TYPE_ERR};
+use super::get_txn_client;
+use super::string::StringCommandCtx;
+use super::KEY_ENCODER;
+use crate::cmd_bitop_max_source_bytes_or_default;
+use crate::utils::{
+    expire_timestamp_of_new_key, key_is_expired, resp_array, resp_err, resp_int, resp_nil,
+};
+use crate::Frame;
+
+/// Bytes counted at once by BITCOUNT and BITPOS
+pub const BITMAP_CHUNK_SIZE: usize = 8 * 1024;
+
+/// Largest bit offset of SETBIT, like the 512MB limit of Redis strings
+pub const BITMAP_MAX_OFFSET: u64 = (1 << 32) - 1;
+
+/// The range of BITCOUNT and BITPOS, in bytes or with BIT in bits. A missing
+/// end is the end of the string.
+#[derive(Debug, Clone, Copy)]
+pub struct BitRange {
+    pub start: i64,
+    pub end: Option<i64>,
+    pub in_bits: bool,
+}
+
+impl BitRange {
+    /// The first and last bits of the range in a string of `len` bytes, None
+    /// if the range is empty. Negative bounds are from the end like GETRANGE.
+    fn resolve(&self, len: usize) -> Option<(u64, u64)> {
+        let total = if self.in_bits { len * 8 } else { len } as i64;
+        let mut start = self.start;
+        let mut end = self.end.unwrap_or(-1);
+        if start < 0 {
+            start = (total + start).max(0);
+        }
+        if end < 0 {
+            end = (total + end).max(0);
+        }
+        end = end.min(total - 1);
+        if total == 0 || start > end {
+            return None;
+        }
+        if self.in_bits {
+            Some((start as u64, end as u64))
+        } else {
+            Some((start as u64 * 8, end as u64 * 8 + 7))
+        }
+    }
+}
+
+/// The operation of BITOP
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BitOp {
+    And,
+    Or,
+    Xor,
+    Not,
+}
+
+impl BitOp {
+    pub fn from_name(name: &str) -> Option<BitOp> {
+        match name.to_uppercase().as_str() {
+            "AND" => Some(BitOp::And),
+            "OR" => Some(BitOp::Or),
+            "XOR" => Some(BitOp::Xor),
+            "NOT" => Some(BitOp::Not),
+            _ => None,
+        }
+    }
+
+    /// The result of the operation over the sources, as long as the longest
+    /// one with the shorter ones padded with zero bytes
+    fn apply(self, sources: &[Vec<u8>]) -> Vec<u8> {
+        let len = sources.iter().map(|s| s.len()).max().unwrap_or(0);
+        if self == BitOp::Not {
+            return sources[0].iter().map(|b| !b).collect();
+        }
+        let mut result = sources[0].clone();
+        result.resize(len, 0);
+        for source in &sources[1..] {
+            for (idx, byte) in result.iter_mut().enumerate() {
+                let other = source.get(idx).copied().unwrap_or(0);
+                match self {
+                    BitOp::And => *byte &= other,
+                    BitOp::Or => *byte |= other,
+                    BitOp::Xor => *byte ^= other,
+                    BitOp::Not => unreachable!(),
+                }
+            }
+        }
+        result
+    }
+}
+
+/// The integer type of a BITFIELD field, i1 to i64 or u1 to u63
+#[derive(Debug, Clone, Copy)]
+pub struct FieldType {
+    pub signed: bool,
+    pub bits: u32,
+}
+
+/// What SET and INCRBY of BITFIELD do with a value out of the range of the
+/// type: wrap it around, saturate it to the min or max, or no write
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Overflow {
+    Wrap,
+    Sat,
+    Fail,
+}
+
+/// An operation of BITFIELD on the field at a bit offset
+#[derive(Debug, Clone, Copy)]
+pub enum FieldOp {
+    Get(FieldType, u64),
+    Set(FieldType, u64, i64, Overflow),
+    Incrby(FieldType, u64, i64, Overflow),
+}
+
+impl FieldType {
+    /// The value of the `bits` low bits of a field, sign extended if signed
+    fn decode(self, raw: u64) -> i64 {
+        if self.signed && self.bits < 64 && raw & (1 << (self.bits - 1)) != 0 {
+            (raw | (u64::MAX << self.bits)) as i64
+        } else {
+            raw as i64
+        }
+    }
+
+    /// The value stored for `target`, None if FAIL refuses it
+    fn fit(self, target: i128, overflow: Overflow) -> Option<i64> {
+        let (min, max) = if self.signed {
+            (-(1i128 << (self.bits - 1)), (1i128 << (self.bits - 1)) - 1)
+        } else {
+            (0, (1i128 << self.bits) - 1)
+        };
+        if (min..=max).contains(&target) {
+            return Some(target as i64);
+        }
+        match overflow {
+            Overflow::Fail => None,
+            Overflow::Sat => Some(target.clamp(min, max) as i64),
+            Overflow::Wrap => {
+                let modulo = 1i128 << self.bits;
+                let wrapped = target.rem_euclid(modulo);
+                if wrapped > max {
+                    Some((wrapped - modulo) as i64)
+                } else {
+                    Some(wrapped as i64)
+                }
+            }
+        }
+    }
+}
+
+impl FieldOp {
+    fn is_write(&self) -> bool {
+        !matches!(self, FieldOp::Get(..))
+    }
+
+    /// The bit after the field
+    fn end(&self) -> u64 {
+        match self {
+            FieldOp::Get(ty, offset)
+            | FieldOp::Set(ty, offset, ..)
+            | FieldOp::Incrby(ty, offset, ..) => offset + ty.bits as u64,
+        }
+    }
+
+    /// Run the operation on the string, long enough for a write. Replies like
+    /// Redis with the value read, the previous value for SET and the new one
+    /// for INCRBY, nil if FAIL refused the write.
+    fn run(&self, data: &mut [u8]) -> Frame {
+        match *self {
+            FieldOp::Get(ty, offset) => resp_int(ty.decode(get_field(data, offset, ty.bits))),
+            FieldOp::Set(ty, offset, value, overflow) => {
+                let old = ty.decode(get_field(data, offset, ty.bits));
+                // like Redis an unsigned field is set with the value as u64
+                let target = if ty.signed {
+                    value as i128
+                } else {
+                    value as u64 as i128
+                };
+                match ty.fit(target, overflow) {
+                    Some(new) => {
+                        set_field(data, offset, ty.bits, new as u64);
+                        resp_int(old)
+                    }
+                    None => resp_nil(),
+                }
+            }
+            FieldOp::Incrby(ty, offset, incr, overflow) => {
+                let old = ty.decode(get_field(data, offset, ty.bits));
+                match ty.fit(old as i128 + incr as i128, overflow) {
+                    Some(new) => {
+                        set_field(data, offset, ty.bits, new as u64);
+                        resp_int(new)
+                    }
+                    None => resp_nil(),
+                }
+            }
+        }
+    }
+}
+
+/// The `bits` bits from `offset`, the most significant first
+fn get_field(data: &[u8], offset: u64, bits: u32) -> u64 {
+    (0..bits as u64).fold(0, |value, i| value << 1 | bit_at(data, offset + i) as u64)
+}
+
+/// Write the `bits` low bits of `value` from `offset`
+fn set_field(data: &mut [u8], offset: u64, bits: u32, value: u64) {
+    for i in 0..bits as u64 {
+        let bit = offset + i;
+        let mask = 0x80 >> (bit % 8);
+        if value >> (bits as u64 - 1 - i) & 1 == 1 {
+            data[(bit / 8) as usize] |= mask;
+        } else {
+            data[(bit / 8) as usize] &= !mask;
+        }
+    }
+}
+
+fn bit_at(data: &[u8], offset: u64) -> bool {
+    match data.get((offset / 8) as usize) {
+        Some(byte) => byte & (0x80 >> (offset % 8)) != 0,
+        None => false,
+    }
+}
+
+/// The mask of a byte from the bit `first` to the bit `last` of the byte
+fn byte_mask(first: u64, last: u64) -> u8 {
+    (0xffu8 >> (first % 8)) & (0xffu8 << (7 - last % 8))
+}
+
+/// The set bits from bit `first` to bit `last` included
+fn count_bits(data: &[u8], first: u64, last: u64) -> u64 {
+    let (first_byte, last_byte) = ((first / 8) as usize, (last / 8) as usize);
+    if first_byte == last_byte {
+        return (data[first_byte] & byte_mask(first, last)).count_ones() as u64;
+    }
+    let mut count = (data[first_byte] & byte_mask(first, 7)).count_ones() as u64
+        + (data[last_byte] & byte_mask(0, last)).count_ones() as u64;
+    for chunk in data[first_byte + 1..last_byte].chunks(BITMAP_CHUNK_SIZE) {
+        let mut words = chunk.chunks_exact(8);
+        for word in &mut words {
+            count += u64::from_be_bytes(word.try_into().unwrap()).count_ones() as u64;
+        }
+        count += words
+            .remainder()
+            .iter()
+            .map(|b| b.count_ones() as u64)
+            .sum::<u64>();
+    }
+    count
+}
+
+/// The first bit set to `bit` from bit `first` to bit `last` included
+fn find_bit(data: &[u8], bit: bool, first: u64, last: u64) -> Option<u64> {
+    // a clear bit is searched as a set bit of the inverted bytes
+    let byte_of = |idx: usize| if bit { data[idx] } else { !data[idx] };
+    let in_byte = |idx: usize, mask: u8| {
+        let masked = byte_of(idx) & mask;
+        if masked == 0 {
+            None
+        } else {
+            Some(idx as u64 * 8 + masked.leading_zeros() as u64)
+        }
+    };
+    let (first_byte, last_byte) = ((first / 8) as usize, (last / 8) as usize);
+    if first_byte == last_byte {
+        return in_byte(first_byte, byte_mask(first, last));
+    }
+    if let Some(pos) = in_byte(first_byte, byte_mask(first, 7)) {
+        return Some(pos);
+    }
+    let skip = if bit { 0x00 } else { 0xff };
+    let mut idx = first_byte + 1;
+    for chunk in data[idx..last_byte].chunks(BITMAP_CHUNK_SIZE) {
+        if let Some(found) = chunk.iter().position(|b| *b != skip) {
+            return in_byte(idx + found, 0xff);
+        }
+        idx += chunk.len();
+    }
+    in_byte(last_byte, byte_mask(0, last))
+}
+
+#[derive(Clone)]
+pub struct BitmapCommandCtx {
+    txn: Option<Arc<Mutex<Transaction>>>,
+}
+
+impl BitmapCommandCtx {
+    pub fn new(txn: Option<Arc<Mutex<Transaction>>>) -> Self {
+        BitmapCommandCtx { txn }
+    }
+
+    /// Set or clear the bit at `offset`, the string is padded with zero bytes
+    /// up to it and the ttl of an existing key is kept. Replies with the
+    /// previous bit.
+    pub async fn do_async_txnkv_setbit(
+        mut self,
+        key: &str,
+        offset: u64,
+        on: bool,
+    ) -> AsyncResult<Frame> {
+        let mut client = get_txn_client()?;
+        let ekey = KEY_ENCODER.encode_txnkv_string(key);
+        let key = key.to_owned();
+
+        let resp = client
+            .exec_in_txn(self.txn.clone(), |txn_rc| {
+                async move {
+                    if self.txn.is_none() {
+                        self.txn = Some(txn_rc.clone())
+                    }
+                    let mut data = vec![];
+                    let mut ttl = None;
+                    let mut txn = txn_rc.lock().await;
+                    if let Some(val) = txn.get(ekey.clone()).await? {
+                        if !matches!(KeyDecoder::decode_key_type(&val), DataType::String) {
+                            return Err(REDIS_WRONG_TYPE_ERR);
+                        }
+                        let old_ttl = KeyDecoder::decode_key_ttl(&val);
+                        if key_is_expired(old_ttl) {
+                            drop(txn);
+                            StringCommandCtx::new(self.txn.clone())
+                                .do_async_txnkv_string_expire_if_needed(&key)
+                                .await?;
+                            txn = txn_rc.lock().await;
+                        } else {
+                            data = KeyDecoder::decode_key_string_value(&val)?;
+                            ttl = Some(old_ttl);
+                        }
+                    }
+
+                    let old = bit_at(&data, offset);
+                    let idx = (offset / 8) as usize;
+                    if data.len() <= idx {
+                        data.resize(idx + 1, 0);
+                    }
+                    let mask = 0x80 >> (offset % 8);
+                    if on {
+                        data[idx] |= mask;
+                    } else {
+                        data[idx] &= !mask;
+                    }
+                    let ttl = ttl.unwrap_or_else(|| expire_timestamp_of_new_key(0));
+                    let eval = KEY_ENCODER.encode_txnkv_string_value(&mut data, ttl)?;
+                    txn.put(ekey, eval).await?;
+                    Ok(old as i64)
+                }
+                .boxed()
+            })
+            .await;
+
+        match resp {
+            Ok(n) => Ok(resp_int(n)),
+            Err(e) => Ok(resp_err(e)),
+        }
+    }
+
+    /// Store the result of `op` over the sources in `dest`, replacing it
+    /// whatever its type, an empty result deletes it. Missing sources are
+    /// empty strings. Replies with the length of the result.
+    pub async fn do_async_txnkv_bitop(
+        mut self,
+        op: BitOp,
+        dest: &str,
+        sources: &[String],
+    ) -> AsyncResult<Frame> {
+        let mut client = get_txn_client()?;
+        let dest = dest.to_owned();
+        let sources = sources.to_owned();
+        let ekeys = KEY_ENCODER.encode_txnkv_strings(&sources);
+        let limit = cmd_bitop_max_source_bytes_or_default() as usize;
+
+        let resp = client
+            .exec_in_txn(self.txn.clone(), |txn_rc| {
+                async move {
+                    if self.txn.is_none() {
+                        self.txn = Some(txn_rc.clone());
+                    }
+                    let mut txn = txn_rc.lock().await;
+                    let kv_map: HashMap<Key, Value> = txn
+                        .batch_get(ekeys.clone())
+                        .await?
+                        .map(|pair| (pair.0, pair.1))
+                        .collect();
+                    drop(txn);
+
+                    let mut datas = Vec::with_capacity(ekeys.len());
+                    for (idx, ekey) in ekeys.iter().enumerate() {
+                        let val = match kv_map.get(ekey) {
+                            Some(val) => val,
+                            None => {
+                                datas.push(vec![]);
+                                continue;
+                            }
+                        };
+                        if !matches!(KeyDecoder::decode_key_type(val), DataType::String) {
+                            return Err(REDIS_WRONG_TYPE_ERR);
+                        }
+                        if key_is_expired(KeyDecoder::decode_key_ttl(val)) {
+                            StringCommandCtx::new(self.txn.clone())
+                                .do_async_txnkv_string_expire_if_needed(&sources[idx])
+                                .await?;
+                            datas.push(vec![]);
+                            continue;
+                        }
+                        let data = KeyDecoder::decode_key_string_value(val)?;
+                        if limit > 0 && data.len() > limit {
+                            return Err(REDIS_BITOP_SOURCE_TOO_LARGE_ERR);
+                        }
+                        datas.push(data);
+                    }
+                    let mut result = op.apply(&datas);
+                    drop(datas);
+
+                    // the destination may be of any type, it is deleted like DEL
+                    match StringCommandCtx::new(self.txn.clone())
+                        .do_async_txnkv_del(&vec![dest.clone()])
+                        .await?
+                    {
+                        Frame::ErrorOwned(e) => return Err(RTError::Owned(e)),
+                        Frame::ErrorString(e) => return Err(RTError::String(e)),
+                        _ => {}
+                    }
+                    let len = result.len() as i64;
+                    if len > 0 {
+                        let ekey = KEY_ENCODER.encode_txnkv_string(&dest);
+                        let ttl = expire_timestamp_of_new_key(0);
+                        let eval = KEY_ENCODER.encode_txnkv_string_value(&mut result, ttl)?;
+                        txn_rc.lock().await.put(ekey, eval).await?;
+                    }
+                    Ok(len)
+                }
+                .boxed()
+            })
+            .await;
+
+        match resp {
+            Ok(n) => Ok(resp_int(n)),
+            Err(e) => Ok(resp_err(e)),
+        }
+    }
+
+    /// Run the BITFIELD operations in order. Without SET and INCRBY the
+    /// string is only read, otherwise it is padded with zero bytes up to the
+    /// last written field and written back with its ttl.
+    pub async fn do_async_txnkv_bitfield(
+        mut self,
+        key: &str,
+        ops: Vec<FieldOp>,
+    ) -> AsyncResult<Frame> {
+        let write_end = ops
+            .iter()
+            .filter(|op| op.is_write())
+            .map(|op| op.end())
+            .max();
+        let write_end = match write_end {
+            Some(end) => end,
+            None => {
+                let mut data = match self.read_bitmap(key).await {
+                    Ok(data) => data,
+                    Err(e) => return Ok(resp_err(e)),
+                };
+                return Ok(resp_array(ops.iter().map(|op| op.run(&mut data)).collect()));
+            }
+        };
+
+        let mut client = get_txn_client()?;
+        let ekey = KEY_ENCODER.encode_txnkv_string(key);
+        let key = key.to_owned();
+
+        let resp = client
+            .exec_in_txn(self.txn.clone(), |txn_rc| {
+                async move {
+                    if self.txn.is_none() {
+                        self.txn = Some(txn_rc.clone())
+                    }
+                    let mut data = vec![];
+                    let mut ttl = None;
+                    let mut txn = txn_rc.lock().await;
+                    if let Some(val) = txn.get(ekey.clone()).await? {
+                        if !matches!(KeyDecoder::decode_key_type(&val), DataType::String) {
+                            return Err(REDIS_WRONG_TYPE_ERR);
+                        }
+                        let old_ttl = KeyDecoder::decode_key_ttl(&val);
+                        if key_is_expired(old_ttl) {
+                            drop(txn);
+                            StringCommandCtx::new(self.txn.clone())
+                                .do_async_txnkv_string_expire_if_needed(&key)
+                                .await?;
+                            txn = txn_rc.lock().await;
+                        } else {
+                            data = KeyDecoder::decode_key_string_value(&val)?;
+                            ttl = Some(old_ttl);
+                        }
+                    }
+
+                    let len = ((write_end + 7) / 8) as usize;
+                    if data.len() < len {
+                        data.resize(len, 0);
+                    }
+                    let resp = ops.iter().map(|op| op.run(&mut data)).collect();
+                    let ttl = ttl.unwrap_or_else(|| expire_timestamp_of_new_key(0));
+                    let eval = KEY_ENCODER.encode_txnkv_string_value(&mut data, ttl)?;
+                    txn.put(ekey, eval).await?;
+                    Ok(resp_array(resp))
+                }
+                .boxed()
+            })
+            .await;
+
+        match resp {
+            Ok(frame) => Ok(frame),
+            Err(e) => Ok(resp_err(e)),
+        }
+    }
+
+    /// The bit at `offset`, 0 past the end of the string
+    pub async fn do_async_txnkv_getbit(self, key: &str, offset: u64) -> AsyncResult<Frame> {
+        match self.read_bitmap(key).await {
+            Ok(data) => Ok(resp_int(bit_at(&data, offset) as i64)),
+            Err(e) => Ok(resp_err(e)),
+        }
+    }
+
+    /// The number of set bits in the range, the whole string without one
+    pub async fn do_async_txnkv_bitcount(
+        self,
+        key: &str,
+        range: Option<BitRange>,
+    ) -> AsyncResult<Frame> {
+        let data = match self.read_bitmap(key).await {
+            Ok(data) => data,
+            Err(e) => return Ok(resp_err(e)),
+        };
+        let range = range.unwrap_or(BitRange {
+            start: 0,
+            end: None,
+            in_bits: false,
+        });
+        match range.resolve(data.len()) {
+            Some((first, last)) => Ok(resp_int(count_bits(&data, first, last) as i64)),
+            None => Ok(resp_int(0)),
+        }
+    }
+
+    /// The position of the first bit set to `bit` in the range, -1 if none.
+    /// Without an end the bits right of the string are clear, so a clear bit
+    /// is found at the end of a string of set bits.
+    pub async fn do_async_txnkv_bitpos(
+        self,
+        key: &str,
+        bit: bool,
+        range: BitRange,
+    ) -> AsyncResult<Frame> {
+        let data = match self.read_bitmap(key).await {
+            Ok(data) => data,
+            Err(e) => return Ok(resp_err(e)),
+        };
+        if data.is_empty() {
+            return Ok(resp_int(if bit { -1 } else { 0 }));
+        }
+        let (first, last) = match range.resolve(data.len()) {
+            Some(bounds) => bounds,
+            None => return Ok(resp_int(-1)),
+        };
+        match find_bit(&data, bit, first, last) {
+            Some(pos) => Ok(resp_int(pos as i64)),
+            None if !bit && range.end.is_none() => Ok(resp_int(data.len() as i64 * 8)),
+            None => Ok(resp_int(-1)),
+        }
+    }
+
+    /// The bytes of the string, empty if the key does not exist or expired
+    async fn read_bitmap(mut self, key: &str) -> AsyncResult<Vec<u8>> {
+        let mut client = get_txn_client()?;
+        let ekey = KEY_ENCODER.encode_txnkv_string(key);
+        let key = key.to_owned();
+
+        // like GET, a read from a new transaction is done with the latest commit
+        if self.txn.is_none() {
+            let readonly_txn = client.begin_consistent_read().await?;
+            self.txn = Some(Arc::new(Mutex::new(readonly_txn)));
+        }
+
+        client
+            .exec_in_txn(self.txn.clone(), |txn_rc| {
+                async move {
+                    if self.txn.is_none() {
+                        self.txn = Some(txn_rc.clone());
+                    }
+                    let mut txn = txn_rc.lock().await;
+                    let val = match txn.get(ekey).await? {
+                        Some(val) => val,
+                        None => return Ok(vec![]),
+                    };
+                    if !matches!(KeyDecoder::decode_key_type(&val), DataType::String) {
+                        return Err(REDIS_WRONG_TYPE_ERR);
+                    }
+                    if key_is_expired(KeyDecoder::decode_key_ttl(&val)) {
+                        drop(txn);
+                        StringCommandCtx::new(self.txn.clone())
+                            .do_async_txnkv_string_expire_if_needed(&key)
+                            .await?;
+                        return Ok(vec![]);
+                    }
+                    KeyDecoder::decode_key_string_value(&val)
+                }
+                .boxed()
+            })
+            .await
+    }
+}
@@ -1,52 +1,226 @@
+use std::any::Any;
+use std::cell::Cell;
 use std::convert::TryInto;
-use std::sync::Arc;
-use tokio::sync::Mutex;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex as StdMutex};
+use std::time::{SystemTime, UNIX_EPOCH};
+use tokio::sync::{oneshot, Mutex};
 
 use tikv_client::Error::StringError;
 use tikv_client::{
-    Backoff, BoundRange, ColumnFamily, Error, Key, KvPair, RawClient, Result as TiKVResult,
-    RetryOptions, Timestamp, TimestampExt, Transaction, TransactionClient, TransactionOptions,
-    Value,
+    Backoff, BoundRange, CheckLevel, ColumnFamily, Error, Key, KvPair, RawClient,
+    Result as TiKVResult, RetryOptions, Timestamp, TimestampExt, TransactionOptions, Value,
 };
 
-use crate::config::LOGGER;
+use crate::config::{ReadConsistency, LOGGER};
+use crate::priority::Priority;
 use crate::{
-    async_deletion_enabled_or_default, is_try_one_pc_commit, is_use_async_commit,
+    async_deletion_enabled_or_default, backend_leader_read_timeout_ms_or_default,
+    backend_read_consistency_or_default, backend_stale_read_ms_or_default,
+    config_resource_group_or_default, is_try_one_pc_commit, is_use_async_commit,
     is_use_pessimistic_txn, txn_lock_backoff_delay_attemps, txn_lock_backoff_delay_ms,
     txn_region_backoff_delay_attemps, txn_region_backoff_delay_ms, txn_retry_count,
 };
 
-use super::errors::{AsyncResult, RTError, KEY_VERSION_EXHUSTED_ERR};
+use super::backend::{StorageBackend, Transaction};
+use super::errors::{
+    AsyncResult, RTError, KEY_VERSION_EXHUSTED_ERR, REDIS_IDEMPOTENT_REPLAYED_ERR,
+};
+use super::idempotency;
+use super::retry::{is_region_error, region_backoff, with_region_retry};
 
 use futures::future::BoxFuture;
 
 use slog::{debug, error};
 
 use crate::metrics::{
-    ACQUIRE_LOCK_DURATION, TIKV_CLIENT_RETRIES, TIKV_ERR_COUNTER, TXN_COUNTER, TXN_DURATION,
-    TXN_MECHANISM_COUNTER, TXN_RETRY_COUNTER, TXN_RETRY_ERR, TXN_RETRY_KIND_COUNTER,
+    ACQUIRE_LOCK_DURATION, IDEMPOTENT_REPLAY_COUNTER, RETRIEVE_TSO_DURATION, SNAPSHOT_COUNTER,
+    TIKV_CLIENT_RETRIES, TIKV_ERR_COUNTER, TSO_BATCH_SIZE, TSO_READ_BATCH_SIZE, TXN_COUNTER,
+    TXN_DURATION, TXN_MECHANISM_COUNTER, TXN_RETRY_COUNTER, TXN_RETRY_ERR, TXN_RETRY_KIND_COUNTER,
 };
 
-use super::{sleep, KEY_ENCODER};
+use super::encoding::KeyEncoder;
+use super::KEY_ENCODER;
 use crate::server::duration_to_sec;
 use tokio::time::Instant;
 
 const MAX_DELAY_MS: u64 = 500;
+// errors of the raw client counted in TIKV_ERR_COUNTER
+const RAW_CLIENT_ERROR: &str = "raw_client_error";
+// timestamp requests waiting for PD
+static TSO_INFLIGHT_REQUESTS: AtomicU64 = AtomicU64::new(0);
+// bits of the logical part in a tso
+const TSO_LOGICAL_BITS: u64 = 18;
+// staleness of the fallback of the leader reads without backend.stale_read_ms
+const DEFAULT_FALLBACK_STALE_MS: u64 = 1000;
+
+tokio::task_local! {
+    /// Set while applying commands of a connection in READONLY mode, snapshot
+    /// reads of these commands use a stale timestamp.
+    pub static STALE_READ: bool;
+
+    /// Snapshot shared by consecutive read commands of a pipeline burst, used
+    /// by `exec_in_txn` instead of beginning a new transaction.
+    pub static PIPELINE_SNAPSHOT: Option<Arc<Mutex<Transaction>>>;
+
+    /// Idempotency token of the write command being applied, taken by the
+    /// first transaction `exec_in_txn` begins for the command.
+    pub static IDEMPOTENCY_TOKEN: Cell<Option<String>>;
+
+    /// Resource group of the connection applying the command, its storage
+    /// calls are tagged with it, see `resource_group`.
+    pub static RESOURCE_GROUP: String;
+
+    /// Priority class of the command being applied, its storage calls are
+    /// tagged with it.
+    pub static PRIORITY: Priority;
+}
+
+/// Resource group of the storage calls of the command being applied, that of
+/// the namespace out of a connection, the HTTP and gRPC calls for instance.
+pub fn resource_group() -> String {
+    RESOURCE_GROUP
+        .try_with(|group| group.clone())
+        .unwrap_or_else(|_| config_resource_group_or_default())
+}
+
+/// Priority class of the storage calls of the command being applied, normal
+/// out of a command, the background jobs for instance.
+pub fn request_priority() -> Priority {
+    PRIORITY.try_with(|p| *p).unwrap_or(Priority::Normal)
+}
+
+/// Timestamp requests of read only transactions waiting for the next fetch
+#[derive(Default)]
+struct ReadTimestampWaiters {
+    /// a fetch is in flight, the waiters get the timestamp of the next one
+    fetching: bool,
+    waiters: Vec<oneshot::Sender<Result<Timestamp, String>>>,
+}
+
+lazy_static! {
+    static ref READ_TIMESTAMP_WAITERS: StdMutex<ReadTimestampWaiters> =
+        StdMutex::new(ReadTimestampWaiters::default());
+}
+
+/// A timestamp request counted in `TSO_INFLIGHT_REQUESTS` till it is dropped,
+/// also when the request is cancelled.
+struct TsoInflight;
+
+impl TsoInflight {
+    /// Count a request, returns it with the number of requests in flight
+    fn start() -> (TsoInflight, u64) {
+        let inflight = TSO_INFLIGHT_REQUESTS.fetch_add(1, Ordering::Relaxed) + 1;
+        (TsoInflight, inflight)
+    }
+}
+
+impl Drop for TsoInflight {
+    fn drop(&mut self) {
+        TSO_INFLIGHT_REQUESTS.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+/// Fetch the timestamps of the read only transactions waiting, one for all
+/// of those waiting when the fetch is issued, till none is left.
+async fn fetch_read_timestamps(client: &'static dyn StorageBackend) {
+    loop {
+        let batch = {
+            let mut pending = READ_TIMESTAMP_WAITERS.lock().unwrap();
+            if pending.waiters.is_empty() {
+                pending.fetching = false;
+                return;
+            }
+            std::mem::take(&mut pending.waiters)
+        };
+        TSO_READ_BATCH_SIZE.observe(batch.len() as f64);
+        let ts = TxnClientWrapper::new(client)
+            .current_timestamp()
+            .await
+            .map_err(|e| e.to_string());
+        for waiter in batch {
+            let _ = waiter.send(ts.clone());
+        }
+    }
+}
+
+/// Timestamp `backend.stale_read_ms` in the past, none if it is not set.
+fn stale_read_timestamp() -> Option<Timestamp> {
+    let stale_ms = backend_stale_read_ms_or_default();
+    if stale_ms == 0 {
+        return None;
+    }
+    Some(timestamp_ms_ago(stale_ms))
+}
+
+fn timestamp_ms_ago(ms: u64) -> Timestamp {
+    let now_ms = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_millis() as u64;
+    let physical = now_ms.saturating_sub(ms);
+    Timestamp::from_version(physical << TSO_LOGICAL_BITS)
+}
+
+/// Timestamp for snapshot reads, `backend.stale_read_ms` in the past for
+/// READONLY connections, otherwise the latest.
+fn snapshot_read_timestamp() -> Timestamp {
+    if STALE_READ.try_with(|stale| *stale).unwrap_or(false) {
+        if let Some(ts) = stale_read_timestamp() {
+            return ts;
+        }
+    }
+    Timestamp::from_version(u64::MAX)
+}
 
 pub struct TxnClientWrapper<'a> {
-    client: &'a TransactionClient,
+    client: &'a dyn StorageBackend,
     retries: u32,
 }
 
 impl TxnClientWrapper<'static> {
-    pub fn new(c: &'static TransactionClient) -> Self {
+    pub fn new(c: &'static dyn StorageBackend) -> Self {
         TxnClientWrapper {
             client: c,
             retries: txn_retry_count(),
         }
     }
 
+    /// Begin the read only transaction of a command reading at the
+    /// `backend.read_consistency` of the namespace: at a timestamp from PD
+    /// for "linearizable", `backend.stale_read_ms` in the past for "stale",
+    /// otherwise like `begin_with_latest`.
+    pub async fn begin_consistent_read(&self) -> TiKVResult<Transaction> {
+        let ts = match backend_read_consistency_or_default() {
+            ReadConsistency::Linearizable => self.read_timestamp().await?,
+            ReadConsistency::Stale => {
+                stale_read_timestamp().unwrap_or_else(snapshot_read_timestamp)
+            }
+            ReadConsistency::Latest => return Ok(self.begin_with_latest()),
+        };
+        Ok(self.begin_read_only(ts))
+    }
+
+    /// Begin a read only transaction at `snapshot_read_timestamp`, falling
+    /// back to `backend.stale_read_ms`, or 1s, in the past on a read taking
+    /// longer than `backend.leader_read_timeout_ms`, see
+    /// `super::backend::Transaction`.
     pub fn begin_with_latest(&self) -> Transaction {
+        let ts = snapshot_read_timestamp();
+        let txn = self.begin_read_only(ts.clone());
+        if backend_leader_read_timeout_ms_or_default() == 0 {
+            return txn;
+        }
+        let fallback_ts =
+            stale_read_timestamp().unwrap_or_else(|| timestamp_ms_ago(DEFAULT_FALLBACK_STALE_MS));
+        if fallback_ts.version() >= ts.version() {
+            // already reading in the past
+            return txn;
+        }
+        txn.with_stale_fallback(self.begin_read_only(fallback_ts))
+    }
+
+    fn begin_read_only(&self, ts: Timestamp) -> Transaction {
         // add retry options
         let region_backoff = Backoff::no_jitter_backoff(
             txn_region_backoff_delay_ms(),
@@ -80,8 +254,66 @@ impl TxnClientWrapper<'static> {
             txn_options
         };
 
-        self.client
-            .new_transaction(Timestamp::from_version(u64::MAX), txn_options.read_only())
+        self.client.new_transaction(ts, txn_options.read_only())
+    }
+
+    /// Fetch a timestamp from PD.
+    ///
+    /// The pd client keeps a single TSO stream and coalesces all requests
+    /// pending at the same time into one batched RPC, so the number of
+    /// requests in flight when this one is issued is recorded as its batch size.
+    pub async fn current_timestamp(&self) -> TiKVResult<Timestamp> {
+        let (_inflight, batch_size) = TsoInflight::start();
+        TSO_BATCH_SIZE.observe(batch_size as f64);
+
+        let start_at = Instant::now();
+        let ts = self.client.current_timestamp().await;
+        RETRIEVE_TSO_DURATION.observe(duration_to_sec(Instant::now() - start_at));
+        ts
+    }
+
+    /// Fetch a timestamp for a read only transaction.
+    ///
+    /// The read only transactions share timestamps: a single fetch is in
+    /// flight at a time, and all those which asked for one meanwhile get the
+    /// timestamp of the next fetch, issued after they asked so their reads
+    /// see every write committed before. Transactions which write need a
+    /// timestamp of their own, see `current_timestamp`.
+    pub async fn read_timestamp(&self) -> TiKVResult<Timestamp> {
+        let (tx, rx) = oneshot::channel();
+        let lead = {
+            let mut pending = READ_TIMESTAMP_WAITERS.lock().unwrap();
+            pending.waiters.push(tx);
+            !std::mem::replace(&mut pending.fetching, true)
+        };
+        if lead {
+            tokio::spawn(fetch_read_timestamps(self.client));
+        }
+        match rx.await {
+            Ok(Ok(ts)) => Ok(ts),
+            Ok(Err(e)) => Err(StringError(e)),
+            Err(_) => Err(StringError("timestamp fetch cancelled".to_owned())),
+        }
+    }
+
+    /// Begin the transaction shared by the reads of a pipeline burst. It only
+    /// buffers lazy expiration deletes, which are fine to lose if the
+    /// connection is closed before it is committed. The bursts of a "stale"
+    /// namespace read `backend.stale_read_ms` in the past without asking PD.
+    pub async fn begin_shared_snapshot(&self) -> TiKVResult<Transaction> {
+        SNAPSHOT_COUNTER.inc();
+        let stale = match backend_read_consistency_or_default() {
+            ReadConsistency::Stale => stale_read_timestamp(),
+            _ => None,
+        };
+        let ts = match stale {
+            Some(ts) => ts,
+            None => self.current_timestamp().await?,
+        };
+        Ok(self.client.new_transaction(
+            ts,
+            TransactionOptions::new_optimistic().drop_check(CheckLevel::None),
+        ))
     }
 
     pub async fn begin(&self) -> TiKVResult<Transaction> {
@@ -123,9 +355,9 @@ impl TxnClientWrapper<'static> {
             .with_label_values(&[mechanism.0, mechanism.1])
             .inc();
 
-        self.client
-            .begin_with_options(txn_options)
+        self.current_timestamp()
             .await
+            .map(|ts| self.client.new_transaction(ts, txn_options))
             .map_err(|err| {
                 TIKV_ERR_COUNTER
                     .with_label_values(&["start_txn_error"])
@@ -135,17 +367,14 @@ impl TxnClientWrapper<'static> {
     }
 
     fn error_retryable(&self, err: &Error) -> bool {
-        let ret = matches!(
-            err,
-            Error::RegionError(_)
-                | Error::EntryNotFoundInRegionCache
-                | Error::KvError { message: _ }
-                | Error::MultipleKeyErrors(_)
-                | Error::PessimisticLockError {
+        let ret = is_region_error(err)
+            || matches!(
+                err,
+                Error::PessimisticLockError {
                     inner: _,
                     success_keys: _,
                 }
-        );
+            );
 
         if ret {
             TIKV_CLIENT_RETRIES.inc();
@@ -160,8 +389,15 @@ impl TxnClientWrapper<'static> {
         f: F,
     ) -> AsyncResult<T>
     where
+        T: 'static,
         F: FnOnce(Arc<Mutex<Transaction>>) -> BoxFuture<'static, AsyncResult<T>> + Clone,
     {
+        let txn = txn.or_else(|| {
+            PIPELINE_SNAPSHOT
+                .try_with(|snapshot| snapshot.clone())
+                .ok()
+                .flatten()
+        });
         match txn {
             Some(txn) => {
                 // call f
@@ -175,6 +411,7 @@ impl TxnClientWrapper<'static> {
                 }
             }
             None => {
+                let token = IDEMPOTENCY_TOKEN.try_with(|t| t.take()).ok().flatten();
                 let mut retry_count = 0;
                 while self.retries > 0 {
                     self.retries -= 1;
@@ -204,6 +441,29 @@ impl TxnClientWrapper<'static> {
                         }
                     };
 
+                    // the write was committed by a previous try or request with the same token
+                    let mut txn = txn;
+                    if let Some(token) = &token {
+                        match idempotency::recorded_reply(&mut txn, token).await {
+                            Ok(Some(reply)) => {
+                                txn.rollback().await?;
+                                IDEMPOTENT_REPLAY_COUNTER.inc();
+                                return match reply.map(|r| Box::new(r) as Box<dyn Any>) {
+                                    Some(reply) => match reply.downcast::<T>() {
+                                        Ok(reply) => Ok(*reply),
+                                        Err(_) => Err(REDIS_IDEMPOTENT_REPLAYED_ERR),
+                                    },
+                                    None => Err(REDIS_IDEMPOTENT_REPLAYED_ERR),
+                                };
+                            }
+                            Ok(None) => {}
+                            Err(e) => {
+                                txn.rollback().await?;
+                                return Err(e);
+                            }
+                        }
+                    }
+
                     let txn_arc = Arc::new(Mutex::new(txn));
 
                     // call f
@@ -216,6 +476,13 @@ impl TxnClientWrapper<'static> {
                     let mut txn = txn_arc.lock().await;
                     let duration = Instant::now() - start_at;
                     ACQUIRE_LOCK_DURATION.observe(duration_to_sec(duration));
+                    if let (Some(token), Ok(res)) = (&token, &result) {
+                        let record = idempotency::encode_record(res);
+                        if let Err(e) = txn.put(idempotency::token_key(token), record).await {
+                            txn.rollback().await?;
+                            return Err(e.into());
+                        }
+                    }
                     match result {
                         Ok(res) => match txn.commit().await {
                             Ok(_) => {
@@ -261,7 +528,7 @@ impl TxnClientWrapper<'static> {
                     }
 
                     // backoff retry
-                    sleep(std::cmp::min(2 + retry_count * 10, 200)).await;
+                    region_backoff(retry_count).await;
                 }
                 error!(LOGGER, "transaction retry count reached limit");
                 TXN_RETRY_ERR
@@ -277,13 +544,22 @@ impl TxnClientWrapper<'static> {
 
 // get_version_for_new must be called outside of a MutexGuard, otherwise it will deadlock.
 pub async fn get_version_for_new(key: &str, txn_rc: Arc<Mutex<Transaction>>) -> AsyncResult<u16> {
+    get_version_for_new_with(&KEY_ENCODER, key, txn_rc).await
+}
+
+/// `get_version_for_new` of a key encoded by `encoder`, of another namespace
+pub async fn get_version_for_new_with(
+    encoder: &KeyEncoder,
+    key: &str,
+    txn_rc: Arc<Mutex<Transaction>>,
+) -> AsyncResult<u16> {
     // check if async deletion is enabled, return ASAP if not
     if !async_deletion_enabled_or_default() {
         return Ok(0);
     }
 
     let mut txn = txn_rc.lock().await;
-    let gc_key = KEY_ENCODER.encode_txnkv_gc_key(key);
+    let gc_key = encoder.encode_txnkv_gc_key(key);
     let next_version = txn.get(gc_key).await?.map_or_else(
         || 0,
         |v| {
@@ -296,7 +572,7 @@ pub async fn get_version_for_new(key: &str, txn_rc: Arc<Mutex<Transaction>>) ->
         },
     );
     // check next version available
-    let gc_version_key = KEY_ENCODER.encode_txnkv_gc_version_key(key, next_version);
+    let gc_version_key = encoder.encode_txnkv_gc_version_key(key, next_version);
     txn.get(gc_version_key)
         .await?
         .map_or_else(|| Ok(next_version), |_| Err(KEY_VERSION_EXHUSTED_ERR))
@@ -320,71 +596,18 @@ impl RawClientWrapper {
         self.client.with_cf(cf)
     }
 
-    fn error_retryable(&self, err: &Error) -> bool {
-        let ret = matches!(
-            err,
-            Error::RegionError(_)
-                | Error::EntryNotFoundInRegionCache
-                | Error::KvError { message: _ }
-                | Error::MultipleKeyErrors(_)
-        );
-
-        if ret {
-            TIKV_CLIENT_RETRIES.inc();
-        }
-        ret
-    }
-
     pub async fn get(&self, key: Key) -> Result<Option<Value>, Error> {
-        let mut last_err: Option<Error> = None;
-        for i in 0..self.retries {
-            match self.client.get(key.clone()).await {
-                Ok(val) => {
-                    return Ok(val);
-                }
-                Err(err) => {
-                    TIKV_ERR_COUNTER
-                        .with_label_values(&["raw_client_error"])
-                        .inc();
-                    if self.error_retryable(&err) {
-                        last_err.replace(err);
-                        sleep(std::cmp::min(2 + i, 200)).await;
-                        continue;
-                    }
-                    return Err(err);
-                }
-            }
-        }
-        match last_err {
-            Some(err) => Err(err),
-            None => Ok(None),
-        }
+        with_region_retry(RAW_CLIENT_ERROR, self.retries, || {
+            self.client.get(key.clone())
+        })
+        .await
     }
 
     pub async fn put(&self, key: Key, val: Value) -> Result<(), Error> {
-        let mut last_err: Option<Error> = None;
-        for i in 0..self.retries {
-            match self.client.put(key.clone(), val.to_owned()).await {
-                Ok(_) => {
-                    return Ok(());
-                }
-                Err(err) => {
-                    TIKV_ERR_COUNTER
-                        .with_label_values(&["raw_client_error"])
-                        .inc();
-                    if self.error_retryable(&err) {
-                        last_err.replace(err);
-                        sleep(std::cmp::min(2 + i, 200)).await;
-                        continue;
-                    }
-                    return Err(err);
-                }
-            }
-        }
-        match last_err {
-            Some(err) => Err(err),
-            None => Ok(()),
-        }
+        with_region_retry(RAW_CLIENT_ERROR, self.retries, || {
+            self.client.put(key.clone(), val.to_owned())
+        })
+        .await
     }
 
     pub async fn compare_and_swap(
@@ -393,166 +616,48 @@ impl RawClientWrapper {
         prev_val: Option<Value>,
         val: Value,
     ) -> Result<(Option<Value>, bool), Error> {
-        let mut last_err: Option<Error> = None;
-        for i in 0..self.retries {
-            match self
-                .client
-                .with_atomic_for_cas()
-                .compare_and_swap(key.clone(), prev_val.clone(), val.to_owned())
-                .await
-            {
-                Ok((val, swapped)) => {
-                    return Ok((val, swapped));
-                }
-                Err(err) => {
-                    TIKV_ERR_COUNTER
-                        .with_label_values(&["raw_client_error"])
-                        .inc();
-                    if self.error_retryable(&err) {
-                        last_err.replace(err);
-                        sleep(std::cmp::min(2 + i, 200)).await;
-                        continue;
-                    }
-                    return Err(err);
-                }
-            }
-        }
-        match last_err {
-            Some(err) => Err(err),
-            None => Ok((None, false)),
-        }
+        let client = self.client.with_atomic_for_cas();
+        with_region_retry(RAW_CLIENT_ERROR, self.retries, || {
+            client.compare_and_swap(key.clone(), prev_val.clone(), val.to_owned())
+        })
+        .await
     }
 
     #[allow(dead_code)]
     pub async fn batch_delete(&self, keys: Vec<Key>) -> Result<(), Error> {
-        let mut last_err: Option<Error> = None;
-        for i in 0..self.retries {
-            match self.client.batch_delete(keys.clone()).await {
-                Ok(val) => {
-                    return Ok(val);
-                }
-                Err(err) => {
-                    TIKV_ERR_COUNTER
-                        .with_label_values(&["raw_client_error"])
-                        .inc();
-                    if self.error_retryable(&err) {
-                        last_err.replace(err);
-                        sleep(std::cmp::min(2 + i, 200)).await;
-                        continue;
-                    }
-                    return Err(err);
-                }
-            }
-        }
-        match last_err {
-            Some(err) => Err(err),
-            None => Ok(()),
-        }
+        with_region_retry(RAW_CLIENT_ERROR, self.retries, || {
+            self.client.batch_delete(keys.clone())
+        })
+        .await
     }
 
     #[allow(dead_code)]
     pub async fn scan(&self, range: BoundRange, limit: u32) -> Result<Vec<KvPair>, Error> {
-        let mut last_err: Option<Error> = None;
-        for i in 0..self.retries {
-            match self.client.scan(range.clone(), limit).await {
-                Ok(val) => {
-                    return Ok(val);
-                }
-                Err(err) => {
-                    TIKV_ERR_COUNTER
-                        .with_label_values(&["raw_client_error"])
-                        .inc();
-                    if self.error_retryable(&err) {
-                        last_err.replace(err);
-                        sleep(std::cmp::min(2 + i, 200)).await;
-                        continue;
-                    }
-                    return Err(err);
-                }
-            }
-        }
-        match last_err {
-            Some(err) => Err(err),
-            None => Ok(Vec::new()),
-        }
+        with_region_retry(RAW_CLIENT_ERROR, self.retries, || {
+            self.client.scan(range.clone(), limit)
+        })
+        .await
     }
 
     pub async fn batch_get(&self, keys: Vec<Key>) -> Result<Vec<KvPair>, Error> {
-        let mut last_err: Option<Error> = None;
-        for i in 0..self.retries {
-            match self.client.batch_get(keys.clone()).await {
-                Ok(val) => {
-                    return Ok(val);
-                }
-                Err(err) => {
-                    TIKV_ERR_COUNTER
-                        .with_label_values(&["raw_client_error"])
-                        .inc();
-                    if self.error_retryable(&err) {
-                        last_err.replace(err);
-                        sleep(std::cmp::min(2 + i, 200)).await;
-                        continue;
-                    }
-                    return Err(err);
-                }
-            }
-        }
-        match last_err {
-            Some(err) => Err(err),
-            None => Ok(Vec::new()),
-        }
+        with_region_retry(RAW_CLIENT_ERROR, self.retries, || {
+            self.client.batch_get(keys.clone())
+        })
+        .await
     }
 
     pub async fn batch_put(&self, kvs: Vec<KvPair>) -> Result<(), Error> {
-        let mut last_err: Option<Error> = None;
-        for i in 0..self.retries {
-            match self.client.batch_put(kvs.clone()).await {
-                Ok(val) => {
-                    return Ok(val);
-                }
-                Err(err) => {
-                    TIKV_ERR_COUNTER
-                        .with_label_values(&["raw_client_error"])
-                        .inc();
-                    if self.error_retryable(&err) {
-                        last_err.replace(err);
-                        sleep(std::cmp::min(2 + i, 200)).await;
-                        continue;
-                    }
-                    return Err(err);
-                }
-            }
-        }
-        match last_err {
-            Some(err) => Err(err),
-            None => Ok(()),
-        }
+        with_region_retry(RAW_CLIENT_ERROR, self.retries, || {
+            self.client.batch_put(kvs.clone())
+        })
+        .await
     }
 
     #[allow(dead_code)]
     pub async fn delete_range(&self, range: BoundRange) -> Result<(), Error> {
-        let mut last_err: Option<Error> = None;
-        for i in 0..self.retries {
-            match self.client.delete_range(range.clone()).await {
-                Ok(val) => {
-                    return Ok(val);
-                }
-                Err(err) => {
-                    TIKV_ERR_COUNTER
-                        .with_label_values(&["raw_client_error"])
-                        .inc();
-                    if self.error_retryable(&err) {
-                        last_err.replace(err);
-                        sleep(std::cmp::min(2 + i, 200)).await;
-                        continue;
-                    }
-                    return Err(err);
-                }
-            }
-        }
-        match last_err {
-            Some(err) => Err(err),
-            None => Ok(()),
-        }
+        with_region_retry(RAW_CLIENT_ERROR, self.retries, || {
+            self.client.delete_range(range.clone())
+        })
+        .await
     }
 }
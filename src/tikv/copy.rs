@@ -0,0 +1,245 @@
+//! COPY of a key of any type.
+//!
+//! All the keys of a user key share a prefix made of it, followed by their
+//! type and the version of the key, see `KeyEncoder`. The copy is a scan of
+//! that prefix for the keys of the current version of the source, written
+//! again with the prefix of the destination and a new version, in the same
+//! transaction as the read of the source. The version of the destination is
+//! new so the data keys of a former destination still waiting for the GC are
+//! not mistaken for the copied ones. Strings and JSON documents are only a
+//! meta value, copied as is.
+//!
+//! TIDIS.MOVE writes the keys the same way with the prefix of another
+//! namespace, then deletes the source in the same transaction, so the key
+//! is in exactly one of the namespaces for the readers. The whole key is
+//! written in one transaction, bounded by the transaction size limit of
+//! TiKV. Strings sealed with the data key of this namespace are not moved,
+//! and the fields of a moved hash are only indexed in the destination once
+//! written again, like hashes written before their index was declared.
+
+use std::convert::TryInto;
+use std::ops::Range;
+use std::sync::Arc;
+
+use futures::future::FutureExt;
+use futures::StreamExt;
+use tikv_client::Key;
+use tokio::sync::Mutex;
+
+use super::backend::Transaction;
+use super::client::{get_version_for_new, get_version_for_new_with};
+use super::encoding::encode::{DATA_TYPE_HASH, STRING_VALUE_SEALED};
+use super::encoding::{DataType, KeyDecoder, KeyEncoder};
+use super::errors::{AsyncResult, REDIS_MOVE_SEALED_VALUE_ERR, REDIS_SAME_OBJECT_ERR};
+use super::index::update_field_index;
+use super::sort::check_reply;
+use super::string::StringCommandCtx;
+use super::KEY_ENCODER;
+use super::{get_instance_id, get_txn_client};
+use crate::utils::{key_is_expired, resp_err, resp_int};
+use crate::Frame;
+
+#[derive(Clone)]
+pub struct CopyCommandCtx {
+    txn: Option<Arc<Mutex<Transaction>>>,
+}
+
+impl CopyCommandCtx {
+    pub fn new(txn: Option<Arc<Mutex<Transaction>>>) -> Self {
+        CopyCommandCtx { txn }
+    }
+
+    /// Copy `src` to `dst` with its ttl, 1 if copied. An existing `dst` is
+    /// only replaced with `replace`, otherwise nothing is copied.
+    pub async fn do_async_txnkv_copy(
+        mut self,
+        src: &str,
+        dst: &str,
+        replace: bool,
+    ) -> AsyncResult<Frame> {
+        if src == dst {
+            return Ok(resp_err(REDIS_SAME_OBJECT_ERR));
+        }
+        let mut client = get_txn_client()?;
+        let src = src.to_owned();
+        let dst = dst.to_owned();
+
+        let resp = client
+            .exec_in_txn(self.txn.clone(), |txn_rc| {
+                async move {
+                    if self.txn.is_none() {
+                        self.txn = Some(txn_rc.clone());
+                    }
+
+                    let dst_meta_key = KEY_ENCODER.encode_txnkv_meta_key(&dst);
+                    let (src_meta, dst_meta) = {
+                        let mut txn = txn_rc.lock().await;
+                        let src_meta = txn.get(KEY_ENCODER.encode_txnkv_meta_key(&src)).await?;
+                        (src_meta, txn.get(dst_meta_key.clone()).await?)
+                    };
+                    let src_meta = match src_meta {
+                        Some(meta) if !key_is_expired(KeyDecoder::decode_key_ttl(&meta)) => meta,
+                        _ => return Ok(0),
+                    };
+                    if let Some(dst_meta) = dst_meta {
+                        if !replace && !key_is_expired(KeyDecoder::decode_key_ttl(&dst_meta)) {
+                            return Ok(0);
+                        }
+                        // an expired destination is removed like a replaced one
+                        check_reply(
+                            StringCommandCtx::new(self.txn.clone())
+                                .do_async_txnkv_del(&vec![dst.clone()])
+                                .await?,
+                        )?;
+                    }
+
+                    if matches!(
+                        KeyDecoder::decode_key_type(&src_meta),
+                        DataType::String | DataType::Json
+                    ) {
+                        txn_rc.lock().await.put(dst_meta_key, src_meta).await?;
+                        return Ok(1);
+                    }
+
+                    let version = KeyDecoder::decode_key_version(&src_meta);
+                    let new_version = get_version_for_new(&dst, txn_rc.clone()).await?;
+                    let mut txn = txn_rc.lock().await;
+                    let mut dst_meta = src_meta;
+                    dst_meta[9..11].copy_from_slice(&new_version.to_be_bytes());
+                    txn.put(dst_meta_key, dst_meta).await?;
+
+                    let dst_prefix = KEY_ENCODER.encode_txnkv_userkey_prefix(dst.as_bytes());
+                    copy_data_keys(
+                        &mut txn,
+                        &src,
+                        version,
+                        &dst_prefix,
+                        new_version,
+                        Some(&dst),
+                    )
+                    .await?;
+                    Ok(1)
+                }
+                .boxed()
+            })
+            .await;
+
+        match resp {
+            Ok(v) => Ok(resp_int(v)),
+            Err(e) => Ok(resp_err(e)),
+        }
+    }
+
+    /// Move `key` to the namespace `namespace` with its ttl, 1 if moved. A key
+    /// of the same name in the namespace, even expired and not removed yet,
+    /// is kept and nothing is moved.
+    pub async fn do_async_txnkv_move(mut self, key: &str, namespace: u16) -> AsyncResult<Frame> {
+        if u64::from(namespace) == get_instance_id() {
+            return Ok(resp_err(REDIS_SAME_OBJECT_ERR));
+        }
+        let mut client = get_txn_client()?;
+        let key = key.to_owned();
+
+        let resp = client
+            .exec_in_txn(self.txn.clone(), |txn_rc| {
+                async move {
+                    if self.txn.is_none() {
+                        self.txn = Some(txn_rc.clone());
+                    }
+
+                    let target = KeyEncoder::with_instance_id(namespace);
+                    let dst_meta_key = target.encode_txnkv_meta_key(&key);
+                    let (src_meta, dst_meta) = {
+                        let mut txn = txn_rc.lock().await;
+                        let src_meta = txn.get(KEY_ENCODER.encode_txnkv_meta_key(&key)).await?;
+                        (src_meta, txn.get(dst_meta_key.clone()).await?)
+                    };
+                    let src_meta = match src_meta {
+                        Some(meta) if !key_is_expired(KeyDecoder::decode_key_ttl(&meta)) => meta,
+                        _ => return Ok(0),
+                    };
+                    if dst_meta.is_some() {
+                        return Ok(0);
+                    }
+
+                    if matches!(
+                        KeyDecoder::decode_key_type(&src_meta),
+                        DataType::String | DataType::Json
+                    ) {
+                        // sealed by the data key of this namespace only
+                        if KeyDecoder::decode_key_version(&src_meta) == STRING_VALUE_SEALED {
+                            return Err(REDIS_MOVE_SEALED_VALUE_ERR);
+                        }
+                        txn_rc.lock().await.put(dst_meta_key, src_meta).await?;
+                    } else {
+                        let version = KeyDecoder::decode_key_version(&src_meta);
+                        let new_version =
+                            get_version_for_new_with(&target, &key, txn_rc.clone()).await?;
+                        let mut txn = txn_rc.lock().await;
+                        let mut dst_meta = src_meta;
+                        dst_meta[9..11].copy_from_slice(&new_version.to_be_bytes());
+                        txn.put(dst_meta_key, dst_meta).await?;
+
+                        // the indexes of the namespace are not known here
+                        let dst_prefix = target.encode_txnkv_userkey_prefix(key.as_bytes());
+                        copy_data_keys(&mut txn, &key, version, &dst_prefix, new_version, None)
+                            .await?;
+                    }
+
+                    check_reply(
+                        StringCommandCtx::new(self.txn.clone())
+                            .do_async_txnkv_del(&vec![key.clone()])
+                            .await?,
+                    )?;
+                    Ok(1)
+                }
+                .boxed()
+            })
+            .await;
+
+        match resp {
+            Ok(v) => Ok(resp_int(v)),
+            Err(e) => Ok(resp_err(e)),
+        }
+    }
+}
+
+/// Write the data keys of the `version` of `src` again with `dst_prefix`, the
+/// user key prefix of the destination, and `new_version`. The fields of a
+/// hash are indexed for `indexed_dst`.
+async fn copy_data_keys(
+    txn: &mut Transaction,
+    src: &str,
+    version: u16,
+    dst_prefix: &[u8],
+    new_version: u16,
+    indexed_dst: Option<&str>,
+) -> AsyncResult<()> {
+    let src_prefix = KEY_ENCODER.encode_txnkv_userkey_prefix(src.as_bytes());
+    let plen = src_prefix.len();
+    let range: Range<Key> = src_prefix.into()..KEY_ENCODER.encode_txnkv_userkey_end(src.as_bytes());
+    let mut iter = txn.scan_stream(range, u32::MAX).await?;
+    while let Some(kv) = iter.next().await {
+        let key: Vec<u8> = kv.0.into();
+        // the meta key itself, or keys of older versions left to the GC
+        if key.len() < plen + 3
+            || u16::from_be_bytes(key[plen + 1..plen + 3].try_into().unwrap()) != version
+        {
+            continue;
+        }
+        let mut new_key = Vec::with_capacity(dst_prefix.len() + key.len() - plen);
+        new_key.extend_from_slice(dst_prefix);
+        new_key.push(key[plen]);
+        new_key.extend_from_slice(&new_version.to_be_bytes());
+        new_key.extend_from_slice(&key[plen + 3..]);
+
+        if key[plen] == DATA_TYPE_HASH {
+            if let Some(dst) = indexed_dst {
+                let field = String::from_utf8_lossy(&key[plen + 4..]).to_string();
+                update_field_index(txn, dst, &field, new_key.clone().into(), Some(&kv.1)).await?;
+            }
+        }
+        txn.put(new_key, kv.1).await?;
+    }
+    Ok(())
+}
@@ -0,0 +1,228 @@
+//! DUMP and RESTORE of a key of any type but streams and JSON documents.
+//!
+//! The value is read whole in one transaction and serialized like Redis does,
+//! see `crate::rdb`, without the ttl which RESTORE takes as an argument. A
+//! restored value is written with the commands of its type in one
+//! transaction with the removal of a replaced key and the ttl.
+
+use std::sync::Arc;
+
+use bytes::Bytes;
+use futures::future::FutureExt;
+use tikv_client::KvPair;
+use tokio::sync::Mutex;
+
+use super::backend::Transaction;
+use super::encoding::{DataType, KeyDecoder};
+use super::errors::{AsyncResult, REDIS_BUSYKEY_ERR, REDIS_DUMP_TYPE_NOT_SUPPORTED_ERR};
+use super::get_txn_client;
+use super::hash::HashCommandCtx;
+use super::list::ListCommandCtx;
+use super::set::SetCommandCtx;
+use super::sort::{check_reply, frame_elements};
+use super::string::StringCommandCtx;
+use super::zset::ZsetCommandCtx;
+use super::KEY_ENCODER;
+use crate::rdb::{dump, RdbValue};
+use crate::utils::{
+    key_is_expired, now_timestamp_in_millis, resp_bulk, resp_err, resp_nil, resp_ok,
+};
+use crate::Frame;
+
+#[derive(Clone)]
+pub struct DumpCommandCtx {
+    txn: Option<Arc<Mutex<Transaction>>>,
+}
+
+fn bytes_to_vecs(elements: Vec<Bytes>) -> Vec<Vec<u8>> {
+    elements.into_iter().map(|e| e.to_vec()).collect()
+}
+
+impl DumpCommandCtx {
+    pub fn new(txn: Option<Arc<Mutex<Transaction>>>) -> Self {
+        DumpCommandCtx { txn }
+    }
+
+    /// The serialized value of the key, nil if it does not exist
+    pub async fn do_async_txnkv_dump(self, key: &str) -> AsyncResult<Frame> {
+        match self.do_async_txnkv_dump_value(key).await {
+            Ok(Some((payload, _))) => Ok(resp_bulk(payload)),
+            Ok(None) => Ok(resp_nil()),
+            Err(e) => Ok(resp_err(e)),
+        }
+    }
+
+    /// The serialized value of the key with its expire timestamp in
+    /// milliseconds, 0 if it does not expire, read in the same transaction
+    pub async fn do_async_txnkv_dump_value(
+        mut self,
+        key: &str,
+    ) -> AsyncResult<Option<(Vec<u8>, u64)>> {
+        let mut client = get_txn_client()?;
+        let meta_key = KEY_ENCODER.encode_txnkv_meta_key(key);
+        let key = key.to_owned();
+
+        // nothing is written, read with latest commit like GET
+        if self.txn.is_none() {
+            let readonly_txn = client.begin_consistent_read().await?;
+            self.txn = Some(Arc::new(Mutex::new(readonly_txn)));
+        }
+
+        client
+            .exec_in_txn(self.txn.clone(), |txn_rc| {
+                async move {
+                    if self.txn.is_none() {
+                        self.txn = Some(txn_rc.clone());
+                    }
+                    let meta_value = match txn_rc.lock().await.get(meta_key).await? {
+                        Some(meta) if !key_is_expired(KeyDecoder::decode_key_ttl(&meta)) => meta,
+                        _ => return Ok(None),
+                    };
+                    let value = match KeyDecoder::decode_key_type(&meta_value) {
+                        DataType::String => {
+                            RdbValue::String(KeyDecoder::decode_key_string_value(&meta_value)?)
+                        }
+                        DataType::List => RdbValue::List(bytes_to_vecs(frame_elements(
+                            ListCommandCtx::new(self.txn.clone())
+                                .do_async_txnkv_lrange(&key, 0, -1)
+                                .await?,
+                        )?)),
+                        DataType::Set => RdbValue::Set(bytes_to_vecs(frame_elements(
+                            SetCommandCtx::new(self.txn.clone())
+                                .do_async_txnkv_smembers(&key)
+                                .await?,
+                        )?)),
+                        DataType::Hash => {
+                            let fvs = frame_elements(
+                                HashCommandCtx::new(self.txn.clone())
+                                    .do_async_txnkv_hgetall(&key, true, true)
+                                    .await?,
+                            )?;
+                            RdbValue::Hash(
+                                fvs.chunks(2)
+                                    .map(|fv| (fv[0].to_vec(), fv[1].to_vec()))
+                                    .collect(),
+                            )
+                        }
+                        DataType::Zset => {
+                            let members = frame_elements(
+                                ZsetCommandCtx::new(self.txn.clone())
+                                    .do_async_txnkv_zrange(&key, 0, -1, true, false)
+                                    .await?,
+                            )?;
+                            let mut scored = Vec::with_capacity(members.len() / 2);
+                            for ms in members.chunks(2) {
+                                // displayed from the f64 of the score key
+                                let score = String::from_utf8_lossy(&ms[1])
+                                    .parse::<f64>()
+                                    .unwrap_or_default();
+                                scored.push((ms[0].to_vec(), score));
+                            }
+                            RdbValue::Zset(scored)
+                        }
+                        _ => return Err(REDIS_DUMP_TYPE_NOT_SUPPORTED_ERR),
+                    };
+                    Ok(Some((
+                        dump(&value),
+                        KeyDecoder::decode_key_ttl(&meta_value),
+                    )))
+                }
+                .boxed()
+            })
+            .await
+    }
+
+    /// Write the value to the key, expiring at `timestamp` in milliseconds
+    /// or never with 0. An existing key is only replaced with `replace`. An
+    /// expired `timestamp` only removes the replaced key, like Redis.
+    pub async fn do_async_txnkv_restore(
+        mut self,
+        key: &str,
+        value: RdbValue,
+        timestamp: u64,
+        replace: bool,
+    ) -> AsyncResult<Frame> {
+        let mut client = get_txn_client()?;
+        let meta_key = KEY_ENCODER.encode_txnkv_meta_key(key);
+        let key = key.to_owned();
+
+        let resp = client
+            .exec_in_txn(self.txn.clone(), |txn_rc| {
+                async move {
+                    if self.txn.is_none() {
+                        self.txn = Some(txn_rc.clone());
+                    }
+                    if let Some(meta) = txn_rc.lock().await.get(meta_key).await? {
+                        if !replace && !key_is_expired(KeyDecoder::decode_key_ttl(&meta)) {
+                            return Err(REDIS_BUSYKEY_ERR);
+                        }
+                        check_reply(
+                            StringCommandCtx::new(self.txn.clone())
+                                .do_async_txnkv_del(&vec![key.clone()])
+                                .await?,
+                        )?;
+                    }
+                    if timestamp > 0 && timestamp <= now_timestamp_in_millis() {
+                        return Ok(());
+                    }
+
+                    let reply = match value {
+                        RdbValue::String(v) => {
+                            return check_reply(
+                                StringCommandCtx::new(self.txn.clone())
+                                    .do_async_txnkv_put(&key, &Bytes::from(v), timestamp)
+                                    .await?,
+                            );
+                        }
+                        RdbValue::List(items) => {
+                            let items = items.into_iter().map(Bytes::from).collect();
+                            ListCommandCtx::new(self.txn.clone())
+                                .do_async_txnkv_push(&key, &items, false)
+                                .await?
+                        }
+                        RdbValue::Set(members) => {
+                            let members = members
+                                .iter()
+                                .map(|m| String::from_utf8_lossy(m).to_string())
+                                .collect();
+                            SetCommandCtx::new(self.txn.clone())
+                                .do_async_txnkv_sadd(&key, &members)
+                                .await?
+                        }
+                        RdbValue::Zset(scored) => {
+                            let (members, scores): (Vec<String>, Vec<f64>) = scored
+                                .iter()
+                                .map(|(m, s)| (String::from_utf8_lossy(m).to_string(), *s))
+                                .unzip();
+                            ZsetCommandCtx::new(self.txn.clone())
+                                .do_async_txnkv_zadd(&key, &members, &scores, None, false, false)
+                                .await?
+                        }
+                        RdbValue::Hash(fvs) => {
+                            let fvs: Vec<KvPair> =
+                                fvs.into_iter().map(|(f, v)| KvPair::new(f, v)).collect();
+                            HashCommandCtx::new(self.txn.clone())
+                                .do_async_txnkv_hset(&key, &fvs, false, false)
+                                .await?
+                        }
+                    };
+                    check_reply(reply)?;
+                    if timestamp > 0 {
+                        check_reply(
+                            StringCommandCtx::new(self.txn.clone())
+                                .do_async_txnkv_expire(&key, timestamp)
+                                .await?,
+                        )?;
+                    }
+                    Ok(())
+                }
+                .boxed()
+            })
+            .await;
+
+        match resp {
+            Ok(()) => Ok(resp_ok()),
+            Err(e) => Ok(resp_err(e)),
+        }
+    }
+}
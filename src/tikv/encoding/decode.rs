@@ -1,7 +1,9 @@
 use std::convert::TryInto;
 
-use crate::tikv::KEY_ENCODER;
+use crate::tikv::errors::AsyncResult;
+use crate::tikv::{encryption, KEY_ENCODER};
 
+use super::encode::STRING_VALUE_SEALED;
 use super::{encode::DATA_TYPE_META, DataType, ENC_GROUP_SIZE, ENC_MARKER, SIGN_MASK};
 use tikv_client::{Key, Value};
 
@@ -39,14 +41,13 @@ impl KeyDecoder {
     }
 
     pub fn decode_key_type(value: &[u8]) -> DataType {
-        match value[0] {
-            0 => DataType::String,
-            1 => DataType::Hash,
-            2 => DataType::List,
-            3 => DataType::Set,
-            4 => DataType::Zset,
-            _ => panic!("no support data type"),
-        }
+        Self::try_decode_key_type(value).expect("no support data type")
+    }
+
+    /// Decode the type of a meta value, None if the type is not known by this
+    /// version, e.g. written by a newer one.
+    pub fn try_decode_key_type(value: &[u8]) -> Option<DataType> {
+        value.first().copied().and_then(DataType::from_type_byte)
     }
 
     pub fn decode_key_ttl(value: &[u8]) -> u64 {
@@ -57,16 +58,27 @@ impl KeyDecoder {
         &value[4..]
     }
 
-    pub fn decode_topo_value(value: &[u8]) -> u64 {
+    pub fn decode_auth_revoke_key_user(value: &[u8]) -> &[u8] {
+        &value[4..]
+    }
+
+    pub fn decode_auth_revoke_value(value: &[u8]) -> u64 {
         u64::from_be_bytes(value.try_into().unwrap())
     }
 
-    pub fn decode_key_string_value(value: &[u8]) -> Value {
-        value[11..].to_vec()
+    pub fn decode_topo_value(value: &[u8]) -> u64 {
+        u64::from_be_bytes(value.try_into().unwrap())
     }
 
-    pub fn decode_key_string_slice(value: &[u8]) -> &[u8] {
-        &value[11..]
+    pub fn decode_key_string_value(value: &[u8]) -> AsyncResult<Value> {
+        if matches!(
+            Self::decode_key_type(value),
+            DataType::String | DataType::Json
+        ) && Self::decode_key_version(value) == STRING_VALUE_SEALED
+        {
+            return encryption::open(&value[11..]);
+        }
+        Ok(value[11..].to_vec())
     }
 
     pub fn decode_key_version(value: &[u8]) -> u16 {
@@ -85,10 +97,16 @@ impl KeyDecoder {
         )
     }
 
+    /// Length of the prefix of the data keys of `ukey`, followed by the
+    /// field, member or score. Computed once for the keys of a scan instead
+    /// of encoding `ukey` again for each of them.
+    pub fn decode_data_key_prefix_len(ukey: &str) -> usize {
+        8 + KEY_ENCODER.encode_bytes(ukey.as_bytes()).len()
+    }
+
     pub fn decode_key_hash_userkey_from_datakey(ukey: &str, key: Key) -> Vec<u8> {
         let key: Vec<u8> = key.into();
-        let enc_ukey = KEY_ENCODER.encode_bytes(ukey.as_bytes());
-        let idx = 8 + enc_ukey.len();
+        let idx = Self::decode_data_key_prefix_len(ukey);
         key[idx..].to_vec()
     }
 
@@ -111,8 +129,7 @@ impl KeyDecoder {
 
     pub fn decode_key_set_member_from_datakey(ukey: &str, key: Key) -> Vec<u8> {
         let key: Vec<u8> = key.into();
-        let enc_ukey = KEY_ENCODER.encode_bytes(ukey.as_bytes());
-        let idx = 8 + enc_ukey.len();
+        let idx = Self::decode_data_key_prefix_len(ukey);
         key[idx..].to_vec()
     }
 
@@ -130,9 +147,15 @@ impl KeyDecoder {
 
     pub fn decode_key_zset_score_from_scorekey(ukey: &str, key: Key) -> f64 {
         let key: Vec<u8> = key.into();
-        let enc_ukey = KEY_ENCODER.encode_bytes(ukey.as_bytes());
-        let idx = 8 + enc_ukey.len();
-        Self::decode_cmp_uint64_to_f64(u64::from_be_bytes(key[idx..idx + 8].try_into().unwrap()))
+        let idx = Self::decode_data_key_prefix_len(ukey);
+        Self::decode_key_zset_score_at(&key, idx)
+    }
+
+    /// The score of a score key whose prefix is `prefix_len` long
+    pub fn decode_key_zset_score_at(key: &[u8], prefix_len: usize) -> f64 {
+        Self::decode_cmp_uint64_to_f64(u64::from_be_bytes(
+            key[prefix_len..prefix_len + 8].try_into().unwrap(),
+        ))
     }
 
     pub fn decode_key_zset_member_from_scorekey(ukey: &str, key: Key) -> Vec<u8> {
@@ -153,6 +176,67 @@ impl KeyDecoder {
         Self::decode_cmp_uint64_to_f64(u64::from_be_bytes(value[..].try_into().unwrap()))
     }
 
+    /// return (ttl, version, len, last_id)
+    pub fn decode_key_stream_meta(value: &[u8]) -> (u64, u16, u64, (u64, u64)) {
+        (
+            u64::from_be_bytes(value[1..9].try_into().unwrap()),
+            u16::from_be_bytes(value[9..11].try_into().unwrap()),
+            u64::from_be_bytes(value[11..19].try_into().unwrap()),
+            (
+                u64::from_be_bytes(value[19..27].try_into().unwrap()),
+                u64::from_be_bytes(value[27..35].try_into().unwrap()),
+            ),
+        )
+    }
+
+    pub fn decode_key_stream_id_from_datakey(ukey: &str, key: Key) -> (u64, u64) {
+        let key: Vec<u8> = key.into();
+        let enc_ukey = KEY_ENCODER.encode_bytes(ukey.as_bytes());
+        let idx = 8 + enc_ukey.len();
+        (
+            u64::from_be_bytes(key[idx..idx + 8].try_into().unwrap()),
+            u64::from_be_bytes(key[idx + 8..idx + 16].try_into().unwrap()),
+        )
+    }
+
+    pub fn decode_key_stream_group_value(value: &[u8]) -> (u64, u64) {
+        (
+            u64::from_be_bytes(value[0..8].try_into().unwrap()),
+            u64::from_be_bytes(value[8..16].try_into().unwrap()),
+        )
+    }
+
+    /// the id is the end of the pending key
+    pub fn decode_key_stream_id_from_pendingkey(key: Key) -> (u64, u64) {
+        let key: Vec<u8> = key.into();
+        let idx = key.len() - 16;
+        (
+            u64::from_be_bytes(key[idx..idx + 8].try_into().unwrap()),
+            u64::from_be_bytes(key[idx + 8..idx + 16].try_into().unwrap()),
+        )
+    }
+
+    /// return (delivery_time, delivery_count, consumer)
+    pub fn decode_key_stream_pending_value(value: &[u8]) -> (u64, u64, String) {
+        (
+            u64::from_be_bytes(value[0..8].try_into().unwrap()),
+            u64::from_be_bytes(value[8..16].try_into().unwrap()),
+            String::from_utf8_lossy(&value[16..]).to_string(),
+        )
+    }
+
+    pub fn decode_key_stream_data_value(value: &[u8]) -> Vec<Vec<u8>> {
+        let mut pairs = vec![];
+        let mut idx = 0;
+        while idx + 4 <= value.len() {
+            let len = u32::from_be_bytes(value[idx..idx + 4].try_into().unwrap()) as usize;
+            idx += 4;
+            pairs.push(value[idx..idx + len].to_vec());
+            idx += len;
+        }
+        pairs
+    }
+
     fn encoded_bytes_len(encoded: &[u8]) -> usize {
         let mut idx = ENC_GROUP_SIZE;
         loop {
@@ -167,6 +251,17 @@ impl KeyDecoder {
         }
     }
 
+    /// the user key is the end of the index key, after the encoded prefix,
+    /// field and value
+    pub fn decode_key_userkey_from_indexkey(key: Key) -> Vec<u8> {
+        let key: Vec<u8> = key.into();
+        let mut idx = 4;
+        for _ in 0..3 {
+            idx += Self::encoded_bytes_len(&key[idx..]);
+        }
+        key[idx..].to_vec()
+    }
+
     pub fn decode_key_gc_userkey_version(key: Key) -> (Vec<u8>, u16) {
         let key: Vec<u8> = key.into();
         let enc_key_start = 5;
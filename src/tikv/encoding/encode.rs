@@ -4,7 +4,10 @@ use super::ENC_GROUP_SIZE;
 use super::ENC_MARKER;
 use super::SIGN_MASK;
 use crate::config_meta_key_number_or_default;
+use crate::tikv::encryption;
+use crate::tikv::errors::AsyncResult;
 use crate::tikv::get_instance_id;
+use bytes::Bytes;
 use std::convert::TryFrom;
 use std::ops::Range;
 use std::ops::RangeInclusive;
@@ -26,6 +29,14 @@ pub const DATA_TYPE_USER_END: u8 = b'v';
 pub const DATA_TYPE_TOPO: u8 = b't';
 pub const DATA_TYPE_GC: u8 = b'g';
 pub const DATA_TYPE_GC_VERSION: u8 = b'v';
+pub const DATA_TYPE_IDEMPOTENCY: u8 = b'i';
+pub const DATA_TYPE_DATA_KEY: u8 = b'k';
+pub const DATA_TYPE_LOCK: u8 = b'L';
+pub const DATA_TYPE_INDEX: u8 = b'I';
+pub const DATA_TYPE_AUTH_REVOKE: u8 = b'a';
+pub const DATA_TYPE_FORMAT: u8 = b'f';
+pub const DATA_TYPE_MEMBER_FEATURES: u8 = b'n';
+pub const DATA_TYPE_WATCH_STAMP: u8 = b'w';
 
 pub const DATA_TYPE_META: u8 = b'm';
 pub const DATA_TYPE_SCORE: u8 = b'S';
@@ -33,9 +44,18 @@ pub const DATA_TYPE_HASH: u8 = b'h';
 pub const DATA_TYPE_LIST: u8 = b'l';
 pub const DATA_TYPE_SET: u8 = b's';
 pub const DATA_TYPE_ZSET: u8 = b'z';
+pub const DATA_TYPE_STREAM: u8 = b'x';
 
 pub const PLACE_HOLDER: u8 = b'`';
 
+/// marks of the consumer group and pending entry keys of a stream, after the
+/// `PLACE_HOLDER` of its entries
+pub const STREAM_GROUP_MARK: u8 = b'g';
+pub const STREAM_PENDING_MARK: u8 = b'p';
+
+/// string meta value version, the value is sealed by the namespace data key
+pub const STRING_VALUE_SEALED: u16 = 1;
+
 impl KeyEncoder {
     pub fn new() -> Self {
         KeyEncoder {
@@ -44,6 +64,15 @@ impl KeyEncoder {
         }
     }
 
+    /// Encoder of the keys of the namespace `instance_id` instead of the one
+    /// served by this instance
+    pub fn with_instance_id(instance_id: u16) -> Self {
+        KeyEncoder {
+            instance_id: instance_id.to_be_bytes(),
+            meta_key_number: config_meta_key_number_or_default(),
+        }
+    }
+
     pub fn encode_bytes(&self, key: &[u8]) -> Vec<u8> {
         let len = key.len();
         let mut index = 0;
@@ -72,6 +101,8 @@ impl KeyEncoder {
             DataType::Set => 3,
             DataType::Zset => 4,
             DataType::Null => 5,
+            DataType::Stream => 6,
+            DataType::Json => 7,
         }
     }
 
@@ -106,6 +137,176 @@ impl KeyEncoder {
         key.into()
     }
 
+    /// encode key recording an applied idempotency token
+    pub fn encode_txnkv_idempotency_key(&self, token: &str) -> Key {
+        let mut key = Vec::with_capacity(4 + token.len());
+        key.push(TXN_KEY_PREFIX);
+        key.extend_from_slice(self.instance_id.as_slice());
+        key.push(DATA_TYPE_IDEMPOTENCY);
+        key.extend_from_slice(token.as_bytes());
+        key.into()
+    }
+
+    pub fn encode_txnkv_idempotency_key_range(&self) -> BoundRange {
+        let mut range_start = Vec::with_capacity(4);
+        range_start.push(TXN_KEY_PREFIX);
+        range_start.extend_from_slice(self.instance_id.as_slice());
+        range_start.push(DATA_TYPE_IDEMPOTENCY);
+        let mut range_end = range_start.clone();
+        range_end.pop();
+        range_end.push(DATA_TYPE_IDEMPOTENCY + 1);
+        let range: Range<Key> = range_start.into()..range_end.into();
+        range.into()
+    }
+
+    /// encode key of the write stamp of a user key, see `crate::tikv::stamp`
+    pub fn encode_txnkv_watch_stamp_key(&self, ukey: &str) -> Key {
+        let enc_ukey = self.encode_bytes(ukey.as_bytes());
+        let mut key = Vec::with_capacity(4 + enc_ukey.len());
+        key.push(TXN_KEY_PREFIX);
+        key.extend_from_slice(self.instance_id.as_slice());
+        key.push(DATA_TYPE_WATCH_STAMP);
+        key.extend_from_slice(&enc_ukey);
+        key.into()
+    }
+
+    /// encode key of the write stamp of the user key of `key`, any key of the
+    /// user keyspace, meta or data key, of any instance. None if `key` is not
+    /// in the user keyspace.
+    pub fn encode_txnkv_watch_stamp_key_of(&self, key: &[u8]) -> Option<Key> {
+        if key.len() < 4 || key[0] != TXN_KEY_PREFIX || key[3] != DATA_TYPE_USER {
+            return None;
+        }
+        // the encoded user key ends with the first group not marked as full
+        let mut end = 4;
+        loop {
+            if end + ENC_GROUP_SIZE + 1 > key.len() {
+                return None;
+            }
+            end += ENC_GROUP_SIZE + 1;
+            if key[end - 1] != ENC_MARKER {
+                break;
+            }
+        }
+        let mut stamp_key = Vec::with_capacity(end);
+        stamp_key.extend_from_slice(&key[..3]);
+        stamp_key.push(DATA_TYPE_WATCH_STAMP);
+        stamp_key.extend_from_slice(&key[4..end]);
+        Some(stamp_key.into())
+    }
+
+    pub fn encode_txnkv_watch_stamp_key_range(&self) -> BoundRange {
+        let mut range_start = Vec::with_capacity(4);
+        range_start.push(TXN_KEY_PREFIX);
+        range_start.extend_from_slice(self.instance_id.as_slice());
+        range_start.push(DATA_TYPE_WATCH_STAMP);
+        let mut range_end = range_start.clone();
+        range_end.pop();
+        range_end.push(DATA_TYPE_WATCH_STAMP + 1);
+        let range: Range<Key> = range_start.into()..range_end.into();
+        range.into()
+    }
+
+    /// encode key recording the revocation of a user, read by all members
+    pub fn encode_txnkv_auth_revoke_key(&self, user: &str) -> Key {
+        let mut key = Vec::with_capacity(4 + user.len());
+        key.push(TXN_KEY_PREFIX);
+        key.extend_from_slice(self.instance_id.as_slice());
+        key.push(DATA_TYPE_AUTH_REVOKE);
+        key.extend_from_slice(user.as_bytes());
+        key.into()
+    }
+
+    pub fn encode_txnkv_auth_revoke_key_range(&self) -> BoundRange {
+        let mut range_start = Vec::with_capacity(4);
+        range_start.push(TXN_KEY_PREFIX);
+        range_start.extend_from_slice(self.instance_id.as_slice());
+        range_start.push(DATA_TYPE_AUTH_REVOKE);
+        let mut range_end = range_start.clone();
+        range_end.pop();
+        range_end.push(DATA_TYPE_AUTH_REVOKE + 1);
+        let range: Range<Key> = range_start.into()..range_end.into();
+        range.into()
+    }
+
+    /// encode key of the data format record of the namespace, checked at startup
+    pub fn encode_txnkv_format_key(&self) -> Key {
+        let mut key = Vec::with_capacity(4);
+        key.push(TXN_KEY_PREFIX);
+        key.extend_from_slice(self.instance_id.as_slice());
+        key.push(DATA_TYPE_FORMAT);
+        key.into()
+    }
+
+    /// encode key of the negotiated features supported by a member, see
+    /// `crate::tikv::format`
+    pub fn encode_txnkv_member_features_key(&self, addr: &str) -> Key {
+        let mut key = Vec::with_capacity(4 + addr.len());
+        key.push(TXN_KEY_PREFIX);
+        key.extend_from_slice(self.instance_id.as_slice());
+        key.push(DATA_TYPE_MEMBER_FEATURES);
+        key.extend_from_slice(addr.as_bytes());
+        key.into()
+    }
+
+    /// encode key of a lock taken with LOCK, outside of the user keyspace
+    pub fn encode_txnkv_lock_key(&self, name: &str) -> Key {
+        let mut key = Vec::with_capacity(4 + name.len());
+        key.push(TXN_KEY_PREFIX);
+        key.extend_from_slice(self.instance_id.as_slice());
+        key.push(DATA_TYPE_LOCK);
+        key.extend_from_slice(name.as_bytes());
+        key.into()
+    }
+
+    fn encode_txnkv_hash_index_value_prefix(
+        &self,
+        prefix: &str,
+        field: &str,
+        value: &[u8],
+    ) -> Vec<u8> {
+        let mut key = Vec::with_capacity(4 + prefix.len() + field.len() + value.len() + 12);
+        key.push(TXN_KEY_PREFIX);
+        key.extend_from_slice(self.instance_id.as_slice());
+        key.push(DATA_TYPE_INDEX);
+        key.extend_from_slice(&self.encode_bytes(prefix.as_bytes()));
+        key.extend_from_slice(&self.encode_bytes(field.as_bytes()));
+        key.extend_from_slice(&self.encode_bytes(value));
+        key
+    }
+
+    /// encode key of the hash index entry of `ukey` with `value` in `field`
+    pub fn encode_txnkv_hash_index_key(
+        &self,
+        prefix: &str,
+        field: &str,
+        value: &[u8],
+        ukey: &str,
+    ) -> Key {
+        let mut key = self.encode_txnkv_hash_index_value_prefix(prefix, field, value);
+        key.extend_from_slice(ukey.as_bytes());
+        key.into()
+    }
+
+    /// the index entries of all keys with `value` in `field`, the last byte of
+    /// an encoded value is below 0xff so it is incremented for the end
+    pub fn encode_txnkv_hash_index_range(
+        &self,
+        prefix: &str,
+        field: &str,
+        value: &[u8],
+    ) -> BoundRange {
+        let range_start = self.encode_txnkv_hash_index_value_prefix(prefix, field, value);
+        let mut range_end = range_start.clone();
+        *range_end.last_mut().unwrap() += 1;
+        let range: Range<Key> = range_start.into()..range_end.into();
+        range.into()
+    }
+
+    pub fn encode_txnkv_hash_index_entry_value(&self) -> Value {
+        vec![self.get_type_bytes(DataType::Hash)]
+    }
+
     pub fn encode_rawkv_string(&self, ukey: &str) -> Key {
         let mut key = Vec::with_capacity(4 + ukey.len());
         key.push(RAW_KEY_PREFIX);
@@ -128,7 +329,17 @@ impl KeyEncoder {
     }
 
     fn encode_txnkv_string_internal(&self, vsize: usize, ttl: u64, version: u16) -> Value {
-        let dt = self.get_type_bytes(DataType::String);
+        self.encode_txnkv_single_value_internal(DataType::String, vsize, ttl, version)
+    }
+
+    fn encode_txnkv_single_value_internal(
+        &self,
+        dt: DataType,
+        vsize: usize,
+        ttl: u64,
+        version: u16,
+    ) -> Value {
+        let dt = self.get_type_bytes(dt);
         let mut val = Vec::with_capacity(11 + vsize);
         val.push(dt);
         val.extend_from_slice(&ttl.to_be_bytes());
@@ -136,18 +347,78 @@ impl KeyEncoder {
         val
     }
 
-    pub fn encode_txnkv_string_slice(&self, value: &[u8], ttl: u64) -> Value {
+    pub fn encode_txnkv_string_slice(&self, value: &[u8], ttl: u64) -> AsyncResult<Value> {
+        if let Some(mut sealed) = encryption::seal(value)? {
+            let mut val = self.encode_txnkv_string_internal(sealed.len(), ttl, STRING_VALUE_SEALED);
+            val.append(&mut sealed);
+            return Ok(val);
+        }
         let mut val = self.encode_txnkv_string_internal(value.len(), ttl, 0);
         val.extend_from_slice(value);
-        val
+        Ok(val)
+    }
+
+    /// a json document is kept in one value like a string, sealed the same way
+    pub fn encode_txnkv_json_value(&self, doc: &[u8], ttl: u64) -> AsyncResult<Value> {
+        if let Some(mut sealed) = encryption::seal(doc)? {
+            let mut val = self.encode_txnkv_single_value_internal(
+                DataType::Json,
+                sealed.len(),
+                ttl,
+                STRING_VALUE_SEALED,
+            );
+            val.append(&mut sealed);
+            return Ok(val);
+        }
+        let mut val = self.encode_txnkv_single_value_internal(DataType::Json, doc.len(), ttl, 0);
+        val.extend_from_slice(doc);
+        Ok(val)
     }
 
-    pub fn encode_txnkv_string_value(&self, value: &mut Value, ttl: u64) -> Value {
+    pub fn encode_txnkv_string_value(&self, value: &mut Value, ttl: u64) -> AsyncResult<Value> {
+        if let Some(mut sealed) = encryption::seal(value)? {
+            let mut val = self.encode_txnkv_string_internal(sealed.len(), ttl, STRING_VALUE_SEALED);
+            val.append(&mut sealed);
+            return Ok(val);
+        }
         let mut val = self.encode_txnkv_string_internal(value.len(), ttl, 0);
         val.append(value);
+        Ok(val)
+    }
+
+    /// update ttl of an encoded string value, the data part is kept as is
+    pub fn encode_txnkv_string_ttl(&self, meta_value: &[u8], ttl: u64) -> Value {
+        let mut val = meta_value.to_vec();
+        val[1..9].copy_from_slice(&ttl.to_be_bytes());
         val
     }
 
+    /// encode key of the wrapped data key for value encryption
+    pub fn encode_txnkv_data_key(&self, id: u32) -> Key {
+        let mut key = Vec::with_capacity(8);
+        key.push(TXN_KEY_PREFIX);
+        key.extend_from_slice(self.instance_id.as_slice());
+        key.push(DATA_TYPE_DATA_KEY);
+        key.extend_from_slice(&id.to_be_bytes());
+        key.into()
+    }
+
+    pub fn encode_txnkv_data_key_start(&self) -> Key {
+        let mut key = Vec::with_capacity(4);
+        key.push(TXN_KEY_PREFIX);
+        key.extend_from_slice(self.instance_id.as_slice());
+        key.push(DATA_TYPE_DATA_KEY);
+        key.into()
+    }
+
+    pub fn encode_txnkv_data_key_end(&self) -> Key {
+        let mut key = Vec::with_capacity(4);
+        key.push(TXN_KEY_PREFIX);
+        key.extend_from_slice(self.instance_id.as_slice());
+        key.push(DATA_TYPE_DATA_KEY + 1);
+        key.into()
+    }
+
     pub fn encode_rawkv_strings(&self, keys: &[String]) -> Vec<Key> {
         keys.iter()
             .map(|ukey| self.encode_rawkv_string(ukey))
@@ -176,6 +447,14 @@ impl KeyEncoder {
         key.into()
     }
 
+    pub fn encode_txnkv_keyspace_start(&self) -> Key {
+        let mut key = Vec::with_capacity(4);
+        key.push(TXN_KEY_PREFIX);
+        key.extend_from_slice(self.instance_id.as_slice());
+        key.push(DATA_TYPE_USER);
+        key.into()
+    }
+
     pub fn encode_txnkv_keyspace_end(&self) -> Key {
         let mut key = Vec::with_capacity(4);
         key.push(TXN_KEY_PREFIX);
@@ -184,6 +463,25 @@ impl KeyEncoder {
         key.into()
     }
 
+    /// the prefix of all the meta, sub meta and data keys of the user key,
+    /// followed by their type and version
+    pub fn encode_txnkv_userkey_prefix(&self, ukey: &[u8]) -> Vec<u8> {
+        let enc_ukey = self.encode_bytes(ukey);
+        let mut key = Vec::with_capacity(5 + enc_ukey.len());
+        key.push(TXN_KEY_PREFIX);
+        key.extend_from_slice(self.instance_id.as_slice());
+        key.push(DATA_TYPE_USER);
+        key.extend_from_slice(&enc_ukey);
+        key
+    }
+
+    /// the key right after all the meta, sub meta and data keys of the user key
+    pub fn encode_txnkv_userkey_end(&self, ukey: &[u8]) -> Key {
+        let mut key = self.encode_txnkv_userkey_prefix(ukey);
+        key.push(u8::MAX);
+        key.into()
+    }
+
     pub fn encode_txnkv_sub_meta_key(&self, ukey: &str, version: u16, idx: u16) -> Key {
         let enc_ukey = self.encode_bytes(ukey.as_bytes());
         let mut key = Vec::with_capacity(10 + enc_ukey.len());
@@ -568,6 +866,160 @@ impl KeyEncoder {
         key.into()
     }
 
+    /// stream entry ids are (milliseconds, sequence), the data keys are sorted
+    /// by id as both parts are big endian
+    pub fn encode_txnkv_stream_data_key(&self, ukey: &str, id: (u64, u64), version: u16) -> Key {
+        let enc_ukey = self.encode_bytes(ukey.as_bytes());
+        let mut key = Vec::with_capacity(24 + enc_ukey.len());
+
+        self.encode_txnkv_type_data_key_prefix(DATA_TYPE_STREAM, &enc_ukey, &mut key, version);
+        key.push(PLACE_HOLDER);
+        key.extend_from_slice(&id.0.to_be_bytes());
+        key.extend_from_slice(&id.1.to_be_bytes());
+        key.into()
+    }
+
+    pub fn encode_txnkv_stream_data_key_id_range(
+        &self,
+        key: &str,
+        start: (u64, u64),
+        end: (u64, u64),
+        version: u16,
+    ) -> BoundRange {
+        let data_key_start = self.encode_txnkv_stream_data_key(key, start, version);
+        let data_key_end = self.encode_txnkv_stream_data_key(key, end, version);
+        let range: RangeInclusive<Key> = data_key_start..=data_key_end;
+        range.into()
+    }
+
+    pub fn encode_txnkv_stream_data_key_range(&self, key: &str, version: u16) -> BoundRange {
+        self.encode_txnkv_stream_data_key_id_range(key, (0, 0), (u64::MAX, u64::MAX), version)
+    }
+
+    /// fields and values of an entry, each prefixed by its u32 length
+    pub fn encode_txnkv_stream_data_value(&self, pairs: &[Bytes]) -> Value {
+        let size = pairs.iter().map(|p| 4 + p.len()).sum();
+        let mut val = Vec::with_capacity(size);
+        for p in pairs {
+            val.extend_from_slice(&(p.len() as u32).to_be_bytes());
+            val.extend_from_slice(p);
+        }
+        val
+    }
+
+    fn encode_txnkv_stream_key_prefix(&self, ukey: &str, version: u16, extra: usize) -> Vec<u8> {
+        let enc_ukey = self.encode_bytes(ukey.as_bytes());
+        let mut key = Vec::with_capacity(8 + enc_ukey.len() + extra);
+        self.encode_txnkv_type_data_key_prefix(DATA_TYPE_STREAM, &enc_ukey, &mut key, version);
+        key
+    }
+
+    /// every key of the stream, its entries, consumer groups and pending
+    /// entries
+    pub fn encode_txnkv_stream_key_range(&self, ukey: &str, version: u16) -> BoundRange {
+        let key_start = self.encode_txnkv_stream_key_prefix(ukey, version, 0);
+        let mut key_end = key_start.clone();
+        key_end.push(u8::MAX);
+        let range: Range<Key> = key_start.into()..key_end.into();
+        range.into()
+    }
+
+    pub fn encode_txnkv_stream_group_key(&self, ukey: &str, group: &str, version: u16) -> Key {
+        let mut key = self.encode_txnkv_stream_key_prefix(ukey, version, 1 + group.len());
+        key.push(STREAM_GROUP_MARK);
+        key.extend_from_slice(group.as_bytes());
+        key.into()
+    }
+
+    /// the last id delivered to the group
+    pub fn encode_txnkv_stream_group_value(&self, last_delivered: (u64, u64)) -> Value {
+        let mut val = Vec::with_capacity(16);
+        val.extend_from_slice(&last_delivered.0.to_be_bytes());
+        val.extend_from_slice(&last_delivered.1.to_be_bytes());
+        val
+    }
+
+    /// pending entries of a group are sorted by id, the group name is
+    /// memcomparable encoded so it is not a prefix of another name
+    pub fn encode_txnkv_stream_pending_key(
+        &self,
+        ukey: &str,
+        group: &str,
+        id: (u64, u64),
+        version: u16,
+    ) -> Key {
+        let enc_group = self.encode_bytes(group.as_bytes());
+        let mut key = self.encode_txnkv_stream_key_prefix(ukey, version, 17 + enc_group.len());
+        key.push(STREAM_PENDING_MARK);
+        key.extend_from_slice(&enc_group);
+        key.extend_from_slice(&id.0.to_be_bytes());
+        key.extend_from_slice(&id.1.to_be_bytes());
+        key.into()
+    }
+
+    pub fn encode_txnkv_stream_pending_key_id_range(
+        &self,
+        ukey: &str,
+        group: &str,
+        start: (u64, u64),
+        end: (u64, u64),
+        version: u16,
+    ) -> BoundRange {
+        let key_start = self.encode_txnkv_stream_pending_key(ukey, group, start, version);
+        let key_end = self.encode_txnkv_stream_pending_key(ukey, group, end, version);
+        let range: RangeInclusive<Key> = key_start..=key_end;
+        range.into()
+    }
+
+    pub fn encode_txnkv_stream_pending_key_range(
+        &self,
+        ukey: &str,
+        group: &str,
+        version: u16,
+    ) -> BoundRange {
+        self.encode_txnkv_stream_pending_key_id_range(
+            ukey,
+            group,
+            (0, 0),
+            (u64::MAX, u64::MAX),
+            version,
+        )
+    }
+
+    /// last delivery time in milliseconds, number of deliveries and the
+    /// consumer owning the pending entry
+    pub fn encode_txnkv_stream_pending_value(
+        &self,
+        delivery_time: u64,
+        delivery_count: u64,
+        consumer: &str,
+    ) -> Value {
+        let mut val = Vec::with_capacity(16 + consumer.len());
+        val.extend_from_slice(&delivery_time.to_be_bytes());
+        val.extend_from_slice(&delivery_count.to_be_bytes());
+        val.extend_from_slice(consumer.as_bytes());
+        val
+    }
+
+    pub fn encode_txnkv_stream_meta_value(
+        &self,
+        ttl: u64,
+        version: u16,
+        len: u64,
+        last_id: (u64, u64),
+    ) -> Value {
+        let dt = self.get_type_bytes(DataType::Stream);
+        let mut val = Vec::with_capacity(35);
+
+        val.push(dt);
+        val.extend_from_slice(&ttl.to_be_bytes());
+        val.extend_from_slice(&version.to_be_bytes());
+        val.extend_from_slice(&len.to_be_bytes());
+        val.extend_from_slice(&last_id.0.to_be_bytes());
+        val.extend_from_slice(&last_id.1.to_be_bytes());
+        val
+    }
+
     pub fn encode_txnkv_gc_key_prefix(&self, ukey: &str, data_type: u8, extra: usize) -> Vec<u8> {
         let enc_ukey = self.encode_bytes(ukey.as_bytes());
         let mut key = Vec::with_capacity(extra + enc_ukey.len());
@@ -9,8 +9,43 @@ pub enum DataType {
     Set,
     Zset,
     Null,
+    Stream,
+    Json,
 }
 
+impl DataType {
+    /// The encoding replied by OBJECT ENCODING, versioned as the layout of
+    /// the meta and data keys may change
+    pub fn encoding(&self) -> &'static str {
+        match self {
+            DataType::String => "tikv-string-v1",
+            DataType::Hash => "tikv-hash-v1",
+            DataType::List => "tikv-list-v1",
+            DataType::Set => "tikv-set-v1",
+            DataType::Zset => "tikv-zset-v1",
+            DataType::Null => "none",
+            DataType::Stream => "tikv-stream-v1",
+            DataType::Json => "tikv-json-v1",
+        }
+    }
+
+    /// Data type of the type byte in meta values, see `KeyEncoder::get_type_bytes`
+    pub fn from_type_byte(b: u8) -> Option<DataType> {
+        match b {
+            0 => Some(DataType::String),
+            1 => Some(DataType::Hash),
+            2 => Some(DataType::List),
+            3 => Some(DataType::Set),
+            4 => Some(DataType::Zset),
+            6 => Some(DataType::Stream),
+            7 => Some(DataType::Json),
+            _ => None,
+        }
+    }
+}
+
+/// The names replied by TYPE, clients switch on them so they must be the
+/// ones used by redis.
 impl fmt::Display for DataType {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match self {
@@ -20,6 +55,8 @@ impl fmt::Display for DataType {
             DataType::Set => write!(f, "set"),
             DataType::Zset => write!(f, "zset"),
             DataType::Null => write!(f, "none"),
+            DataType::Stream => write!(f, "stream"),
+            DataType::Json => write!(f, "ReJSON-RL"),
         }
     }
 }
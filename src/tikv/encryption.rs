@@ -0,0 +1,278 @@
+use std::collections::HashMap;
+use std::convert::TryInto;
+use std::ops::Range;
+use std::sync::RwLock;
+use std::time::Duration;
+
+use futures::FutureExt;
+use hyper::{Body, Client, Method, Request};
+use ring::aead::{Aad, LessSafeKey, Nonce, UnboundKey, AES_256_GCM, NONCE_LEN};
+use ring::rand::{SecureRandom, SystemRandom};
+use slog::{info, warn};
+use tikv_client::{BoundRange, Key};
+
+use super::errors::{
+    AsyncResult, RTError, REDIS_DATA_KEYS_NOT_LOADED_ERR, REDIS_DATA_KEY_NOT_FOUND_ERR,
+    REDIS_DECRYPT_VALUE_ERR, REDIS_ENCRYPTION_DISABLED_ERR,
+};
+use super::{get_txn_client, KEY_ENCODER};
+use crate::config::LOGGER;
+use crate::utils::json_quote;
+use crate::{
+    encryption_enabled_or_default, encryption_kms_key_id_or_default,
+    encryption_kms_token_or_default, encryption_kms_url_or_default,
+    encryption_master_key_or_default,
+};
+
+const DATA_KEY_LEN: usize = 32;
+const DATA_KEY_REFRESH_INTERVAL: u64 = 10000;
+const KMS_REQUEST_TIMEOUT: u64 = 5000;
+
+/// Data keys of this instance namespace, unwrapped with the master key.
+///
+/// Values are always sealed with the newest data key, older keys are kept
+/// so values written before a rotation are still readable.
+struct DataKeyRing {
+    current: u32,
+    keys: HashMap<u32, LessSafeKey>,
+}
+
+lazy_static! {
+    static ref DATA_KEY_RING: RwLock<Option<DataKeyRing>> = RwLock::new(None);
+    static ref RNG: SystemRandom = SystemRandom::new();
+    /// The master key unwrapped by the KMS, kept once fetched
+    static ref KMS_MASTER_KEY: RwLock<Option<Vec<u8>>> = RwLock::new(None);
+}
+
+fn new_key(bytes: &[u8]) -> AsyncResult<LessSafeKey> {
+    let unbound = UnboundKey::new(&AES_256_GCM, bytes)
+        .map_err(|_| RTError::String("ERR invalid encryption key length"))?;
+    Ok(LessSafeKey::new(unbound))
+}
+
+/// Ask the KMS at `url` to unwrap the master key.
+///
+/// The KMS is posted `{"key_id": ..., "ciphertext": ...}` with the wrapped
+/// master key of the config, and replies `{"plaintext": ...}` with the hex
+/// encoded master key, so Vault transit or a cloud KMS can be fronted by a
+/// small service. The key never leaves the memory of the instance.
+async fn fetch_kms_master_key(url: &str) -> AsyncResult<Vec<u8>> {
+    let body = format!(
+        "{{\"key_id\":{},\"ciphertext\":{}}}",
+        json_quote(&encryption_kms_key_id_or_default()),
+        json_quote(&encryption_master_key_or_default())
+    );
+    let mut req = Request::builder()
+        .method(Method::POST)
+        .uri(url)
+        .header("content-type", "application/json");
+    let token = encryption_kms_token_or_default();
+    if !token.is_empty() {
+        req = req.header("authorization", format!("Bearer {}", token));
+    }
+    let req = req
+        .body(Body::from(body))
+        .map_err(|e| RTError::Owned(format!("ERR invalid kms request: {}", e)))?;
+
+    let timeout = Duration::from_millis(KMS_REQUEST_TIMEOUT);
+    let resp = match tokio::time::timeout(timeout, Client::new().request(req)).await {
+        Ok(Ok(resp)) if resp.status().is_success() => resp,
+        Ok(Ok(resp)) => {
+            return Err(RTError::Owned(format!(
+                "ERR kms refused to unwrap the master key: {}",
+                resp.status()
+            )))
+        }
+        Ok(Err(e)) => return Err(RTError::Owned(format!("ERR kms request failed: {}", e))),
+        Err(_) => {
+            return Err(RTError::Owned(format!(
+                "ERR kms request timeout after {:?}",
+                timeout
+            )))
+        }
+    };
+    let body = hyper::body::to_bytes(resp.into_body())
+        .await
+        .map_err(|e| RTError::Owned(format!("ERR kms request failed: {}", e)))?;
+    let reply: serde_json::Value = serde_json::from_slice(&body)?;
+    let plaintext = reply["plaintext"]
+        .as_str()
+        .ok_or(RTError::String("ERR kms reply has no plaintext"))?;
+    hex::decode(plaintext)
+        .map_err(|_| RTError::String("ERR master key of the kms must be hex encoded"))
+}
+
+/// The master key, given hex encoded in the config or, if `encryption_kms_url`
+/// is set, wrapped in the config and unwrapped by the KMS
+async fn master_key_bytes() -> AsyncResult<Vec<u8>> {
+    let url = encryption_kms_url_or_default();
+    if url.is_empty() {
+        return hex::decode(encryption_master_key_or_default())
+            .map_err(|_| RTError::String("ERR encryption master key must be hex encoded"));
+    }
+    if let Some(key) = KMS_MASTER_KEY.read().unwrap().clone() {
+        return Ok(key);
+    }
+    let key = fetch_kms_master_key(&url).await?;
+    KMS_MASTER_KEY.write().unwrap().replace(key.clone());
+    Ok(key)
+}
+
+fn random_nonce() -> [u8; NONCE_LEN] {
+    let mut nonce = [0u8; NONCE_LEN];
+    RNG.fill(&mut nonce).unwrap();
+    nonce
+}
+
+/// nonce + ciphertext + tag
+fn seal_with(key: &LessSafeKey, plain: &[u8], out: &mut Vec<u8>) {
+    let nonce = random_nonce();
+    let start = out.len();
+    out.extend_from_slice(&nonce);
+    out.extend_from_slice(plain);
+    let mut in_out = out.split_off(start + NONCE_LEN);
+    key.seal_in_place_append_tag(
+        Nonce::assume_unique_for_key(nonce),
+        Aad::empty(),
+        &mut in_out,
+    )
+    .unwrap();
+    out.append(&mut in_out);
+}
+
+fn open_with(key: &LessSafeKey, sealed: &[u8]) -> AsyncResult<Vec<u8>> {
+    if sealed.len() < NONCE_LEN {
+        return Err(REDIS_DECRYPT_VALUE_ERR);
+    }
+    let nonce: [u8; NONCE_LEN] = sealed[..NONCE_LEN].try_into().unwrap();
+    let mut in_out = sealed[NONCE_LEN..].to_vec();
+    let len = key
+        .open_in_place(
+            Nonce::assume_unique_for_key(nonce),
+            Aad::empty(),
+            &mut in_out,
+        )
+        .map_err(|_| REDIS_DECRYPT_VALUE_ERR)?
+        .len();
+    in_out.truncate(len);
+    Ok(in_out)
+}
+
+/// Seal the value with the current data key, return None if encryption is
+/// disabled, and an error if the data keys are not loaded.
+///
+/// Layout: data key id(u32) + nonce + ciphertext + tag
+pub fn seal(plain: &[u8]) -> AsyncResult<Option<Vec<u8>>> {
+    if !encryption_enabled_or_default() {
+        return Ok(None);
+    }
+    let ring = DATA_KEY_RING.read().unwrap();
+    let (current, key) = ring
+        .as_ref()
+        .and_then(|r| r.keys.get(&r.current).map(|key| (r.current, key)))
+        .ok_or(REDIS_DATA_KEYS_NOT_LOADED_ERR)?;
+    let mut out = Vec::with_capacity(4 + NONCE_LEN + plain.len() + AES_256_GCM.tag_len());
+    out.extend_from_slice(&current.to_be_bytes());
+    seal_with(key, plain, &mut out);
+    Ok(Some(out))
+}
+
+/// Open a value sealed by `seal`, the data key is looked up by the id in the
+/// value, so it works with encryption disabled as long as the keys are loaded.
+pub fn open(sealed: &[u8]) -> AsyncResult<Vec<u8>> {
+    if sealed.len() < 4 {
+        return Err(REDIS_DECRYPT_VALUE_ERR);
+    }
+    let id = u32::from_be_bytes(sealed[..4].try_into().unwrap());
+    let ring = DATA_KEY_RING.read().unwrap();
+    let key = ring
+        .as_ref()
+        .and_then(|r| r.keys.get(&id))
+        .ok_or(REDIS_DATA_KEY_NOT_FOUND_ERR)?;
+    open_with(key, &sealed[4..])
+}
+
+fn data_key_range() -> BoundRange {
+    let range: Range<Key> =
+        KEY_ENCODER.encode_txnkv_data_key_start()..KEY_ENCODER.encode_txnkv_data_key_end();
+    range.into()
+}
+
+/// Load all wrapped data keys from tikv, a new data key is created if `create`
+/// is set or there is no data key yet. Returns the current data key id.
+async fn load_data_keys(create: bool) -> AsyncResult<u32> {
+    let master_bytes = master_key_bytes().await?;
+    let mut client = get_txn_client()?;
+    let keys = client
+        .exec_in_txn(None, |txn_rc| {
+            async move {
+                let master = new_key(&master_bytes)?;
+                let mut txn = txn_rc.lock().await;
+                let mut keys = HashMap::new();
+                let mut max_id = 0;
+                for kv in txn.scan(data_key_range(), u32::MAX).await? {
+                    let ekey: Vec<u8> = kv.0.into();
+                    let id = u32::from_be_bytes(ekey[ekey.len() - 4..].try_into().unwrap());
+                    let data_key = open_with(&master, &kv.1)?;
+                    keys.insert(id, new_key(&data_key)?);
+                    max_id = max_id.max(id);
+                }
+
+                if create || keys.is_empty() {
+                    max_id += 1;
+                    let mut data_key = [0u8; DATA_KEY_LEN];
+                    RNG.fill(&mut data_key).unwrap();
+                    let mut wrapped = Vec::new();
+                    seal_with(&master, &data_key, &mut wrapped);
+                    txn.put(KEY_ENCODER.encode_txnkv_data_key(max_id), wrapped)
+                        .await?;
+                    keys.insert(max_id, new_key(&data_key)?);
+                }
+                Ok((max_id, keys))
+            }
+            .boxed()
+        })
+        .await;
+
+    let (current, keys) = keys?;
+    DATA_KEY_RING
+        .write()
+        .unwrap()
+        .replace(DataKeyRing { current, keys });
+    Ok(current)
+}
+
+/// Initialize the data keys at startup and keep them in sync with other
+/// instances of the same namespace, must be called after backend connected.
+pub async fn init_data_keys() -> AsyncResult<()> {
+    if !encryption_enabled_or_default() {
+        return Ok(());
+    }
+    let current = load_data_keys(false).await?;
+    info!(
+        LOGGER,
+        "value encryption enabled, current data key {}", current
+    );
+
+    tokio::spawn(async {
+        let mut interval = tokio::time::interval(Duration::from_millis(DATA_KEY_REFRESH_INTERVAL));
+        loop {
+            interval.tick().await;
+            if let Err(e) = load_data_keys(false).await {
+                warn!(LOGGER, "refresh data keys failed: {}", e);
+            }
+        }
+    });
+    Ok(())
+}
+
+/// Create a new data key for subsequent writes, the old keys are retained.
+/// Other instances pick up the new key in the next refresh interval.
+pub async fn rotate_data_key() -> AsyncResult<u32> {
+    if !encryption_enabled_or_default() {
+        return Err(REDIS_ENCRYPTION_DISABLED_ERR);
+    }
+    let current = load_data_keys(true).await?;
+    info!(LOGGER, "data key rotated, current data key {}", current);
+    Ok(current)
+}
@@ -38,6 +38,12 @@ impl From<LuaError> for RTError {
     }
 }
 
+impl From<serde_json::Error> for RTError {
+    fn from(e: serde_json::Error) -> Self {
+        RTError::Owned(format!("ERR {}", e))
+    }
+}
+
 impl From<&'static str> for RTError {
     fn from(e: &'static str) -> Self {
         RTError::String(e)
@@ -69,9 +75,16 @@ pub const REDIS_VALUE_IS_NOT_VALID_FLOAT_ERR: RTError =
 pub const REDIS_BACKEND_NOT_CONNECTED_ERR: RTError = RTError::String("ERR backend not connected");
 pub const REDIS_COMPARE_AND_SWAP_EXHAUSTED_ERR: RTError =
     RTError::String("ERR compare-and-swap exhausted");
+pub const REDIS_UNKNOWN_DATA_TYPE_ERR: RTError =
+    RTError::String("ERR unknown data type, written by a newer version?");
 pub const REDIS_NOT_SUPPORTED_ERR: RTError = RTError::String("ERR not supported");
 pub const REDIS_NOT_SUPPORTED_DEBUG_SUB_COMMAND_ERR: RTError =
     RTError::String("ERR not supported debug sub command");
+pub const REDIS_DEBUG_COMMAND_DISABLED_ERR: RTError = RTError::String(
+    "ERR DEBUG command not allowed. If the enable_debug_command option is set to \"local\", \
+     you can run it from a local connection, otherwise you need to set this option in the \
+     configuration file, and then restart the server.",
+);
 pub const REDIS_AUTH_WHEN_DISABLED_ERR: RTError =
     RTError::String("ERR Client sent AUTH, but no password is set");
 pub const REDIS_AUTH_INVALID_PASSWORD_ERR: RTError = RTError::String("ERR invalid password");
@@ -81,16 +94,131 @@ pub const REDIS_NO_MATCHING_SCRIPT_ERR: RTError =
 pub const REDIS_LUA_CONTEXT_IS_NOT_INITIALIZED_ERR: RTError =
     RTError::String("ERR lua context is not initialized");
 pub const REDIS_LUA_PANIC: RTError = RTError::String("ERR lua panic");
+pub const REDIS_NOTBUSY_ERR: RTError =
+    RTError::String("NOTBUSY No scripts in execution right now.");
+pub const REDIS_UNKILLABLE_ERR: RTError = RTError::String(
+    "UNKILLABLE Sorry the script already executed write commands against the dataset. \
+     You can wait the script to be aborted when it runs over lua_time_limit.",
+);
 pub const REDIS_UNKNOWN_SUBCOMMAND: RTError =
     RTError::String("Unknown subcommand or wrong number of arguments");
 pub const DECREMENT_OVERFLOW: RTError = RTError::String("Decrement would overflow");
 pub const REDIS_LIST_TOO_LARGE_ERR: RTError = RTError::String("ERR list is too large to execute");
+pub const REDIS_INTER_TOO_LARGE_ERR: RTError =
+    RTError::String("ERR intersection is too large to execute");
+pub const REDIS_UNION_TOO_LARGE_ERR: RTError = RTError::String("ERR union is too large to execute");
+pub const REDIS_DIFF_TOO_LARGE_ERR: RTError =
+    RTError::String("ERR difference is too large to execute");
 pub const KEY_VERSION_EXHUSTED_ERR: RTError = RTError::String("ERR key version exhausted");
 pub const REDIS_MULTI_NESTED_ERR: RTError = RTError::String("ERR MULTI calls can not be nested");
 pub const REDIS_DISCARD_WITHOUT_MULTI_ERR: RTError = RTError::String("ERR DISCARD without MULTI");
 pub const REDIS_EXEC_WITHOUT_MULTI_ERR: RTError = RTError::String("ERR EXEC without MULTI");
+pub const REDIS_WATCH_INSIDE_MULTI_ERR: RTError =
+    RTError::String("ERR WATCH inside MULTI is not allowed");
 pub const REDIS_EXEC_ERR: RTError =
     RTError::String("EXECABORT Transaction discarded because of previous errors.");
+pub const REDIS_READONLY_ERR: RTError =
+    RTError::String("READONLY You can't write against a read only connection.");
+
+pub const REDIS_IDEMPOTENT_REPLAYED_ERR: RTError =
+    RTError::String("ERR request with this idempotency token was already applied");
+
+pub const REDIS_MEMORY_BACKEND_DISABLED_ERR: RTError =
+    RTError::String("ERR memory backend requires tidis built with memory-backend feature");
+
+pub const REDIS_ENCRYPTION_DISABLED_ERR: RTError =
+    RTError::String("ERR value encryption is not enabled");
+pub const REDIS_DATA_KEY_NOT_FOUND_ERR: RTError =
+    RTError::String("ERR data key of the value not found");
+pub const REDIS_DECRYPT_VALUE_ERR: RTError = RTError::String("ERR failed to decrypt value");
+pub const REDIS_DATA_KEYS_NOT_LOADED_ERR: RTError =
+    RTError::String("ERR data keys are not loaded, value can not be encrypted");
 
 pub const REDIS_INVALID_CLIENT_ID_ERR: RTError = RTError::String("ERR Invalid client ID");
 pub const REDIS_NO_SUCH_CLIENT_ERR: RTError = RTError::String("ERR No such client");
+
+pub const REDIS_INVALID_SLOT_ERR: RTError = RTError::String("ERR Invalid or out of range slot");
+pub const REDIS_SLOT_SCAN_LIMIT_ERR: RTError =
+    RTError::String("ERR keyspace over cluster_slot_scan_max_keys keys, slot not scanned");
+
+pub const REDIS_MEMORY_SOFT_LIMIT_ERR: RTError =
+    RTError::String("DENIED memory usage over the soft limit, scans are refused");
+
+pub const REDIS_INVALID_STREAM_ID_ERR: RTError =
+    RTError::String("ERR Invalid stream ID specified as stream command argument");
+pub const REDIS_STREAM_ID_TOO_SMALL_ERR: RTError = RTError::String(
+    "ERR The ID specified in XADD is equal or smaller than the target stream top item",
+);
+pub const REDIS_STREAM_ID_ZERO_ERR: RTError =
+    RTError::String("ERR The ID specified in XADD must be greater than 0-0");
+pub const REDIS_BUSYGROUP_ERR: RTError =
+    RTError::String("BUSYGROUP Consumer Group name already exists");
+pub const REDIS_XGROUP_KEY_REQUIRED_ERR: RTError = RTError::String(
+    "ERR The XGROUP subcommand requires the key to exist. Note that for CREATE you may want to use the MKSTREAM option to create an empty stream automatically.",
+);
+
+pub const REDIS_JSON_INVALID_PATH_ERR: RTError = RTError::String("ERR invalid JSON path");
+pub const REDIS_JSON_NEW_AT_ROOT_ERR: RTError =
+    RTError::String("ERR new objects must be created at the root");
+
+pub const REDIS_GEO_MEMBER_ERR: RTError =
+    RTError::String("ERR could not decode requested zset member");
+pub const REDIS_NO_HASH_INDEX_ERR: RTError =
+    RTError::String("ERR no hash index is declared for this prefix and field");
+pub const REDIS_LPOS_RANK_ERR: RTError = RTError::String(
+    "ERR RANK can't be zero: use 1 to start from the first match, \
+     2 from the second ... or use negative to start from the end of the list",
+);
+pub const REDIS_LPOS_COUNT_ERR: RTError = RTError::String("ERR COUNT can't be negative");
+pub const REDIS_LPOS_MAXLEN_ERR: RTError = RTError::String("ERR MAXLEN can't be negative");
+pub const REDIS_NUMKEYS_ERR: RTError = RTError::String("ERR numkeys should be greater than 0");
+pub const REDIS_COUNT_NOT_POSITIVE_ERR: RTError =
+    RTError::String("ERR count should be greater than 0");
+pub const REDIS_TIMEOUT_NEGATIVE_ERR: RTError = RTError::String("ERR timeout is negative");
+pub const REDIS_TIMEOUT_OUT_OF_RANGE_ERR: RTError = RTError::String("ERR timeout is out of range");
+pub const REDIS_OFFSET_OUT_OF_RANGE_ERR: RTError = RTError::String("ERR offset is out of range");
+pub const REDIS_STRING_TOO_LONG_ERR: RTError =
+    RTError::String("ERR string exceeds maximum allowed size (proto-max-bulk-len)");
+pub const REDIS_BIT_OFFSET_ERR: RTError =
+    RTError::String("ERR bit offset is not an integer or out of range");
+pub const REDIS_BIT_VALUE_ERR: RTError =
+    RTError::String("ERR bit is not an integer or out of range");
+pub const REDIS_BITPOS_BIT_ERR: RTError = RTError::String("ERR The bit argument must be 1 or 0.");
+pub const REDIS_BITFIELD_TYPE_ERR: RTError = RTError::String(
+    "ERR Invalid bitfield type. Use something like i16 u8. \
+     Note that u64 is not supported but i64 is.",
+);
+pub const REDIS_BITFIELD_OVERFLOW_ERR: RTError =
+    RTError::String("ERR Invalid OVERFLOW type specified");
+pub const REDIS_BITOP_NOT_ERR: RTError =
+    RTError::String("ERR BITOP NOT must be called with a single source key.");
+pub const REDIS_BITOP_SOURCE_TOO_LARGE_ERR: RTError =
+    RTError::String("ERR BITOP source string is too large to execute");
+pub const REDIS_SORT_SCORE_ERR: RTError =
+    RTError::String("ERR One or more scores can't be converted into double");
+pub const REDIS_SORT_TOO_LARGE_ERR: RTError =
+    RTError::String("ERR sort source is too large to execute");
+pub const REDIS_MOVE_SEALED_VALUE_ERR: RTError =
+    RTError::String("ERR encrypted values can not be moved to another namespace");
+pub const REDIS_SAME_OBJECT_ERR: RTError =
+    RTError::String("ERR source and destination objects are the same");
+pub const REDIS_DB_INDEX_OUT_OF_RANGE_ERR: RTError =
+    RTError::String("ERR DB index is out of range");
+pub const REDIS_OBJECT_ACCESS_NOT_TRACKED_ERR: RTError =
+    RTError::String("ERR access of keys is not tracked, set object_access_tracking to enable it");
+pub const REDIS_DUMP_PAYLOAD_ERR: RTError =
+    RTError::String("ERR DUMP payload version or checksum are wrong");
+pub const REDIS_BAD_DATA_FORMAT_ERR: RTError = RTError::String("ERR Bad data format");
+pub const REDIS_BUSYKEY_ERR: RTError = RTError::String("BUSYKEY Target key name already exists.");
+pub const REDIS_INVALID_TTL_ERR: RTError = RTError::String("ERR Invalid TTL value, must be >= 0");
+pub const REDIS_DUMP_TYPE_NOT_SUPPORTED_ERR: RTError =
+    RTError::String("ERR DUMP of streams and JSON documents is not supported");
+pub const REDIS_MIGRATE_CONNECT_ERR: RTError =
+    RTError::String("IOERR error or timeout connecting to the client");
+pub const REDIS_MIGRATE_IO_ERR: RTError =
+    RTError::String("IOERR error or timeout reading to target instance");
+pub const REDIS_GETEX_INVALID_EXPIRE_ERR: RTError =
+    RTError::String("ERR invalid expire time in 'getex' command");
+pub const REDIS_MIN_MAX_NOT_FLOAT_ERR: RTError = RTError::String("ERR min or max is not a float");
+pub const REDIS_MIN_MAX_NOT_STRING_RANGE_ERR: RTError =
+    RTError::String("ERR min or max not valid string range item");
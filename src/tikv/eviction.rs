@@ -0,0 +1,298 @@
+//! Eviction of keys when the namespace is over quota, like volatile-ttl.
+//!
+//! Each run of the evict job samples the next `EVICTION_SCAN_LIMIT` keys of the
+//! keyspace, resuming where the previous run stopped, and the keys and bytes of
+//! the namespace are counted over a whole pass. If it holds more than
+//! `eviction_max_keys` keys or `eviction_max_bytes` bytes, the run deletes at
+//! most `eviction_budget` keys, and the `max_deletes` of the job, with a ttl of
+//! its sample, the soonest to expire first, in the transaction of the scan and
+//! only if they were not written since. Keys without a ttl are never evicted.
+
+use std::collections::BinaryHeap;
+use std::str;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex as StdMutex};
+
+use futures::FutureExt;
+use prometheus::core::Collector;
+use slog::info;
+use tikv_client::{Key, KvPair};
+use tokio::sync::Mutex;
+
+use super::backend::Transaction;
+use super::encoding::{DataType, KeyDecoder};
+use super::errors::AsyncResult;
+use super::expiration::{EXPIRE_REASON_ACTIVE, EXPIRE_REASON_LAZY};
+use super::hash::HashCommandCtx;
+use super::list::ListCommandCtx;
+use super::set::SetCommandCtx;
+use super::stream::StreamCommandCtx;
+use super::string::StringCommandCtx;
+use super::zset::ZsetCommandCtx;
+use super::{get_txn_client, KEY_ENCODER};
+use crate::config::LOGGER;
+use crate::metrics::{EVICTED_KEY_COUNTER, EXPIRED_KEY_REMOVAL_COUNTER};
+use crate::triggers::{fire, EVENT_EVICTED};
+use crate::{
+    backend_eviction_budget_or_default, backend_eviction_max_bytes_or_default,
+    backend_eviction_max_keys_or_default,
+};
+
+/// Meta, sub meta and data keys scanned by a run of the evict job
+const EVICTION_SCAN_LIMIT: u32 = 10000;
+
+static KEYSPACE_KEYS: AtomicU64 = AtomicU64::new(0);
+static KEYSPACE_BYTES: AtomicU64 = AtomicU64::new(0);
+
+/// The pass of the evict job over the keyspace
+#[derive(Default)]
+struct Pass {
+    /// the key the next run scans from, the start of the keyspace for none
+    cursor: Option<Vec<u8>>,
+    keys: u64,
+    bytes: u64,
+}
+
+lazy_static! {
+    static ref PASS: StdMutex<Pass> = StdMutex::new(Pass::default());
+}
+
+/// The keys scanned by a run
+#[derive(Default)]
+struct Sample {
+    keys: u64,
+    bytes: u64,
+    /// the key after the last one scanned, none at the end of the keyspace
+    next: Option<Vec<u8>>,
+    /// `(ttl, user key, meta key, stamp)` of the keys with a ttl, the
+    /// soonest to expire first
+    candidates: Vec<(u64, Vec<u8>, Key, Vec<u8>)>,
+}
+
+pub fn eviction_enabled() -> bool {
+    backend_eviction_max_keys_or_default() > 0 || backend_eviction_max_bytes_or_default() > 0
+}
+
+/// The type, ttl and version of a meta value, which change when the key is
+/// written again. The values of strings are not versioned.
+fn meta_stamp(meta: &[u8]) -> Vec<u8> {
+    let len = match KeyDecoder::try_decode_key_type(meta) {
+        Some(DataType::String) | Some(DataType::Json) => 9,
+        _ => 11,
+    };
+    meta[..len.min(meta.len())].to_vec()
+}
+
+/// Sample `kvs`, keeping the `budget` keys with a ttl the soonest to expire
+fn sample_keys(kvs: Vec<KvPair>, budget: usize) -> Sample {
+    let mut sample = Sample::default();
+    if kvs.len() as u32 >= EVICTION_SCAN_LIMIT {
+        if let Some(kv) = kvs.last() {
+            let mut next: Vec<u8> = kv.0.clone().into();
+            next.push(0);
+            sample.next = Some(next);
+        }
+    }
+    // max heap of the keys with a ttl, keeps the `budget` soonest to expire
+    let mut candidates = BinaryHeap::new();
+    for kv in kvs {
+        let (userkey, is_meta_key) = KeyDecoder::decode_key_userkey_from_metakey(&kv.0);
+        sample.bytes += (Vec::<u8>::from(kv.0.clone()).len() + kv.1.len()) as u64;
+        if !is_meta_key {
+            continue;
+        }
+        sample.keys += 1;
+        let ttl = KeyDecoder::decode_key_ttl(&kv.1);
+        if ttl == 0 {
+            continue;
+        }
+        candidates.push((ttl, userkey, kv.0, meta_stamp(&kv.1)));
+        if candidates.len() > budget {
+            candidates.pop();
+        }
+    }
+    sample.candidates = candidates.into_sorted_vec();
+    sample
+}
+
+/// The keys and bytes of the namespace, those counted by the previous pass
+/// till the current one with `sample` counted more
+fn keyspace_size(pass_keys: u64, pass_bytes: u64, sample: &Sample) -> (u64, u64) {
+    (
+        KEYSPACE_KEYS
+            .load(Ordering::Relaxed)
+            .max(pass_keys + sample.keys),
+        KEYSPACE_BYTES
+            .load(Ordering::Relaxed)
+            .max(pass_bytes + sample.bytes),
+    )
+}
+
+/// Delete `key` in the transaction if its meta key still has `stamp`,
+/// returns the number of keys deleted.
+async fn evict_key(
+    txn_rc: Arc<Mutex<Transaction>>,
+    key: &str,
+    meta_key: Key,
+    stamp: &[u8],
+) -> AsyncResult<i64> {
+    let dt = match txn_rc.lock().await.get(meta_key).await? {
+        Some(meta) if meta_stamp(&meta) == stamp => KeyDecoder::try_decode_key_type(&meta),
+        _ => return Ok(0),
+    };
+    let txn = Some(txn_rc);
+    match dt {
+        Some(DataType::String) | Some(DataType::Json) => {
+            StringCommandCtx::new(txn)
+                .do_async_txnkv_string_del(key)
+                .await
+        }
+        Some(DataType::Hash) => HashCommandCtx::new(txn).do_async_txnkv_hash_del(key).await,
+        Some(DataType::List) => ListCommandCtx::new(txn).do_async_txnkv_list_del(key).await,
+        Some(DataType::Set) => SetCommandCtx::new(txn).do_async_txnkv_set_del(key).await,
+        Some(DataType::Zset) => ZsetCommandCtx::new(txn).do_async_txnk_zset_del(key).await,
+        Some(DataType::Stream) => {
+            StreamCommandCtx::new(txn)
+                .do_async_txnkv_stream_del(key)
+                .await
+        }
+        Some(DataType::Null) | None => Ok(0),
+    }
+}
+
+/// Evict at most `max_deletes` keys over quota, 0 for the eviction budget
+/// only, returns the number of keys evicted.
+pub async fn evict_over_quota(max_deletes: usize) -> AsyncResult<usize> {
+    if !eviction_enabled() {
+        return Ok(0);
+    }
+    let mut budget = backend_eviction_budget_or_default();
+    if max_deletes > 0 {
+        budget = budget.min(max_deletes);
+    }
+    let max_keys = backend_eviction_max_keys_or_default();
+    let max_bytes = backend_eviction_max_bytes_or_default();
+    let (cursor, pass_keys, pass_bytes) = {
+        let pass = PASS.lock().unwrap();
+        (pass.cursor.clone(), pass.keys, pass.bytes)
+    };
+
+    let mut client = get_txn_client()?;
+    let (sample, evicted) = client
+        .exec_in_txn(None, |txn_rc| {
+            async move {
+                let start: Key = match cursor {
+                    Some(cursor) => cursor.into(),
+                    None => KEY_ENCODER.encode_txnkv_keyspace_start(),
+                };
+                let range = start..KEY_ENCODER.encode_txnkv_keyspace_end();
+                let kvs = txn_rc
+                    .lock()
+                    .await
+                    .scan(range, EVICTION_SCAN_LIMIT)
+                    .await?
+                    .collect();
+                let mut sample = sample_keys(kvs, budget);
+                let (keys, bytes) = keyspace_size(pass_keys, pass_bytes, &sample);
+                let over_keys = if max_keys > 0 && keys > max_keys {
+                    (keys - max_keys) as usize
+                } else {
+                    0
+                };
+                // the size of a key is not known, spend the whole budget when over bytes
+                let to_evict = if max_bytes > 0 && bytes > max_bytes {
+                    budget
+                } else {
+                    over_keys.min(budget)
+                };
+
+                let mut evicted = vec![];
+                for (_, userkey, meta_key, stamp) in std::mem::take(&mut sample.candidates) {
+                    if evicted.len() >= to_evict {
+                        break;
+                    }
+                    // only keys written by commands, which are utf-8
+                    let key = match str::from_utf8(&userkey) {
+                        Ok(key) => key.to_owned(),
+                        Err(_) => continue,
+                    };
+                    if evict_key(txn_rc.clone(), &key, meta_key, &stamp).await? > 0 {
+                        evicted.push(key);
+                    }
+                }
+                Ok((sample, evicted))
+            }
+            .boxed()
+        })
+        .await?;
+
+    {
+        let mut pass = PASS.lock().unwrap();
+        pass.keys = pass_keys + sample.keys;
+        pass.bytes = pass_bytes + sample.bytes;
+        pass.cursor = sample.next.clone();
+        if pass.cursor.is_none() {
+            KEYSPACE_KEYS.store(pass.keys, Ordering::Relaxed);
+            KEYSPACE_BYTES.store(pass.bytes, Ordering::Relaxed);
+            *pass = Pass::default();
+        }
+    }
+
+    if evicted.is_empty() {
+        return Ok(0);
+    }
+    EVICTED_KEY_COUNTER.inc_by(evicted.len() as u64);
+    for victim in &evicted {
+        fire(EVENT_EVICTED, victim).await;
+    }
+    let (keys, bytes) = keyspace_size(pass_keys, pass_bytes, &sample);
+    info!(
+        LOGGER,
+        "[EVICTION] namespace over quota with {} keys and {} bytes, evicted {} keys",
+        keys,
+        bytes,
+        evicted.len()
+    );
+    Ok(evicted.len())
+}
+
+/// Removed expired keys with the `(name, value)` label, all of them for none
+fn expired_keys(label: Option<(&str, &str)>) -> u64 {
+    EXPIRED_KEY_REMOVAL_COUNTER
+        .collect()
+        .iter()
+        .flat_map(|mf| mf.get_metric().iter())
+        .filter(|m| match label {
+            Some((name, value)) => m
+                .get_label()
+                .iter()
+                .any(|l| l.get_name() == name && l.get_value() == value),
+            None => true,
+        })
+        .map(|m| m.get_counter().get_value() as u64)
+        .sum()
+}
+
+/// Stats of the keyspace reported in `INFO STATS`
+pub fn encode_stats_info() -> String {
+    let mut info = format!("# Stats\r\nexpired_keys:{}\r\n", expired_keys(None));
+    for kind in ["string", "hash", "list", "set", "zset", "stream"] {
+        info.push_str(&format!(
+            "expired_keys_{}:{}\r\n",
+            kind,
+            expired_keys(Some(("kind", kind)))
+        ));
+    }
+    info.push_str(&format!(
+        "expired_keys_lazy:{}\r\nexpired_keys_active:{}\r\nevicted_keys:{}\r\n\
+        keyspace_keys:{}\r\nkeyspace_bytes:{}\r\nmaxkeys:{}\r\nmaxbytes:{}\r\n",
+        expired_keys(Some(("reason", EXPIRE_REASON_LAZY))),
+        expired_keys(Some(("reason", EXPIRE_REASON_ACTIVE))),
+        EVICTED_KEY_COUNTER.get(),
+        KEYSPACE_KEYS.load(Ordering::Relaxed),
+        KEYSPACE_BYTES.load(Ordering::Relaxed),
+        backend_eviction_max_keys_or_default(),
+        backend_eviction_max_bytes_or_default()
+    ));
+    info
+}
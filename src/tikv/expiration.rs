@@ -0,0 +1,194 @@
+//! Removal of the expired keys and forecast of the expirations of the
+//! namespace reported by TTLFORECAST.
+//!
+//! An expired key is removed lazily by the command accessing it, or by the
+//! expire job, which removes the expired keys of the next
+//! `EXPIRE_SCAN_LIMIT` keys of the keyspace at each run, resuming where the
+//! previous run stopped. The removals are counted by data type and reason,
+//! `lazy` or `active`.
+//!
+//! There is no index of the keys by ttl, the forecast scans the meta keys of
+//! the keyspace like the evict job and counts the keys with a ttl in each
+//! bucket of time from now, so operators can see an expiration storm coming
+//! and give the gc workers more room before it.
+
+use std::sync::Mutex as StdMutex;
+
+use futures::StreamExt;
+use tikv_client::{Key, KvPair};
+
+use super::encoding::{DataType, KeyDecoder};
+use super::errors::AsyncResult;
+use super::hash::HashCommandCtx;
+use super::list::ListCommandCtx;
+use super::set::SetCommandCtx;
+use super::stream::StreamCommandCtx;
+use super::string::StringCommandCtx;
+use super::zset::ZsetCommandCtx;
+use super::{get_txn_client, KEY_ENCODER};
+use crate::config_instance_id_or_default;
+use crate::metrics::{EXPIRED_KEY_REMOVAL_COUNTER, REMOVED_EXPIRED_KEY_COUNTER};
+use crate::utils::{key_is_expired, now_timestamp_in_millis};
+
+/// Meta, sub meta and data keys scanned by a run of the expire job
+const EXPIRE_SCAN_LIMIT: u32 = 10000;
+
+pub const EXPIRE_REASON_LAZY: &str = "lazy";
+pub const EXPIRE_REASON_ACTIVE: &str = "active";
+
+tokio::task_local! {
+    /// Why the expired keys removed by the task are removed, lazy if unset
+    pub static EXPIRE_REASON: &'static str;
+}
+
+lazy_static! {
+    /// The key the next run of the expire job scans from, the start of the
+    /// keyspace for none
+    static ref EXPIRE_CURSOR: StdMutex<Option<Vec<u8>>> = StdMutex::new(None);
+}
+
+/// Count an expired key of `kind` removed, for the reason of the task
+pub fn count_expired_key(kind: &str) {
+    let reason = EXPIRE_REASON
+        .try_with(|reason| *reason)
+        .unwrap_or(EXPIRE_REASON_LAZY);
+    REMOVED_EXPIRED_KEY_COUNTER.with_label_values(&[kind]).inc();
+    EXPIRED_KEY_REMOVAL_COUNTER
+        .with_label_values(&[&config_instance_id_or_default(), kind, reason])
+        .inc();
+}
+
+/// Remove `key` of type `dt` if it is still expired, returns the number of
+/// keys removed
+async fn expire_key(key: &str, dt: Option<DataType>) -> AsyncResult<i64> {
+    match dt {
+        Some(DataType::String) | Some(DataType::Json) => {
+            StringCommandCtx::new(None)
+                .do_async_txnkv_string_expire_if_needed(key)
+                .await
+        }
+        Some(DataType::Hash) => {
+            HashCommandCtx::new(None)
+                .do_async_txnkv_hash_expire_if_needed(key)
+                .await
+        }
+        Some(DataType::List) => {
+            ListCommandCtx::new(None)
+                .do_async_txnkv_list_expire_if_needed(key)
+                .await
+        }
+        Some(DataType::Set) => {
+            SetCommandCtx::new(None)
+                .do_async_txnkv_set_expire_if_needed(key)
+                .await
+        }
+        Some(DataType::Zset) => {
+            ZsetCommandCtx::new(None)
+                .do_async_txnkv_zset_expire_if_needed(key)
+                .await
+        }
+        Some(DataType::Stream) => {
+            StreamCommandCtx::new(None)
+                .do_async_txnkv_stream_expire_if_needed(key)
+                .await
+        }
+        Some(DataType::Null) | None => Ok(0),
+    }
+}
+
+/// Remove at most `max_deletes` expired keys, 0 for no limit, among the next
+/// keys of the keyspace, returns the number of keys removed.
+pub async fn remove_expired_keys(max_deletes: usize) -> AsyncResult<usize> {
+    let cursor = EXPIRE_CURSOR.lock().unwrap().clone();
+    let client = get_txn_client()?;
+    let mut snapshot = client.begin_with_latest();
+    let start: Key = match cursor {
+        Some(cursor) => cursor.into(),
+        None => KEY_ENCODER.encode_txnkv_keyspace_start(),
+    };
+    let range = start..KEY_ENCODER.encode_txnkv_keyspace_end();
+    let kvs: Vec<KvPair> = snapshot.scan(range, EXPIRE_SCAN_LIMIT).await?.collect();
+
+    let mut next = None;
+    if kvs.len() as u32 >= EXPIRE_SCAN_LIMIT {
+        if let Some(kv) = kvs.last() {
+            let mut key: Vec<u8> = kv.0.clone().into();
+            key.push(0);
+            next = Some(key);
+        }
+    }
+    let mut removed = 0;
+    for kv in kvs {
+        let (userkey, is_meta_key) = KeyDecoder::decode_key_userkey_from_metakey(&kv.0);
+        if !is_meta_key || !key_is_expired(KeyDecoder::decode_key_ttl(&kv.1)) {
+            continue;
+        }
+        if max_deletes > 0 && removed >= max_deletes {
+            // the next run starts from this key
+            next = Some(kv.0.into());
+            break;
+        }
+        // only keys written by commands, which are utf-8
+        let key = match String::from_utf8(userkey) {
+            Ok(key) => key,
+            Err(_) => continue,
+        };
+        let dt = KeyDecoder::try_decode_key_type(&kv.1);
+        removed += EXPIRE_REASON
+            .scope(EXPIRE_REASON_ACTIVE, expire_key(&key, dt))
+            .await? as usize;
+    }
+    *EXPIRE_CURSOR.lock().unwrap() = next;
+    Ok(removed)
+}
+
+/// Number of keys of the namespace by expire time
+#[derive(Debug, Default)]
+pub struct Forecast {
+    /// start of the first bucket, in ms
+    pub now: u64,
+    /// keys expiring in each bucket from now
+    pub buckets: Vec<u64>,
+    /// keys expiring after the last bucket
+    pub later: u64,
+    /// expired keys not removed yet
+    pub overdue: u64,
+    /// keys without a ttl
+    pub persistent: u64,
+}
+
+/// Count the keys expiring in each of `buckets` buckets of `bucket_ms` from
+/// now, read from a snapshot of the keyspace.
+pub async fn expiration_forecast(bucket_ms: u64, buckets: usize) -> AsyncResult<Forecast> {
+    let client = get_txn_client()?;
+    let mut snapshot = client.begin_with_latest();
+    let mut forecast = Forecast {
+        now: now_timestamp_in_millis(),
+        buckets: vec![0; buckets],
+        ..Default::default()
+    };
+
+    let range = KEY_ENCODER.encode_txnkv_keyspace_start()..KEY_ENCODER.encode_txnkv_keyspace_end();
+    let mut iter = snapshot.scan_stream(range, u32::MAX).await?;
+    while let Some(kv) = iter.next().await {
+        let (_, is_meta_key) = KeyDecoder::decode_key_userkey_from_metakey(&kv.0);
+        if !is_meta_key {
+            continue;
+        }
+        let ttl = KeyDecoder::decode_key_ttl(&kv.1);
+        if ttl == 0 {
+            forecast.persistent += 1;
+        } else if ttl < forecast.now {
+            forecast.overdue += 1;
+        } else {
+            match forecast
+                .buckets
+                .get_mut(((ttl - forecast.now) / bucket_ms) as usize)
+            {
+                Some(count) => *count += 1,
+                None => forecast.later += 1,
+            }
+        }
+    }
+    Ok(forecast)
+}
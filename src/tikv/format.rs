@@ -0,0 +1,363 @@
+//! Data format record of the namespace, fencing incompatible binaries.
+//!
+//! The record holds the version of the encoding of the keys and values, the
+//! layout of the namespace prefix and the features whose data a binary must
+//! know to read and write the keyspace correctly, like sealed strings or hash
+//! indexes. It is written by the first instance of the namespace and checked
+//! by each instance at startup, which refuses to start if the record was
+//! written by a newer binary, is older than the oldest version it reads, or
+//! has a feature it does not know. An instance not enabling in its config a
+//! feature of the record, encryption or hash indexes, is refused too, as it
+//! would read sealed strings as plain values and write hashes without
+//! keeping their indexes.
+//!
+//! A binary reading an older version starts and keeps writing the old format,
+//! `TIDIS.FORMAT UPGRADE` bumps the record to its version once the migration
+//! is complete, fencing the older binaries out from then on.
+//!
+//! The features of a new encoding written by a member could not be read by
+//! the older members of a fleet in a rolling deploy, they are negotiated
+//! instead: each member registers the negotiated features it supports next
+//! to its topology key, and once every live member supports one, it is
+//! activated in the record by the topology manager. Writers check
+//! `feature_active` before using the new encoding, and the binaries started
+//! from then on without the feature are refused at startup like any unknown
+//! feature. An active feature is never deactivated.
+
+use std::convert::TryInto;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use futures::FutureExt;
+use slog::{info, warn};
+
+use super::backend::Transaction;
+use super::errors::{AsyncResult, RTError};
+use super::{get_txn_client, KEY_ENCODER};
+use crate::config::{is_use_txn_api, LOGGER};
+use crate::utils::{resp_array, resp_bulk, resp_int};
+use crate::{config_hash_indexes_or_default, encryption_enabled_or_default, Frame};
+
+/// Version of the encoding of the keys and values written by this binary
+pub const DATA_FORMAT_VERSION: u32 = 1;
+/// Oldest version of the encoding this binary reads
+pub const MIN_DATA_FORMAT_VERSION: u32 = 1;
+/// Layout of the namespace prefix, the txn prefix followed by the instance id
+pub const NAMESPACE_LAYOUT: u16 = 1;
+
+/// String values sealed by the data keys of `encryption`
+pub const FEATURE_SEALED_STRINGS: u64 = 1;
+/// Index entries of hash fields kept by `hash_indexes`
+pub const FEATURE_HASH_INDEXES: u64 = 1 << 1;
+
+/// Collections packed in the value of their meta key, negotiated
+pub const FEATURE_PACKED_COLLECTIONS: u64 = 1 << 2;
+/// Version 2 of the index of the keys by expire time, negotiated
+pub const FEATURE_TTL_INDEX_V2: u64 = 1 << 3;
+
+const FEATURES: [(u64, &str); 4] = [
+    (FEATURE_SEALED_STRINGS, "sealed-strings"),
+    (FEATURE_HASH_INDEXES, "hash-indexes"),
+    (FEATURE_PACKED_COLLECTIONS, "packed-collections"),
+    (FEATURE_TTL_INDEX_V2, "ttl-index-v2"),
+];
+
+/// Features activated once every live member supports them
+const NEGOTIATED_FEATURES: u64 = FEATURE_PACKED_COLLECTIONS | FEATURE_TTL_INDEX_V2;
+
+/// Negotiated features this binary reads and writes, none of them is
+/// implemented yet, a binary implementing one adds it here
+pub const SUPPORTED_NEGOTIATED_FEATURES: u64 = 0;
+
+/// Features enabled by the config, which an instance serving the keyspace
+/// of a record with them must enable as well
+const CONFIG_FEATURES: u64 = FEATURE_SEALED_STRINGS | FEATURE_HASH_INDEXES;
+
+/// Features known by this binary
+const KNOWN_FEATURES: u64 =
+    FEATURE_SEALED_STRINGS | FEATURE_HASH_INDEXES | SUPPORTED_NEGOTIATED_FEATURES;
+
+/// Features of the record, refreshed by the topology manager
+static ACTIVE_FEATURES: AtomicU64 = AtomicU64::new(0);
+
+/// Whether the feature is active in the record of the namespace, so its
+/// data can be written
+pub fn feature_active(feature: u64) -> bool {
+    ACTIVE_FEATURES.load(Ordering::Relaxed) & feature == feature
+}
+
+#[derive(Debug, Clone, Copy)]
+struct FormatRecord {
+    version: u32,
+    layout: u16,
+    features: u64,
+}
+
+impl FormatRecord {
+    /// The record of a namespace first served by this binary
+    fn current() -> FormatRecord {
+        FormatRecord {
+            version: DATA_FORMAT_VERSION,
+            layout: NAMESPACE_LAYOUT,
+            features: enabled_features(),
+        }
+    }
+
+    fn decode(value: &[u8]) -> Option<FormatRecord> {
+        if value.len() < 14 {
+            return None;
+        }
+        Some(FormatRecord {
+            version: u32::from_be_bytes(value[..4].try_into().unwrap()),
+            layout: u16::from_be_bytes(value[4..6].try_into().unwrap()),
+            features: u64::from_be_bytes(value[6..14].try_into().unwrap()),
+        })
+    }
+
+    fn encode(&self) -> Vec<u8> {
+        let mut value = Vec::with_capacity(14);
+        value.extend_from_slice(&self.version.to_be_bytes());
+        value.extend_from_slice(&self.layout.to_be_bytes());
+        value.extend_from_slice(&self.features.to_be_bytes());
+        value
+    }
+
+    /// Why this binary can not serve the keyspace of the record, if so
+    fn incompatibility(&self) -> Option<String> {
+        if self.layout != NAMESPACE_LAYOUT {
+            return Some(format!(
+                "namespace layout {} is not supported, expected {}",
+                self.layout, NAMESPACE_LAYOUT
+            ));
+        }
+        if self.version > DATA_FORMAT_VERSION {
+            return Some(format!(
+                "data format version {} was written by a newer binary, this one supports up to {}",
+                self.version, DATA_FORMAT_VERSION
+            ));
+        }
+        if self.version < MIN_DATA_FORMAT_VERSION {
+            return Some(format!(
+                "data format version {} is older than {}, migrate it with an older binary first",
+                self.version, MIN_DATA_FORMAT_VERSION
+            ));
+        }
+        let unknown = self.features & !KNOWN_FEATURES;
+        if unknown != 0 {
+            return Some(format!("unknown data format features {:#x}", unknown));
+        }
+        let disabled = self.features & CONFIG_FEATURES & !enabled_features();
+        if disabled != 0 {
+            return Some(format!(
+                "data format features [{}] are not enabled by the config",
+                feature_names(disabled).join(",")
+            ));
+        }
+        None
+    }
+}
+
+/// Features enabled by the config of this instance
+fn enabled_features() -> u64 {
+    let mut features = 0;
+    if encryption_enabled_or_default() {
+        features |= FEATURE_SEALED_STRINGS;
+    }
+    if !config_hash_indexes_or_default().is_empty() {
+        features |= FEATURE_HASH_INDEXES;
+    }
+    features
+}
+
+fn feature_names(features: u64) -> Vec<&'static str> {
+    FEATURES
+        .iter()
+        .filter(|(flag, _)| features & flag != 0)
+        .map(|(_, name)| *name)
+        .collect()
+}
+
+/// Read the record and write it back with `f` applied, if it returns a new one
+async fn update_record<F>(f: F) -> AsyncResult<FormatRecord>
+where
+    F: FnOnce(Option<FormatRecord>) -> AsyncResult<(FormatRecord, bool)> + Clone + Send + 'static,
+{
+    let mut client = get_txn_client()?;
+    let ekey = KEY_ENCODER.encode_txnkv_format_key();
+
+    client
+        .exec_in_txn(None, |txn_rc| {
+            async move {
+                let mut txn = txn_rc.lock().await;
+                let record = match txn.get(ekey.clone()).await? {
+                    Some(value) => match FormatRecord::decode(&value) {
+                        Some(record) => Some(record),
+                        None => return Err(RTError::String("ERR data format record is corrupted")),
+                    },
+                    None => None,
+                };
+                let (record, changed) = f(record)?;
+                if changed {
+                    txn.put(ekey, record.encode()).await?;
+                }
+                Ok(record)
+            }
+            .boxed()
+        })
+        .await
+}
+
+/// Check the record of the namespace at startup, writing it if it is the
+/// first instance, and add the features enabled by the config. Fails if this
+/// binary is not compatible with the data, must be called after backend
+/// connected. Nothing is checked with the raw api.
+pub async fn check_data_format() -> AsyncResult<()> {
+    if !is_use_txn_api() {
+        return Ok(());
+    }
+    let record = update_record(|record| match record {
+        None => Ok((FormatRecord::current(), true)),
+        Some(record) => {
+            if let Some(reason) = record.incompatibility() {
+                return Err(RTError::Owned(format!("ERR {}, refusing to start", reason)));
+            }
+            let features = record.features | enabled_features();
+            Ok((
+                FormatRecord { features, ..record },
+                features != record.features,
+            ))
+        }
+    })
+    .await?;
+    ACTIVE_FEATURES.store(record.features, Ordering::Relaxed);
+
+    info!(
+        LOGGER,
+        "data format version {}, features [{}]",
+        record.version,
+        feature_names(record.features).join(",")
+    );
+    if record.version < DATA_FORMAT_VERSION {
+        warn!(
+            LOGGER,
+            "data format version {} is older than {}, run TIDIS.FORMAT UPGRADE once migrated",
+            record.version,
+            DATA_FORMAT_VERSION
+        );
+    }
+    Ok(())
+}
+
+/// Register the negotiated features supported by the member at `address`,
+/// and activate in the record those supported by all the
+/// live `members`. Called by the topology manager in its transaction, which
+/// refreshes the features active on this member.
+pub async fn negotiate_features(
+    txn: &mut Transaction,
+    address: &str,
+    members: &[String],
+) -> AsyncResult<()> {
+    txn.put(
+        KEY_ENCODER.encode_txnkv_member_features_key(address),
+        SUPPORTED_NEGOTIATED_FEATURES.to_be_bytes().to_vec(),
+    )
+    .await?;
+
+    let registered: Vec<Vec<u8>> = txn
+        .batch_get(
+            members
+                .iter()
+                .map(|member| KEY_ENCODER.encode_txnkv_member_features_key(member)),
+        )
+        .await?
+        .map(|kv| kv.1)
+        .collect();
+    // the members not registered yet, or running an older binary which does
+    // not register, support none of them
+    let common = if registered.len() < members.len() {
+        0
+    } else {
+        registered
+            .iter()
+            .fold(NEGOTIATED_FEATURES, |common, value| match value.get(..8) {
+                Some(features) => common & u64::from_be_bytes(features.try_into().unwrap()),
+                None => 0,
+            })
+    };
+
+    let ekey = KEY_ENCODER.encode_txnkv_format_key();
+    let record = match txn.get(ekey.clone()).await? {
+        Some(value) => FormatRecord::decode(&value),
+        None => None,
+    };
+    if let Some(mut record) = record {
+        let activated = common & !record.features;
+        if activated != 0 {
+            record.features |= activated;
+            txn.put(ekey, record.encode()).await?;
+            info!(
+                LOGGER,
+                "data format features [{}] supported by all members, activated",
+                feature_names(activated).join(",")
+            );
+        }
+        ACTIVE_FEATURES.store(record.features, Ordering::Relaxed);
+    }
+    Ok(())
+}
+
+/// Bump the record to the version of this binary, replies the version of the
+/// record. It does not migrate anything, it tells the older binaries the data
+/// is not theirs anymore.
+pub async fn upgrade_data_format() -> AsyncResult<Frame> {
+    let record = update_record(|record| {
+        let (record, missing) = match record {
+            Some(record) => (record, false),
+            None => (FormatRecord::current(), true),
+        };
+        if let Some(reason) = record.incompatibility() {
+            return Err(RTError::Owned(format!("ERR {}", reason)));
+        }
+        if record.version == DATA_FORMAT_VERSION {
+            return Ok((record, missing));
+        }
+        Ok((
+            FormatRecord {
+                version: DATA_FORMAT_VERSION,
+                ..record
+            },
+            true,
+        ))
+    })
+    .await?;
+    info!(
+        LOGGER,
+        "data format record at version {} after upgrade", record.version
+    );
+    Ok(resp_int(record.version as i64))
+}
+
+/// The record of the namespace and the versions supported by this binary
+pub async fn data_format_info() -> AsyncResult<Frame> {
+    let record = update_record(|record| match record {
+        Some(record) => Ok((record, false)),
+        // written at startup, unless it was removed since
+        None => Ok((FormatRecord::current(), false)),
+    })
+    .await?;
+    let features = feature_names(record.features)
+        .into_iter()
+        .map(|name| resp_bulk(name.as_bytes().to_vec()))
+        .collect();
+    Ok(resp_array(vec![
+        resp_bulk(b"version".to_vec()),
+        resp_int(record.version as i64),
+        resp_bulk(b"layout".to_vec()),
+        resp_int(record.layout as i64),
+        resp_bulk(b"features".to_vec()),
+        resp_array(features),
+        resp_bulk(b"supported_version".to_vec()),
+        resp_int(DATA_FORMAT_VERSION as i64),
+        resp_bulk(b"min_supported_version".to_vec()),
+        resp_int(MIN_DATA_FORMAT_VERSION as i64),
+    ]))
+}
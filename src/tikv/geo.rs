@@ -0,0 +1,312 @@
+//! GEO commands on the sorted set encoding.
+//!
+//! GEOADD writes the members with the 52 bits geohash of their position as
+//! score through ZADD, so a geo set is a sorted set to all other commands.
+//! GEOSEARCH scans the score ranges of the cells covering the searched area
+//! with `search_ranges` and keeps the members in the shape.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use futures::future::FutureExt;
+use futures::StreamExt;
+use tikv_client::{BoundRange, Key, Value};
+use tokio::sync::Mutex;
+
+use super::backend::Transaction;
+use super::encoding::{DataType, KeyDecoder};
+use super::errors::{AsyncResult, REDIS_GEO_MEMBER_ERR, REDIS_WRONG_TYPE_ERR};
+use super::get_txn_client;
+use super::zset::ZsetCommandCtx;
+use super::KEY_ENCODER;
+use crate::geohash::{decode, distance, distance_in_shape, search_ranges, Shape};
+use crate::utils::{key_is_expired, resp_array, resp_bulk, resp_err, resp_int, resp_nil};
+use crate::Frame;
+
+/// The center of a GEOSEARCH
+#[derive(Debug, Clone)]
+pub enum Origin {
+    Member(String),
+    LonLat(f64, f64),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SortOrder {
+    Asc,
+    Desc,
+}
+
+/// Options of GEOSEARCH, the distances are replied in `unit` meters
+#[derive(Debug, Default, Clone, Copy)]
+pub struct SearchOptions {
+    pub sort: Option<SortOrder>,
+    pub count: Option<usize>,
+    pub any: bool,
+    pub withcoord: bool,
+    pub withdist: bool,
+    pub withhash: bool,
+    pub unit: f64,
+}
+
+/// A distance replied by the GEO commands, with 4 decimals like Redis
+fn resp_distance(meters: f64, unit: f64) -> Frame {
+    resp_bulk(format!("{:.4}", meters / unit).into_bytes())
+}
+
+fn resp_position(score: f64) -> Frame {
+    let (lon, lat) = decode(score as u64);
+    resp_array(vec![
+        resp_bulk(lon.to_string().into_bytes()),
+        resp_bulk(lat.to_string().into_bytes()),
+    ])
+}
+
+#[derive(Clone)]
+pub struct GeoCommandCtx {
+    txn: Option<Arc<Mutex<Transaction>>>,
+}
+
+impl GeoCommandCtx {
+    pub fn new(txn: Option<Arc<Mutex<Transaction>>>) -> Self {
+        GeoCommandCtx { txn }
+    }
+
+    /// Positions of the members, nil for missing members
+    pub async fn do_async_txnkv_geopos(
+        mut self,
+        key: &str,
+        members: &[String],
+    ) -> AsyncResult<Frame> {
+        let mut client = get_txn_client()?;
+        let key = key.to_owned();
+        let members = members.to_owned();
+
+        let resp = client
+            .exec_in_txn(self.txn.clone(), |txn_rc| {
+                async move {
+                    if self.txn.is_none() {
+                        self.txn = Some(txn_rc.clone());
+                    }
+                    let scores = self.get_scores(&txn_rc, &key, &members).await?;
+                    Ok(resp_array(
+                        scores
+                            .into_iter()
+                            .map(|score| score.map_or_else(resp_nil, resp_position))
+                            .collect(),
+                    ))
+                }
+                .boxed()
+            })
+            .await;
+
+        match resp {
+            Ok(frame) => Ok(frame),
+            Err(e) => Ok(resp_err(e)),
+        }
+    }
+
+    /// Distance between two members in `unit` meters, nil if one is missing
+    pub async fn do_async_txnkv_geodist(
+        mut self,
+        key: &str,
+        member1: &str,
+        member2: &str,
+        unit: f64,
+    ) -> AsyncResult<Frame> {
+        let mut client = get_txn_client()?;
+        let key = key.to_owned();
+        let members = vec![member1.to_owned(), member2.to_owned()];
+
+        let resp = client
+            .exec_in_txn(self.txn.clone(), |txn_rc| {
+                async move {
+                    if self.txn.is_none() {
+                        self.txn = Some(txn_rc.clone());
+                    }
+                    let scores = self.get_scores(&txn_rc, &key, &members).await?;
+                    match (scores[0], scores[1]) {
+                        (Some(score1), Some(score2)) => {
+                            let (lon1, lat1) = decode(score1 as u64);
+                            let (lon2, lat2) = decode(score2 as u64);
+                            Ok(resp_distance(distance(lon1, lat1, lon2, lat2), unit))
+                        }
+                        _ => Ok(resp_nil()),
+                    }
+                }
+                .boxed()
+            })
+            .await;
+
+        match resp {
+            Ok(frame) => Ok(frame),
+            Err(e) => Ok(resp_err(e)),
+        }
+    }
+
+    /// Members in the shape around the origin, sorted by distance if asked
+    /// or to keep the `count` nearest ones. With `any` the scan stops at the
+    /// first `count` members found.
+    pub async fn do_async_txnkv_geosearch(
+        mut self,
+        key: &str,
+        origin: Origin,
+        shape: Shape,
+        options: SearchOptions,
+    ) -> AsyncResult<Frame> {
+        let mut client = get_txn_client()?;
+        let key = key.to_owned();
+
+        let resp = client
+            .exec_in_txn(self.txn.clone(), |txn_rc| {
+                async move {
+                    if self.txn.is_none() {
+                        self.txn = Some(txn_rc.clone());
+                    }
+                    let version = match self.get_zset_version(&txn_rc, &key).await? {
+                        Some(version) => version,
+                        None if matches!(origin, Origin::Member(_)) => {
+                            return Err(REDIS_GEO_MEMBER_ERR)
+                        }
+                        None => return Ok(resp_array(vec![])),
+                    };
+                    let center = match &origin {
+                        Origin::Member(member) => {
+                            let members = vec![member.clone()];
+                            match self.get_scores(&txn_rc, &key, &members).await?[0] {
+                                Some(score) => decode(score as u64),
+                                None => return Err(REDIS_GEO_MEMBER_ERR),
+                            }
+                        }
+                        Origin::LonLat(lon, lat) => (*lon, *lat),
+                    };
+
+                    let mut txn = txn_rc.lock().await;
+                    // (member, distance, score)
+                    let mut found = vec![];
+                    'ranges: for (start, end) in search_ranges(center, shape) {
+                        let start_key = KEY_ENCODER.encode_txnkv_zset_score_key_score_start(
+                            &key,
+                            start as f64,
+                            true,
+                            version,
+                        );
+                        let end_key = KEY_ENCODER.encode_txnkv_zset_score_key_score_end(
+                            &key, end as f64, false, version,
+                        );
+                        let range: BoundRange = (start_key..end_key).into();
+                        let mut iter = txn.scan_stream(range, u32::MAX).await?;
+                        while let Some(kv) = iter.next().await {
+                            let score = KeyDecoder::decode_key_zset_score_from_scorekey(&key, kv.0);
+                            let (lon, lat) = decode(score as u64);
+                            if let Some(dist) = distance_in_shape(center, shape, lon, lat) {
+                                found.push((kv.1, dist, score));
+                                if options.any && Some(found.len()) == options.count {
+                                    break 'ranges;
+                                }
+                            }
+                        }
+                    }
+
+                    // the nearest members are kept with COUNT without ANY
+                    let sort = match options.sort {
+                        None if options.count.is_some() && !options.any => Some(SortOrder::Asc),
+                        sort => sort,
+                    };
+                    match sort {
+                        Some(SortOrder::Asc) => found.sort_by(|a, b| a.1.total_cmp(&b.1)),
+                        Some(SortOrder::Desc) => found.sort_by(|a, b| b.1.total_cmp(&a.1)),
+                        None => {}
+                    }
+                    if let Some(count) = options.count {
+                        found.truncate(count);
+                    }
+
+                    let with_info = options.withcoord || options.withdist || options.withhash;
+                    let resp = found
+                        .into_iter()
+                        .map(|(member, dist, score)| {
+                            if !with_info {
+                                return resp_bulk(member);
+                            }
+                            let mut item = vec![resp_bulk(member)];
+                            if options.withdist {
+                                item.push(resp_distance(dist, options.unit));
+                            }
+                            if options.withhash {
+                                item.push(resp_int(score as i64));
+                            }
+                            if options.withcoord {
+                                item.push(resp_position(score));
+                            }
+                            resp_array(item)
+                        })
+                        .collect();
+                    Ok(resp_array(resp))
+                }
+                .boxed()
+            })
+            .await;
+
+        match resp {
+            Ok(frame) => Ok(frame),
+            Err(e) => Ok(resp_err(e)),
+        }
+    }
+
+    /// The version of the sorted set, None if it does not exist or expired
+    async fn get_zset_version(
+        &self,
+        txn_rc: &Arc<Mutex<Transaction>>,
+        key: &str,
+    ) -> AsyncResult<Option<u16>> {
+        let mut txn = txn_rc.lock().await;
+        let meta_key = KEY_ENCODER.encode_txnkv_meta_key(key);
+        let meta_value = match txn.get(meta_key).await? {
+            Some(meta_value) => meta_value,
+            None => return Ok(None),
+        };
+        if !matches!(KeyDecoder::decode_key_type(&meta_value), DataType::Zset) {
+            return Err(REDIS_WRONG_TYPE_ERR);
+        }
+        let (ttl, version, _) = KeyDecoder::decode_key_meta(&meta_value);
+        if key_is_expired(ttl) {
+            drop(txn);
+            ZsetCommandCtx::new(self.txn.clone())
+                .do_async_txnkv_zset_expire_if_needed(key)
+                .await?;
+            return Ok(None);
+        }
+        Ok(Some(version))
+    }
+
+    /// The scores of the members, read with a single batch get
+    async fn get_scores(
+        &self,
+        txn_rc: &Arc<Mutex<Transaction>>,
+        key: &str,
+        members: &[String],
+    ) -> AsyncResult<Vec<Option<f64>>> {
+        let version = match self.get_zset_version(txn_rc, key).await? {
+            Some(version) => version,
+            None => return Ok(vec![None; members.len()]),
+        };
+        let mut txn = txn_rc.lock().await;
+        let data_keys: Vec<Key> = members
+            .iter()
+            .map(|m| KEY_ENCODER.encode_txnkv_zset_data_key(key, m, version))
+            .collect();
+        let scores = txn
+            .batch_get(data_keys.clone())
+            .await?
+            .map(|kv| kv.into())
+            .collect::<HashMap<Key, Value>>();
+        Ok(data_keys
+            .iter()
+            .map(|data_key| {
+                scores
+                    .get(data_key)
+                    .map(|value| KeyDecoder::decode_key_zset_data_value(value))
+            })
+            .collect())
+    }
+}
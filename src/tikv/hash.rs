@@ -13,16 +13,22 @@ use crate::{
     Frame,
 };
 
-use futures::{future::FutureExt, stream, StreamExt};
+use super::backend::Transaction;
+use super::index::{remove_key_index, update_field_index};
+use futures::{future::FutureExt, StreamExt};
 use slog::debug;
 use std::{collections::HashMap, convert::TryInto, ops::Range, sync::Arc};
-use tikv_client::{BoundRange, Key, KvPair, Transaction, Value};
+use tikv_client::{BoundRange, Key, KvPair, Value};
 use tokio::sync::Mutex;
 
 use super::errors::*;
-use crate::utils::{resp_array, resp_bulk, resp_err, resp_int, resp_nil};
+use super::expiration::count_expired_key;
+use crate::utils::{
+    expire_timestamp_of_new_key, resp_array, resp_bulk, resp_err, resp_int, resp_nil, ReplyBuilder,
+};
 
-use crate::metrics::REMOVED_EXPIRED_KEY_COUNTER;
+use crate::cmdlog::ship_expired;
+use crate::triggers::{fire, EVENT_EXPIRED};
 
 #[derive(Clone)]
 pub struct HashCommandCtx {
@@ -155,6 +161,14 @@ impl<'a> HashCommandCtx {
                                     &String::from_utf8_lossy(&field),
                                     version,
                                 );
+                                update_field_index(
+                                    &mut txn,
+                                    &key,
+                                    &String::from_utf8_lossy(&field),
+                                    datakey.clone(),
+                                    Some(&kv.1),
+                                )
+                                .await?;
                                 txn.put(datakey, kv.1).await?;
                             }
 
@@ -190,7 +204,7 @@ impl<'a> HashCommandCtx {
                             txn = txn_rc.lock().await;
 
                             // not exists
-                            let ttl = 0;
+                            let ttl = expire_timestamp_of_new_key(0);
                             let mut fields_data_key = vec![];
                             for kv in fvs_copy.clone() {
                                 let field: Vec<u8> = kv.0.into();
@@ -210,6 +224,14 @@ impl<'a> HashCommandCtx {
                                     &String::from_utf8_lossy(&field),
                                     version,
                                 );
+                                update_field_index(
+                                    &mut txn,
+                                    &key,
+                                    &String::from_utf8_lossy(&field),
+                                    datakey.clone(),
+                                    Some(&kv.1),
+                                )
+                                .await?;
                                 txn.put(datakey, kv.1).await?;
                             }
 
@@ -540,39 +562,23 @@ impl<'a> HashCommandCtx {
                             ..KEY_ENCODER.encode_txnkv_hash_data_key_end(&key, version);
                         let bound_range: BoundRange = range.into();
                         // scan return iterator
-                        let iter = txn.scan_stream(bound_range, u32::MAX).await?;
-
-                        let resp: Vec<Frame>;
-                        if with_field && with_value {
-                            resp = iter
-                                .flat_map(|kv| {
-                                    let field: Vec<u8> =
-                                        KeyDecoder::decode_key_hash_userkey_from_datakey(
-                                            &key, kv.0,
-                                        );
-                                    stream::iter([resp_bulk(field), resp_bulk(kv.1)])
-                                })
-                                .collect()
-                                .await;
-                        } else if with_field {
-                            resp = iter
-                                .flat_map(|kv| {
-                                    let field: Vec<u8> =
-                                        KeyDecoder::decode_key_hash_userkey_from_datakey(
-                                            &key, kv.0,
-                                        );
-                                    stream::iter([resp_bulk(field)])
-                                })
-                                .collect()
-                                .await;
-                        } else {
-                            resp = iter
-                                .flat_map(|kv| stream::iter([resp_bulk(kv.1)]))
-                                .collect()
-                                .await;
+                        let mut iter = txn.scan_stream(bound_range, u32::MAX).await?;
+
+                        // the fields follow the prefix of the data keys, the values
+                        // are owned already
+                        let prefix_len = KeyDecoder::decode_data_key_prefix_len(&key);
+                        let mut reply = ReplyBuilder::new();
+                        while let Some(kv) = iter.next().await {
+                            if with_field {
+                                let data_key: Vec<u8> = kv.0.into();
+                                reply.push_bulk(&data_key[prefix_len..]);
+                            }
+                            if with_value || !with_field {
+                                reply.push(resp_bulk(kv.1));
+                            }
                         }
 
-                        Ok(resp_array(resp))
+                        Ok(reply.build())
                     } else {
                         Ok(resp_array(vec![]))
                     }
@@ -619,6 +625,10 @@ impl<'a> HashCommandCtx {
                                     KEY_ENCODER.encode_txnkv_hash_data_key(&key, field, version)
                                 })
                                 .collect();
+                            for (field, data_key) in fields.iter().zip(&data_keys) {
+                                update_field_index(&mut txn, &key, field, data_key.clone(), None)
+                                    .await?;
+                            }
                             for pair in txn.batch_get(data_keys).await? {
                                 txn.delete(pair.0).await?;
                                 deleted += 1;
@@ -762,8 +772,11 @@ impl<'a> HashCommandCtx {
                             prev_int = 0;
                             // create new meta key first
                             let meta_size = config_meta_key_number_or_default();
-                            let meta_value =
-                                KEY_ENCODER.encode_txnkv_hash_meta_value(0, version, meta_size);
+                            let meta_value = KEY_ENCODER.encode_txnkv_hash_meta_value(
+                                expire_timestamp_of_new_key(0),
+                                version,
+                                meta_size,
+                            );
                             txn.put(meta_key, meta_value).await?;
 
                             // add a sub meta key with a random index
@@ -775,9 +788,11 @@ impl<'a> HashCommandCtx {
                         }
                     }
                     let new_int = prev_int + step;
-                    // update data key
-                    txn.put(data_key, new_int.to_string().as_bytes().to_vec())
+                    let new_value = new_int.to_string().as_bytes().to_vec();
+                    update_field_index(&mut txn, &key, &field, data_key.clone(), Some(&new_value))
                         .await?;
+                    // update data key
+                    txn.put(data_key, new_value).await?;
 
                     Ok(new_int)
                 }
@@ -810,6 +825,7 @@ impl<'a> HashCommandCtx {
                             drop(txn);
                             let meta_size = self.txnkv_sum_key_size(&key, version).await?;
                             txn = txn_arc.lock().await;
+                            remove_key_index(&mut txn, &key, version).await?;
 
                             if meta_size > async_del_hash_threshold_or_default() as i64 {
                                 // do async del
@@ -858,9 +874,10 @@ impl<'a> HashCommandCtx {
     pub async fn do_async_txnkv_hash_expire_if_needed(mut self, key: &str) -> AsyncResult<i64> {
         let mut client = get_txn_client()?;
         let key = key.to_owned();
+        let expired_key = key.clone();
         let meta_key = KEY_ENCODER.encode_txnkv_meta_key(&key);
 
-        client
+        let expired = client
             .exec_in_txn(self.txn.clone(), |txn_arc| {
                 async move {
                     if self.txn.is_none() {
@@ -872,11 +889,12 @@ impl<'a> HashCommandCtx {
                             let (ttl, version, _meta_size) =
                                 KeyDecoder::decode_key_meta(&meta_value);
                             if !key_is_expired(ttl) {
-                                return Ok(0);
+                                return Ok(None);
                             }
                             drop(txn);
                             let meta_size = self.txnkv_sum_key_size(&key, version).await?;
                             txn = txn_arc.lock().await;
+                            remove_key_index(&mut txn, &key, version).await?;
 
                             if meta_size > async_expire_hash_threshold_or_default() as i64 {
                                 // do async del
@@ -912,16 +930,22 @@ impl<'a> HashCommandCtx {
 
                                 txn.delete(meta_key).await?;
                             }
-                            REMOVED_EXPIRED_KEY_COUNTER
-                                .with_label_values(&["hash"])
-                                .inc();
-                            Ok(1)
+                            count_expired_key("hash");
+                            Ok(Some(ttl))
                         }
-                        None => Ok(0),
+                        None => Ok(None),
                     }
                 }
                 .boxed()
             })
-            .await
+            .await?;
+        match expired {
+            Some(expire_at) => {
+                fire(EVENT_EXPIRED, &expired_key).await;
+                ship_expired(DataType::Hash, &expired_key, expire_at).await;
+                Ok(1)
+            }
+            None => Ok(0),
+        }
     }
 }
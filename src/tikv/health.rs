@@ -0,0 +1,152 @@
+use std::sync::RwLock;
+use std::time::Duration;
+
+use slog::warn;
+use tokio::time::{self, Instant, MissedTickBehavior};
+
+use super::{get_txn_client, KEY_ENCODER};
+use crate::backend_health_check_interval_or_default;
+use crate::config::LOGGER;
+use crate::metrics::{BACKEND_PROBE_DURATION, BACKEND_UP_GAUGER, TXN_RETRY_COUNTER};
+use crate::server::duration_to_sec;
+
+/// Backend state observed by the health prober.
+#[derive(Debug, Clone, Default)]
+pub struct BackendHealth {
+    pub pd_reachable: bool,
+    pub tikv_reachable: bool,
+    pub pd_probe_ms: u64,
+    pub tikv_probe_ms: u64,
+    /// transaction retries (region errors, lock backoff) since last probe
+    pub recent_txn_retries: u64,
+    pub consecutive_failures: u64,
+    pub last_error: String,
+}
+
+impl BackendHealth {
+    pub fn is_ready(&self) -> bool {
+        self.pd_reachable && self.tikv_reachable
+    }
+}
+
+lazy_static! {
+    static ref BACKEND_HEALTH: RwLock<BackendHealth> = RwLock::new(BackendHealth::default());
+}
+
+pub fn backend_health() -> BackendHealth {
+    BACKEND_HEALTH.read().unwrap().clone()
+}
+
+/// Probe PD and TiKV, each of them failing if it does not answer within
+/// `timeout`, so a hung backend is reported instead of stalling the prober.
+async fn probe(state: &mut BackendHealth, timeout: Duration) {
+    let client = match get_txn_client() {
+        Ok(c) => c,
+        Err(e) => {
+            state.pd_reachable = false;
+            state.tikv_reachable = false;
+            state.last_error = e.to_string();
+            return;
+        }
+    };
+
+    // PD liveness, fetch a tso
+    let start_at = Instant::now();
+    let pd_result = time::timeout(timeout, client.current_timestamp()).await;
+    let duration = Instant::now() - start_at;
+    BACKEND_PROBE_DURATION
+        .with_label_values(&["pd"])
+        .observe(duration_to_sec(duration));
+    state.pd_probe_ms = duration.as_millis() as u64;
+    state.pd_reachable = match pd_result {
+        Ok(Ok(_)) => true,
+        Ok(Err(e)) => {
+            state.last_error = format!("pd: {}", e);
+            false
+        }
+        Err(_) => {
+            state.last_error = format!("pd: probe timed out after {:?}", timeout);
+            false
+        }
+    };
+
+    // TiKV read path, point get on the region of this instance
+    let start_at = Instant::now();
+    let mut snapshot = client.begin_with_latest();
+    let tikv_result = time::timeout(
+        timeout,
+        snapshot.get(KEY_ENCODER.encode_txnkv_cluster_topo_start()),
+    )
+    .await;
+    let duration = Instant::now() - start_at;
+    BACKEND_PROBE_DURATION
+        .with_label_values(&["tikv"])
+        .observe(duration_to_sec(duration));
+    state.tikv_probe_ms = duration.as_millis() as u64;
+    state.tikv_reachable = match tikv_result {
+        Ok(Ok(_)) => true,
+        Ok(Err(e)) => {
+            state.last_error = format!("tikv: {}", e);
+            false
+        }
+        Err(_) => {
+            state.last_error = format!("tikv: probe timed out after {:?}", timeout);
+            false
+        }
+    };
+}
+
+/// Probe PD and TiKV periodically, the result is reflected in `INFO BACKEND`,
+/// the `/ready` http endpoint and `tikv_redis_backend_up` metrics.
+pub async fn run_backend_health_checker() {
+    let period = Duration::from_millis(backend_health_check_interval_or_default());
+    let mut interval = time::interval(period);
+    interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+    let mut last_retries = TXN_RETRY_COUNTER.get();
+    loop {
+        interval.tick().await;
+
+        let mut state = backend_health();
+        probe(&mut state, period).await;
+
+        let retries = TXN_RETRY_COUNTER.get();
+        state.recent_txn_retries = retries - last_retries;
+        last_retries = retries;
+
+        if state.is_ready() {
+            state.consecutive_failures = 0;
+        } else {
+            state.consecutive_failures += 1;
+            warn!(
+                LOGGER,
+                "backend unhealthy for {} checks, {}", state.consecutive_failures, state.last_error
+            );
+        }
+        BACKEND_UP_GAUGER
+            .with_label_values(&["pd"])
+            .set(state.pd_reachable as i64);
+        BACKEND_UP_GAUGER
+            .with_label_values(&["tikv"])
+            .set(state.tikv_reachable as i64);
+
+        *BACKEND_HEALTH.write().unwrap() = state;
+    }
+}
+
+/// Encode the `INFO BACKEND` section
+pub fn encode_backend_info() -> String {
+    let state = backend_health();
+    format!(
+        "# Backend\r\nbackend_ready:{}\r\npd_reachable:{}\r\ntikv_reachable:{}\r\n\
+        pd_probe_ms:{}\r\ntikv_probe_ms:{}\r\nrecent_txn_retries:{}\r\n\
+        consecutive_failures:{}\r\nlast_error:{}\r\n",
+        state.is_ready() as u8,
+        state.pd_reachable as u8,
+        state.tikv_reachable as u8,
+        state.pd_probe_ms,
+        state.tikv_probe_ms,
+        state.recent_txn_retries,
+        state.consecutive_failures,
+        state.last_error
+    )
+}
@@ -0,0 +1,126 @@
+//! Idempotency tokens of write commands.
+//!
+//! The token of a write is recorded in the transaction applying it, together
+//! with the reply, so a retry carrying an already committed token is answered
+//! with the recorded reply instead of being applied twice. Records expire after
+//! `backend.idempotency_token_ttl_ms` and are swept by the GC master.
+
+use std::any::Any;
+use std::convert::TryInto;
+
+use bytes::Bytes;
+use futures::StreamExt;
+use tikv_client::{Key, Value};
+
+use super::backend::Transaction;
+use super::errors::AsyncResult;
+use super::{get_txn_client, KEY_ENCODER};
+use crate::utils::{now_timestamp_in_millis, timestamp_from_ttl};
+use crate::{backend_idempotency_token_ttl_ms_or_default, Frame};
+
+const REPLY_SIMPLE: u8 = b'+';
+const REPLY_ERROR: u8 = b'-';
+const REPLY_INTEGER: u8 = b':';
+const REPLY_BULK: u8 = b'$';
+const REPLY_NULL: u8 = b'_';
+// the reply could not be recorded, e.g. an array
+const REPLY_UNRECORDED: u8 = b'?';
+
+pub fn token_key(token: &str) -> Key {
+    KEY_ENCODER.encode_txnkv_idempotency_key(token)
+}
+
+/// Encode the record of an applied write: expire timestamp followed by the
+/// reply if it is a scalar frame.
+pub fn encode_record(reply: &dyn Any) -> Value {
+    let expire_at = timestamp_from_ttl(backend_idempotency_token_ttl_ms_or_default());
+    let mut value = expire_at.to_be_bytes().to_vec();
+    match reply.downcast_ref::<Frame>() {
+        Some(Frame::Simple(s)) => {
+            value.push(REPLY_SIMPLE);
+            value.extend_from_slice(s.as_bytes());
+        }
+        Some(Frame::ErrorOwned(s)) => {
+            value.push(REPLY_ERROR);
+            value.extend_from_slice(s.as_bytes());
+        }
+        Some(Frame::ErrorString(s)) => {
+            value.push(REPLY_ERROR);
+            value.extend_from_slice(s.as_bytes());
+        }
+        Some(Frame::Integer(i)) => {
+            value.push(REPLY_INTEGER);
+            value.extend_from_slice(&i.to_be_bytes());
+        }
+        Some(Frame::Bulk(b)) => {
+            value.push(REPLY_BULK);
+            value.extend_from_slice(b);
+        }
+        Some(Frame::Null) => value.push(REPLY_NULL),
+        _ => value.push(REPLY_UNRECORDED),
+    }
+    value
+}
+
+fn record_expired(value: &[u8], now: u64) -> bool {
+    value.len() < 9 || u64::from_be_bytes(value[..8].try_into().unwrap()) <= now
+}
+
+/// Decode the recorded reply, `None` if the record is expired. The inner
+/// `None` means the write was applied but its reply was not recorded.
+pub fn decode_record(value: &[u8]) -> Option<Option<Frame>> {
+    if record_expired(value, now_timestamp_in_millis()) {
+        return None;
+    }
+    let payload = &value[9..];
+    let reply = match value[8] {
+        REPLY_SIMPLE => Frame::Simple(String::from_utf8_lossy(payload).to_string()),
+        REPLY_ERROR => Frame::ErrorOwned(String::from_utf8_lossy(payload).to_string()),
+        REPLY_INTEGER if payload.len() == 8 => {
+            Frame::Integer(i64::from_be_bytes(payload.try_into().unwrap()))
+        }
+        REPLY_BULK => Frame::Bulk(Bytes::copy_from_slice(payload)),
+        REPLY_NULL => Frame::Null,
+        _ => return Some(None),
+    };
+    Some(Some(reply))
+}
+
+/// Look up the token in the transaction, returns the recorded reply if the
+/// write was already applied and the record is not expired.
+pub async fn recorded_reply(
+    txn: &mut Transaction,
+    token: &str,
+) -> AsyncResult<Option<Option<Frame>>> {
+    match txn.get(token_key(token)).await? {
+        Some(value) => Ok(decode_record(&value)),
+        None => Ok(None),
+    }
+}
+
+/// Delete at most `max_deletes` expired token records, 0 for all of them,
+/// returns the number deleted.
+pub async fn sweep_expired_tokens(max_deletes: usize) -> AsyncResult<usize> {
+    let client = get_txn_client()?;
+    let mut txn = client.begin().await?;
+    let now = now_timestamp_in_millis();
+    let mut expired = vec![];
+    {
+        let mut iter = txn
+            .scan_stream(KEY_ENCODER.encode_txnkv_idempotency_key_range(), u32::MAX)
+            .await?;
+        while let Some(kv) = iter.next().await {
+            if record_expired(&kv.1, now) {
+                expired.push(kv.0);
+                if expired.len() == max_deletes {
+                    break;
+                }
+            }
+        }
+    }
+    for key in &expired {
+        txn.delete(key.clone()).await?;
+    }
+    txn.commit().await?;
+    Ok(expired.len())
+}
@@ -0,0 +1,163 @@
+//! Secondary indexes of hash fields looked up with HINDEX LOOKUP.
+//!
+//! An index is declared in the config for the hashes with keys starting with
+//! a prefix and one of their fields. Each write of an indexed field replaces
+//! the index entry of its old value with one of its new value in the same
+//! transaction, and deleting the field or the hash removes the entry. An
+//! entry is a system key made of the prefix, field, value and user key, so
+//! the keys with a value are a single range scan. Lookups still check each
+//! key against its hash, hashes written before the index was declared are
+//! only indexed once the field is written again.
+
+use std::sync::Arc;
+
+use futures::future::FutureExt;
+use tikv_client::Key;
+use tokio::sync::Mutex;
+
+use super::backend::Transaction;
+use super::encoding::{DataType, KeyDecoder};
+use super::errors::{AsyncResult, REDIS_NO_HASH_INDEX_ERR};
+use super::{get_txn_client, KEY_ENCODER};
+use crate::config::HashIndexRule;
+use crate::config_hash_indexes_or_default;
+use crate::utils::{key_is_expired, resp_array, resp_bulk, resp_err};
+use crate::Frame;
+
+/// The index rules of `field` covering `key`
+fn field_rules(key: &str, field: &str) -> Vec<HashIndexRule> {
+    config_hash_indexes_or_default()
+        .into_iter()
+        .filter(|rule| rule.field == field && key.starts_with(&rule.prefix))
+        .collect()
+}
+
+/// Move the index entries of `key` to the `new` value of `field` stored at
+/// `data_key`, None if the field is deleted. The old value is only read for
+/// indexed fields.
+pub async fn update_field_index(
+    txn: &mut Transaction,
+    key: &str,
+    field: &str,
+    data_key: Key,
+    new: Option<&[u8]>,
+) -> AsyncResult<()> {
+    let rules = field_rules(key, field);
+    if rules.is_empty() {
+        return Ok(());
+    }
+    let old = txn.get(data_key).await?;
+    if old.as_deref() == new {
+        return Ok(());
+    }
+    for rule in rules {
+        if let Some(old) = &old {
+            let index_key = KEY_ENCODER.encode_txnkv_hash_index_key(&rule.prefix, field, old, key);
+            txn.delete(index_key).await?;
+        }
+        if let Some(new) = new {
+            let index_key = KEY_ENCODER.encode_txnkv_hash_index_key(&rule.prefix, field, new, key);
+            txn.put(index_key, KEY_ENCODER.encode_txnkv_hash_index_entry_value())
+                .await?;
+        }
+    }
+    Ok(())
+}
+
+/// Remove the index entries of all indexed fields of the hash `key` with
+/// `version` before it is deleted
+pub async fn remove_key_index(txn: &mut Transaction, key: &str, version: u16) -> AsyncResult<()> {
+    for rule in config_hash_indexes_or_default() {
+        if !key.starts_with(&rule.prefix) {
+            continue;
+        }
+        let data_key = KEY_ENCODER.encode_txnkv_hash_data_key(key, &rule.field, version);
+        if let Some(value) = txn.get(data_key).await? {
+            let index_key =
+                KEY_ENCODER.encode_txnkv_hash_index_key(&rule.prefix, &rule.field, &value, key);
+            txn.delete(index_key).await?;
+        }
+    }
+    Ok(())
+}
+
+#[derive(Clone)]
+pub struct IndexCommandCtx {
+    txn: Option<Arc<Mutex<Transaction>>>,
+}
+
+impl IndexCommandCtx {
+    pub fn new(txn: Option<Arc<Mutex<Transaction>>>) -> Self {
+        IndexCommandCtx { txn }
+    }
+
+    /// The keys with `value` in `field` among the hashes of `prefix`, at most
+    /// `count` of them if not 0. Entries left by a key deleted or rewritten
+    /// another way are skipped.
+    pub async fn do_async_txnkv_hindex_lookup(
+        mut self,
+        prefix: &str,
+        field: &str,
+        value: &[u8],
+        count: usize,
+    ) -> AsyncResult<Frame> {
+        let rule = HashIndexRule {
+            prefix: prefix.to_owned(),
+            field: field.to_owned(),
+        };
+        if !config_hash_indexes_or_default().contains(&rule) {
+            return Ok(resp_err(REDIS_NO_HASH_INDEX_ERR));
+        }
+
+        let mut client = get_txn_client()?;
+        let value = value.to_vec();
+
+        // like HGET, a read from a new transaction is done with the latest commit
+        if self.txn.is_none() {
+            let readonly_txn = client.begin_consistent_read().await?;
+            self.txn = Some(Arc::new(Mutex::new(readonly_txn)));
+        }
+
+        client
+            .exec_in_txn(self.txn.clone(), |txn_rc| {
+                async move {
+                    let mut txn = txn_rc.lock().await;
+                    let range = KEY_ENCODER.encode_txnkv_hash_index_range(
+                        &rule.prefix,
+                        &rule.field,
+                        &value,
+                    );
+                    let index_keys = txn.scan_keys(range, u32::MAX).await?;
+
+                    let mut keys = vec![];
+                    for index_key in index_keys {
+                        if count > 0 && keys.len() >= count {
+                            break;
+                        }
+                        let ukey = KeyDecoder::decode_key_userkey_from_indexkey(index_key);
+                        let ukey = String::from_utf8_lossy(&ukey).to_string();
+                        let meta_key = KEY_ENCODER.encode_txnkv_meta_key(&ukey);
+                        let meta_value = match txn.get(meta_key).await? {
+                            Some(meta_value) => meta_value,
+                            None => continue,
+                        };
+                        if !matches!(KeyDecoder::decode_key_type(&meta_value), DataType::Hash) {
+                            continue;
+                        }
+                        let (ttl, version, _) = KeyDecoder::decode_key_meta(&meta_value);
+                        if key_is_expired(ttl) {
+                            continue;
+                        }
+                        let data_key =
+                            KEY_ENCODER.encode_txnkv_hash_data_key(&ukey, &rule.field, version);
+                        if txn.get(data_key).await?.as_deref() == Some(&value[..]) {
+                            keys.push(resp_bulk(ukey.into_bytes()));
+                        }
+                    }
+                    Ok(resp_array(keys))
+                }
+                .boxed()
+            })
+            .await
+    }
+}
@@ -0,0 +1,455 @@
+//! Streaming intersection, union and difference of set and zset keys.
+//!
+//! The data keys of a set or zset are sorted by member, so the members of
+//! each key are scanned as a sorted stream and the streams are merged like a
+//! k-way merge join. The inputs are never materialized, only a page of the
+//! members of each key is kept in memory, and the merge stops as soon as no
+//! more member can be found or the caller has enough members. A scan error
+//! ends the merge with the error, never with a truncated result.
+
+use std::ops::Range;
+use std::sync::Arc;
+
+use futures::stream::{self, BoxStream};
+use futures::{StreamExt, TryStreamExt};
+use tikv_client::{Key, KvPair};
+use tokio::sync::Mutex;
+
+use super::backend::Transaction;
+use super::encoding::{DataType, KeyDecoder};
+use super::errors::{AsyncResult, REDIS_WRONG_TYPE_ERR};
+use super::KEY_ENCODER;
+use crate::utils::key_is_expired;
+
+/// Data keys of a key read by each scan of its member stream
+const MEMBER_SCAN_PAGE_SIZE: u32 = 1024;
+
+/// Member and score of an element, sets have a score of 1
+type Element = (Vec<u8>, f64);
+
+type Source = BoxStream<'static, AsyncResult<Element>>;
+
+/// How the weighted scores of a member in each key are combined
+#[derive(Debug, Clone, Copy)]
+pub enum Aggregate {
+    Sum,
+    Min,
+    Max,
+}
+
+impl Aggregate {
+    pub fn from_name(name: &str) -> Option<Aggregate> {
+        match name.to_uppercase().as_str() {
+            "SUM" => Some(Aggregate::Sum),
+            "MIN" => Some(Aggregate::Min),
+            "MAX" => Some(Aggregate::Max),
+            _ => None,
+        }
+    }
+
+    /// Combine the scores of a member, each multiplied by the weight of its
+    /// key, missing weights are 1
+    pub fn combine(&self, scores: &[f64], weights: &[f64]) -> f64 {
+        let indexed: Vec<(usize, f64)> = scores.iter().copied().enumerate().collect();
+        self.combine_indexed(&indexed, weights)
+    }
+
+    /// Combine the scores of a member found in some of the keys only, each
+    /// with the index of its key
+    pub fn combine_indexed(&self, scores: &[(usize, f64)], weights: &[f64]) -> f64 {
+        let weighted = scores.iter().map(|(idx, score)| {
+            let weighted = score * weights.get(*idx).copied().unwrap_or(1.0);
+            // an infinite score weighted by 0 is 0, like Redis
+            if weighted.is_nan() {
+                0.0
+            } else {
+                weighted
+            }
+        });
+        match self {
+            Aggregate::Sum => weighted.sum(),
+            Aggregate::Min => weighted.fold(f64::INFINITY, f64::min),
+            Aggregate::Max => weighted.fold(f64::NEG_INFINITY, f64::max),
+        }
+    }
+}
+
+/// The set operation of ZINTER, ZUNION and ZDIFF and their STORE variants
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SetOperation {
+    Inter,
+    Union,
+    Diff,
+}
+
+/// Type and version of the set or zset `key`, None if it is missing or
+/// expired
+async fn key_meta(
+    txn: &mut Transaction,
+    key: &str,
+    with_zsets: bool,
+) -> AsyncResult<Option<(DataType, u16)>> {
+    let meta_key = KEY_ENCODER.encode_txnkv_meta_key(key);
+    let meta_value = match txn.get(meta_key).await? {
+        Some(meta_value) => meta_value,
+        None => return Ok(None),
+    };
+    let data_type = KeyDecoder::decode_key_type(&meta_value);
+    match data_type {
+        DataType::Set => {}
+        DataType::Zset if with_zsets => {}
+        _ => return Err(REDIS_WRONG_TYPE_ERR),
+    }
+    let (ttl, version, _) = KeyDecoder::decode_key_meta(&meta_value);
+    if key_is_expired(ttl) {
+        return Ok(None);
+    }
+    Ok(Some((data_type, version)))
+}
+
+/// Read the page of the members of the set or zset `key` starting at
+/// `start`, with the start of the next page if the data keys are not all
+/// read. None once all the pages are read.
+async fn member_page(
+    txn: Arc<Mutex<Transaction>>,
+    key: String,
+    is_set: bool,
+    version: u16,
+    start: Option<Key>,
+) -> AsyncResult<Option<(Vec<Element>, Option<Key>)>> {
+    let start = match start {
+        Some(start) => start,
+        None => return Ok(None),
+    };
+    let end = if is_set {
+        KEY_ENCODER.encode_txnkv_set_data_key_end(&key, version)
+    } else {
+        KEY_ENCODER.encode_txnkv_zset_data_key_end(&key, version)
+    };
+    let range: Range<Key> = start..end;
+    let kvs: Vec<KvPair> = txn
+        .lock()
+        .await
+        .scan(range, MEMBER_SCAN_PAGE_SIZE)
+        .await?
+        .collect();
+
+    let mut next = None;
+    if kvs.len() as u32 >= MEMBER_SCAN_PAGE_SIZE {
+        if let Some(kv) = kvs.last() {
+            // the data key just after the last one read
+            let mut after: Vec<u8> = kv.0.clone().into();
+            after.push(0);
+            next = Some(Key::from(after));
+        }
+    }
+    let page = kvs
+        .into_iter()
+        .map(|kv| {
+            if is_set {
+                (
+                    KeyDecoder::decode_key_set_member_from_datakey(&key, kv.0),
+                    1.0,
+                )
+            } else {
+                let score = KeyDecoder::decode_key_zset_data_value(&kv.1);
+                (
+                    KeyDecoder::decode_key_zset_member_from_datakey(&key, kv.0),
+                    score,
+                )
+            }
+        })
+        .collect();
+    Ok(Some((page, next)))
+}
+
+/// The members of `key` at `version` sorted by member, read a page at a time
+/// from `txn`. The stream ends with the error of a failed scan.
+fn member_stream(
+    txn: Arc<Mutex<Transaction>>,
+    key: String,
+    data_type: DataType,
+    version: u16,
+) -> Source {
+    let is_set = matches!(data_type, DataType::Set);
+    let start = if is_set {
+        KEY_ENCODER.encode_txnkv_set_data_key_start(&key, version)
+    } else {
+        KEY_ENCODER.encode_txnkv_zset_data_key_start(&key, version)
+    };
+    stream::try_unfold(Some(start), move |start| {
+        member_page(txn.clone(), key.clone(), is_set, version, start)
+    })
+    .map_ok(|page| stream::iter(page.into_iter().map(Ok)))
+    .try_flatten()
+    .boxed()
+}
+
+/// Open the member streams of the existing `keys` with the index of their
+/// key, the types of all the keys are checked before any is scanned
+async fn open_sources(
+    txn_rc: &Arc<Mutex<Transaction>>,
+    keys: &[String],
+    with_zsets: bool,
+) -> AsyncResult<Vec<(usize, Source)>> {
+    let mut txn = txn_rc.lock().await;
+    let mut sources = Vec::with_capacity(keys.len());
+    for (idx, key) in keys.iter().enumerate() {
+        if let Some((data_type, version)) = key_meta(&mut txn, key, with_zsets).await? {
+            let source = member_stream(txn_rc.clone(), key.clone(), data_type, version);
+            sources.push((idx, source));
+        }
+    }
+    Ok(sources)
+}
+
+pub struct Intersection {
+    sources: Vec<Source>,
+    heads: Vec<Element>,
+    done: bool,
+}
+
+impl Intersection {
+    /// Open the member streams of `keys` in `txn_rc`, the keys must be sets
+    /// or also zsets if `with_zsets`. A missing or expired key makes the
+    /// intersection empty without scanning the others.
+    pub async fn open(
+        txn_rc: &Arc<Mutex<Transaction>>,
+        keys: &[String],
+        with_zsets: bool,
+    ) -> AsyncResult<Intersection> {
+        let mut txn = txn_rc.lock().await;
+        let mut sources = Vec::with_capacity(keys.len());
+        for key in keys {
+            match key_meta(&mut txn, key, with_zsets).await? {
+                Some((data_type, version)) => sources.push(member_stream(
+                    txn_rc.clone(),
+                    key.clone(),
+                    data_type,
+                    version,
+                )),
+                None => return Ok(Intersection::empty()),
+            }
+        }
+        Ok(Intersection {
+            done: sources.is_empty(),
+            sources,
+            heads: vec![],
+        })
+    }
+
+    fn empty() -> Intersection {
+        Intersection {
+            sources: vec![],
+            heads: vec![],
+            done: true,
+        }
+    }
+
+    /// Returns the next member in all the keys with its score in each key,
+    /// None once a stream is exhausted.
+    pub async fn next(&mut self) -> AsyncResult<Option<(Vec<u8>, Vec<f64>)>> {
+        if self.done {
+            return Ok(None);
+        }
+        if self.heads.is_empty() {
+            for source in self.sources.iter_mut() {
+                match source.try_next().await? {
+                    Some(element) => self.heads.push(element),
+                    None => {
+                        self.done = true;
+                        return Ok(None);
+                    }
+                }
+            }
+        }
+
+        loop {
+            let max = self.heads.iter().map(|h| &h.0).max().unwrap().clone();
+            let mut all_equal = true;
+            for (head, source) in self.heads.iter_mut().zip(self.sources.iter_mut()) {
+                while head.0 < max {
+                    match source.try_next().await? {
+                        Some(element) => *head = element,
+                        None => {
+                            self.done = true;
+                            return Ok(None);
+                        }
+                    }
+                }
+                if head.0 != max {
+                    all_equal = false;
+                }
+            }
+            if !all_equal {
+                continue;
+            }
+
+            let scores = self.heads.iter().map(|h| h.1).collect();
+            for (head, source) in self.heads.iter_mut().zip(self.sources.iter_mut()) {
+                match source.try_next().await? {
+                    Some(element) => *head = element,
+                    None => self.done = true,
+                }
+            }
+            return Ok(Some((max, scores)));
+        }
+    }
+
+    /// Count the members in all the keys, stops at `limit` if not 0
+    pub async fn count(&mut self, limit: usize) -> AsyncResult<usize> {
+        let mut count = 0;
+        while limit == 0 || count < limit {
+            if self.next().await?.is_none() {
+                break;
+            }
+            count += 1;
+        }
+        Ok(count)
+    }
+}
+
+/// Members in any of the keys, a missing or expired key is empty
+pub struct Union {
+    sources: Vec<(usize, Source)>,
+    /// The head of each source, None once exhausted
+    heads: Vec<Option<Element>>,
+    started: bool,
+}
+
+impl Union {
+    pub async fn open(txn_rc: &Arc<Mutex<Transaction>>, keys: &[String]) -> AsyncResult<Union> {
+        Ok(Union {
+            sources: open_sources(txn_rc, keys, true).await?,
+            heads: vec![],
+            started: false,
+        })
+    }
+
+    /// Returns the next member in any of the keys with its score in the keys
+    /// holding it, each with the index of its key, None once all the streams
+    /// are exhausted.
+    pub async fn next(&mut self) -> AsyncResult<Option<(Vec<u8>, Vec<(usize, f64)>)>> {
+        if !self.started {
+            self.started = true;
+            for (_, source) in self.sources.iter_mut() {
+                self.heads.push(source.try_next().await?);
+            }
+        }
+
+        let min = match self.heads.iter().flatten().map(|h| &h.0).min() {
+            Some(min) => min.clone(),
+            None => return Ok(None),
+        };
+        let mut scores = vec![];
+        for (head, (idx, source)) in self.heads.iter_mut().zip(self.sources.iter_mut()) {
+            if matches!(head, Some(h) if h.0 == min) {
+                scores.push((*idx, head.as_ref().unwrap().1));
+                *head = source.try_next().await?;
+            }
+        }
+        Ok(Some((min, scores)))
+    }
+}
+
+/// Members of the first key not in any of the others, a missing or expired
+/// key is empty
+pub struct Difference {
+    first: Option<Source>,
+    others: Vec<Source>,
+    /// The head of each of the others, None once exhausted
+    heads: Vec<Option<Element>>,
+    started: bool,
+}
+
+impl Difference {
+    pub async fn open(
+        txn_rc: &Arc<Mutex<Transaction>>,
+        keys: &[String],
+    ) -> AsyncResult<Difference> {
+        let mut sources = open_sources(txn_rc, keys, true).await?;
+        let first = match sources.first() {
+            Some((0, _)) => Some(sources.remove(0).1),
+            _ => None,
+        };
+        Ok(Difference {
+            first,
+            others: sources.into_iter().map(|(_, source)| source).collect(),
+            heads: vec![],
+            started: false,
+        })
+    }
+
+    /// Returns the next member of the first key in none of the others with
+    /// its score in the first key, None once the first stream is exhausted.
+    pub async fn next(&mut self) -> AsyncResult<Option<Element>> {
+        let first = match self.first.as_mut() {
+            Some(first) => first,
+            None => return Ok(None),
+        };
+        if !self.started {
+            self.started = true;
+            for source in self.others.iter_mut() {
+                self.heads.push(source.try_next().await?);
+            }
+        }
+
+        'members: while let Some(element) = first.try_next().await? {
+            for (head, source) in self.heads.iter_mut().zip(self.others.iter_mut()) {
+                while matches!(head, Some(h) if h.0 < element.0) {
+                    *head = source.try_next().await?;
+                }
+                if matches!(head, Some(h) if h.0 == element.0) {
+                    continue 'members;
+                }
+            }
+            return Ok(Some(element));
+        }
+        Ok(None)
+    }
+}
+
+/// The members of a set operation on zsets and sets, with their score
+/// aggregated from the keys holding them
+pub enum Combination {
+    Inter(Intersection),
+    Union(Union),
+    Diff(Difference),
+}
+
+impl Combination {
+    pub async fn open(
+        txn_rc: &Arc<Mutex<Transaction>>,
+        operation: SetOperation,
+        keys: &[String],
+    ) -> AsyncResult<Combination> {
+        Ok(match operation {
+            SetOperation::Inter => {
+                Combination::Inter(Intersection::open(txn_rc, keys, true).await?)
+            }
+            SetOperation::Union => Combination::Union(Union::open(txn_rc, keys).await?),
+            SetOperation::Diff => Combination::Diff(Difference::open(txn_rc, keys).await?),
+        })
+    }
+
+    /// Returns the next member sorted by member with its score, the weighted
+    /// scores are aggregated for an intersection or an union, a difference
+    /// keeps the score of the first key.
+    pub async fn next(
+        &mut self,
+        weights: &[f64],
+        aggregate: Aggregate,
+    ) -> AsyncResult<Option<Element>> {
+        Ok(match self {
+            Combination::Inter(inter) => inter
+                .next()
+                .await?
+                .map(|(member, scores)| (member, aggregate.combine(&scores, weights))),
+            Combination::Union(union) => union
+                .next()
+                .await?
+                .map(|(member, scores)| (member, aggregate.combine_indexed(&scores, weights))),
+            Combination::Diff(diff) => diff.next().await?,
+        })
+    }
+}
@@ -0,0 +1,241 @@
+//! JSON documents written with JSON.SET and read with JSON.GET and JSON.DEL.
+//!
+//! A document is kept whole in the value of its key like a string, with its
+//! own type so the string commands refuse it, and is parsed on each command
+//! to run the paths against it.
+
+use super::backend::Transaction;
+use super::errors::*;
+use super::get_txn_client;
+use super::string::StringCommandCtx;
+use super::KEY_ENCODER;
+use super::{
+    encoding::{DataType, KeyDecoder},
+    errors::AsyncResult,
+};
+use crate::jsonpath::{JsonPath, SetMode};
+use crate::utils::{expire_timestamp_of_new_key, resp_bulk, resp_err, resp_int, resp_nil, resp_ok};
+use crate::{utils::key_is_expired, Frame};
+use futures::future::FutureExt;
+use serde_json::{Map, Value};
+use std::sync::Arc;
+use tokio::sync::Mutex;
+
+#[derive(Clone)]
+pub struct JsonCommandCtx {
+    txn: Option<Arc<Mutex<Transaction>>>,
+}
+
+impl JsonCommandCtx {
+    pub fn new(txn: Option<Arc<Mutex<Transaction>>>) -> Self {
+        JsonCommandCtx { txn }
+    }
+
+    /// Set `value` at `path` of the document, a new document is only created
+    /// at the root. Nil if NX or XX left the document unchanged.
+    pub async fn do_async_txnkv_json_set(
+        mut self,
+        key: &str,
+        path: JsonPath,
+        value: Value,
+        mode: SetMode,
+    ) -> AsyncResult<Frame> {
+        let mut client = get_txn_client()?;
+        let key = key.to_owned();
+        let ekey = KEY_ENCODER.encode_txnkv_string(&key);
+
+        let resp = client
+            .exec_in_txn(self.txn.clone(), |txn_rc| {
+                async move {
+                    if self.txn.is_none() {
+                        self.txn = Some(txn_rc.clone());
+                    }
+                    let mut txn = txn_rc.lock().await;
+                    let mut doc = None;
+                    if let Some(meta_value) = txn.get(ekey.clone()).await? {
+                        if !matches!(KeyDecoder::decode_key_type(&meta_value), DataType::Json) {
+                            return Err(REDIS_WRONG_TYPE_ERR);
+                        }
+                        let ttl = KeyDecoder::decode_key_ttl(&meta_value);
+                        if key_is_expired(ttl) {
+                            drop(txn);
+                            StringCommandCtx::new(self.txn.clone())
+                                .do_async_txnkv_string_expire_if_needed(&key)
+                                .await?;
+                            txn = txn_rc.lock().await;
+                        } else {
+                            let data = KeyDecoder::decode_key_string_value(&meta_value)?;
+                            doc = Some((serde_json::from_slice::<Value>(&data)?, ttl));
+                        }
+                    }
+
+                    let (doc, ttl) = match doc {
+                        Some((mut doc, ttl)) => {
+                            if path.set(&mut doc, &value, mode) == 0 {
+                                return Ok(false);
+                            }
+                            (doc, ttl)
+                        }
+                        None => {
+                            if !path.is_root() {
+                                return Err(REDIS_JSON_NEW_AT_ROOT_ERR);
+                            }
+                            if mode == SetMode::Existing {
+                                return Ok(false);
+                            }
+                            (value, expire_timestamp_of_new_key(0))
+                        }
+                    };
+                    let eval =
+                        KEY_ENCODER.encode_txnkv_json_value(&serde_json::to_vec(&doc)?, ttl)?;
+                    txn.put(ekey, eval).await?;
+                    Ok(true)
+                }
+                .boxed()
+            })
+            .await;
+
+        match resp {
+            Ok(true) => Ok(resp_ok()),
+            Ok(false) => Ok(resp_nil()),
+            Err(e) => Ok(resp_err(e)),
+        }
+    }
+
+    /// The values at `paths` serialized, keyed by path if more than one path
+    /// is given. A legacy path replies with its value and a JSONPath with the
+    /// array of the values it selects.
+    pub async fn do_async_txnkv_json_get(
+        mut self,
+        key: &str,
+        paths: Vec<(String, JsonPath)>,
+    ) -> AsyncResult<Frame> {
+        let mut client = get_txn_client()?;
+        let key = key.to_owned();
+        let ekey = KEY_ENCODER.encode_txnkv_string(&key);
+
+        // like GET, a read from a new transaction is done with the latest commit
+        if self.txn.is_none() {
+            let readonly_txn = client.begin_consistent_read().await?;
+            self.txn = Some(Arc::new(Mutex::new(readonly_txn)));
+        }
+
+        let resp = client
+            .exec_in_txn(self.txn.clone(), |txn_rc| {
+                async move {
+                    if self.txn.is_none() {
+                        self.txn = Some(txn_rc.clone());
+                    }
+                    let mut txn = txn_rc.lock().await;
+                    let meta_value = match txn.get(ekey).await? {
+                        Some(meta_value) => meta_value,
+                        None => return Ok(resp_nil()),
+                    };
+                    if !matches!(KeyDecoder::decode_key_type(&meta_value), DataType::Json) {
+                        return Err(REDIS_WRONG_TYPE_ERR);
+                    }
+                    let ttl = KeyDecoder::decode_key_ttl(&meta_value);
+                    if key_is_expired(ttl) {
+                        drop(txn);
+                        StringCommandCtx::new(self.txn.clone())
+                            .do_async_txnkv_string_expire_if_needed(&key)
+                            .await?;
+                        return Ok(resp_nil());
+                    }
+                    let data = KeyDecoder::decode_key_string_value(&meta_value)?;
+                    let doc: Value = serde_json::from_slice(&data)?;
+
+                    let reply = if paths.len() == 1 {
+                        path_reply(&doc, &paths[0].0, &paths[0].1)?
+                    } else {
+                        let mut map = Map::new();
+                        for (name, path) in &paths {
+                            map.insert(name.clone(), path_reply(&doc, name, path)?);
+                        }
+                        Value::Object(map)
+                    };
+                    Ok(resp_bulk(serde_json::to_vec(&reply)?))
+                }
+                .boxed()
+            })
+            .await;
+
+        match resp {
+            Ok(frame) => Ok(frame),
+            Err(e) => Ok(resp_err(e)),
+        }
+    }
+
+    /// Delete the values at `path`, the whole key for the root. Replies with
+    /// the number of values deleted.
+    pub async fn do_async_txnkv_json_del(
+        mut self,
+        key: &str,
+        path: JsonPath,
+    ) -> AsyncResult<Frame> {
+        let mut client = get_txn_client()?;
+        let key = key.to_owned();
+        let ekey = KEY_ENCODER.encode_txnkv_string(&key);
+
+        let resp = client
+            .exec_in_txn(self.txn.clone(), |txn_rc| {
+                async move {
+                    if self.txn.is_none() {
+                        self.txn = Some(txn_rc.clone());
+                    }
+                    let mut txn = txn_rc.lock().await;
+                    let meta_value = match txn.get(ekey.clone()).await? {
+                        Some(meta_value) => meta_value,
+                        None => return Ok(0),
+                    };
+                    if !matches!(KeyDecoder::decode_key_type(&meta_value), DataType::Json) {
+                        return Err(REDIS_WRONG_TYPE_ERR);
+                    }
+                    let ttl = KeyDecoder::decode_key_ttl(&meta_value);
+                    if key_is_expired(ttl) {
+                        drop(txn);
+                        StringCommandCtx::new(self.txn.clone())
+                            .do_async_txnkv_string_expire_if_needed(&key)
+                            .await?;
+                        return Ok(0);
+                    }
+                    if path.is_root() {
+                        txn.delete(ekey).await?;
+                        return Ok(1);
+                    }
+
+                    let data = KeyDecoder::decode_key_string_value(&meta_value)?;
+                    let mut doc: Value = serde_json::from_slice(&data)?;
+                    let deleted = path.delete(&mut doc);
+                    if deleted > 0 {
+                        let eval =
+                            KEY_ENCODER.encode_txnkv_json_value(&serde_json::to_vec(&doc)?, ttl)?;
+                        txn.put(ekey, eval).await?;
+                    }
+                    Ok(deleted as i64)
+                }
+                .boxed()
+            })
+            .await;
+
+        match resp {
+            Ok(n) => Ok(resp_int(n)),
+            Err(e) => Ok(resp_err(e)),
+        }
+    }
+}
+
+/// The reply of JSON.GET for one path, a legacy path must select a value
+fn path_reply(doc: &Value, name: &str, path: &JsonPath) -> AsyncResult<Value> {
+    let values = path.get(doc);
+    if !path.is_legacy() {
+        return Ok(Value::Array(values.into_iter().cloned().collect()));
+    }
+    match values.first() {
+        Some(value) => Ok((*value).clone()),
+        None => Err(RTError::Owned(format!(
+            "ERR Path '{}' does not exist",
+            name
+        ))),
+    }
+}
@@ -1,5 +1,7 @@
+use super::backend::Transaction;
 use super::client::get_version_for_new;
 use super::errors::*;
+use super::expiration::count_expired_key;
 use super::get_txn_client;
 use super::KEY_ENCODER;
 use super::{
@@ -9,8 +11,11 @@ use super::{
 use crate::async_del_list_threshold_or_default;
 use crate::cmd_linsert_length_limit_or_default;
 use crate::cmd_lrem_length_limit_or_default;
-use crate::metrics::REMOVED_EXPIRED_KEY_COUNTER;
-use crate::utils::{resp_array, resp_bulk, resp_err, resp_int, resp_nil, resp_ok};
+use crate::cmdlog::ship_expired;
+use crate::triggers::{fire, EVENT_EXPIRED};
+use crate::utils::{
+    expire_timestamp_of_new_key, resp_array, resp_bulk, resp_err, resp_int, resp_nil, resp_ok,
+};
 use crate::{utils::key_is_expired, Frame};
 use bytes::Bytes;
 use core::ops::RangeFrom;
@@ -19,7 +24,7 @@ use futures::future::FutureExt;
 use futures::StreamExt;
 use std::convert::TryInto;
 use std::sync::Arc;
-use tikv_client::{BoundRange, Key, Transaction};
+use tikv_client::{BoundRange, Key};
 use tokio::sync::Mutex;
 
 const INIT_INDEX: u64 = 1 << 32;
@@ -121,8 +126,12 @@ impl<'a> ListCommandCtx {
                             }
 
                             // add meta key
-                            let meta_value =
-                                KEY_ENCODER.encode_txnkv_list_meta_value(0, version, left, right);
+                            let meta_value = KEY_ENCODER.encode_txnkv_list_meta_value(
+                                expire_timestamp_of_new_key(0),
+                                version,
+                                left,
+                                right,
+                            );
                             txn.put(meta_key, meta_value).await?;
 
                             Ok(right - left)
@@ -261,6 +270,61 @@ impl<'a> ListCommandCtx {
         }
     }
 
+    /// Pop `count` elements of the first non empty list of `keys`, probed in
+    /// order in one transaction. Replies the key and the popped elements,
+    /// nil if all the lists are empty.
+    pub async fn do_async_txnkv_mpop(
+        mut self,
+        keys: &[String],
+        op_left: bool,
+        count: i64,
+    ) -> AsyncResult<Frame> {
+        let mut client = get_txn_client()?;
+        let keys = keys.to_owned();
+
+        client
+            .exec_in_txn(self.txn.clone(), |txn_rc| {
+                async move {
+                    if self.txn.is_none() {
+                        self.txn = Some(txn_rc.clone());
+                    }
+                    for key in keys {
+                        let meta_key = KEY_ENCODER.encode_txnkv_meta_key(&key);
+                        let meta_value = txn_rc.lock().await.get(meta_key).await?;
+                        match meta_value {
+                            Some(meta_value) => {
+                                if !matches!(
+                                    KeyDecoder::decode_key_type(&meta_value),
+                                    DataType::List
+                                ) {
+                                    return Ok(resp_err(REDIS_WRONG_TYPE_ERR));
+                                }
+                            }
+                            None => continue,
+                        }
+                        // an expired list pops nothing
+                        let elements = match self
+                            .clone()
+                            .do_async_txnkv_pop(&key, op_left, count)
+                            .await?
+                        {
+                            Frame::Null => continue,
+                            Frame::Array(elements) => elements,
+                            Frame::Bulk(element) => vec![Frame::Bulk(element)],
+                            frame => return Ok(frame),
+                        };
+                        return Ok(resp_array(vec![
+                            resp_bulk(key.into_bytes()),
+                            resp_array(elements),
+                        ]));
+                    }
+                    Ok(resp_nil())
+                }
+                .boxed()
+            })
+            .await
+    }
+
     pub async fn do_async_txnkv_ltrim(
         mut self,
         key: &str,
@@ -544,6 +608,96 @@ impl<'a> ListCommandCtx {
             .await
     }
 
+    /// Indexes of the elements equal to `ele`, from the `rank`th match on,
+    /// from the tail with a negative `rank`. At most `count` indexes are
+    /// replied, all of them with 0, or the first index alone without
+    /// `count`. Only `maxlen` elements are compared if it is not 0. The
+    /// elements are streamed from the storage and not kept.
+    pub async fn do_async_txnkv_lpos(
+        mut self,
+        key: &str,
+        ele: &Bytes,
+        rank: i64,
+        count: Option<usize>,
+        maxlen: usize,
+    ) -> AsyncResult<Frame> {
+        let mut client = get_txn_client()?;
+        let meta_key = KEY_ENCODER.encode_txnkv_meta_key(key);
+        let key = key.to_owned();
+        let ele = ele.to_owned();
+
+        client
+            .exec_in_txn(self.txn.clone(), |txn_rc| {
+                async move {
+                    if self.txn.is_none() {
+                        self.txn = Some(txn_rc.clone());
+                    }
+                    let empty = || match count {
+                        Some(_) => resp_array(vec![]),
+                        None => resp_nil(),
+                    };
+
+                    let mut txn = txn_rc.lock().await;
+                    let meta_value = match txn.get(meta_key).await? {
+                        Some(meta_value) => meta_value,
+                        None => return Ok(empty()),
+                    };
+                    if !matches!(KeyDecoder::decode_key_type(&meta_value), DataType::List) {
+                        return Ok(resp_err(REDIS_WRONG_TYPE_ERR));
+                    }
+                    let (ttl, version, left, _) = KeyDecoder::decode_key_list_meta(&meta_value);
+                    if key_is_expired(ttl) {
+                        drop(txn);
+                        self.clone()
+                            .do_async_txnkv_list_expire_if_needed(&key)
+                            .await?;
+                        return Ok(empty());
+                    }
+
+                    let bound_range = KEY_ENCODER.encode_txnkv_list_data_key_range(&key, version);
+                    let mut iter = if rank > 0 {
+                        txn.scan_stream(bound_range, u32::MAX).await?
+                    } else {
+                        txn.scan_reverse_stream(bound_range, u32::MAX).await?
+                    };
+
+                    let mut skip = rank.unsigned_abs() - 1;
+                    let limit = match count {
+                        Some(0) => usize::MAX,
+                        Some(count) => count,
+                        None => 1,
+                    };
+                    let mut indexes = vec![];
+                    let mut compared = 0;
+                    while let Some(kv) = iter.next().await {
+                        if maxlen > 0 && compared == maxlen {
+                            break;
+                        }
+                        compared += 1;
+                        if kv.1[..] != ele[..] {
+                            continue;
+                        }
+                        if skip > 0 {
+                            skip -= 1;
+                            continue;
+                        }
+                        let idx = KeyDecoder::decode_key_list_idx_from_datakey(&key, kv.0);
+                        indexes.push((idx - left) as i64);
+                        if indexes.len() == limit {
+                            break;
+                        }
+                    }
+
+                    match count {
+                        Some(_) => Ok(resp_array(indexes.into_iter().map(resp_int).collect())),
+                        None => Ok(indexes.first().map_or_else(resp_nil, |idx| resp_int(*idx))),
+                    }
+                }
+                .boxed()
+            })
+            .await
+    }
+
     pub async fn do_async_txnkv_lset(
         mut self,
         key: &str,
@@ -1011,9 +1165,10 @@ impl<'a> ListCommandCtx {
     pub async fn do_async_txnkv_list_expire_if_needed(mut self, key: &str) -> AsyncResult<i64> {
         let mut client = get_txn_client()?;
         let key = key.to_owned();
+        let expired_key = key.clone();
         let meta_key = KEY_ENCODER.encode_txnkv_meta_key(&key);
 
-        client
+        let expired = client
             .exec_in_txn(self.txn.clone(), |txn_rc| {
                 async move {
                     if self.txn.is_none() {
@@ -1026,7 +1181,7 @@ impl<'a> ListCommandCtx {
                             let (ttl, version, left, right) =
                                 KeyDecoder::decode_key_list_meta(&meta_value);
                             if !key_is_expired(ttl) {
-                                return Ok(0);
+                                return Ok(None);
                             }
                             let len = right - left;
                             if len >= async_del_list_threshold_or_default() as u64 {
@@ -1055,16 +1210,22 @@ impl<'a> ListCommandCtx {
                                 txn.delete(meta_key).await?;
                             }
 
-                            REMOVED_EXPIRED_KEY_COUNTER
-                                .with_label_values(&["list"])
-                                .inc();
-                            Ok(1)
+                            count_expired_key("list");
+                            Ok(Some(ttl))
                         }
-                        None => Ok(0),
+                        None => Ok(None),
                     }
                 }
                 .boxed()
             })
-            .await
+            .await?;
+        match expired {
+            Some(expire_at) => {
+                fire(EVENT_EXPIRED, &expired_key).await;
+                ship_expired(DataType::List, &expired_key, expire_at).await;
+                Ok(1)
+            }
+            None => Ok(0),
+        }
     }
 }
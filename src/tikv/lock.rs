@@ -0,0 +1,165 @@
+//! Locks with fencing tokens taken with LOCK, EXTEND and UNLOCK.
+//!
+//! A lock is a system key outside of the user keyspace holding the fencing
+//! token of its last acquisition, the expire timestamp and the owner. The key
+//! is read and written in one transaction, so two owners racing for a lock
+//! conflict in TiKV and only one of them commits. The token is kept when the
+//! lock is released or expires, each new acquisition gets a greater token,
+//! which the owner passes to the storage it protects to reject stale writers.
+
+use std::convert::TryInto;
+use std::sync::Arc;
+
+use ::futures::future::FutureExt;
+use tikv_client::Value;
+use tokio::sync::Mutex;
+
+use super::backend::Transaction;
+use super::errors::AsyncResult;
+use super::{get_txn_client, KEY_ENCODER};
+use crate::utils::{now_timestamp_in_millis, resp_int, resp_nil};
+use crate::Frame;
+
+/// Fencing token, expire timestamp in ms and owner of a lock
+struct LockState {
+    token: u64,
+    expire_at: u64,
+    owner: Vec<u8>,
+}
+
+impl LockState {
+    fn decode(value: &[u8]) -> LockState {
+        if value.len() < 16 {
+            return LockState {
+                token: 0,
+                expire_at: 0,
+                owner: vec![],
+            };
+        }
+        LockState {
+            token: u64::from_be_bytes(value[..8].try_into().unwrap()),
+            expire_at: u64::from_be_bytes(value[8..16].try_into().unwrap()),
+            owner: value[16..].to_vec(),
+        }
+    }
+
+    fn encode(&self) -> Value {
+        let mut value = Vec::with_capacity(16 + self.owner.len());
+        value.extend_from_slice(&self.token.to_be_bytes());
+        value.extend_from_slice(&self.expire_at.to_be_bytes());
+        value.extend_from_slice(&self.owner);
+        value
+    }
+
+    fn held_by(&self, owner: &str, now: u64) -> bool {
+        self.expire_at > now && self.owner == owner.as_bytes()
+    }
+}
+
+#[derive(Clone)]
+pub struct LockCommandCtx {
+    txn: Option<Arc<Mutex<Transaction>>>,
+}
+
+impl LockCommandCtx {
+    pub fn new(txn: Option<Arc<Mutex<Transaction>>>) -> Self {
+        LockCommandCtx { txn }
+    }
+
+    /// Read the state of the lock, apply `f` to it with the current time and
+    /// write it back if `f` returns a reply with the new state.
+    async fn update_lock<F>(mut self, name: &str, f: F) -> AsyncResult<Frame>
+    where
+        F: FnOnce(LockState, u64) -> (Frame, Option<LockState>) + Clone + Send + 'static,
+    {
+        let mut client = get_txn_client()?;
+        let ekey = KEY_ENCODER.encode_txnkv_lock_key(name);
+
+        client
+            .exec_in_txn(self.txn.clone(), |txn_rc| {
+                async move {
+                    if self.txn.is_none() {
+                        self.txn = Some(txn_rc.clone());
+                    }
+                    let mut txn = txn_rc.lock().await;
+                    let state = match txn.get(ekey.clone()).await? {
+                        Some(value) => LockState::decode(&value),
+                        None => LockState::decode(&[]),
+                    };
+                    let (reply, new_state) = f(state, now_timestamp_in_millis());
+                    if let Some(new_state) = new_state {
+                        txn.put(ekey, new_state.encode()).await?;
+                    }
+                    Ok(reply)
+                }
+                .boxed()
+            })
+            .await
+    }
+
+    /// Take the lock for `ttl` ms, replies with the fencing token, or nil if
+    /// another owner holds it. The owner holding the lock refreshes its ttl
+    /// and keeps its token.
+    pub async fn do_async_txnkv_lock(
+        self,
+        name: &str,
+        owner: &str,
+        ttl: u64,
+    ) -> AsyncResult<Frame> {
+        let owner = owner.to_owned();
+        self.update_lock(name, move |state, now| {
+            let held = state.expire_at > now;
+            if held && state.owner != owner.as_bytes() {
+                return (resp_nil(), None);
+            }
+            let token = if held { state.token } else { state.token + 1 };
+            let new_state = LockState {
+                token,
+                expire_at: now + ttl,
+                owner: owner.into_bytes(),
+            };
+            (resp_int(token as i64), Some(new_state))
+        })
+        .await
+    }
+
+    /// Reset the ttl of the lock held by `owner`, replies 1, or 0 if it does
+    /// not hold the lock anymore.
+    pub async fn do_async_txnkv_extend(
+        self,
+        name: &str,
+        owner: &str,
+        ttl: u64,
+    ) -> AsyncResult<Frame> {
+        let owner = owner.to_owned();
+        self.update_lock(name, move |state, now| {
+            if !state.held_by(&owner, now) {
+                return (resp_int(0), None);
+            }
+            let new_state = LockState {
+                expire_at: now + ttl,
+                ..state
+            };
+            (resp_int(1), Some(new_state))
+        })
+        .await
+    }
+
+    /// Release the lock held by `owner`, replies 1, or 0 if it does not hold
+    /// the lock anymore. The fencing token is kept for the next acquisition.
+    pub async fn do_async_txnkv_unlock(self, name: &str, owner: &str) -> AsyncResult<Frame> {
+        let owner = owner.to_owned();
+        self.update_lock(name, move |state, now| {
+            if !state.held_by(&owner, now) {
+                return (resp_int(0), None);
+            }
+            let new_state = LockState {
+                token: state.token,
+                expire_at: 0,
+                owner: vec![],
+            };
+            (resp_int(1), Some(new_state))
+        })
+        .await
+    }
+}
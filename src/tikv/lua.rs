@@ -1,11 +1,15 @@
-use std::sync::Arc;
+use std::sync::{Arc, Mutex as StdMutex};
 
+use super::backend::Transaction;
 use super::errors::AsyncResult;
+use crate::cmd::{script_set_written, script_start, SCRIPT_ID_REGISTRY_KEY};
+use crate::cmdlog::{cmdlog_enabled, write_applied, Reply, WriteEvent};
 use crate::db::Db;
+use crate::notify::notify_enabled;
+use crate::triggers::triggers_enabled;
 use crate::utils::{lua_resp_to_redis_resp, redis_resp_to_lua_resp, resp_err, sha1hex};
 use crate::{utils::resp_invalid_arguments, Command, Frame};
 use bytes::Bytes;
-use tikv_client::Transaction;
 use tokio::sync::Mutex;
 
 use crate::config::LOGGER;
@@ -18,11 +22,23 @@ use mlua::{prelude::*, Lua, Value as LuaValue, Variadic};
 pub struct LuaCommandCtx<'a> {
     txn: Option<Arc<Mutex<Transaction>>>,
     lua: &'a Option<Lua>,
+    effects: Arc<StdMutex<Vec<WriteEvent>>>,
 }
 
 impl<'a> LuaCommandCtx<'a> {
     pub fn new(txn: Option<Arc<Mutex<Transaction>>>, lua: &'a Option<Lua>) -> Self {
-        LuaCommandCtx { txn, lua }
+        LuaCommandCtx {
+            txn,
+            lua,
+            effects: Arc::new(StdMutex::new(vec![])),
+        }
+    }
+
+    /// The write commands called by the script, shipped to the command log
+    /// and triggers in place of the script once its transaction is committed,
+    /// so the consumers see the same writes whatever the script computed.
+    pub fn take_effects(&self) -> Vec<WriteEvent> {
+        std::mem::take(&mut *self.effects.lock().unwrap())
     }
 
     pub async fn do_async_eval_inner(
@@ -37,6 +53,10 @@ impl<'a> LuaCommandCtx<'a> {
         };
         //let lua = lua_rc.lock().await;
 
+        // registered for the watchdog and SCRIPT KILL until the script returns
+        let script_guard = script_start();
+        lua.set_named_registry_value(SCRIPT_ID_REGISTRY_KEY, script_guard.id())?;
+
         let globals = lua.globals();
 
         // Add KEYS and ARGV to lua state
@@ -57,11 +77,13 @@ impl<'a> LuaCommandCtx<'a> {
         // create redis.* commands table
         let redis = lua.create_table()?;
         let txn_rc = self.txn;
+        let effects = self.effects.clone();
 
         // redis.call()
         // redis.pcall()
         let redis_call = lua.create_async_function(move |_lua, args: Variadic<LuaValue>| {
             let txn_rc = txn_rc.clone();
+            let effects = effects.clone();
             // package arguments(without cmd) to argv
             async move {
                 if args.len() == 0 {
@@ -85,6 +107,17 @@ impl<'a> LuaCommandCtx<'a> {
                 }
 
                 let cmd = Command::from_argv(&cmd_name, &argv).unwrap();
+                let effect = if cmd.is_write() {
+                    let id: u64 = _lua.named_registry_value(SCRIPT_ID_REGISTRY_KEY)?;
+                    script_set_written(id);
+                    if cmdlog_enabled() || triggers_enabled() || notify_enabled() {
+                        Some(WriteEvent::new(cmd.get_name(), cmd.written_type(), &argv))
+                    } else {
+                        None
+                    }
+                } else {
+                    None
+                };
                 let txn_rc1 = txn_rc.clone().unwrap();
                 let txn = txn_rc1.lock().await;
                 let txn_ts = txn.start_timestamp();
@@ -96,14 +129,35 @@ impl<'a> LuaCommandCtx<'a> {
                     Command::Decr(mut cmd) => cmd.incr_by(txn_rc.clone(), false).await,
                     Command::DecrBy(mut cmd) => cmd.incr_by(txn_rc.clone(), false).await,
                     Command::Strlen(cmd) => cmd.strlen(txn_rc.clone()).await,
+                    Command::Setbit(cmd) => cmd.setbit(txn_rc.clone()).await,
+                    Command::Getbit(cmd) => cmd.getbit(txn_rc.clone()).await,
+                    Command::Bitcount(cmd) => cmd.bitcount(txn_rc.clone()).await,
+                    Command::Bitpos(cmd) => cmd.bitpos(txn_rc.clone()).await,
+                    Command::Bitop(cmd) => cmd.bitop(txn_rc.clone()).await,
+                    Command::Bitfield(cmd) => cmd.bitfield(txn_rc.clone()).await,
+                    Command::Sort(cmd) => cmd.sort(txn_rc.clone()).await,
+                    Command::Object(cmd) => cmd.object(txn_rc.clone()).await,
+                    Command::Randomkey(cmd) => cmd.randomkey(txn_rc.clone()).await,
+                    Command::Copy(cmd) => cmd.copy(txn_rc.clone()).await,
+                    Command::Move(cmd) => cmd.move_key(txn_rc.clone()).await,
+                    Command::Dump(cmd) => cmd.dump(txn_rc.clone()).await,
+                    Command::Restore(cmd) => cmd.restore(txn_rc.clone()).await,
+                    Command::Migrate(cmd) => cmd.migrate(txn_rc.clone()).await,
                     Command::Del(cmd) => cmd.del(txn_rc.clone()).await,
                     Command::Exists(cmd) => cmd.exists(txn_rc.clone()).await,
                     Command::Get(cmd) => cmd.get(txn_rc.clone()).await,
+                    Command::Getdel(cmd) => cmd.getdel(txn_rc.clone()).await,
+                    Command::Getex(cmd) => cmd.getex(txn_rc.clone()).await,
+                    Command::Setrange(cmd) => cmd.setrange(txn_rc.clone()).await,
                     Command::Set(cmd) => cmd.set(txn_rc.clone()).await,
                     Command::SetNX(cmd) => cmd.put_not_exists(txn_rc.clone()).await,
                     Command::SetEX(cmd) => cmd.setex(txn_rc.clone()).await,
                     Command::Mget(cmd) => cmd.batch_get(txn_rc.clone()).await,
                     Command::Mset(cmd) => cmd.batch_put(txn_rc.clone()).await,
+                    Command::Throttle(cmd) => cmd.throttle(txn_rc.clone()).await,
+                    Command::Lock(cmd) => cmd.lock(txn_rc.clone()).await,
+                    Command::Extend(cmd) => cmd.extend(txn_rc.clone()).await,
+                    Command::Unlock(cmd) => cmd.unlock(txn_rc.clone()).await,
                     Command::Type(cmd) => cmd.cmd_type(txn_rc.clone()).await,
                     Command::TTL(cmd) => cmd.ttl(false, txn_rc.clone()).await,
                     Command::PTTL(cmd) => cmd.ttl(true, txn_rc.clone()).await,
@@ -132,6 +186,9 @@ impl<'a> LuaCommandCtx<'a> {
                     Command::Lrange(cmd) => cmd.lrange(txn_rc.clone()).await,
                     Command::Llen(cmd) => cmd.llen(txn_rc.clone()).await,
                     Command::Lindex(cmd) => cmd.lindex(txn_rc.clone()).await,
+                    Command::Lpos(cmd) => cmd.lpos(txn_rc.clone()).await,
+                    Command::Lmpop(cmd) => cmd.lmpop(txn_rc.clone()).await,
+                    Command::Blmpop(cmd) => cmd.lmpop(txn_rc.clone()).await,
                     Command::Lset(cmd) => cmd.lset(txn_rc.clone()).await,
                     Command::Ltrim(cmd) => cmd.ltrim(txn_rc.clone()).await,
                     Command::Lrem(cmd) => cmd.lrem(txn_rc.clone()).await,
@@ -143,10 +200,14 @@ impl<'a> LuaCommandCtx<'a> {
                     Command::Smembers(cmd) => cmd.smembers(txn_rc.clone()).await,
                     Command::Srandmember(cmd) => cmd.srandmember(txn_rc.clone()).await,
                     Command::Spop(cmd) => cmd.spop(txn_rc.clone()).await,
+                    Command::Sdrain(cmd) => cmd.sdrain(txn_rc.clone()).await,
                     Command::Srem(cmd) => cmd.srem(txn_rc.clone()).await,
+                    Command::Sinter(cmd) => cmd.sinter(txn_rc.clone()).await,
+                    Command::Sintercard(cmd) => cmd.sintercard(txn_rc.clone()).await,
                     Command::Zadd(cmd) => cmd.zadd(txn_rc.clone()).await,
                     Command::Zcard(cmd) => cmd.zcard(txn_rc.clone()).await,
                     Command::Zscore(cmd) => cmd.zscore(txn_rc.clone()).await,
+                    Command::Zmscore(cmd) => cmd.zmscore(txn_rc.clone()).await,
                     Command::Zrem(cmd) => cmd.zrem(txn_rc.clone()).await,
                     Command::Zremrangebyscore(cmd) => cmd.zremrangebyscore(txn_rc.clone()).await,
                     Command::Zremrangebyrank(cmd) => cmd.zremrangebyrank(txn_rc.clone()).await,
@@ -157,8 +218,36 @@ impl<'a> LuaCommandCtx<'a> {
                     Command::Zcount(cmd) => cmd.zcount(txn_rc.clone()).await,
                     Command::Zpopmin(cmd) => cmd.zpop(txn_rc.clone(), true).await,
                     Command::Zpopmax(cmd) => cmd.zpop(txn_rc.clone(), false).await,
+                    Command::Zmpop(cmd) => cmd.zmpop(txn_rc.clone()).await,
+                    Command::Bzmpop(cmd) => cmd.zmpop(txn_rc.clone()).await,
+                    Command::Zrangestore(cmd) => cmd.zrangestore(txn_rc.clone()).await,
                     Command::Zrank(cmd) => cmd.zrank(txn_rc.clone()).await,
                     Command::Zincryby(cmd) => cmd.zincrby(txn_rc.clone()).await,
+                    Command::Zinter(cmd) => cmd.zcombine(txn_rc.clone()).await,
+                    Command::Zunion(cmd) => cmd.zcombine(txn_rc.clone()).await,
+                    Command::Zdiff(cmd) => cmd.zcombine(txn_rc.clone()).await,
+                    Command::Zinterstore(cmd) => cmd.zcombine(txn_rc.clone()).await,
+                    Command::Zunionstore(cmd) => cmd.zcombine(txn_rc.clone()).await,
+                    Command::Zdiffstore(cmd) => cmd.zcombine(txn_rc.clone()).await,
+                    Command::Zintercard(cmd) => cmd.zintercard(txn_rc.clone()).await,
+                    Command::Geoadd(cmd) => cmd.geoadd(txn_rc.clone()).await,
+                    Command::Geopos(cmd) => cmd.geopos(txn_rc.clone()).await,
+                    Command::Geodist(cmd) => cmd.geodist(txn_rc.clone()).await,
+                    Command::Geosearch(cmd) => cmd.geosearch(txn_rc.clone()).await,
+                    Command::Xadd(cmd) => cmd.xadd(txn_rc.clone()).await,
+                    Command::Xlen(cmd) => cmd.xlen(txn_rc.clone()).await,
+                    Command::Xrange(cmd) => cmd.xrange(txn_rc.clone(), false).await,
+                    Command::Xrevrange(cmd) => cmd.xrange(txn_rc.clone(), true).await,
+                    Command::Xread(cmd) => cmd.xread(txn_rc.clone()).await,
+                    Command::Xgroup(cmd) => cmd.xgroup(txn_rc.clone()).await,
+                    Command::Xreadgroup(cmd) => cmd.xreadgroup(txn_rc.clone()).await,
+                    Command::Xack(cmd) => cmd.xack(txn_rc.clone()).await,
+                    Command::Xpending(cmd) => cmd.xpending(txn_rc.clone()).await,
+                    Command::Xclaim(cmd) => cmd.xclaim(txn_rc.clone()).await,
+                    Command::Jsonset(cmd) => cmd.json_set(txn_rc.clone()).await,
+                    Command::Jsonget(cmd) => cmd.json_get(txn_rc.clone()).await,
+                    Command::Jsondel(cmd) => cmd.json_del(txn_rc.clone()).await,
+                    Command::Hindex(cmd) => cmd.hindex(txn_rc.clone()).await,
                     Command::Scan(cmd) => cmd.scan(txn_rc.clone()).await,
                     Command::Xscan(cmd) => cmd.scan(txn_rc.clone()).await,
                     _ => Ok(resp_invalid_arguments()),
@@ -166,6 +255,11 @@ impl<'a> LuaCommandCtx<'a> {
                 match result {
                     Ok(resp) => {
                         debug!(LOGGER, "response call from lua {:?}", resp);
+                        if let Some(effect) = effect {
+                            if write_applied(&effect.cmd, Reply::of(&resp)) {
+                                effects.lock().unwrap().push(effect);
+                            }
+                        }
                         let lua_resp = redis_resp_to_lua_resp(resp, _lua);
                         Ok(lua_resp)
                     }
@@ -0,0 +1,316 @@
+//! In-process storage backend, enabled with the `memory-backend` feature.
+//!
+//! Keys are encoded exactly as they are for TiKV, so all commands behave the
+//! same, which makes it possible to run tidis and its tests without a cluster.
+//! Transactions read the latest committed data and writes are applied when
+//! committed without conflict detection, data is lost on exit.
+
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, RwLock};
+
+use futures::future::{self, BoxFuture};
+use futures::stream::{self, BoxStream};
+use futures::{FutureExt, StreamExt};
+use tikv_client::{
+    BoundRange, Key, KvPair, Result as BackendResult, Timestamp, TimestampExt, TransactionOptions,
+    Value,
+};
+
+use super::backend::{StorageBackend, StorageTxn, Transaction};
+
+type Store = Arc<RwLock<BTreeMap<Key, Value>>>;
+
+#[derive(Default)]
+pub struct MemoryBackend {
+    store: Store,
+    ts: AtomicU64,
+}
+
+impl MemoryBackend {
+    pub fn new() -> MemoryBackend {
+        MemoryBackend::default()
+    }
+}
+
+impl StorageBackend for MemoryBackend {
+    fn name(&self) -> &'static str {
+        "memory"
+    }
+
+    fn current_timestamp(&self) -> BoxFuture<'_, BackendResult<Timestamp>> {
+        let version = self.ts.fetch_add(1, Ordering::Relaxed) + 1;
+        future::ready(Ok(Timestamp::from_version(version))).boxed()
+    }
+
+    fn new_transaction(&self, _ts: Timestamp, _options: TransactionOptions) -> Transaction {
+        Transaction::new(Box::new(MemoryTxn {
+            store: self.store.clone(),
+            mutations: BTreeMap::new(),
+        }))
+    }
+}
+
+/// Buffers the mutations until commit, reads see the own mutations.
+struct MemoryTxn {
+    store: Store,
+    mutations: BTreeMap<Key, Option<Value>>,
+}
+
+impl MemoryTxn {
+    fn get_sync(&self, key: &Key) -> Option<Value> {
+        match self.mutations.get(key) {
+            Some(mutation) => mutation.clone(),
+            None => self.store.read().unwrap().get(key).cloned(),
+        }
+    }
+
+    /// Merge committed data with the mutations in range, in ascending order,
+    /// until `limit` pairs are merged
+    fn scan_sync(&self, range: BoundRange, limit: u32) -> Vec<KvPair> {
+        let (start, end) = range.into_keys();
+        let in_range = |key: &Key| end.as_ref().map_or(true, |end| key < end);
+
+        let store = self.store.read().unwrap();
+        let mut committed = store
+            .range(start.clone()..)
+            .take_while(|(k, _)| in_range(k))
+            .peekable();
+        let mut mutations = self
+            .mutations
+            .range(start..)
+            .take_while(|(k, _)| in_range(k))
+            .peekable();
+        let mut kvs = vec![];
+        while kvs.len() < limit as usize {
+            let from_mutations = match (committed.peek(), mutations.peek()) {
+                (None, None) => break,
+                (Some(_), None) => false,
+                (None, Some(_)) => true,
+                (Some((committed_key, _)), Some((mutated_key, _))) => mutated_key <= committed_key,
+            };
+            let (key, value) = if from_mutations {
+                let (key, mutation) = mutations.next().unwrap();
+                // the mutation overrides the committed value of the key
+                if committed.peek().map_or(false, |(k, _)| *k == key) {
+                    committed.next();
+                }
+                match mutation {
+                    Some(value) => (key, value),
+                    None => continue,
+                }
+            } else {
+                committed.next().unwrap()
+            };
+            kvs.push(KvPair::new(key.clone(), value.clone()));
+        }
+        kvs
+    }
+
+    fn scan_reverse_sync(&self, range: BoundRange, limit: u32) -> Vec<KvPair> {
+        let mut kvs = self.scan_sync(range, u32::MAX);
+        kvs.reverse();
+        kvs.truncate(limit as usize);
+        kvs
+    }
+}
+
+impl StorageTxn for MemoryTxn {
+    fn get(&mut self, key: Key) -> BoxFuture<'_, BackendResult<Option<Value>>> {
+        future::ready(Ok(self.get_sync(&key))).boxed()
+    }
+
+    fn key_exists(&mut self, key: Key) -> BoxFuture<'_, BackendResult<bool>> {
+        future::ready(Ok(self.get_sync(&key).is_some())).boxed()
+    }
+
+    fn batch_get(&mut self, keys: Vec<Key>) -> BoxFuture<'_, BackendResult<Vec<KvPair>>> {
+        let kvs = keys
+            .into_iter()
+            .filter_map(|k| self.get_sync(&k).map(|v| KvPair::new(k, v)))
+            .collect();
+        future::ready(Ok(kvs)).boxed()
+    }
+
+    fn scan(&mut self, range: BoundRange, limit: u32) -> BoxFuture<'_, BackendResult<Vec<KvPair>>> {
+        future::ready(Ok(self.scan_sync(range, limit))).boxed()
+    }
+
+    fn scan_keys(
+        &mut self,
+        range: BoundRange,
+        limit: u32,
+    ) -> BoxFuture<'_, BackendResult<Vec<Key>>> {
+        let keys = self
+            .scan_sync(range, limit)
+            .into_iter()
+            .map(|kv| kv.0)
+            .collect();
+        future::ready(Ok(keys)).boxed()
+    }
+
+    fn scan_stream(
+        &mut self,
+        range: BoundRange,
+        limit: u32,
+    ) -> BoxFuture<'_, BackendResult<BoxStream<'static, KvPair>>> {
+        let kvs = self.scan_sync(range, limit);
+        future::ready(Ok(stream::iter(kvs).boxed())).boxed()
+    }
+
+    fn scan_reverse_stream(
+        &mut self,
+        range: BoundRange,
+        limit: u32,
+    ) -> BoxFuture<'_, BackendResult<BoxStream<'static, KvPair>>> {
+        let kvs = self.scan_reverse_sync(range, limit);
+        future::ready(Ok(stream::iter(kvs).boxed())).boxed()
+    }
+
+    fn scan_keys_stream(
+        &mut self,
+        range: BoundRange,
+        limit: u32,
+    ) -> BoxFuture<'_, BackendResult<BoxStream<'static, Key>>> {
+        let keys: Vec<Key> = self
+            .scan_sync(range, limit)
+            .into_iter()
+            .map(|kv| kv.0)
+            .collect();
+        future::ready(Ok(stream::iter(keys).boxed())).boxed()
+    }
+
+    fn put(&mut self, key: Key, value: Value) -> BoxFuture<'_, BackendResult<()>> {
+        self.mutations.insert(key, Some(value));
+        future::ready(Ok(())).boxed()
+    }
+
+    fn delete(&mut self, key: Key) -> BoxFuture<'_, BackendResult<()>> {
+        self.mutations.insert(key, None);
+        future::ready(Ok(())).boxed()
+    }
+
+    fn commit(&mut self) -> BoxFuture<'_, BackendResult<Option<Timestamp>>> {
+        let mut store = self.store.write().unwrap();
+        for (key, mutation) in std::mem::take(&mut self.mutations) {
+            match mutation {
+                Some(value) => store.insert(key, value),
+                None => store.remove(&key),
+            };
+        }
+        future::ready(Ok(None)).boxed()
+    }
+
+    fn rollback(&mut self) -> BoxFuture<'_, BackendResult<()>> {
+        self.mutations.clear();
+        future::ready(Ok(())).boxed()
+    }
+}
+
+#[cfg(all(test, feature = "memory-backend"))]
+mod tests {
+    use std::sync::Once;
+
+    use bytes::Bytes;
+    use tikv_client::{Key, KvPair, TransactionOptions};
+
+    use super::MemoryBackend;
+    use crate::tikv::backend::{StorageBackend, Transaction};
+    use crate::tikv::do_memory_connect;
+    use crate::tikv::hash::HashCommandCtx;
+    use crate::tikv::string::StringCommandCtx;
+    use crate::Frame;
+
+    static CONNECT: Once = Once::new();
+
+    /// Serve the commands from the memory backend, shared by the tests
+    fn connect() {
+        CONNECT.call_once(|| do_memory_connect().unwrap());
+    }
+
+    async fn begin(backend: &MemoryBackend) -> Transaction {
+        let ts = backend.current_timestamp().await.unwrap();
+        backend.new_transaction(ts, TransactionOptions::new_optimistic())
+    }
+
+    fn scanned_keys(txn_kvs: impl Iterator<Item = KvPair>) -> Vec<Key> {
+        txn_kvs.map(|kv| kv.0).collect()
+    }
+
+    #[tokio::test]
+    async fn set_get() {
+        connect();
+        let value = Bytes::from_static(b"value1");
+        StringCommandCtx::new(None)
+            .do_async_txnkv_put("memory:set_get", &value, 0)
+            .await
+            .unwrap();
+        let reply = StringCommandCtx::new(None)
+            .do_async_txnkv_get("memory:set_get")
+            .await
+            .unwrap();
+        assert!(reply == "value1");
+    }
+
+    #[tokio::test]
+    async fn hset_hgetall() {
+        connect();
+        let fvs = vec![
+            KvPair::new("f1".to_owned(), b"v1".to_vec()),
+            KvPair::new("f2".to_owned(), b"v2".to_vec()),
+        ];
+        let reply = HashCommandCtx::new(None)
+            .do_async_txnkv_hset("memory:hset_hgetall", &fvs, false, false)
+            .await
+            .unwrap();
+        assert!(matches!(reply, Frame::Integer(2)));
+        let reply = HashCommandCtx::new(None)
+            .do_async_txnkv_hgetall("memory:hset_hgetall", true, true)
+            .await
+            .unwrap();
+        match reply {
+            Frame::Array(items) => {
+                assert_eq!(items.len(), 4);
+                for (item, expected) in items.iter().zip(["f1", "v1", "f2", "v2"]) {
+                    assert!(*item == expected);
+                }
+            }
+            _ => panic!("hgetall replied {:?}", reply),
+        }
+    }
+
+    #[tokio::test]
+    async fn scan_with_limit() {
+        let backend = MemoryBackend::new();
+        let mut txn = begin(&backend).await;
+        for key in ["k1", "k3", "k5"] {
+            txn.put(key.to_owned(), b"v".to_vec()).await.unwrap();
+        }
+        txn.commit().await.unwrap();
+
+        // the mutations of the transaction are merged with the committed keys
+        let mut txn = begin(&backend).await;
+        txn.put("k2".to_owned(), b"v".to_vec()).await.unwrap();
+        txn.delete("k3".to_owned()).await.unwrap();
+        let range = Key::from("k".to_owned())..Key::from("l".to_owned());
+        let keys = scanned_keys(txn.scan(range.clone(), 2).await.unwrap());
+        assert_eq!(
+            keys,
+            vec![Key::from("k1".to_owned()), Key::from("k2".to_owned())]
+        );
+        let keys = scanned_keys(txn.scan(range.clone(), 10).await.unwrap());
+        assert_eq!(keys.len(), 3);
+        assert!(txn.scan(range, 0).await.unwrap().next().is_none());
+    }
+
+    #[tokio::test]
+    async fn rollback() {
+        let backend = MemoryBackend::new();
+        let mut txn = begin(&backend).await;
+        txn.put("k1".to_owned(), b"v1".to_vec()).await.unwrap();
+        txn.rollback().await.unwrap();
+
+        let mut txn = begin(&backend).await;
+        assert_eq!(txn.get("k1".to_owned()).await.unwrap(), None);
+    }
+}
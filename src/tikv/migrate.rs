@@ -0,0 +1,192 @@
+//! MIGRATE of keys to another Redis or tidis instance.
+//!
+//! The keys are dumped, see `super::dump`, and restored on the target with
+//! their remaining ttl through an outbound connection, one key after the
+//! other. Without COPY the migrated keys are deleted afterwards in one
+//! transaction, only those whose value is still the dumped one, so a write
+//! made while the key was on its way is not lost but left on the source.
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use bytes::Bytes;
+use futures::future::FutureExt;
+use tokio::sync::Mutex;
+
+use super::backend::Transaction;
+use super::dump::DumpCommandCtx;
+use super::errors::{AsyncResult, RTError, REDIS_MIGRATE_CONNECT_ERR, REDIS_MIGRATE_IO_ERR};
+use super::get_txn_client;
+use super::sort::check_reply;
+use super::string::StringCommandCtx;
+use crate::outbound::OutboundClient;
+use crate::utils::{resp_err, resp_ok, ttl_from_timestamp};
+use crate::Frame;
+
+/// Where MIGRATE sends the keys
+#[derive(Debug, Clone)]
+pub struct MigrateTarget {
+    pub host: String,
+    pub port: u16,
+    pub db: i64,
+    /// Milliseconds for the connection and each reply
+    pub timeout: u64,
+    /// The username if any and the password to authenticate with
+    pub auth: Option<(Option<String>, String)>,
+}
+
+#[derive(Clone)]
+pub struct MigrateCommandCtx {
+    txn: Option<Arc<Mutex<Transaction>>>,
+}
+
+/// The error replied by the target
+fn target_error(frame: &Frame) -> Option<RTError> {
+    let e = match frame {
+        Frame::ErrorOwned(e) => e.as_str(),
+        Frame::ErrorString(e) => *e,
+        _ => return None,
+    };
+    Some(RTError::Owned(format!(
+        "ERR Target instance replied with error: {}",
+        e
+    )))
+}
+
+impl MigrateCommandCtx {
+    pub fn new(txn: Option<Arc<Mutex<Transaction>>>) -> Self {
+        MigrateCommandCtx { txn }
+    }
+
+    /// Send the existing keys to the target, NOKEY if none exists. The keys
+    /// restored before an error of the target are migrated all the same.
+    pub async fn do_async_txnkv_migrate(
+        self,
+        target: &MigrateTarget,
+        keys: &[String],
+        copy: bool,
+        replace: bool,
+    ) -> AsyncResult<Frame> {
+        let mut dumped = vec![];
+        for key in keys {
+            let value = DumpCommandCtx::new(self.txn.clone())
+                .do_async_txnkv_dump_value(key)
+                .await;
+            match value {
+                Ok(Some((payload, timestamp))) => dumped.push((key.clone(), payload, timestamp)),
+                Ok(None) => {}
+                Err(e) => return Ok(resp_err(e)),
+            }
+        }
+        if dumped.is_empty() {
+            return Ok(Frame::Simple("NOKEY".to_owned()));
+        }
+
+        // like Redis, no timeout is a timeout of a second
+        let timeout = Duration::from_millis(if target.timeout == 0 {
+            1000
+        } else {
+            target.timeout
+        });
+        let mut client = match OutboundClient::connect(&target.host, target.port, timeout).await {
+            Ok(client) => client,
+            Err(_) => return Ok(resp_err(REDIS_MIGRATE_CONNECT_ERR)),
+        };
+        let mut setup = vec![];
+        if let Some((username, password)) = &target.auth {
+            let mut args = vec![Bytes::from("AUTH")];
+            if let Some(username) = username {
+                args.push(Bytes::from(username.clone()));
+            }
+            args.push(Bytes::from(password.clone()));
+            setup.push(args);
+        }
+        if target.db != 0 {
+            setup.push(vec![
+                Bytes::from("SELECT"),
+                Bytes::from(target.db.to_string()),
+            ]);
+        }
+        for args in setup {
+            match client.call(args).await {
+                Ok(frame) => {
+                    if let Some(e) = target_error(&frame) {
+                        return Ok(resp_err(e));
+                    }
+                }
+                Err(_) => return Ok(resp_err(REDIS_MIGRATE_IO_ERR)),
+            }
+        }
+
+        let mut migrated = vec![];
+        let mut error = None;
+        for (key, payload, timestamp) in dumped {
+            // a key about to expire is sent with the smallest ttl RESTORE takes
+            let ttl = match timestamp {
+                0 => 0,
+                timestamp => ttl_from_timestamp(timestamp).max(1),
+            };
+            let mut args = vec![
+                Bytes::from("RESTORE"),
+                Bytes::from(key.clone()),
+                Bytes::from(ttl.to_string()),
+                Bytes::from(payload.clone()),
+            ];
+            if replace {
+                args.push(Bytes::from("REPLACE"));
+            }
+            match client.call(args).await {
+                Ok(frame) => match target_error(&frame) {
+                    Some(e) => error = Some(e),
+                    None => migrated.push((key, payload)),
+                },
+                Err(_) => {
+                    error = Some(REDIS_MIGRATE_IO_ERR);
+                    break;
+                }
+            }
+        }
+
+        if !copy && !migrated.is_empty() {
+            if let Err(e) = self.delete_unchanged(migrated).await {
+                return Ok(resp_err(e));
+            }
+        }
+        match error {
+            Some(e) => Ok(resp_err(e)),
+            None => Ok(resp_ok()),
+        }
+    }
+
+    /// Delete the keys whose value is still the migrated payload
+    async fn delete_unchanged(mut self, migrated: Vec<(String, Vec<u8>)>) -> AsyncResult<()> {
+        let mut client = get_txn_client()?;
+        client
+            .exec_in_txn(self.txn.clone(), |txn_rc| {
+                async move {
+                    if self.txn.is_none() {
+                        self.txn = Some(txn_rc.clone());
+                    }
+                    let mut unchanged = vec![];
+                    for (key, payload) in migrated {
+                        let current = DumpCommandCtx::new(self.txn.clone())
+                            .do_async_txnkv_dump_value(&key)
+                            .await?;
+                        if current.map_or(false, |(current, _)| current == payload) {
+                            unchanged.push(key);
+                        }
+                    }
+                    if !unchanged.is_empty() {
+                        check_reply(
+                            StringCommandCtx::new(self.txn.clone())
+                                .do_async_txnkv_del(&unchanged)
+                                .await?,
+                        )?;
+                    }
+                    Ok(())
+                }
+                .boxed()
+            })
+            .await
+    }
+}
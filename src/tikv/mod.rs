@@ -19,22 +19,46 @@ use crate::{
     backend_grpc_keepalive_timeout_or_default, backend_key_file_or_default,
     backend_max_batch_size_or_default, backend_max_batch_wait_time_or_default,
     backend_max_inflight_requests_or_default, backend_overload_threshold_or_default,
-    backend_timeout_or_default, config_meta_key_number_or_default, conn_concurrency_or_default,
-    fetch_idx_and_add,
+    backend_storage_or_default, backend_timeout_or_default, config_meta_key_number_or_default,
+    conn_concurrency_or_default, fetch_idx_and_add,
 };
 
+use self::backend::StorageBackend;
 use self::client::RawClientWrapper;
 use self::client::TxnClientWrapper;
 
 use self::errors::{AsyncResult, RTError};
 
+pub mod backend;
+pub mod bitmap;
 pub mod client;
+pub mod copy;
+pub mod dump;
 pub mod encoding;
+pub mod encryption;
 pub mod errors;
+pub mod eviction;
+pub mod expiration;
+pub mod format;
+pub mod geo;
 pub mod hash;
+pub mod health;
+pub mod idempotency;
+pub mod index;
+pub mod intersect;
+pub mod json;
 pub mod list;
+pub mod lock;
 pub mod lua;
+#[cfg(feature = "memory-backend")]
+pub mod memory;
+pub mod migrate;
+pub mod retry;
+pub mod rpclog;
 pub mod set;
+pub mod sort;
+pub mod stamp;
+pub mod stream;
 pub mod string;
 pub mod zset;
 
@@ -82,7 +106,7 @@ pub fn stop_profiler() {
 
 pub static mut TIKV_RAW_CLIENT: Option<RawClient> = None;
 
-pub static mut TIKV_TXN_CLIENTS: Option<Vec<TransactionClient>> = None;
+pub static mut TIKV_TXN_CLIENTS: Option<Vec<Box<dyn StorageBackend>>> = None;
 pub static mut TIKV_TXN_CLIENT_IDX: AtomicUsize = AtomicUsize::new(0);
 
 pub static mut INSTANCE_ID: u64 = 0;
@@ -115,7 +139,7 @@ pub fn get_txn_client() -> Result<TxnClientWrapper<'static>, RTError> {
         idx = (idx + 1) % TIKV_TXN_CLIENTS.as_ref().unwrap().len();
         TIKV_TXN_CLIENT_IDX.store(idx, Relaxed);
 
-        &TIKV_TXN_CLIENTS.as_ref().unwrap()[idx]
+        TIKV_TXN_CLIENTS.as_ref().unwrap()[idx].as_ref()
     };
     let ret = TxnClientWrapper::new(client);
     Ok(ret)
@@ -155,7 +179,7 @@ pub async fn do_async_txn_connect(addrs: Vec<String>) -> AsyncResult<()> {
         let client =
             TransactionClient::new_with_config(addrs.clone(), config.clone(), Some(LOGGER.clone()))
                 .await?;
-        clients.push(client);
+        clients.push(Box::new(client) as Box<dyn StorageBackend>);
     }
     unsafe {
         TIKV_TXN_CLIENTS.replace(clients);
@@ -184,7 +208,26 @@ pub async fn do_async_raw_connect(addrs: Vec<String>) -> AsyncResult<()> {
     Ok(())
 }
 
+/// Use the in-process backend instead of connecting to TiKV, only the
+/// transactional API is available.
+#[cfg(feature = "memory-backend")]
+pub fn do_memory_connect() -> AsyncResult<()> {
+    let backend: Box<dyn StorageBackend> = Box::new(memory::MemoryBackend::new());
+    unsafe {
+        TIKV_TXN_CLIENTS.replace(vec![backend]);
+    }
+    Ok(())
+}
+
+#[cfg(not(feature = "memory-backend"))]
+pub fn do_memory_connect() -> AsyncResult<()> {
+    Err(errors::REDIS_MEMORY_BACKEND_DISABLED_ERR)
+}
+
 pub async fn do_async_connect(addrs: Vec<String>) -> AsyncResult<()> {
+    if backend_storage_or_default() == "memory" {
+        return do_memory_connect();
+    }
     do_async_txn_connect(addrs.clone()).await?;
     do_async_raw_connect(addrs).await?;
     Ok(())
@@ -0,0 +1,72 @@
+//! Retries of the storage calls failing on transient region errors.
+//!
+//! A region split or merge, a leader moved to another store or a store
+//! unreachable for a moment make a call fail once the TiKV client gave up
+//! its own backoff, while the same call succeeds with the refreshed region
+//! cache a few milliseconds later. Every storage call goes through here:
+//!
+//! - the reads of a transaction, see `super::backend::Transaction`, which
+//!   read the same snapshot whatever the attempt, so commands running in a
+//!   read only or shared transaction are retried too;
+//! - the calls of the raw client, see `super::client::RawClientWrapper`.
+//!
+//! Writes and commits of a transaction are not retried alone, `exec_in_txn`
+//! retries the whole transaction from a new one on the same errors.
+
+use std::future::Future;
+
+use tikv_client::Error;
+
+use super::sleep;
+use crate::metrics::{TIKV_CLIENT_RETRIES, TIKV_ERR_COUNTER};
+
+/// Attempts of a read of a transaction before its error is replied
+pub const TXN_READ_ATTEMPTS: u32 = 10;
+
+/// Whether the error is transient: the region of the key changed or could
+/// not be reached, and a new attempt with the refreshed region cache may
+/// succeed
+pub fn is_region_error(err: &Error) -> bool {
+    matches!(
+        err,
+        Error::RegionError(_)
+            | Error::EntryNotFoundInRegionCache
+            | Error::KvError { message: _ }
+            | Error::MultipleKeyErrors(_)
+            | Error::Grpc(_)
+    )
+}
+
+/// Wait before the attempt after `attempt`, growing up to 200ms
+pub async fn region_backoff(attempt: u32) {
+    sleep(std::cmp::min(2 + attempt * 10, 200)).await;
+}
+
+/// Call until it succeeds, fails with an error which is not a region error,
+/// or `attempts` calls failed. Errors are counted in
+/// `tikv_redis_tikv_reported_errors_count_total` with `err_label`.
+pub async fn with_region_retry<T, F, Fut>(
+    err_label: &'static str,
+    attempts: u32,
+    mut call: F,
+) -> Result<T, Error>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T, Error>>,
+{
+    let mut attempt = 1;
+    loop {
+        match call().await {
+            Ok(val) => return Ok(val),
+            Err(err) => {
+                TIKV_ERR_COUNTER.with_label_values(&[err_label]).inc();
+                if attempt >= attempts || !is_region_error(&err) {
+                    return Err(err);
+                }
+                TIKV_CLIENT_RETRIES.inc();
+                region_backoff(attempt).await;
+                attempt += 1;
+            }
+        }
+    }
+}
@@ -0,0 +1,120 @@
+//! Outliers of the storage calls of the transactions, for TIDIS.RPCLOG.
+//!
+//! Each call of a `Transaction` is timed in `tikv_redis_tikv_rpc_duration_seconds`
+//! by operation, and those taking at least `backend.rpc_log_slower_than_ms`
+//! are counted in `tikv_redis_tikv_slow_rpc_total` and kept in a ring buffer
+//! of the latest `backend.rpc_log_max_len` ones, like the SLOWLOG of Redis.
+//!
+//! The TiKV client does not tell which region and store served a call nor
+//! how many times it backed off, so an entry has the first key of the call
+//! instead, the region holding it is the one to look at on the TiKV side,
+//! and the resource group and priority class of the command which made it.
+//!
+//! The request context the TiKV client fills has no resource group and the
+//! default priority, the client pinned has no way to set them, so TiKV does
+//! not throttle a group nor a class yet and they are only known on this side.
+
+use std::collections::VecDeque;
+use std::future::Future;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+
+use tikv_client::Key;
+use tokio::time::Instant;
+
+use super::client::{request_priority, resource_group};
+
+use crate::metrics::{TIKV_RPC_DURATION, TIKV_SLOW_RPC_COUNTER};
+use crate::server::duration_to_sec;
+use crate::utils::{now_timestamp_in_millis, resp_array, resp_bulk, resp_int};
+use crate::{backend_rpc_log_max_len_or_default, backend_rpc_log_slower_than_ms_or_default, Frame};
+
+/// A slow storage call
+struct RpcLogEntry {
+    id: u64,
+    /// Unix time the call ended at, in seconds
+    timestamp: u64,
+    duration_us: u64,
+    op: &'static str,
+    /// The first key of the call, empty for commit and rollback
+    key: Vec<u8>,
+    resource_group: String,
+    priority: &'static str,
+}
+
+lazy_static! {
+    static ref RPC_LOG: Mutex<VecDeque<RpcLogEntry>> = Mutex::new(VecDeque::new());
+}
+
+/// Id of the next entry, not reset by TIDIS.RPCLOG RESET like SLOWLOG
+static RPC_LOG_SEQ: AtomicU64 = AtomicU64::new(0);
+
+/// The key of a call to record, copied only when the rpc log is enabled as
+/// the key itself is moved into the call
+pub fn logged_key(key: Option<&Key>) -> Vec<u8> {
+    match key {
+        Some(key) if backend_rpc_log_slower_than_ms_or_default() > 0 => key.clone().into(),
+        _ => vec![],
+    }
+}
+
+/// Run the storage call `op` on `key`, see `logged_key`, recording it if it
+/// is slow
+pub async fn traced<T>(op: &'static str, key: Vec<u8>, call: impl Future<Output = T>) -> T {
+    let slower_than_ms = backend_rpc_log_slower_than_ms_or_default();
+    let start_at = Instant::now();
+    let res = call.await;
+    let duration = Instant::now() - start_at;
+    TIKV_RPC_DURATION
+        .with_label_values(&[op])
+        .observe(duration_to_sec(duration));
+
+    if slower_than_ms > 0 && duration.as_millis() as u64 >= slower_than_ms {
+        TIKV_SLOW_RPC_COUNTER.with_label_values(&[op]).inc();
+        let entry = RpcLogEntry {
+            id: RPC_LOG_SEQ.fetch_add(1, Ordering::Relaxed),
+            timestamp: now_timestamp_in_millis() / 1000,
+            duration_us: duration.as_micros() as u64,
+            op,
+            key,
+            resource_group: resource_group(),
+            priority: request_priority().as_str(),
+        };
+        let max_len = backend_rpc_log_max_len_or_default();
+        let mut log = RPC_LOG.lock().unwrap();
+        log.push_front(entry);
+        log.truncate(max_len);
+    }
+    res
+}
+
+/// The latest `count` entries, newest first, each as
+/// `[id, timestamp, duration in microseconds, operation, hex encoded key,
+/// resource group, priority]`
+pub fn rpc_log_entries(count: usize) -> Frame {
+    let log = RPC_LOG.lock().unwrap();
+    let entries = log
+        .iter()
+        .take(count)
+        .map(|entry| {
+            resp_array(vec![
+                resp_int(entry.id as i64),
+                resp_int(entry.timestamp as i64),
+                resp_int(entry.duration_us as i64),
+                resp_bulk(entry.op.as_bytes().to_vec()),
+                resp_bulk(hex::encode(&entry.key).into_bytes()),
+                resp_bulk(entry.resource_group.as_bytes().to_vec()),
+                resp_bulk(entry.priority.as_bytes().to_vec()),
+            ])
+        })
+        .collect();
+    resp_array(entries)
+}
+
+pub fn rpc_log_len() -> usize {
+    RPC_LOG.lock().unwrap().len()
+}
+
+pub fn rpc_log_reset() {
+    RPC_LOG.lock().unwrap().clear();
+}
@@ -1,7 +1,10 @@
+use super::backend::Transaction;
 use super::client::get_version_for_new;
 use super::errors::*;
+use super::expiration::count_expired_key;
 use super::gen_next_meta_index;
 use super::get_txn_client;
+use super::intersect::Intersection;
 use super::KEY_ENCODER;
 use super::{
     encoding::{DataType, KeyDecoder},
@@ -9,26 +12,34 @@ use super::{
 };
 use crate::async_del_set_threshold_or_default;
 use crate::async_expire_set_threshold_or_default;
+use crate::cmd_inter_length_limit_or_default;
 use crate::utils::count_unique_keys;
-use crate::utils::{key_is_expired, resp_array, resp_bulk, resp_err, resp_int, resp_nil};
+use crate::utils::{
+    expire_timestamp_of_new_key, key_is_expired, resp_array, resp_bulk, resp_err, resp_int,
+    resp_nil, ReplyBuilder,
+};
 use crate::Frame;
 use ::futures::future::FutureExt;
 use futures::StreamExt;
 use rand::prelude::SliceRandom;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::convert::TryInto;
 use std::sync::Arc;
 use tikv_client::Key;
-use tikv_client::Transaction;
 use tikv_client::Value;
 use tokio::sync::Mutex;
 
 use rand::{rngs::SmallRng, Rng, SeedableRng};
 
-use crate::metrics::REMOVED_EXPIRED_KEY_COUNTER;
+use crate::cmdlog::ship_expired;
+use crate::triggers::{fire, EVENT_EXPIRED};
 
 const RANDOM_BASE: i64 = 100;
 
+/// Members of the smallest set probed in the other sets by SINTERCARD with
+/// each batch get
+const SINTERCARD_PROBE_BATCH: usize = 256;
+
 #[derive(Clone)]
 pub struct SetCommandCtx {
     txn: Option<Arc<Mutex<Transaction>>>,
@@ -152,8 +163,11 @@ impl SetCommandCtx {
 
                             // create a new meta key if key already expired above
                             if expired {
-                                let new_meta_value =
-                                    KEY_ENCODER.encode_txnkv_set_meta_value(0, version, 0);
+                                let new_meta_value = KEY_ENCODER.encode_txnkv_set_meta_value(
+                                    expire_timestamp_of_new_key(0),
+                                    version,
+                                    0,
+                                );
                                 txn.put(meta_key, new_meta_value).await?;
                             }
 
@@ -173,7 +187,11 @@ impl SetCommandCtx {
                                 txn.put(data_key, vec![0]).await?;
                             }
                             // create meta key
-                            let meta_value = KEY_ENCODER.encode_txnkv_set_meta_value(0, version, 0);
+                            let meta_value = KEY_ENCODER.encode_txnkv_set_meta_value(
+                                expire_timestamp_of_new_key(0),
+                                version,
+                                0,
+                            );
                             txn.put(meta_key, meta_value).await?;
 
                             let added = count_unique_keys(&members) as i64;
@@ -293,30 +311,27 @@ impl SetCommandCtx {
                                     Ok(resp_int(0))
                                 }
                             } else {
-                                let mut resp = vec![];
-                                let mut member_data_keys = Vec::with_capacity(members.len());
-                                for m in &members {
-                                    let data_key =
-                                        KEY_ENCODER.encode_txnkv_set_data_key(&key, m, version);
-                                    member_data_keys.push(data_key);
-                                }
-
-                                // batch get
+                                let member_data_keys: Vec<Key> = members
+                                    .iter()
+                                    .map(|m| {
+                                        KEY_ENCODER.encode_txnkv_set_data_key(&key, m, version)
+                                    })
+                                    .collect();
+
+                                // all the members in one batch get, the members
+                                // found are looked up by their data key
                                 let member_result = txn
-                                    .batch_get(member_data_keys)
+                                    .batch_get(member_data_keys.clone())
                                     .await?
                                     .map(|kv| kv.into())
                                     .collect::<HashMap<Key, Value>>();
 
-                                for m in &members {
-                                    let data_key =
-                                        KEY_ENCODER.encode_txnkv_set_data_key(&key, m, version);
-                                    match member_result.get(&data_key) {
-                                        Some(_) => resp.push(resp_int(1)),
-                                        None => resp.push(resp_int(0)),
-                                    }
-                                }
-
+                                let resp = member_data_keys
+                                    .iter()
+                                    .map(|data_key| {
+                                        resp_int(member_result.contains_key(data_key) as i64)
+                                    })
+                                    .collect();
                                 Ok(resp_array(resp))
                             }
                         }
@@ -470,19 +485,17 @@ impl SetCommandCtx {
                             let bound_range =
                                 KEY_ENCODER.encode_txnkv_set_data_key_range(&key, version);
 
-                            let iter = txn.scan_keys_stream(bound_range, u32::MAX).await?;
+                            let mut iter = txn.scan_keys_stream(bound_range, u32::MAX).await?;
 
-                            let resp = iter
-                                .map(|k| {
-                                    // decode member from data key
-                                    let user_key =
-                                        KeyDecoder::decode_key_set_member_from_datakey(&key, k);
-                                    resp_bulk(user_key)
-                                })
-                                .collect()
-                                .await;
+                            // the members follow the prefix of the data keys
+                            let prefix_len = KeyDecoder::decode_data_key_prefix_len(&key);
+                            let mut reply = ReplyBuilder::new();
+                            while let Some(k) = iter.next().await {
+                                let data_key: Vec<u8> = k.into();
+                                reply.push_bulk(&data_key[prefix_len..]);
+                            }
 
-                            Ok(resp_array(resp))
+                            Ok(reply.build())
                         }
                         None => Ok(resp_array(vec![])),
                     }
@@ -492,6 +505,130 @@ impl SetCommandCtx {
             .await
     }
 
+    /// Members of the intersection of the sets, refused if more than
+    /// `cmd_inter_length_limit` members are found.
+    pub async fn do_async_txnkv_sinter(mut self, keys: &[String]) -> AsyncResult<Frame> {
+        let mut client = get_txn_client()?;
+        let keys = keys.to_owned();
+
+        client
+            .exec_in_txn(self.txn.clone(), |txn_rc| {
+                async move {
+                    if self.txn.is_none() {
+                        self.txn = Some(txn_rc.clone());
+                    }
+
+                    let mut inter = Intersection::open(&txn_rc, &keys, false).await?;
+
+                    let limit_len = cmd_inter_length_limit_or_default() as usize;
+                    let mut resp = vec![];
+                    while let Some((member, _)) = inter.next().await? {
+                        if limit_len > 0 && resp.len() >= limit_len {
+                            return Err(REDIS_INTER_TOO_LARGE_ERR);
+                        }
+                        resp.push(resp_bulk(member));
+                    }
+                    Ok(resp_array(resp))
+                }
+                .boxed()
+            })
+            .await
+    }
+
+    /// Cardinality of the intersection of the sets, stops counting at
+    /// `limit` if not 0. The members of the smallest set are scanned and
+    /// probed in each of the other sets with a batch get of their data keys,
+    /// so the larger sets are never scanned.
+    pub async fn do_async_txnkv_sintercard(
+        mut self,
+        keys: &[String],
+        limit: usize,
+    ) -> AsyncResult<Frame> {
+        let mut client = get_txn_client()?;
+        let keys = keys.to_owned();
+
+        client
+            .exec_in_txn(self.txn.clone(), |txn_rc| {
+                async move {
+                    if self.txn.is_none() {
+                        self.txn = Some(txn_rc.clone());
+                    }
+
+                    let mut sets = Vec::with_capacity(keys.len());
+                    for key in keys {
+                        let meta_key = KEY_ENCODER.encode_txnkv_meta_key(&key);
+                        let meta_value = match txn_rc.lock().await.get(meta_key).await? {
+                            Some(meta_value) => meta_value,
+                            // a missing key makes the intersection empty
+                            None => return Ok(resp_int(0)),
+                        };
+                        if !matches!(KeyDecoder::decode_key_type(&meta_value), DataType::Set) {
+                            return Err(REDIS_WRONG_TYPE_ERR);
+                        }
+                        let (ttl, version, _) = KeyDecoder::decode_key_meta(&meta_value);
+                        if key_is_expired(ttl) {
+                            return Ok(resp_int(0));
+                        }
+                        let size = self.clone().txnkv_sum_key_size(&key, version).await?;
+                        sets.push((key, version, size));
+                    }
+                    let smallest = (0..sets.len()).min_by_key(|&idx| sets[idx].2).unwrap();
+                    let (key, version, _) = sets.swap_remove(smallest);
+                    // the data key of a member is this prefix followed by the member
+                    let probe_prefixes: Vec<Vec<u8>> = sets
+                        .iter()
+                        .map(|(key, version, _)| {
+                            KEY_ENCODER
+                                .encode_txnkv_set_data_key_start(key, *version)
+                                .into()
+                        })
+                        .collect();
+
+                    let range = KEY_ENCODER.encode_txnkv_set_data_key_range(&key, version);
+                    let mut batches = txn_rc
+                        .lock()
+                        .await
+                        .scan_keys_stream(range, u32::MAX)
+                        .await?
+                        .map(|k| KeyDecoder::decode_key_set_member_from_datakey(&key, k))
+                        .chunks(SINTERCARD_PROBE_BATCH);
+                    let mut count = 0;
+                    while let Some(mut members) = batches.next().await {
+                        for prefix in &probe_prefixes {
+                            let data_keys: Vec<Key> = members
+                                .iter()
+                                .map(|member| {
+                                    [prefix.as_slice(), member.as_slice()].concat().into()
+                                })
+                                .collect();
+                            let found: HashSet<Key> = txn_rc
+                                .lock()
+                                .await
+                                .batch_get(data_keys)
+                                .await?
+                                .map(|kv| kv.0)
+                                .collect();
+                            members.retain(|member| {
+                                found.contains(&Key::from(
+                                    [prefix.as_slice(), member.as_slice()].concat(),
+                                ))
+                            });
+                            if members.is_empty() {
+                                break;
+                            }
+                        }
+                        count += members.len();
+                        if limit > 0 && count >= limit {
+                            return Ok(resp_int(limit as i64));
+                        }
+                    }
+                    Ok(resp_int(count as i64))
+                }
+                .boxed()
+            })
+            .await
+    }
+
     pub async fn do_async_txnkv_srem(
         mut self,
         key: &str,
@@ -585,14 +722,15 @@ impl SetCommandCtx {
         }
     }
 
-    /// spop will pop members by alphabetical order
-    pub async fn do_async_txnkv_spop(mut self, key: &str, count: u64) -> AsyncResult<Frame> {
+    /// Pop the first `count` members by alphabetical order in one
+    /// transaction, the meta key is deleted with the last member.
+    async fn txnkv_pop_members(mut self, key: &str, count: u64) -> AsyncResult<Vec<Frame>> {
         let mut client = get_txn_client()?;
         let key = key.to_owned();
         let meta_key = KEY_ENCODER.encode_txnkv_meta_key(&key);
         let rand_idx = gen_next_meta_index();
 
-        let resp = client
+        client
             .exec_in_txn(self.txn.clone(), |txn_rc| {
                 async move {
                     if self.txn.is_none() {
@@ -676,9 +814,12 @@ impl SetCommandCtx {
                 }
                 .boxed()
             })
-            .await;
+            .await
+    }
 
-        match resp {
+    /// spop will pop members by alphabetical order
+    pub async fn do_async_txnkv_spop(self, key: &str, count: u64) -> AsyncResult<Frame> {
+        match self.txnkv_pop_members(key, count).await {
             Ok(mut v) => {
                 if count == 1 {
                     if v.is_empty() {
@@ -694,6 +835,15 @@ impl SetCommandCtx {
         }
     }
 
+    /// Pop up to `count` members, always replied as an array, so consumers of
+    /// a set used as a work queue can take a batch of items atomically.
+    pub async fn do_async_txnkv_sdrain(self, key: &str, count: u64) -> AsyncResult<Frame> {
+        match self.txnkv_pop_members(key, count).await {
+            Ok(v) => Ok(resp_array(v)),
+            Err(e) => Ok(resp_err(e)),
+        }
+    }
+
     pub async fn do_async_txnkv_set_del(mut self, key: &str) -> AsyncResult<i64> {
         let mut client = get_txn_client()?;
         let key = key.to_owned();
@@ -762,9 +912,10 @@ impl SetCommandCtx {
     pub async fn do_async_txnkv_set_expire_if_needed(mut self, key: &str) -> AsyncResult<i64> {
         let mut client = get_txn_client()?;
         let key = key.to_owned();
+        let expired_key = key.clone();
         let meta_key = KEY_ENCODER.encode_txnkv_meta_key(&key);
 
-        client
+        let expired = client
             .exec_in_txn(self.txn.clone(), |txn_rc| {
                 async move {
                     if self.txn.is_none() {
@@ -776,7 +927,7 @@ impl SetCommandCtx {
                         Some(meta_value) => {
                             let (ttl, version, _) = KeyDecoder::decode_key_meta(&meta_value);
                             if !key_is_expired(ttl) {
-                                return Ok(0);
+                                return Ok(None);
                             }
                             drop(txn);
                             let size = self.txnkv_sum_key_size(&key, version).await?;
@@ -815,17 +966,23 @@ impl SetCommandCtx {
 
                                 txn.delete(meta_key).await?;
                             }
-                            REMOVED_EXPIRED_KEY_COUNTER
-                                .with_label_values(&["set"])
-                                .inc();
+                            count_expired_key("set");
 
-                            Ok(1)
+                            Ok(Some(ttl))
                         }
-                        None => Ok(0),
+                        None => Ok(None),
                     }
                 }
                 .boxed()
             })
-            .await
+            .await?;
+        match expired {
+            Some(expire_at) => {
+                fire(EVENT_EXPIRED, &expired_key).await;
+                ship_expired(DataType::Set, &expired_key, expire_at).await;
+                Ok(1)
+            }
+            None => Ok(0),
+        }
     }
 }
@@ -0,0 +1,363 @@
+//! SORT on a list, set or sorted set.
+//!
+//! The elements are read whole in one transaction, then the weights of BY
+//! and the values of GET are looked up with a batch get of the keys the
+//! pattern makes of the elements, and for `key->field` patterns with a
+//! second batch get of the fields of the hashes. With STORE the result
+//! replaces the destination as a list in the same transaction. The elements
+//! are limited to `cmd_sort_length_limit` as they are all held in memory.
+
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use bytes::Bytes;
+use futures::future::FutureExt;
+use tikv_client::{Key, Value};
+use tokio::sync::Mutex;
+
+use super::backend::Transaction;
+use super::encoding::{DataType, KeyDecoder};
+use super::errors::{
+    AsyncResult, RTError, REDIS_SORT_SCORE_ERR, REDIS_SORT_TOO_LARGE_ERR, REDIS_WRONG_TYPE_ERR,
+};
+use super::get_txn_client;
+use super::list::ListCommandCtx;
+use super::set::SetCommandCtx;
+use super::string::StringCommandCtx;
+use super::zset::ZsetCommandCtx;
+use super::KEY_ENCODER;
+use crate::cmd_sort_length_limit_or_default;
+use crate::utils::{key_is_expired, resp_array, resp_bulk, resp_err, resp_int, resp_nil};
+use crate::Frame;
+
+/// The options of `SORT key [BY pattern] [LIMIT offset count] [GET pattern
+/// ...] [ASC|DESC] [ALPHA] [STORE destination]`
+#[derive(Debug, Clone, Default)]
+pub struct SortOptions {
+    pub by: Option<String>,
+    pub limit: Option<(i64, i64)>,
+    pub gets: Vec<String>,
+    pub desc: bool,
+    pub alpha: bool,
+    pub store: Option<String>,
+}
+
+#[derive(Clone)]
+pub struct SortCommandCtx {
+    txn: Option<Arc<Mutex<Transaction>>>,
+}
+
+/// The key made of `pattern` with its first `*` replaced by the element, and
+/// the hash field after the `->` following it. None if there is no `*`.
+fn pattern_key(pattern: &str, element: &[u8]) -> Option<(String, Option<String>)> {
+    let star = pattern.find('*')?;
+    let (key_pattern, field) = match pattern[star..].find("->") {
+        Some(pos) if star + pos + 2 < pattern.len() => (
+            &pattern[..star + pos],
+            Some(pattern[star + pos + 2..].to_owned()),
+        ),
+        _ => (pattern, None),
+    };
+    let key = format!(
+        "{}{}{}",
+        &key_pattern[..star],
+        String::from_utf8_lossy(element),
+        &key_pattern[star + 1..]
+    );
+    Some((key, field))
+}
+
+/// The reply of a read of the source as its elements, or its error
+pub(super) fn frame_elements(frame: Frame) -> AsyncResult<Vec<Bytes>> {
+    match frame {
+        Frame::Array(items) => Ok(items
+            .into_iter()
+            .filter_map(|item| match item {
+                Frame::Bulk(element) => Some(element),
+                _ => None,
+            })
+            .collect()),
+        Frame::ErrorOwned(e) => Err(RTError::Owned(e)),
+        Frame::ErrorString(e) => Err(RTError::String(e)),
+        _ => Ok(vec![]),
+    }
+}
+
+/// Fails with the error of a write reply
+pub(super) fn check_reply(frame: Frame) -> AsyncResult<()> {
+    match frame {
+        Frame::ErrorOwned(e) => Err(RTError::Owned(e)),
+        Frame::ErrorString(e) => Err(RTError::String(e)),
+        _ => Ok(()),
+    }
+}
+
+/// An element with the weight it is sorted by
+struct SortItem {
+    element: Bytes,
+    weight: Option<Vec<u8>>,
+    score: f64,
+}
+
+/// Compare like Redis, ALPHA with missing weights first, otherwise the
+/// weights as numbers where missing ones are 0, and equal ones by the
+/// elements themselves so the order is defined
+fn compare(a: &SortItem, b: &SortItem, alpha: bool) -> Ordering {
+    let cmp = if alpha {
+        a.weight.cmp(&b.weight)
+    } else {
+        a.score.partial_cmp(&b.score).unwrap_or(Ordering::Equal)
+    };
+    cmp.then_with(|| a.element.cmp(&b.element))
+}
+
+impl SortCommandCtx {
+    pub fn new(txn: Option<Arc<Mutex<Transaction>>>) -> Self {
+        SortCommandCtx { txn }
+    }
+
+    /// The values the pattern points to for each element, `#` for the
+    /// element itself. Only existing strings, or fields of existing hashes
+    /// with `->`, have a value.
+    async fn lookup(
+        &self,
+        txn_rc: &Arc<Mutex<Transaction>>,
+        pattern: &str,
+        elements: &[Bytes],
+    ) -> AsyncResult<Vec<Option<Vec<u8>>>> {
+        if pattern == "#" {
+            return Ok(elements.iter().map(|e| Some(e.to_vec())).collect());
+        }
+        let keys: Vec<Option<(String, Option<String>)>> = elements
+            .iter()
+            .map(|element| pattern_key(pattern, element))
+            .collect();
+        let mut meta_keys: Vec<Key> = keys
+            .iter()
+            .flatten()
+            .map(|(key, _)| KEY_ENCODER.encode_txnkv_meta_key(key))
+            .collect();
+        meta_keys.sort();
+        meta_keys.dedup();
+
+        let mut txn = txn_rc.lock().await;
+        let metas: HashMap<Key, Value> = txn
+            .batch_get(meta_keys)
+            .await?
+            .map(|pair| (pair.0, pair.1))
+            .collect();
+
+        let mut values = Vec::with_capacity(keys.len());
+        let mut field_keys = vec![];
+        for (idx, key) in keys.iter().enumerate() {
+            let (key, field) = match key {
+                Some(key) => key,
+                None => {
+                    values.push(None);
+                    continue;
+                }
+            };
+            let meta = match metas.get(&KEY_ENCODER.encode_txnkv_meta_key(key)) {
+                Some(meta) if !key_is_expired(KeyDecoder::decode_key_ttl(meta)) => meta,
+                _ => {
+                    values.push(None);
+                    continue;
+                }
+            };
+            match (KeyDecoder::decode_key_type(meta), field) {
+                (DataType::String, None) => {
+                    values.push(Some(KeyDecoder::decode_key_string_value(meta)?));
+                }
+                (DataType::Hash, Some(field)) => {
+                    let (_, version, _) = KeyDecoder::decode_key_meta(meta);
+                    let data_key = KEY_ENCODER.encode_txnkv_hash_data_key(key, field, version);
+                    field_keys.push((idx, data_key));
+                    values.push(None);
+                }
+                _ => values.push(None),
+            }
+        }
+
+        if !field_keys.is_empty() {
+            let fields: HashMap<Key, Value> = txn
+                .batch_get(
+                    field_keys
+                        .iter()
+                        .map(|(_, k)| k.clone())
+                        .collect::<Vec<Key>>(),
+                )
+                .await?
+                .map(|pair| (pair.0, pair.1))
+                .collect();
+            for (idx, data_key) in field_keys {
+                values[idx] = fields.get(&data_key).cloned();
+            }
+        }
+        Ok(values)
+    }
+
+    /// Sort the elements of the list, set or sorted set by themselves or by
+    /// the weights of BY, and reply them or the values of GET. A BY pattern
+    /// without `*` keeps the order of the key.
+    pub async fn do_async_txnkv_sort(
+        mut self,
+        key: &str,
+        options: SortOptions,
+    ) -> AsyncResult<Frame> {
+        let mut client = get_txn_client()?;
+        let meta_key = KEY_ENCODER.encode_txnkv_meta_key(key);
+        let key = key.to_owned();
+        let limit_len = cmd_sort_length_limit_or_default() as usize;
+
+        // without STORE nothing is written, read with latest commit like GET
+        if self.txn.is_none() && options.store.is_none() {
+            let readonly_txn = client.begin_consistent_read().await?;
+            self.txn = Some(Arc::new(Mutex::new(readonly_txn)));
+        }
+
+        let resp = client
+            .exec_in_txn(self.txn.clone(), |txn_rc| {
+                async move {
+                    if self.txn.is_none() {
+                        self.txn = Some(txn_rc.clone());
+                    }
+                    let meta_value = txn_rc.lock().await.get(meta_key).await?;
+                    // the reads of each type check the ttl of the key
+                    let source = match meta_value {
+                        Some(meta_value) => match KeyDecoder::decode_key_type(&meta_value) {
+                            DataType::List => {
+                                ListCommandCtx::new(self.txn.clone())
+                                    .do_async_txnkv_lrange(&key, 0, -1)
+                                    .await?
+                            }
+                            DataType::Set => {
+                                SetCommandCtx::new(self.txn.clone())
+                                    .do_async_txnkv_smembers(&key)
+                                    .await?
+                            }
+                            DataType::Zset => {
+                                ZsetCommandCtx::new(self.txn.clone())
+                                    .do_async_txnkv_zrange(&key, 0, -1, false, false)
+                                    .await?
+                            }
+                            _ => return Err(REDIS_WRONG_TYPE_ERR),
+                        },
+                        None => resp_array(vec![]),
+                    };
+                    let mut elements = frame_elements(source)?;
+                    if limit_len > 0 && elements.len() > limit_len {
+                        return Err(REDIS_SORT_TOO_LARGE_ERR);
+                    }
+
+                    let dont_sort = options.by.as_ref().map_or(false, |by| !by.contains('*'));
+                    if dont_sort {
+                        if options.desc {
+                            elements.reverse();
+                        }
+                    } else {
+                        let weights = match &options.by {
+                            Some(by) => self.lookup(&txn_rc, by, &elements).await?,
+                            None => elements.iter().map(|e| Some(e.to_vec())).collect(),
+                        };
+                        let mut items = Vec::with_capacity(elements.len());
+                        for (element, weight) in elements.into_iter().zip(weights) {
+                            let mut score = 0.0;
+                            if !options.alpha {
+                                if let Some(weight) = &weight {
+                                    score = String::from_utf8_lossy(weight)
+                                        .parse::<f64>()
+                                        .ok()
+                                        .filter(|score| !score.is_nan())
+                                        .ok_or(REDIS_SORT_SCORE_ERR)?;
+                                }
+                            }
+                            items.push(SortItem {
+                                element,
+                                weight,
+                                score,
+                            });
+                        }
+                        items.sort_by(|a, b| {
+                            let cmp = compare(a, b, options.alpha);
+                            if options.desc {
+                                cmp.reverse()
+                            } else {
+                                cmp
+                            }
+                        });
+                        elements = items.into_iter().map(|item| item.element).collect();
+                    }
+
+                    let (offset, count) = match options.limit {
+                        Some((offset, count)) => (
+                            offset.max(0) as usize,
+                            if count < 0 {
+                                usize::MAX
+                            } else {
+                                count as usize
+                            },
+                        ),
+                        None => (0, usize::MAX),
+                    };
+                    let elements: Vec<Bytes> =
+                        elements.into_iter().skip(offset).take(count).collect();
+
+                    let results = if options.gets.is_empty() {
+                        elements.iter().map(|e| Some(e.to_vec())).collect()
+                    } else {
+                        let mut values = Vec::with_capacity(options.gets.len());
+                        for pattern in &options.gets {
+                            values.push(self.lookup(&txn_rc, pattern, &elements).await?);
+                        }
+                        let mut results = Vec::with_capacity(elements.len() * values.len());
+                        for idx in 0..elements.len() {
+                            for pattern_values in values.iter_mut() {
+                                results.push(pattern_values[idx].take());
+                            }
+                        }
+                        results
+                    };
+
+                    let dest = match &options.store {
+                        Some(dest) => dest,
+                        None => {
+                            return Ok(resp_array(
+                                results
+                                    .into_iter()
+                                    .map(|v| v.map_or_else(resp_nil, resp_bulk))
+                                    .collect(),
+                            ))
+                        }
+                    };
+                    // the destination may be of any type, it is deleted like DEL,
+                    // missing values are stored as empty strings
+                    check_reply(
+                        StringCommandCtx::new(self.txn.clone())
+                            .do_async_txnkv_del(&vec![dest.clone()])
+                            .await?,
+                    )?;
+                    let len = results.len() as i64;
+                    if len > 0 {
+                        let values = results
+                            .into_iter()
+                            .map(|v| Bytes::from(v.unwrap_or_default()))
+                            .collect();
+                        check_reply(
+                            ListCommandCtx::new(self.txn.clone())
+                                .do_async_txnkv_push(dest, &values, false)
+                                .await?,
+                        )?;
+                    }
+                    Ok(resp_int(len))
+                }
+                .boxed()
+            })
+            .await;
+
+        match resp {
+            Ok(frame) => Ok(frame),
+            Err(e) => Ok(resp_err(e)),
+        }
+    }
+}
@@ -0,0 +1,67 @@
+//! Write stamps of the user keys, checked by EXEC for WATCH.
+//!
+//! A transaction writing any key of a user key, its meta key or one of its
+//! data keys, also puts a new stamp of the user key when it commits. Writing
+//! an element of an existing collection does not always write its meta key,
+//! so EXEC compares the stamps read by WATCH to tell a watched key was
+//! written, or created if it had no stamp. EXEC writes the stamps back, or
+//! deletes them if they did not exist, so a write committed after its
+//! snapshot makes it fail to commit. Stamps expire `WATCH_STAMP_TTL_MS` after
+//! the last write of their key and are swept by the `sweep_watch_stamps` job,
+//! a key watched longer than that may abort EXEC though it was not written.
+
+use std::convert::TryInto;
+
+use futures::StreamExt;
+use rand::Rng;
+use tikv_client::{Key, Value};
+
+use super::errors::AsyncResult;
+use super::{get_txn_client, KEY_ENCODER};
+use crate::utils::{now_timestamp_in_millis, timestamp_from_ttl};
+
+const WATCH_STAMP_TTL_MS: u64 = 3600 * 1000;
+
+pub fn stamp_key(ukey: &str) -> Key {
+    KEY_ENCODER.encode_txnkv_watch_stamp_key(ukey)
+}
+
+/// Encode a new stamp: expire timestamp followed by random bytes, so two
+/// writes within the same millisecond have different stamps.
+pub fn new_stamp() -> Value {
+    let expire_at = timestamp_from_ttl(WATCH_STAMP_TTL_MS);
+    let mut value = expire_at.to_be_bytes().to_vec();
+    value.extend_from_slice(&rand::thread_rng().gen::<[u8; 8]>());
+    value
+}
+
+fn stamp_expired(value: &[u8], now: u64) -> bool {
+    value.len() < 8 || u64::from_be_bytes(value[..8].try_into().unwrap()) <= now
+}
+
+/// Delete at most `max_deletes` expired stamps, 0 for all of them, returns
+/// the number deleted.
+pub async fn sweep_expired_stamps(max_deletes: usize) -> AsyncResult<usize> {
+    let client = get_txn_client()?;
+    let mut txn = client.begin().await?;
+    let now = now_timestamp_in_millis();
+    let mut expired = vec![];
+    {
+        let mut iter = txn
+            .scan_stream(KEY_ENCODER.encode_txnkv_watch_stamp_key_range(), u32::MAX)
+            .await?;
+        while let Some(kv) = iter.next().await {
+            if stamp_expired(&kv.1, now) {
+                expired.push(kv.0);
+                if expired.len() == max_deletes {
+                    break;
+                }
+            }
+        }
+    }
+    for key in &expired {
+        txn.delete(key.clone()).await?;
+    }
+    txn.commit().await?;
+    Ok(expired.len())
+}
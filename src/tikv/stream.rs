@@ -0,0 +1,1219 @@
+//! Streams taken with XADD and read with XLEN, XRANGE, XREVRANGE and XREAD,
+//! or by the consumer groups of XGROUP, XREADGROUP, XACK, XPENDING and XCLAIM.
+//!
+//! The entries of a stream are data keys ordered by entry id under the
+//! version of the stream, the meta value keeps the number of entries and the
+//! last id given, so ids keep increasing even if the last entries are
+//! trimmed. Ranges are plain scans of the data keys, in reverse for XREVRANGE.
+//!
+//! A consumer group is a key under the same version holding the last id
+//! delivered to the group, and each entry delivered but not acknowledged yet
+//! is a pending key of the group ordered by id, holding its consumer, last
+//! delivery time and delivery count. Both are written in the transaction of
+//! the read, so a delivered entry stays pending across restarts until it is
+//! acknowledged or claimed by another consumer.
+
+use super::backend::Transaction;
+use super::client::get_version_for_new;
+use super::errors::*;
+use super::expiration::count_expired_key;
+use super::get_txn_client;
+use super::KEY_ENCODER;
+use super::{
+    encoding::{DataType, KeyDecoder},
+    errors::AsyncResult,
+};
+use crate::async_del_stream_threshold_or_default;
+use crate::cmdlog::ship_expired;
+use crate::triggers::{fire, EVENT_EXPIRED};
+use crate::utils::{
+    expire_timestamp_of_new_key, now_timestamp_in_millis, resp_array, resp_bulk, resp_err,
+    resp_int, resp_nil, resp_ok,
+};
+use crate::{utils::key_is_expired, Frame};
+use bytes::Bytes;
+use futures::future::FutureExt;
+use futures::StreamExt;
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use tikv_client::KvPair;
+use tokio::sync::Mutex;
+
+/// Entry id of a stream, milliseconds and sequence number
+pub type StreamId = (u64, u64);
+
+/// Id of the entry added by XADD
+#[derive(Debug, Clone, Copy)]
+pub enum NewEntryId {
+    /// `*`, the current time and the next sequence number
+    Auto,
+    /// `ms-*`, the next sequence number in the given milliseconds
+    AutoSeq(u64),
+    Explicit(StreamId),
+}
+
+/// Pending entries listed by the extended form of XPENDING
+#[derive(Debug, Clone)]
+pub struct PendingRange {
+    /// minimum idle time in milliseconds
+    pub idle: u64,
+    pub start: StreamId,
+    pub end: StreamId,
+    pub count: u32,
+    pub consumer: Option<String>,
+}
+
+/// Options of XCLAIM
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ClaimOptions {
+    /// `IDLE ms`, the idle time of the claimed entries
+    pub idle: Option<u64>,
+    /// `TIME ms`, the last delivery time of the claimed entries
+    pub time: Option<u64>,
+    /// `RETRYCOUNT count`, the delivery count of the claimed entries
+    pub retry_count: Option<u64>,
+    /// `FORCE`, claim entries of the stream even if they are not pending
+    pub force: bool,
+    /// `JUSTID`, reply with ids and keep the delivery count
+    pub justid: bool,
+}
+
+/// Parse the `ms-seq` form of an id, or `ms` with the sequence `default_seq`,
+/// `-` and `+` are the smallest and greatest ids.
+pub fn parse_stream_id(id: &str, default_seq: u64) -> Option<StreamId> {
+    match id {
+        "-" => return Some((0, 0)),
+        "+" => return Some((u64::MAX, u64::MAX)),
+        _ => {}
+    }
+    match id.split_once('-') {
+        Some((ms, seq)) => Some((ms.parse().ok()?, seq.parse().ok()?)),
+        None => Some((id.parse().ok()?, default_seq)),
+    }
+}
+
+pub fn format_stream_id(id: StreamId) -> String {
+    format!("{}-{}", id.0, id.1)
+}
+
+/// The id right after `id`, None if `id` is the greatest one
+pub fn next_stream_id(id: StreamId) -> Option<StreamId> {
+    match id {
+        (u64::MAX, u64::MAX) => None,
+        (ms, u64::MAX) => Some((ms + 1, 0)),
+        (ms, seq) => Some((ms, seq + 1)),
+    }
+}
+
+/// The id right before `id`, None if `id` is the smallest one
+pub fn prev_stream_id(id: StreamId) -> Option<StreamId> {
+    match id {
+        (0, 0) => None,
+        (ms, 0) => Some((ms - 1, u64::MAX)),
+        (ms, seq) => Some((ms, seq - 1)),
+    }
+}
+
+/// Id of the entry added after `last`, an error if it is not greater
+fn new_entry_id(id: NewEntryId, last: StreamId) -> AsyncResult<StreamId> {
+    let id = match id {
+        NewEntryId::Auto => {
+            let now = now_timestamp_in_millis();
+            if now > last.0 {
+                (now, 0)
+            } else {
+                next_stream_id(last).ok_or(REDIS_STREAM_ID_TOO_SMALL_ERR)?
+            }
+        }
+        NewEntryId::AutoSeq(ms) => {
+            if ms > last.0 {
+                (ms, 0)
+            } else if ms == last.0 && last.1 < u64::MAX {
+                (ms, last.1 + 1)
+            } else {
+                return Err(REDIS_STREAM_ID_TOO_SMALL_ERR);
+            }
+        }
+        NewEntryId::Explicit(id) => id,
+    };
+    if id == (0, 0) {
+        return Err(REDIS_STREAM_ID_ZERO_ERR);
+    }
+    if id <= last {
+        return Err(REDIS_STREAM_ID_TOO_SMALL_ERR);
+    }
+    Ok(id)
+}
+
+fn no_group_err(key: &str, group: &str) -> RTError {
+    RTError::Owned(format!(
+        "NOGROUP No such key '{}' or consumer group '{}'",
+        key, group
+    ))
+}
+
+/// Reply of an entry, its id and its fields and values
+fn entry_frame(key: &str, kv: KvPair) -> Frame {
+    let id = KeyDecoder::decode_key_stream_id_from_datakey(key, kv.0);
+    let pairs = KeyDecoder::decode_key_stream_data_value(&kv.1)
+        .into_iter()
+        .map(resp_bulk)
+        .collect();
+    resp_array(vec![
+        resp_bulk(format_stream_id(id).into_bytes()),
+        resp_array(pairs),
+    ])
+}
+
+#[derive(Clone)]
+pub struct StreamCommandCtx {
+    txn: Option<Arc<Mutex<Transaction>>>,
+}
+
+impl StreamCommandCtx {
+    pub fn new(txn: Option<Arc<Mutex<Transaction>>>) -> Self {
+        StreamCommandCtx { txn }
+    }
+
+    /// Add an entry with the fields and values in `pairs`, and trim the oldest
+    /// entries to keep at most `maxlen` of them. Replies with the id of the
+    /// entry, or nil if the stream does not exist and `nomkstream` is set.
+    pub async fn do_async_txnkv_xadd(
+        mut self,
+        key: &str,
+        id: NewEntryId,
+        pairs: &[Bytes],
+        maxlen: Option<u64>,
+        nomkstream: bool,
+    ) -> AsyncResult<Frame> {
+        let mut client = get_txn_client()?;
+        let key = key.to_owned();
+        let pairs = pairs.to_owned();
+        let meta_key = KEY_ENCODER.encode_txnkv_meta_key(&key);
+
+        let resp = client
+            .exec_in_txn(self.txn.clone(), |txn_rc| {
+                async move {
+                    if self.txn.is_none() {
+                        self.txn = Some(txn_rc.clone());
+                    }
+                    let mut txn = txn_rc.lock().await;
+                    let (ttl, version, mut len, last_id) = match txn.get(meta_key.clone()).await? {
+                        Some(meta_value) => {
+                            // check key type and ttl
+                            if !matches!(KeyDecoder::decode_key_type(&meta_value), DataType::Stream)
+                            {
+                                return Err(REDIS_WRONG_TYPE_ERR);
+                            }
+                            let (ttl, version, len, last_id) =
+                                KeyDecoder::decode_key_stream_meta(&meta_value);
+                            if key_is_expired(ttl) {
+                                drop(txn);
+                                self.clone()
+                                    .do_async_txnkv_stream_expire_if_needed(&key)
+                                    .await?;
+                                if nomkstream {
+                                    return Ok(None);
+                                }
+                                let version = get_version_for_new(&key, txn_rc.clone()).await?;
+                                txn = txn_rc.lock().await;
+                                (expire_timestamp_of_new_key(0), version, 0, (0, 0))
+                            } else {
+                                (ttl, version, len, last_id)
+                            }
+                        }
+                        None => {
+                            if nomkstream {
+                                return Ok(None);
+                            }
+                            // get next version available for new key
+                            drop(txn);
+                            let version = get_version_for_new(&key, txn_rc.clone()).await?;
+                            txn = txn_rc.lock().await;
+                            (expire_timestamp_of_new_key(0), version, 0, (0, 0))
+                        }
+                    };
+
+                    let id = new_entry_id(id, last_id)?;
+                    let maxlen = maxlen.unwrap_or(u64::MAX);
+                    // trim before adding, the new entry is the last one to go
+                    if len >= maxlen {
+                        let trimmed = len + 1 - maxlen.max(1);
+                        let bound_range =
+                            KEY_ENCODER.encode_txnkv_stream_data_key_range(&key, version);
+                        let keys: Vec<_> = txn
+                            .scan_keys(bound_range, trimmed.min(u32::MAX as u64) as u32)
+                            .await?
+                            .collect();
+                        for k in keys {
+                            txn.delete(k).await?;
+                            len -= 1;
+                        }
+                    }
+                    if maxlen > 0 {
+                        let data_key = KEY_ENCODER.encode_txnkv_stream_data_key(&key, id, version);
+                        txn.put(data_key, KEY_ENCODER.encode_txnkv_stream_data_value(&pairs))
+                            .await?;
+                        len += 1;
+                    }
+
+                    let meta_value =
+                        KEY_ENCODER.encode_txnkv_stream_meta_value(ttl, version, len, id);
+                    txn.put(meta_key, meta_value).await?;
+                    Ok(Some(id))
+                }
+                .boxed()
+            })
+            .await;
+
+        match resp {
+            Ok(Some(id)) => Ok(resp_bulk(format_stream_id(id).into_bytes())),
+            Ok(None) => Ok(resp_nil()),
+            Err(e) => Ok(resp_err(e)),
+        }
+    }
+
+    pub async fn do_async_txnkv_xlen(mut self, key: &str) -> AsyncResult<Frame> {
+        let mut client = get_txn_client()?;
+        let key = key.to_owned();
+        let meta_key = KEY_ENCODER.encode_txnkv_meta_key(&key);
+
+        client
+            .exec_in_txn(self.txn.clone(), |txn_rc| {
+                async move {
+                    if self.txn.is_none() {
+                        self.txn = Some(txn_rc.clone());
+                    }
+
+                    let mut txn = txn_rc.lock().await;
+                    match txn.get(meta_key).await? {
+                        Some(meta_value) => {
+                            // check type and ttl
+                            if !matches!(KeyDecoder::decode_key_type(&meta_value), DataType::Stream)
+                            {
+                                return Ok(resp_err(REDIS_WRONG_TYPE_ERR));
+                            }
+                            let (ttl, _, len, _) = KeyDecoder::decode_key_stream_meta(&meta_value);
+                            if key_is_expired(ttl) {
+                                drop(txn);
+                                self.clone()
+                                    .do_async_txnkv_stream_expire_if_needed(&key)
+                                    .await?;
+                                return Ok(resp_int(0));
+                            }
+                            Ok(resp_int(len as i64))
+                        }
+                        None => Ok(resp_int(0)),
+                    }
+                }
+                .boxed()
+            })
+            .await
+    }
+
+    /// Entries with ids in `[start, end]`, at most `count` of them if not 0,
+    /// from the greatest id if `rev`.
+    pub async fn do_async_txnkv_xrange(
+        mut self,
+        key: &str,
+        start: StreamId,
+        end: StreamId,
+        count: u32,
+        rev: bool,
+    ) -> AsyncResult<Frame> {
+        let mut client = get_txn_client()?;
+        let key = key.to_owned();
+        let meta_key = KEY_ENCODER.encode_txnkv_meta_key(&key);
+        let limit = if count == 0 { u32::MAX } else { count };
+
+        client
+            .exec_in_txn(self.txn.clone(), |txn_rc| {
+                async move {
+                    if self.txn.is_none() {
+                        self.txn = Some(txn_rc.clone());
+                    }
+
+                    let mut txn = txn_rc.lock().await;
+                    match txn.get(meta_key).await? {
+                        Some(meta_value) => {
+                            // check key type and ttl
+                            if !matches!(KeyDecoder::decode_key_type(&meta_value), DataType::Stream)
+                            {
+                                return Ok(resp_err(REDIS_WRONG_TYPE_ERR));
+                            }
+                            let (ttl, version, _, _) =
+                                KeyDecoder::decode_key_stream_meta(&meta_value);
+                            if key_is_expired(ttl) {
+                                drop(txn);
+                                self.clone()
+                                    .do_async_txnkv_stream_expire_if_needed(&key)
+                                    .await?;
+                                return Ok(resp_array(vec![]));
+                            }
+                            if start > end {
+                                return Ok(resp_array(vec![]));
+                            }
+
+                            let bound_range = KEY_ENCODER
+                                .encode_txnkv_stream_data_key_id_range(&key, start, end, version);
+                            let iter = if rev {
+                                txn.scan_reverse_stream(bound_range, limit).await?
+                            } else {
+                                txn.scan_stream(bound_range, limit).await?
+                            };
+                            let entries: Vec<Frame> =
+                                iter.map(|kv| entry_frame(&key, kv)).collect().await;
+                            Ok(resp_array(entries))
+                        }
+                        None => Ok(resp_array(vec![])),
+                    }
+                }
+                .boxed()
+            })
+            .await
+    }
+
+    /// Entries of each key with ids greater than the id given for the key, at
+    /// most `count` of them per key if not 0. A key without a given id, `$`,
+    /// has no entries as reads never block. Replies nil if no key has entries.
+    pub async fn do_async_txnkv_xread(
+        mut self,
+        keys: &[String],
+        ids: &[Option<StreamId>],
+        count: u32,
+    ) -> AsyncResult<Frame> {
+        let mut client = get_txn_client()?;
+        let keys = keys.to_owned();
+        let ids = ids.to_owned();
+        let limit = if count == 0 { u32::MAX } else { count };
+
+        client
+            .exec_in_txn(self.txn.clone(), |txn_rc| {
+                async move {
+                    if self.txn.is_none() {
+                        self.txn = Some(txn_rc.clone());
+                    }
+
+                    let mut resp = vec![];
+                    let mut txn = txn_rc.lock().await;
+                    for (key, id) in keys.iter().zip(ids) {
+                        let meta_key = KEY_ENCODER.encode_txnkv_meta_key(key);
+                        let meta_value = match txn.get(meta_key).await? {
+                            Some(meta_value) => meta_value,
+                            None => continue,
+                        };
+                        if !matches!(KeyDecoder::decode_key_type(&meta_value), DataType::Stream) {
+                            return Ok(resp_err(REDIS_WRONG_TYPE_ERR));
+                        }
+                        let (ttl, version, _, _) = KeyDecoder::decode_key_stream_meta(&meta_value);
+                        if key_is_expired(ttl) {
+                            drop(txn);
+                            self.clone()
+                                .do_async_txnkv_stream_expire_if_needed(key)
+                                .await?;
+                            txn = txn_rc.lock().await;
+                            continue;
+                        }
+                        let start = match id.and_then(next_stream_id) {
+                            Some(start) => start,
+                            None => continue,
+                        };
+
+                        let bound_range = KEY_ENCODER.encode_txnkv_stream_data_key_id_range(
+                            key,
+                            start,
+                            (u64::MAX, u64::MAX),
+                            version,
+                        );
+                        let entries: Vec<Frame> = txn
+                            .scan_stream(bound_range, limit)
+                            .await?
+                            .map(|kv| entry_frame(key, kv))
+                            .collect()
+                            .await;
+                        if !entries.is_empty() {
+                            resp.push(resp_array(vec![
+                                resp_bulk(key.as_bytes().to_vec()),
+                                resp_array(entries),
+                            ]));
+                        }
+                    }
+                    if resp.is_empty() {
+                        return Ok(resp_nil());
+                    }
+                    Ok(resp_array(resp))
+                }
+                .boxed()
+            })
+            .await
+    }
+
+    /// Create the consumer group `group` delivering the entries after `id`,
+    /// or after the last entry if None. An empty stream is created for a
+    /// missing key if `mkstream`.
+    pub async fn do_async_txnkv_xgroup_create(
+        mut self,
+        key: &str,
+        group: &str,
+        id: Option<StreamId>,
+        mkstream: bool,
+    ) -> AsyncResult<Frame> {
+        let mut client = get_txn_client()?;
+        let key = key.to_owned();
+        let group = group.to_owned();
+
+        let resp = client
+            .exec_in_txn(self.txn.clone(), |txn_rc| {
+                async move {
+                    if self.txn.is_none() {
+                        self.txn = Some(txn_rc.clone());
+                    }
+
+                    let (version, last_id) = match self.get_stream_meta(&txn_rc, &key).await? {
+                        Some((_, version, _, last_id)) => (version, last_id),
+                        None => {
+                            if !mkstream {
+                                return Err(REDIS_XGROUP_KEY_REQUIRED_ERR);
+                            }
+                            let version = get_version_for_new(&key, txn_rc.clone()).await?;
+                            let meta_key = KEY_ENCODER.encode_txnkv_meta_key(&key);
+                            let meta_value = KEY_ENCODER.encode_txnkv_stream_meta_value(
+                                expire_timestamp_of_new_key(0),
+                                version,
+                                0,
+                                (0, 0),
+                            );
+                            txn_rc.lock().await.put(meta_key, meta_value).await?;
+                            (version, (0, 0))
+                        }
+                    };
+
+                    let mut txn = txn_rc.lock().await;
+                    let group_key =
+                        KEY_ENCODER.encode_txnkv_stream_group_key(&key, &group, version);
+                    if txn.key_exists(group_key.clone()).await? {
+                        return Err(REDIS_BUSYGROUP_ERR);
+                    }
+                    let group_value =
+                        KEY_ENCODER.encode_txnkv_stream_group_value(id.unwrap_or(last_id));
+                    txn.put(group_key, group_value).await?;
+                    Ok(())
+                }
+                .boxed()
+            })
+            .await;
+
+        match resp {
+            Ok(()) => Ok(resp_ok()),
+            Err(e) => Ok(resp_err(e)),
+        }
+    }
+
+    /// Remove the consumer group and its pending entries, replies with the
+    /// number of groups removed.
+    pub async fn do_async_txnkv_xgroup_destroy(
+        mut self,
+        key: &str,
+        group: &str,
+    ) -> AsyncResult<Frame> {
+        let mut client = get_txn_client()?;
+        let key = key.to_owned();
+        let group = group.to_owned();
+
+        let resp = client
+            .exec_in_txn(self.txn.clone(), |txn_rc| {
+                async move {
+                    if self.txn.is_none() {
+                        self.txn = Some(txn_rc.clone());
+                    }
+
+                    let version = match self.get_stream_meta(&txn_rc, &key).await? {
+                        Some((_, version, _, _)) => version,
+                        None => return Err(REDIS_XGROUP_KEY_REQUIRED_ERR),
+                    };
+                    let mut txn = txn_rc.lock().await;
+                    let group_key =
+                        KEY_ENCODER.encode_txnkv_stream_group_key(&key, &group, version);
+                    if !txn.key_exists(group_key.clone()).await? {
+                        return Ok(0);
+                    }
+                    txn.delete(group_key).await?;
+
+                    let bound_range =
+                        KEY_ENCODER.encode_txnkv_stream_pending_key_range(&key, &group, version);
+                    let mut iter = txn.scan_keys_stream(bound_range, u32::MAX).await?;
+                    while let Some(k) = iter.next().await {
+                        txn.delete(k).await?;
+                    }
+                    Ok(1)
+                }
+                .boxed()
+            })
+            .await;
+
+        match resp {
+            Ok(n) => Ok(resp_int(n)),
+            Err(e) => Ok(resp_err(e)),
+        }
+    }
+
+    /// Set the last id delivered to the group, the last entry if None
+    pub async fn do_async_txnkv_xgroup_setid(
+        mut self,
+        key: &str,
+        group: &str,
+        id: Option<StreamId>,
+    ) -> AsyncResult<Frame> {
+        let mut client = get_txn_client()?;
+        let key = key.to_owned();
+        let group = group.to_owned();
+
+        let resp = client
+            .exec_in_txn(self.txn.clone(), |txn_rc| {
+                async move {
+                    if self.txn.is_none() {
+                        self.txn = Some(txn_rc.clone());
+                    }
+
+                    let (version, last_id) = match self.get_stream_meta(&txn_rc, &key).await? {
+                        Some((_, version, _, last_id)) => (version, last_id),
+                        None => return Err(REDIS_XGROUP_KEY_REQUIRED_ERR),
+                    };
+                    let mut txn = txn_rc.lock().await;
+                    let group_key =
+                        KEY_ENCODER.encode_txnkv_stream_group_key(&key, &group, version);
+                    if !txn.key_exists(group_key.clone()).await? {
+                        return Err(no_group_err(&key, &group));
+                    }
+                    let group_value =
+                        KEY_ENCODER.encode_txnkv_stream_group_value(id.unwrap_or(last_id));
+                    txn.put(group_key, group_value).await?;
+                    Ok(())
+                }
+                .boxed()
+            })
+            .await;
+
+        match resp {
+            Ok(()) => Ok(resp_ok()),
+            Err(e) => Ok(resp_err(e)),
+        }
+    }
+
+    /// Drop the pending entries of a consumer, replies with their number
+    pub async fn do_async_txnkv_xgroup_delconsumer(
+        mut self,
+        key: &str,
+        group: &str,
+        consumer: &str,
+    ) -> AsyncResult<Frame> {
+        let mut client = get_txn_client()?;
+        let key = key.to_owned();
+        let group = group.to_owned();
+        let consumer = consumer.to_owned();
+
+        let resp = client
+            .exec_in_txn(self.txn.clone(), |txn_rc| {
+                async move {
+                    if self.txn.is_none() {
+                        self.txn = Some(txn_rc.clone());
+                    }
+
+                    let version = match self.get_stream_meta(&txn_rc, &key).await? {
+                        Some((_, version, _, _)) => version,
+                        None => return Err(REDIS_XGROUP_KEY_REQUIRED_ERR),
+                    };
+                    let mut txn = txn_rc.lock().await;
+                    let group_key =
+                        KEY_ENCODER.encode_txnkv_stream_group_key(&key, &group, version);
+                    if !txn.key_exists(group_key).await? {
+                        return Err(no_group_err(&key, &group));
+                    }
+
+                    let bound_range =
+                        KEY_ENCODER.encode_txnkv_stream_pending_key_range(&key, &group, version);
+                    let pending: Vec<KvPair> = txn.scan(bound_range, u32::MAX).await?.collect();
+                    let mut deleted = 0;
+                    for kv in pending {
+                        let (_, _, owner) = KeyDecoder::decode_key_stream_pending_value(&kv.1);
+                        if owner == consumer {
+                            txn.delete(kv.0).await?;
+                            deleted += 1;
+                        }
+                    }
+                    Ok(deleted)
+                }
+                .boxed()
+            })
+            .await;
+
+        match resp {
+            Ok(n) => Ok(resp_int(n)),
+            Err(e) => Ok(resp_err(e)),
+        }
+    }
+
+    /// Read for `consumer` of the group, at most `count` entries per key if
+    /// not 0. A key without a given id, `>`, gets the entries never delivered
+    /// to the group, which become pending for the consumer unless `noack`.
+    /// Otherwise the key gets the entries pending for the consumer after the
+    /// id, nil in place of the fields of entries trimmed since.
+    #[allow(clippy::too_many_arguments)]
+    pub async fn do_async_txnkv_xreadgroup(
+        mut self,
+        group: &str,
+        consumer: &str,
+        keys: &[String],
+        ids: &[Option<StreamId>],
+        count: u32,
+        noack: bool,
+    ) -> AsyncResult<Frame> {
+        let mut client = get_txn_client()?;
+        let group = group.to_owned();
+        let consumer = consumer.to_owned();
+        let keys = keys.to_owned();
+        let ids = ids.to_owned();
+        let limit = if count == 0 { u32::MAX } else { count };
+
+        let resp = client
+            .exec_in_txn(self.txn.clone(), |txn_rc| {
+                async move {
+                    if self.txn.is_none() {
+                        self.txn = Some(txn_rc.clone());
+                    }
+
+                    let now = now_timestamp_in_millis();
+                    let mut resp = vec![];
+                    for (key, id) in keys.iter().zip(ids) {
+                        let version = match self.get_stream_meta(&txn_rc, key).await? {
+                            Some((_, version, _, _)) => version,
+                            None => return Err(no_group_err(key, &group)),
+                        };
+                        let mut txn = txn_rc.lock().await;
+                        let group_key =
+                            KEY_ENCODER.encode_txnkv_stream_group_key(key, &group, version);
+                        let last_delivered = match txn.get(group_key.clone()).await? {
+                            Some(group_value) => {
+                                KeyDecoder::decode_key_stream_group_value(&group_value)
+                            }
+                            None => return Err(no_group_err(key, &group)),
+                        };
+
+                        let entries = match id {
+                            None => {
+                                let start = match next_stream_id(last_delivered) {
+                                    Some(start) => start,
+                                    None => continue,
+                                };
+                                let bound_range = KEY_ENCODER
+                                    .encode_txnkv_stream_data_key_id_range(
+                                        key,
+                                        start,
+                                        (u64::MAX, u64::MAX),
+                                        version,
+                                    );
+                                let kvs: Vec<KvPair> =
+                                    txn.scan(bound_range, limit).await?.collect();
+                                if kvs.is_empty() {
+                                    continue;
+                                }
+                                let mut entries = Vec::with_capacity(kvs.len());
+                                let mut last_id = last_delivered;
+                                for kv in kvs {
+                                    last_id = KeyDecoder::decode_key_stream_id_from_datakey(
+                                        key,
+                                        kv.0.clone(),
+                                    );
+                                    if !noack {
+                                        let pending_key = KEY_ENCODER
+                                            .encode_txnkv_stream_pending_key(
+                                                key, &group, last_id, version,
+                                            );
+                                        let pending_value = KEY_ENCODER
+                                            .encode_txnkv_stream_pending_value(now, 1, &consumer);
+                                        txn.put(pending_key, pending_value).await?;
+                                    }
+                                    entries.push(entry_frame(key, kv));
+                                }
+                                let group_value =
+                                    KEY_ENCODER.encode_txnkv_stream_group_value(last_id);
+                                txn.put(group_key, group_value).await?;
+                                entries
+                            }
+                            Some(id) => {
+                                let mut entries = vec![];
+                                if let Some(start) = next_stream_id(id) {
+                                    let bound_range = KEY_ENCODER
+                                        .encode_txnkv_stream_pending_key_id_range(
+                                            key,
+                                            &group,
+                                            start,
+                                            (u64::MAX, u64::MAX),
+                                            version,
+                                        );
+                                    let pending: Vec<KvPair> =
+                                        txn.scan(bound_range, u32::MAX).await?.collect();
+                                    for kv in pending {
+                                        if entries.len() as u32 >= limit {
+                                            break;
+                                        }
+                                        let (_, _, owner) =
+                                            KeyDecoder::decode_key_stream_pending_value(&kv.1);
+                                        if owner != consumer {
+                                            continue;
+                                        }
+                                        let id =
+                                            KeyDecoder::decode_key_stream_id_from_pendingkey(kv.0);
+                                        let data_key = KEY_ENCODER
+                                            .encode_txnkv_stream_data_key(key, id, version);
+                                        let entry = match txn.get(data_key.clone()).await? {
+                                            Some(value) => {
+                                                entry_frame(key, KvPair::new(data_key, value))
+                                            }
+                                            None => resp_array(vec![
+                                                resp_bulk(format_stream_id(id).into_bytes()),
+                                                resp_nil(),
+                                            ]),
+                                        };
+                                        entries.push(entry);
+                                    }
+                                }
+                                entries
+                            }
+                        };
+                        resp.push(resp_array(vec![
+                            resp_bulk(key.as_bytes().to_vec()),
+                            resp_array(entries),
+                        ]));
+                    }
+                    if resp.is_empty() {
+                        return Ok(resp_nil());
+                    }
+                    Ok(resp_array(resp))
+                }
+                .boxed()
+            })
+            .await;
+
+        match resp {
+            Ok(frame) => Ok(frame),
+            Err(e) => Ok(resp_err(e)),
+        }
+    }
+
+    /// Acknowledge the pending entries of the group, replies with the number
+    /// of entries that were pending.
+    pub async fn do_async_txnkv_xack(
+        mut self,
+        key: &str,
+        group: &str,
+        ids: &[StreamId],
+    ) -> AsyncResult<Frame> {
+        let mut client = get_txn_client()?;
+        let key = key.to_owned();
+        let group = group.to_owned();
+        let ids = ids.to_owned();
+
+        let resp = client
+            .exec_in_txn(self.txn.clone(), |txn_rc| {
+                async move {
+                    if self.txn.is_none() {
+                        self.txn = Some(txn_rc.clone());
+                    }
+
+                    let version = match self.get_stream_meta(&txn_rc, &key).await? {
+                        Some((_, version, _, _)) => version,
+                        None => return Ok(0),
+                    };
+                    let mut txn = txn_rc.lock().await;
+                    let group_key =
+                        KEY_ENCODER.encode_txnkv_stream_group_key(&key, &group, version);
+                    if !txn.key_exists(group_key).await? {
+                        return Ok(0);
+                    }
+                    let mut acked = 0;
+                    for id in ids {
+                        let pending_key =
+                            KEY_ENCODER.encode_txnkv_stream_pending_key(&key, &group, id, version);
+                        if txn.key_exists(pending_key.clone()).await? {
+                            txn.delete(pending_key).await?;
+                            acked += 1;
+                        }
+                    }
+                    Ok(acked)
+                }
+                .boxed()
+            })
+            .await;
+
+        match resp {
+            Ok(n) => Ok(resp_int(n)),
+            Err(e) => Ok(resp_err(e)),
+        }
+    }
+
+    /// The summary of the pending entries of the group, or the pending
+    /// entries in `range` with their consumer, idle time and delivery count.
+    pub async fn do_async_txnkv_xpending(
+        mut self,
+        key: &str,
+        group: &str,
+        range: Option<PendingRange>,
+    ) -> AsyncResult<Frame> {
+        let mut client = get_txn_client()?;
+        let key = key.to_owned();
+        let group = group.to_owned();
+
+        let resp = client
+            .exec_in_txn(self.txn.clone(), |txn_rc| {
+                async move {
+                    if self.txn.is_none() {
+                        self.txn = Some(txn_rc.clone());
+                    }
+
+                    let version = match self.get_stream_meta(&txn_rc, &key).await? {
+                        Some((_, version, _, _)) => version,
+                        None => return Err(no_group_err(&key, &group)),
+                    };
+                    let mut txn = txn_rc.lock().await;
+                    let group_key =
+                        KEY_ENCODER.encode_txnkv_stream_group_key(&key, &group, version);
+                    if !txn.key_exists(group_key).await? {
+                        return Err(no_group_err(&key, &group));
+                    }
+
+                    let range = match range {
+                        Some(range) => range,
+                        None => {
+                            let bound_range = KEY_ENCODER
+                                .encode_txnkv_stream_pending_key_range(&key, &group, version);
+                            let pending: Vec<KvPair> =
+                                txn.scan(bound_range, u32::MAX).await?.collect();
+                            let (first, last) = match (pending.first(), pending.last()) {
+                                (Some(first), Some(last)) => (
+                                    KeyDecoder::decode_key_stream_id_from_pendingkey(
+                                        first.0.clone(),
+                                    ),
+                                    KeyDecoder::decode_key_stream_id_from_pendingkey(
+                                        last.0.clone(),
+                                    ),
+                                ),
+                                _ => {
+                                    return Ok(resp_array(vec![
+                                        resp_int(0),
+                                        resp_nil(),
+                                        resp_nil(),
+                                        resp_nil(),
+                                    ]))
+                                }
+                            };
+                            // consumers are sorted by name like Redis does
+                            let mut consumers: BTreeMap<String, i64> = BTreeMap::new();
+                            for kv in &pending {
+                                let (_, _, owner) =
+                                    KeyDecoder::decode_key_stream_pending_value(&kv.1);
+                                *consumers.entry(owner).or_insert(0) += 1;
+                            }
+                            let consumers = consumers
+                                .into_iter()
+                                .map(|(owner, n)| {
+                                    resp_array(vec![
+                                        resp_bulk(owner.into_bytes()),
+                                        resp_bulk(n.to_string().into_bytes()),
+                                    ])
+                                })
+                                .collect();
+                            return Ok(resp_array(vec![
+                                resp_int(pending.len() as i64),
+                                resp_bulk(format_stream_id(first).into_bytes()),
+                                resp_bulk(format_stream_id(last).into_bytes()),
+                                resp_array(consumers),
+                            ]));
+                        }
+                    };
+
+                    if range.start > range.end || range.count == 0 {
+                        return Ok(resp_array(vec![]));
+                    }
+                    let now = now_timestamp_in_millis();
+                    let bound_range = KEY_ENCODER.encode_txnkv_stream_pending_key_id_range(
+                        &key,
+                        &group,
+                        range.start,
+                        range.end,
+                        version,
+                    );
+                    let mut entries = vec![];
+                    let mut iter = txn.scan_stream(bound_range, u32::MAX).await?;
+                    while let Some(kv) = iter.next().await {
+                        let (delivery_time, delivery_count, owner) =
+                            KeyDecoder::decode_key_stream_pending_value(&kv.1);
+                        let idle = now.saturating_sub(delivery_time);
+                        if idle < range.idle
+                            || range.consumer.as_ref().map_or(false, |c| *c != owner)
+                        {
+                            continue;
+                        }
+                        let id = KeyDecoder::decode_key_stream_id_from_pendingkey(kv.0);
+                        entries.push(resp_array(vec![
+                            resp_bulk(format_stream_id(id).into_bytes()),
+                            resp_bulk(owner.into_bytes()),
+                            resp_int(idle as i64),
+                            resp_int(delivery_count as i64),
+                        ]));
+                        if entries.len() as u32 >= range.count {
+                            break;
+                        }
+                    }
+                    Ok(resp_array(entries))
+                }
+                .boxed()
+            })
+            .await;
+
+        match resp {
+            Ok(frame) => Ok(frame),
+            Err(e) => Ok(resp_err(e)),
+        }
+    }
+
+    /// Give `consumer` the pending entries idle for at least `min_idle`
+    /// milliseconds. Entries trimmed from the stream are no longer pending and
+    /// are not claimed. Replies with the claimed entries, or their ids only.
+    #[allow(clippy::too_many_arguments)]
+    pub async fn do_async_txnkv_xclaim(
+        mut self,
+        key: &str,
+        group: &str,
+        consumer: &str,
+        min_idle: u64,
+        ids: &[StreamId],
+        options: ClaimOptions,
+    ) -> AsyncResult<Frame> {
+        let mut client = get_txn_client()?;
+        let key = key.to_owned();
+        let group = group.to_owned();
+        let consumer = consumer.to_owned();
+        let ids = ids.to_owned();
+
+        let resp = client
+            .exec_in_txn(self.txn.clone(), |txn_rc| {
+                async move {
+                    if self.txn.is_none() {
+                        self.txn = Some(txn_rc.clone());
+                    }
+
+                    let version = match self.get_stream_meta(&txn_rc, &key).await? {
+                        Some((_, version, _, _)) => version,
+                        None => return Err(no_group_err(&key, &group)),
+                    };
+                    let mut txn = txn_rc.lock().await;
+                    let group_key =
+                        KEY_ENCODER.encode_txnkv_stream_group_key(&key, &group, version);
+                    if !txn.key_exists(group_key).await? {
+                        return Err(no_group_err(&key, &group));
+                    }
+
+                    let now = now_timestamp_in_millis();
+                    let delivery_time = options
+                        .time
+                        .or_else(|| options.idle.map(|idle| now.saturating_sub(idle)))
+                        .unwrap_or(now);
+                    let mut claimed = vec![];
+                    for id in ids {
+                        let pending_key =
+                            KEY_ENCODER.encode_txnkv_stream_pending_key(&key, &group, id, version);
+                        let delivery_count = match txn.get(pending_key.clone()).await? {
+                            Some(pending_value) => {
+                                let (time, count, _) =
+                                    KeyDecoder::decode_key_stream_pending_value(&pending_value);
+                                if now.saturating_sub(time) < min_idle {
+                                    continue;
+                                }
+                                count
+                            }
+                            None if options.force => 0,
+                            None => continue,
+                        };
+                        let data_key = KEY_ENCODER.encode_txnkv_stream_data_key(&key, id, version);
+                        let value = match txn.get(data_key.clone()).await? {
+                            Some(value) => value,
+                            None => {
+                                txn.delete(pending_key).await?;
+                                continue;
+                            }
+                        };
+                        let delivery_count = options.retry_count.unwrap_or(if options.justid {
+                            delivery_count
+                        } else {
+                            delivery_count + 1
+                        });
+                        let pending_value = KEY_ENCODER.encode_txnkv_stream_pending_value(
+                            delivery_time,
+                            delivery_count,
+                            &consumer,
+                        );
+                        txn.put(pending_key, pending_value).await?;
+                        claimed.push(if options.justid {
+                            resp_bulk(format_stream_id(id).into_bytes())
+                        } else {
+                            entry_frame(&key, KvPair::new(data_key, value))
+                        });
+                    }
+                    Ok(resp_array(claimed))
+                }
+                .boxed()
+            })
+            .await;
+
+        match resp {
+            Ok(frame) => Ok(frame),
+            Err(e) => Ok(resp_err(e)),
+        }
+    }
+
+    pub async fn do_async_txnkv_stream_del(mut self, key: &str) -> AsyncResult<i64> {
+        let mut client = get_txn_client()?;
+        let key = key.to_owned();
+        let meta_key = KEY_ENCODER.encode_txnkv_meta_key(&key);
+
+        client
+            .exec_in_txn(self.txn.clone(), |txn_rc| {
+                async move {
+                    if self.txn.is_none() {
+                        self.txn = Some(txn_rc.clone());
+                    }
+
+                    let mut txn = txn_rc.lock().await;
+                    match txn.get(meta_key.clone()).await? {
+                        Some(meta_value) => {
+                            let (_, version, len, _) =
+                                KeyDecoder::decode_key_stream_meta(&meta_value);
+                            if len >= async_del_stream_threshold_or_default() as u64 {
+                                // async delete
+                                // delete meta key and create gc key and gc version key with the version
+                                txn.delete(meta_key).await?;
+
+                                let gc_key = KEY_ENCODER.encode_txnkv_gc_key(&key);
+                                txn.put(gc_key, version.to_be_bytes()).await?;
+
+                                let gc_version_key =
+                                    KEY_ENCODER.encode_txnkv_gc_version_key(&key, version);
+                                txn.put(
+                                    gc_version_key,
+                                    vec![KEY_ENCODER.get_type_bytes(DataType::Stream)],
+                                )
+                                .await?;
+                            } else {
+                                let bound_range =
+                                    KEY_ENCODER.encode_txnkv_stream_key_range(&key, version);
+                                let mut iter = txn.scan_keys_stream(bound_range, u32::MAX).await?;
+
+                                while let Some(k) = iter.next().await {
+                                    txn.delete(k).await?;
+                                }
+                                txn.delete(meta_key).await?;
+                            }
+                            Ok(1)
+                        }
+                        None => Ok(0),
+                    }
+                }
+                .boxed()
+            })
+            .await
+    }
+
+    pub async fn do_async_txnkv_stream_expire_if_needed(mut self, key: &str) -> AsyncResult<i64> {
+        let mut client = get_txn_client()?;
+        let key = key.to_owned();
+        let expired_key = key.clone();
+        let meta_key = KEY_ENCODER.encode_txnkv_meta_key(&key);
+
+        let expired = client
+            .exec_in_txn(self.txn.clone(), |txn_rc| {
+                async move {
+                    if self.txn.is_none() {
+                        self.txn = Some(txn_rc.clone());
+                    }
+
+                    let mut txn = txn_rc.lock().await;
+                    match txn.get(meta_key.clone()).await? {
+                        Some(meta_value) => {
+                            let (ttl, version, len, _) =
+                                KeyDecoder::decode_key_stream_meta(&meta_value);
+                            if !key_is_expired(ttl) {
+                                return Ok(None);
+                            }
+                            if len >= async_del_stream_threshold_or_default() as u64 {
+                                // async delete
+                                // delete meta key and create gc key and gc version key with the version
+                                txn.delete(meta_key).await?;
+
+                                let gc_key = KEY_ENCODER.encode_txnkv_gc_key(&key);
+                                txn.put(gc_key, version.to_be_bytes()).await?;
+
+                                let gc_version_key =
+                                    KEY_ENCODER.encode_txnkv_gc_version_key(&key, version);
+                                txn.put(
+                                    gc_version_key,
+                                    vec![KEY_ENCODER.get_type_bytes(DataType::Stream)],
+                                )
+                                .await?;
+                            } else {
+                                let bound_range =
+                                    KEY_ENCODER.encode_txnkv_stream_key_range(&key, version);
+                                let mut iter = txn.scan_keys_stream(bound_range, u32::MAX).await?;
+
+                                while let Some(k) = iter.next().await {
+                                    txn.delete(k).await?;
+                                }
+                                txn.delete(meta_key).await?;
+                            }
+
+                            count_expired_key("stream");
+                            Ok(Some(ttl))
+                        }
+                        None => Ok(None),
+                    }
+                }
+                .boxed()
+            })
+            .await?;
+        match expired {
+            Some(expire_at) => {
+                fire(EVENT_EXPIRED, &expired_key).await;
+                ship_expired(DataType::Stream, &expired_key, expire_at).await;
+                Ok(1)
+            }
+            None => Ok(0),
+        }
+    }
+
+    /// The (ttl, version, len, last_id) of the stream at `key`, None if it
+    /// does not exist or has expired
+    async fn get_stream_meta(
+        &self,
+        txn_rc: &Arc<Mutex<Transaction>>,
+        key: &str,
+    ) -> AsyncResult<Option<(u64, u16, u64, StreamId)>> {
+        let mut txn = txn_rc.lock().await;
+        let meta_key = KEY_ENCODER.encode_txnkv_meta_key(key);
+        let meta_value = match txn.get(meta_key).await? {
+            Some(meta_value) => meta_value,
+            None => return Ok(None),
+        };
+        if !matches!(KeyDecoder::decode_key_type(&meta_value), DataType::Stream) {
+            return Err(REDIS_WRONG_TYPE_ERR);
+        }
+        let meta = KeyDecoder::decode_key_stream_meta(&meta_value);
+        if key_is_expired(meta.0) {
+            drop(txn);
+            self.clone()
+                .do_async_txnkv_stream_expire_if_needed(key)
+                .await?;
+            return Ok(None);
+        }
+        Ok(Some(meta))
+    }
+}
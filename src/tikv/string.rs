@@ -1,3 +1,4 @@
+use super::backend::Transaction;
 use super::{
     encoding::{DataType, KeyDecoder},
     errors::AsyncResult,
@@ -10,22 +11,51 @@ use crate::{
 };
 use ::futures::future::FutureExt;
 use futures::StreamExt;
-use regex::bytes::Regex;
 use std::collections::HashMap;
 use std::str;
 use std::sync::Arc;
-use tikv_client::{BoundRange, Key, KvPair, Transaction, Value};
+use tikv_client::{BoundRange, Key, KvPair, Value};
 use tokio::sync::Mutex;
 
 use super::errors::*;
+use super::expiration::count_expired_key;
 use super::{get_client, get_txn_client};
-use super::{hash::HashCommandCtx, list::ListCommandCtx, set::SetCommandCtx, zset::ZsetCommandCtx};
+use super::{
+    hash::HashCommandCtx, list::ListCommandCtx, set::SetCommandCtx, stream::StreamCommandCtx,
+    zset::ZsetCommandCtx,
+};
 use crate::utils::{
-    key_is_expired, resp_err, resp_int, resp_ok_ignore, resp_str, sleep, ttl_from_timestamp,
+    clamp_expire_timestamp, expire_timestamp_of_new_key, glob_match, key_is_expired,
+    now_timestamp_in_millis, resp_err, resp_int, resp_ok_ignore, resp_str, sleep, string_range,
+    ttl_from_timestamp,
 };
 use bytes::Bytes;
-
-use crate::metrics::REMOVED_EXPIRED_KEY_COUNTER;
+use rand::Rng;
+
+use crate::cluster::key_slot;
+use crate::cmdlog::ship_expired;
+use crate::config_cluster_slot_scan_max_keys_or_default;
+use crate::triggers::{fire, EVENT_EXPIRED};
+
+/// Largest string SETRANGE writes, the 512MB limit of Redis strings
+pub const STRING_MAX_LEN: u64 = 512 * 1024 * 1024;
+
+/// Keys whose range is found expired before RANDOMKEY gives up
+const RANDOMKEY_MAX_PROBES: usize = 16;
+
+/// A random key between `low` and `high`, sharing their common prefix, with
+/// a random byte in their range at the first one they differ at and random
+/// bytes after it.
+fn random_key_between(low: &[u8], high: &[u8]) -> Vec<u8> {
+    let mut rng = rand::thread_rng();
+    let common = low.iter().zip(high).take_while(|(l, h)| l == h).count();
+    let mut key = low[..common].to_vec();
+    if common < low.len() && common < high.len() {
+        key.push(rng.gen_range(low[common]..=high[common]));
+    }
+    key.extend_from_slice(&rng.gen::<[u8; 8]>());
+    key
+}
 
 #[derive(Clone)]
 pub struct StringCommandCtx {
@@ -53,7 +83,7 @@ impl StringCommandCtx {
 
         // if get is executed from a new transaction, we can do get with latest commit
         if self.txn.is_none() {
-            let readonly_txn = client.begin_with_latest();
+            let readonly_txn = client.begin_consistent_read().await?;
             self.txn = Some(Arc::new(Mutex::new(readonly_txn)));
         }
 
@@ -82,7 +112,109 @@ impl StringCommandCtx {
                                 return Ok(resp_nil());
                             }
 
-                            let data = KeyDecoder::decode_key_string_value(&val);
+                            let data = KeyDecoder::decode_key_string_value(&val)?;
+                            Ok(resp_bulk(data))
+                        }
+                        None => Ok(resp_nil()),
+                    }
+                }
+                .boxed()
+            })
+            .await
+    }
+
+    /// GETDEL, the value of the string is read and the key removed in the
+    /// same transaction
+    pub async fn do_async_txnkv_getdel(mut self, key: &str) -> AsyncResult<Frame> {
+        let mut client = get_txn_client()?;
+        let ekey = KEY_ENCODER.encode_txnkv_string(key);
+        let key = key.to_owned();
+
+        client
+            .exec_in_txn(self.txn.clone(), |txn_rc| {
+                async move {
+                    if self.txn.is_none() {
+                        self.txn = Some(txn_rc.clone());
+                    }
+
+                    let mut txn = txn_rc.lock().await;
+
+                    match txn.get(ekey.clone()).await? {
+                        Some(val) => {
+                            let dt = KeyDecoder::decode_key_type(&val);
+                            if !matches!(dt, DataType::String) {
+                                return Ok(resp_err(REDIS_WRONG_TYPE_ERR));
+                            }
+
+                            let ttl = KeyDecoder::decode_key_ttl(&val);
+                            if key_is_expired(ttl) {
+                                drop(txn);
+                                self.do_async_txnkv_string_expire_if_needed(&key).await?;
+                                return Ok(resp_nil());
+                            }
+
+                            let data = KeyDecoder::decode_key_string_value(&val)?;
+                            txn.delete(ekey).await?;
+                            Ok(resp_bulk(data))
+                        }
+                        None => Ok(resp_nil()),
+                    }
+                }
+                .boxed()
+            })
+            .await
+    }
+
+    /// GETEX, the value of the string with its expire timestamp in
+    /// milliseconds set in the same transaction, 0 to persist the key and
+    /// None to leave it. A past timestamp removes the key like Redis.
+    pub async fn do_async_txnkv_getex(
+        mut self,
+        key: &str,
+        timestamp: Option<u64>,
+    ) -> AsyncResult<Frame> {
+        let mut client = get_txn_client()?;
+        let ekey = KEY_ENCODER.encode_txnkv_string(key);
+        let key = key.to_owned();
+
+        client
+            .exec_in_txn(self.txn.clone(), |txn_rc| {
+                async move {
+                    if self.txn.is_none() {
+                        self.txn = Some(txn_rc.clone());
+                    }
+
+                    let mut txn = txn_rc.lock().await;
+
+                    match txn.get(ekey.clone()).await? {
+                        Some(val) => {
+                            let dt = KeyDecoder::decode_key_type(&val);
+                            if !matches!(dt, DataType::String) {
+                                return Ok(resp_err(REDIS_WRONG_TYPE_ERR));
+                            }
+
+                            let ttl = KeyDecoder::decode_key_ttl(&val);
+                            if key_is_expired(ttl) {
+                                drop(txn);
+                                self.do_async_txnkv_string_expire_if_needed(&key).await?;
+                                return Ok(resp_nil());
+                            }
+
+                            let data = KeyDecoder::decode_key_string_value(&val)?;
+                            match timestamp {
+                                Some(ts) if ts > 0 && ts <= now_timestamp_in_millis() => {
+                                    txn.delete(ekey).await?;
+                                }
+                                Some(ts) => {
+                                    let ts = clamp_expire_timestamp(ts);
+                                    if ts != ttl {
+                                        let new_meta_value =
+                                            KEY_ENCODER.encode_txnkv_string_ttl(&val, ts);
+                                        txn.put(ekey, new_meta_value).await?;
+                                    }
+                                }
+                                None => {}
+                            }
                             Ok(resp_bulk(data))
                         }
                         None => Ok(resp_nil()),
@@ -98,7 +230,10 @@ impl StringCommandCtx {
         let ekey = KEY_ENCODER.encode_rawkv_string(key);
 
         match client.get(ekey).await? {
-            Some(val) => Ok(resp_str(&KeyDecoder::decode_key_type(&val).to_string())),
+            Some(val) => match KeyDecoder::try_decode_key_type(&val) {
+                Some(dt) => Ok(resp_str(&dt.to_string())),
+                None => Ok(resp_err(REDIS_UNKNOWN_DATA_TYPE_ERR)),
+            },
             None => Ok(resp_str(&DataType::Null.to_string())),
         }
     }
@@ -110,7 +245,7 @@ impl StringCommandCtx {
 
         // if get is executed from a new transaction, we can do get with latest commit
         if self.txn.is_none() {
-            let readonly_txn = client.begin_with_latest();
+            let readonly_txn = client.begin_consistent_read().await?;
             self.txn = Some(Arc::new(Mutex::new(readonly_txn)));
         }
 
@@ -132,8 +267,10 @@ impl StringCommandCtx {
                                 return Ok(resp_str(&DataType::Null.to_string()));
                             }
 
-                            let dt = KeyDecoder::decode_key_type(&val);
-                            Ok(resp_str(&dt.to_string()))
+                            match KeyDecoder::try_decode_key_type(&val) {
+                                Some(dt) => Ok(resp_str(&dt.to_string())),
+                                None => Ok(resp_err(REDIS_UNKNOWN_DATA_TYPE_ERR)),
+                            }
                         }
                         None => Ok(resp_str(&DataType::Null.to_string())),
                     }
@@ -143,6 +280,114 @@ impl StringCommandCtx {
             .await
     }
 
+    /// OBJECT ENCODING of the key, nil if it does not exist
+    pub async fn do_async_txnkv_object_encoding(mut self, key: &str) -> AsyncResult<Frame> {
+        let mut client = get_txn_client()?;
+        let ekey = KEY_ENCODER.encode_txnkv_string(key);
+        let key = key.to_owned();
+
+        if self.txn.is_none() {
+            let readonly_txn = client.begin_consistent_read().await?;
+            self.txn = Some(Arc::new(Mutex::new(readonly_txn)));
+        }
+
+        client
+            .exec_in_txn(self.txn.clone(), |txn_rc| {
+                async move {
+                    if self.txn.is_none() {
+                        self.txn = Some(txn_rc.clone());
+                    }
+
+                    let mut txn = txn_rc.lock().await;
+                    match txn.get(ekey).await? {
+                        Some(val) => {
+                            if key_is_expired(KeyDecoder::decode_key_ttl(&val)) {
+                                drop(txn);
+                                self.do_async_txnkv_string_expire_if_needed(&key).await?;
+                                return Ok(resp_nil());
+                            }
+
+                            match KeyDecoder::try_decode_key_type(&val) {
+                                Some(dt) => Ok(resp_bulk(dt.encoding().as_bytes().to_vec())),
+                                None => Ok(resp_err(REDIS_UNKNOWN_DATA_TYPE_ERR)),
+                            }
+                        }
+                        None => Ok(resp_nil()),
+                    }
+                }
+                .boxed()
+            })
+            .await
+    }
+
+    pub async fn do_async_rawkv_getrange(
+        &self,
+        key: &str,
+        start: i64,
+        end: i64,
+    ) -> AsyncResult<Frame> {
+        let client = get_client()?;
+        let ekey = KEY_ENCODER.encode_rawkv_string(key);
+        match client.get(ekey).await? {
+            Some(val) => Ok(resp_bulk(val[string_range(val.len(), start, end)].to_vec())),
+            None => Ok(resp_bulk(vec![])),
+        }
+    }
+
+    pub async fn do_async_txnkv_getrange(
+        mut self,
+        key: &str,
+        start: i64,
+        end: i64,
+    ) -> AsyncResult<Frame> {
+        let mut client = get_txn_client()?;
+        let ekey = KEY_ENCODER.encode_txnkv_string(key);
+        let key = key.to_owned();
+
+        // if get is executed from a new transaction, we can do get with latest commit
+        if self.txn.is_none() {
+            let readonly_txn = client.begin_consistent_read().await?;
+            self.txn = Some(Arc::new(Mutex::new(readonly_txn)));
+        }
+
+        client
+            .exec_in_txn(self.txn.clone(), |txn_rc| {
+                async move {
+                    if self.txn.is_none() {
+                        self.txn = Some(txn_rc.clone());
+                    }
+
+                    let mut txn = txn_rc.lock().await;
+
+                    match txn.get(ekey).await? {
+                        Some(val) => {
+                            let dt = KeyDecoder::decode_key_type(&val);
+                            if !matches!(dt, DataType::String) {
+                                return Ok(resp_err(REDIS_WRONG_TYPE_ERR));
+                            }
+
+                            // ttl saved in milliseconds
+                            let ttl = KeyDecoder::decode_key_ttl(&val);
+                            if key_is_expired(ttl) {
+                                // delete key
+                                drop(txn);
+                                self.do_async_txnkv_string_expire_if_needed(&key).await?;
+                                return Ok(resp_bulk(vec![]));
+                            }
+
+                            let data = KeyDecoder::decode_key_string_value(&val)?;
+                            Ok(resp_bulk(
+                                data[string_range(data.len(), start, end)].to_vec(),
+                            ))
+                        }
+                        None => Ok(resp_bulk(vec![])),
+                    }
+                }
+                .boxed()
+            })
+            .await
+    }
+
     pub async fn do_async_rawkv_strlen(&self, key: &str) -> AsyncResult<Frame> {
         let client = get_client()?;
         let ekey = KEY_ENCODER.encode_rawkv_string(key);
@@ -159,7 +404,7 @@ impl StringCommandCtx {
 
         // if get is executed from a new transaction, we can do get with latest commit
         if self.txn.is_none() {
-            let readonly_txn = client.begin_with_latest();
+            let readonly_txn = client.begin_consistent_read().await?;
             self.txn = Some(Arc::new(Mutex::new(readonly_txn)));
         }
 
@@ -188,7 +433,7 @@ impl StringCommandCtx {
                                 return Ok(resp_int(0));
                             }
 
-                            let data = KeyDecoder::decode_key_string_value(&val);
+                            let data = KeyDecoder::decode_key_string_value(&val)?;
                             Ok(resp_int(data.len() as i64))
                         }
                         None => Ok(resp_int(0)),
@@ -214,7 +459,8 @@ impl StringCommandCtx {
     ) -> AsyncResult<Frame> {
         let mut client = get_txn_client()?;
         let ekey = KEY_ENCODER.encode_txnkv_string(key);
-        let eval = KEY_ENCODER.encode_txnkv_string_value(&mut val.to_vec(), timestamp);
+        let eval = KEY_ENCODER
+            .encode_txnkv_string_value(&mut val.to_vec(), expire_timestamp_of_new_key(timestamp))?;
         let resp = client
             .exec_in_txn(self.txn.clone(), |txn_rc| {
                 async move {
@@ -256,7 +502,7 @@ impl StringCommandCtx {
 
         // if get is executed from a new transaction, we can do get with latest commit
         if self.txn.is_none() {
-            let readonly_txn = client.begin_with_latest();
+            let readonly_txn = client.begin_consistent_read().await?;
             self.txn = Some(Arc::new(Mutex::new(readonly_txn)));
         }
 
@@ -272,7 +518,7 @@ impl StringCommandCtx {
                     let ret: HashMap<Key, Value> =
                         result.into_iter().map(|pair| (pair.0, pair.1)).collect();
 
-                    let values: Vec<Frame> = ekeys
+                    let values = ekeys
                         .into_iter()
                         .map(|k| {
                             let data = ret.get(k.as_ref());
@@ -280,15 +526,15 @@ impl StringCommandCtx {
                                 Some(val) => {
                                     let ttl = KeyDecoder::decode_key_ttl(val);
                                     if key_is_expired(ttl) {
-                                        return Frame::Null;
+                                        return Ok(Frame::Null);
                                     }
-                                    let data = KeyDecoder::decode_key_string_value(val);
-                                    Frame::Bulk(data.into())
+                                    let data = KeyDecoder::decode_key_string_value(val)?;
+                                    Ok(Frame::Bulk(data.into()))
                                 }
-                                None => Frame::Null,
+                                None => Ok(Frame::Null),
                             }
                         })
-                        .collect();
+                        .collect::<AsyncResult<Vec<Frame>>>()?;
                     Ok(Frame::Array(values))
                 }
                 .boxed()
@@ -349,7 +595,8 @@ impl StringCommandCtx {
         let mut client = get_txn_client()?;
         let key = key.to_owned();
         let ekey = KEY_ENCODER.encode_txnkv_string(&key);
-        let eval = KEY_ENCODER.encode_txnkv_string_value(&mut value.to_vec(), 0);
+        let eval = KEY_ENCODER
+            .encode_txnkv_string_value(&mut value.to_vec(), expire_timestamp_of_new_key(0))?;
 
         let resp = client
             .exec_in_txn(self.txn.clone(), |txn_rc| {
@@ -405,7 +652,7 @@ impl StringCommandCtx {
 
         // if get is executed from a new transaction, we can do get with latest commit
         if self.txn.is_none() {
-            let readonly_txn = client.begin_with_latest();
+            let readonly_txn = client.begin_consistent_read().await?;
             self.txn = Some(Arc::new(Mutex::new(readonly_txn)));
         }
 
@@ -497,6 +744,8 @@ impl StringCommandCtx {
                         self.txn = Some(txn_rc.clone())
                     }
                     let prev_int;
+                    // an existing key keeps its ttl, the default one is for new keys
+                    let mut new_ttl = None;
                     let mut txn = txn_rc.lock().await;
                     match txn.get(ekey.clone()).await? {
                         Some(val) => {
@@ -509,10 +758,11 @@ impl StringCommandCtx {
                                 txn = txn_rc.lock().await;
                                 prev_int = 0;
                             } else {
-                                let real_value = KeyDecoder::decode_key_string_slice(&val);
-                                prev_int = str::from_utf8(real_value)
+                                let real_value = KeyDecoder::decode_key_string_value(&val)?;
+                                prev_int = str::from_utf8(&real_value)
                                     .map_err(RTError::to_is_not_integer_error)?
                                     .parse::<i64>()?;
+                                new_ttl = Some(ttl);
                             }
                         }
                         None => {
@@ -522,8 +772,10 @@ impl StringCommandCtx {
 
                     let new_int = prev_int + step;
                     let new_val = new_int.to_string();
-                    let eval =
-                        KEY_ENCODER.encode_txnkv_string_value(&mut new_val.as_bytes().to_vec(), 0);
+                    let eval = KEY_ENCODER.encode_txnkv_string_value(
+                        &mut new_val.as_bytes().to_vec(),
+                        new_ttl.unwrap_or_else(|| expire_timestamp_of_new_key(0)),
+                    )?;
                     txn.put(ekey, eval).await?;
                     Ok(new_int)
                 }
@@ -537,6 +789,235 @@ impl StringCommandCtx {
         }
     }
 
+    /// Rate limit like CL.THROTTLE with the generic cell rate algorithm. The
+    /// key is a string holding the theoretical arrival time in microseconds,
+    /// it expires once the limit is fully reset. `emission_interval` is the
+    /// microseconds between two requests at the sustained rate.
+    pub async fn do_async_txnkv_throttle(
+        mut self,
+        key: &str,
+        max_burst: i64,
+        emission_interval: i64,
+        quantity: i64,
+    ) -> AsyncResult<Frame> {
+        let mut client = get_txn_client()?;
+        let ekey = KEY_ENCODER.encode_txnkv_string(key);
+        let key = key.to_owned();
+
+        client
+            .exec_in_txn(self.txn.clone(), |txn_rc| {
+                async move {
+                    if self.txn.is_none() {
+                        self.txn = Some(txn_rc.clone())
+                    }
+                    let now = now_timestamp_in_millis() as i64 * 1000;
+                    let mut tat = now;
+                    let mut txn = txn_rc.lock().await;
+                    if let Some(val) = txn.get(ekey.clone()).await? {
+                        if !matches!(KeyDecoder::decode_key_type(&val), DataType::String) {
+                            return Err(REDIS_WRONG_TYPE_ERR);
+                        }
+                        let ttl = KeyDecoder::decode_key_ttl(&val);
+                        if key_is_expired(ttl) {
+                            drop(txn);
+                            self.clone()
+                                .do_async_txnkv_string_expire_if_needed(&key)
+                                .await?;
+                            txn = txn_rc.lock().await;
+                        } else {
+                            let real_value = KeyDecoder::decode_key_string_value(&val)?;
+                            tat = str::from_utf8(&real_value)
+                                .map_err(RTError::to_is_not_integer_error)?
+                                .parse::<i64>()?
+                                .max(now);
+                        }
+                    }
+
+                    let tolerance = emission_interval.saturating_mul(max_burst.saturating_add(1));
+                    let increment = emission_interval.saturating_mul(quantity);
+                    let new_tat = tat.saturating_add(increment);
+                    let allow_at = new_tat.saturating_sub(tolerance);
+                    let limited = now < allow_at;
+                    let (reset_after, retry_after) = if limited {
+                        // a quantity over the burst is never allowed
+                        let retry_after = if increment <= tolerance {
+                            allow_at - now
+                        } else {
+                            -1
+                        };
+                        (tat - now, retry_after)
+                    } else {
+                        if new_tat > now {
+                            let expire_at = (new_tat as u64 + 999) / 1000;
+                            let eval = KEY_ENCODER.encode_txnkv_string_value(
+                                &mut new_tat.to_string().into_bytes(),
+                                clamp_expire_timestamp(expire_at),
+                            )?;
+                            txn.put(ekey, eval).await?;
+                        }
+                        (new_tat - now, -1)
+                    };
+
+                    let next = tolerance - reset_after;
+                    let remaining = if next > 0 {
+                        next / emission_interval
+                    } else {
+                        0
+                    };
+                    // microseconds rounded up to seconds, -1 stays -1
+                    let secs = |us: i64| {
+                        if us < 0 {
+                            -1
+                        } else {
+                            (us + 999_999) / 1_000_000
+                        }
+                    };
+                    Ok(resp_array(vec![
+                        resp_int(limited as i64),
+                        resp_int(max_burst.saturating_add(1)),
+                        resp_int(remaining),
+                        resp_int(secs(retry_after)),
+                        resp_int(secs(reset_after)),
+                    ]))
+                }
+                .boxed()
+            })
+            .await
+    }
+
+    pub async fn do_async_rawkv_append(self, key: &str, value: &Bytes) -> AsyncResult<Frame> {
+        let client = get_client()?;
+        let ekey = KEY_ENCODER.encode_rawkv_string(key);
+        for i in 0..2000 {
+            let prev = client.get(ekey.clone()).await?;
+            let mut new_val = prev.clone().unwrap_or_default();
+            new_val.extend_from_slice(value);
+            let new_len = new_val.len();
+            let (_, ret) = client.compare_and_swap(ekey.clone(), prev, new_val).await?;
+            if ret {
+                return Ok(resp_int(new_len as i64));
+            }
+            sleep(std::cmp::min(i, 200)).await;
+        }
+        Err(REDIS_COMPARE_AND_SWAP_EXHAUSTED_ERR)
+    }
+
+    /// Append the value to the string, the ttl of an existing key is kept.
+    /// Strings are stored as a single value, so the whole value is read and
+    /// rewritten. There is no fast path writing only a tail chunk, it needs a
+    /// chunked encoding of the strings, read by every string command, first.
+    pub async fn do_async_txnkv_append(mut self, key: &str, value: &Bytes) -> AsyncResult<Frame> {
+        let mut client = get_txn_client()?;
+        let ekey = KEY_ENCODER.encode_txnkv_string(key);
+        let key = key.to_owned();
+        let value = value.to_owned();
+
+        let resp = client
+            .exec_in_txn(self.txn.clone(), |txn_rc| {
+                async move {
+                    if self.txn.is_none() {
+                        self.txn = Some(txn_rc.clone())
+                    }
+                    let mut new_val = vec![];
+                    let mut ttl = None;
+                    let mut txn = txn_rc.lock().await;
+                    if let Some(val) = txn.get(ekey.clone()).await? {
+                        if !matches!(KeyDecoder::decode_key_type(&val), DataType::String) {
+                            return Err(REDIS_WRONG_TYPE_ERR);
+                        }
+                        let old_ttl = KeyDecoder::decode_key_ttl(&val);
+                        if key_is_expired(old_ttl) {
+                            drop(txn);
+                            self.clone()
+                                .do_async_txnkv_string_expire_if_needed(&key)
+                                .await?;
+                            txn = txn_rc.lock().await;
+                        } else {
+                            new_val = KeyDecoder::decode_key_string_value(&val)?;
+                            ttl = Some(old_ttl);
+                        }
+                    }
+                    new_val.extend_from_slice(&value);
+                    let new_len = new_val.len() as i64;
+                    let ttl = ttl.unwrap_or_else(|| expire_timestamp_of_new_key(0));
+                    let eval = KEY_ENCODER.encode_txnkv_string_value(&mut new_val, ttl)?;
+                    txn.put(ekey, eval).await?;
+                    Ok(new_len)
+                }
+                .boxed()
+            })
+            .await;
+
+        match resp {
+            Ok(n) => Ok(resp_int(n)),
+            Err(e) => Ok(resp_err(e)),
+        }
+    }
+
+    /// Overwrite the string at `offset` with `value`, padding it with zero
+    /// bytes up to the offset first. An empty `value` writes nothing, which
+    /// does not create the key. Replies the length of the string after.
+    pub async fn do_async_txnkv_setrange(
+        mut self,
+        key: &str,
+        offset: usize,
+        value: &Bytes,
+    ) -> AsyncResult<Frame> {
+        let mut client = get_txn_client()?;
+        let ekey = KEY_ENCODER.encode_txnkv_string(key);
+        let key = key.to_owned();
+        let value = value.to_owned();
+
+        let resp = client
+            .exec_in_txn(self.txn.clone(), |txn_rc| {
+                async move {
+                    if self.txn.is_none() {
+                        self.txn = Some(txn_rc.clone())
+                    }
+                    let mut data = vec![];
+                    let mut ttl = None;
+                    let mut txn = txn_rc.lock().await;
+                    if let Some(val) = txn.get(ekey.clone()).await? {
+                        if !matches!(KeyDecoder::decode_key_type(&val), DataType::String) {
+                            return Err(REDIS_WRONG_TYPE_ERR);
+                        }
+                        let old_ttl = KeyDecoder::decode_key_ttl(&val);
+                        if key_is_expired(old_ttl) {
+                            drop(txn);
+                            self.clone()
+                                .do_async_txnkv_string_expire_if_needed(&key)
+                                .await?;
+                            txn = txn_rc.lock().await;
+                        } else {
+                            data = KeyDecoder::decode_key_string_value(&val)?;
+                            ttl = Some(old_ttl);
+                        }
+                    }
+                    if value.is_empty() {
+                        return Ok(data.len() as i64);
+                    }
+
+                    let end = offset + value.len();
+                    if data.len() < end {
+                        data.resize(end, 0);
+                    }
+                    data[offset..end].copy_from_slice(&value);
+                    let new_len = data.len() as i64;
+                    let ttl = ttl.unwrap_or_else(|| expire_timestamp_of_new_key(0));
+                    let eval = KEY_ENCODER.encode_txnkv_string_value(&mut data, ttl)?;
+                    txn.put(ekey, eval).await?;
+                    Ok(new_len)
+                }
+                .boxed()
+            })
+            .await;
+
+        match resp {
+            Ok(n) => Ok(resp_int(n)),
+            Err(e) => Ok(resp_err(e)),
+        }
+    }
+
     pub async fn do_async_txnkv_string_del(mut self, key: &str) -> AsyncResult<i64> {
         let mut client = get_txn_client()?;
         let key = key.to_owned();
@@ -563,8 +1044,9 @@ impl StringCommandCtx {
     pub async fn do_async_txnkv_string_expire_if_needed(mut self, key: &str) -> AsyncResult<i64> {
         let mut client = get_txn_client()?;
         let key = key.to_owned();
+        let expired_key = key.clone();
 
-        client
+        let expired = client
             .exec_in_txn(self.txn.clone(), |txn_rc| {
                 async move {
                     if self.txn.is_none() {
@@ -575,23 +1057,32 @@ impl StringCommandCtx {
                     if let Some(v) = txn.get(ekey.to_owned()).await? {
                         let ttl = KeyDecoder::decode_key_ttl(&v);
                         if key_is_expired(ttl) {
+                            let data_type =
+                                KeyDecoder::try_decode_key_type(&v).unwrap_or(DataType::String);
                             txn.delete(ekey).await?;
-                            REMOVED_EXPIRED_KEY_COUNTER
-                                .with_label_values(&["string"])
-                                .inc();
-                            return Ok(1);
+                            count_expired_key("string");
+                            return Ok(Some((data_type, ttl)));
                         }
                     }
-                    Ok(0)
+                    Ok(None)
                 }
                 .boxed()
             })
-            .await
+            .await?;
+        match expired {
+            Some((data_type, expire_at)) => {
+                fire(EVENT_EXPIRED, &expired_key).await;
+                ship_expired(data_type, &expired_key, expire_at).await;
+                Ok(1)
+            }
+            None => Ok(0),
+        }
     }
 
     pub async fn do_async_txnkv_expire(mut self, key: &str, timestamp: u64) -> AsyncResult<Frame> {
         let mut client = get_txn_client()?;
         let key = key.to_owned();
+        let timestamp = clamp_expire_timestamp(timestamp);
         let ekey = KEY_ENCODER.encode_txnkv_string(&key);
 
         let resp = client
@@ -613,16 +1104,15 @@ impl StringCommandCtx {
                             let version = KeyDecoder::decode_key_version(&meta_value);
 
                             match dt {
-                                DataType::String => {
+                                DataType::String | DataType::Json => {
                                     // check key expired
                                     if key_is_expired(ttl) {
                                         drop(txn);
                                         self.do_async_txnkv_string_expire_if_needed(&key).await?;
                                         return Ok(0);
                                     }
-                                    let value = KeyDecoder::decode_key_string_slice(&meta_value);
                                     let new_meta_value =
-                                        KEY_ENCODER.encode_txnkv_string_slice(value, timestamp);
+                                        KEY_ENCODER.encode_txnkv_string_ttl(&meta_value, timestamp);
                                     txn.put(ekey, new_meta_value).await?;
                                     Ok(1)
                                 }
@@ -681,6 +1171,23 @@ impl StringCommandCtx {
                                     txn.put(ekey, new_meta_value).await?;
                                     Ok(1)
                                 }
+                                DataType::Stream => {
+                                    if key_is_expired(ttl) {
+                                        drop(txn);
+                                        StreamCommandCtx::new(self.txn.clone())
+                                            .do_async_txnkv_stream_expire_if_needed(&key)
+                                            .await?;
+                                        return Ok(0);
+                                    }
+                                    let (_, version, len, last_id) =
+                                        KeyDecoder::decode_key_stream_meta(&meta_value);
+                                    let new_meta_value = KEY_ENCODER
+                                        .encode_txnkv_stream_meta_value(
+                                            timestamp, version, len, last_id,
+                                        );
+                                    txn.put(ekey, new_meta_value).await?;
+                                    Ok(1)
+                                }
                                 _ => Ok(0),
                             }
                         }
@@ -717,7 +1224,7 @@ impl StringCommandCtx {
                             if key_is_expired(ttl) {
                                 drop(txn);
                                 match dt {
-                                    DataType::String => {
+                                    DataType::String | DataType::Json => {
                                         self.do_async_txnkv_string_expire_if_needed(&key).await?;
                                     }
                                     DataType::Hash => {
@@ -740,6 +1247,11 @@ impl StringCommandCtx {
                                             .do_async_txnkv_zset_expire_if_needed(&key)
                                             .await?;
                                     }
+                                    DataType::Stream => {
+                                        StreamCommandCtx::new(self.txn.clone())
+                                            .do_async_txnkv_stream_expire_if_needed(&key)
+                                            .await?;
+                                    }
                                     _ => {}
                                 }
                                 return Ok(resp_int(-2));
@@ -798,7 +1310,7 @@ impl StringCommandCtx {
                     let mut resp = 0;
                     for idx in 0..keys_len {
                         match dts[idx] {
-                            DataType::String => {
+                            DataType::String | DataType::Json => {
                                 self.clone().do_async_txnkv_string_del(&keys[idx]).await?;
                                 resp += 1;
                             }
@@ -826,6 +1338,12 @@ impl StringCommandCtx {
                                     .await?;
                                 resp += 1;
                             }
+                            DataType::Stream => {
+                                StreamCommandCtx::new(self.txn.clone())
+                                    .do_async_txnkv_stream_del(&keys[idx])
+                                    .await?;
+                                resp += 1;
+                            }
                             DataType::Null => {}
                         }
                     }
@@ -844,11 +1362,11 @@ impl StringCommandCtx {
         mut self,
         start: &str,
         count: u32,
-        regex: &str,
+        pattern: &str,
     ) -> AsyncResult<Frame> {
         let mut client = get_txn_client()?;
         let ekey = KEY_ENCODER.encode_txnkv_string(start);
-        let re = Regex::new(regex).unwrap();
+        let pattern = pattern.as_bytes().to_vec();
 
         client
             .exec_in_txn(self.txn.clone(), |txn_rc| {
@@ -911,13 +1429,13 @@ impl StringCommandCtx {
                             if retrieved_key_count == (count - 1) as usize {
                                 next_key = userkey.clone();
                                 retrieved_key_count += 1;
-                                if re.is_match(&userkey) {
+                                if glob_match(&pattern, &userkey, false) {
                                     keys.push(resp_bulk(userkey));
                                 }
                                 break;
                             }
                             retrieved_key_count += 1;
-                            if re.is_match(&userkey) {
+                            if glob_match(&pattern, &userkey, false) {
                                 keys.push(resp_bulk(userkey));
                             }
                         }
@@ -931,4 +1449,125 @@ impl StringCommandCtx {
             })
             .await
     }
+
+    /// A random live key, the one of the first key found from a random point
+    /// between the first and the last keys, wrapping around to the first key
+    /// past the last one. Expired keys are skipped, nil if the keyspace is
+    /// empty or only expired keys were found.
+    pub async fn do_async_txnkv_randomkey(mut self) -> AsyncResult<Frame> {
+        let mut client = get_txn_client()?;
+
+        if self.txn.is_none() {
+            let readonly_txn = client.begin_consistent_read().await?;
+            self.txn = Some(Arc::new(Mutex::new(readonly_txn)));
+        }
+
+        client
+            .exec_in_txn(self.txn.clone(), |txn_rc| {
+                async move {
+                    let mut txn = txn_rc.lock().await;
+                    let start = KEY_ENCODER.encode_txnkv_keyspace_start();
+                    let end = KEY_ENCODER.encode_txnkv_keyspace_end();
+
+                    let first = match txn.scan(start.clone()..end.clone(), 1).await?.next() {
+                        Some(kv) => kv.0,
+                        None => return Ok(resp_nil()),
+                    };
+                    let last = txn
+                        .scan_reverse_stream(start.clone()..end.clone(), 1)
+                        .await?
+                        .next()
+                        .await
+                        .map_or_else(|| first.clone(), |kv| kv.0);
+                    let first: Vec<u8> = first.into();
+                    let last: Vec<u8> = last.into();
+                    let mut from: Key = random_key_between(&first, &last).into();
+
+                    let mut wrapped = false;
+                    let mut probes = 0;
+                    while probes < RANDOMKEY_MAX_PROBES {
+                        let key = match txn.scan(from.clone()..end.clone(), 1).await?.next() {
+                            Some(kv) => kv.0,
+                            None if !wrapped => {
+                                wrapped = true;
+                                from = start.clone();
+                                continue;
+                            }
+                            None => break,
+                        };
+                        probes += 1;
+
+                        // the point may fall among the sub meta or data keys of
+                        // a key, which is picked as well
+                        let (userkey, _) = KeyDecoder::decode_key_userkey_from_metakey(&key);
+                        let meta_key =
+                            KEY_ENCODER.encode_txnkv_meta_key(&String::from_utf8_lossy(&userkey));
+                        if let Some(meta) = txn.get(meta_key).await? {
+                            if !key_is_expired(KeyDecoder::decode_key_ttl(&meta)) {
+                                return Ok(resp_bulk(userkey));
+                            }
+                        }
+                        from = KEY_ENCODER.encode_txnkv_userkey_end(&userkey);
+                    }
+                    Ok(resp_nil())
+                }
+                .boxed()
+            })
+            .await
+    }
+
+    /// Number of keys in the cluster `slot`, and the first `count` of them
+    /// if some are asked for, the scan stops once they are found. Fails if
+    /// the keyspace holds more than `cluster_slot_scan_max_keys` keys before
+    /// the scan is done, the slot of a large namespace is not answered with
+    /// the keys of a part of it.
+    pub async fn do_async_txnkv_keys_in_slot(
+        mut self,
+        slot: usize,
+        count: Option<usize>,
+    ) -> AsyncResult<(u64, Vec<Vec<u8>>)> {
+        let mut client = get_txn_client()?;
+        let max_keys = config_cluster_slot_scan_max_keys_or_default();
+
+        client
+            .exec_in_txn(self.txn.clone(), |txn_rc| {
+                async move {
+                    if self.txn.is_none() {
+                        self.txn = Some(txn_rc.clone());
+                    }
+                    let mut txn = txn_rc.lock().await;
+                    let range = KEY_ENCODER.encode_txnkv_keyspace_start()
+                        ..KEY_ENCODER.encode_txnkv_keyspace_end();
+                    let mut iter = txn.scan_stream(range, u32::MAX).await?;
+
+                    let mut in_slot: u64 = 0;
+                    let mut keys = vec![];
+                    let mut scanned: u64 = 0;
+                    while let Some(kv) = iter.next().await {
+                        if count.map_or(false, |count| keys.len() >= count) {
+                            break;
+                        }
+                        let (userkey, is_meta_key) =
+                            KeyDecoder::decode_key_userkey_from_metakey(&kv.0);
+                        if !is_meta_key {
+                            continue;
+                        }
+                        if max_keys > 0 && scanned >= max_keys {
+                            return Err(REDIS_SLOT_SCAN_LIMIT_ERR);
+                        }
+                        scanned += 1;
+                        let ttl = KeyDecoder::decode_key_ttl(&kv.1);
+                        if !key_is_expired(ttl) && key_slot(&userkey) == slot {
+                            in_slot += 1;
+                            if count.is_some() {
+                                keys.push(userkey);
+                            }
+                        }
+                    }
+                    Ok((in_slot, keys))
+                }
+                .boxed()
+            })
+            .await
+    }
 }
@@ -1,7 +1,12 @@
+use super::backend::Transaction;
 use super::client::get_version_for_new;
 use super::errors::*;
+use super::expiration::count_expired_key;
 use super::gen_next_meta_index;
 use super::get_txn_client;
+use super::intersect::{Aggregate, Combination, Intersection, SetOperation};
+use super::sort::check_reply;
+use super::string::StringCommandCtx;
 use super::KEY_ENCODER;
 use super::{
     encoding::{DataType, KeyDecoder},
@@ -9,17 +14,51 @@ use super::{
 };
 use crate::async_del_zset_threshold_or_default;
 use crate::async_expire_zset_threshold_or_default;
-use crate::utils::{key_is_expired, resp_array, resp_bulk, resp_err, resp_int, resp_nil};
+use crate::cmd_inter_length_limit_or_default;
+use crate::utils::{
+    expire_timestamp_of_new_key, key_is_expired, resp_array, resp_bulk, resp_err, resp_int,
+    resp_nil, ReplyBuilder,
+};
 use crate::Frame;
 use ::futures::future::FutureExt;
 use futures::StreamExt;
 use std::collections::HashMap;
 use std::convert::TryInto;
 use std::sync::Arc;
-use tikv_client::{BoundRange, Key, Transaction, Value};
+use tikv_client::{BoundRange, Key, Value};
 use tokio::sync::Mutex;
 
-use crate::metrics::REMOVED_EXPIRED_KEY_COUNTER;
+use crate::cmdlog::ship_expired;
+use crate::triggers::{fire, EVENT_EXPIRED};
+
+/// A bound of a BYLEX range
+#[derive(Debug, Clone)]
+pub enum LexBound {
+    /// `-`, before all the members
+    Min,
+    /// `+`, after all the members
+    Max,
+    Inclusive(String),
+    Exclusive(String),
+}
+
+/// The range read by ZRANGESTORE, the bounds of a score or lex range being
+/// the lowest first whatever REV
+#[derive(Debug, Clone)]
+pub enum ZrangeBy {
+    Rank(i64, i64),
+    Score {
+        min: f64,
+        min_inclusive: bool,
+        max: f64,
+        max_inclusive: bool,
+    },
+    Lex(LexBound, LexBound),
+}
+
+/// Members added to the destination at a time by the STORE variants of
+/// ZINTER, ZUNION and ZDIFF
+const STORE_BATCH_SIZE: usize = 512;
 
 #[derive(Clone)]
 pub struct ZsetCommandCtx {
@@ -306,8 +345,11 @@ impl ZsetCommandCtx {
                                 .await?;
                             // add meta key
                             let size = members.len() as i64;
-                            let new_meta_value =
-                                KEY_ENCODER.encode_txnkv_zset_meta_value(0, version, 0);
+                            let new_meta_value = KEY_ENCODER.encode_txnkv_zset_meta_value(
+                                expire_timestamp_of_new_key(0),
+                                version,
+                                0,
+                            );
                             txn.put(meta_key, new_meta_value).await?;
                             Ok(size)
                         }
@@ -413,6 +455,73 @@ impl ZsetCommandCtx {
             .await
     }
 
+    /// Scores of the members, read with a single batch get
+    pub async fn do_async_txnkv_zmscore(
+        mut self,
+        key: &str,
+        members: &[String],
+    ) -> AsyncResult<Frame> {
+        let mut client = get_txn_client()?;
+        let meta_key = KEY_ENCODER.encode_txnkv_meta_key(key);
+        let key = key.to_owned();
+        let members = members.to_owned();
+
+        client
+            .exec_in_txn(self.txn.clone(), |txn_rc| {
+                async move {
+                    if self.txn.is_none() {
+                        self.txn = Some(txn_rc.clone());
+                    }
+
+                    let mut txn = txn_rc.lock().await;
+
+                    match txn.get(meta_key).await? {
+                        Some(meta_value) => {
+                            // check key type and ttl
+                            if !matches!(KeyDecoder::decode_key_type(&meta_value), DataType::Zset) {
+                                return Ok(resp_err(REDIS_WRONG_TYPE_ERR));
+                            }
+
+                            let (ttl, version, _) = KeyDecoder::decode_key_meta(&meta_value);
+                            if key_is_expired(ttl) {
+                                drop(txn);
+                                self.clone()
+                                    .do_async_txnkv_zset_expire_if_needed(&key)
+                                    .await?;
+                                return Ok(resp_array(vec![resp_nil(); members.len()]));
+                            }
+
+                            let data_keys: Vec<Key> = members
+                                .iter()
+                                .map(|m| KEY_ENCODER.encode_txnkv_zset_data_key(&key, m, version))
+                                .collect();
+                            let scores = txn
+                                .batch_get(data_keys.clone())
+                                .await?
+                                .map(|kv| kv.into())
+                                .collect::<HashMap<Key, Value>>();
+
+                            let resp = data_keys
+                                .iter()
+                                .map(|data_key| match scores.get(data_key) {
+                                    Some(data_value) => {
+                                        let score =
+                                            KeyDecoder::decode_key_zset_data_value(data_value);
+                                        resp_bulk(score.to_string().as_bytes().to_vec())
+                                    }
+                                    None => resp_nil(),
+                                })
+                                .collect();
+                            Ok(resp_array(resp))
+                        }
+                        None => Ok(resp_array(vec![resp_nil(); members.len()])),
+                    }
+                }
+                .boxed()
+            })
+            .await
+    }
+
     pub async fn do_async_txnkv_zcount(
         mut self,
         key: &str,
@@ -468,7 +577,8 @@ impl ZsetCommandCtx {
                             );
                             let range = start_key..=end_key;
                             let bound_range: BoundRange = range.into();
-                            let iter = txn.scan_stream(bound_range, u32::MAX).await?;
+                            // count the score keys without fetching their values
+                            let iter = txn.scan_keys_stream(bound_range, u32::MAX).await?;
 
                             Ok(resp_int(iter.count().await as i64))
                         }
@@ -480,6 +590,171 @@ impl ZsetCommandCtx {
             .await
     }
 
+    /// Members of the intersection, union or difference of the sets and
+    /// zsets sorted by their score, refused if more than
+    /// `cmd_inter_length_limit` members are found. The scores of an
+    /// intersection or an union are the aggregate of the weighted scores.
+    pub async fn do_async_txnkv_zcombine(
+        mut self,
+        operation: SetOperation,
+        keys: &[String],
+        weights: &[f64],
+        aggregate: Aggregate,
+        with_scores: bool,
+    ) -> AsyncResult<Frame> {
+        let mut client = get_txn_client()?;
+        let keys = keys.to_owned();
+        let weights = weights.to_owned();
+
+        client
+            .exec_in_txn(self.txn.clone(), |txn_rc| {
+                async move {
+                    if self.txn.is_none() {
+                        self.txn = Some(txn_rc.clone());
+                    }
+
+                    let mut combination = Combination::open(&txn_rc, operation, &keys).await?;
+
+                    let limit_len = cmd_inter_length_limit_or_default() as usize;
+                    let mut members = vec![];
+                    while let Some((member, score)) = combination.next(&weights, aggregate).await? {
+                        if limit_len > 0 && members.len() >= limit_len {
+                            return Err(match operation {
+                                SetOperation::Inter => REDIS_INTER_TOO_LARGE_ERR,
+                                SetOperation::Union => REDIS_UNION_TOO_LARGE_ERR,
+                                SetOperation::Diff => REDIS_DIFF_TOO_LARGE_ERR,
+                            });
+                        }
+                        members.push((score, member));
+                    }
+                    // members are scanned in order, a stable sort keeps ties by member
+                    members.sort_by(|a, b| a.0.total_cmp(&b.0));
+
+                    let mut resp = vec![];
+                    for (score, member) in members {
+                        resp.push(resp_bulk(member));
+                        if with_scores {
+                            resp.push(resp_bulk(score.to_string().as_bytes().to_vec()));
+                        }
+                    }
+                    Ok(resp_array(resp))
+                }
+                .boxed()
+            })
+            .await
+    }
+
+    /// Store the intersection, union or difference of the sets and zsets in
+    /// `dst` in one transaction, replacing `dst` whatever its type, or
+    /// deleting it if the result is empty. The members are added as they are
+    /// merged, `STORE_BATCH_SIZE` at a time, unless `dst` is one of the
+    /// keys, the whole result is read before `dst` is replaced then. A scan
+    /// error aborts the transaction, `dst` is left as it was.
+    pub async fn do_async_txnkv_zcombinestore(
+        mut self,
+        dst: &str,
+        operation: SetOperation,
+        keys: &[String],
+        weights: &[f64],
+        aggregate: Aggregate,
+    ) -> AsyncResult<Frame> {
+        let mut client = get_txn_client()?;
+        let dst = dst.to_owned();
+        let keys = keys.to_owned();
+        let weights = weights.to_owned();
+
+        let resp = client
+            .exec_in_txn(self.txn.clone(), |txn_rc| {
+                async move {
+                    if self.txn.is_none() {
+                        self.txn = Some(txn_rc.clone());
+                    }
+
+                    let mut combination = Combination::open(&txn_rc, operation, &keys).await?;
+
+                    let mut read = None;
+                    if keys.contains(&dst) {
+                        let mut members = vec![];
+                        while let Some(element) = combination.next(&weights, aggregate).await? {
+                            members.push(element);
+                        }
+                        read = Some(members.into_iter());
+                    }
+
+                    // the destination may be of any type, it is deleted like DEL
+                    check_reply(
+                        StringCommandCtx::new(self.txn.clone())
+                            .do_async_txnkv_del(&vec![dst.clone()])
+                            .await?,
+                    )?;
+                    let mut stored = 0;
+                    loop {
+                        let mut members = Vec::with_capacity(STORE_BATCH_SIZE);
+                        let mut scores = Vec::with_capacity(STORE_BATCH_SIZE);
+                        while members.len() < STORE_BATCH_SIZE {
+                            let element = match read.as_mut() {
+                                Some(read) => read.next(),
+                                None => combination.next(&weights, aggregate).await?,
+                            };
+                            match element {
+                                Some((member, score)) => {
+                                    members.push(String::from_utf8_lossy(&member).to_string());
+                                    scores.push(score);
+                                }
+                                None => break,
+                            }
+                        }
+                        if members.is_empty() {
+                            break;
+                        }
+                        stored += members.len() as i64;
+                        let last_batch = members.len() < STORE_BATCH_SIZE;
+                        check_reply(
+                            ZsetCommandCtx::new(self.txn.clone())
+                                .do_async_txnkv_zadd(&dst, &members, &scores, None, false, false)
+                                .await?,
+                        )?;
+                        if last_batch {
+                            break;
+                        }
+                    }
+                    Ok(resp_int(stored))
+                }
+                .boxed()
+            })
+            .await;
+
+        match resp {
+            Ok(frame) => Ok(frame),
+            Err(e) => Ok(resp_err(e)),
+        }
+    }
+
+    /// Cardinality of the intersection of the sets and zsets, stops counting
+    /// at `limit` if not 0.
+    pub async fn do_async_txnkv_zintercard(
+        mut self,
+        keys: &[String],
+        limit: usize,
+    ) -> AsyncResult<Frame> {
+        let mut client = get_txn_client()?;
+        let keys = keys.to_owned();
+
+        client
+            .exec_in_txn(self.txn.clone(), |txn_rc| {
+                async move {
+                    if self.txn.is_none() {
+                        self.txn = Some(txn_rc.clone());
+                    }
+
+                    let mut inter = Intersection::open(&txn_rc, &keys, true).await?;
+                    Ok(resp_int(inter.count(limit).await? as i64))
+                }
+                .boxed()
+            })
+            .await
+    }
+
     pub async fn do_async_txnkv_zrange(
         mut self,
         key: &str,
@@ -501,7 +776,7 @@ impl ZsetCommandCtx {
 
                     let mut txn = txn_rc.lock().await;
 
-                    let mut resp = vec![];
+                    let mut reply = ReplyBuilder::new();
                     match txn.get(meta_key.to_owned()).await? {
                         Some(meta_value) => {
                             // check key type and ttl
@@ -540,6 +815,8 @@ impl ZsetCommandCtx {
                                 .scan_stream(bound_range, size.try_into().unwrap())
                                 .await?;
 
+                            // the scores follow the prefix of the score keys
+                            let prefix_len = KeyDecoder::decode_data_key_prefix_len(&key);
                             let mut idx = 0;
                             while let Some(kv) = iter.next().await {
                                 if idx < min {
@@ -551,30 +828,21 @@ impl ZsetCommandCtx {
                                 }
                                 idx += 1;
 
-                                // decode member key from data key
-                                let member = kv.1;
-                                if reverse {
-                                    resp.insert(0, resp_bulk(member));
-                                } else {
-                                    resp.push(resp_bulk(member));
-                                }
+                                // the member is the value of the score key
+                                reply.push(resp_bulk(kv.1));
                                 if with_scores {
-                                    // decode vec[u8] to f64
-                                    let score =
-                                        KeyDecoder::decode_key_zset_score_from_scorekey(&key, kv.0);
-                                    if reverse {
-                                        resp.insert(
-                                            1,
-                                            resp_bulk(score.to_string().as_bytes().to_vec()),
-                                        );
-                                    } else {
-                                        resp.push(resp_bulk(score.to_string().as_bytes().to_vec()));
-                                    }
+                                    let score_key: Vec<u8> = kv.0.into();
+                                    reply.push_display(KeyDecoder::decode_key_zset_score_at(
+                                        &score_key, prefix_len,
+                                    ));
                                 }
                             }
-                            Ok(resp_array(resp))
+                            if reverse {
+                                reply.reverse(if with_scores { 2 } else { 1 });
+                            }
+                            Ok(reply.build())
                         }
-                        None => Ok(resp_array(resp)),
+                        None => Ok(reply.build()),
                     }
                 }
                 .boxed()
@@ -606,7 +874,7 @@ impl ZsetCommandCtx {
 
                     let mut txn = txn_rc.lock().await;
 
-                    let mut resp = vec![];
+                    let mut reply = ReplyBuilder::new();
                     match txn.get(meta_key.to_owned()).await? {
                         Some(meta_value) => {
                             // check key type and ttl
@@ -653,30 +921,23 @@ impl ZsetCommandCtx {
                                 .scan_stream(bound_range, size.try_into().unwrap())
                                 .await?;
 
+                            // the scores follow the prefix of the score keys
+                            let prefix_len = KeyDecoder::decode_data_key_prefix_len(&key);
                             while let Some(kv) = iter.next().await {
-                                let member = kv.1;
-                                if reverse {
-                                    resp.insert(0, resp_bulk(member));
-                                } else {
-                                    resp.push(resp_bulk(member));
-                                }
+                                reply.push(resp_bulk(kv.1));
                                 if with_scores {
-                                    // decode score from score key
-                                    let score =
-                                        KeyDecoder::decode_key_zset_score_from_scorekey(&key, kv.0);
-                                    if reverse {
-                                        resp.insert(
-                                            1,
-                                            resp_bulk(score.to_string().as_bytes().to_vec()),
-                                        );
-                                    } else {
-                                        resp.push(resp_bulk(score.to_string().as_bytes().to_vec()));
-                                    }
+                                    let score_key: Vec<u8> = kv.0.into();
+                                    reply.push_display(KeyDecoder::decode_key_zset_score_at(
+                                        &score_key, prefix_len,
+                                    ));
                                 }
                             }
-                            Ok(resp_array(resp))
+                            if reverse {
+                                reply.reverse(if with_scores { 2 } else { 1 });
+                            }
+                            Ok(reply.build())
                         }
-                        None => Ok(resp_array(resp)),
+                        None => Ok(reply.build()),
                     }
                 }
                 .boxed()
@@ -684,6 +945,223 @@ impl ZsetCommandCtx {
             .await
     }
 
+    /// The members and scores of the range of the zset at `version`, from the
+    /// highest if `rev`
+    async fn txnkv_zrange_members(
+        self,
+        txn_rc: &Arc<Mutex<Transaction>>,
+        key: &str,
+        version: u16,
+        by: &ZrangeBy,
+        rev: bool,
+    ) -> AsyncResult<Vec<(String, f64)>> {
+        let mut members = vec![];
+        match by {
+            ZrangeBy::Rank(start, stop) => {
+                let size = self.txnkv_sum_key_size(key, version).await?;
+                let (mut start, mut stop) = (*start, *stop);
+                if start < 0 {
+                    start += size;
+                }
+                if stop < 0 {
+                    stop += size;
+                }
+                let start = start.max(0);
+                let stop = stop.min(size - 1);
+                if start > stop {
+                    return Ok(members);
+                }
+                // the ranks from the highest are the ranks from the lowest
+                // the other way round
+                let (start, stop) = if rev {
+                    (size - 1 - stop, size - 1 - start)
+                } else {
+                    (start, stop)
+                };
+                let bound_range = KEY_ENCODER.encode_txnkv_zset_score_key_range(key, version);
+                let mut txn = txn_rc.lock().await;
+                let mut iter = txn
+                    .scan_stream(bound_range, (stop + 1).try_into().unwrap())
+                    .await?
+                    .skip(start as usize);
+                let prefix_len = KeyDecoder::decode_data_key_prefix_len(key);
+                while let Some(kv) = iter.next().await {
+                    let score_key: Vec<u8> = kv.0.into();
+                    members.push((
+                        String::from_utf8_lossy(&kv.1).to_string(),
+                        KeyDecoder::decode_key_zset_score_at(&score_key, prefix_len),
+                    ));
+                }
+            }
+            ZrangeBy::Score {
+                min,
+                min_inclusive,
+                max,
+                max_inclusive,
+            } => {
+                if min > max {
+                    return Ok(members);
+                }
+                let start_key = KEY_ENCODER.encode_txnkv_zset_score_key_score_start(
+                    key,
+                    *min,
+                    *min_inclusive,
+                    version,
+                );
+                let end_key = KEY_ENCODER.encode_txnkv_zset_score_key_score_end(
+                    key,
+                    *max,
+                    *max_inclusive,
+                    version,
+                );
+                if start_key >= end_key {
+                    return Ok(members);
+                }
+                let range: BoundRange = (start_key..end_key).into();
+                let mut txn = txn_rc.lock().await;
+                let mut iter = txn.scan_stream(range, u32::MAX).await?;
+                let prefix_len = KeyDecoder::decode_data_key_prefix_len(key);
+                while let Some(kv) = iter.next().await {
+                    let score_key: Vec<u8> = kv.0.into();
+                    members.push((
+                        String::from_utf8_lossy(&kv.1).to_string(),
+                        KeyDecoder::decode_key_zset_score_at(&score_key, prefix_len),
+                    ));
+                }
+            }
+            ZrangeBy::Lex(min, max) => {
+                // the data keys are sorted by member, a key just after a
+                // member is the member followed by a zero byte
+                let after = |member: &str| {
+                    let mut data_key: Vec<u8> = KEY_ENCODER
+                        .encode_txnkv_zset_data_key(key, member, version)
+                        .into();
+                    data_key.push(0);
+                    Key::from(data_key)
+                };
+                let start_key = match min {
+                    LexBound::Min => KEY_ENCODER.encode_txnkv_zset_data_key_start(key, version),
+                    LexBound::Max => return Ok(members),
+                    LexBound::Inclusive(member) => {
+                        KEY_ENCODER.encode_txnkv_zset_data_key(key, member, version)
+                    }
+                    LexBound::Exclusive(member) => after(member),
+                };
+                let end_key = match max {
+                    LexBound::Min => return Ok(members),
+                    LexBound::Max => KEY_ENCODER.encode_txnkv_zset_data_key_end(key, version),
+                    LexBound::Inclusive(member) => after(member),
+                    LexBound::Exclusive(member) => {
+                        KEY_ENCODER.encode_txnkv_zset_data_key(key, member, version)
+                    }
+                };
+                if start_key >= end_key {
+                    return Ok(members);
+                }
+                let range: BoundRange = (start_key..end_key).into();
+                let mut txn = txn_rc.lock().await;
+                let mut iter = txn.scan_stream(range, u32::MAX).await?;
+                while let Some(kv) = iter.next().await {
+                    let score = KeyDecoder::decode_key_zset_data_value(&kv.1);
+                    let member = KeyDecoder::decode_key_zset_member_from_datakey(key, kv.0);
+                    members.push((String::from_utf8_lossy(&member).to_string(), score));
+                }
+            }
+        }
+        if rev {
+            members.reverse();
+        }
+        Ok(members)
+    }
+
+    /// Store the members of the range of `src` in `dst` in one transaction,
+    /// replacing `dst` whatever its type, or deleting it if the range is
+    /// empty. `limit` skips and takes members of a score or lex range after
+    /// REV, a negative count takes all of them.
+    pub async fn do_async_txnkv_zrangestore(
+        mut self,
+        dst: &str,
+        src: &str,
+        by: ZrangeBy,
+        rev: bool,
+        limit: Option<(i64, i64)>,
+    ) -> AsyncResult<Frame> {
+        let mut client = get_txn_client()?;
+        let meta_key = KEY_ENCODER.encode_txnkv_meta_key(src);
+        let dst = dst.to_owned();
+        let src = src.to_owned();
+
+        let resp = client
+            .exec_in_txn(self.txn.clone(), |txn_rc| {
+                async move {
+                    if self.txn.is_none() {
+                        self.txn = Some(txn_rc.clone());
+                    }
+                    let meta_value = txn_rc.lock().await.get(meta_key).await?;
+                    let mut members = match meta_value {
+                        Some(meta_value) => {
+                            if !matches!(KeyDecoder::decode_key_type(&meta_value), DataType::Zset) {
+                                return Err(REDIS_WRONG_TYPE_ERR);
+                            }
+                            let (ttl, version, _) = KeyDecoder::decode_key_meta(&meta_value);
+                            if key_is_expired(ttl) {
+                                self.clone()
+                                    .do_async_txnkv_zset_expire_if_needed(&src)
+                                    .await?;
+                                vec![]
+                            } else {
+                                self.clone()
+                                    .txnkv_zrange_members(&txn_rc, &src, version, &by, rev)
+                                    .await?
+                            }
+                        }
+                        None => vec![],
+                    };
+                    if let Some((offset, count)) = limit {
+                        let count = if count < 0 {
+                            usize::MAX
+                        } else {
+                            count as usize
+                        };
+                        members = if offset < 0 {
+                            vec![]
+                        } else {
+                            members
+                                .into_iter()
+                                .skip(offset as usize)
+                                .take(count)
+                                .collect()
+                        };
+                    }
+
+                    // the destination may be of any type, it is deleted like DEL
+                    check_reply(
+                        StringCommandCtx::new(self.txn.clone())
+                            .do_async_txnkv_del(&vec![dst.clone()])
+                            .await?,
+                    )?;
+                    let len = members.len() as i64;
+                    if len > 0 {
+                        let (members, scores): (Vec<String>, Vec<f64>) =
+                            members.into_iter().unzip();
+                        check_reply(
+                            ZsetCommandCtx::new(self.txn.clone())
+                                .do_async_txnkv_zadd(&dst, &members, &scores, None, false, false)
+                                .await?,
+                        )?;
+                    }
+                    Ok(resp_int(len))
+                }
+                .boxed()
+            })
+            .await;
+
+        match resp {
+            Ok(frame) => Ok(frame),
+            Err(e) => Ok(resp_err(e)),
+        }
+    }
+
     // pub async fn do_async_txnkv_zrange_by_lex(self, key: &str, min: &str, with_min: bool, max: &str, with_max: bool, with_scores: bool, reverse: bool) -> AsyncResult<Frame> {
     //     Ok(resp_nil())
     // }
@@ -754,9 +1232,13 @@ impl ZsetCommandCtx {
                                     poped_count += 1;
                                 }
                             } else {
-                                let iter = txn
-                                    .scan_keys_reverse(bound_range, count.try_into().unwrap())
-                                    .await?;
+                                // highest scores first, the score keys sort by score
+                                let iter: Vec<Key> = txn
+                                    .scan_reverse_stream(bound_range, count.try_into().unwrap())
+                                    .await?
+                                    .map(|kv| kv.0)
+                                    .collect()
+                                    .await;
                                 for k in iter {
                                     let member = KeyDecoder::decode_key_zset_member_from_scorekey(
                                         &key,
@@ -829,6 +1311,64 @@ impl ZsetCommandCtx {
         }
     }
 
+    /// Pop `count` members of the first non empty sorted set of `keys`,
+    /// probed in order in one transaction. Replies the key and the popped
+    /// members with their scores, nil if all the sorted sets are empty.
+    pub async fn do_async_txnkv_zmpop(
+        mut self,
+        keys: &[String],
+        from_min: bool,
+        count: u64,
+    ) -> AsyncResult<Frame> {
+        let mut client = get_txn_client()?;
+        let keys = keys.to_owned();
+
+        client
+            .exec_in_txn(self.txn.clone(), |txn_rc| {
+                async move {
+                    if self.txn.is_none() {
+                        self.txn = Some(txn_rc.clone());
+                    }
+                    for key in keys {
+                        let meta_key = KEY_ENCODER.encode_txnkv_meta_key(&key);
+                        let meta_value = txn_rc.lock().await.get(meta_key).await?;
+                        match meta_value {
+                            Some(meta_value) => {
+                                if !matches!(
+                                    KeyDecoder::decode_key_type(&meta_value),
+                                    DataType::Zset
+                                ) {
+                                    return Ok(resp_err(REDIS_WRONG_TYPE_ERR));
+                                }
+                            }
+                            None => continue,
+                        }
+                        // an expired sorted set pops nothing
+                        let flat = match self
+                            .clone()
+                            .do_async_txnkv_zpop(&key, from_min, count)
+                            .await?
+                        {
+                            Frame::Array(flat) if flat.is_empty() => continue,
+                            Frame::Array(flat) => flat,
+                            frame => return Ok(frame),
+                        };
+                        let members = flat
+                            .chunks(2)
+                            .map(|pair| resp_array(pair.to_vec()))
+                            .collect();
+                        return Ok(resp_array(vec![
+                            resp_bulk(key.into_bytes()),
+                            resp_array(members),
+                        ]));
+                    }
+                    Ok(resp_nil())
+                }
+                .boxed()
+            })
+            .await
+    }
+
     pub async fn do_async_txnkv_zrank(mut self, key: &str, member: &str) -> AsyncResult<Frame> {
         let mut client = get_txn_client()?;
         let meta_key = KEY_ENCODER.encode_txnkv_meta_key(key);
@@ -988,8 +1528,11 @@ impl ZsetCommandCtx {
 
                             prev_score = 0f64;
 
-                            let meta_value =
-                                KEY_ENCODER.encode_txnkv_zset_meta_value(0, version, 0);
+                            let meta_value = KEY_ENCODER.encode_txnkv_zset_meta_value(
+                                expire_timestamp_of_new_key(0),
+                                version,
+                                0,
+                            );
                             txn.put(meta_key, meta_value).await?;
                             data_key =
                                 KEY_ENCODER.encode_txnkv_zset_data_key(&key, &member, version);
@@ -1443,9 +1986,10 @@ impl ZsetCommandCtx {
     pub async fn do_async_txnkv_zset_expire_if_needed(mut self, key: &str) -> AsyncResult<i64> {
         let mut client = get_txn_client()?;
         let key = key.to_owned();
+        let expired_key = key.clone();
         let meta_key = KEY_ENCODER.encode_txnkv_meta_key(&key);
 
-        client
+        let expired = client
             .exec_in_txn(self.txn.clone(), |txn_rc| {
                 async move {
                     if self.txn.is_none() {
@@ -1457,7 +2001,7 @@ impl ZsetCommandCtx {
                         Some(meta_value) => {
                             let ttl = KeyDecoder::decode_key_ttl(&meta_value);
                             if !key_is_expired(ttl) {
-                                return Ok(0);
+                                return Ok(None);
                             }
 
                             let version = KeyDecoder::decode_key_version(&meta_value);
@@ -1515,16 +2059,22 @@ impl ZsetCommandCtx {
                                 txn.delete(meta_key).await?;
                             }
 
-                            REMOVED_EXPIRED_KEY_COUNTER
-                                .with_label_values(&["zset"])
-                                .inc();
-                            Ok(1)
+                            count_expired_key("zset");
+                            Ok(Some(ttl))
                         }
-                        None => Ok(0),
+                        None => Ok(None),
                     }
                 }
                 .boxed()
             })
-            .await
+            .await?;
+        match expired {
+            Some(expire_at) => {
+                fire(EVENT_EXPIRED, &expired_key).await;
+                ship_expired(DataType::Zset, &expired_key, expire_at).await;
+                Ok(1)
+            }
+            None => Ok(0),
+        }
     }
 }
@@ -0,0 +1,210 @@
+//! Event triggers on key patterns.
+//!
+//! Each rule of `[[server.triggers]]` matches keys with a glob `pattern` and a
+//! list of `events`, the names of write commands (`set`, `del`, `hset`, ...),
+//! `expired` for keys removed after their TTL, `evicted` for keys evicted over
+//! quota, or `*` for all of them. Matched
+//! events are delivered asynchronously to the rule target, POSTed as JSON to an
+//! `http://` webhook or published to a `nats://host:port/subject` queue.
+//!
+//! The rules fire for the writes which changed the keyspace, once committed,
+//! those queued by MULTI when EXEC committed them. A write replying an error
+//! or which changed nothing does not fire.
+//!
+//! Delivery is best effort, events are dropped when the queue is full or the
+//! target keeps failing, so writes are never slowed down by a target.
+
+use std::collections::HashMap;
+use std::sync::RwLock;
+use std::time::Duration;
+
+use hyper::{Body, Client, Method, Request};
+use slog::{error, info, warn};
+use tokio::sync::mpsc::{self, Sender};
+
+use crate::cmdlog::{NatsSink, WriteEvent};
+use crate::config::{TriggerRule, LOGGER};
+use crate::metrics::TRIGGER_EVENT_COUNTER;
+use crate::notify::notify_keyspace_event;
+use crate::utils::{glob_match, json_quote, now_timestamp_in_millis};
+use crate::{config_instance_id_or_default, config_triggers_or_default};
+
+const TRIGGER_QUEUE_SIZE: usize = 10000;
+const WEBHOOK_TIMEOUT: u64 = 3000;
+const DELIVERY_RETRIES: u64 = 2;
+
+pub const EVENT_EXPIRED: &str = "expired";
+pub const EVENT_EVICTED: &str = "evicted";
+
+struct CompiledRule {
+    pattern: Vec<u8>,
+    events: Vec<String>,
+    target: String,
+}
+
+impl CompiledRule {
+    fn matches(&self, event: &str, key: &str) -> bool {
+        self.events.iter().any(|e| e == "*" || e == event)
+            && glob_match(&self.pattern, key.as_bytes(), false)
+    }
+}
+
+struct TriggerEvent {
+    target: String,
+    event: String,
+    key: String,
+    ts: u64,
+}
+
+impl TriggerEvent {
+    fn to_json(&self) -> String {
+        format!(
+            "{{\"ts\":{},\"namespace\":{},\"event\":{},\"key\":{}}}",
+            self.ts,
+            json_quote(&config_instance_id_or_default()),
+            json_quote(&self.event),
+            json_quote(&self.key)
+        )
+    }
+}
+
+lazy_static! {
+    static ref RULES: Vec<CompiledRule> = compile_rules(config_triggers_or_default());
+    static ref TRIGGER_TX: RwLock<Option<Sender<TriggerEvent>>> = RwLock::new(None);
+}
+
+fn compile_rules(rules: Vec<TriggerRule>) -> Vec<CompiledRule> {
+    rules
+        .into_iter()
+        .map(|rule| CompiledRule {
+            pattern: rule.pattern.into_bytes(),
+            events: rule.events.iter().map(|e| e.to_lowercase()).collect(),
+            target: rule.target,
+        })
+        .collect()
+}
+
+pub fn triggers_enabled() -> bool {
+    !RULES.is_empty()
+}
+
+/// Number of matched events queued to be delivered
+pub fn trigger_queue_depth() -> usize {
+    match TRIGGER_TX.read().unwrap().as_ref() {
+        Some(tx) => TRIGGER_QUEUE_SIZE.saturating_sub(tx.capacity()),
+        None => 0,
+    }
+}
+
+/// Fire the rules matching `event` on `key`, and publish its keyspace
+/// notifications
+pub async fn fire(event: &str, key: &str) {
+    notify_keyspace_event(event, key);
+    if !triggers_enabled() {
+        return;
+    }
+    let tx = match TRIGGER_TX.read().unwrap().clone() {
+        Some(tx) => tx,
+        None => return,
+    };
+    for rule in RULES.iter().filter(|rule| rule.matches(event, key)) {
+        let trigger_event = TriggerEvent {
+            target: rule.target.clone(),
+            event: event.to_owned(),
+            key: key.to_owned(),
+            ts: now_timestamp_in_millis(),
+        };
+        if tx.try_send(trigger_event).is_err() {
+            TRIGGER_EVENT_COUNTER.with_label_values(&["dropped"]).inc();
+        }
+    }
+}
+
+/// Fire the rules matching the keys written by the command
+pub async fn fire_write(event: &WriteEvent) {
+    for key in &event.keys {
+        fire(&event.cmd, &String::from_utf8_lossy(key)).await;
+    }
+}
+
+async fn deliver_webhook(url: &str, payload: String) -> crate::Result<()> {
+    let req = Request::builder()
+        .method(Method::POST)
+        .uri(url)
+        .header("content-type", "application/json")
+        .body(Body::from(payload))?;
+    let resp = tokio::time::timeout(
+        Duration::from_millis(WEBHOOK_TIMEOUT),
+        Client::new().request(req),
+    )
+    .await
+    .map_err(|_| "webhook timeout")??;
+    if !resp.status().is_success() {
+        return Err(format!("webhook replied {}", resp.status()).into());
+    }
+    Ok(())
+}
+
+/// Deliver the matched events to the rule targets until the server exits,
+/// does nothing if no rule is configured.
+pub async fn run_trigger_dispatcher() {
+    if !triggers_enabled() {
+        return;
+    }
+    let (tx, mut rx) = mpsc::channel::<TriggerEvent>(TRIGGER_QUEUE_SIZE);
+    *TRIGGER_TX.write().unwrap() = Some(tx);
+    info!(LOGGER, "[TRIGGER] {} rules loaded", RULES.len());
+
+    let mut nats_sinks: HashMap<String, NatsSink> = HashMap::new();
+    while let Some(event) = rx.recv().await {
+        let payload = event.to_json();
+        let mut attempt = 0;
+        loop {
+            let res = if event.target.starts_with("nats://") {
+                if !nats_sinks.contains_key(&event.target) {
+                    match NatsSink::from_url(&event.target) {
+                        Some(sink) => {
+                            nats_sinks.insert(event.target.clone(), sink);
+                        }
+                        None => {
+                            error!(LOGGER, "[TRIGGER] invalid target {}", event.target);
+                            break;
+                        }
+                    }
+                }
+                let sink = nats_sinks.get_mut(&event.target).unwrap();
+                sink.publish(&[payload.clone()]).await
+            } else {
+                deliver_webhook(&event.target, payload.clone()).await
+            };
+            match res {
+                Ok(_) => {
+                    TRIGGER_EVENT_COUNTER
+                        .with_label_values(&["delivered"])
+                        .inc();
+                    break;
+                }
+                Err(e) if attempt < DELIVERY_RETRIES => {
+                    attempt += 1;
+                    warn!(
+                        LOGGER,
+                        "[TRIGGER] deliver to {} failed: {}, retry {}/{}",
+                        event.target,
+                        e,
+                        attempt,
+                        DELIVERY_RETRIES
+                    );
+                    tokio::time::sleep(Duration::from_millis(100 * attempt)).await;
+                }
+                Err(e) => {
+                    error!(
+                        LOGGER,
+                        "[TRIGGER] deliver to {} failed: {}, drop event", event.target, e
+                    );
+                    TRIGGER_EVENT_COUNTER.with_label_values(&["failed"]).inc();
+                    break;
+                }
+            }
+        }
+    }
+}
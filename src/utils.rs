@@ -1,4 +1,5 @@
 use crate::frame::Frame;
+use bytes::BytesMut;
 use hex::ToHex;
 use mlua::{Lua, Value as LuaValue};
 use sha1::{Digest, Sha1};
@@ -6,11 +7,13 @@ use std::io;
 use std::{
     collections::HashSet,
     convert::TryInto,
+    fmt,
     time::{SystemTime, UNIX_EPOCH},
 };
 use tokio::time::Duration;
 
 use crate::tikv::errors::{RTError, REDIS_LUA_PANIC};
+use crate::{backend_default_ttl_ms_or_default, backend_max_ttl_ms_or_default};
 use rustls::{
     internal::pemfile::{certs, rsa_private_keys},
     AllowAnyAuthenticatedClient, RootCertStore,
@@ -22,6 +25,9 @@ use std::path::Path;
 
 const TIMESTAMP_FORMAT: &str = "%Y/%m/%d %H:%M:%S%.3f %:z";
 
+/// Bytes reserved at once for the bulk strings of a `ReplyBuilder`
+const REPLY_ARENA_CHUNK_SIZE: usize = 16 * 1024;
+
 pub fn resp_ok() -> Frame {
     Frame::Simple("OK".to_string())
 }
@@ -66,6 +72,80 @@ pub fn resp_array(val: Vec<Frame>) -> Frame {
     Frame::Array(val)
 }
 
+/// Builds an array reply of many bulk strings. The bytes of the strings are
+/// copied to chunks shared by the elements, which reference them with
+/// `Bytes` slices, instead of a `Vec` allocated for each element.
+#[derive(Debug, Default)]
+pub struct ReplyBuilder {
+    frames: Vec<Frame>,
+    arena: BytesMut,
+}
+
+impl ReplyBuilder {
+    pub fn new() -> ReplyBuilder {
+        ReplyBuilder::default()
+    }
+
+    pub fn with_capacity(len: usize) -> ReplyBuilder {
+        ReplyBuilder {
+            frames: Vec::with_capacity(len),
+            arena: BytesMut::new(),
+        }
+    }
+
+    fn reserve(&mut self, additional: usize) {
+        if self.arena.capacity() < additional {
+            self.arena.reserve(additional.max(REPLY_ARENA_CHUNK_SIZE));
+        }
+    }
+
+    /// Push a bulk string copied to the arena, for the members and fields
+    /// decoded from the keys
+    pub fn push_bulk(&mut self, val: &[u8]) {
+        self.reserve(val.len());
+        self.arena.extend_from_slice(val);
+        self.frames.push(Frame::Bulk(self.arena.split().freeze()));
+    }
+
+    /// Push the formatted value as a bulk string, like a score
+    pub fn push_display(&mut self, val: impl fmt::Display) {
+        use std::fmt::Write;
+
+        // a formatted number is shorter than this
+        self.reserve(32);
+        let _ = write!(self.arena, "{}", val);
+        self.frames.push(Frame::Bulk(self.arena.split().freeze()));
+    }
+
+    /// Push a frame as it is, for the values already owned
+    pub fn push(&mut self, frame: Frame) {
+        self.frames.push(frame);
+    }
+
+    pub fn len(&self) -> usize {
+        self.frames.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.frames.is_empty()
+    }
+
+    /// Reverse the order of the elements in groups of `group`, like the
+    /// member and score pairs of WITHSCORES
+    pub fn reverse(&mut self, group: usize) {
+        self.frames.reverse();
+        if group > 1 {
+            for chunk in self.frames.chunks_mut(group) {
+                chunk.reverse();
+            }
+        }
+    }
+
+    pub fn build(self) -> Frame {
+        Frame::Array(self.frames)
+    }
+}
+
 pub async fn sleep(ms: u32) {
     tokio::time::sleep(Duration::from_millis(ms as u64)).await;
 }
@@ -162,10 +242,42 @@ pub fn now_timestamp_in_millis() -> u64 {
     d.as_secs() * 1000 + d.subsec_millis() as u64
 }
 
+pub fn now_timestamp_in_micros() -> u64 {
+    let d = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .expect("Time went backwards");
+    d.as_secs() * 1_000_000 + d.subsec_micros() as u64
+}
+
 pub fn timestamp_from_ttl(ttl: u64) -> u64 {
     ttl + now_timestamp_in_millis()
 }
 
+/// Clamp the expire timestamp to `max_ttl_ms` from now, a key without
+/// expiration is clamped as well.
+pub fn clamp_expire_timestamp(timestamp: u64) -> u64 {
+    let max_ttl = backend_max_ttl_ms_or_default();
+    if max_ttl == 0 {
+        return timestamp;
+    }
+    let max_timestamp = timestamp_from_ttl(max_ttl);
+    if timestamp == 0 || timestamp > max_timestamp {
+        max_timestamp
+    } else {
+        timestamp
+    }
+}
+
+/// Expire timestamp of a key being created, `default_ttl_ms` applies if the
+/// key is created without expiration.
+pub fn expire_timestamp_of_new_key(timestamp: u64) -> u64 {
+    let default_ttl = backend_default_ttl_ms_or_default();
+    if timestamp == 0 && default_ttl > 0 {
+        return clamp_expire_timestamp(timestamp_from_ttl(default_ttl));
+    }
+    clamp_expire_timestamp(timestamp)
+}
+
 pub fn ttl_from_timestamp(timestamp: u64) -> u64 {
     let now = now_timestamp_in_millis();
     if now > timestamp {
@@ -227,6 +339,258 @@ pub fn sha1hex(s: &str) -> String {
     sha1.encode_hex::<String>()
 }
 
+fn request_arg(parts: &[Frame], i: usize) -> &[u8] {
+    match parts.get(i) {
+        Some(Frame::Bulk(b)) => b,
+        Some(Frame::Simple(s)) => s.as_bytes(),
+        _ => b"",
+    }
+}
+
+/// Whether the argument `i` of the request `parts` is a password, which is
+/// never logged: the arguments of AUTH, the AUTH ones of HELLO and MIGRATE,
+/// and the values of the password parameters of CONFIG SET.
+fn is_secret_arg(parts: &[Frame], i: usize) -> bool {
+    let token =
+        |at: usize, name: &str| request_arg(parts, at).eq_ignore_ascii_case(name.as_bytes());
+    let name = String::from_utf8_lossy(request_arg(parts, 0)).to_lowercase();
+    match name.as_str() {
+        "auth" => i > 0,
+        // HELLO protover AUTH username password
+        "hello" => (2..i).any(|at| token(at, "AUTH") && i <= at + 2),
+        // the options after the timeout, up to the keys
+        "migrate" => (6..i)
+            .take_while(|at| !token(*at, "KEYS"))
+            .any(|at| (token(at, "AUTH") && i == at + 1) || (token(at, "AUTH2") && i <= at + 2)),
+        "config" => {
+            token(1, "SET")
+                && i >= 3
+                && i % 2 == 1
+                && (token(i - 1, "requirepass") || token(i - 1, "masterauth"))
+        }
+        _ => false,
+    }
+}
+
+/// Whether the request frame carries a password, see `is_secret_arg`
+pub fn has_secret_args(frame: &Frame) -> bool {
+    match frame {
+        Frame::Array(parts) => (1..parts.len()).any(|i| is_secret_arg(parts, i)),
+        _ => false,
+    }
+}
+
+/// Describe a request frame for logging with all argument values redacted,
+/// keep the command name and the key (or a hash of it) for troubleshooting.
+pub fn redact_request(frame: &Frame, hash_key: bool) -> String {
+    let parts = match frame {
+        Frame::Array(parts) => parts,
+        _ => return "<redacted>".to_owned(),
+    };
+    let mut desc = match parts.first() {
+        Some(Frame::Bulk(name)) => String::from_utf8_lossy(name).to_lowercase(),
+        _ => return "<redacted>".to_owned(),
+    };
+    if let (Some(Frame::Bulk(key)), false) = (parts.get(1), is_secret_arg(parts, 1)) {
+        let key = String::from_utf8_lossy(key);
+        desc.push(' ');
+        if hash_key {
+            desc.push_str(&sha1hex(&key)[..12]);
+        } else {
+            desc.push_str(&key);
+        }
+    }
+    if parts.len() > 2 {
+        desc.push_str(&format!(" <{} args redacted>", parts.len() - 2));
+    }
+    desc
+}
+
+/// Describe a request frame for logging, keep at most `max_args` arguments
+/// and `max_arg_len` bytes of each, 0 for no limit. Passwords are redacted.
+pub fn describe_request(frame: &Frame, max_args: usize, max_arg_len: usize) -> String {
+    let parts = match frame {
+        Frame::Array(parts) => parts,
+        _ => return format!("{:?}", frame),
+    };
+    let mut desc = String::new();
+    for (i, part) in parts.iter().enumerate() {
+        // the command name is not counted as an argument
+        if max_args > 0 && i > max_args {
+            desc.push_str(&format!(" <{} more args>", parts.len() - i));
+            break;
+        }
+        let other;
+        let arg: &[u8] = match part {
+            Frame::Bulk(b) => b,
+            Frame::Simple(s) => s.as_bytes(),
+            _ => {
+                other = format!("{:?}", part);
+                other.as_bytes()
+            }
+        };
+        if i > 0 {
+            desc.push(' ');
+        }
+        if is_secret_arg(parts, i) {
+            desc.push_str("<redacted>");
+        } else if max_arg_len > 0 && arg.len() > max_arg_len {
+            desc.push_str(&format!(
+                "{:?}<{} more bytes>",
+                String::from_utf8_lossy(&arg[..max_arg_len]),
+                arg.len() - max_arg_len
+            ));
+        } else {
+            desc.push_str(&format!("{:?}", String::from_utf8_lossy(arg)));
+        }
+    }
+    desc
+}
+
+/// Quote `s` as a JSON string
+pub fn json_quote(s: &str) -> String {
+    let mut quoted = String::with_capacity(s.len() + 2);
+    quoted.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            c if (c as u32) < 0x20 => quoted.push_str(&format!("\\u{:04x}", c as u32)),
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+/// Byte range of a string of `len` bytes selected by the inclusive `start`
+/// and `end` offsets of GETRANGE, negative offsets count from the end.
+pub fn string_range(len: usize, start: i64, end: i64) -> std::ops::Range<usize> {
+    if start < 0 && end < 0 && start > end {
+        return 0..0;
+    }
+    let len = len as i64;
+    let start = if start < 0 {
+        (len + start).max(0)
+    } else {
+        start
+    };
+    let end = if end < 0 {
+        (len + end).max(0)
+    } else {
+        end.min(len - 1)
+    };
+    if len == 0 || start > end {
+        return 0..0;
+    }
+    start as usize..end as usize + 1
+}
+
+/// Match the byte `c` against the element of `pattern` at `p`, which is not
+/// a `*`, returns the position of the next element if it matches.
+fn glob_match_one(pattern: &[u8], mut p: usize, c: u8, nocase: bool) -> Option<usize> {
+    let eq = |a: u8, b: u8| {
+        if nocase {
+            a.eq_ignore_ascii_case(&b)
+        } else {
+            a == b
+        }
+    };
+    match pattern[p] {
+        b'?' => Some(p + 1),
+        b'[' => {
+            p += 1;
+            let not = p < pattern.len() && pattern[p] == b'^';
+            if not {
+                p += 1;
+            }
+            let mut matched = false;
+            // an unterminated class ends with the pattern
+            while p < pattern.len() {
+                if pattern[p] == b'\\' && p + 1 < pattern.len() {
+                    p += 1;
+                    if eq(pattern[p], c) {
+                        matched = true;
+                    }
+                } else if pattern[p] == b']' {
+                    p += 1;
+                    break;
+                } else if p + 2 < pattern.len() && pattern[p + 1] == b'-' {
+                    let (mut start, mut end) = (pattern[p], pattern[p + 2]);
+                    let mut c = c;
+                    if start > end {
+                        std::mem::swap(&mut start, &mut end);
+                    }
+                    if nocase {
+                        start = start.to_ascii_lowercase();
+                        end = end.to_ascii_lowercase();
+                        c = c.to_ascii_lowercase();
+                    }
+                    p += 2;
+                    if c >= start && c <= end {
+                        matched = true;
+                    }
+                } else if eq(pattern[p], c) {
+                    matched = true;
+                }
+                p += 1;
+            }
+            if matched != not {
+                Some(p)
+            } else {
+                None
+            }
+        }
+        b'\\' if p + 1 < pattern.len() && eq(pattern[p + 1], c) => Some(p + 2),
+        b'\\' if p + 1 < pattern.len() => None,
+        lit if eq(lit, c) => Some(p + 1),
+        _ => None,
+    }
+}
+
+/// Match `string` against the glob `pattern` like Redis `stringmatchlen`,
+/// supporting `*`, `?`, `[abc]`, `[^a-z]` and `\` escapes, binary safe.
+///
+/// On a mismatch, only the last `*` is backtracked to match one more byte,
+/// the earlier ones never have to match more, so the match takes at most
+/// the product of the lengths whatever the pattern.
+pub fn glob_match(pattern: &[u8], string: &[u8], nocase: bool) -> bool {
+    let (mut p, mut s) = (0, 0);
+    // the element after the last `*` and the position of string it matches
+    let mut backtrack: Option<(usize, usize)> = None;
+    while s < string.len() {
+        if p < pattern.len() && pattern[p] == b'*' {
+            while p < pattern.len() && pattern[p] == b'*' {
+                p += 1;
+            }
+            if p == pattern.len() {
+                return true;
+            }
+            backtrack = Some((p, s));
+            continue;
+        }
+        if p < pattern.len() {
+            if let Some(next) = glob_match_one(pattern, p, string[s], nocase) {
+                p = next;
+                s += 1;
+                continue;
+            }
+        }
+        match backtrack {
+            Some((star_p, star_s)) => {
+                p = star_p;
+                s = star_s + 1;
+                backtrack = Some((star_p, s));
+            }
+            None => return false,
+        }
+    }
+    while p < pattern.len() && pattern[p] == b'*' {
+        p += 1;
+    }
+    p == pattern.len()
+}
+
 pub fn count_unique_keys<T: std::hash::Hash + std::cmp::Eq>(keys: &[T]) -> usize {
     keys.iter().collect::<HashSet<&T>>().len()
 }
@@ -0,0 +1,365 @@
+//! RESP over WebSocket.
+//!
+//! With `ws_port` set, browser dashboards and edge workers connect through
+//! the HTTP ingress in front of tidis and talk RESP inside WebSocket messages.
+//! After the upgrade the connection is served like a TCP client, with AUTH,
+//! the ip filter and the limits of the server. The payloads of the messages of
+//! the client are read as one RESP stream, whatever their boundaries, and the
+//! replies written before each flush are sent as one binary message.
+
+use std::io;
+
+use async_std::io::{BufReader, BufWriter, WriteExt};
+use async_std::net::TcpStream;
+use bytes::{Buf, BytesMut};
+use futures::AsyncReadExt;
+use sha1::{Digest, Sha1};
+
+/// Appended to the key of the client to compute the accept key, RFC 6455
+const ACCEPT_GUID: &str = "258EAFA5-E914-47DA-95CA-C5AB0DC85B11";
+
+/// Largest head of the upgrade request
+const MAX_REQUEST_HEAD_LEN: usize = 8 * 1024;
+
+/// Subprotocol selected when the client offers it
+const SUBPROTOCOL: &str = "resp";
+
+const OP_CONTINUATION: u8 = 0x0;
+const OP_TEXT: u8 = 0x1;
+const OP_BINARY: u8 = 0x2;
+const OP_CLOSE: u8 = 0x8;
+const OP_PING: u8 = 0x9;
+const OP_PONG: u8 = 0xa;
+
+fn invalid_data(reason: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, reason.to_owned())
+}
+
+/// Standard base64 with padding, for the accept key
+fn base64_encode(data: &[u8]) -> String {
+    const ALPHABET: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut out = String::with_capacity((data.len() + 2) / 3 * 4);
+    for chunk in data.chunks(3) {
+        let b = [
+            chunk[0],
+            *chunk.get(1).unwrap_or(&0),
+            *chunk.get(2).unwrap_or(&0),
+        ];
+        let n = (b[0] as u32) << 16 | (b[1] as u32) << 8 | b[2] as u32;
+        for i in 0..4 {
+            if i <= chunk.len() {
+                out.push(ALPHABET[(n >> (18 - 6 * i) & 0x3f) as usize] as char);
+            } else {
+                out.push('=');
+            }
+        }
+    }
+    out
+}
+
+fn accept_key(key: &str) -> String {
+    let mut hasher = Sha1::new();
+    hasher.update(key.as_bytes());
+    hasher.update(ACCEPT_GUID.as_bytes());
+    base64_encode(&hasher.finalize())
+}
+
+fn header_has_token(value: &str, token: &str) -> bool {
+    value
+        .split(',')
+        .any(|t| t.trim().eq_ignore_ascii_case(token))
+}
+
+/// The response to the head of the upgrade request, the error to reply if
+/// it is not a WebSocket handshake
+fn handshake_response(head: &str) -> Result<String, &'static str> {
+    let mut lines = head.split("\r\n");
+    match lines.next().map(|l| l.split(' ').collect::<Vec<_>>()) {
+        Some(line) if line.len() == 3 && line[0] == "GET" && line[2] == "HTTP/1.1" => {}
+        _ => return Err("405 Method Not Allowed"),
+    }
+    let (mut upgrade, mut connection, mut version) = (false, false, false);
+    let mut key = None;
+    let mut resp_offered = false;
+    for line in lines {
+        let (name, value) = match line.split_once(':') {
+            Some((name, value)) => (name.trim(), value.trim()),
+            None => continue,
+        };
+        if name.eq_ignore_ascii_case("Upgrade") {
+            upgrade = header_has_token(value, "websocket");
+        } else if name.eq_ignore_ascii_case("Connection") {
+            connection = header_has_token(value, "Upgrade");
+        } else if name.eq_ignore_ascii_case("Sec-WebSocket-Version") {
+            version = value == "13";
+        } else if name.eq_ignore_ascii_case("Sec-WebSocket-Key") {
+            key = Some(value.to_owned());
+        } else if name.eq_ignore_ascii_case("Sec-WebSocket-Protocol") {
+            resp_offered |= header_has_token(value, SUBPROTOCOL);
+        }
+    }
+    let key = match key {
+        Some(key) if upgrade && connection => key,
+        _ => return Err("400 Bad Request"),
+    };
+    if !version {
+        return Err("426 Upgrade Required\r\nSec-WebSocket-Version: 13");
+    }
+    let mut response = format!(
+        "HTTP/1.1 101 Switching Protocols\r\nUpgrade: websocket\r\nConnection: Upgrade\r\n\
+         Sec-WebSocket-Accept: {}\r\n",
+        accept_key(&key)
+    );
+    if resp_offered {
+        response.push_str(&format!("Sec-WebSocket-Protocol: {}\r\n", SUBPROTOCOL));
+    }
+    response.push_str("\r\n");
+    Ok(response)
+}
+
+/// Read the upgrade request and reply to it, returns the bytes of the first
+/// frames read after the head.
+pub async fn accept_handshake(socket: &TcpStream) -> io::Result<Vec<u8>> {
+    let mut stream = socket.clone();
+    let mut buf = vec![];
+    let head_len = loop {
+        if let Some(pos) = buf.windows(4).position(|w| w == b"\r\n\r\n") {
+            break pos + 4;
+        }
+        if buf.len() > MAX_REQUEST_HEAD_LEN {
+            return Err(invalid_data("request head too large"));
+        }
+        let mut chunk = [0; 1024];
+        let n = stream.read(&mut chunk).await?;
+        if n == 0 {
+            return Err(io::ErrorKind::UnexpectedEof.into());
+        }
+        buf.extend_from_slice(&chunk[..n]);
+    };
+    let head = String::from_utf8_lossy(&buf[..head_len]).to_string();
+    match handshake_response(&head) {
+        Ok(response) => {
+            stream.write_all(response.as_bytes()).await?;
+            stream.flush().await?;
+            Ok(buf.split_off(head_len))
+        }
+        Err(status) => {
+            let response = format!(
+                "HTTP/1.1 {}\r\nContent-Length: 0\r\nConnection: close\r\n\r\n",
+                status
+            );
+            stream.write_all(response.as_bytes()).await?;
+            stream.flush().await?;
+            Err(invalid_data("not a websocket handshake"))
+        }
+    }
+}
+
+/// A frame of the server, never masked
+fn encode_frame(opcode: u8, payload: &[u8]) -> Vec<u8> {
+    let mut frame = Vec::with_capacity(payload.len() + 10);
+    frame.push(0x80 | opcode);
+    match payload.len() {
+        len if len < 126 => frame.push(len as u8),
+        len if len <= u16::MAX as usize => {
+            frame.push(126);
+            frame.extend_from_slice(&(len as u16).to_be_bytes());
+        }
+        len => {
+            frame.push(127);
+            frame.extend_from_slice(&(len as u64).to_be_bytes());
+        }
+    }
+    frame.extend_from_slice(payload);
+    frame
+}
+
+struct FrameHeader {
+    opcode: u8,
+    mask: [u8; 4],
+    len: usize,
+    payload_len: u64,
+}
+
+/// The header of the frame at the start of `raw`, None if incomplete
+fn parse_header(raw: &[u8]) -> io::Result<Option<FrameHeader>> {
+    if raw.len() < 2 {
+        return Ok(None);
+    }
+    if raw[0] & 0x70 != 0 {
+        return Err(invalid_data("reserved bits set"));
+    }
+    // the frames of a client are always masked
+    if raw[1] & 0x80 == 0 {
+        return Err(invalid_data("unmasked frame"));
+    }
+    let opcode = raw[0] & 0x0f;
+    let (payload_len, pos) = match raw[1] & 0x7f {
+        126 if raw.len() >= 4 => (u16::from_be_bytes([raw[2], raw[3]]) as u64, 4),
+        127 if raw.len() >= 10 => {
+            let mut len = [0; 8];
+            len.copy_from_slice(&raw[2..10]);
+            (u64::from_be_bytes(len), 10)
+        }
+        126 | 127 => return Ok(None),
+        len => (len as u64, 2),
+    };
+    if opcode >= OP_CLOSE && (payload_len > 125 || raw[0] & 0x80 == 0) {
+        return Err(invalid_data("invalid control frame"));
+    }
+    if raw.len() < pos + 4 {
+        return Ok(None);
+    }
+    let mut mask = [0; 4];
+    mask.copy_from_slice(&raw[pos..pos + 4]);
+    Ok(Some(FrameHeader {
+        opcode,
+        mask,
+        len: pos + 4,
+        payload_len,
+    }))
+}
+
+/// The WebSocket stream of a `Connection`, a byte stream of the payloads of
+/// the client and of the replies.
+///
+/// Reads are safe to be interrupted, like the ones of a TCP stream, the
+/// frames are decoded from the bytes already read without waiting.
+#[derive(Debug)]
+pub struct WsStream {
+    r: BufReader<TcpStream>,
+    w: BufWriter<TcpStream>,
+    /// Read and not yet decoded
+    raw: BytesMut,
+    /// Payload of the data frame being read still to read
+    remaining: u64,
+    mask: [u8; 4],
+    mask_pos: usize,
+    /// Pongs and the close frame to send
+    control: Vec<u8>,
+    /// Replies written since the last flush
+    out: Vec<u8>,
+    closed: bool,
+}
+
+impl WsStream {
+    /// The stream of an upgraded connection, `read` the bytes read after
+    /// the upgrade request
+    pub fn new(socket: TcpStream, read: Vec<u8>) -> WsStream {
+        WsStream {
+            r: BufReader::new(socket.clone()),
+            w: BufWriter::new(socket),
+            raw: BytesMut::from(&read[..]),
+            remaining: 0,
+            mask: [0; 4],
+            mask_pos: 0,
+            control: vec![],
+            out: vec![],
+            closed: false,
+        }
+    }
+
+    /// Decode the payload of the data frames into `buf`, None if more bytes
+    /// must be read first, 0 once the client closed the connection.
+    fn decode(&mut self, buf: &mut [u8]) -> io::Result<Option<usize>> {
+        loop {
+            if self.closed {
+                return Ok(Some(0));
+            }
+            if self.remaining > 0 {
+                if self.raw.is_empty() {
+                    return Ok(None);
+                }
+                let n = buf
+                    .len()
+                    .min(self.raw.len())
+                    .min(self.remaining.min(usize::MAX as u64) as usize);
+                for (i, b) in buf[..n].iter_mut().enumerate() {
+                    *b = self.raw[i] ^ self.mask[(self.mask_pos + i) % 4];
+                }
+                self.mask_pos += n;
+                self.raw.advance(n);
+                self.remaining -= n as u64;
+                return Ok(Some(n));
+            }
+
+            let header = match parse_header(&self.raw)? {
+                Some(header) => header,
+                None => return Ok(None),
+            };
+            match header.opcode {
+                OP_CONTINUATION | OP_TEXT | OP_BINARY => {
+                    self.raw.advance(header.len);
+                    self.remaining = header.payload_len;
+                    self.mask = header.mask;
+                    self.mask_pos = 0;
+                }
+                OP_CLOSE | OP_PING | OP_PONG => {
+                    // control frames are short, decoded whole
+                    let end = header.len + header.payload_len as usize;
+                    if self.raw.len() < end {
+                        return Ok(None);
+                    }
+                    let payload: Vec<u8> = self.raw[header.len..end]
+                        .iter()
+                        .enumerate()
+                        .map(|(i, b)| b ^ header.mask[i % 4])
+                        .collect();
+                    self.raw.advance(end);
+                    match header.opcode {
+                        OP_PING => self.control.extend(encode_frame(OP_PONG, &payload)),
+                        OP_CLOSE => {
+                            // echo the status code
+                            let status = &payload[..payload.len().min(2)];
+                            self.control.extend(encode_frame(OP_CLOSE, status));
+                            self.closed = true;
+                        }
+                        _ => {}
+                    }
+                }
+                _ => return Err(invalid_data("unknown opcode")),
+            }
+        }
+    }
+
+    async fn write_control(&mut self) -> io::Result<()> {
+        if !self.control.is_empty() {
+            let control = std::mem::take(&mut self.control);
+            self.w.write_all(&control).await?;
+            self.w.flush().await?;
+        }
+        Ok(())
+    }
+
+    pub async fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        loop {
+            let decoded = self.decode(buf)?;
+            self.write_control().await?;
+            if let Some(n) = decoded {
+                return Ok(n);
+            }
+            let mut chunk = [0; 4096];
+            let n = self.r.read(&mut chunk).await?;
+            if n == 0 {
+                return Ok(0);
+            }
+            self.raw.extend_from_slice(&chunk[..n]);
+        }
+    }
+
+    /// Buffer `buf` for the message of the next flush
+    pub fn write(&mut self, buf: &[u8]) {
+        self.out.extend_from_slice(buf);
+    }
+
+    pub async fn flush(&mut self) -> io::Result<()> {
+        self.write_control().await?;
+        if !self.out.is_empty() {
+            let message = encode_frame(OP_BINARY, &self.out);
+            self.out.clear();
+            self.w.write_all(&message).await?;
+            self.w.flush().await?;
+        }
+        Ok(())
+    }
+}
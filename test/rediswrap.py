@@ -13,6 +13,21 @@ class RedisWrapper:
     # auth password when requirepass is true
     password = ""
 
+    # the server runs with notify_keyspace_events = "KEA"
+    notify_keyspace_events = False
+
+    # log_file of the server, running with log_level = "debug"
+    log_file = ""
+
+    # eviction_max_keys of the namespace, 0 when eviction is disabled
+    eviction_max_keys = 0
+
+    # default_ttl_ms of the namespace, 0 when new keys have no ttl
+    default_ttl_ms = 0
+
+    # enable_debug_command of the server, the tests connect from loopback
+    enable_debug_command = "no"
+
     @classmethod
     def set_instance_manually(cls, ip=default_ip, port=default_port):
         cls._set_instance(ip, port)
@@ -1,9 +1,12 @@
+import binascii
+import json
+import time
 import unittest
 
 from redis import exceptions
 
 from rediswrap import RedisWrapper
-from test_util import random_string
+from test_util import CmdType, dump_payload, random_string
 
 
 class GenericTest(unittest.TestCase):
@@ -24,6 +27,21 @@ class GenericTest(unittest.TestCase):
         self.assertTrue(RedisWrapper.auth())
         self.assertTrue(self.r.ping())
 
+    @unittest.skipUnless(RedisWrapper.log_file, "skip when the log of the server is not known")
+    def test_auth_not_logged(self):
+        secret = random_string(32)
+        with self.assertRaises(exceptions.ResponseError):
+            self.r.execute_command('auth', secret)
+        with self.assertRaises(exceptions.ResponseError):
+            self.r.execute_command('auth', 'default', secret)
+        with self.assertRaises(exceptions.ResponseError):
+            self.r.execute_command('hello', 3, 'auth', 'default', secret)
+        with self.assertRaises(exceptions.ResponseError):
+            self.r.execute_command('config', 'set', 'requirepass', secret)
+        self.r.execute_command('migrate', '127.0.0.1', 1, self.k1, 0, 10, 'auth2', 'default', secret)
+        with open(RedisWrapper.log_file) as log:
+            self.assertNotIn(secret, log.read())
+
     def test_ping(self):
         self.assertTrue(self.r.ping())
 
@@ -58,6 +76,153 @@ class GenericTest(unittest.TestCase):
         err = cm.exception
         self.assertEqual(str(err), 'DISCARD without MULTI')
 
+    def test_multi_wrong_arity(self):
+        # a command rejected while queued makes EXEC discard the transaction
+        self.assertTrue(self.r.execute_command('multi'))
+        self.assertEqual(self.r.execute_command('set', self.k1, 'value1'), 'QUEUED')
+        with self.assertRaises(exceptions.ResponseError) as cm:
+            self.r.execute_command('get', self.k1, self.k2)
+        self.assertIn('wrong number of arguments', str(cm.exception))
+        with self.assertRaises(exceptions.ExecAbortError) as cm:
+            self.r.execute_command('exec')
+        self.assertEqual(str(cm.exception), 'Transaction discarded because of previous errors.')
+        self.assertIsNone(self.r.get(self.k1))
+
+        # the next transaction is not discarded
+        self.assertTrue(self.r.execute_command('multi'))
+        self.r.execute_command('set', self.k1, 'value1')
+        self.assertListEqual(self.r.execute_command('exec'), ['OK'])
+
+    def test_watch(self):
+        self.r.execute_command('set', self.k1, 'value1')
+        self.assertTrue(self.r.execute_command('watch', self.k1))
+        self.assertTrue(self.r.execute_command('multi'))
+        self.r.execute_command('set', self.k2, 'value2')
+        self.assertListEqual(self.r.execute_command('exec'), ['OK'])
+
+        # a write of the watched key aborts EXEC
+        client2 = RedisWrapper.clone()
+        self.assertTrue(self.r.execute_command('watch', self.k1))
+        self.assertTrue(client2.execute_command('set', self.k1, 'value2'))
+        self.assertTrue(self.r.execute_command('multi'))
+        self.r.execute_command('set', self.k2, 'value3')
+        self.assertIsNone(self.r.execute_command('exec'))
+        self.assertEqual(self.r.get(self.k2), 'value2')
+
+        self.assertTrue(self.r.execute_command('watch', self.k1))
+        self.assertTrue(self.r.execute_command('unwatch'))
+        self.assertTrue(client2.execute_command('set', self.k1, 'value3'))
+        self.assertTrue(self.r.execute_command('multi'))
+        self.assertListEqual(self.r.execute_command('exec'), [])
+
+    def test_watch_hash_field(self):
+        # HSET on a watched existing hash aborts EXEC
+        self.r.execute_command('hset', self.k1, 'f1', 'v1')
+        client2 = RedisWrapper.clone()
+        self.assertTrue(self.r.execute_command('watch', self.k1))
+        self.assertEqual(client2.execute_command('hset', self.k1, 'f2', 'v2'), 1)
+        self.assertTrue(self.r.execute_command('multi'))
+        self.r.execute_command('set', self.k2, 'value1')
+        self.assertIsNone(self.r.execute_command('exec'))
+        self.assertIsNone(self.r.get(self.k2))
+
+        # even if it writes the same value again
+        self.assertTrue(self.r.execute_command('watch', self.k1))
+        self.assertEqual(client2.execute_command('hset', self.k1, 'f2', 'v2'), 0)
+        self.assertTrue(self.r.execute_command('multi'))
+        self.r.execute_command('set', self.k2, 'value1')
+        self.assertIsNone(self.r.execute_command('exec'))
+        self.assertIsNone(self.r.get(self.k2))
+
+    def test_watch_absent_key(self):
+        # SET of a watched absent key aborts EXEC
+        client2 = RedisWrapper.clone()
+        self.assertTrue(self.r.execute_command('watch', self.k1))
+        self.assertTrue(client2.execute_command('set', self.k1, 'value1'))
+        self.assertTrue(self.r.execute_command('multi'))
+        self.r.execute_command('set', self.k2, 'value2')
+        self.assertIsNone(self.r.execute_command('exec'))
+        self.assertIsNone(self.r.get(self.k2))
+
+        # the key still absent does not
+        self.r.execute_command('del', self.k1)
+        self.assertTrue(self.r.execute_command('watch', self.k1))
+        self.assertTrue(self.r.execute_command('multi'))
+        self.r.execute_command('set', self.k2, 'value2')
+        self.assertListEqual(self.r.execute_command('exec'), ['OK'])
+
+    def test_pubsub(self):
+        p = self.r.pubsub()
+        p.subscribe('__chan1__')
+        p.psubscribe('__chan*')
+        self.assertEqual(p.get_message(timeout=1)['type'], 'subscribe')
+        self.assertEqual(p.get_message(timeout=1)['type'], 'psubscribe')
+
+        self.assertEqual(self.r.execute_command('publish', '__chan1__', 'hello'), 2)
+        self.assertEqual(self.r.execute_command('publish', '__chan2__', 'world'), 1)
+        self.assertEqual(self.r.execute_command('publish', '__other__', 'nobody'), 0)
+
+        # channel and pattern messages may interleave
+        msgs = [p.get_message(timeout=1) for _ in range(3)]
+        self.assertCountEqual([(m['type'], m['pattern'], m['channel'], m['data']) for m in msgs], [
+            ('message', None, '__chan1__', 'hello'),
+            ('pmessage', '__chan*', '__chan1__', 'hello'),
+            ('pmessage', '__chan*', '__chan2__', 'world'),
+        ])
+
+        p.punsubscribe('__chan*')
+        p.unsubscribe('__chan1__')
+        self.assertEqual(p.get_message(timeout=1)['type'], 'punsubscribe')
+        self.assertEqual(p.get_message(timeout=1)['data'], 0)
+        p.close()
+
+    @unittest.skipUnless(RedisWrapper.notify_keyspace_events, "skip when notifications are disabled")
+    def test_keyspace_notifications(self):
+        p = self.r.pubsub()
+        p.psubscribe('__keyevent@0__:*')
+        self.assertEqual(p.get_message(timeout=1)['type'], 'psubscribe')
+
+        # failed and no-op writes are not notified
+        self.assertEqual(self.r.execute_command('del', self.k1), 0)
+        self.assertEqual(self.r.execute_command('srem', self.k2, 'member'), 0)
+        self.r.execute_command('set', self.k1, 'value1')
+        self.assertIsNone(self.r.execute_command('set', self.k1, 'value2', 'nx'))
+        self.assertRaises(exceptions.ResponseError, self.r.execute_command, 'lpush', self.k1, 'value1')
+        self.assertEqual(self.r.execute_command('del', self.k1), 1)
+
+        # the writes of EXEC once committed
+        self.assertTrue(self.r.execute_command('multi'))
+        self.r.execute_command('sadd', self.k2, 'member')
+        self.r.execute_command('srem', self.k2, 'member')
+        self.assertListEqual(self.r.execute_command('exec'), [1, 1])
+
+        msgs = [p.get_message(timeout=1) for _ in range(5)]
+        self.assertEqual([(m['channel'], m['data']) for m in msgs if m], [
+            ('__keyevent@0__:set', self.k1),
+            ('__keyevent@0__:del', self.k1),
+            ('__keyevent@0__:sadd', self.k2),
+            ('__keyevent@0__:srem', self.k2),
+        ])
+        p.close()
+
+    def test_lock(self):
+        name = '__lock1__'
+        self.r.execute_command('unlock', name, 'owner1')
+        self.r.execute_command('unlock', name, 'owner2')
+        token = self.r.execute_command('lock', name, 'owner1', 10000)
+        self.assertIsNotNone(token)
+        self.assertEqual(self.r.execute_command('lock', name, 'owner1', 10000), token)
+        self.assertIsNone(self.r.execute_command('lock', name, 'owner2', 10000))
+        self.assertEqual(self.r.execute_command('extend', name, 'owner2', 10000), 0)
+        self.assertEqual(self.r.execute_command('extend', name, 'owner1', 10000), 1)
+        self.assertEqual(self.r.execute_command('unlock', name, 'owner2'), 0)
+        self.assertEqual(self.r.execute_command('unlock', name, 'owner1'), 1)
+        self.assertEqual(self.r.execute_command('unlock', name, 'owner1'), 0)
+        self.assertGreater(self.r.execute_command('lock', name, 'owner2', 100), token)
+        time.sleep(0.2)
+        self.assertGreater(self.r.execute_command('lock', name, 'owner1', 10000), token + 1)
+        self.assertEqual(self.r.execute_command('unlock', name, 'owner1'), 1)
+
     def test_client(self):
         client1 = self.r
         client1_id = client1.execute_command("client id")
@@ -74,6 +239,130 @@ class GenericTest(unittest.TestCase):
         self.assertEqual(client2.execute_command("client kill id", client1_id), 1)
         self.assertEqual(client2.execute_command("client list id", client1_id), "")
 
+    def test_client_user(self):
+        client_id = self.r.execute_command("client id")
+        self.assertIn('user=default', self.r.execute_command("client list id", client_id))
+        client2 = RedisWrapper.clone()
+        self.assertIsNotNone(client2.execute_command("client id"))
+        self.assertEqual(client2.execute_command("client kill user", '__nobody__'), 0)
+        self.assertRaises(exceptions.ResponseError, self.r.execute_command, 'client revoke')
+
+    def test_readonly(self):
+        k1, k2 = self.k1, self.k2
+        # every command which may modify the keyspace, a command missing from
+        # Command::is_write would write on a READONLY connection
+        writes = [
+            ('del', k1), ('set', k1, 'v'), ('setnx', k1, 'v'), ('setex', k1, 10, 'v'),
+            ('mset', k1, 'v'), ('expire', k1, 10), ('expireat', k1, 10), ('pexpire', k1, 10),
+            ('pexpireat', k1, 10), ('persist', k1), ('incr', k1), ('decr', k1),
+            ('incrby', k1, 1), ('decrby', k1, 1), ('append', k1, 'v'), ('setrange', k1, 0, 'v'),
+            ('setbit', k1, 0, 1), ('bitop', 'and', k1, k2), ('bitfield', k1, 'set', 'u8', 0, 1),
+            ('getdel', k1), ('getex', k1, 'ex', 10), ('cl.throttle', k1, 1, 1, 1),
+            ('lock', k1, 'owner', 1000), ('extend', k1, 'owner', 1000), ('unlock', k1, 'owner'),
+            ('hset', k1, 'f', 'v'), ('hmset', k1, 'f', 'v'), ('hsetnx', k1, 'f', 'v'),
+            ('hdel', k1, 'f'), ('hincrby', k1, 'f', 1), ('lpush', k1, 'a'), ('rpush', k1, 'a'),
+            ('lpop', k1), ('rpop', k1), ('lmpop', 1, k1, 'left'), ('blmpop', 1, 1, k1, 'left'),
+            ('lset', k1, 0, 'a'), ('ltrim', k1, 0, 1), ('lrem', k1, 0, 'a'),
+            ('linsert', k1, 'before', 'a', 'b'), ('sadd', k1, 'a'), ('spop', k1),
+            ('sdrain', k1, 1), ('srem', k1, 'a'), ('zadd', k1, 1, 'a'), ('zrem', k1, 'a'),
+            ('zremrangebyscore', k1, 0, 1), ('zremrangebyrank', k1, 0, 1), ('zpopmin', k1),
+            ('zpopmax', k1), ('zmpop', 1, k1, 'min'), ('bzmpop', 1, 1, k1, 'min'),
+            ('zrangestore', k2, k1, 0, 1), ('zinterstore', k2, 1, k1), ('zunionstore', k2, 1, k1),
+            ('zdiffstore', k2, 1, k1), ('zincrby', k1, 1, 'a'), ('geoadd', k1, 13.3, 38.1, 'a'),
+            ('xadd', k1, '*', 'f', 'v'), ('xgroup', 'create', k1, 'g', '$', 'mkstream'),
+            ('xreadgroup', 'group', 'g', 'c', 'streams', k1, '>'), ('xack', k1, 'g', '0-1'),
+            ('xclaim', k1, 'g', 'c', 0, '0-1'), ('json.set', k1, '$', '1'), ('json.del', k1),
+            ('eval', 'return 1', 0), ('evalsha', 'e0e1f9fabfc9d4800c877a703b823ac0578ff8db', 0),
+            ('copy', k1, k2), ('tidis.move', k1, '__other__'), ('restore', k1, 0, 'payload'),
+            ('sort', k1, 'store', k2), ('migrate', '127.0.0.1', 6380, k1, 0, 1000),
+            ('tidis.format', 'upgrade'),
+        ]
+        client = RedisWrapper.clone()
+        self.assertTrue(client.execute_command('readonly'))
+        for write in writes:
+            with self.assertRaises(exceptions.ResponseError, msg=write[0]) as cm:
+                client.execute_command(*write)
+            self.assertTrue(str(cm.exception).startswith('READONLY'), write[0])
+
+        # reads, including those of the commands writing with some options
+        self.assertIsNone(client.get(k1))
+        self.assertEqual(client.execute_command('hindex', 'list'), self.r.execute_command('hindex', 'list'))
+        self.assertListEqual(client.execute_command('sort', k1), [])
+        self.assertTrue(client.execute_command('readwrite'))
+        self.assertTrue(client.set(k1, 'v'))
+        self.assertEqual(self.r.get(k1), 'v')
+
+    def test_idempotency(self):
+        client = RedisWrapper.clone()
+        token = random_string(16)
+        self.assertTrue(client.execute_command('idempotency', token))
+        self.assertEqual(client.incr(self.k1), 1)
+        # a retry with the same token gets the recorded reply, not applied again
+        self.assertTrue(client.execute_command('idempotency', token))
+        self.assertEqual(client.incr(self.k1), 1)
+        self.assertEqual(self.r.get(self.k1), '1')
+        # the token is recorded once committed, whichever connection retries
+        self.assertTrue(self.r.execute_command('idempotency', token))
+        self.assertEqual(self.r.incr(self.k1), 1)
+        self.assertEqual(self.r.get(self.k1), '1')
+
+        # the token only applies to the command following IDEMPOTENCY
+        self.assertEqual(client.incr(self.k1), 2)
+        self.assertTrue(client.execute_command('idempotency', random_string(16)))
+        self.assertEqual(client.incr(self.k1), 3)
+
+        # a read in between drops the token
+        token = random_string(16)
+        self.assertTrue(client.execute_command('idempotency', token))
+        self.assertEqual(client.get(self.k1), '3')
+        self.assertEqual(client.incr(self.k1), 4)
+        self.assertTrue(client.execute_command('idempotency', token))
+        self.assertEqual(client.incr(self.k1), 5)
+
+        # recorded replies other than integers
+        token = random_string(16)
+        self.assertTrue(client.execute_command('idempotency', token))
+        self.assertTrue(client.set(self.k2, 'v1'))
+        self.r.set(self.k2, 'v2')
+        self.assertTrue(client.execute_command('idempotency', token))
+        self.assertTrue(client.set(self.k2, 'v1'))
+        self.assertEqual(self.r.get(self.k2), 'v2')
+
+    def test_idempotency_args(self):
+        self.assertRaises(exceptions.ResponseError, self.r.execute_command, 'idempotency')
+        self.assertRaises(exceptions.ResponseError, self.r.execute_command, 'idempotency', 'a', 'b')
+
+    def test_lolwut(self):
+        art = self.r.execute_command('lolwut')
+        self.assertIn('Tidis ver.', art)
+        rows = art.split('\n')
+        self.assertEqual(len(rows[0]), 32)
+        self.assertEqual(len(self.r.execute_command('lolwut', 10).split('\n')[0]), 10)
+        self.assertEqual(len(self.r.execute_command('lolwut', 1000).split('\n')[0]), 256)
+        self.assertEqual(self.r.execute_command('lolwut', 'version', 5, 10), self.r.execute_command('lolwut', 10))
+
+    def test_lolwut_args(self):
+        self.assertRaises(exceptions.ResponseError, self.r.execute_command, 'lolwut', 0)
+        self.assertRaises(exceptions.ResponseError, self.r.execute_command, 'lolwut', 'cols')
+        self.assertRaises(exceptions.ResponseError, self.r.execute_command, 'lolwut', 'version')
+        self.assertRaises(exceptions.ResponseError, self.r.execute_command, 'lolwut', 'version', 'v')
+
+    @unittest.skipUnless(RedisWrapper.enable_debug_command == "no", "skip when DEBUG is enabled")
+    def test_debug_disabled(self):
+        with self.assertRaises(exceptions.ResponseError) as cm:
+            self.r.execute_command('debug', 'help')
+        self.assertIn('DEBUG command not allowed', str(cm.exception))
+        with self.assertRaises(exceptions.ResponseError) as cm:
+            self.r.execute_command('debug', 'profiler_start')
+        self.assertIn('DEBUG command not allowed', str(cm.exception))
+
+    @unittest.skipIf(RedisWrapper.enable_debug_command == "no", "skip when DEBUG is disabled")
+    def test_debug_enabled(self):
+        self.assertGreater(len(self.r.execute_command('debug', 'help')), 0)
+        with self.assertRaises(exceptions.ResponseError) as cm:
+            self.r.execute_command('debug', 'unknown')
+        self.assertIn('not supported debug sub command', str(cm.exception))
+
     def test_scan(self):
         # add some keys for scan test
         for i in range(0, 10):
@@ -94,7 +383,7 @@ class GenericTest(unittest.TestCase):
         part2_scan = self.r.execute_command('xscan', part1_scan[0], 'count', 10)
         self.assertEqual(part2_scan[0], 'list:9')
         self.assertEqual(len(part2_scan[1]), 10)
-        match_scan = self.r.execute_command('xscan', '', 'count', 100, 'match', '^hash:*')
+        match_scan = self.r.execute_command('xscan', '', 'count', 100, 'match', 'hash:*')
         self.assertEqual(match_scan[0], '')
         self.assertEqual(len(match_scan[1]), 10)
 
@@ -108,9 +397,304 @@ class GenericTest(unittest.TestCase):
             keys.append('zset:' + str(i))
         self.r.delete(*keys)
 
+    def test_cluster_keys_in_slot(self):
+        keys = ['{__slot__}:1', '{__slot__}:2']
+        slot = binascii.crc_hqx(b'__slot__', 0) & 16383
+        count = self.r.execute_command('cluster', 'countkeysinslot', slot)
+        for key in keys:
+            self.assertTrue(self.r.set(key, 'value'))
+        self.assertEqual(self.r.execute_command('cluster', 'countkeysinslot', slot), count + 2)
+        in_slot = self.r.execute_command('cluster', 'getkeysinslot', slot, 1000)
+        for key in keys:
+            self.assertIn(key, in_slot)
+        self.assertEqual(len(self.r.execute_command('cluster', 'getkeysinslot', slot, 1)), 1)
+        self.assertRaises(exceptions.ResponseError, self.r.execute_command,
+                          'cluster', 'countkeysinslot', 16384)
+        self.r.delete(*keys)
+
+    def test_route(self):
+        reply = self.r.execute_command('tidis.route', '{__slot__}:1')
+        route = dict(zip(reply[::2], reply[1::2]))
+        self.assertEqual(route['hashtag'], '__slot__')
+        self.assertEqual(route['slot'], binascii.crc_hqx(b'__slot__', 0) & 16383)
+        self.assertTrue(route['encoded_key'].startswith(route['prefix']))
+        self.assertIn(route['local'], [0, 1])
+        reply = self.r.execute_command('tidis.route', '{}:1')
+        route = dict(zip(reply[::2], reply[1::2]))
+        self.assertIsNone(route['hashtag'])
+        self.assertEqual(route['slot'], binascii.crc_hqx(b'{}:1', 0) & 16383)
+        self.assertRaises(exceptions.ResponseError, self.r.execute_command, 'tidis.route')
+
+    def test_rpclog(self):
+        self.assertTrue(self.r.execute_command('tidis.rpclog', 'reset'))
+        self.assertEqual(self.r.execute_command('tidis.rpclog', 'len'), 0)
+        self.assertEqual(self.r.execute_command('tidis.rpclog', 'get'), [])
+        for entry in self.r.execute_command('tidis.rpclog', 'get', -1):
+            self.assertEqual(len(entry), 7)
+        self.assertRaises(exceptions.ResponseError, self.r.execute_command, 'tidis.rpclog', 'len', 1)
+        self.assertRaises(exceptions.ResponseError, self.r.execute_command, 'tidis.rpclog', 'nosuch')
+
+    def test_format(self):
+        record = self.r.execute_command('tidis.format', 'get')
+        record = dict(zip(record[::2], record[1::2]))
+        self.assertEqual(record['version'], record['supported_version'])
+        self.assertEqual(record['layout'], 1)
+        self.assertEqual(self.r.execute_command('tidis.format', 'upgrade'), record['version'])
+        self.assertRaises(exceptions.ResponseError, self.r.execute_command, 'tidis.format', 'nosuch')
+        self.assertRaises(exceptions.ResponseError, self.r.execute_command, 'tidis.format', 'get', 1)
+
+    def test_snapshot(self):
+        self.assertRaises(exceptions.ResponseError, self.r.execute_command, 'tidis.format', 'nosuch')
+        snapshot = json.loads(self.r.execute_command('tidis.snapshot', 'get'))
+        self.assertGreaterEqual(snapshot['connections']['count'], 1)
+        self.assertTrue(any(c['cmd'] == 'tidis.snapshot' for c in snapshot['in_flight']))
+        self.assertIn('queues', snapshot)
+        self.assertGreaterEqual(len(snapshot['recent_errors']), 1)
+        self.assertRaises(exceptions.ResponseError, self.r.execute_command, 'tidis.snapshot', 'save')
+        self.assertRaises(exceptions.ResponseError, self.r.execute_command, 'tidis.snapshot', 'get', 1)
+
+    @unittest.skipUnless(RedisWrapper.eviction_max_keys, "skip when eviction is disabled")
+    def test_eviction(self):
+        def evicted_keys():
+            return self.r.info('stats')['evicted_keys']
+
+        before = evicted_keys()
+        self.assertTrue(self.r.set(self.k1, 'value'))
+        keys = ['__evict{}__'.format(i) for i in range(RedisWrapper.eviction_max_keys + 10)]
+        for key in keys:
+            self.assertTrue(self.r.set(key, 'value', ex=3600))
+        deadline = time.time() + 120
+        while evicted_keys() == before and time.time() < deadline:
+            time.sleep(1)
+        evicted = evicted_keys() - before
+        self.assertGreater(evicted, 0)
+        # other keys with a ttl may be evicted too, keys without ttl are kept
+        self.assertLessEqual(len(keys) - self.r.execute_command('exists', *keys), evicted)
+        self.assertEqual(self.r.get(self.k1), 'value')
+        self.r.execute_command('del', *keys)
+
+    def test_scan_match_glob(self):
+        key = '__glob__' + 'a' * 64
+        self.assertTrue(self.r.set(key, 'value'))
+
+        def scan(pattern):
+            return self.r.execute_command('xscan', '', 'count', 100000, 'match', pattern)[1]
+
+        self.assertIn(key, scan('__glob__*'))
+        self.assertIn(key, scan('__gl?b__[a-c]*a'))
+        self.assertIn(key, scan('__glob__[^b]*'))
+        self.assertNotIn(key, scan('__glob__[^a]*'))
+        self.assertIn(key, scan('\\_\\_glob__*'))
+
+        # the pathological patterns backtrack a single star
+        start = time.time()
+        self.assertNotIn(key, scan('__glob__' + 'a*' * 32 + 'b'))
+        self.assertIn(key, scan('__glob__' + '*a' * 32))
+        self.assertLess(time.time() - start, 5)
+        self.r.delete(key)
+
+    def test_ttlforecast(self):
+        before = self.r.execute_command('ttlforecast', 'BUCKET', '1h', 'COUNT', 2)
+        self.assertTrue(self.r.set(self.k1, 'value', ex=1800))
+        self.assertTrue(self.r.set(self.k2, 'value', ex=5400))
+        after = self.r.execute_command('ttlforecast', 'BUCKET', '1h', 'COUNT', 2)
+        self.assertEqual(after[2:4], ['bucket', '1h'])
+        buckets = after[after.index('buckets') + 1]
+        self.assertEqual(len(buckets), 2)
+        before_buckets = before[before.index('buckets') + 1]
+        self.assertGreaterEqual(buckets[0], before_buckets[0] + 1)
+        self.assertGreaterEqual(buckets[1], before_buckets[1] + 1)
+        self.assertRaises(exceptions.ResponseError, self.r.execute_command, 'ttlforecast', 'BUCKET', '2m')
+
+    def test_ttlforecast_default(self):
+        before = self.r.execute_command('ttlforecast')
+        self.assertTrue(self.r.set(self.k1, 'value'))
+        after = self.r.execute_command('ttlforecast')
+        self.assertEqual(after[0::2], ['namespace', 'bucket', 'start', 'buckets', 'later', 'overdue',
+                                       'persistent'])
+        self.assertEqual(after[3], '1m')
+        self.assertEqual(len(after[7]), 60)
+        self.assertGreaterEqual(after[13], before[13] + 1)
+
+    def test_ttlforecast_args(self):
+        for args in [('BUCKET',), ('COUNT', 0), ('COUNT', 1441), ('COUNT', 'a'), ('LIMIT', 1),
+                     ('BUCKET', '1m', 'COUNT')]:
+            self.assertRaises(exceptions.ResponseError, self.r.execute_command, 'ttlforecast', *args)
+        reply = self.r.execute_command('ttlforecast', 'bucket', '5M', 'count', 1440)
+        self.assertEqual(reply[3], '5m')
+        self.assertEqual(len(reply[7]), 1440)
+
+    def test_expired_keys_stats(self):
+        before = self.r.execute_command('info', 'stats')
+        self.assertIn('expired_keys_active', before)
+        self.assertTrue(self.r.set(self.k1, 'v', px=100))
+        self.assertEqual(self.r.hset(self.k2, 'f', 'v'), 1)
+        self.assertTrue(self.r.pexpire(self.k2, 100))
+        time.sleep(0.2)
+        self.assertIsNone(self.r.get(self.k1))
+        self.assertIsNone(self.r.hget(self.k2, 'f'))
+        after = self.r.execute_command('info', 'stats')
+        self.assertGreaterEqual(after['expired_keys_string'], before['expired_keys_string'] + 1)
+        self.assertGreaterEqual(after['expired_keys_hash'], before['expired_keys_hash'] + 1)
+        self.assertGreaterEqual(after['expired_keys'], before['expired_keys'] + 2)
+
+    def test_jobs_leader(self):
+        # the single instance of the tests holds the lease of the leader
+        info = self.r.execute_command('info', 'jobs')
+        self.assertEqual(info['leader'], 1)
+        self.assertEqual(info['job_evict']['max_deletes'], 1000)
+        self.assertIsNone(self.r.execute_command('lock', 'tidis.jobs', '__other__', 1000))
+
+    def test_config_gc(self):
+        settings = self.r.config_get('async_gc_*')
+        self.assertListEqual(sorted(settings.keys()), [
+            'async_gc_batch_size', 'async_gc_concurrency', 'async_gc_deletes_per_sec',
+            'async_gc_target_latency_ms'])
+        self.assertTrue(self.r.config_set('async_gc_batch_size', 10))
+        self.assertEqual(self.r.config_get('async_gc_batch_size'), {'async_gc_batch_size': '10'})
+        self.assertIn('gc_paced_deletes_per_sec', self.r.execute_command('info', 'gc'))
+        self.assertTrue(self.r.config_set('async_gc_batch_size', settings['async_gc_batch_size']))
+        self.assertEqual(self.r.config_get('maxmemory'), {})
+        self.assertRaises(exceptions.ResponseError, self.r.config_set, 'maxmemory', 10)
+        self.assertRaises(exceptions.ResponseError, self.r.config_set, 'async_gc_concurrency', -1)
+
+    def test_config_log_sample(self):
+        settings = self.r.config_get('log_sample_*')
+        self.assertListEqual(sorted(settings.keys()), ['log_sample_rate', 'log_sample_slower_than_us'])
+        self.assertTrue(self.r.execute_command('config', 'set', 'log_sample_rate', 100,
+                                               'log_sample_slower_than_us', 5000))
+        self.assertEqual(self.r.config_get('log_sample_rate'), {'log_sample_rate': '100'})
+        self.assertTrue(self.r.ping())
+        self.assertTrue(self.r.execute_command('config', 'set',
+                                               'log_sample_rate', settings['log_sample_rate'],
+                                               'log_sample_slower_than_us',
+                                               settings['log_sample_slower_than_us']))
+
+    def test_object(self):
+        self.r.execute_command('del', self.k1, self.k2)
+        self.assertIsNone(self.r.object('encoding', self.k1))
+        self.assertTrue(self.r.set(self.k1, 'v'))
+        self.assertEqual(self.r.object('encoding', self.k1), 'tikv-string-v1')
+        self.assertEqual(self.r.object('refcount', self.k1), 1)
+        self.assertEqual(self.r.hset(self.k2, 'f', 'v'), 1)
+        self.assertEqual(self.r.object('encoding', self.k2), 'tikv-hash-v1')
+        self.assertRaises(exceptions.ResponseError, self.r.execute_command, 'object', 'nosuch', self.k1)
+        self.r.execute_command('del', self.k1, self.k2)
+
+    def test_randomkey(self):
+        self.assertTrue(self.r.set(self.k1, 'v'))
+        self.assertTrue(self.r.set(self.k2, 'v'))
+        self.assertIsNotNone(self.r.randomkey())
+        self.assertRaises(exceptions.ResponseError, self.r.execute_command, 'randomkey', self.k1)
+
+    def test_copy(self):
+        self.assertEqual(self.r.execute_command('copy', self.k1, self.k2), 0)
+        self.assertTrue(self.r.set(self.k1, 'v', ex=100))
+        self.assertEqual(self.r.execute_command('copy', self.k1, self.k2), 1)
+        self.assertEqual(self.r.get(self.k2), 'v')
+        self.assertGreater(self.r.ttl(self.k2), 0)
+        self.assertEqual(self.r.execute_command('copy', self.k1, self.k2), 0)
+
+        self.r.execute_command('del', self.k1)
+        self.assertEqual(self.r.hset(self.k1, mapping={'f1': 'v1', 'f2': 'v2'}), 2)
+        self.assertEqual(self.r.execute_command('copy', self.k1, self.k2, 'replace'), 1)
+        self.assertEqual(self.r.type(self.k2), 'hash')
+        self.assertDictEqual(self.r.hgetall(self.k2), {'f1': 'v1', 'f2': 'v2'})
+        self.assertEqual(self.r.ttl(self.k2), -1)
+        self.assertEqual(self.r.hset(self.k2, 'f3', 'v3'), 1)
+        self.assertEqual(self.r.hlen(self.k1), 2)
+
+        self.r.execute_command('del', self.k1)
+        self.assertEqual(self.r.zadd(self.k1, {'a': 1, 'b': 2}), 2)
+        self.assertEqual(self.r.execute_command('copy', self.k1, self.k2, 'db', 0, 'replace'), 1)
+        self.assertListEqual(self.r.zrange(self.k2, 0, -1, withscores=True), [('a', 1), ('b', 2)])
+        self.assertRaises(exceptions.ResponseError, self.r.execute_command, 'copy', self.k1, self.k1)
+        self.assertRaises(exceptions.ResponseError, self.r.execute_command, 'copy', self.k1, self.k2, 'db', 1)
+
+    def test_move(self):
+        # a namespace no test server serves, the keys moved there stay
+        namespace = 65535
+        key = '__move_%s__' % random_string(8)
+        self.assertEqual(self.r.execute_command('tidis.move', key, namespace), 0)
+        self.assertEqual(self.r.rpush(key, 'a', 'b'), 2)
+        self.assertEqual(self.r.execute_command('tidis.move', key, namespace), 1)
+        self.assertEqual(self.r.exists(key), 0)
+        self.assertTrue(self.r.set(key, 'v'))
+        self.assertEqual(self.r.execute_command('tidis.move', key, namespace), 0)
+        self.assertEqual(self.r.get(key), 'v')
+        self.r.execute_command('del', key)
+        self.assertRaises(exceptions.ResponseError, self.r.execute_command, 'tidis.move', key, 'ns')
+
+    def test_dump_restore(self):
+        self.assertIsNone(self.r.dump(self.k1))
+        # the payload of DUMP for the string 10 in Redis
+        payload = b'\x00\xc0\n\t\x00\xbem\x06\x89Z(\x00\n'
+        self.assertTrue(self.r.restore(self.k1, 0, payload))
+        self.assertEqual(self.r.get(self.k1), '10')
+        self.assertRaises(exceptions.ResponseError, self.r.restore, self.k1, 0, payload)
+        self.assertRaises(exceptions.ResponseError, self.r.restore, self.k2, 0, payload[:-1] + b'\x00')
+        # a checksum of 0 is checked too
+        self.assertRaises(exceptions.ResponseError, self.r.restore, self.k2, 0, payload[:-8] + b'\x00' * 8)
+        self.assertTrue(self.r.restore(self.k2, 0, dump_payload(b'\x00\xc0\n'), replace=True))
+        self.assertEqual(self.r.get(self.k2), '10')
+        # an LZF string of 4GB compressed in one byte is refused before it is allocated
+        lzf = b'\x00\xc3\x01\x80\xff\xff\xff\xff\x00'
+        self.assertRaises(exceptions.ResponseError, self.r.restore, self.k2, 0, dump_payload(lzf), replace=True)
+
+        self.r.execute_command('del', self.k1)
+        self.assertEqual(self.r.rpush(self.k1, 'a', 'b', 'c'), 3)
+        payload = self.r.dump(self.k1)
+        self.assertTrue(self.r.restore(self.k2, 100000, payload, replace=True))
+        self.assertListEqual(self.r.lrange(self.k2, 0, -1), ['a', 'b', 'c'])
+        self.assertGreater(self.r.ttl(self.k2), 0)
+
+        self.r.execute_command('del', self.k1)
+        self.assertEqual(self.r.hset(self.k1, mapping={'f1': 'v1', 'f2': 'v2'}), 2)
+        self.assertTrue(self.r.restore(self.k2, 0, self.r.dump(self.k1), replace=True))
+        self.assertDictEqual(self.r.hgetall(self.k2), {'f1': 'v1', 'f2': 'v2'})
+        self.assertEqual(self.r.ttl(self.k2), -1)
+
+    def test_migrate(self):
+        host = self.r.connection_pool.connection_kwargs['host']
+        port = self.r.connection_pool.connection_kwargs['port']
+        self.assertEqual(self.r.execute_command('migrate', host, port, self.k1, 0, 1000), 'NOKEY')
+        self.assertTrue(self.r.set(self.k1, 'v'))
+        # the target is this instance, where the key exists already
+        self.assertRaises(exceptions.ResponseError, self.r.execute_command,
+                          'migrate', host, port, self.k1, 0, 1000)
+        self.assertEqual(self.r.execute_command('migrate', host, port, '', 0, 1000, 'copy', 'replace',
+                                                'keys', self.k1, self.k2), 'OK')
+        self.assertEqual(self.r.get(self.k1), 'v')
+        self.assertRaises(exceptions.ResponseError, self.r.execute_command,
+                          'migrate', host, port, self.k1, 0, 1000, 'keys', self.k2)
+
     def tearDown(self):
         pass
 
+    def test_type(self):
+        self.assertEqual(self.r.type(self.k1), CmdType.NULL.value)
+        writes = [
+            (CmdType.STRING, ('set', self.k1, 'v')),
+            (CmdType.STRING, ('setbit', self.k1, 7, 1)),
+            (CmdType.HASH, ('hset', self.k1, 'f', 'v')),
+            (CmdType.LIST, ('rpush', self.k1, 'a')),
+            (CmdType.SET, ('sadd', self.k1, 'a')),
+            (CmdType.ZSET, ('zadd', self.k1, 1, 'a')),
+            (CmdType.ZSET, ('geoadd', self.k1, 13.361389, 38.115556, 'a')),
+            (CmdType.STREAM, ('xadd', self.k1, '*', 'f', 'v')),
+            (CmdType.JSON, ('json.set', self.k1, '$', '{"a":1}')),
+        ]
+        for expected, write in writes:
+            self.r.execute_command('del', self.k1)
+            self.r.execute_command(*write)
+            self.assertEqual(self.r.type(self.k1), expected.value, write[0])
+
+        self.r.execute_command('del', self.k1)
+        self.assertEqual(self.r.type(self.k1), CmdType.NULL.value)
+        self.assertTrue(self.r.set(self.k1, 'v', px=100))
+        time.sleep(0.2)
+        self.assertEqual(self.r.type(self.k1), CmdType.NULL.value)
+
     @classmethod
     def tearDownClass(cls):
         cls.r.execute_command('del', cls.k1)
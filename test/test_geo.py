@@ -0,0 +1,69 @@
+import unittest
+
+from rediswrap import RedisWrapper
+from test_util import CmdType
+
+
+class GeoTest(unittest.TestCase):
+    @classmethod
+    def setUpClass(cls):
+        cls.r = RedisWrapper().get_instance()
+
+        cls.k1 = '__geo1__'
+        cls.k2 = '__geo2__'
+
+    def setUp(self):
+        self.r.execute_command('del', self.k1)
+        self.r.execute_command('del', self.k2)
+        self.assertEqual(self.r.execute_command('geoadd', self.k1,
+                                                13.361389, 38.115556, 'Palermo',
+                                                15.087269, 37.502669, 'Catania'), 2)
+
+    def test_geoadd(self):
+        self.assertEqual(self.r.execute_command('type', self.k1), CmdType.ZSET.value)
+        self.assertEqual(self.r.execute_command('zcard', self.k1), 2)
+        self.assertEqual(self.r.execute_command('geoadd', self.k1, 13.361389, 38.115556, 'Palermo'), 0)
+        self.assertEqual(self.r.execute_command('geoadd', self.k1, 'NX', 'CH', 13.5, 38.1, 'Palermo'), 0)
+        self.assertEqual(self.r.execute_command('geoadd', self.k1, 'XX', 'CH', 13.5, 38.1, 'Palermo'), 1)
+        with self.assertRaises(Exception) as cm:
+            self.r.execute_command('geoadd', self.k1, 200, 38.1, 'Nowhere')
+        self.assertEqual(str(cm.exception), 'invalid longitude,latitude pair 200.000000,38.100000')
+
+    def test_geopos(self):
+        pos = self.r.execute_command('geopos', self.k1, 'Palermo', 'Nowhere')
+        self.assertAlmostEqual(float(pos[0][0]), 13.361389, places=5)
+        self.assertAlmostEqual(float(pos[0][1]), 38.115556, places=5)
+        self.assertIsNone(pos[1])
+        self.assertEqual(self.r.execute_command('geopos', self.k2, 'Palermo'), [None])
+
+    def test_geodist(self):
+        self.assertEqual(self.r.execute_command('geodist', self.k1, 'Palermo', 'Catania'), '166274.1516')
+        self.assertEqual(self.r.execute_command('geodist', self.k1, 'Palermo', 'Catania', 'km'), '166.2742')
+        self.assertIsNone(self.r.execute_command('geodist', self.k1, 'Palermo', 'Nowhere'))
+
+    def test_geosearch(self):
+        self.assertEqual(self.r.execute_command('geosearch', self.k1, 'FROMLONLAT', 15, 37,
+                                                'BYRADIUS', 200, 'km', 'ASC'), ['Catania', 'Palermo'])
+        self.assertEqual(self.r.execute_command('geosearch', self.k1, 'FROMLONLAT', 15, 37,
+                                                'BYRADIUS', 100, 'km'), ['Catania'])
+        self.assertEqual(self.r.execute_command('geosearch', self.k1, 'FROMLONLAT', 15, 37,
+                                                'BYRADIUS', 200, 'km', 'DESC', 'COUNT', 1), ['Palermo'])
+        self.assertEqual(self.r.execute_command('geosearch', self.k1, 'FROMLONLAT', 15, 37,
+                                                'BYBOX', 400, 400, 'km', 'ASC', 'WITHDIST'),
+                         [['Catania', '56.4413'], ['Palermo', '190.4424']])
+        self.assertEqual(self.r.execute_command('geosearch', self.k1, 'FROMMEMBER', 'Palermo',
+                                                'BYRADIUS', 10, 'km'), ['Palermo'])
+        with self.assertRaises(Exception) as cm:
+            self.r.execute_command('geosearch', self.k1, 'FROMMEMBER', 'Nowhere', 'BYRADIUS', 10, 'km')
+        self.assertEqual(str(cm.exception), 'could not decode requested zset member')
+        self.assertEqual(self.r.execute_command('geosearch', self.k2, 'FROMLONLAT', 15, 37,
+                                                'BYRADIUS', 200, 'km'), [])
+
+    def tearDown(self):
+        pass
+
+    @classmethod
+    def tearDownClass(cls):
+        cls.r.execute_command('del', cls.k1)
+        cls.r.execute_command('del', cls.k2)
+        print('test data cleaned up')
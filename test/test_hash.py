@@ -192,6 +192,16 @@ class HashTest(unittest.TestCase):
         time.sleep(6)
         self.assertEqual(self.r.hlen(self.k1), 0)
 
+    def test_hindex(self):
+        # the indexes are declared in the config, the test config has none
+        # for this prefix
+        prefix = self.k1 + ':'
+        indexes = self.r.execute_command('hindex', 'list')
+        self.assertNotIn([prefix, self.f1], indexes)
+        with self.assertRaises(Exception) as cm:
+            self.r.execute_command('hindex', 'lookup', prefix, self.f1, self.v1)
+        self.assertEqual(str(cm.exception), 'no hash index is declared for this prefix and field')
+
     def tearDown(self):
         pass
 
@@ -2,11 +2,14 @@ import sys
 import unittest
 
 from test_generic import GenericTest
+from test_geo import GeoTest
 from test_hash import HashTest
 from test_invalid import InvalidTest
+from test_json import JsonTest
 from test_list import ListTest
 from test_lua import LuaTest
 from test_set import SetTest
+from test_stream import StreamTest
 from test_string import StringTest
 from test_zset import ZsetTest
 
@@ -19,6 +22,9 @@ if __name__ == '__main__':
     suite.addTest(unittest.TestLoader().loadTestsFromTestCase(ListTest))
     suite.addTest(unittest.TestLoader().loadTestsFromTestCase(SetTest))
     suite.addTest(unittest.TestLoader().loadTestsFromTestCase(ZsetTest))
+    suite.addTest(unittest.TestLoader().loadTestsFromTestCase(GeoTest))
+    suite.addTest(unittest.TestLoader().loadTestsFromTestCase(StreamTest))
+    suite.addTest(unittest.TestLoader().loadTestsFromTestCase(JsonTest))
     suite.addTest(unittest.TestLoader().loadTestsFromTestCase(LuaTest))
 
     runner = unittest.TextTestRunner(verbosity=2)
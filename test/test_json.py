@@ -0,0 +1,107 @@
+import json
+import time
+import unittest
+
+from rediswrap import RedisWrapper
+from test_util import CmdType
+
+
+class JsonTest(unittest.TestCase):
+    @classmethod
+    def setUpClass(cls):
+        cls.r = RedisWrapper().get_instance()
+
+        cls.k1 = '__json1__'
+        cls.k2 = '__json2__'
+
+        cls.doc = {'name': 'tidis', 'tags': ['redis', 'tikv'], 'nested': {'a': 1, 'b': None}}
+
+    def setUp(self):
+        self.r.execute_command('del', self.k1)
+        self.r.execute_command('del', self.k2)
+        pass
+
+    def json_set(self, key, path, value, *options):
+        return self.r.execute_command('json.set', key, path, json.dumps(value), *options)
+
+    def json_get(self, key, *paths):
+        res = self.r.execute_command('json.get', key, *paths)
+        return None if res is None else json.loads(res)
+
+    def test_set_get(self):
+        self.assertTrue(self.json_set(self.k1, '$', self.doc))
+        self.assertEqual(self.json_get(self.k1), self.doc)
+        self.assertEqual(self.json_get(self.k1, '.'), self.doc)
+        self.assertEqual(self.json_get(self.k2), None)
+        self.assertEqual(self.r.type(self.k1), CmdType.JSON.value)
+
+    def test_get_paths(self):
+        self.json_set(self.k1, '$', self.doc)
+        self.assertEqual(self.json_get(self.k1, '.name'), 'tidis')
+        self.assertEqual(self.json_get(self.k1, 'nested.a'), 1)
+        self.assertEqual(self.json_get(self.k1, '$.name'), ['tidis'])
+        self.assertEqual(self.json_get(self.k1, '$.tags[-1]'), ['tikv'])
+        self.assertEqual(self.json_get(self.k1, "$['nested']['a']"), [1])
+        self.assertEqual(self.json_get(self.k1, '$.tags[*]'), ['redis', 'tikv'])
+        self.assertEqual(self.json_get(self.k1, '$.missing'), [])
+        self.assertRaises(Exception, self.json_get, self.k1, '.missing')
+        self.assertEqual(self.json_get(self.k1, '$.name', '$.nested.a'),
+                         {'$.name': ['tidis'], '$.nested.a': [1]})
+
+    def test_set_path(self):
+        self.json_set(self.k1, '$', self.doc)
+        self.assertTrue(self.json_set(self.k1, '$.nested.b', [1, 2]))
+        self.assertTrue(self.json_set(self.k1, '$.nested.c', 'new'))
+        self.assertTrue(self.json_set(self.k1, '$.tags[0]', 'resp'))
+        self.assertEqual(self.json_get(self.k1, '$.nested'), [{'a': 1, 'b': [1, 2], 'c': 'new'}])
+        self.assertEqual(self.json_get(self.k1, '.tags'), ['resp', 'tikv'])
+        self.assertEqual(self.json_set(self.k1, '$.missing.a', 1), None)
+        self.assertRaises(Exception, self.json_set, self.k2, '$.a', 1)
+        self.assertRaises(Exception, self.r.execute_command, 'json.set', self.k1, '$', '{bad')
+
+    def test_set_nx_xx(self):
+        self.assertEqual(self.json_set(self.k1, '$', self.doc, 'XX'), None)
+        self.assertTrue(self.json_set(self.k1, '$', self.doc, 'NX'))
+        self.assertEqual(self.json_set(self.k1, '$', {}, 'NX'), None)
+        self.assertEqual(self.json_set(self.k1, '$.name', 'x', 'NX'), None)
+        self.assertTrue(self.json_set(self.k1, '$.other', 'x', 'NX'))
+        self.assertEqual(self.json_set(self.k1, '$.absent', 'x', 'XX'), None)
+        self.assertTrue(self.json_set(self.k1, '$.name', 'x', 'XX'))
+        self.assertEqual(self.json_get(self.k1, '.name'), 'x')
+        self.assertEqual(self.json_get(self.k1, '.other'), 'x')
+
+    def test_del(self):
+        self.json_set(self.k1, '$', self.doc)
+        self.assertEqual(self.r.execute_command('json.del', self.k1, '$.nested.a'), 1)
+        self.assertEqual(self.r.execute_command('json.del', self.k1, '$.nested.a'), 0)
+        self.assertEqual(self.r.execute_command('json.del', self.k1, '$.tags[*]'), 2)
+        self.assertEqual(self.json_get(self.k1, '$.tags'), [[]])
+        self.assertEqual(self.r.execute_command('json.del', self.k1), 1)
+        self.assertEqual(self.r.exists(self.k1), 0)
+        self.assertEqual(self.r.execute_command('json.del', self.k1), 0)
+
+    def test_wrong_type(self):
+        self.r.set(self.k1, 'value')
+        self.assertRaises(Exception, self.json_get, self.k1)
+        self.assertRaises(Exception, self.json_set, self.k1, '$.a', 1)
+        self.json_set(self.k2, '$', self.doc)
+        self.assertRaises(Exception, self.r.get, self.k2)
+
+    def test_expire(self):
+        self.json_set(self.k1, '$', self.doc)
+        self.assertTrue(self.r.execute_command('pexpire', self.k1, 1000))
+        self.json_set(self.k1, '$.name', 'x')
+        pttl = self.r.execute_command('pttl', self.k1)
+        self.assertLessEqual(pttl, 1000)
+        self.assertGreater(pttl, 0)
+        time.sleep(2)
+        self.assertEqual(self.json_get(self.k1), None)
+
+    def tearDown(self):
+        pass
+
+    @classmethod
+    def tearDownClass(cls):
+        cls.r.execute_command('del', cls.k1)
+        cls.r.execute_command('del', cls.k2)
+        print('test data cleaned up')
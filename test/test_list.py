@@ -61,6 +61,44 @@ class ListTest(unittest.TestCase):
         for i in range(200):
             self.assertEqual(self.r.lindex(self.k1, i), str(i))
 
+    def test_lpos(self):
+        self.assertEqual(self.r.rpush(self.k1, 'a', 'b', 'c', '1', '2', '3', 'c', 'c'), 8)
+        self.assertEqual(self.r.execute_command('lpos', self.k1, 'c'), 2)
+        self.assertEqual(self.r.execute_command('lpos', self.k1, 'c', 'RANK', 2), 6)
+        self.assertEqual(self.r.execute_command('lpos', self.k1, 'c', 'RANK', -1), 7)
+        self.assertEqual(self.r.execute_command('lpos', self.k1, 'c', 'COUNT', 2), [2, 6])
+        self.assertEqual(self.r.execute_command('lpos', self.k1, 'c', 'COUNT', 0), [2, 6, 7])
+        self.assertEqual(self.r.execute_command('lpos', self.k1, 'c', 'RANK', -1, 'COUNT', 2), [7, 6])
+        self.assertEqual(self.r.execute_command('lpos', self.k1, 'c', 'COUNT', 0, 'MAXLEN', 3), [2])
+        self.assertIsNone(self.r.execute_command('lpos', self.k1, 'x'))
+        self.assertEqual(self.r.execute_command('lpos', self.k1, 'x', 'COUNT', 1), [])
+        self.assertIsNone(self.r.execute_command('lpos', self.k2, 'c'))
+        with self.assertRaises(Exception):
+            self.r.execute_command('lpos', self.k1, 'c', 'RANK', 0)
+        with self.assertRaises(Exception):
+            self.r.execute_command('lpos', self.k1, 'c', 'COUNT', -1)
+        with self.assertRaises(Exception):
+            self.r.execute_command('lpos', self.k1, 'c', 'MAXLEN', -1)
+
+    def test_lmpop(self):
+        self.assertIsNone(self.r.execute_command('lmpop', 2, self.k1, self.k2, 'LEFT'))
+        self.assertEqual(self.r.rpush(self.k2, 'a', 'b', 'c'), 3)
+        self.assertEqual(self.r.execute_command('lmpop', 2, self.k1, self.k2, 'LEFT'), [self.k2, ['a']])
+        self.assertEqual(self.r.execute_command('lmpop', 2, self.k1, self.k2, 'RIGHT', 'COUNT', 5),
+                         [self.k2, ['c', 'b']])
+        self.assertIsNone(self.r.execute_command('blmpop', 0.1, 2, self.k1, self.k2, 'LEFT'))
+        self.assertEqual(self.r.rpush(self.k1, 'x'), 1)
+        self.assertEqual(self.r.execute_command('blmpop', 1, 2, self.k1, self.k2, 'LEFT'), [self.k1, ['x']])
+        with self.assertRaises(Exception):
+            self.r.execute_command('lmpop', 0, self.k1, 'LEFT')
+        with self.assertRaises(Exception):
+            self.r.execute_command('lmpop', 1, self.k1, 'LEFT', 'COUNT', 0)
+        with self.assertRaises(Exception):
+            self.r.execute_command('blmpop', -1, 1, self.k1, 'LEFT')
+        with self.assertRaises(Exception) as cm:
+            self.r.execute_command('blmpop', '1e300', 1, self.k1, 'LEFT')
+        self.assertIn('timeout is out of range', str(cm.exception))
+
     def test_lrange(self):
         for i in range(200):
             self.assertTrue(self.r.rpush(self.k1, str(i)))
@@ -219,6 +257,27 @@ class ListTest(unittest.TestCase):
         time.sleep(6)
         self.assertEqual(self.r.llen(self.k1), 0)
 
+    def test_sort(self):
+        self.assertEqual(self.r.rpush(self.k1, '3', '1', '10', '2'), 4)
+        self.assertListEqual(self.r.execute_command('sort', self.k1), ['1', '2', '3', '10'])
+        self.assertListEqual(self.r.execute_command('sort', self.k1, 'desc', 'limit', 1, 2), ['3', '2'])
+        self.assertListEqual(self.r.execute_command('sort', self.k1, 'alpha'), ['1', '10', '2', '3'])
+        self.assertListEqual(self.r.execute_command('sort', self.k1, 'by', 'nosort'), ['3', '1', '10', '2'])
+
+        weights = {'__w_1__': '4', '__w_2__': '3', '__w_3__': '2', '__w_10__': '1'}
+        self.assertTrue(self.r.mset(weights))
+        self.assertEqual(self.r.hset('__h_1__', 'name', 'one'), 1)
+        self.assertListEqual(self.r.execute_command('sort', self.k1, 'by', '__w_*__'), ['10', '3', '2', '1'])
+        self.assertListEqual(self.r.execute_command('sort', self.k1, 'get', '#', 'get', '__h_*__->name', 'limit', 0, 1),
+                             ['1', 'one'])
+
+        self.assertEqual(self.r.execute_command('sort', self.k1, 'by', '__w_*__', 'store', self.k2), 4)
+        self.assertListEqual(self.r.lrange(self.k2, 0, -1), ['10', '3', '2', '1'])
+
+        self.assertTrue(self.r.rpush(self.k1, 'a'))
+        self.assertRaises(Exception, self.r.execute_command, 'sort', self.k1)
+        self.r.delete('__h_1__', *weights.keys())
+
     def tearDown(self):
         pass
 
@@ -63,6 +63,8 @@ class SetTest(unittest.TestCase):
         self.assertEqual(self.r.sadd(self.k1, self.v2), 1)
         self.assertListEqual(self.r.execute_command('smismember', self.k1, self.v1, self.v2, NOT_EXISTS_LITERAL),
                              [1, 1, 0])
+        self.assertListEqual(self.r.execute_command('smismember', self.k1, self.v1, NOT_EXISTS_LITERAL, self.v1),
+                             [1, 0, 1])
 
     def test_smembers(self):
         for i in range(200):
@@ -90,6 +92,35 @@ class SetTest(unittest.TestCase):
         self.assertEqual(self.r.srem(self.k1, "1", "2", "3"), 3)
         self.assertEqual(self.r.scard(self.k1), 107)
 
+    def test_sinter(self):
+        for i in range(200):
+            self.assertEqual(self.r.sadd(self.k1, str(i)), 1)
+        for i in range(100, 300):
+            self.assertEqual(self.r.sadd(self.k2, str(i)), 1)
+        self.assertSetEqual(self.r.sinter(self.k1, self.k2), set([str(i) for i in range(100, 200)]))
+        self.assertEqual(self.r.execute_command('sintercard', 2, self.k1, self.k2), 100)
+        self.assertEqual(self.r.execute_command('sintercard', 2, self.k1, self.k2, 'limit', 10), 10)
+        self.assertEqual(self.r.sadd(self.k3, '0', '150', '250', 'x'), 4)
+        self.assertEqual(self.r.execute_command('sintercard', 3, self.k1, self.k2, self.k3), 1)
+        self.assertEqual(self.r.execute_command('sintercard', 2, self.k3, self.k1, 'limit', 1), 1)
+        self.r.execute_command('del', self.k3)
+        # missing key makes the intersection empty
+        self.assertSetEqual(self.r.sinter(self.k1, self.k2, self.k3), set())
+        self.assertEqual(self.r.execute_command('sintercard', 3, self.k1, self.k2, self.k3), 0)
+
+    def test_sdrain(self):
+        self.assertListEqual(self.r.execute_command('sdrain', self.k1, 10), [])
+        for i in range(20):
+            self.assertEqual(self.r.sadd(self.k1, str(i)), 1)
+        drained = self.r.execute_command('sdrain', self.k1, 1)
+        self.assertEqual(len(drained), 1)
+        drained += self.r.execute_command('sdrain', self.k1, 10)
+        self.assertEqual(len(drained), 11)
+        self.assertEqual(self.r.scard(self.k1), 9)
+        drained += self.r.execute_command('sdrain', self.k1, 100)
+        self.assertSetEqual(set(drained), set([str(i) for i in range(20)]))
+        self.assertEqual(self.r.exists(self.k1), 0)
+
     def test_spop(self):
         for i in range(200):
             self.assertEqual(self.r.sadd(self.k1, str(i)), 1)
@@ -0,0 +1,207 @@
+import time
+import unittest
+
+from rediswrap import RedisWrapper
+from test_util import CmdType, trigger_async_del_size
+
+
+class StreamTest(unittest.TestCase):
+    @classmethod
+    def setUpClass(cls):
+        cls.r = RedisWrapper().get_instance()
+
+        cls.k1 = '__stream1__'
+        cls.k2 = '__stream2__'
+
+        cls.f1 = 'field1'
+        cls.v1 = 'value1'
+
+    def setUp(self):
+        self.r.execute_command('del', self.k1)
+        self.r.execute_command('del', self.k2)
+        pass
+
+    def test_xadd(self):
+        ids = [self.r.xadd(self.k1, {self.f1: str(i)}) for i in range(100)]
+        self.assertEqual(ids, sorted(ids, key=lambda id: tuple(map(int, id.split('-')))))
+        self.assertEqual(len(set(ids)), 100)
+        self.assertEqual(self.r.xlen(self.k1), 100)
+
+    def test_xadd_id(self):
+        self.assertEqual(self.r.xadd(self.k1, {self.f1: self.v1}, id='5-1'), '5-1')
+        self.assertEqual(self.r.xadd(self.k1, {self.f1: self.v1}, id='5-*'), '5-2')
+        self.assertEqual(self.r.xadd(self.k1, {self.f1: self.v1}, id='7'), '7-0')
+        self.assertRaises(Exception, self.r.xadd, self.k1, {self.f1: self.v1}, id='7-0')
+        self.assertRaises(Exception, self.r.xadd, self.k1, {self.f1: self.v1}, id='6-5')
+        self.assertRaises(Exception, self.r.xadd, self.k2, {self.f1: self.v1}, id='0-0')
+        self.assertEqual(self.r.xlen(self.k1), 3)
+
+    def test_xadd_maxlen(self):
+        for i in range(10):
+            self.r.xadd(self.k1, {self.f1: str(i)}, id='{}-0'.format(i + 1), maxlen=3)
+        self.assertEqual(self.r.xlen(self.k1), 3)
+        entries = self.r.xrange(self.k1)
+        self.assertEqual([e[0] for e in entries], ['8-0', '9-0', '10-0'])
+        # ids keep increasing once trimmed
+        self.assertRaises(Exception, self.r.xadd, self.k1, {self.f1: self.v1}, id='9-5')
+
+    def test_xadd_nomkstream(self):
+        self.assertIsNone(self.r.xadd(self.k1, {self.f1: self.v1}, nomkstream=True))
+        self.assertEqual(self.r.exists(self.k1), 0)
+
+    def test_xrange(self):
+        for i in range(10):
+            self.r.xadd(self.k1, {self.f1: str(i), 'n': 'x'}, id='{}-{}'.format(i // 2 + 1, i % 2))
+        entries = self.r.xrange(self.k1)
+        self.assertEqual(len(entries), 10)
+        self.assertEqual(entries[0], ('1-0', {self.f1: '0', 'n': 'x'}))
+        self.assertEqual([e[0] for e in self.r.xrange(self.k1, '2', '3')], ['2-0', '2-1', '3-0', '3-1'])
+        self.assertEqual([e[0] for e in self.r.xrange(self.k1, '(2-1', '+', count=2)], ['3-0', '3-1'])
+        self.assertEqual(self.r.xrange(self.k1, '4', '2'), [])
+        self.assertEqual(self.r.xrange(self.k2), [])
+
+    def test_xrevrange(self):
+        for i in range(10):
+            self.r.xadd(self.k1, {self.f1: str(i)}, id='{}-0'.format(i + 1))
+        entries = self.r.xrevrange(self.k1, count=3)
+        self.assertEqual([e[0] for e in entries], ['10-0', '9-0', '8-0'])
+        self.assertEqual([e[0] for e in self.r.xrevrange(self.k1, '3', '(1')], ['3-0', '2-0'])
+
+    def test_xread(self):
+        for i in range(5):
+            self.r.xadd(self.k1, {self.f1: str(i)}, id='{}-0'.format(i + 1))
+        self.r.xadd(self.k2, {self.f1: self.v1}, id='1-0')
+        res = self.r.xread({self.k1: '3-0', self.k2: '0'})
+        self.assertEqual(res, [[self.k1, [('4-0', {self.f1: '3'}), ('5-0', {self.f1: '4'})]],
+                               [self.k2, [('1-0', {self.f1: self.v1})]]])
+        res = self.r.xread({self.k1: '0'}, count=1)
+        self.assertEqual(res, [[self.k1, [('1-0', {self.f1: '0'})]]])
+        self.assertFalse(self.r.xread({self.k1: '5-0', self.k2: '$'}))
+
+    def test_xread_entries(self):
+        # the reply nests the fields of an entry five arrays deep
+        fields = {'f{}'.format(i): 'v{}'.format(i) for i in range(10)}
+        for i in range(3):
+            self.r.xadd(self.k1, fields, id='{}-0'.format(i + 1))
+        res = self.r.xread({self.k1: '0'})
+        self.assertEqual(res, [[self.k1, [('{}-0'.format(i + 1), fields) for i in range(3)]]])
+        res = self.r.xread({self.k1: '1-0'}, count=1)
+        self.assertEqual(res, [[self.k1, [('2-0', fields)]]])
+
+    def test_xgroup(self):
+        self.assertRaises(Exception, self.r.xgroup_create, self.k1, 'g1')
+        self.assertTrue(self.r.xgroup_create(self.k1, 'g1', mkstream=True))
+        self.assertEqual(self.r.xlen(self.k1), 0)
+        self.assertRaises(Exception, self.r.xgroup_create, self.k1, 'g1')
+        self.assertTrue(self.r.xgroup_setid(self.k1, 'g1', '0'))
+        self.assertRaises(Exception, self.r.xgroup_setid, self.k1, 'g2', '0')
+        self.assertEqual(self.r.xgroup_destroy(self.k1, 'g1'), 1)
+        self.assertEqual(self.r.xgroup_destroy(self.k1, 'g1'), 0)
+
+    def test_xreadgroup(self):
+        for i in range(5):
+            self.r.xadd(self.k1, {self.f1: str(i)}, id='{}-0'.format(i + 1))
+        self.r.xgroup_create(self.k1, 'g1', id='0')
+        res = self.r.xreadgroup('g1', 'c1', {self.k1: '>'}, count=2)
+        self.assertEqual(res, [[self.k1, [('1-0', {self.f1: '0'}), ('2-0', {self.f1: '1'})]]])
+        res = self.r.xreadgroup('g1', 'c2', {self.k1: '>'})
+        self.assertEqual([e[0] for e in res[0][1]], ['3-0', '4-0', '5-0'])
+        self.assertFalse(self.r.xreadgroup('g1', 'c2', {self.k1: '>'}))
+        # the history of a consumer is its pending entries
+        res = self.r.xreadgroup('g1', 'c1', {self.k1: '0'})
+        self.assertEqual([e[0] for e in res[0][1]], ['1-0', '2-0'])
+        self.r.xadd(self.k1, {self.f1: self.v1}, id='6-0')
+        self.r.xreadgroup('g1', 'c1', {self.k1: '>'}, noack=True)
+        res = self.r.xreadgroup('g1', 'c1', {self.k1: '0'})
+        self.assertEqual([e[0] for e in res[0][1]], ['1-0', '2-0'])
+        self.assertRaises(Exception, self.r.xreadgroup, 'g2', 'c1', {self.k1: '>'})
+
+    def test_xack_xpending(self):
+        for i in range(5):
+            self.r.xadd(self.k1, {self.f1: str(i)}, id='{}-0'.format(i + 1))
+        self.r.xgroup_create(self.k1, 'g1', id='0')
+        self.assertEqual(self.r.xpending(self.k1, 'g1')['pending'], 0)
+        self.r.xreadgroup('g1', 'c1', {self.k1: '>'}, count=3)
+        self.r.xreadgroup('g1', 'c2', {self.k1: '>'})
+        pending = self.r.xpending(self.k1, 'g1')
+        self.assertEqual(pending['pending'], 5)
+        self.assertEqual(pending['min'], '1-0')
+        self.assertEqual(pending['max'], '5-0')
+        self.assertEqual([(c['name'], c['pending']) for c in pending['consumers']], [('c1', 3), ('c2', 2)])
+
+        self.assertEqual(self.r.xack(self.k1, 'g1', '1-0', '2-0', '9-0'), 2)
+        self.assertEqual(self.r.xack(self.k1, 'g1', '1-0'), 0)
+        entries = self.r.xpending_range(self.k1, 'g1', '-', '+', 10)
+        self.assertEqual([e['message_id'] for e in entries], ['3-0', '4-0', '5-0'])
+        self.assertEqual(entries[0]['consumer'], 'c1')
+        self.assertEqual(entries[0]['times_delivered'], 1)
+        entries = self.r.xpending_range(self.k1, 'g1', '-', '+', 10, consumername='c2')
+        self.assertEqual([e['message_id'] for e in entries], ['4-0', '5-0'])
+        self.assertEqual(self.r.xgroup_delconsumer(self.k1, 'g1', 'c2'), 2)
+        self.assertEqual(self.r.xpending(self.k1, 'g1')['pending'], 1)
+
+    def test_xclaim(self):
+        for i in range(3):
+            self.r.xadd(self.k1, {self.f1: str(i)}, id='{}-0'.format(i + 1))
+        self.r.xgroup_create(self.k1, 'g1', id='0')
+        self.r.xreadgroup('g1', 'c1', {self.k1: '>'})
+        self.assertEqual(self.r.xclaim(self.k1, 'g1', 'c2', 60000, ['1-0']), [])
+        time.sleep(0.2)
+        res = self.r.xclaim(self.k1, 'g1', 'c2', 100, ['1-0', '9-0'])
+        self.assertEqual(res, [('1-0', {self.f1: '0'})])
+        res = self.r.xclaim(self.k1, 'g1', 'c2', 0, ['2-0'], justid=True)
+        self.assertEqual(res, ['2-0'])
+        entries = self.r.xpending_range(self.k1, 'g1', '-', '+', 10, consumername='c2')
+        self.assertEqual([(e['message_id'], e['times_delivered']) for e in entries], [('1-0', 2), ('2-0', 1)])
+        # trimmed entries are no longer pending
+        self.r.xadd(self.k1, {self.f1: self.v1}, maxlen=1)
+        self.assertEqual(self.r.xclaim(self.k1, 'g1', 'c1', 0, ['3-0']), [])
+        self.assertEqual(self.r.xpending(self.k1, 'g1')['pending'], 2)
+
+    def test_type(self):
+        self.assertEqual(self.r.type(self.k1), CmdType.NULL.value)
+        self.r.xadd(self.k1, {self.f1: self.v1})
+        self.assertEqual(self.r.type(self.k1), CmdType.STREAM.value)
+        self.assertRaises(Exception, self.r.lpush, self.k1, self.v1)
+        self.r.rpush(self.k2, self.v1)
+        self.assertRaises(Exception, self.r.xadd, self.k2, {self.f1: self.v1})
+
+    def test_del(self):
+        self.r.xadd(self.k1, {self.f1: self.v1}, id='5-0')
+        self.assertEqual(self.r.execute_command('del', self.k1), 1)
+        self.assertEqual(self.r.xlen(self.k1), 0)
+        # ids start over once the stream is deleted
+        self.assertEqual(self.r.xadd(self.k1, {self.f1: self.v1}, id='1-0'), '1-0')
+
+    def test_async_del(self):
+        size = trigger_async_del_size()
+        for i in range(size):
+            self.r.xadd(self.k1, {self.f1: str(i)})
+        self.assertEqual(self.r.xlen(self.k1), size)
+        self.assertTrue(self.r.delete(self.k1))
+        self.assertEqual(self.r.xlen(self.k1), 0)
+        self.assertEqual(self.r.xrange(self.k1), [])
+        self.r.xadd(self.k1, {self.f1: self.v1})
+        self.assertEqual(self.r.xlen(self.k1), 1)
+
+    def test_expire(self):
+        self.r.xadd(self.k1, {self.f1: self.v1})
+        self.assertTrue(self.r.execute_command('pexpire', self.k1, 1000))
+        pttl = self.r.execute_command('pttl', self.k1)
+        self.assertLessEqual(pttl, 1000)
+        self.assertGreater(pttl, 0)
+        self.assertEqual(self.r.persist(self.k1), 1)
+        self.assertEqual(self.r.execute_command('pttl', self.k1), -1)
+        self.assertTrue(self.r.execute_command('pexpire', self.k1, 1000))
+        time.sleep(2)
+        self.assertEqual(self.r.xlen(self.k1), 0)
+        self.assertEqual(self.r.xrange(self.k1), [])
+
+    def tearDown(self):
+        pass
+
+    @classmethod
+    def tearDownClass(cls):
+        cls.r.execute_command('del', cls.k1)
+        cls.r.execute_command('del', cls.k2)
+        print('test data cleaned up')
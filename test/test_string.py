@@ -1,6 +1,8 @@
 import time
 import unittest
 
+from redis import exceptions
+
 from rediswrap import RedisWrapper
 from test_util import sec_ts_after_five_secs, msec_ts_after_five_secs, NOT_EXISTS_LITERAL, CmdType, \
     trigger_async_del_size, random_string
@@ -27,6 +29,14 @@ class StringTest(unittest.TestCase):
         v1 = self.r.get(self.k1)
         self.assertEqual(self.v1, v1, '{} != {}'.format(v1, self.v1))
 
+    def test_get_large(self):
+        # replies this large are written with vectored writes
+        v1 = random_string(200 * 1024)
+        v2 = random_string(100)
+        self.assertTrue(self.r.mset({self.k1: v1, self.k2: v2}))
+        self.assertEqual(self.r.get(self.k1), v1)
+        self.assertListEqual(self.r.mget(self.k1, self.k2, self.k1), [v1, v2, v1])
+
     def test_set(self):
         self.assertTrue(self.r.set(self.k1, self.v1))
         v1 = self.r.get(self.k1)
@@ -111,6 +121,28 @@ class StringTest(unittest.TestCase):
         err = cm.exception
         self.assertEqual(str(err), 'value is not an integer or out of range')
 
+    def test_incr_ttl(self):
+        # a new key has the default ttl
+        self.assertEqual(self.r.execute_command("INCR", self.k1), 1)
+        if RedisWrapper.default_ttl_ms:
+            self.assertGreater(self.r.pttl(self.k1), 0)
+            self.assertLessEqual(self.r.pttl(self.k1), RedisWrapper.default_ttl_ms)
+        else:
+            self.assertEqual(self.r.ttl(self.k1), -1)
+
+        # an existing key keeps its ttl
+        self.assertTrue(self.r.set(self.k1, 10, ex=100))
+        self.assertEqual(self.r.execute_command("INCR", self.k1), 11)
+        self.assertLessEqual(self.r.ttl(self.k1), 100)
+        self.assertGreater(self.r.ttl(self.k1), 90)
+
+        # a persistent key stays persistent
+        self.assertTrue(self.r.set(self.k2, 10))
+        self.r.persist(self.k2)
+        self.assertEqual(self.r.ttl(self.k2), -1)
+        self.assertEqual(self.r.execute_command("INCRBY", self.k2, 5), 15)
+        self.assertEqual(self.r.ttl(self.k2), -1)
+
     def test_incrby(self):
         self.assertEqual(self.r.incrby(self.k1, 1), 1)
         self.assertEqual(self.r.incrby(self.k1, 9), 10)
@@ -156,6 +188,205 @@ class StringTest(unittest.TestCase):
         self.assertTrue(self.r.set(self.k1, self.v1))
         self.assertEqual(self.r.strlen(self.k1), len(self.v1))
 
+    @staticmethod
+    def reference_getrange(value, start, end):
+        # offsets handling of getrangeCommand in redis t_string.c
+        length = len(value)
+        if start < 0 and end < 0 and start > end:
+            return ''
+        if start < 0:
+            start = max(length + start, 0)
+        if end < 0:
+            end = max(length + end, 0)
+        end = min(end, length - 1)
+        if start > end or length == 0:
+            return ''
+        return value[start:end + 1]
+
+    def test_getrange(self):
+        offsets = [-100, -7, -6, -5, -1, 0, 1, 5, 6, 7, 100]
+        for value in ['', 'x', self.v1]:
+            self.assertTrue(self.r.set(self.k1, value))
+            for start in offsets:
+                for end in offsets:
+                    expected = self.reference_getrange(value, start, end)
+                    self.assertEqual(self.r.getrange(self.k1, start, end), expected)
+                    self.assertEqual(self.r.execute_command('substr', self.k1, start, end), expected)
+        self.assertEqual(self.r.getrange(self.k2, 0, -1), '')
+
+    def test_getdel(self):
+        self.assertTrue(self.r.set(self.k1, self.v1))
+        self.assertEqual(self.r.execute_command('getdel', self.k1), self.v1)
+        self.assertIsNone(self.r.get(self.k1))
+        self.assertIsNone(self.r.execute_command('getdel', self.k1))
+        self.r.lpush(self.k2, self.v1)
+        with self.assertRaises(Exception):
+            self.r.execute_command('getdel', self.k2)
+        self.assertEqual(self.r.llen(self.k2), 1)
+
+    def test_getex(self):
+        self.assertTrue(self.r.set(self.k1, self.v1))
+        self.assertEqual(self.r.execute_command('getex', self.k1), self.v1)
+        self.assertEqual(self.r.ttl(self.k1), -1)
+        self.assertEqual(self.r.execute_command('getex', self.k1, 'EX', 100), self.v1)
+        self.assertGreater(self.r.ttl(self.k1), 90)
+        self.assertEqual(self.r.execute_command('getex', self.k1, 'PX', 50000), self.v1)
+        self.assertLessEqual(self.r.pttl(self.k1), 50000)
+        at = int(time.time()) + 200
+        self.assertEqual(self.r.execute_command('getex', self.k1, 'EXAT', at), self.v1)
+        self.assertGreater(self.r.ttl(self.k1), 190)
+        self.assertEqual(self.r.execute_command('getex', self.k1, 'PERSIST'), self.v1)
+        self.assertEqual(self.r.ttl(self.k1), -1)
+        with self.assertRaises(Exception):
+            self.r.execute_command('getex', self.k1, 'EX', 0)
+        with self.assertRaises(Exception):
+            self.r.execute_command('getex', self.k1, 'EX', 10, 'PERSIST')
+        self.assertEqual(self.r.execute_command('getex', self.k1, 'PXAT', 1), self.v1)
+        self.assertIsNone(self.r.get(self.k1))
+        self.assertIsNone(self.r.execute_command('getex', self.k2, 'EX', 10))
+
+    def test_append(self):
+        self.assertEqual(self.r.append(self.k1, self.v1), len(self.v1))
+        self.assertEqual(self.r.append(self.k1, self.v2), len(self.v1) + len(self.v2))
+        self.assertEqual(self.r.get(self.k1), self.v1 + self.v2)
+        self.assertTrue(self.r.expire(self.k1, 5))
+        self.assertEqual(self.r.append(self.k1, ''), len(self.v1) + len(self.v2))
+        self.assertGreater(self.r.ttl(self.k1), 0)
+
+    def test_setrange(self):
+        self.assertTrue(self.r.set(self.k1, 'Hello World'))
+        self.assertEqual(self.r.setrange(self.k1, 6, 'Redis'), 11)
+        self.assertEqual(self.r.get(self.k1), 'Hello Redis')
+        # writing past the end pads with zero bytes and keeps the ttl
+        self.assertTrue(self.r.expire(self.k1, 5))
+        self.assertEqual(self.r.setrange(self.k1, 13, 'x'), 14)
+        self.assertEqual(self.r.get(self.k1), 'Hello Redis\x00\x00x')
+        self.assertGreater(self.r.ttl(self.k1), 0)
+        self.assertEqual(self.r.getrange(self.k1, 11, -1), '\x00\x00x')
+        # an empty value does not create the key
+        self.assertEqual(self.r.setrange(self.k2, 5, ''), 0)
+        self.assertEqual(self.r.exists(self.k2), 0)
+        self.assertEqual(self.r.setrange(self.k2, 2, 'ab'), 4)
+        self.assertEqual(self.r.get(self.k2), '\x00\x00ab')
+        with self.assertRaises(Exception):
+            self.r.setrange(self.k1, -1, 'x')
+        with self.assertRaises(Exception):
+            self.r.setrange(self.k1, 512 * 1024 * 1024, 'x')
+        # an end past u64::MAX is too long as well
+        with self.assertRaises(exceptions.ResponseError) as cm:
+            self.r.execute_command('setrange', self.k1, 18446744073709551615, 'x')
+        self.assertIn('string exceeds maximum allowed size', str(cm.exception))
+        self.assertEqual(self.r.get(self.k1), 'Hello Redis\x00\x00x')
+
+    def test_bitmap(self):
+        self.assertEqual(self.r.setbit(self.k1, 7, 1), 0)
+        self.assertEqual(self.r.setbit(self.k1, 7, 1), 1)
+        self.assertEqual(self.r.getbit(self.k1, 7), 1)
+        self.assertEqual(self.r.getbit(self.k1, 6), 0)
+        self.assertEqual(self.r.getbit(self.k1, 100), 0)
+        self.assertEqual(self.r.get(self.k1), '\x01')
+        # setting a bit past the end pads with zero bytes and keeps the ttl
+        self.assertTrue(self.r.expire(self.k1, 5))
+        self.assertEqual(self.r.setbit(self.k1, 23, 0), 0)
+        self.assertEqual(self.r.strlen(self.k1), 3)
+        self.assertGreater(self.r.ttl(self.k1), 0)
+
+        self.assertTrue(self.r.set(self.k2, 'foobar'))
+        self.assertEqual(self.r.bitcount(self.k2), 26)
+        self.assertEqual(self.r.bitcount(self.k2, 0, 0), 4)
+        self.assertEqual(self.r.bitcount(self.k2, 1, 1), 6)
+        self.assertEqual(self.r.bitcount(self.k2, -2, -1), 7)
+        self.assertEqual(self.r.execute_command('bitcount', self.k2, 5, 30, 'BIT'), 17)
+        self.assertEqual(self.r.bitcount('__string_missing__'), 0)
+
+        self.assertTrue(self.r.set(self.k2, b'\x00\x0f'))
+        self.assertEqual(self.r.bitpos(self.k2, 1), 12)
+        self.assertEqual(self.r.bitpos(self.k2, 0), 0)
+        self.assertEqual(self.r.bitpos(self.k2, 1, 0, 0), -1)
+        self.assertEqual(self.r.execute_command('bitpos', self.k2, 0, 12, -1, 'BIT'), -1)
+        # without an end the bits past the string are clear
+        self.assertTrue(self.r.set(self.k2, b'\x7f'))
+        self.assertEqual(self.r.bitpos(self.k2, 0, 0), 0)
+        self.assertTrue(self.r.set(self.k2, b'\xff'))
+        self.assertEqual(self.r.bitpos(self.k2, 0), 8)
+        self.assertEqual(self.r.bitpos(self.k2, 0, 0, -1), -1)
+        self.assertEqual(self.r.bitpos('__string_missing__', 1), -1)
+
+        with self.assertRaisesRegex(Exception, 'bit is not an integer or out of range'):
+            self.r.setbit(self.k1, 0, 2)
+        with self.assertRaisesRegex(Exception, 'bit offset is not an integer or out of range'):
+            self.r.getbit(self.k1, -1)
+
+    def test_bitfield(self):
+        self.assertEqual(self.r.execute_command('bitfield', self.k1, 'SET', 'u8', 0, 97), [0])
+        self.assertEqual(self.r.get(self.k1), 'a')
+        self.assertEqual(self.r.execute_command('bitfield', self.k1, 'GET', 'u4', 0, 'GET', 'i8', 0,
+                                                'GET', 'u8', '#1'), [6, 97, 0])
+        self.assertEqual(self.r.execute_command('bitfield', self.k1, 'INCRBY', 'u8', '#1', 98,
+                                                'GET', 'u16', 0), [98, 24930])
+        self.assertEqual(self.r.get(self.k1), 'ab')
+        # WRAP is the default overflow
+        self.assertEqual(self.r.execute_command('bitfield', self.k2, 'INCRBY', 'u2', 0, 5), [1])
+        self.assertEqual(self.r.execute_command('bitfield', self.k2, 'INCRBY', 'i4', 4, -9), [7])
+        self.assertEqual(self.r.execute_command('bitfield', self.k2, 'OVERFLOW', 'SAT',
+                                                'INCRBY', 'i4', 4, 100,
+                                                'INCRBY', 'u2', 0, -5), [7, 0])
+        self.assertEqual(self.r.execute_command('bitfield', self.k2, 'OVERFLOW', 'FAIL',
+                                                'INCRBY', 'u2', 0, 4, 'SET', 'i4', 4, -8), [None, 7])
+        self.assertEqual(self.r.execute_command('bitfield', self.k2, 'GET', 'i4', 4), [-8])
+        self.assertEqual(self.r.execute_command('bitfield', self.k2, 'GET', 'u8', 0, 'GET', 'u8', 64),
+                         [8, 0])
+        self.assertEqual(self.r.execute_command('bitfield', '__string_missing__', 'GET', 'u8', 0), [0])
+        self.assertEqual(self.r.exists('__string_missing__'), 0)
+
+        with self.assertRaisesRegex(Exception, 'Invalid bitfield type'):
+            self.r.execute_command('bitfield', self.k1, 'GET', 'u64', 0)
+        with self.assertRaisesRegex(Exception, 'bit offset is not an integer or out of range'):
+            self.r.execute_command('bitfield', self.k1, 'GET', 'u8', -1)
+        with self.assertRaisesRegex(Exception, 'Invalid OVERFLOW type specified'):
+            self.r.execute_command('bitfield', self.k1, 'OVERFLOW', 'NONE')
+
+    def test_bitop(self):
+        dest = '__string_dest__'
+        self.assertTrue(self.r.set(self.k1, 'ab'))
+        self.assertTrue(self.r.set(self.k2, 'c'))
+        self.assertEqual(self.r.bitop('AND', dest, self.k1, self.k2), 2)
+        self.assertEqual(self.r.get(dest), 'a\x00')
+        self.assertEqual(self.r.bitop('OR', dest, self.k1, self.k2), 2)
+        self.assertEqual(self.r.get(dest), 'cb')
+        self.assertEqual(self.r.bitop('XOR', dest, self.k1, self.k1), 2)
+        self.assertEqual(self.r.get(dest), '\x00\x00')
+        self.assertEqual(self.r.bitop('NOT', dest, self.k2), 1)
+        self.assertEqual(self.r.bitcount(dest), 4)
+        # a missing source is an empty string
+        self.assertEqual(self.r.bitop('OR', dest, self.k2, '__string_missing__'), 1)
+        self.assertEqual(self.r.get(dest), 'c')
+        # an empty result deletes the destination
+        self.assertEqual(self.r.bitop('AND', dest, '__string_missing__'), 0)
+        self.assertEqual(self.r.exists(dest), 0)
+
+        with self.assertRaisesRegex(Exception, 'BITOP NOT must be called with a single source key'):
+            self.r.bitop('NOT', dest, self.k1, self.k2)
+        self.r.lpush(dest, 'a')
+        with self.assertRaisesRegex(Exception, 'WRONGTYPE'):
+            self.r.bitop('AND', self.k1, dest)
+        # the destination is replaced whatever its type
+        self.assertEqual(self.r.bitop('AND', dest, self.k2), 1)
+        self.assertEqual(self.r.get(dest), 'c')
+        self.r.delete(dest)
+
+    def test_throttle(self):
+        for remaining in [2, 1, 0]:
+            self.assertListEqual(self.r.execute_command('cl.throttle', self.k1, 2, 1, 60), [0, 3, remaining, -1, 60 * (3 - remaining)])
+        limited = self.r.execute_command('cl.throttle', self.k1, 2, 1, 60)
+        self.assertListEqual(limited[:3], [1, 3, 0])
+        self.assertTrue(0 < limited[3] <= 60)
+        # a quantity over the burst never fits
+        self.assertEqual(self.r.execute_command('cl.throttle', self.k2, 2, 1, 60, 4)[:4], [1, 3, 3, -1])
+        self.assertGreater(self.r.ttl(self.k1), 0)
+        self.r.execute_command('del', self.k1)
+        self.assertEqual(self.r.execute_command('cl.throttle', self.k1, 2, 1, 60, 0)[:3], [0, 3, 3])
+
     def test_del(self):
         self.assertTrue(self.r.set(self.k1, self.v1))
         v1 = self.r.get(self.k1)
@@ -15,6 +15,8 @@ class CmdType(Enum):
     LIST = "list"
     SET = "set"
     ZSET = "zset"
+    STREAM = "stream"
+    JSON = "ReJSON-RL"
     NULL = "none"
 
 
@@ -22,6 +24,21 @@ def trigger_async_del_size():
     return ASYNC_DEL_THRESHOLD
 
 
+def crc64_jones(data):
+    crc = 0
+    for b in data:
+        crc ^= b
+        for _ in range(8):
+            crc = (crc >> 1) ^ (0x95ac9329ac4bc9b5 if crc & 1 else 0)
+    return crc
+
+
+def dump_payload(body, version=9):
+    """The DUMP payload of the RDB encoded value `body`"""
+    data = body + version.to_bytes(2, 'little')
+    return data + crc64_jones(data).to_bytes(8, 'little')
+
+
 def current_sec_ts():
     return int(floor(time.time()))
 
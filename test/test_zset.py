@@ -1,6 +1,8 @@
 import time
 import unittest
 
+from redis import exceptions
+
 from rediswrap import RedisWrapper
 from test_util import sec_ts_after_five_secs, msec_ts_after_five_secs, NOT_EXISTS_LITERAL, CmdType, \
     trigger_async_del_size
@@ -100,6 +102,8 @@ class ZsetTest(unittest.TestCase):
             self.assertEqual(self.r.zadd(self.k1, {str(i): 100 - i}), 1)
         self.assertListEqual(self.r.zrevrangebyscore(self.k1, '+inf', '-inf'), [str(i) for i in range(100)])
         self.assertListEqual(self.r.zrevrangebyscore(self.k1, '-1', '0'), [])
+        self.assertListEqual(self.r.zrevrangebyscore(self.k1, 10, 8, withscores=True),
+                             [('90', 10), ('91', 9), ('92', 8)])
 
     def test_zremrangebyscore(self):
         for i in range(100):
@@ -127,6 +131,12 @@ class ZsetTest(unittest.TestCase):
         for i in range(100):
             self.assertEqual(self.r.zscore(self.k1, str(i)), i)
 
+    def test_zmscore(self):
+        self.assertListEqual(self.r.execute_command('zmscore', self.k1, '1', '2'), [None, None])
+        for i in range(100):
+            self.assertEqual(self.r.zadd(self.k1, {str(i): i}), 1)
+        self.assertListEqual(self.r.execute_command('zmscore', self.k1, '1', self.v1, '99'), ['1', None, '99'])
+
     def test_zrem(self):
         for i in range(100):
             self.assertEqual(self.r.zadd(self.k1, {str(i): i}), 1)
@@ -153,6 +163,26 @@ class ZsetTest(unittest.TestCase):
         self.assertEqual(self.r.zadd(self.k1, {self.v1: 1, self.v2: 2}), 2)
         self.assertListEqual(self.r.zpopmax(self.k1), [(self.v2, 2)])
 
+    def test_zmpop(self):
+        self.assertIsNone(self.r.execute_command('zmpop', 2, self.k1, self.k2, 'MIN'))
+        self.assertEqual(self.r.zadd(self.k2, {'a': 1, 'b': 2, 'c': 3}), 3)
+        self.assertEqual(self.r.execute_command('zmpop', 2, self.k1, self.k2, 'MIN'), [self.k2, [['a', '1']]])
+        self.assertEqual(self.r.execute_command('zmpop', 2, self.k1, self.k2, 'MAX', 'COUNT', 5),
+                         [self.k2, [['c', '3'], ['b', '2']]])
+        self.assertEqual(self.r.exists(self.k2), 0)
+        self.assertIsNone(self.r.execute_command('bzmpop', 0.1, 2, self.k1, self.k2, 'MIN'))
+        self.assertEqual(self.r.zadd(self.k1, {'x': 1}), 1)
+        self.assertEqual(self.r.execute_command('bzmpop', 1, 2, self.k1, self.k2, 'MAX'), [self.k1, [['x', '1']]])
+        with self.assertRaises(Exception):
+            self.r.execute_command('zmpop', 0, self.k1, 'MIN')
+        with self.assertRaises(Exception):
+            self.r.execute_command('zmpop', 1, self.k1, 'MIN', 'COUNT', 0)
+        with self.assertRaises(Exception):
+            self.r.execute_command('zmpop', 1, self.k1, 'LEFT')
+        with self.assertRaises(Exception) as cm:
+            self.r.execute_command('bzmpop', '1e300', 1, self.k1, 'MIN')
+        self.assertIn('timeout is out of range', str(cm.exception))
+
     def test_zincrby(self):
         self.assertEqual(self.r.zadd(self.k1, {self.v1: 1, self.v2: 2}), 2)
         self.assertListEqual(self.r.zrange(self.k1, 0, -1, False, True), [(self.v1, 1), (self.v2, 2)])
@@ -259,6 +289,83 @@ class ZsetTest(unittest.TestCase):
         time.sleep(6)
         self.assertEqual(self.r.zcard(self.k1), 0)
 
+    def test_zinter(self):
+        for i in range(200):
+            self.assertEqual(self.r.zadd(self.k1, {str(i): i}), 1)
+        for i in range(100, 300):
+            self.assertEqual(self.r.zadd(self.k2, {str(i): 1}), 1)
+        members = self.r.execute_command('zinter', 2, self.k1, self.k2)
+        self.assertListEqual(members, [str(i) for i in range(100, 200)])
+        res = self.r.execute_command('zinter', 2, self.k1, self.k2, 'weights', 2, 3, 'withscores')
+        self.assertListEqual(res[:2], ['100', '203'])
+        res = self.r.execute_command('zinter', 2, self.k1, self.k2, 'aggregate', 'min', 'withscores')
+        self.assertListEqual(res[:2], ['100', '1'])
+        self.assertEqual(self.r.execute_command('zintercard', 2, self.k1, self.k2), 100)
+        self.assertEqual(self.r.execute_command('zintercard', 2, self.k1, self.k2, 'limit', 10), 10)
+
+    def test_zunion_zdiff(self):
+        for i in range(10):
+            self.assertEqual(self.r.zadd(self.k1, {str(i): i}), 1)
+        for i in range(5, 15):
+            self.assertEqual(self.r.zadd(self.k2, {str(i): 1}), 1)
+        members = self.r.execute_command('zunion', 2, self.k1, self.k2)
+        self.assertEqual(len(members), 15)
+        res = self.r.execute_command('zunion', 2, self.k1, self.k2, 'weights', 2, 3, 'withscores')
+        self.assertListEqual(res[-2:], ['9', '21'])
+        res = self.r.execute_command('zunion', 2, self.k1, self.k2, 'aggregate', 'max', 'withscores')
+        self.assertListEqual(res[-2:], ['9', '9'])
+        self.assertListEqual(self.r.execute_command('zdiff', 2, self.k1, self.k2), [str(i) for i in range(5)])
+        self.assertListEqual(self.r.execute_command('zdiff', 2, self.k1, 'nosuchkey'),
+                             [str(i) for i in range(10)])
+        self.assertListEqual(self.r.execute_command('zdiff', 2, 'nosuchkey', self.k1), [])
+        self.assertRaises(exceptions.ResponseError, self.r.execute_command,
+                          'zdiff', 2, self.k1, self.k2, 'weights', 1, 1)
+
+    def test_zstore(self):
+        dst = self.k1 + '_dst'
+        for i in range(2000):
+            self.assertEqual(self.r.zadd(self.k1, {str(i): i}), 1)
+        for i in range(1000, 3000):
+            self.assertEqual(self.r.zadd(self.k2, {str(i): 1}), 1)
+        self.assertEqual(self.r.execute_command('zinterstore', dst, 2, self.k1, self.k2), 1000)
+        self.assertEqual(self.r.zscore(dst, '1000'), 1001)
+        self.assertEqual(self.r.execute_command('zunionstore', dst, 2, self.k1, self.k2), 3000)
+        self.assertEqual(self.r.zcard(dst), 3000)
+        self.assertEqual(self.r.execute_command('zdiffstore', dst, 2, self.k1, self.k2), 1000)
+        self.assertEqual(self.r.zcard(dst), 1000)
+        # the destination may be one of the keys
+        self.assertEqual(self.r.execute_command('zdiffstore', dst, 2, dst, self.k2), 1000)
+        self.assertEqual(self.r.execute_command('zunionstore', dst, 2, dst, self.k2, 'weights', 2, 1), 3000)
+        self.assertEqual(self.r.zscore(dst, '999'), 1998)
+        # an empty result deletes the destination
+        self.assertEqual(self.r.execute_command('zinterstore', dst, 2, self.k1, 'nosuchkey'), 0)
+        self.assertEqual(self.r.exists(dst), 0)
+        self.assertRaises(exceptions.ResponseError, self.r.execute_command,
+                          'zunionstore', dst, 2, self.k1, self.k2, 'withscores')
+        self.r.execute_command('del', dst)
+
+    def test_zrangestore(self):
+        for i in range(10):
+            self.assertEqual(self.r.zadd(self.k1, {chr(ord('a') + i): i}), 1)
+        self.assertEqual(self.r.execute_command('zrangestore', self.k2, self.k1, 2, 4), 3)
+        self.assertListEqual(self.r.zrange(self.k2, 0, -1), ['c', 'd', 'e'])
+        self.assertEqual(self.r.execute_command('zrangestore', self.k2, self.k1, 0, 1, 'rev'), 2)
+        self.assertListEqual(self.r.zrange(self.k2, 0, -1, withscores=True), [('i', 8), ('j', 9)])
+        self.assertEqual(self.r.execute_command('zrangestore', self.k2, self.k1, '(2', 5, 'byscore'), 3)
+        self.assertListEqual(self.r.zrange(self.k2, 0, -1), ['d', 'e', 'f'])
+        self.assertEqual(self.r.execute_command('zrangestore', self.k2, self.k1, '+inf', '-inf',
+                                                'byscore', 'rev', 'limit', 1, 2), 2)
+        self.assertListEqual(self.r.zrange(self.k2, 0, -1), ['h', 'i'])
+        self.assertEqual(self.r.execute_command('zrangestore', self.k2, self.k1, '[b', '(d', 'bylex'), 2)
+        self.assertListEqual(self.r.zrange(self.k2, 0, -1), ['b', 'c'])
+        # an empty range deletes the destination
+        self.assertEqual(self.r.execute_command('zrangestore', self.k2, self.k1, 20, 30, 'byscore'), 0)
+        self.assertEqual(self.r.exists(self.k2), 0)
+        self.assertRaises(exceptions.ResponseError, self.r.execute_command,
+                          'zrangestore', self.k2, self.k1, 'a', 'b', 'byscore')
+        self.assertRaises(exceptions.ResponseError, self.r.execute_command,
+                          'zrangestore', self.k2, self.k1, 0, 1, 'limit', 0, 1)
+
     def tearDown(self):
         pass
 